@@ -1,18 +1,53 @@
 use std::collections::hash_map::Values;
-use std::{collections::HashMap, sync::Arc};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fmt,
+    sync::Arc,
+};
 
-use log::info;
+use log::{info, warn};
+use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use uuid::Uuid;
 
-use crate::exchange::api::ExchangeInfo;
-use crate::strategy::strategy::StrategyId;
+use crate::exchange::api::{
+    ExchangeInfo, ExchangePosition, SymbolInfo, SymbolStatus, DEFAULT_MAX_LEVERAGE,
+    DEFAULT_MIN_LEVERAGE,
+};
+use crate::exchange::halt::TradingHalts;
+use crate::exchange::types::{ApiError, ApiResult};
+use crate::strategy::strategy::{StrategyId, TradeAggregates};
+use crate::utils::number::{from_decimal, to_decimal};
+use crate::utils::time::{Clock, SystemClock};
 use crate::{
     account::trade::{OrderSide, Position},
     exchange::api::ExchangeApi,
     strategy::signal::SignalMessage,
 };
 
-use super::trade::{PositionId, TradeTx};
+use super::events::{TradeCallback, TradeEvent};
+use super::quote::{self, EquityBreakdown, DEFAULT_BASE_CURRENCY};
+use super::risk::{self, DailyLossLimit, DailyPnl, KillSwitch};
+use super::trade::{ContractType, Fill, PositionId, TradeTx};
+use super::user_data::UserDataEvent;
+
+/// Identifier of an account managed by the bot.
+pub type AccountId = String;
+
+/// Identifier of the account used by strategies which don't specify an account.
+pub const DEFAULT_ACCOUNT_ID: &str = "default";
+
+/// Maintenance margin rate used to estimate liquidation prices, the rate of the lowest notional
+/// tier on Binance futures.
+pub const MAINTENANCE_MARGIN_RATE: f64 = 0.004;
+
+/// Taker fee rate used to estimate order fees.
+pub const TAKER_FEE_RATE: f64 = 0.0005;
+
+/// Relative difference allowed between the exchange and tracked quantity of a position before it
+/// is reported as a mismatch, covers rounding of the quantity to the symbol's step size.
+pub const RECONCILE_QTY_TOLERANCE: f64 = 0.001;
 
 /// Represents a trading account with positions, trades, and an exchange API.
 pub struct Account {
@@ -25,6 +60,32 @@ pub struct Account {
     /// A flag indicating whether the account is in dry run mode.
     dry_run: bool,
     position_signals: HashMap<PositionId, Vec<SignalMessage>>,
+    /// The balance the account started with, used as the base of the account equity.
+    initial_balance: f64,
+    /// Wallet balance last reported by the exchange user data stream.
+    exchange_balance: Option<f64>,
+    /// Trading rules of symbols traded on the account, fetched from the exchange once.
+    symbol_info: HashMap<String, SymbolInfo>,
+    /// Currency the equity is reported in, profit in other quote assets is converted to it.
+    base_currency: String,
+    /// Last prices of the cross pairs profit in other quote assets is converted at.
+    conversion_prices: HashMap<String, f64>,
+    /// Maximum loss realized within a UTC day before trading is halted.
+    daily_loss_limit: Option<DailyLossLimit>,
+    /// Profit realized since the start of the current UTC day.
+    daily_pnl: DailyPnl,
+    /// Switch refusing new positions, engaged when the daily loss limit is breached.
+    kill_switch: KillSwitch,
+    /// Minimum order notional in USD, raises the minimum of the exchange if higher.
+    min_notional: f64,
+    /// Symbols halted by the exchange, no positions are opened on them until they resume.
+    trading_halts: TradingHalts,
+    /// Running aggregates of the trades of each strategy, updated as trades are recorded.
+    trade_aggregates: HashMap<StrategyId, TradeAggregates>,
+    /// Callbacks fired after every position opened or closed.
+    trade_callbacks: Vec<TradeCallback>,
+    /// Clock the trading day is read from.
+    clock: Arc<dyn Clock>,
 }
 
 impl Account {
@@ -51,6 +112,19 @@ impl Account {
             trades: vec![],
             dry_run,
             position_signals: HashMap::new(),
+            initial_balance: 0.0,
+            exchange_balance: None,
+            symbol_info: HashMap::new(),
+            base_currency: DEFAULT_BASE_CURRENCY.to_string(),
+            conversion_prices: HashMap::new(),
+            daily_loss_limit: None,
+            daily_pnl: DailyPnl::default(),
+            kill_switch: KillSwitch::default(),
+            min_notional: 0.0,
+            trading_halts: TradingHalts::default(),
+            trade_aggregates: HashMap::new(),
+            trade_callbacks: vec![],
+            clock: Arc::new(SystemClock),
         };
 
         if init_workers {
@@ -59,7 +133,9 @@ impl Account {
         _self
     }
 
-    /// Opens a position on the exchange.
+    /// Opens a position on the exchange, refused while the kill switch is engaged, while the symbol
+    /// is halted by the exchange, if the stop loss is on the wrong side of the open price or if the
+    /// order notional is below the minimum.
     ///
     /// # Parameters
     ///
@@ -85,26 +161,289 @@ impl Account {
         strategy_id: Option<StrategyId>,
         stop_loss: Option<f64>,
     ) -> Option<&mut Position> {
-        if let Ok(mut position) = self
+        match self
+            .try_open_position(
+                symbol,
+                margin_usd,
+                leverage,
+                order_side,
+                open_price,
+                strategy_id,
+                stop_loss,
+                None,
+            )
+            .await
+        {
+            Ok(position) => Some(position),
+            Err(e) => {
+                warn!("Unable to open position on {symbol}, {e}");
+                None
+            }
+        }
+    }
+
+    /// Opens a position on the exchange like `open_position`, returning why the position wasn't
+    /// opened.
+    ///
+    /// # Parameters
+    ///
+    /// * `symbol` - The symbol of the asset.
+    /// * `margin_usd` - The margin allocated for the position in USD.
+    /// * `leverage` - The leverage used for the position.
+    /// * `order_side` - The side of the order (Buy or Sell).
+    /// * `open_price` - The price at which the position is opened.
+    /// * `strategy_id` - Optional strategy ID associated with the position.
+    /// * `stop_loss` - Optional stop-loss price for the position.
+    /// * `client_order_id` - Optional client order ID, an order retried with the same ID is only
+    ///   placed once by the exchange.
+    ///
+    /// # Returns
+    ///
+    /// A mutable reference to the opened position, or an `OrderError` if the order was refused by
+    /// the account or failed on the exchange. A position filled beyond its stop loss is closed at
+    /// the fill price and rejected.
+
+    pub async fn try_open_position(
+        &mut self,
+        symbol: &str,
+        margin_usd: f64,
+        leverage: u32,
+        order_side: OrderSide,
+        open_price: f64,
+        strategy_id: Option<StrategyId>,
+        stop_loss: Option<f64>,
+        client_order_id: Option<&str>,
+    ) -> Result<&mut Position, OrderError> {
+        if !self.is_trading_enabled() {
+            return Err(OrderError::Rejected(
+                "trading is halted by the kill switch".to_string(),
+            ));
+        }
+
+        self.check_trading_status(symbol)
+            .map_err(OrderError::Rejected)?;
+
+        if let Some(stop_loss) = stop_loss {
+            Position::validate_stop_loss(order_side, open_price, stop_loss)
+                .map_err(OrderError::Rejected)?;
+        }
+
+        let symbol_info = self.get_symbol_info(symbol).await;
+        symbol_info
+            .validate_leverage(leverage)
+            .map_err(OrderError::Rejected)?;
+        symbol_info
+            .contract_type
+            .validate()
+            .map_err(OrderError::Rejected)?;
+
+        // rejected locally rather than by the exchange with an opaque error
+        self.validate_notional(symbol, margin_usd * leverage as f64)
+            .await
+            .map_err(OrderError::Rejected)?;
+
+        // the order is sized by the quantity the position is recorded with, in contracts for
+        // inverse contracts
+        let mut position =
+            Position::new(symbol, open_price, order_side, margin_usd, leverage, None);
+        position.set_contract_type(symbol_info.contract_type);
+        position.quantity = symbol_info.round_qty(position.quantity);
+
+        if position.quantity <= 0.0 {
+            return Err(OrderError::Rejected(format!(
+                "Quantity is below the minimum step size of {symbol}"
+            )));
+        }
+
+        let now = self.clock.now();
+        let mut position = self
             .exchange_api
             .clone()
-            .open_position(symbol, margin_usd, leverage, order_side, open_price)
+            .open_position(position, client_order_id)
             .await
-        {
-            position.set_stop_loss(stop_loss);
-            position.set_strategy_id(strategy_id);
-            let position_id = position.id;
-            // insert new position into account positions
-            self.positions.insert(position.id, position);
+            .map_err(|e| {
+                // orders of a symbol under maintenance are paused until it's due a recheck
+                self.trading_halts.record_error(symbol, &e, now);
+                OrderError::Exchange(e)
+            })?;
+        // the position may fill beyond the stop loss, it's closed right away rather than held
+        // without one
+        let crossed_stop = position.set_stop_loss(stop_loss).err();
+        position.set_strategy_id(strategy_id);
+        position.open_time = self.clock.now();
+        let position_id = position.id;
+        let fill_price = position.open_price;
+        self.emit_trade_event(TradeEvent::Opened {
+            position: position.clone(),
+            strategy_id,
+        });
+        // insert new position into account positions
+        self.positions.insert(position_id, position);
+
+        if let Some(e) = crossed_stop {
+            warn!("Closing position on {symbol} filled beyond its stop loss, {e}");
+            let reason = format!("Filled at {fill_price} beyond the stop loss");
+            self.close_position_with_reason(position_id, fill_price, Some(reason))
+                .await?;
+
+            return Err(OrderError::Rejected(format!(
+                "filled at {fill_price} beyond the stop loss, the position was closed"
+            )));
+        }
+
+        self.positions
+            .get_mut(&position_id)
+            .ok_or_else(|| OrderError::Rejected(format!("Position {position_id} not recorded")))
+    }
+
+    /// Checks a leverage is allowed for a symbol before an order is placed.
+    ///
+    /// The allowed range comes from the symbol info of the exchange, if the exchange doesn't
+    /// provide symbol info the default range of `1..=125` is used.
+    ///
+    /// # Parameters
+    ///
+    /// * `symbol` - The symbol of the asset.
+    /// * `leverage` - The leverage to check.
+    ///
+    /// # Returns
+    ///
+    /// `Ok(())` if the leverage is allowed, otherwise an error message describing the allowed range.
+
+    pub async fn validate_leverage(&mut self, symbol: &str, leverage: u32) -> Result<(), String> {
+        self.get_symbol_info(symbol)
+            .await
+            .validate_leverage(leverage)
+    }
+
+    /// Checks the notional of an order is at least the minimum of the symbol, or the minimum set
+    /// on the account if it's higher.
+    ///
+    /// # Parameters
+    ///
+    /// * `symbol` - The symbol of the asset.
+    /// * `notional` - The notional value of the order, its margin multiplied by its leverage.
+    ///
+    /// # Returns
+    ///
+    /// `Ok(())` if the notional is allowed, otherwise an error message naming the minimum.
+
+    pub async fn validate_notional(&mut self, symbol: &str, notional: f64) -> Result<(), String> {
+        let min_notional = self
+            .get_symbol_info(symbol)
+            .await
+            .min_notional
+            .max(self.min_notional);
+
+        if notional < min_notional {
+            return Err(format!(
+                "Order notional {notional} below minimum {min_notional} for {symbol}"
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Simulates opening a position without placing an order.
+    ///
+    /// The quantity is sized the same way as an opened position and rounded to the symbol's step
+    /// size. The liquidation price is estimated for an isolated position using
+    /// `MAINTENANCE_MARGIN_RATE` and the fee using `TAKER_FEE_RATE`.
+    ///
+    /// # Parameters
+    ///
+    /// * `symbol` - The symbol of the asset.
+    /// * `order_side` - The side of the order (Buy or Sell).
+    /// * `margin_usd` - The margin allocated for the position in USD.
+    /// * `leverage` - The leverage used for the position.
+    /// * `entry_price` - The price the position would be opened at.
+    ///
+    /// # Returns
+    ///
+    /// An `OrderSimulation` with the sizing, estimates and any risk limits the order breaks.
+
+    pub async fn simulate_order(
+        &mut self,
+        symbol: &str,
+        order_side: OrderSide,
+        margin_usd: f64,
+        leverage: u32,
+        entry_price: f64,
+    ) -> OrderSimulation {
+        let symbol_info = self.get_symbol_info(symbol).await;
+        let mut risk_violations = vec![];
+
+        if let Err(e) = symbol_info.validate_leverage(leverage) {
+            risk_violations.push(e);
+        }
+        if let Err(e) = symbol_info.contract_type.validate() {
+            risk_violations.push(e);
+        }
 
-            return self.positions.get_mut(&position_id);
+        let mut position = Position::new(
+            symbol,
+            entry_price,
+            order_side.clone(),
+            margin_usd,
+            leverage,
+            None,
+        );
+        position.set_contract_type(symbol_info.contract_type);
+        let quantity = symbol_info.round_qty(position.quantity);
+        let notional = match symbol_info.contract_type {
+            ContractType::Linear => quantity * entry_price,
+            ContractType::Inverse { contract_size } => quantity * contract_size,
         };
+        let estimated_fee = notional * TAKER_FEE_RATE;
 
-        None
+        if quantity <= 0.0 {
+            risk_violations.push(format!(
+                "Quantity is below the minimum step size of {symbol}"
+            ));
+        }
+
+        let available_margin = self.equity() - self.committed_margin();
+        if margin_usd + estimated_fee > available_margin {
+            risk_violations.push(format!(
+                "Margin and fee of {} exceed the available margin of {available_margin}",
+                margin_usd + estimated_fee
+            ));
+        }
+
+        // an isolated position is liquidated once its loss uses up the initial margin down to
+        // the maintenance margin
+        let liquidation_price = if leverage > 0 {
+            let loss_ratio = 1.0 / leverage as f64 - MAINTENANCE_MARGIN_RATE;
+            let price = match order_side {
+                OrderSide::Buy => entry_price * (1.0 - loss_ratio),
+                OrderSide::Sell => entry_price * (1.0 + loss_ratio),
+            };
+            Some(symbol_info.round_price(price.max(0.0)))
+        } else {
+            None
+        };
+
+        OrderSimulation {
+            symbol: symbol.to_string(),
+            order_side,
+            margin_usd,
+            leverage,
+            entry_price,
+            quantity,
+            notional,
+            liquidation_price,
+            estimated_fee,
+            passes_risk_limits: risk_violations.is_empty(),
+            risk_violations,
+        }
     }
 
     /// Closes a position on the exchange.
     ///
+    /// The profit of the trade counts towards the daily loss limit, if the limit is breached the
+    /// kill switch is engaged until the next UTC day and, if configured, all other positions are
+    /// closed at their last price.
+    ///
     /// # Parameters
     ///
     /// * `position_id` - The ID of the position to close.
@@ -119,25 +458,277 @@ impl Account {
         position_id: PositionId,
         close_price: f64,
     ) -> Option<&mut TradeTx> {
-        if let Some(position) = self.positions.get(&position_id).cloned() {
+        self.close_position_with_reason(position_id, close_price, None)
+            .await
+            .ok()
+    }
+
+    /// Closes a position on the exchange like `close_position`, returning why the position wasn't
+    /// closed.
+    ///
+    /// # Parameters
+    ///
+    /// * `position_id` - The ID of the position to close.
+    /// * `close_price` - The price at which the position is closed.
+    ///
+    /// # Returns
+    ///
+    /// A reference to the trade transaction, or an `OrderError` if the position isn't tracked or
+    /// the exchange failed to close it.
+
+    pub async fn try_close_position(
+        &mut self,
+        position_id: PositionId,
+        close_price: f64,
+    ) -> Result<&mut TradeTx, OrderError> {
+        self.close_position_with_reason(position_id, close_price, None)
+            .await
+    }
+
+    /// Closes a position on the exchange like `close_position`, recording why it was closed.
+    async fn close_position_with_reason(
+        &mut self,
+        position_id: PositionId,
+        close_price: f64,
+        reason: Option<String>,
+    ) -> Result<&mut TradeTx, OrderError> {
+        let trade_tx_id = self
+            .close_exchange_position(position_id, close_price, reason)
+            .await?;
+
+        if self.record_realized_pnl(trade_tx_id) {
+            self.close_positions_on_breach().await;
+        }
+
+        self.trades
+            .iter_mut()
+            .find(|e| e.id == trade_tx_id)
+            .ok_or_else(|| OrderError::Rejected(format!("Trade {trade_tx_id} not recorded")))
+    }
+
+    /// Closes the positions of a strategy which have been held longer than a maximum duration.
+    ///
+    /// # Parameters
+    ///
+    /// * `strategy_id` - The strategy whose positions are checked.
+    /// * `max_hold_secs` - Maximum number of seconds a position may be held.
+    /// * `now` - The current timestamp in milliseconds, ie. the close time of the latest kline.
+    /// * `close_price` - The price expired positions are closed at.
+    ///
+    /// # Returns
+    ///
+    /// The trades of the closed positions, with the close reason recorded.
+
+    pub async fn close_expired_positions(
+        &mut self,
+        strategy_id: StrategyId,
+        max_hold_secs: u64,
+        now: u64,
+        close_price: f64,
+    ) -> Vec<TradeTx> {
+        let max_hold_mili = max_hold_secs * 1000;
+
+        let expired: Vec<PositionId> = self
+            .strategy_positions(strategy_id)
+            .iter()
+            .filter(|pos| pos.age(now) > max_hold_mili)
+            .map(|pos| pos.id)
+            .collect();
+
+        let mut trades = vec![];
+
+        for position_id in expired {
+            let reason = format!("Max hold of {max_hold_secs}s exceeded");
             if let Ok(trade_tx) = self
-                .exchange_api
-                .close_position(position.clone(), close_price)
+                .close_position_with_reason(position_id, close_price, Some(reason))
+                .await
+            {
+                trades.push(trade_tx.clone());
+            }
+        }
+
+        trades
+    }
+
+    /// Closes the positions whose stop loss or take profit was reached by the last price of their
+    /// symbol.
+    ///
+    /// # Parameters
+    ///
+    /// * `last_prices` - Last prices by symbol, positions of symbols without a price are kept.
+    ///
+    /// # Returns
+    ///
+    /// The trades of the closed positions, with the close reason recorded.
+
+    pub async fn close_positions_at_exits(
+        &mut self,
+        last_prices: &HashMap<String, f64>,
+    ) -> Vec<TradeTx> {
+        let exits: Vec<(PositionId, f64, String)> = self
+            .positions
+            .values()
+            .filter_map(|pos| {
+                let price = *last_prices.get(&pos.symbol)?;
+                let reason = pos.exit_reason(price)?;
+                Some((pos.id, price, reason))
+            })
+            .collect();
+
+        let mut trades = vec![];
+
+        for (position_id, price, reason) in exits {
+            match self
+                .close_position_with_reason(position_id, price, Some(reason))
                 .await
             {
-                self.positions.remove(&position.id);
+                Ok(trade_tx) => trades.push(trade_tx.clone()),
+                Err(e) => warn!("Unable to close position {position_id} at its exit, {e}"),
+            }
+        }
+
+        trades
+    }
+
+    /// Seeds the account with the positions held on the exchange which it doesn't track, ie.
+    /// positions opened before the bot was restarted, so they are managed like any other position.
+    ///
+    /// # Returns
+    ///
+    /// The adopted positions, or an `ApiError` if the exchange positions can't be retrieved.
+
+    pub async fn adopt_exchange_positions(&mut self) -> ApiResult<Vec<Position>> {
+        let reconciliation = self
+            .reconcile(Some(OrphanAction::Adopt), &HashMap::new())
+            .await?;
+
+        Ok(reconciliation.adopted)
+    }
 
-                let trade_tx_id = trade_tx.id;
+    /// Compares the positions held on the exchange with the positions tracked by the account.
+    ///
+    /// Positions are compared by symbol and side, tracked positions of the same symbol and side
+    /// are added up as the exchange holds them as a single position. Orphans, positions held on
+    /// the exchange which the account doesn't track, are adopted or closed if an action is given.
+    ///
+    /// # Parameters
+    ///
+    /// * `orphan_action` - Optional action to take on orphan positions.
+    /// * `last_prices` - Last prices by symbol used to close orphans, orphans without a price are
+    ///   closed at their entry price.
+    ///
+    /// # Returns
+    ///
+    /// A `Reconciliation` listing the discrepancies found and the orphans adopted or closed, or an
+    /// `ApiError` if the exchange positions can't be retrieved.
 
-                self.trades.push(trade_tx);
+    pub async fn reconcile(
+        &mut self,
+        orphan_action: Option<OrphanAction>,
+        last_prices: &HashMap<String, f64>,
+    ) -> ApiResult<Reconciliation> {
+        let exchange_positions = self.exchange_api.get_positions().await?;
+
+        let mut tracked: BTreeMap<(String, OrderSide), (f64, Vec<PositionId>)> = BTreeMap::new();
+        for position in self.positions.values() {
+            let entry = tracked
+                .entry((position.symbol.clone(), position.order_side.clone()))
+                .or_default();
+            entry.0 += position.quantity;
+            entry.1.push(position.id);
+        }
 
-                if let Some(tx) = self.trades.iter_mut().find(|e| e.id == trade_tx_id) {
-                    return Some(tx);
+        let mut discrepancies = vec![];
+        let mut orphans = vec![];
+
+        for exchange_position in &exchange_positions {
+            let key = (
+                exchange_position.symbol.clone(),
+                exchange_position.order_side.clone(),
+            );
+
+            match tracked.remove(&key) {
+                Some((tracked_quantity, position_ids)) => {
+                    let difference = (tracked_quantity - exchange_position.quantity).abs();
+                    if difference > exchange_position.quantity * RECONCILE_QTY_TOLERANCE {
+                        discrepancies.push(PositionDiscrepancy {
+                            symbol: key.0,
+                            order_side: key.1,
+                            kind: DiscrepancyKind::QuantityMismatch,
+                            exchange_quantity: exchange_position.quantity,
+                            tracked_quantity,
+                            position_ids,
+                        });
+                    }
                 }
-            };
-        };
+                None => {
+                    discrepancies.push(PositionDiscrepancy {
+                        symbol: key.0,
+                        order_side: key.1,
+                        kind: DiscrepancyKind::Untracked,
+                        exchange_quantity: exchange_position.quantity,
+                        tracked_quantity: 0.0,
+                        position_ids: vec![],
+                    });
+                    orphans.push(exchange_position.to_position());
+                }
+            }
+        }
 
-        None
+        for ((symbol, order_side), (tracked_quantity, position_ids)) in tracked {
+            discrepancies.push(PositionDiscrepancy {
+                symbol,
+                order_side,
+                kind: DiscrepancyKind::MissingOnExchange,
+                exchange_quantity: 0.0,
+                tracked_quantity,
+                position_ids,
+            });
+        }
+
+        let mut adopted = vec![];
+        let mut closed = vec![];
+
+        match orphan_action {
+            Some(OrphanAction::Adopt) => {
+                for position in orphans {
+                    info!(
+                        "Adopting {} position on {} held on the exchange",
+                        position.order_side, position.symbol
+                    );
+                    self.positions.insert(position.id, position.clone());
+                    adopted.push(position);
+                }
+            }
+            Some(OrphanAction::Close) => {
+                for position in orphans {
+                    let close_price = last_prices
+                        .get(&position.symbol)
+                        .copied()
+                        .unwrap_or(position.open_price);
+
+                    match self
+                        .exchange_api
+                        .close_position(position.clone(), close_price)
+                        .await
+                    {
+                        Ok(trade_tx) => closed.push(trade_tx),
+                        Err(e) => warn!(
+                            "Unable to close orphan position on {}, {e}",
+                            position.symbol
+                        ),
+                    }
+                }
+            }
+            None => {}
+        }
+
+        Ok(Reconciliation {
+            exchange_positions,
+            discrepancies,
+            adopted,
+            closed,
+        })
     }
 
     pub fn add_position_meta(&mut self, position_id: PositionId, signal: &SignalMessage) {
@@ -145,7 +736,7 @@ impl Account {
         signals.push(signal.clone())
     }
 
-    pub fn get_position_meta(&mut self, position_id: PositionId) -> Option<Vec<SignalMessage>> {
+    pub fn get_position_meta(&self, position_id: PositionId) -> Option<Vec<SignalMessage>> {
         self.position_signals.get(&position_id).cloned()
     }
 
@@ -224,6 +815,27 @@ impl Account {
         positions
     }
 
+    /// Detaches the open positions of a strategy so they are managed manually.
+    ///
+    /// # Parameters
+    ///
+    /// * `strategy_id` - The ID of the strategy.
+    ///
+    /// # Returns
+    ///
+    /// The number of positions detached.
+
+    pub fn detach_strategy_positions(&mut self, strategy_id: StrategyId) -> usize {
+        let mut detached = 0;
+        for pos in self.positions.values_mut() {
+            if pos.strategy_id == Some(strategy_id) {
+                pos.set_strategy_id(None);
+                detached += 1;
+            }
+        }
+        detached
+    }
+
     /// Returns trade transactions associated with a specific strategy ID.
     ///
     /// # Parameters
@@ -234,75 +846,726 @@ impl Account {
     ///
     /// A vector containing references to trade transactions associated with the strategy.
 
-    pub fn strategy_trades(&self, _strategy_id: StrategyId) -> Vec<&TradeTx> {
+    pub fn strategy_trades(&self, strategy_id: StrategyId) -> Vec<&TradeTx> {
         let mut trades = vec![];
         for trade in &self.trades {
-            if let Some(strategy_id) = trade.position.strategy_id {
-                if strategy_id == strategy_id {
-                    trades.push(trade)
-                }
+            if trade.position.strategy_id == Some(strategy_id) {
+                trades.push(trade)
             }
         }
         trades
     }
 
-    /// Checks if the account is in dry run mode.
-    ///
-    /// # Returns
-    ///
-    /// A boolean indicating whether the account is in dry run mode.
+    /// Returns the running aggregates of the trades of a strategy, empty if the strategy has no
+    /// trades.
 
-    pub fn is_dry_run(&self) -> bool {
-        self.dry_run
+    pub fn strategy_trade_aggregates(&self, strategy_id: StrategyId) -> TradeAggregates {
+        self.trade_aggregates
+            .get(&strategy_id)
+            .cloned()
+            .unwrap_or_default()
     }
 
-    /// Sets the exchange API and dry run mode.
+    /// Cancels resting orders on the account's exchange.
     ///
     /// # Parameters
     ///
-    /// * `api` - A thread-safe reference to the exchange API.
-    /// * `dry_run` - A flag indicating whether to operate in dry run mode.
+    /// * `symbol` - The symbol to cancel orders on.
+    /// * `order_id` - The order to cancel, if `None` all open orders for the symbol are canceled.
+    ///
+    /// # Returns
+    ///
+    /// The exchange response, or the `ApiError` returned by the exchange API.
 
-    pub fn set_exchange_api(&mut self, api: Arc<dyn ExchangeApi>, dry_run: bool) {
-        self.dry_run = dry_run;
-        self.exchange_api = api;
+    pub async fn cancel_orders(&self, symbol: &str, order_id: Option<&str>) -> ApiResult<Value> {
+        match order_id {
+            Some(order_id) => self.exchange_api.cancel_order(symbol, order_id).await,
+            None => self.exchange_api.cancel_all_orders(symbol).await,
+        }
     }
 
-    /// Retrieves account information.
+    /// Returns the current equity of the account.
+    ///
+    /// Equity is the initial balance plus the realized profit of all closed trades, converted to
+    /// the base currency at the last conversion prices. Profit in quote assets without a
+    /// conversion price is left out, see `equity_breakdown`.
     ///
     /// # Returns
     ///
-    /// Account information including positions, trades, and exchange API details.
+    /// The account equity in the base currency.
 
-    pub async fn info(&self) -> AccountInfo {
-        let info = self.exchange_api.info().await.ok();
-        AccountInfo {
-            dry_run: self.dry_run,
-            exchange_api: info,
-            positions: self.positions.values().map(|el| el.clone()).collect(),
-            trade_transactions: self.trades.clone(),
-        }
+    pub fn equity(&self) -> f64 {
+        self.equity_breakdown(&self.conversion_prices).equity
     }
 
-    /// Retrieves a position by its ID.
+    /// Returns the equity of the account converted to its base currency.
+    ///
+    /// The initial balance is held in the base currency, the realized profit of each trade in the
+    /// quote asset of its symbol. Profit in quote assets without a conversion rate is reported
+    /// in the subtotals only.
     ///
     /// # Parameters
     ///
-    /// * `position_id` - The ID of the position to retrieve.
+    /// * `last_prices` - Last prices by symbol, used as cross rates between quote assets.
     ///
     /// # Returns
     ///
-    /// A reference to the position if found, otherwise `None`.
+    /// The `EquityBreakdown` of the account.
 
-    pub fn get_position(&self, position_id: &PositionId) -> Option<&Position> {
-        self.positions.get(position_id)
-    }
+    pub fn equity_breakdown(&self, last_prices: &HashMap<String, f64>) -> EquityBreakdown {
+        let mut amounts: BTreeMap<String, Decimal> = BTreeMap::new();
+        amounts.insert(self.base_currency.clone(), to_decimal(self.initial_balance));
 
-    // ---
-    // Private Methods
-    // ---
+        for trade in &self.trades {
+            // symbols without a recognised asset are assumed to settle in the base currency
+            let asset = quote::settlement_asset(&trade.position)
+                .unwrap_or_else(|| self.base_currency.clone());
+            *amounts.entry(asset).or_default() += trade.profit;
+        }
 
-    /// Initializes worker threads for the account.
+        let subtotals = amounts
+            .into_iter()
+            .map(|(quote, amount)| (quote, from_decimal(amount)))
+            .collect();
+
+        EquityBreakdown::new(&self.base_currency, subtotals, last_prices)
+    }
+
+    /// Lists the cross pair symbols needed to convert the profit of the account to its base
+    /// currency, the direct and inverse pair of every other quote asset traded.
+
+    pub fn conversion_symbols(&self) -> Vec<String> {
+        let quotes: BTreeSet<String> = self
+            .trades
+            .iter()
+            .filter_map(|trade| quote::settlement_asset(&trade.position))
+            .filter(|quote| *quote != self.base_currency)
+            .collect();
+
+        quotes
+            .iter()
+            .flat_map(|quote| quote::conversion_symbols(quote, &self.base_currency))
+            .collect()
+    }
+
+    /// Updates the prices of the cross pairs profit in other quote assets is converted at, pairs
+    /// without a price keep their previous price.
+    ///
+    /// # Parameters
+    ///
+    /// * `last_prices` - Last prices by symbol, prices of other symbols are ignored.
+
+    pub fn set_conversion_prices(&mut self, last_prices: &HashMap<String, f64>) {
+        for symbol in self.conversion_symbols() {
+            if let Some(price) = last_prices.get(&symbol) {
+                self.conversion_prices.insert(symbol, *price);
+            }
+        }
+    }
+
+    /// Sets the currency the equity of the account is reported in.
+    ///
+    /// # Parameters
+    ///
+    /// * `base_currency` - The base currency, ie. `USDT`.
+
+    pub fn set_base_currency(&mut self, base_currency: &str) {
+        self.base_currency = base_currency.to_uppercase();
+    }
+
+    /// Returns the currency the equity of the account is reported in.
+
+    pub fn base_currency(&self) -> &str {
+        &self.base_currency
+    }
+
+    /// Returns the total margin held by open positions.
+    ///
+    /// # Returns
+    ///
+    /// The committed margin in USD.
+
+    pub fn committed_margin(&self) -> f64 {
+        self.positions.values().map(|pos| pos.margin_usd).sum()
+    }
+
+    /// Returns the margin held by open positions of a strategy.
+    ///
+    /// # Parameters
+    ///
+    /// * `strategy_id` - The strategy ID to sum margin for.
+    ///
+    /// # Returns
+    ///
+    /// The committed margin of the strategy in USD.
+
+    pub fn strategy_committed_margin(&self, strategy_id: StrategyId) -> f64 {
+        self.strategy_positions(strategy_id)
+            .iter()
+            .map(|pos| pos.margin_usd)
+            .sum()
+    }
+
+    /// Sets the balance the account equity is calculated from.
+    ///
+    /// # Parameters
+    ///
+    /// * `initial_balance` - The starting balance in USD.
+
+    pub fn set_initial_balance(&mut self, initial_balance: f64) {
+        self.initial_balance = initial_balance;
+    }
+
+    /// Checks if the account is in dry run mode.
+    ///
+    /// # Returns
+    ///
+    /// A boolean indicating whether the account is in dry run mode.
+
+    pub fn is_dry_run(&self) -> bool {
+        self.dry_run
+    }
+
+    /// Returns the exchange API used to open and close positions.
+
+    pub fn exchange_api(&self) -> Arc<dyn ExchangeApi> {
+        self.exchange_api.clone()
+    }
+
+    /// Sets the exchange API and dry run mode.
+    ///
+    /// # Parameters
+    ///
+    /// * `api` - A thread-safe reference to the exchange API.
+    /// * `dry_run` - A flag indicating whether to operate in dry run mode.
+
+    pub fn set_exchange_api(&mut self, api: Arc<dyn ExchangeApi>, dry_run: bool) {
+        self.dry_run = dry_run;
+        self.exchange_api = api;
+    }
+
+    /// Sets the maximum loss realized within a UTC day before trading is halted.
+    ///
+    /// # Parameters
+    ///
+    /// * `daily_loss_limit` - The limit, `None` to trade without a limit.
+
+    pub fn set_daily_loss_limit(&mut self, daily_loss_limit: Option<DailyLossLimit>) {
+        self.daily_loss_limit = daily_loss_limit;
+    }
+
+    /// Sets the minimum notional of orders, used where it's higher than the minimum of the
+    /// exchange.
+    ///
+    /// # Parameters
+    ///
+    /// * `min_notional` - The minimum order notional in USD.
+
+    pub fn set_min_notional(&mut self, min_notional: f64) {
+        self.min_notional = min_notional;
+    }
+
+    /// Returns the minimum notional of orders set on the account.
+
+    pub fn min_notional(&self) -> f64 {
+        self.min_notional
+    }
+
+    /// Returns the maximum loss realized within a UTC day before trading is halted.
+
+    pub fn daily_loss_limit(&self) -> Option<DailyLossLimit> {
+        self.daily_loss_limit
+    }
+
+    /// Checks the exchange hasn't halted trading on a symbol, ie. for maintenance. The last known
+    /// status is read, it's refreshed by the bot every `SYMBOL_STATUS_RECHECK_SECS` so halted
+    /// symbols resume on their own.
+
+    pub fn check_trading_status(&self, symbol: &str) -> Result<(), String> {
+        self.trading_halts.check(symbol, self.clock.now())
+    }
+
+    /// Returns the symbols halted by the exchange, shared with clones of the halts.
+
+    pub fn trading_halts(&self) -> TradingHalts {
+        self.trading_halts.clone()
+    }
+
+    /// Registers a callback fired after every position opened or closed on the account, ie. to
+    /// report trades to an external risk system.
+
+    pub fn on_trade(&mut self, callback: TradeCallback) {
+        self.trade_callbacks.push(callback);
+    }
+
+    /// Sets the clock the trading day is read from.
+
+    pub fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.clock = clock;
+    }
+
+    /// Checks if new positions can be opened, `false` while the kill switch is engaged.
+
+    pub fn is_trading_enabled(&self) -> bool {
+        !self.kill_switch.is_engaged(self.clock.now())
+    }
+
+    /// Returns the profit realized since the start of the current UTC day.
+
+    pub fn daily_realized_pnl(&self) -> f64 {
+        self.daily_pnl.realized_pnl(self.clock.now())
+    }
+
+    /// Returns the loss which can still be realized today, `None` if no daily loss limit is set.
+
+    pub fn daily_loss_budget(&self) -> Option<f64> {
+        self.daily_loss_limit
+            .as_ref()
+            .map(|limit| self.daily_pnl.remaining_budget(limit, self.clock.now()))
+    }
+
+    /// Retrieves account information.
+    ///
+    /// # Parameters
+    ///
+    /// * `last_prices` - Last prices by symbol, used to convert the equity to the base currency.
+    ///
+    /// # Returns
+    ///
+    /// Account information including positions, trades, and exchange API details.
+
+    pub async fn info(&self, last_prices: &HashMap<String, f64>) -> AccountInfo {
+        let info = self.exchange_api.info().await.ok();
+
+        let mut prices = self.conversion_prices.clone();
+        prices.extend(
+            last_prices
+                .iter()
+                .map(|(symbol, price)| (symbol.clone(), *price)),
+        );
+        let equity_breakdown = self.equity_breakdown(&prices);
+
+        AccountInfo {
+            dry_run: self.dry_run,
+            initial_balance: self.initial_balance,
+            equity: equity_breakdown.equity,
+            equity_breakdown,
+            exchange_balance: self.exchange_balance,
+            exchange_api: info,
+            positions: self.positions.values().map(|el| el.clone()).collect(),
+            trade_transactions: self.trades.clone(),
+            trading_enabled: self.is_trading_enabled(),
+            daily_realized_pnl: self.daily_realized_pnl(),
+            daily_loss_limit: self.daily_loss_limit,
+            daily_loss_budget: self.daily_loss_budget(),
+        }
+    }
+
+    /// Captures the current balance and equity of the account.
+    ///
+    /// # Parameters
+    ///
+    /// * `timestamp` - The time the snapshot is taken at.
+    /// * `last_prices` - Last prices by symbol used to value open positions, positions without
+    ///   a price are valued at their open price. Profit is converted to the base currency at the
+    ///   last conversion prices, profit without a conversion price is left out.
+    ///
+    /// # Returns
+    ///
+    /// An `AccountSnapshot` of the account.
+
+    pub fn snapshot(&self, timestamp: u64, last_prices: &HashMap<String, f64>) -> AccountSnapshot {
+        let unrealized_pnl: Decimal = self
+            .positions
+            .values()
+            .filter_map(|pos| {
+                let last_price = last_prices
+                    .get(&pos.symbol)
+                    .copied()
+                    .unwrap_or(pos.open_price);
+                let asset =
+                    quote::settlement_asset(pos).unwrap_or_else(|| self.base_currency.clone());
+                let rate =
+                    quote::conversion_rate(&asset, &self.base_currency, &self.conversion_prices)?;
+
+                Some(TradeTx::calc_profit(last_price, pos) * to_decimal(rate))
+            })
+            .sum();
+
+        let balance = self.equity();
+
+        AccountSnapshot {
+            timestamp,
+            balance,
+            equity: from_decimal(to_decimal(balance) + unrealized_pnl),
+            open_positions: self.positions.len(),
+            unrealized_pnl: from_decimal(unrealized_pnl),
+        }
+    }
+
+    /// Sums the traded notional of closed trades over a period, by symbol and side.
+    ///
+    /// Both legs of a trade are counted, the opening leg if the position was opened within the
+    /// period and the closing leg if the position was closed within it. The closing leg is traded
+    /// on the opposite side of the position, ie. closing a long position is a sell.
+    ///
+    /// # Parameters
+    ///
+    /// * `from_ts` - Optional start of the period, inclusive.
+    /// * `to_ts` - Optional end of the period, inclusive.
+    ///
+    /// # Returns
+    ///
+    /// A `TurnoverReport` for the period.
+
+    pub fn turnover_report(&self, from_ts: Option<u64>, to_ts: Option<u64>) -> TurnoverReport {
+        let in_period =
+            |ts: u64| from_ts.is_none_or(|from| ts >= from) && to_ts.is_none_or(|to| ts <= to);
+
+        let mut report = TurnoverReport {
+            from_ts,
+            to_ts,
+            ..Default::default()
+        };
+
+        for trade in &self.trades {
+            let position = &trade.position;
+
+            if in_period(position.open_time) {
+                report.add_fill(
+                    &position.symbol,
+                    position.order_side,
+                    position.open_price * position.quantity,
+                );
+            }
+
+            if in_period(trade.close_time) {
+                let close_side = match position.order_side {
+                    OrderSide::Buy => OrderSide::Sell,
+                    OrderSide::Sell => OrderSide::Buy,
+                };
+                report.add_fill(
+                    &position.symbol,
+                    close_side,
+                    trade.close_price * position.quantity,
+                );
+            }
+        }
+
+        report
+    }
+
+    /// Applies an update pushed by the exchange user data stream to the account.
+    ///
+    /// Filled opening orders update the entry price and quantity of the position opened by the
+    /// order, account updates set the exchange wallet balance and sync the entry of positions which are
+    /// the only open position for their symbol. Filled liquidation orders remove the liquidated
+    /// position and record the forced close as a trade.
+    ///
+    /// # Parameters
+    ///
+    /// * `event` - The user data event received from the exchange.
+    ///
+    /// # Returns
+    ///
+    /// The trade recorded for a liquidated position, otherwise `None`.
+
+    pub async fn handle_user_data_event(&mut self, event: UserDataEvent) -> Option<TradeTx> {
+        match event {
+            UserDataEvent::OrderUpdate(order) => {
+                if order.liquidation && order.is_filled() {
+                    let trade_tx = self.liquidate_position(
+                        &order.symbol,
+                        order.order_side,
+                        order.avg_price,
+                        order.trade_time,
+                    )?;
+
+                    // a liquidation breaching the daily loss limit closes the other positions
+                    if self.record_realized_pnl(trade_tx.id) {
+                        self.close_positions_on_breach().await;
+                    }
+
+                    return Some(trade_tx);
+                }
+
+                // closing orders are accounted for when the position is closed
+                if !order.is_filled() || order.reduce_only {
+                    return None;
+                }
+
+                let position = self
+                    .positions
+                    .values_mut()
+                    .find(|pos| pos.order_id == Some(order.order_id));
+
+                if let Some(position) = position {
+                    if order.avg_price > 0.0 {
+                        position.open_price = order.avg_price;
+                    }
+                    position.quantity = order.filled_qty;
+                }
+            }
+            UserDataEvent::AccountUpdate(update) => {
+                let balance = update
+                    .balances
+                    .iter()
+                    .find(|b| b.asset == self.base_currency);
+                if let Some(balance) = balance {
+                    self.exchange_balance = Some(balance.wallet_balance);
+                }
+
+                for position_update in update.positions {
+                    let mut positions = self
+                        .positions
+                        .values_mut()
+                        .filter(|pos| pos.symbol == position_update.symbol);
+
+                    if let (Some(position), None) = (positions.next(), positions.next()) {
+                        if position_update.amount == 0.0 {
+                            warn!(
+                                "Position {} on {} was closed on the exchange",
+                                position.id, position.symbol
+                            );
+                        } else {
+                            position.quantity = position_update.amount.abs();
+                            position.open_price = position_update.entry_price;
+                        }
+                    }
+                }
+            }
+        }
+
+        None
+    }
+
+    /// Retrieves a position by its ID.
+    ///
+    /// # Parameters
+    ///
+    /// * `position_id` - The ID of the position to retrieve.
+    ///
+    /// # Returns
+    ///
+    /// A reference to the position if found, otherwise `None`.
+
+    pub fn get_position(&self, position_id: &PositionId) -> Option<&Position> {
+        self.positions.get(position_id)
+    }
+
+    // ---
+    // Private Methods
+    // ---
+
+    /// Removes the position closed by an exchange liquidation order and records the forced close.
+    ///
+    /// The liquidation order is on the opposite side of the position, if several positions match
+    /// the latest opened one is assumed to be liquidated.
+    fn liquidate_position(
+        &mut self,
+        symbol: &str,
+        order_side: OrderSide,
+        liquidation_price: f64,
+        liquidation_time: u64,
+    ) -> Option<TradeTx> {
+        let position_side = match order_side {
+            OrderSide::Buy => OrderSide::Sell,
+            OrderSide::Sell => OrderSide::Buy,
+        };
+
+        let position_id = self
+            .positions
+            .values()
+            .filter(|pos| pos.symbol == symbol && pos.order_side == position_side)
+            .max_by(|a, b| a.open_time.cmp(&b.open_time))
+            .map(|pos| pos.id)?;
+
+        // SAFETY: position ID found above
+        let position = self.positions.remove(&position_id).unwrap();
+
+        warn!(
+            "Position {} on {} was liquidated at {liquidation_price}",
+            position.id, position.symbol
+        );
+
+        let mut trade_tx = TradeTx::new(liquidation_price, liquidation_time, position);
+        trade_tx.liquidated = true;
+        self.record_trade(trade_tx.clone());
+
+        Some(trade_tx)
+    }
+
+    /// Closes a position on the exchange and records the trade with a close reason, without
+    /// checking the daily loss limit.
+    async fn close_exchange_position(
+        &mut self,
+        position_id: PositionId,
+        close_price: f64,
+        reason: Option<String>,
+    ) -> Result<Uuid, OrderError> {
+        let position =
+            self.positions.get(&position_id).cloned().ok_or_else(|| {
+                OrderError::Rejected(format!("Position {position_id} not tracked"))
+            })?;
+
+        let mut trade_tx = self
+            .exchange_api
+            .close_position(position, close_price)
+            .await
+            .map_err(OrderError::Exchange)?;
+        trade_tx.close_time = self.clock.now();
+        trade_tx.close_reason = reason;
+
+        self.positions.remove(&position_id);
+
+        let trade_tx_id = trade_tx.id;
+        self.record_trade(trade_tx);
+
+        Ok(trade_tx_id)
+    }
+
+    /// Records a trade, adding it to the running aggregates of its strategy and firing the trade
+    /// callbacks.
+    fn record_trade(&mut self, trade_tx: TradeTx) {
+        if let Some(strategy_id) = trade_tx.position.strategy_id {
+            self.trade_aggregates
+                .entry(strategy_id)
+                .or_default()
+                .add_trade(&trade_tx);
+        }
+
+        self.emit_trade_event(TradeEvent::closed(&trade_tx));
+        self.trades.push(trade_tx);
+    }
+
+    /// Calls the trade callbacks with an event.
+    fn emit_trade_event(&self, event: TradeEvent) {
+        for callback in &self.trade_callbacks {
+            callback(&event);
+        }
+    }
+
+    /// Adds the profit of a recorded trade to the daily profit, engaging the kill switch if the
+    /// daily loss limit is breached.
+    ///
+    /// Returns `true` if the trade breached the limit and positions should be closed.
+    fn record_realized_pnl(&mut self, trade_tx_id: Uuid) -> bool {
+        let now = self.clock.now();
+
+        let Some(trade_tx) = self.trades.iter().find(|tx| tx.id == trade_tx_id) else {
+            return false;
+        };
+        self.daily_pnl.record(trade_tx.quote_profit(), now);
+
+        let Some(limit) = self.daily_loss_limit else {
+            return false;
+        };
+
+        if self.daily_pnl.remaining_budget(&limit, now) > 0.0 || self.kill_switch.is_engaged(now) {
+            return false;
+        }
+
+        warn!(
+            "Daily loss limit of {} breached with a realized loss of {}, trading is halted until the next UTC day",
+            limit.limit_usd,
+            -self.daily_pnl.realized_pnl(now)
+        );
+        self.kill_switch.engage_until(risk::next_day_start(now));
+
+        limit.close_positions
+    }
+
+    /// Closes all open positions at their last price after the daily loss limit is breached,
+    /// positions without a price are left open and logged rather than closed at a made up price.
+    async fn close_positions_on_breach(&mut self) {
+        let symbols: Vec<String> = self
+            .positions
+            .values()
+            .map(|pos| pos.symbol.clone())
+            .collect::<BTreeSet<String>>()
+            .into_iter()
+            .collect();
+
+        if symbols.is_empty() {
+            return;
+        }
+
+        let mut last_prices = self
+            .exchange_api
+            .get_last_prices(&symbols)
+            .await
+            .unwrap_or_default();
+
+        // symbols missing from the batch are fetched one by one
+        for symbol in symbols {
+            if last_prices.contains_key(&symbol) {
+                continue;
+            }
+            if let Ok(ticker) = self.exchange_api.get_ticker(&symbol).await {
+                last_prices.insert(symbol, ticker.last_price);
+            }
+        }
+
+        let positions: Vec<(PositionId, f64)> = self
+            .positions
+            .values()
+            .filter_map(|pos| match last_prices.get(&pos.symbol) {
+                Some(price) => Some((pos.id, *price)),
+                None => {
+                    warn!(
+                        "Position {} on {} left open after the daily loss limit breach, no last price",
+                        pos.id, pos.symbol
+                    );
+                    None
+                }
+            })
+            .collect();
+
+        for (position_id, close_price) in positions {
+            let reason = Some("Daily loss limit breached".to_string());
+            let trade_tx_id = match self
+                .close_exchange_position(position_id, close_price, reason)
+                .await
+            {
+                Ok(trade_tx_id) => trade_tx_id,
+                Err(e) => {
+                    warn!("Unable to close position {position_id} after the daily loss limit breach, {e}");
+                    continue;
+                }
+            };
+
+            self.record_realized_pnl(trade_tx_id);
+        }
+    }
+
+    /// Gets the trading rules of a symbol, fetched from the exchange once and cached.
+    ///
+    /// If the exchange doesn't provide symbol info, rules without rounding and the default
+    /// leverage range are returned.
+    async fn get_symbol_info(&mut self, symbol: &str) -> SymbolInfo {
+        if let Some(info) = self.symbol_info.get(symbol) {
+            return info.clone();
+        }
+
+        match self.exchange_api.get_symbol_info(symbol).await {
+            Ok(info) => {
+                self.symbol_info.insert(symbol.to_string(), info.clone());
+                info
+            }
+            Err(_) => SymbolInfo {
+                symbol: symbol.to_string(),
+                tick_size: 0.0,
+                step_size: 0.0,
+                min_leverage: DEFAULT_MIN_LEVERAGE,
+                max_leverage: DEFAULT_MAX_LEVERAGE,
+                contract_type: ContractType::Linear,
+                min_notional: 0.0,
+                status: SymbolStatus::Trading,
+            },
+        }
+    }
+
+    /// Initializes worker threads for the account.
     async fn init(&self) {
         // start any worker threads for account
     }
@@ -311,25 +1574,251 @@ impl Account {
 #[derive(Serialize, Deserialize)]
 pub struct AccountInfo {
     dry_run: bool,
+    initial_balance: f64,
+    equity: f64,
+    equity_breakdown: EquityBreakdown,
+    #[serde(default)]
+    exchange_balance: Option<f64>,
     exchange_api: Option<ExchangeInfo>,
     positions: Vec<Position>,
     trade_transactions: Vec<TradeTx>,
+    /// Whether new positions can be opened, `false` while the kill switch is engaged.
+    trading_enabled: bool,
+    /// Profit realized since the start of the current UTC day.
+    daily_realized_pnl: f64,
+    daily_loss_limit: Option<DailyLossLimit>,
+    /// Loss which can still be realized today, `None` without a daily loss limit.
+    daily_loss_budget: Option<f64>,
+}
+
+/// Outcome of a simulated order, nothing is placed on the exchange.
+///
+/// `risk_violations` lists every risk limit the order breaks, the order passes the risk limits
+/// when it is empty.
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct OrderSimulation {
+    pub symbol: String,
+    pub order_side: OrderSide,
+    pub margin_usd: f64,
+    pub leverage: u32,
+    pub entry_price: f64,
+    /// Quantity rounded to the symbol's step size.
+    pub quantity: f64,
+    pub notional: f64,
+    /// Estimated liquidation price, `None` if the leverage is zero.
+    pub liquidation_price: Option<f64>,
+    pub estimated_fee: f64,
+    pub passes_risk_limits: bool,
+    pub risk_violations: Vec<String>,
+}
+
+/// Reason an order of the account wasn't placed.
+
+#[derive(Debug, Clone)]
+pub enum OrderError {
+    /// Refused by the account before reaching the exchange, ie. by a risk limit.
+    Rejected(String),
+    /// Failed or refused by the exchange.
+    Exchange(ApiError),
+}
+
+impl OrderError {
+    /// Whether the order may succeed when it's retried, ie. after a network error.
+
+    pub fn is_transient(&self) -> bool {
+        match self {
+            OrderError::Rejected(_) => false,
+            OrderError::Exchange(e) => e.is_transient(),
+        }
+    }
+}
+
+impl fmt::Display for OrderError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            OrderError::Rejected(reason) => write!(f, "{reason}"),
+            OrderError::Exchange(e) => write!(f, "exchange error: {e}"),
+        }
+    }
+}
+
+/// What to do with orphan positions, positions held on the exchange which the account doesn't
+/// track.
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OrphanAction {
+    /// Track the position on the account, without a strategy.
+    Adopt,
+    /// Close the position on the exchange, the close isn't recorded as a trade of the account.
+    Close,
+}
+
+/// Kind of difference between the exchange and the account for a symbol and side.
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiscrepancyKind {
+    /// Held on the exchange but not tracked by the account.
+    Untracked,
+    /// Tracked by the account but not held on the exchange.
+    MissingOnExchange,
+    /// Held on the exchange with a different quantity than tracked.
+    QuantityMismatch,
+}
+
+/// A difference between the position held on the exchange and the positions tracked by the
+/// account for a symbol and side.
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PositionDiscrepancy {
+    pub symbol: String,
+    pub order_side: OrderSide,
+    pub kind: DiscrepancyKind,
+    pub exchange_quantity: f64,
+    pub tracked_quantity: f64,
+    /// IDs of the tracked positions of the symbol and side.
+    pub position_ids: Vec<PositionId>,
+}
+
+/// Outcome of reconciling the positions of an account with the exchange.
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Reconciliation {
+    pub exchange_positions: Vec<ExchangePosition>,
+    pub discrepancies: Vec<PositionDiscrepancy>,
+    /// Orphan positions which are now tracked by the account.
+    pub adopted: Vec<Position>,
+    /// Orphan positions which were closed on the exchange.
+    pub closed: Vec<TradeTx>,
+}
+
+/// Point in time record of the account balance, used to chart account growth.
+///
+/// `balance` includes realized profit only, `equity` also includes the unrealized profit of open
+/// positions.
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AccountSnapshot {
+    pub timestamp: u64,
+    pub balance: f64,
+    pub equity: f64,
+    pub open_positions: usize,
+    pub unrealized_pnl: f64,
+}
+
+/// Traded notional of a single symbol, split by side.
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct SymbolTurnover {
+    pub buy_notional: f64,
+    pub sell_notional: f64,
+    pub total_notional: f64,
+    pub fills: usize,
+}
+
+/// Traded notional of the account over a period, ie. for compliance reporting.
+///
+/// Fees are not included as trades don't record the fees charged by the exchange.
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct TurnoverReport {
+    pub from_ts: Option<u64>,
+    pub to_ts: Option<u64>,
+    pub symbols: BTreeMap<String, SymbolTurnover>,
+    pub total_notional: f64,
+    pub fills: usize,
+}
+
+impl TurnoverReport {
+    // ---
+    // Private Methods
+    // ---
+
+    fn add_fill(&mut self, symbol: &str, order_side: OrderSide, notional: f64) {
+        let turnover = self.symbols.entry(symbol.to_string()).or_default();
+        match order_side {
+            OrderSide::Buy => turnover.buy_notional += notional,
+            OrderSide::Sell => turnover.sell_notional += notional,
+        }
+        turnover.total_notional += notional;
+        turnover.fills += 1;
+
+        self.total_notional += notional;
+        self.fills += 1;
+    }
+}
+
+/// Writes the fills of trades within a period as CSV, ie. for tax and accounting exports.
+///
+/// # Parameters
+///
+/// * `trades` - The trades to export, trades listed more than once are exported once.
+/// * `from_ts` - Optional start of the period, inclusive.
+/// * `to_ts` - Optional end of the period, inclusive.
+///
+/// # Returns
+///
+/// The CSV with a header and one row per fill, oldest first.
+
+pub fn fills_csv(
+    trades: &[TradeTx],
+    from_ts: Option<u64>,
+    to_ts: Option<u64>,
+) -> Result<String, csv::Error> {
+    let in_period =
+        |ts: u64| from_ts.is_none_or(|from| ts >= from) && to_ts.is_none_or(|to| ts <= to);
+
+    let mut trade_ids = BTreeSet::new();
+    let mut fills: Vec<Fill> = trades
+        .iter()
+        .filter(|trade| trade_ids.insert(trade.id))
+        .flat_map(|trade| trade.fills())
+        .filter(|fill| in_period(fill.timestamp))
+        .collect();
+    fills.sort_by_key(|fill| fill.timestamp);
+
+    // the header is written even if there are no fills
+    let mut writer = csv::WriterBuilder::new()
+        .has_headers(false)
+        .from_writer(vec![]);
+    writer.write_record([
+        "timestamp",
+        "symbol",
+        "side",
+        "price",
+        "quantity",
+        "fee",
+        "realized_pnl",
+    ])?;
+    for fill in &fills {
+        writer.serialize(fill)?;
+    }
+
+    let bytes = writer.into_inner().map_err(|e| e.into_error())?;
+    Ok(String::from_utf8_lossy(&bytes).into_owned())
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
     use crate::utils::number::generate_random_id;
+    use crate::utils::time::{DAY_AS_MILI, HOUR_AS_MILI, SEC_AS_MILI};
     use crate::{
         account::trade::OrderSide,
-        exchange::{api::ExchangeApi, mock::MockExchangeApi},
+        exchange::{
+            api::ExchangeApi,
+            halt::SYMBOL_STATUS_RECHECK_SECS,
+            mock::{MockExchangeApi, MOCK_MIN_NOTIONAL, MOCK_PRICE},
+        },
+        testutil::TestClock,
     };
+    use serde_json::json;
     use tokio::test;
     use uuid::Uuid;
 
     #[test]
     async fn test_open_position() {
-        let exchange_api: Arc<dyn ExchangeApi> = Arc::new(MockExchangeApi {});
+        let exchange_api: Arc<dyn ExchangeApi> = Arc::new(MockExchangeApi::default());
         let mut account = Account::new(exchange_api.clone(), false, true).await;
 
         // Open a position
@@ -346,9 +1835,113 @@ mod test {
         assert_eq!(account.positions.len(), 1);
     }
 
+    #[test]
+    async fn test_open_position_validates_leverage() {
+        let exchange_api: Arc<dyn ExchangeApi> = Arc::new(MockExchangeApi::default());
+        let mut account = Account::new(exchange_api, false, true).await;
+
+        for leverage in [0, DEFAULT_MAX_LEVERAGE + 1] {
+            assert!(account
+                .validate_leverage("BTCUSDT", leverage)
+                .await
+                .unwrap_err()
+                .contains("between 1 and 125"));
+
+            let position = account
+                .open_position(
+                    "BTCUSDT",
+                    1000.0,
+                    leverage,
+                    OrderSide::Buy,
+                    50000.0,
+                    None,
+                    None,
+                )
+                .await;
+            assert!(position.is_none());
+        }
+        assert!(account.positions.is_empty());
+
+        let position = account
+            .open_position("BTCUSDT", 1000.0, 20, OrderSide::Buy, 50000.0, None, None)
+            .await
+            .unwrap();
+        assert_eq!(position.leverage, 20);
+        assert_eq!(account.positions.len(), 1);
+    }
+
+    #[test]
+    async fn test_open_position_sized_in_contracts() {
+        let mock_api = Arc::new(MockExchangeApi::default());
+        let exchange_api: Arc<dyn ExchangeApi> = mock_api.clone();
+        let mut account = Account::new(exchange_api, false, true).await;
+
+        // linear orders are sized in the base asset, rounded to the step size
+        let position = account
+            .open_position("BTCUSDT", 100.0, 3, OrderSide::Buy, 70000.0, None, None)
+            .await
+            .unwrap();
+        assert_eq!(position.quantity, 0.004);
+        assert!(position.order_id.is_some());
+
+        // inverse orders are sized in contracts
+        mock_api.set_contract_type(
+            "ETHUSDT",
+            ContractType::Inverse {
+                contract_size: 10.0,
+            },
+        );
+        let position = account
+            .open_position("ETHUSDT", 100.0, 2, OrderSide::Sell, 3000.0, None, None)
+            .await
+            .unwrap();
+        assert_eq!(position.quantity, 20.0);
+
+        // contracts without a positive size are rejected before an order is placed
+        mock_api.set_contract_type("SOLUSDT", ContractType::Inverse { contract_size: 0.0 });
+        let result = account
+            .try_open_position("SOLUSDT", 100.0, 1, OrderSide::Buy, 150.0, None, None, None)
+            .await;
+        assert!(matches!(result, Err(OrderError::Rejected(_))));
+        assert_eq!(account.positions.len(), 2);
+    }
+
+    #[test]
+    async fn test_open_position_rejects_below_min_notional() {
+        let exchange_api: Arc<dyn ExchangeApi> = Arc::new(MockExchangeApi::default());
+        let mut account = Account::new(exchange_api, false, true).await;
+
+        // 2 USD at 2x is below the 5 USD minimum of the exchange
+        assert_eq!(
+            account.validate_notional("BTCUSDT", 4.0).await,
+            Err(format!(
+                "Order notional 4 below minimum {MOCK_MIN_NOTIONAL} for BTCUSDT"
+            ))
+        );
+        let position = account
+            .open_position("BTCUSDT", 2.0, 2, OrderSide::Buy, 50000.0, None, None)
+            .await;
+        assert!(position.is_none());
+        assert!(account.positions.is_empty());
+
+        // the account minimum raises the minimum of the exchange
+        account.set_min_notional(50.0);
+        assert_eq!(
+            account.validate_notional("BTCUSDT", 20.0).await,
+            Err("Order notional 20 below minimum 50 for BTCUSDT".to_string())
+        );
+        assert!(account.validate_notional("BTCUSDT", 50.0).await.is_ok());
+
+        account
+            .open_position("BTCUSDT", 25.0, 2, OrderSide::Buy, 50000.0, None, None)
+            .await
+            .unwrap();
+        assert_eq!(account.positions.len(), 1);
+    }
+
     #[test]
     async fn test_close_position() {
-        let exchange_api: Arc<dyn ExchangeApi> = Arc::new(MockExchangeApi {});
+        let exchange_api: Arc<dyn ExchangeApi> = Arc::new(MockExchangeApi::default());
         let mut account = Account::new(exchange_api.clone(), false, true).await;
 
         // Open a position
@@ -371,7 +1964,7 @@ mod test {
 
     #[test]
     async fn test_close_multiple_positions() {
-        let exchange_api: Arc<dyn ExchangeApi> = Arc::new(MockExchangeApi {});
+        let exchange_api: Arc<dyn ExchangeApi> = Arc::new(MockExchangeApi::default());
         let mut account = Account::new(exchange_api.clone(), false, true).await;
 
         const NUM_POSITIONS: usize = 10; // Change this to the desired number of positions for testing
@@ -437,7 +2030,7 @@ mod test {
 
     #[test]
     async fn test_open_positions() {
-        let exchange_api: Arc<dyn ExchangeApi> = Arc::new(MockExchangeApi {});
+        let exchange_api: Arc<dyn ExchangeApi> = Arc::new(MockExchangeApi::default());
         let mut account = Account::new(exchange_api.clone(), false, true).await;
 
         // Open a position
@@ -458,7 +2051,7 @@ mod test {
 
     #[test]
     async fn test_strategy_open_positions() {
-        let exchange_api: Arc<dyn ExchangeApi> = Arc::new(MockExchangeApi {});
+        let exchange_api: Arc<dyn ExchangeApi> = Arc::new(MockExchangeApi::default());
         let mut account = Account::new(exchange_api.clone(), false, true).await;
 
         let strategy_id_1 = Uuid::new_v4();
@@ -537,4 +2130,740 @@ mod test {
         assert!(open_positions_strategy_1.contains(&position_2_id));
         assert!(open_positions_strategy_2.contains(&position_3_id));
     }
+
+    #[test]
+    async fn test_account_snapshot() {
+        let exchange_api: Arc<dyn ExchangeApi> = Arc::new(MockExchangeApi::default());
+        let mut account = Account::new(exchange_api.clone(), false, true).await;
+        account.set_initial_balance(1000.0);
+
+        let position_id = account
+            .open_position("BTCUSDT", 100.0, 1, OrderSide::Buy, 100.0, None, None)
+            .await
+            .unwrap()
+            .id;
+        account.close_position(position_id, 110.0).await;
+
+        account
+            .open_position("ETHUSDT", 100.0, 1, OrderSide::Sell, 50.0, None, None)
+            .await;
+
+        let last_prices = HashMap::from([("ETHUSDT".to_string(), 45.0)]);
+        let snapshot = account.snapshot(1_700_000_000_000, &last_prices);
+
+        assert_eq!(snapshot.timestamp, 1_700_000_000_000);
+        assert_eq!(snapshot.balance, 1010.0);
+        assert_eq!(snapshot.unrealized_pnl, 10.0);
+        assert_eq!(snapshot.equity, 1020.0);
+        assert_eq!(snapshot.open_positions, 1);
+    }
+
+    #[test]
+    async fn test_turnover_report() {
+        let exchange_api: Arc<dyn ExchangeApi> = Arc::new(MockExchangeApi::default());
+        let mut account = Account::new(exchange_api.clone(), false, true).await;
+
+        // long 1 BTCUSDT at 100, closed at 110
+        let position_id = account
+            .open_position("BTCUSDT", 100.0, 1, OrderSide::Buy, 100.0, None, None)
+            .await
+            .unwrap()
+            .id;
+        account.close_position(position_id, 110.0).await;
+
+        // short 2 ETHUSDT at 50, closed at 45
+        let position_id = account
+            .open_position("ETHUSDT", 100.0, 1, OrderSide::Sell, 50.0, None, None)
+            .await
+            .unwrap()
+            .id;
+        account.close_position(position_id, 45.0).await;
+
+        // open positions have no closed trade yet
+        account
+            .open_position("BTCUSDT", 100.0, 1, OrderSide::Buy, 100.0, None, None)
+            .await;
+
+        let report = account.turnover_report(None, None);
+
+        let btc = &report.symbols["BTCUSDT"];
+        assert_eq!(btc.buy_notional, 100.0);
+        assert_eq!(btc.sell_notional, 110.0);
+        assert_eq!(btc.total_notional, 210.0);
+        assert_eq!(btc.fills, 2);
+
+        let eth = &report.symbols["ETHUSDT"];
+        assert_eq!(eth.buy_notional, 90.0);
+        assert_eq!(eth.sell_notional, 100.0);
+        assert_eq!(eth.total_notional, 190.0);
+
+        assert_eq!(report.total_notional, 400.0);
+        assert_eq!(report.fills, 4);
+
+        // trades outside the period are excluded
+        let report = account.turnover_report(None, Some(1_000));
+        assert!(report.symbols.is_empty());
+        assert_eq!(report.total_notional, 0.0);
+    }
+
+    #[test]
+    async fn test_fills_csv() {
+        let exchange_api: Arc<dyn ExchangeApi> = Arc::new(MockExchangeApi::default());
+        let mut account = Account::new(exchange_api.clone(), false, true).await;
+
+        // long 1 BTCUSDT at 100 closed at 110, short 2 ETHUSDT at 50 closed at 45
+        for (symbol, order_side, open_price, close_price) in [
+            ("BTCUSDT", OrderSide::Buy, 100.0, 110.0),
+            ("ETHUSDT", OrderSide::Sell, 50.0, 45.0),
+        ] {
+            let position_id = account
+                .open_position(symbol, 100.0, 1, order_side, open_price, None, None)
+                .await
+                .unwrap()
+                .id;
+            account.close_position(position_id, close_price).await;
+        }
+
+        // a trade listed twice, ie. by the account and a strategy summary, is exported once
+        let mut trades = account.trades();
+        trades.push(trades[0].clone());
+
+        let csv = fills_csv(&trades, None, None).unwrap();
+        let rows: Vec<&str> = csv.lines().collect();
+
+        assert_eq!(
+            rows[0],
+            "timestamp,symbol,side,price,quantity,fee,realized_pnl"
+        );
+        assert_eq!(rows.len(), 5);
+
+        let mut fills: Vec<Vec<&str>> = rows[1..]
+            .iter()
+            .map(|row| row.split(',').skip(1).collect())
+            .collect();
+        fills.sort();
+        assert_eq!(
+            fills,
+            vec![
+                vec!["BTCUSDT", "Buy", "100.0", "1.0", "0.05", "0.0"],
+                vec!["BTCUSDT", "Sell", "110.0", "1.0", "0.055", "10.0"],
+                vec!["ETHUSDT", "Buy", "45.0", "2.0", "0.045", "10.0"],
+                vec!["ETHUSDT", "Sell", "50.0", "2.0", "0.05", "0.0"],
+            ]
+        );
+
+        // fills outside the period are excluded
+        let csv = fills_csv(&trades, None, Some(1_000)).unwrap();
+        assert_eq!(csv.lines().count(), 1);
+    }
+
+    #[test]
+    async fn test_simulate_order() {
+        let exchange_api: Arc<dyn ExchangeApi> = Arc::new(MockExchangeApi::default());
+        let mut account = Account::new(exchange_api, false, true).await;
+        account.set_initial_balance(5000.0);
+
+        let simulation = account
+            .simulate_order("BTCUSDT", OrderSide::Buy, 1000.0, 10, 30000.0)
+            .await;
+
+        // 10,000 notional at 30,000 rounded to the 0.001 step size
+        assert_eq!(simulation.quantity, 0.333);
+        assert_eq!(simulation.liquidation_price, Some(27120.0));
+        assert!((simulation.estimated_fee - 4.995).abs() < 1e-9);
+        assert!(simulation.passes_risk_limits);
+
+        let simulation = account
+            .simulate_order("BTCUSDT", OrderSide::Sell, 1000.0, 10, 30000.0)
+            .await;
+        assert_eq!(simulation.liquidation_price, Some(32880.0));
+
+        // nothing is placed
+        assert_eq!(account.positions.len(), 0);
+
+        let simulation = account
+            .simulate_order("BTCUSDT", OrderSide::Buy, 6000.0, 200, 30000.0)
+            .await;
+        assert!(!simulation.passes_risk_limits);
+        assert_eq!(simulation.risk_violations.len(), 2);
+    }
+
+    #[test]
+    async fn test_adopt_exchange_positions_on_startup() {
+        let mock = Arc::new(MockExchangeApi::default());
+        let exchange_api: Arc<dyn ExchangeApi> = mock.clone();
+        let mut account = Account::new(exchange_api, true, false).await;
+
+        // BTCUSDT long opened before the bot was restarted
+        mock.set_positions(vec![ExchangePosition {
+            symbol: "BTCUSDT".to_string(),
+            order_side: OrderSide::Buy,
+            quantity: 0.5,
+            entry_price: 100.0,
+            leverage: 2,
+        }]);
+
+        let adopted = account.adopt_exchange_positions().await.unwrap();
+        assert_eq!(adopted.len(), 1);
+
+        let position = account.get_position(&adopted[0].id).unwrap();
+        assert!(position.adopted);
+        assert_eq!(position.symbol, "BTCUSDT");
+        assert_eq!(position.quantity, 0.5);
+        assert_eq!(position.open_price, 100.0);
+        assert_eq!(position.strategy_id, None);
+
+        // positions already tracked aren't adopted twice
+        assert!(account.adopt_exchange_positions().await.unwrap().is_empty());
+        assert_eq!(account.positions().count(), 1);
+    }
+
+    #[test]
+    async fn test_reconcile_reports_untracked_exchange_position() {
+        let mock = Arc::new(MockExchangeApi::default());
+        let exchange_api: Arc<dyn ExchangeApi> = mock.clone();
+        let mut account = Account::new(exchange_api, false, true).await;
+
+        // 1 BTCUSDT tracked by the account and held on the exchange
+        let tracked_id = account
+            .open_position("BTCUSDT", 100.0, 1, OrderSide::Buy, 100.0, None, None)
+            .await
+            .unwrap()
+            .id;
+
+        // ETHUSDT short opened manually on the exchange
+        let orphan = ExchangePosition {
+            symbol: "ETHUSDT".to_string(),
+            order_side: OrderSide::Sell,
+            quantity: 2.0,
+            entry_price: 50.0,
+            leverage: 5,
+        };
+        mock.set_positions(vec![
+            ExchangePosition {
+                symbol: "BTCUSDT".to_string(),
+                order_side: OrderSide::Buy,
+                quantity: 1.0,
+                entry_price: 100.0,
+                leverage: 1,
+            },
+            orphan.clone(),
+        ]);
+
+        let reconciliation = account.reconcile(None, &HashMap::new()).await.unwrap();
+
+        assert_eq!(reconciliation.exchange_positions.len(), 2);
+        assert_eq!(
+            reconciliation.discrepancies,
+            vec![PositionDiscrepancy {
+                symbol: "ETHUSDT".to_string(),
+                order_side: OrderSide::Sell,
+                kind: DiscrepancyKind::Untracked,
+                exchange_quantity: 2.0,
+                tracked_quantity: 0.0,
+                position_ids: vec![],
+            }]
+        );
+        // report only without an action
+        assert_eq!(account.positions.len(), 1);
+
+        // adopted orphans are tracked from then on
+        let reconciliation = account
+            .reconcile(Some(OrphanAction::Adopt), &HashMap::new())
+            .await
+            .unwrap();
+        assert_eq!(reconciliation.adopted.len(), 1);
+        assert_eq!(reconciliation.adopted[0].quantity, 2.0);
+        assert_eq!(account.positions.len(), 2);
+
+        let reconciliation = account.reconcile(None, &HashMap::new()).await.unwrap();
+        assert!(reconciliation.discrepancies.is_empty());
+
+        // positions closed outside the bot are missing on the exchange
+        mock.set_positions(vec![orphan]);
+        let reconciliation = account.reconcile(None, &HashMap::new()).await.unwrap();
+        assert_eq!(
+            reconciliation.discrepancies[0].kind,
+            DiscrepancyKind::MissingOnExchange
+        );
+        assert_eq!(
+            reconciliation.discrepancies[0].position_ids,
+            vec![tracked_id]
+        );
+    }
+
+    #[test]
+    async fn test_trade_callback_fires_on_open_and_close() {
+        let exchange_api: Arc<dyn ExchangeApi> = Arc::new(MockExchangeApi::default());
+        let mut account = Account::new(exchange_api, false, true).await;
+        let strategy_id = Uuid::new_v4();
+
+        let events = Arc::new(std::sync::Mutex::new(vec![]));
+        let received = events.clone();
+        account.on_trade(Arc::new(move |event: &TradeEvent| {
+            received.lock().unwrap().push(event.clone());
+        }));
+
+        let position = account
+            .open_position(
+                "BTCUSDT",
+                100.0,
+                1,
+                OrderSide::Buy,
+                100.0,
+                Some(strategy_id),
+                None,
+            )
+            .await
+            .unwrap()
+            .clone();
+
+        let received = events.lock().unwrap().clone();
+        match &received[..] {
+            [TradeEvent::Opened {
+                position: opened,
+                strategy_id: opened_strategy_id,
+            }] => {
+                assert_eq!(opened.id, position.id);
+                assert_eq!(opened.open_price, 100.0);
+                assert_eq!(*opened_strategy_id, Some(strategy_id));
+            }
+            events => panic!("unexpected events {events:?}"),
+        }
+
+        let trade_tx = account
+            .close_position(position.id, 110.0)
+            .await
+            .unwrap()
+            .clone();
+
+        let received = events.lock().unwrap().clone();
+        match &received[..] {
+            [_, TradeEvent::Closed {
+                trade_tx: closed,
+                strategy_id: closed_strategy_id,
+                reason,
+            }] => {
+                assert_eq!(closed.id, trade_tx.id);
+                assert_eq!(closed.position.id, position.id);
+                assert_eq!(closed.close_price, 110.0);
+                assert_eq!(*closed_strategy_id, Some(strategy_id));
+                assert_eq!(*reason, None);
+            }
+            events => panic!("unexpected events {events:?}"),
+        }
+    }
+
+    #[test]
+    async fn test_close_expired_positions() {
+        let exchange_api: Arc<dyn ExchangeApi> = Arc::new(MockExchangeApi::default());
+        let mut account = Account::new(exchange_api, false, true).await;
+        let strategy_id = Uuid::new_v4();
+        let open_time = 1_700_000_000_000;
+
+        for _ in 0..2 {
+            account
+                .open_position(
+                    "BTCUSDT",
+                    100.0,
+                    1,
+                    OrderSide::Buy,
+                    100.0,
+                    Some(strategy_id),
+                    None,
+                )
+                .await;
+        }
+
+        // first position was opened an hour before the second
+        let mut ids: Vec<PositionId> = account.positions.keys().copied().collect();
+        ids.sort();
+        account.positions.get_mut(&ids[0]).unwrap().open_time = open_time - 3_600_000;
+        account.positions.get_mut(&ids[1]).unwrap().open_time = open_time;
+
+        // next kline closes 30 minutes after the second position was opened
+        let trades = account
+            .close_expired_positions(strategy_id, 3_600, open_time + 1_800_000, 110.0)
+            .await;
+
+        assert_eq!(trades.len(), 1);
+        assert_eq!(trades[0].position.id, ids[0]);
+        assert_eq!(trades[0].close_price, 110.0);
+        assert_eq!(
+            trades[0].close_reason,
+            Some("Max hold of 3600s exceeded".to_string())
+        );
+        assert_eq!(account.trades()[0].close_reason, trades[0].close_reason);
+        assert!(account.positions.contains_key(&ids[1]));
+    }
+
+    #[test]
+    async fn test_positions_closed_at_exits() {
+        let mock_api = Arc::new(MockExchangeApi::default());
+        let exchange_api: Arc<dyn ExchangeApi> = mock_api.clone();
+        let mut account = Account::new(exchange_api, false, true).await;
+
+        // a fill beyond the stop loss closes the position right away
+        mock_api.set_fill_price(Some(94.0));
+        let result = account
+            .try_open_position(
+                "BTCUSDT",
+                100.0,
+                1,
+                OrderSide::Buy,
+                100.0,
+                None,
+                Some(95.0),
+                None,
+            )
+            .await;
+        assert!(matches!(result, Err(OrderError::Rejected(_))));
+        assert!(account.positions.is_empty());
+        assert_eq!(account.trades()[0].close_price, 94.0);
+
+        mock_api.set_fill_price(None);
+        let long_id = account
+            .open_position("BTCUSDT", 100.0, 1, OrderSide::Buy, 100.0, None, Some(95.0))
+            .await
+            .unwrap()
+            .id;
+        let short = account
+            .open_position("ETHUSDT", 100.0, 1, OrderSide::Sell, 100.0, None, None)
+            .await
+            .unwrap();
+        short.set_take_profit(Some(90.0)).unwrap();
+        let short_id = short.id;
+
+        // neither exit is reached
+        let last_prices =
+            HashMap::from([("BTCUSDT".to_string(), 96.0), ("ETHUSDT".to_string(), 91.0)]);
+        assert!(account
+            .close_positions_at_exits(&last_prices)
+            .await
+            .is_empty());
+
+        // the stop loss of the long and the take profit of the short are reached
+        let last_prices =
+            HashMap::from([("BTCUSDT".to_string(), 95.0), ("ETHUSDT".to_string(), 89.0)]);
+        let mut trades = account.close_positions_at_exits(&last_prices).await;
+        trades.sort_by_key(|trade| trade.position.symbol.clone());
+
+        assert_eq!(trades.len(), 2);
+        assert_eq!(
+            (trades[0].position.id, trades[0].close_price),
+            (long_id, 95.0)
+        );
+        assert_eq!(
+            trades[0].close_reason,
+            Some("Stop loss 95 reached".to_string())
+        );
+        assert_eq!(
+            (trades[1].position.id, trades[1].close_price),
+            (short_id, 89.0)
+        );
+        assert_eq!(
+            trades[1].close_reason,
+            Some("Take profit 90 reached".to_string())
+        );
+        assert!(account.positions.is_empty());
+    }
+
+    #[test]
+    async fn test_equity_converted_across_quote_assets() {
+        let exchange_api: Arc<dyn ExchangeApi> = Arc::new(MockExchangeApi::default());
+        let mut account = Account::new(exchange_api, false, true).await;
+        account.set_initial_balance(1000.0);
+
+        for (symbol, close_price) in [("BTCUSDT", 110.0), ("ETHUSDC", 120.0)] {
+            let position_id = account
+                .open_position(symbol, 100.0, 1, OrderSide::Buy, 100.0, None, None)
+                .await
+                .unwrap()
+                .id;
+            account
+                .close_position(position_id, close_price)
+                .await
+                .unwrap();
+        }
+
+        let usdt_profit = from_decimal(account.trades()[0].profit);
+        let usdc_profit = from_decimal(account.trades()[1].profit);
+        assert!(usdt_profit > 0.0 && usdc_profit > 0.0);
+        assert_eq!(
+            account.conversion_symbols(),
+            vec!["USDCUSDT".to_string(), "USDTUSDC".to_string()]
+        );
+
+        // USDC converted at the direct cross rate
+        let last_prices = HashMap::from([("USDCUSDT".to_string(), 0.99)]);
+        let breakdown = account.equity_breakdown(&last_prices);
+        assert_eq!(breakdown.base_currency, "USDT");
+        assert_eq!(breakdown.subtotals["USDT"], 1000.0 + usdt_profit);
+        assert_eq!(breakdown.subtotals["USDC"], usdc_profit);
+        assert!(breakdown.unconverted.is_empty());
+        assert!((breakdown.equity - (1000.0 + usdt_profit + usdc_profit * 0.99)).abs() < 1e-9);
+
+        // or the inverse of the opposite pair
+        let last_prices = HashMap::from([("USDTUSDC".to_string(), 1.25)]);
+        let breakdown = account.equity_breakdown(&last_prices);
+        assert!((breakdown.equity - (1000.0 + usdt_profit + usdc_profit / 1.25)).abs() < 1e-9);
+
+        // without a rate the USDC subtotal is reported but left out of the equity
+        let info = account.info(&HashMap::new()).await;
+        assert_eq!(info.equity, 1000.0 + usdt_profit);
+        assert_eq!(info.equity_breakdown.unconverted, vec!["USDC".to_string()]);
+        assert_eq!(info.equity_breakdown.subtotals["USDC"], usdc_profit);
+        assert_eq!(account.equity(), 1000.0 + usdt_profit);
+
+        // equity used for margin checks is converted at the last conversion prices
+        account.set_conversion_prices(&HashMap::from([
+            ("USDCUSDT".to_string(), 0.99),
+            ("BTCUSDT".to_string(), 50_000.0),
+        ]));
+        assert!((account.equity() - (1000.0 + usdt_profit + usdc_profit * 0.99)).abs() < 1e-9);
+        let info = account.info(&HashMap::new()).await;
+        assert_eq!(info.equity, account.equity());
+    }
+
+    #[test]
+    async fn test_daily_loss_limit_halts_trading_until_next_day() {
+        let exchange_api: Arc<dyn ExchangeApi> = Arc::new(MockExchangeApi::default());
+        let mut account = Account::new(exchange_api, false, true).await;
+
+        // 2024-01-01 10:00 UTC
+        let day_start = 1_704_067_200_000;
+        let clock = Arc::new(TestClock::new(day_start + 10 * HOUR_AS_MILI));
+        account.set_clock(clock.clone());
+        account.set_daily_loss_limit(Some(DailyLossLimit {
+            limit_usd: 80.0,
+            close_positions: true,
+        }));
+
+        let open = |account: &mut Account, price: f64| {
+            let position = Position::new("BTCUSDT", price, OrderSide::Buy, 100.0, 1, None);
+            account.positions.insert(position.id, position.clone());
+            position.id
+        };
+
+        // a loss within the budget leaves trading enabled
+        let first = open(&mut account, 100.0);
+        let held = open(&mut account, 200.0);
+        account.close_position(first, 50.0).await.unwrap();
+
+        assert!(account.is_trading_enabled());
+        assert_eq!(account.daily_realized_pnl(), -50.0);
+        assert_eq!(account.daily_loss_budget(), Some(30.0));
+
+        // breaching the limit halts trading and closes the held position at its last price
+        let second = open(&mut account, 100.0);
+        account.close_position(second, 60.0).await.unwrap();
+
+        assert!(!account.is_trading_enabled());
+        assert!(account.positions.is_empty());
+        let closed = account
+            .trades
+            .iter()
+            .find(|tx| tx.position.id == held)
+            .unwrap();
+        assert_eq!(closed.close_price, MOCK_PRICE);
+        assert_eq!(
+            closed.close_reason,
+            Some("Daily loss limit breached".to_string())
+        );
+        assert_eq!(account.daily_realized_pnl(), -140.0);
+        assert_eq!(account.daily_loss_budget(), Some(0.0));
+        assert!(account
+            .open_position("BTCUSDT", 100.0, 1, OrderSide::Buy, 100.0, None, None)
+            .await
+            .is_none());
+
+        // trading resumes at the next UTC day with a fresh budget
+        clock.set(day_start + DAY_AS_MILI);
+
+        let info = account.info(&HashMap::new()).await;
+        assert!(info.trading_enabled);
+        assert_eq!(info.daily_realized_pnl, 0.0);
+        assert_eq!(info.daily_loss_budget, Some(80.0));
+        assert!(account
+            .open_position("BTCUSDT", 100.0, 1, OrderSide::Buy, 100.0, None, None)
+            .await
+            .is_some());
+    }
+
+    #[test]
+    async fn test_account_update_sets_base_currency_balance() {
+        let exchange_api: Arc<dyn ExchangeApi> = Arc::new(MockExchangeApi::default());
+        let mut account = Account::new(exchange_api, false, true).await;
+        account.set_base_currency("usdc");
+
+        let balance = |asset: &str, wallet_balance| crate::account::user_data::BalanceUpdate {
+            asset: asset.to_string(),
+            wallet_balance,
+        };
+        let event = UserDataEvent::AccountUpdate(crate::account::user_data::AccountUpdate {
+            balances: vec![balance("USDT", 100.0), balance("USDC", 250.0)],
+            positions: vec![],
+        });
+        account.handle_user_data_event(event).await;
+
+        assert_eq!(account.exchange_balance, Some(250.0));
+    }
+
+    #[test]
+    async fn test_liquidation_breaching_daily_loss_limit_closes_positions() {
+        let exchange_api: Arc<dyn ExchangeApi> = Arc::new(MockExchangeApi::default());
+        let mut account = Account::new(exchange_api, false, true).await;
+        account.set_daily_loss_limit(Some(DailyLossLimit {
+            limit_usd: 50.0,
+            close_positions: true,
+        }));
+
+        let liquidated = Position::new("BTCUSDT", 100.0, OrderSide::Buy, 100.0, 1, None);
+        let held = Position::new("ETHUSDT", 100.0, OrderSide::Buy, 100.0, 1, None);
+        for position in [&liquidated, &held] {
+            account.positions.insert(position.id, position.clone());
+        }
+
+        let event = UserDataEvent::OrderUpdate(crate::account::user_data::OrderUpdate {
+            symbol: "BTCUSDT".to_string(),
+            order_id: 1,
+            order_side: OrderSide::Sell,
+            status: "FILLED".to_string(),
+            avg_price: 40.0,
+            filled_qty: 1.0,
+            realized_profit: -60.0,
+            reduce_only: true,
+            liquidation: true,
+            trade_time: 1_704_067_200_000,
+        });
+        let trade = account.handle_user_data_event(event).await.unwrap();
+        assert_eq!(trade.position.id, liquidated.id);
+
+        // the liquidation breaches the limit, so the other position is closed too
+        assert!(!account.is_trading_enabled());
+        assert!(account.positions.is_empty());
+        let closed = account
+            .trades
+            .iter()
+            .find(|tx| tx.position.id == held.id)
+            .unwrap();
+        assert_eq!(closed.close_price, MOCK_PRICE);
+
+        // other accounts keep trading
+        let other_api: Arc<dyn ExchangeApi> = Arc::new(MockExchangeApi::default());
+        let other = Account::new(other_api, false, true).await;
+        assert!(other.is_trading_enabled());
+    }
+
+    #[test]
+    async fn test_position_open_and_close_times() {
+        let exchange_api: Arc<dyn ExchangeApi> = Arc::new(MockExchangeApi::default());
+        let mut account = Account::new(exchange_api, false, true).await;
+
+        let open_time = 1_704_067_200_000;
+        let clock = Arc::new(TestClock::new(open_time));
+        account.set_clock(clock.clone());
+
+        let position = account
+            .open_position("BTCUSDT", 100.0, 1, OrderSide::Buy, 100.0, None, None)
+            .await
+            .unwrap()
+            .clone();
+        assert_eq!(position.open_time, open_time);
+
+        clock.set(open_time + 90 * 60_000);
+        assert_eq!(position.age(clock.now()), 90 * 60_000);
+
+        let trade_tx = account.close_position(position.id, 110.0).await.unwrap();
+        assert_eq!(trade_tx.position.open_time, open_time);
+        assert_eq!(trade_tx.close_time, open_time + 90 * 60_000);
+        assert_eq!(trade_tx.duration(), 90 * 60_000);
+
+        // trades saved with date strings are migrated
+        let mut saved = serde_json::to_value(&*trade_tx).unwrap();
+        saved["close_time"] = json!("2024-01-01T01:30:00Z");
+        saved["position"]["open_time"] = json!("2024-01-01T00:00:00Z");
+        let migrated: TradeTx = serde_json::from_value(saved).unwrap();
+        assert_eq!(migrated.close_time, open_time + 90 * 60_000);
+        assert_eq!(migrated.duration(), 90 * 60_000);
+    }
+
+    #[test]
+    async fn test_open_position_refused_while_symbol_halted() {
+        let mock_api = Arc::new(MockExchangeApi::default());
+        mock_api.set_symbol_status("BTCUSDT", SymbolStatus::Halted("BREAK".to_string()));
+        let exchange_api: Arc<dyn ExchangeApi> = mock_api.clone();
+        let mut account = Account::new(exchange_api, false, true).await;
+
+        let start = 1_704_067_200_000;
+        let clock = Arc::new(TestClock::new(start));
+        account.set_clock(clock.clone());
+        let trading_halts = account.trading_halts();
+
+        // the status of the symbol is fetched in the background once it's been checked
+        assert!(account.check_trading_status("BTCUSDT").is_ok());
+        trading_halts.refresh(mock_api.as_ref(), start).await;
+
+        assert!(account
+            .open_position("BTCUSDT", 100.0, 1, OrderSide::Buy, 100.0, None, None)
+            .await
+            .is_none());
+        assert_eq!(trading_halts.halted()["BTCUSDT"].status, "BREAK");
+
+        // the halt holds until the status is refreshed
+        mock_api.set_symbol_status("BTCUSDT", SymbolStatus::Trading);
+        let symbol_info_requests = mock_api.symbol_info_requests();
+        assert!(account
+            .open_position("BTCUSDT", 100.0, 1, OrderSide::Buy, 100.0, None, None)
+            .await
+            .is_none());
+        assert_eq!(mock_api.symbol_info_requests(), symbol_info_requests);
+
+        trading_halts.refresh(mock_api.as_ref(), start).await;
+        assert!(account
+            .open_position("BTCUSDT", 100.0, 1, OrderSide::Buy, 100.0, None, None)
+            .await
+            .is_some());
+        assert!(trading_halts.halted().is_empty());
+    }
+
+    #[test]
+    async fn test_maintenance_errors_halt_symbols_without_status() {
+        let exchange_api: Arc<dyn ExchangeApi> = Arc::new(MockExchangeApi::default());
+        let mut account = Account::new(exchange_api, false, true).await;
+
+        let start = 1_704_067_200_000;
+        let clock = Arc::new(TestClock::new(start));
+        account.set_clock(clock.clone());
+        let trading_halts = account.trading_halts();
+
+        let maintenance = ApiError::Halted("System maintenance".to_string());
+        trading_halts.record_error("BTCUSDT", &maintenance, start);
+        trading_halts.record_error("ETHUSDT", &ApiError::Network("timeout".to_string()), start);
+
+        assert!(account.check_trading_status("BTCUSDT").is_err());
+        assert!(account.check_trading_status("ETHUSDT").is_ok());
+        assert_eq!(trading_halts.halted()["BTCUSDT"].status, "MAINTENANCE");
+
+        // symbols halted by an error are retried once due a recheck, as an exchange without
+        // symbol statuses never reports them trading again
+        clock.set(start + SYMBOL_STATUS_RECHECK_SECS * SEC_AS_MILI);
+        assert!(account.check_trading_status("BTCUSDT").is_ok());
+        assert!(trading_halts.halted().is_empty());
+    }
+
+    #[test]
+    async fn test_refresh_stops_without_symbol_statuses() {
+        let mock_api =
+            MockExchangeApi::with_symbol_info_error(ApiError::Unsupported("no info".to_string()));
+        let trading_halts = TradingHalts::default();
+
+        assert!(trading_halts.check("BTCUSDT", 0).is_ok());
+        trading_halts.refresh(&mock_api, 0).await;
+        trading_halts.refresh(&mock_api, 0).await;
+
+        // statuses aren't requested again once the exchange doesn't support them
+        assert_eq!(mock_api.symbol_info_requests(), 1);
+        assert!(trading_halts.check("BTCUSDT", 0).is_ok());
+    }
 }
@@ -0,0 +1,51 @@
+use std::sync::Arc;
+
+use serde::Serialize;
+
+use crate::{
+    account::trade::{Position, TradeTx},
+    strategy::strategy::StrategyId,
+};
+
+/// A position opened or closed on an account, passed to the trade callbacks of the account.
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum TradeEvent {
+    Opened {
+        position: Position,
+        strategy_id: Option<StrategyId>,
+    },
+    Closed {
+        trade_tx: TradeTx,
+        strategy_id: Option<StrategyId>,
+        /// Why the position was closed, ie. a max hold or liquidation, `None` for closes
+        /// requested by a strategy signal or the API.
+        reason: Option<String>,
+    },
+}
+
+impl TradeEvent {
+    /// Builds the event of a recorded trade, liquidated trades without a close reason are
+    /// reported as liquidations.
+
+    pub fn closed(trade_tx: &TradeTx) -> Self {
+        let reason = match (&trade_tx.close_reason, trade_tx.liquidated) {
+            (Some(reason), _) => Some(reason.clone()),
+            (None, true) => Some("Liquidated".to_string()),
+            (None, false) => None,
+        };
+
+        TradeEvent::Closed {
+            trade_tx: trade_tx.clone(),
+            strategy_id: trade_tx.position.strategy_id,
+            reason,
+        }
+    }
+}
+
+/// Callback fired after every position opened or closed on an account.
+///
+/// Callbacks are called while the account is locked so must return quickly, slow consumers such
+/// as notifiers should spawn a task to handle the event.
+pub type TradeCallback = Arc<dyn Fn(&TradeEvent) + Send + Sync>;
@@ -1,2 +1,7 @@
 pub mod account;
+pub mod events;
+pub mod quote;
+pub mod risk;
+pub mod schedule;
 pub mod trade;
+pub mod user_data;
@@ -0,0 +1,129 @@
+use std::collections::{BTreeMap, HashMap};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    account::trade::{ContractType, Position},
+    exchange::symbol::Symbol,
+    utils::number::{from_decimal, to_decimal},
+};
+
+/// Currency account equity is reported in unless `BASE_CURRENCY` is set.
+pub const DEFAULT_BASE_CURRENCY: &str = "USDT";
+
+/// Returns the asset the profit of a position is settled in, the quote asset for linear contracts
+/// and the base asset for inverse contracts, `None` if the symbol can't be parsed.
+
+pub fn settlement_asset(position: &Position) -> Option<String> {
+    let symbol = Symbol::from_canonical(&position.symbol).ok()?;
+
+    match position.contract_type {
+        ContractType::Linear => Some(symbol.quote),
+        ContractType::Inverse { .. } => Some(symbol.base),
+    }
+}
+
+/// Builds the cross pair symbols a quote asset can be converted to the base currency with.
+///
+/// # Arguments
+///
+/// * `quote` - The quote asset to convert from.
+/// * `base_currency` - The currency to convert to.
+///
+/// # Returns
+///
+/// The direct pair, ie. `USDCUSDT`, followed by the inverse pair, ie. `USDTUSDC`.
+
+pub fn conversion_symbols(quote: &str, base_currency: &str) -> [String; 2] {
+    [
+        format!("{quote}{base_currency}"),
+        format!("{base_currency}{quote}"),
+    ]
+}
+
+/// Finds the rate a quote asset converts to the base currency at.
+///
+/// # Arguments
+///
+/// * `quote` - The quote asset to convert from.
+/// * `base_currency` - The currency to convert to.
+/// * `last_prices` - Last prices by symbol, the direct cross pair is preferred over the inverse.
+///
+/// # Returns
+///
+/// The amount of base currency one unit of the quote asset is worth, or `None` if neither cross
+/// pair has a price.
+
+pub fn conversion_rate(
+    quote: &str,
+    base_currency: &str,
+    last_prices: &HashMap<String, f64>,
+) -> Option<f64> {
+    if quote == base_currency {
+        return Some(1.0);
+    }
+
+    let [direct, inverse] = conversion_symbols(quote, base_currency);
+
+    match last_prices.get(&direct) {
+        Some(price) if *price > 0.0 => Some(*price),
+        _ => last_prices
+            .get(&inverse)
+            .filter(|price| **price > 0.0)
+            .map(|price| 1.0 / price),
+    }
+}
+
+/// Account equity converted to a single base currency.
+///
+/// Subtotals are kept in their quote asset. Subtotals of quote assets without a conversion rate
+/// are left out of `equity` and listed in `unconverted`, so a missing rate doesn't misstate the
+/// equity silently.
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct EquityBreakdown {
+    pub base_currency: String,
+    /// Equity in the base currency, from the subtotals which could be converted.
+    pub equity: f64,
+    /// Balance and realized profit by quote asset, in the quote asset.
+    pub subtotals: BTreeMap<String, f64>,
+    /// Quote assets without a conversion rate.
+    pub unconverted: Vec<String>,
+}
+
+impl EquityBreakdown {
+    /// Converts subtotals by quote asset to the base currency.
+    ///
+    /// # Arguments
+    ///
+    /// * `base_currency` - The currency equity is reported in.
+    /// * `subtotals` - Amounts by quote asset, in the quote asset.
+    /// * `last_prices` - Last prices by symbol, used as cross rates.
+    ///
+    /// # Returns
+    ///
+    /// The `EquityBreakdown` of the subtotals.
+
+    pub fn new(
+        base_currency: &str,
+        subtotals: BTreeMap<String, f64>,
+        last_prices: &HashMap<String, f64>,
+    ) -> Self {
+        let mut equity = to_decimal(0.0);
+        let mut unconverted = vec![];
+
+        for (quote, subtotal) in &subtotals {
+            match conversion_rate(quote, base_currency, last_prices) {
+                Some(rate) => equity += to_decimal(*subtotal) * to_decimal(rate),
+                None => unconverted.push(quote.clone()),
+            }
+        }
+
+        Self {
+            base_currency: base_currency.to_string(),
+            equity: from_decimal(equity),
+            subtotals,
+            unconverted,
+        }
+    }
+}
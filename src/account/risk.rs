@@ -0,0 +1,132 @@
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
+
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+use crate::utils::{
+    number::{from_decimal, to_decimal},
+    time::{floor_mili_ts, DAY_AS_MILI},
+};
+
+/// Switch refusing new positions of an account while engaged, each account has its own switch so
+/// a breach on one account doesn't halt trading on the others.
+///
+/// The switch is engaged until a timestamp, it releases itself once the timestamp has passed.
+
+#[derive(Debug, Clone, Default)]
+pub struct KillSwitch {
+    halted_until: Arc<AtomicU64>,
+}
+
+impl KillSwitch {
+    /// Refuses new positions until a timestamp, a later halt already in place is kept.
+    ///
+    /// # Arguments
+    ///
+    /// * `until` - Timestamp in milliseconds trading resumes at.
+
+    pub fn engage_until(&self, until: u64) {
+        self.halted_until.fetch_max(until, Ordering::SeqCst);
+    }
+
+    /// Checks if new positions are refused at a timestamp.
+
+    pub fn is_engaged(&self, now: u64) -> bool {
+        now < self.halted_until.load(Ordering::SeqCst)
+    }
+}
+
+/// Maximum realized loss of an account within a UTC day.
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct DailyLossLimit {
+    /// Realized loss in USD at which trading is halted, as a positive amount.
+    pub limit_usd: f64,
+    /// Whether all open positions of the account are closed once the limit is breached.
+    pub close_positions: bool,
+}
+
+impl DailyLossLimit {
+    /// Builds a daily loss limit from settings.
+    ///
+    /// # Arguments
+    ///
+    /// * `limit_usd` - Loss limit in USD, no limit applies if empty or not a positive number.
+    /// * `close_positions` - `True` to close all positions once the limit is breached.
+    ///
+    /// # Returns
+    ///
+    /// The `DailyLossLimit`, or `None` if no limit is set.
+
+    pub fn from_settings(limit_usd: &str, close_positions: &str) -> Option<Self> {
+        let limit_usd: f64 = limit_usd.parse().ok()?;
+
+        if limit_usd <= 0.0 {
+            return None;
+        }
+
+        Some(Self {
+            limit_usd,
+            close_positions: close_positions == "True",
+        })
+    }
+}
+
+/// Realized profit of an account since the start of the current UTC day.
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DailyPnl {
+    /// Start of the UTC day the profit was realized on.
+    day_start: u64,
+    realized_pnl: Decimal,
+}
+
+impl DailyPnl {
+    /// Adds the profit of a closed trade, the tally restarts on the first trade of a new day.
+    ///
+    /// # Arguments
+    ///
+    /// * `profit` - Realized profit of the trade, negative for a loss.
+    /// * `now` - The current timestamp in milliseconds.
+
+    pub fn record(&mut self, profit: Decimal, now: u64) {
+        let day_start = day_start(now);
+
+        if day_start != self.day_start {
+            self.day_start = day_start;
+            self.realized_pnl = Decimal::ZERO;
+        }
+
+        self.realized_pnl += profit;
+    }
+
+    /// Realized profit of the day of a timestamp, `0` once that day has no trades.
+
+    pub fn realized_pnl(&self, now: u64) -> f64 {
+        if day_start(now) == self.day_start {
+            from_decimal(self.realized_pnl)
+        } else {
+            0.0
+        }
+    }
+
+    /// Loss which can still be realized before a limit is breached, never negative.
+
+    pub fn remaining_budget(&self, limit: &DailyLossLimit, now: u64) -> f64 {
+        from_decimal(to_decimal(limit.limit_usd) + to_decimal(self.realized_pnl(now))).max(0.0)
+    }
+}
+
+/// Start of the UTC day after a timestamp, when a daily loss limit resets.
+
+pub fn next_day_start(now: u64) -> u64 {
+    day_start(now) + DAY_AS_MILI
+}
+
+/// Start of the UTC day of a timestamp.
+fn day_start(now: u64) -> u64 {
+    floor_mili_ts(now, DAY_AS_MILI)
+}
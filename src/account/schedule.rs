@@ -0,0 +1,341 @@
+use std::{collections::HashMap, sync::Arc};
+
+use log::warn;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    account::{account::AccountId, trade::OrderSide},
+    storage::manager::StorageManager,
+    utils::time::{timestamp_to_string, Clock},
+};
+
+pub type ScheduledOrderId = Uuid;
+
+/// Time after its scheduled time an order which failed to be placed is retried for, in
+/// milliseconds.
+pub const SCHEDULED_ORDER_MAX_DELAY_MS: u64 = 60_000;
+
+/// A market order placed at a set time regardless of signals, ie. to enter or exit at a session
+/// open or close.
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ScheduledOrder {
+    pub id: ScheduledOrderId,
+    /// Time the order is placed at.
+    pub at_ts: u64,
+    pub symbol: String,
+    pub side: OrderSide,
+    /// Margin of the position opened by the order, in USD.
+    pub size: f64,
+    pub leverage: u32,
+    /// Account the order is placed on, the default account if `None`.
+    pub account_id: Option<AccountId>,
+    pub created_at: u64,
+}
+
+impl ScheduledOrder {
+    /// Creates a new scheduled order.
+    ///
+    /// # Arguments
+    ///
+    /// * `at_ts` - Time the order is placed at.
+    /// * `symbol` - The symbol to trade.
+    /// * `side` - The side of the order.
+    /// * `size` - Margin of the position opened by the order, in USD.
+    /// * `leverage` - Leverage of the position opened by the order.
+    /// * `account_id` - Account the order is placed on, the default account if `None`.
+    /// * `created_at` - Time the order was scheduled at.
+    ///
+    /// # Returns
+    ///
+    /// A new `ScheduledOrder`.
+
+    pub fn new(
+        at_ts: u64,
+        symbol: &str,
+        side: OrderSide,
+        size: f64,
+        leverage: u32,
+        account_id: Option<AccountId>,
+        created_at: u64,
+    ) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            at_ts,
+            symbol: symbol.to_string(),
+            side,
+            size,
+            leverage,
+            account_id,
+            created_at,
+        }
+    }
+}
+
+/// Registry of scheduled orders, due orders are read by a background task which places them.
+///
+/// Orders are persisted with the storage manager whenever they change, so they survive restarts.
+/// A due order stays pending until it's placed, orders which can't be placed are retried until
+/// they are `SCHEDULED_ORDER_MAX_DELAY_MS` late and then expire. Orders whose time passed while
+/// the bot was not running are skipped on reload rather than placed late.
+
+pub struct OrderScheduler {
+    orders: HashMap<ScheduledOrderId, ScheduledOrder>,
+    storage_manager: Arc<dyn StorageManager>,
+    clock: Arc<dyn Clock>,
+}
+
+impl OrderScheduler {
+    /// Creates an order scheduler, loading orders previously saved to storage.
+    ///
+    /// # Arguments
+    ///
+    /// * `storage_manager` - Storage the orders are persisted to.
+    /// * `clock` - Clock the order times are compared against.
+    ///
+    /// # Returns
+    ///
+    /// A new `OrderScheduler` holding the saved orders which are not yet due.
+
+    pub async fn new(storage_manager: Arc<dyn StorageManager>, clock: Arc<dyn Clock>) -> Self {
+        let saved = match storage_manager.get_scheduled_orders().await {
+            Ok(orders) => orders,
+            Err(e) => {
+                warn!("Unable to load scheduled orders, {e}");
+                vec![]
+            }
+        };
+
+        let now = clock.now();
+        let (expired, orders): (Vec<ScheduledOrder>, Vec<ScheduledOrder>) =
+            saved.into_iter().partition(|order| order.at_ts <= now);
+
+        for order in &expired {
+            warn!(
+                "Skipping scheduled {:?} order {} on {}, its time {} has passed",
+                order.side,
+                order.id,
+                order.symbol,
+                timestamp_to_string(order.at_ts)
+            );
+        }
+
+        let _self = Self {
+            orders: orders.into_iter().map(|order| (order.id, order)).collect(),
+            storage_manager,
+            clock,
+        };
+
+        if !expired.is_empty() {
+            _self.save().await;
+        }
+
+        _self
+    }
+
+    /// Registers a new scheduled order.
+    ///
+    /// # Arguments
+    ///
+    /// * `order` - The order to schedule.
+    ///
+    /// # Returns
+    ///
+    /// The scheduled order, or an error if its time has already passed.
+
+    pub async fn schedule(&mut self, order: ScheduledOrder) -> Result<ScheduledOrder, String> {
+        if order.at_ts <= self.clock.now() {
+            return Err(format!(
+                "Scheduled time {} has already passed",
+                timestamp_to_string(order.at_ts)
+            ));
+        }
+
+        self.orders.insert(order.id, order.clone());
+        self.save().await;
+
+        Ok(order)
+    }
+
+    /// Cancels a scheduled order.
+    ///
+    /// # Arguments
+    ///
+    /// * `order_id` - ID of the order to cancel.
+    ///
+    /// # Returns
+    ///
+    /// The cancelled order, or `None` if no pending order has the ID.
+
+    pub async fn cancel(&mut self, order_id: &ScheduledOrderId) -> Option<ScheduledOrder> {
+        let order = self.orders.remove(order_id);
+        if order.is_some() {
+            self.save().await;
+        }
+        order
+    }
+
+    /// Lists all pending orders, ordered by scheduled time.
+
+    pub fn orders(&self) -> Vec<ScheduledOrder> {
+        let mut orders: Vec<ScheduledOrder> = self.orders.values().cloned().collect();
+        orders.sort_by_key(|order| order.at_ts);
+        orders
+    }
+
+    /// Lists the pending orders whose time has arrived, they stay pending until completed.
+    ///
+    /// # Returns
+    ///
+    /// The due orders, ordered by scheduled time.
+
+    pub fn due(&self) -> Vec<ScheduledOrder> {
+        let now = self.clock.now();
+
+        self.orders()
+            .into_iter()
+            .filter(|order| order.at_ts <= now)
+            .collect()
+    }
+
+    /// Removes an order once it has been placed.
+    ///
+    /// # Arguments
+    ///
+    /// * `order_id` - ID of the placed order.
+    ///
+    /// # Returns
+    ///
+    /// The placed order, or `None` if it was cancelled while being placed.
+
+    pub async fn complete(&mut self, order_id: &ScheduledOrderId) -> Option<ScheduledOrder> {
+        self.cancel(order_id).await
+    }
+
+    /// Removes the orders which could not be placed within `SCHEDULED_ORDER_MAX_DELAY_MS` of
+    /// their time.
+    ///
+    /// # Returns
+    ///
+    /// The expired orders, ordered by scheduled time.
+
+    pub async fn take_expired(&mut self) -> Vec<ScheduledOrder> {
+        let now = self.clock.now();
+
+        let expired: Vec<ScheduledOrder> = self
+            .orders()
+            .into_iter()
+            .filter(|order| order.at_ts + SCHEDULED_ORDER_MAX_DELAY_MS < now)
+            .collect();
+
+        if expired.is_empty() {
+            return expired;
+        }
+
+        for order in &expired {
+            self.orders.remove(&order.id);
+        }
+        self.save().await;
+
+        expired
+    }
+
+    // ---
+    // Private Methods
+    // ---
+
+    /// Persists all pending orders, failures are logged as orders are still held in memory.
+    async fn save(&self) {
+        if let Err(e) = self
+            .storage_manager
+            .save_scheduled_orders(&self.orders())
+            .await
+        {
+            warn!("Unable to save scheduled orders, {e}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use tokio::test;
+
+    use crate::{storage::memory::MemoryStorage, testutil::TestClock};
+
+    #[test]
+    async fn test_scheduled_order_fires_once() {
+        let start = 1_700_000_000_000;
+        let storage_manager: Arc<dyn StorageManager> = Arc::new(MemoryStorage::default());
+        let clock = Arc::new(TestClock::new(start));
+
+        let mut scheduler = OrderScheduler::new(storage_manager.clone(), clock.clone()).await;
+
+        let past = ScheduledOrder::new(start, "BTCUSDT", OrderSide::Buy, 100.0, 1, None, start);
+        assert!(scheduler.schedule(past).await.is_err());
+
+        let order = ScheduledOrder::new(
+            start + 1_000,
+            "BTCUSDT",
+            OrderSide::Buy,
+            100.0,
+            1,
+            None,
+            start,
+        );
+        scheduler.schedule(order.clone()).await.unwrap();
+        assert!(scheduler.due().is_empty());
+
+        clock.set(start + 1_000);
+        assert_eq!(scheduler.due(), vec![order.clone()]);
+
+        // a due order stays pending, and saved, until it's placed
+        let saved = storage_manager.get_scheduled_orders().await.unwrap();
+        assert_eq!(saved, vec![order.clone()]);
+
+        assert_eq!(scheduler.complete(&order.id).await, Some(order));
+
+        clock.set(start + 2_000);
+        assert!(scheduler.due().is_empty());
+        assert!(scheduler.orders().is_empty());
+
+        // orders whose time passed while the bot was not running are skipped on reload
+        let late = ScheduledOrder::new(
+            start + 3_000,
+            "ETHUSDT",
+            OrderSide::Sell,
+            50.0,
+            2,
+            None,
+            start,
+        );
+        scheduler.schedule(late).await.unwrap();
+
+        clock.set(start + 4_000);
+        let mut scheduler = OrderScheduler::new(storage_manager, clock.clone()).await;
+        assert!(scheduler.orders().is_empty());
+        assert!(scheduler.due().is_empty());
+
+        // orders which can't be placed are retried until they expire
+        let failing = ScheduledOrder::new(
+            start + 5_000,
+            "ETHUSDT",
+            OrderSide::Sell,
+            50.0,
+            2,
+            None,
+            start,
+        );
+        scheduler.schedule(failing.clone()).await.unwrap();
+
+        clock.set(start + 5_000 + SCHEDULED_ORDER_MAX_DELAY_MS);
+        assert!(scheduler.take_expired().await.is_empty());
+        assert_eq!(scheduler.due(), vec![failing.clone()]);
+
+        clock.set(start + 5_001 + SCHEDULED_ORDER_MAX_DELAY_MS);
+        assert_eq!(scheduler.take_expired().await, vec![failing]);
+        assert!(scheduler.due().is_empty());
+    }
+}
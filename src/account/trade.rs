@@ -1,11 +1,16 @@
 use std::fmt::Display;
 
+use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
 
 use crate::{
+    account::account::TAKER_FEE_RATE,
     strategy::signal::SignalMessage,
     strategy::strategy::StrategyId,
-    utils::time::{generate_ts, timestamp_to_string},
+    utils::{
+        number::{from_decimal, to_decimal},
+        time::{deserialize_timestamp, generate_ts},
+    },
 };
 
 use uuid::Uuid;
@@ -44,6 +49,103 @@ impl Display for OrderSide {
     }
 }
 
+/// How a futures contract is sized and settled.
+///
+/// Linear contracts, ie. USDT-margined, are sized in the base asset and settle profit in the quote
+/// asset. Inverse contracts, ie. COIN-margined, are sized in contracts worth a fixed amount of the
+/// quote asset and settle profit in the base asset.
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+pub enum ContractType {
+    #[default]
+    Linear,
+    Inverse {
+        /// Value of one contract in the quote asset, ie. `100` USD for BTC on Binance.
+        contract_size: f64,
+    },
+}
+
+impl ContractType {
+    /// Checks the contract type can size a position, inverse contracts need a positive contract
+    /// size.
+    ///
+    /// # Returns
+    ///
+    /// `Ok(())` if the contract type is valid, otherwise an error message.
+
+    pub fn validate(&self) -> Result<(), String> {
+        match self {
+            ContractType::Inverse { contract_size }
+                if !contract_size.is_finite() || *contract_size <= 0.0 =>
+            {
+                Err(format!(
+                    "Contract size must be positive, got {contract_size}"
+                ))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Calculates the quantity of a position.
+    ///
+    /// # Arguments
+    ///
+    /// * `notional` - Value of the position in the quote asset, the margin times the leverage.
+    /// * `price` - The price the position is opened at.
+    ///
+    /// # Returns
+    ///
+    /// The quantity in the base asset for linear contracts, in contracts for inverse contracts.
+
+    pub fn quantity(&self, notional: f64, price: f64) -> f64 {
+        match self {
+            ContractType::Linear => notional / price,
+            ContractType::Inverse { contract_size } => notional / contract_size,
+        }
+    }
+
+    /// Calculates the profit of closing a position.
+    ///
+    /// # Arguments
+    ///
+    /// * `order_side` - The side of the position.
+    /// * `quantity` - The quantity of the position, as returned by `quantity`.
+    /// * `open_price` - The price the position was opened at.
+    /// * `close_price` - The price the position is closed at.
+    ///
+    /// # Returns
+    ///
+    /// The profit in the quote asset for linear contracts, in the base asset for inverse contracts.
+
+    pub fn profit(
+        &self,
+        order_side: OrderSide,
+        quantity: f64,
+        open_price: f64,
+        close_price: f64,
+    ) -> Decimal {
+        let quantity = to_decimal(quantity);
+        let open_price = to_decimal(open_price);
+        let close_price = to_decimal(close_price);
+
+        let long_profit = match self {
+            ContractType::Linear => (close_price - open_price) * quantity,
+            ContractType::Inverse { contract_size } => {
+                if open_price.is_zero() || close_price.is_zero() {
+                    return Decimal::ZERO;
+                }
+                let value = quantity * to_decimal(*contract_size);
+                value / open_price - value / close_price
+            }
+        };
+
+        match order_side {
+            OrderSide::Buy => long_profit,
+            OrderSide::Sell => -long_profit,
+        }
+    }
+}
+
 /// Struct representing a trading position.
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct Position {
@@ -53,8 +155,10 @@ pub struct Position {
     pub symbol: String,
     /// The side of the order (Buy or Sell).
     pub order_side: OrderSide,
-    /// The time when the position was opened.
-    pub open_time: String,
+    /// The time when the position was opened in milliseconds, positions saved with a date string
+    /// are migrated when loaded.
+    #[serde(default, deserialize_with = "deserialize_timestamp")]
+    pub open_time: u64,
     /// The price at which the position was opened.
     pub open_price: f64,
     /// The quantity of the asset in the position.
@@ -67,6 +171,18 @@ pub struct Position {
     pub strategy_id: Option<StrategyId>,
     /// The optional stop loss price for the position.
     pub stop_loss: Option<f64>,
+    /// The optional take profit price for the position.
+    #[serde(default)]
+    pub take_profit: Option<f64>,
+    /// How the contract is sized and settled, positions saved before it was recorded are linear.
+    #[serde(default)]
+    pub contract_type: ContractType,
+    /// Whether the position was opened outside the bot and adopted from the exchange.
+    #[serde(default)]
+    pub adopted: bool,
+    /// ID of the exchange order which opened the position, `None` for simulated positions.
+    #[serde(default)]
+    pub order_id: Option<u64>,
 }
 
 impl Position {
@@ -93,7 +209,7 @@ impl Position {
         stop_loss: Option<f64>,
     ) -> Self {
         let total = margin_usd * leverage as f64;
-        let qty = total / open_price;
+        let qty = ContractType::Linear.quantity(total, open_price);
 
         Self {
             id: Uuid::new_v4(),
@@ -101,22 +217,163 @@ impl Position {
             order_side,
             open_price,
             stop_loss,
+            take_profit: None,
             quantity: qty,
             margin_usd,
             leverage,
             strategy_id: None,
-            open_time: timestamp_to_string(generate_ts()),
+            open_time: generate_ts(),
+            contract_type: ContractType::Linear,
+            adopted: false,
+            order_id: None,
         }
     }
 
+    /// Sets how the contract of the position is sized and settled, the quantity is resized from
+    /// the margin and leverage for the contract type.
+    ///
+    /// # Arguments
+    ///
+    /// * `contract_type` - The contract type of the symbol.
+
+    pub fn set_contract_type(&mut self, contract_type: ContractType) {
+        self.contract_type = contract_type;
+        self.quantity =
+            contract_type.quantity(self.margin_usd * self.leverage as f64, self.open_price);
+    }
+
     /// Sets the stop loss price for the position.
     ///
     /// # Arguments
     ///
     /// * `stop_loss` - The optional stop loss price for the position.
+    ///
+    /// # Returns
+    ///
+    /// `Ok(())` if the stop loss is set, otherwise an error message if it's on the wrong side of
+    /// the open price, which leaves the stop loss unchanged.
 
-    pub fn set_stop_loss(&mut self, stop_loss: Option<f64>) {
-        self.stop_loss = stop_loss
+    pub fn set_stop_loss(&mut self, stop_loss: Option<f64>) -> Result<(), String> {
+        if let Some(stop_loss) = stop_loss {
+            Position::validate_stop_loss(self.order_side, self.open_price, stop_loss)?;
+        }
+        self.stop_loss = stop_loss;
+        Ok(())
+    }
+
+    /// Sets the take profit price for the position.
+    ///
+    /// # Arguments
+    ///
+    /// * `take_profit` - The optional take profit price for the position.
+    ///
+    /// # Returns
+    ///
+    /// `Ok(())` if the take profit is set, otherwise an error message if it's on the wrong side
+    /// of the open price, which leaves the take profit unchanged.
+
+    pub fn set_take_profit(&mut self, take_profit: Option<f64>) -> Result<(), String> {
+        if let Some(take_profit) = take_profit {
+            Position::validate_take_profit(self.order_side, self.open_price, take_profit)?;
+        }
+        self.take_profit = take_profit;
+        Ok(())
+    }
+
+    /// Checks a stop loss is below the open price of a long or above the open price of a short,
+    /// a stop loss on the other side would trigger immediately or never.
+    ///
+    /// # Arguments
+    ///
+    /// * `order_side` - The side of the position.
+    /// * `open_price` - The price the position is opened at.
+    /// * `stop_loss` - The stop loss price.
+    ///
+    /// # Returns
+    ///
+    /// `Ok(())` if the stop loss is valid, otherwise an error message describing where it must be.
+
+    pub fn validate_stop_loss(
+        order_side: OrderSide,
+        open_price: f64,
+        stop_loss: f64,
+    ) -> Result<(), String> {
+        match order_side {
+            OrderSide::Buy if stop_loss >= open_price => Err(format!(
+                "Stop loss {stop_loss} of a long must be below the open price {open_price}"
+            )),
+            OrderSide::Sell if stop_loss <= open_price => Err(format!(
+                "Stop loss {stop_loss} of a short must be above the open price {open_price}"
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Checks a take profit is above the open price of a long or below the open price of a short.
+    ///
+    /// # Arguments
+    ///
+    /// * `order_side` - The side of the position.
+    /// * `open_price` - The price the position is opened at.
+    /// * `take_profit` - The take profit price.
+    ///
+    /// # Returns
+    ///
+    /// `Ok(())` if the take profit is valid, otherwise an error message describing where it must
+    /// be.
+
+    pub fn validate_take_profit(
+        order_side: OrderSide,
+        open_price: f64,
+        take_profit: f64,
+    ) -> Result<(), String> {
+        match order_side {
+            OrderSide::Buy if take_profit <= open_price => Err(format!(
+                "Take profit {take_profit} of a long must be above the open price {open_price}"
+            )),
+            OrderSide::Sell if take_profit >= open_price => Err(format!(
+                "Take profit {take_profit} of a short must be below the open price {open_price}"
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Checks whether a price reached the stop loss or take profit of the position.
+    ///
+    /// # Arguments
+    ///
+    /// * `price` - The last price of the symbol.
+    ///
+    /// # Returns
+    ///
+    /// Why the position must be closed at the price, or `None` if it's kept open.
+
+    pub fn exit_reason(&self, price: f64) -> Option<String> {
+        let is_long = self.order_side == OrderSide::Buy;
+
+        if let Some(stop_loss) = self.stop_loss {
+            if (is_long && price <= stop_loss) || (!is_long && price >= stop_loss) {
+                return Some(format!("Stop loss {stop_loss} reached"));
+            }
+        }
+
+        if let Some(take_profit) = self.take_profit {
+            if (is_long && price >= take_profit) || (!is_long && price <= take_profit) {
+                return Some(format!("Take profit {take_profit} reached"));
+            }
+        }
+
+        None
+    }
+
+    /// Returns how long the position has been held at a time, in milliseconds.
+    ///
+    /// # Arguments
+    ///
+    /// * `now` - The current timestamp in milliseconds.
+
+    pub fn age(&self, now: u64) -> u64 {
+        now.saturating_sub(self.open_time)
     }
 
     /// Sets the strategy ID associated with the position.
@@ -130,19 +387,45 @@ impl Position {
     }
 }
 
+/// A single order filled for a trade, ie. a row of the fills export used for accounting.
+
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct Fill {
+    pub timestamp: u64,
+    pub symbol: String,
+    pub side: OrderSide,
+    pub price: f64,
+    pub quantity: f64,
+    /// Fee estimated at `TAKER_FEE_RATE`, trades don't record the fees charged by the exchange.
+    pub fee: f64,
+    /// Profit realized by the fill, only closing fills realize profit.
+    pub realized_pnl: f64,
+}
+
 /// Struct representing a trading transaction.
 #[derive(Clone, Serialize, Deserialize, Debug)]
 pub struct TradeTx {
     /// The unique identifier of the trade transaction.
     pub id: Uuid,
-    pub profit: f64,
-    /// The time when the position was closed.
-    pub close_time: String,
+    /// Realized profit, carried as a decimal so the profit of many trades adds up exactly, and
+    /// serialized as a float. Prices and quantities are kept as the floats the exchange reports.
+    #[serde(with = "rust_decimal::serde::float")]
+    pub profit: Decimal,
+    /// The time when the position was closed in milliseconds, trades saved with a date string are
+    /// migrated when loaded.
+    #[serde(default, deserialize_with = "deserialize_timestamp")]
+    pub close_time: u64,
     /// The price at which the position was closed.
     pub close_price: f64,
     /// The position associated with the trade transaction.
     pub position: Position,
     pub meta: Option<TradeTxMeta>,
+    /// Whether the position was force closed by the exchange liquidating it.
+    #[serde(default)]
+    pub liquidated: bool,
+    /// Why the position was closed, if it was closed by the bot rather than by a signal.
+    #[serde(default)]
+    pub close_reason: Option<String>,
 }
 impl TradeTx {
     /// Creates a new trade transaction with the given parameters.
@@ -163,12 +446,68 @@ impl TradeTx {
             id: Uuid::new_v4(),
             close_price,
             profit,
-            close_time: timestamp_to_string(close_time),
+            close_time,
             position,
             meta: None,
+            liquidated: false,
+            close_reason: None,
         }
     }
 
+    /// Returns the profit of the trade in the quote asset, the profit of inverse contracts is
+    /// settled in the base asset and converted at the close price.
+
+    pub fn quote_profit(&self) -> Decimal {
+        match self.position.contract_type {
+            ContractType::Linear => self.profit,
+            ContractType::Inverse { .. } => self.profit * to_decimal(self.close_price),
+        }
+    }
+
+    /// Returns how long the position was held before it was closed, in milliseconds.
+
+    pub fn duration(&self) -> u64 {
+        self.position.age(self.close_time)
+    }
+
+    /// Returns the fills of the trade, the order opening the position followed by the order
+    /// closing it on the opposite side.
+
+    pub fn fills(&self) -> [Fill; 2] {
+        let position = &self.position;
+        let fill = |timestamp: u64, side: OrderSide, price: f64, realized_pnl: f64| Fill {
+            timestamp,
+            symbol: position.symbol.clone(),
+            side,
+            price,
+            quantity: position.quantity,
+            fee: from_decimal(
+                to_decimal(price) * to_decimal(position.quantity) * to_decimal(TAKER_FEE_RATE),
+            ),
+            realized_pnl,
+        };
+
+        let close_side = match position.order_side {
+            OrderSide::Buy => OrderSide::Sell,
+            OrderSide::Sell => OrderSide::Buy,
+        };
+
+        [
+            fill(
+                position.open_time,
+                position.order_side,
+                position.open_price,
+                0.0,
+            ),
+            fill(
+                self.close_time,
+                close_side,
+                self.close_price,
+                from_decimal(self.profit),
+            ),
+        ]
+    }
+
     pub fn add_signal(&mut self, signal: &SignalMessage) {
         if let Some(meta) = &mut self.meta {
             meta.signals.push(signal.clone())
@@ -194,13 +533,13 @@ impl TradeTx {
     //     }
     // }
 
-    pub fn calc_profit(close_price: f64, position: &Position) -> f64 {
-        let total_open_usd = position.open_price * position.quantity;
-        let total_close_usd = close_price * position.quantity;
-        match position.order_side {
-            OrderSide::Buy => total_close_usd - total_open_usd,
-            OrderSide::Sell => total_open_usd - total_close_usd,
-        }
+    pub fn calc_profit(close_price: f64, position: &Position) -> Decimal {
+        position.contract_type.profit(
+            position.order_side,
+            position.quantity,
+            position.open_price,
+            close_price,
+        )
     }
 }
 
@@ -236,14 +575,69 @@ mod test {
 
         // Assert that other fields have default values
         assert!(position.strategy_id.is_none());
-        assert!(string_to_timestamp(&position.open_time).unwrap() <= generate_ts());
+        assert!(position.open_time <= generate_ts());
     }
 
     #[test]
     async fn position_set_stop_loss() {
         let mut position = Position::new("ETHUSD", 2000.0, OrderSide::Sell, 500.0, 5, None);
-        position.set_stop_loss(Some(1900.0));
-        assert_eq!(position.stop_loss, Some(1900.0));
+        position.set_stop_loss(Some(2100.0)).unwrap();
+        assert_eq!(position.stop_loss, Some(2100.0));
+    }
+
+    #[test]
+    async fn position_rejects_exits_on_wrong_side() {
+        let mut long = Position::new("ETHUSD", 2000.0, OrderSide::Buy, 500.0, 5, None);
+        let mut short = Position::new("ETHUSD", 2000.0, OrderSide::Sell, 500.0, 5, None);
+
+        // stop losses above a long entry or below a short entry are rejected
+        assert!(long.set_stop_loss(Some(2100.0)).is_err());
+        assert!(long.set_stop_loss(Some(2000.0)).is_err());
+        assert!(short.set_stop_loss(Some(1900.0)).is_err());
+        assert_eq!((long.stop_loss, short.stop_loss), (None, None));
+
+        // take profits are the inverse
+        assert!(long.set_take_profit(Some(1900.0)).is_err());
+        assert!(short.set_take_profit(Some(2100.0)).is_err());
+        assert_eq!((long.take_profit, short.take_profit), (None, None));
+
+        long.set_stop_loss(Some(1900.0)).unwrap();
+        long.set_take_profit(Some(2100.0)).unwrap();
+        short.set_stop_loss(Some(2100.0)).unwrap();
+        short.set_take_profit(Some(1900.0)).unwrap();
+        assert_eq!(
+            (long.stop_loss, long.take_profit),
+            (Some(1900.0), Some(2100.0))
+        );
+        assert_eq!(
+            (short.stop_loss, short.take_profit),
+            (Some(2100.0), Some(1900.0))
+        );
+
+        // clearing is always allowed
+        assert!(long.set_stop_loss(None).is_ok());
+        assert_eq!(long.stop_loss, None);
+    }
+
+    #[test]
+    async fn position_exit_reason() {
+        let mut long = Position::new("ETHUSD", 2000.0, OrderSide::Buy, 500.0, 5, Some(1900.0));
+        let mut short = Position::new("ETHUSD", 2000.0, OrderSide::Sell, 500.0, 5, Some(2100.0));
+        long.set_take_profit(Some(2100.0)).unwrap();
+        short.set_take_profit(Some(1900.0)).unwrap();
+
+        for price in [1950.0, 2000.0, 2050.0] {
+            assert_eq!(long.exit_reason(price), None);
+            assert_eq!(short.exit_reason(price), None);
+        }
+
+        assert!(long.exit_reason(1900.0).unwrap().starts_with("Stop loss"));
+        assert!(long.exit_reason(2150.0).unwrap().starts_with("Take profit"));
+        assert!(short.exit_reason(2150.0).unwrap().starts_with("Stop loss"));
+        assert!(short
+            .exit_reason(1900.0)
+            .unwrap()
+            .starts_with("Take profit"));
     }
 
     #[test]
@@ -264,7 +658,7 @@ mod test {
         let trade_tx = TradeTx::new(close_price, close_time, position.clone());
 
         assert_eq!(trade_tx.close_price, close_price);
-        assert_eq!(trade_tx.close_time, timestamp_to_string(close_time));
+        assert_eq!(trade_tx.close_time, close_time);
         assert_eq!(trade_tx.position, position);
 
         // Assert that trade_tx has a unique ID
@@ -274,7 +668,7 @@ mod test {
         // Assert that calc_profit calculates correctly
         let expected_profit = (close_price - position.open_price) * position.quantity;
         assert_eq!(
-            TradeTx::calc_profit(close_price, &position),
+            from_decimal(TradeTx::calc_profit(close_price, &position)),
             expected_profit
         );
     }
@@ -285,15 +679,68 @@ mod test {
             id: Uuid::new_v4(),
             symbol: "BTCUSD".to_string(),
             order_side: OrderSide::Buy,
-            open_time: "2021-01-01T00:00:00Z".to_string(),
+            open_time: 1_609_459_200_000,
             open_price: 50000.0,
             quantity: 0.0,
             margin_usd: 0.0,
             leverage: 10,
             strategy_id: None,
             stop_loss: None,
+            take_profit: None,
+            contract_type: ContractType::Linear,
+            adopted: false,
+            order_id: None,
         };
         let trade_tx_zero_qty = TradeTx::new(51000.0, generate_ts(), position_zero_qty);
-        assert_eq!(trade_tx_zero_qty.profit, 0.0);
+        assert!(trade_tx_zero_qty.profit.is_zero());
+    }
+
+    #[test]
+    async fn test_inverse_contract_size_and_profit() {
+        let contract_type = ContractType::Inverse {
+            contract_size: 100.0,
+        };
+
+        let linear = Position::new("BTCUSD", 50000.0, OrderSide::Buy, 1000.0, 10, None);
+        let mut inverse = linear.clone();
+        inverse.set_contract_type(contract_type);
+
+        // linear quantity is in BTC, inverse quantity in contracts of 100 USD
+        assert_eq!(linear.quantity, 0.2);
+        assert_eq!(inverse.quantity, 100.0);
+
+        // linear profit is in USD, inverse profit in BTC
+        let linear_profit = from_decimal(TradeTx::calc_profit(55000.0, &linear));
+        let inverse_profit = from_decimal(TradeTx::calc_profit(55000.0, &inverse));
+        assert_eq!(linear_profit, 1000.0);
+        assert!((inverse_profit - 10000.0 * (1.0 / 50000.0 - 1.0 / 55000.0)).abs() < 1e-12);
+
+        // both are worth the same in USD at the close price
+        assert!((inverse_profit * 55000.0 - linear_profit).abs() < 1e-6);
+
+        // the inverse loss of a long is larger in BTC than its gain on the same move up
+        let inverse_loss = from_decimal(TradeTx::calc_profit(45000.0, &inverse));
+        assert!(inverse_loss < 0.0 && inverse_loss.abs() > inverse_profit);
+
+        // shorts take the opposite side
+        let mut short = Position::new("BTCUSD", 50000.0, OrderSide::Sell, 1000.0, 10, None);
+        short.set_contract_type(contract_type);
+        assert_eq!(
+            from_decimal(TradeTx::calc_profit(55000.0, &short)),
+            -inverse_profit
+        );
+
+        // inverse profit is aggregated in the quote asset
+        let linear_tx = TradeTx::new(55000.0, generate_ts(), linear);
+        let inverse_tx = TradeTx::new(55000.0, generate_ts(), inverse);
+        assert_eq!(linear_tx.quote_profit(), linear_tx.profit);
+        assert!((from_decimal(inverse_tx.quote_profit()) - linear_profit).abs() < 1e-6);
+
+        // contracts without a positive size can't size a position
+        assert!(contract_type.validate().is_ok());
+        assert!(ContractType::Linear.validate().is_ok());
+        for contract_size in [0.0, -100.0, f64::NAN] {
+            assert!(ContractType::Inverse { contract_size }.validate().is_err());
+        }
     }
 }
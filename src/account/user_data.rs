@@ -0,0 +1,315 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    exchange::types::{ApiError, ApiResult},
+    utils::number::parse_f64_from_value,
+};
+
+use super::trade::OrderSide;
+
+/// Account and order updates pushed by an exchange on its user data stream.
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum UserDataEvent {
+    OrderUpdate(OrderUpdate),
+    AccountUpdate(AccountUpdate),
+}
+
+/// Update to an order placed by the account, ie. an order being filled.
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct OrderUpdate {
+    pub symbol: String,
+    pub order_id: u64,
+    pub order_side: OrderSide,
+    /// Exchange order status, eg. `NEW`, `PARTIALLY_FILLED` or `FILLED`.
+    pub status: String,
+    pub avg_price: f64,
+    pub filled_qty: f64,
+    pub realized_profit: f64,
+    pub reduce_only: bool,
+    /// Whether the order was placed by the exchange to liquidate or auto-deleverage a position.
+    pub liquidation: bool,
+    pub trade_time: u64,
+}
+
+impl OrderUpdate {
+    /// Returns `true` if any quantity of the order has been filled.
+
+    pub fn is_filled(&self) -> bool {
+        matches!(self.status.as_str(), "FILLED" | "PARTIALLY_FILLED") && self.filled_qty > 0.0
+    }
+}
+
+/// Update to the balances and positions of the account.
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AccountUpdate {
+    pub balances: Vec<BalanceUpdate>,
+    pub positions: Vec<PositionUpdate>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BalanceUpdate {
+    pub asset: String,
+    pub wallet_balance: f64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PositionUpdate {
+    pub symbol: String,
+    /// Signed position amount, negative for short positions.
+    pub amount: f64,
+    pub entry_price: f64,
+    pub unrealized_pnl: f64,
+}
+
+impl UserDataEvent {
+    /// Parses an event received on the Binance futures user data stream.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - The decoded JSON event.
+    ///
+    /// # Returns
+    ///
+    /// The parsed event, `None` for event types which don't update the account, or a parsing
+    /// error if the event is malformed.
+
+    pub fn from_binance_value(value: &Value) -> ApiResult<Option<Self>> {
+        let event_type = value
+            .get("e")
+            .and_then(|e| e.as_str())
+            .ok_or("Missing 'e' key from user data event")?;
+
+        match event_type {
+            "ORDER_TRADE_UPDATE" => {
+                let order = value
+                    .get("o")
+                    .ok_or("Missing 'o' key from ORDER_TRADE_UPDATE event")?;
+
+                let order_side = match order.get("S").and_then(|s| s.as_str()) {
+                    Some("BUY") => OrderSide::Buy,
+                    Some("SELL") => OrderSide::Sell,
+                    other => {
+                        return Err(ApiError::Parsing(format!(
+                            "Unknown order side in ORDER_TRADE_UPDATE event: {other:?}"
+                        )))
+                    }
+                };
+
+                // forced closes are identified by the client order ID prefix the exchange uses
+                let client_order_id = order.get("c").and_then(|c| c.as_str()).unwrap_or("");
+                let liquidation = client_order_id.starts_with("autoclose-")
+                    || client_order_id.starts_with("adl_autoclose")
+                    || order.get("o").and_then(|o| o.as_str()) == Some("LIQUIDATION");
+
+                Ok(Some(UserDataEvent::OrderUpdate(OrderUpdate {
+                    symbol: parse_str("s", order)?,
+                    order_id: parse_u64("i", order)?,
+                    order_side,
+                    status: parse_str("X", order)?,
+                    avg_price: parse_f64_from_value("ap", order)?,
+                    filled_qty: parse_f64_from_value("z", order)?,
+                    realized_profit: parse_f64_from_value("rp", order)?,
+                    reduce_only: order.get("R").and_then(|r| r.as_bool()).unwrap_or(false),
+                    liquidation,
+                    trade_time: parse_u64("T", order)?,
+                })))
+            }
+            "ACCOUNT_UPDATE" => {
+                let account = value
+                    .get("a")
+                    .ok_or("Missing 'a' key from ACCOUNT_UPDATE event")?;
+
+                let mut balances = vec![];
+                for balance in parse_array("B", account)? {
+                    balances.push(BalanceUpdate {
+                        asset: parse_str("a", balance)?,
+                        wallet_balance: parse_f64_from_value("wb", balance)?,
+                    });
+                }
+
+                let mut positions = vec![];
+                for position in parse_array("P", account)? {
+                    positions.push(PositionUpdate {
+                        symbol: parse_str("s", position)?,
+                        amount: parse_f64_from_value("pa", position)?,
+                        entry_price: parse_f64_from_value("ep", position)?,
+                        unrealized_pnl: parse_f64_from_value("up", position)?,
+                    });
+                }
+
+                Ok(Some(UserDataEvent::AccountUpdate(AccountUpdate {
+                    balances,
+                    positions,
+                })))
+            }
+            _ => Ok(None),
+        }
+    }
+}
+
+fn parse_str(key: &str, value: &Value) -> ApiResult<String> {
+    value
+        .get(key)
+        .and_then(|val| val.as_str())
+        .map(|val| val.to_string())
+        .ok_or_else(|| ApiError::Parsing(format!("Missing '{key}' key from user data event")))
+}
+
+fn parse_u64(key: &str, value: &Value) -> ApiResult<u64> {
+    value
+        .get(key)
+        .and_then(|val| val.as_u64())
+        .ok_or_else(|| ApiError::Parsing(format!("Missing '{key}' key from user data event")))
+}
+
+fn parse_array<'a>(key: &str, value: &'a Value) -> ApiResult<&'a Vec<Value>> {
+    match value.get(key) {
+        Some(val) => val
+            .as_array()
+            .ok_or_else(|| ApiError::Parsing(format!("Invalid '{key}' key in user data event"))),
+        None => Ok(&EMPTY),
+    }
+}
+
+/// Used for array keys which are omitted from an event, ie. no position updates.
+static EMPTY: Vec<Value> = Vec::new();
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    use serde_json::json;
+    use tokio::test;
+
+    use crate::{
+        account::account::Account,
+        exchange::{api::ExchangeApi, mock::MockExchangeApi},
+    };
+
+    fn order_trade_update() -> Value {
+        json!({
+            "e": "ORDER_TRADE_UPDATE",
+            "E": 1568879465651u64,
+            "T": 1568879465650u64,
+            "o": {
+                "s": "BTCUSDT",
+                "c": "TEST",
+                "S": "BUY",
+                "o": "MARKET",
+                "f": "GTC",
+                "q": "0.500",
+                "p": "0",
+                "ap": "101.50",
+                "sp": "0",
+                "x": "TRADE",
+                "X": "FILLED",
+                "i": 8886774,
+                "l": "0.500",
+                "z": "0.500",
+                "L": "101.50",
+                "N": "USDT",
+                "n": "0.02030000",
+                "T": 1568879465650u64,
+                "t": 1,
+                "b": "0",
+                "a": "0",
+                "m": false,
+                "R": false,
+                "wt": "CONTRACT_PRICE",
+                "ot": "MARKET",
+                "ps": "BOTH",
+                "cp": false,
+                "rp": "0"
+            }
+        })
+    }
+
+    #[test]
+    async fn test_parse_order_trade_update() {
+        let event = UserDataEvent::from_binance_value(&order_trade_update())
+            .unwrap()
+            .unwrap();
+
+        let order = match event.clone() {
+            UserDataEvent::OrderUpdate(order) => order,
+            other => panic!("Expected order update, got {other:?}"),
+        };
+        assert_eq!(order.symbol, "BTCUSDT");
+        assert_eq!(order.order_id, 8886774);
+        assert_eq!(order.order_side, OrderSide::Buy);
+        assert_eq!(order.avg_price, 101.5);
+        assert_eq!(order.filled_qty, 0.5);
+        assert!(order.is_filled());
+
+        let exchange_api: Arc<dyn ExchangeApi> = Arc::new(MockExchangeApi::default());
+        let mut account = Account::new(exchange_api, false, true).await;
+        let position = account
+            .open_position("BTCUSDT", 50.0, 1, OrderSide::Buy, 100.0, None, None)
+            .await
+            .unwrap();
+        let (position_id, order_id) = (position.id, position.order_id.unwrap());
+
+        // a fill of another order on the same symbol and side doesn't touch the position
+        let mut value = order_trade_update();
+        value["o"]["i"] = json!(order_id + 1);
+        let event = UserDataEvent::from_binance_value(&value).unwrap().unwrap();
+        account.handle_user_data_event(event).await;
+        assert_eq!(
+            account.get_position(&position_id).unwrap().open_price,
+            100.0
+        );
+
+        // fill updates entry price and quantity of the position opened by the order
+        value["o"]["i"] = json!(order_id);
+        let event = UserDataEvent::from_binance_value(&value).unwrap().unwrap();
+        account.handle_user_data_event(event).await;
+
+        let position = account.get_position(&position_id).unwrap();
+        assert_eq!(position.open_price, 101.5);
+        assert_eq!(position.quantity, 0.5);
+
+        // events which don't update the account are skipped
+        let event = UserDataEvent::from_binance_value(&json!({ "e": "listenKeyExpired" }));
+        assert!(matches!(event, Ok(None)));
+    }
+
+    #[test]
+    async fn test_parse_liquidation_order_update() {
+        let mut value = order_trade_update();
+        value["o"]["c"] = json!("autoclose-1568879465650");
+        value["o"]["S"] = json!("SELL");
+        value["o"]["ap"] = json!("90.00");
+        value["o"]["R"] = json!(true);
+
+        let event = UserDataEvent::from_binance_value(&value).unwrap().unwrap();
+
+        match &event {
+            UserDataEvent::OrderUpdate(order) => assert!(order.liquidation),
+            other => panic!("Expected order update, got {other:?}"),
+        };
+
+        let exchange_api: Arc<dyn ExchangeApi> = Arc::new(MockExchangeApi::default());
+        let mut account = Account::new(exchange_api, false, true).await;
+        let position_id = account
+            .open_position("BTCUSDT", 50.0, 1, OrderSide::Buy, 100.0, None, None)
+            .await
+            .unwrap()
+            .id;
+
+        // liquidated position is removed and the forced close is recorded
+        let trade = account.handle_user_data_event(event).await.unwrap();
+        assert!(trade.liquidated);
+        assert_eq!(trade.position.id, position_id);
+        assert_eq!(trade.close_price, 90.0);
+        assert!(account.get_position(&position_id).is_none());
+
+        let trades = account.trades();
+        assert_eq!(trades.len(), 1);
+        assert!(trades[0].liquidated);
+    }
+}
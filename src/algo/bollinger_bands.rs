@@ -3,6 +3,7 @@ use crate::market::kline::Kline;
 use crate::market::trade::Trade;
 use crate::strategy::{
     algorithm::Algorithm,
+    indicator::{AlgoContext, Indicator},
     types::{AlgoError, AlgoEvalResult},
 };
 use crate::utils::number::parse_usize_from_value;
@@ -29,64 +30,30 @@ impl BollingerBands {
         })
     }
 
-    fn calculate_sma(&self) -> f64 {
-        if self.data_points.len() < self.period {
-            return 0.0; // Not enough data
-        }
-        self.data_points
-            .iter()
-            .rev()
-            .take(self.period)
-            .map(|k| k.close)
-            .sum::<f64>()
-            / self.period as f64
-    }
-
-    fn calculate_std_dev(&self, sma: f64) -> f64 {
-        if self.data_points.len() < self.period {
-            return 0.0;
-        }
-        let variance: f64 = self
-            .data_points
-            .iter()
-            .rev()
-            .take(self.period)
-            .map(|k| {
-                let diff = k.close - sma;
-                diff * diff
-            })
-            .sum::<f64>()
-            / self.period as f64;
-
-        variance.sqrt()
-    }
-
-    fn calculate_bollinger_bands(&self) -> (f64, f64, f64) {
-        let sma = self.calculate_sma();
-        let std_dev = self.calculate_std_dev(sma);
+    /// Upper, middle and lower bands at the evaluated kline, `None` until the indicators are
+    /// warmed up.
+    fn bollinger_bands(&self, ctx: &AlgoContext) -> Option<(f64, f64, f64)> {
+        let sma = ctx.indicator(Indicator::Sma(self.period))?;
+        let std_dev = ctx.indicator(Indicator::StdDev(self.period))?;
         let upper_band = sma + std_dev * self.multiplier;
         let lower_band = sma - std_dev * self.multiplier;
 
-        (upper_band, sma, lower_band)
+        Some((upper_band, sma, lower_band))
     }
 }
 
 impl Algorithm for BollingerBands {
-    fn evaluate(&mut self, kline: Kline, trades: &[Trade]) -> AlgoEvalResult {
+    fn evaluate(&mut self, kline: Kline, trades: &[Trade], ctx: &AlgoContext) -> AlgoEvalResult {
         self.data_points.push(kline.clone());
 
-        let (upper_band, _middle_band, lower_band) = self.calculate_bollinger_bands();
-
         // Example trading logic based on Bollinger Bands
-        let result = if kline.close > upper_band {
+        let result = match self.bollinger_bands(ctx) {
             // Price is above the upper band - potential sell signal (overbought condition)
-            AlgoEvalResult::Sell
-        } else if kline.close < lower_band {
+            Some((upper_band, _, _)) if kline.close > upper_band => AlgoEvalResult::Sell,
             // Price is below the lower band - potential buy signal (oversold condition)
-            AlgoEvalResult::Buy
-        } else {
-            // Price is within the bands - no clear signal
-            AlgoEvalResult::Ignore
+            Some((_, _, lower_band)) if kline.close < lower_band => AlgoEvalResult::Buy,
+            // Price is within the bands, or the bands aren't warmed up - no clear signal
+            _ => AlgoEvalResult::Ignore,
         };
 
         self.clean_data_points();
@@ -103,6 +70,10 @@ impl Algorithm for BollingerBands {
         &self.params
     }
 
+    fn indicators(&self) -> Vec<Indicator> {
+        vec![Indicator::Sma(self.period), Indicator::StdDev(self.period)]
+    }
+
     fn set_params(&mut self, params: Value) -> Result<(), AlgoError> {
         if let Ok(period) = parse_usize_from_value("period", &params) {
             self.period = period
@@ -1,4 +1,8 @@
-use std::time::Duration;
+use std::{
+    collections::HashMap,
+    sync::{Arc, OnceLock, RwLock},
+    time::Duration,
+};
 
 use serde_json::Value;
 
@@ -21,80 +25,102 @@ use super::{
     volume_continuation_reversal::VolumeContinuationReversal, volume_profile::VolumeProfile,
 };
 
+/// Factory constructing an algorithm from its parameters, registered by name with `AlgoBuilder`.
+pub type AlgoFactory = Arc<dyn Fn(Value) -> Result<Box<dyn Algorithm>, AlgoError> + Send + Sync>;
+
+/// Registry of algorithm factories by name, populated with the built-in algorithms on first use.
+static ALGO_REGISTRY: OnceLock<RwLock<HashMap<String, AlgoFactory>>> = OnceLock::new();
+
 /// A builder for constructing instances of algorithms based on their names and parameters.
 ///
-/// This struct provides a method to build various trading algorithm instances dynamically
-/// based on the algorithm's name, the desired interval for evaluation, and any specific
-/// parameters required by the algorithm.
+/// Algorithms are looked up by name in a registry, built-in algorithms are registered at startup
+/// and other algorithms can be added with `AlgoBuilder::register`.
 
 pub struct AlgoBuilder {}
 
 impl AlgoBuilder {
+    /// Registers an algorithm factory, replacing any algorithm already registered with the name.
+    ///
+    /// # Arguments
+    ///
+    /// * `algorithm_name` - The name strategies use to build the algorithm.
+    /// * `factory` - Constructs the algorithm from the algorithm parameters.
+
+    pub fn register<F>(algorithm_name: &str, factory: F)
+    where
+        F: Fn(Value) -> Result<Box<dyn Algorithm>, AlgoError> + Send + Sync + 'static,
+    {
+        Self::registry()
+            .write()
+            .unwrap()
+            .insert(algorithm_name.to_string(), Arc::new(factory));
+    }
+
     /// Constructs a new algorithm instance based on provided specifications.
     ///
     /// # Arguments
     ///
     /// * `algorithm_name` - A string slice representing the name of the algorithm to construct.
-    /// * `interval` - A string slice representing the interval between k-lines for the algorithm's operation.
     /// * `algorithm_params` - A `Value` containing any specific parameters required by the algorithm.
     ///
     /// # Returns
     ///
     /// A `Result` containing the constructed algorithm boxed as a `dyn Algorithm` if successful,
-    /// or an `AlgoError` if an error occurs during construction.
+    /// or an `AlgoError` if the name is not registered or an error occurs during construction.
 
     pub fn build_algorithm(
         algorithm_name: &str,
         algorithm_params: Value,
     ) -> Result<Box<dyn Algorithm>, AlgoError> {
-        match algorithm_name {
-            "EmaSmaCrossover" => {
-                let algo = EmaSmaCrossover::new(algorithm_params)?;
-                Ok(Box::new(algo))
-            }
-            "SimpleMovingAverage" => {
-                let algo = SimpleMovingAverage::new(algorithm_params)?;
-                Ok(Box::new(algo))
-            }
-            "ThreeMaCrossover" => {
-                let algo = ThreeMaCrossover::new(algorithm_params)?;
-                Ok(Box::new(algo))
-            }
-            "Rsi" => {
-                let algo = Rsi::new(algorithm_params)?;
-                Ok(Box::new(algo))
-            }
-            "RsiEmaSma" => {
-                let algo = Rsi::new(algorithm_params)?;
-                Ok(Box::new(algo))
-            }
-            "BollingerBands" => {
-                let algo = BollingerBands::new(algorithm_params)?;
-                Ok(Box::new(algo))
-            }
-            "Macd" => {
-                let algo = Macd::new(algorithm_params)?;
-                Ok(Box::new(algo))
-            }
-            "MacdBollingerBands" => {
-                let algo = MacdBollingerBands::new(algorithm_params)?;
-                Ok(Box::new(algo))
-            }
-            "VolumeProfile" => {
-                let algo = VolumeProfile::new(algorithm_params)?;
-                Ok(Box::new(algo))
-            }
-            "VolumeContinuation" => {
-                let algo = VolumeContinuation::new(algorithm_params)?;
-                Ok(Box::new(algo))
-            }
-            "VolumeContinuationReversal" => {
-                let algo = VolumeContinuationReversal::new(algorithm_params)?;
-                Ok(Box::new(algo))
-            }
-            _ => Err(AlgoError::UnkownName(
+        // factory is cloned so the registry isn't locked while the algorithm is built
+        let factory = Self::registry()
+            .read()
+            .unwrap()
+            .get(algorithm_name)
+            .cloned();
+
+        match factory {
+            Some(factory) => factory(algorithm_params),
+            None => Err(AlgoError::UnkownName(
                 format!("Strategy name {algorithm_name} is incorrect").to_string(),
             )),
         }
     }
+
+    // ---
+    // Private Methods
+    // ---
+
+    fn registry() -> &'static RwLock<HashMap<String, AlgoFactory>> {
+        ALGO_REGISTRY.get_or_init(|| {
+            let builtins: Vec<(&str, AlgoFactory)> = vec![
+                ("EmaSmaCrossover", builtin(EmaSmaCrossover::new)),
+                ("SimpleMovingAverage", builtin(SimpleMovingAverage::new)),
+                ("ThreeMaCrossover", builtin(ThreeMaCrossover::new)),
+                ("Rsi", builtin(Rsi::new)),
+                ("RsiEmaSma", builtin(Rsi::new)),
+                ("BollingerBands", builtin(BollingerBands::new)),
+                ("Macd", builtin(Macd::new)),
+                ("MacdBollingerBands", builtin(MacdBollingerBands::new)),
+                ("VolumeProfile", builtin(VolumeProfile::new)),
+                ("VolumeContinuation", builtin(VolumeContinuation::new)),
+                (
+                    "VolumeContinuationReversal",
+                    builtin(VolumeContinuationReversal::new),
+                ),
+            ];
+
+            RwLock::new(
+                builtins
+                    .into_iter()
+                    .map(|(name, factory)| (name.to_string(), factory))
+                    .collect(),
+            )
+        })
+    }
+}
+
+/// Wraps the constructor of a built-in algorithm as an `AlgoFactory`.
+fn builtin<A: Algorithm + 'static>(new: fn(Value) -> Result<A, AlgoError>) -> AlgoFactory {
+    Arc::new(move |params| Ok(Box::new(new(params)?) as Box<dyn Algorithm>))
 }
@@ -4,9 +4,13 @@ use crate::market::kline::Kline;
 
 use crate::market::trade::Trade;
 use crate::strategy::types::AlgoError;
-use crate::strategy::{algorithm::Algorithm, types::AlgoEvalResult};
+use crate::strategy::{
+    algorithm::Algorithm,
+    indicator::{AlgoContext, Indicator, IndicatorSet},
+    types::AlgoEvalResult,
+};
 use crate::utils::number::parse_usize_from_value;
-use ta::indicators::{ExponentialMovingAverage, SimpleMovingAverage};
+use ta::indicators::ExponentialMovingAverage;
 
 // use indicators::exponential_moving_average::ExponentialMovingAverage;
 // use indicators::simple_moving_average::SimpleMovingAverage;
@@ -20,7 +24,6 @@ pub struct EmaSmaCrossover {
     ema_period: usize,
     sma_period: usize,
     ema: ExponentialMovingAverage,
-    sma: SimpleMovingAverage,
     params: Value,
 }
 
@@ -33,15 +36,17 @@ impl EmaSmaCrossover {
 
         let ema = ExponentialMovingAverage::new(ema_period)
             .or_else(|e| Err(AlgoError::InvalidParams(e.to_string())))?;
-        let sma = SimpleMovingAverage::new(sma_period)
-            .or_else(|e| Err(AlgoError::InvalidParams(e.to_string())))?;
+        if sma_period == 0 {
+            return Err(AlgoError::InvalidParams(
+                "sma_period must be greater than 0".to_string(),
+            ));
+        }
 
         Ok(Self {
             data_points: vec![],
             ema_period,
             sma_period,
             ema,
-            sma,
             params,
         })
     }
@@ -49,19 +54,14 @@ impl EmaSmaCrossover {
     fn calculate_ema(&mut self, kline: Kline) -> f64 {
         self.ema.next(kline.close)
     }
-
-    fn calculate_sma(&mut self, kline: Kline) -> f64 {
-        self.sma.next(kline.close)
-    }
 }
 
 impl Algorithm for EmaSmaCrossover {
-    fn evaluate(&mut self, kline: Kline, trades: &[Trade]) -> AlgoEvalResult {
+    fn evaluate(&mut self, kline: Kline, trades: &[Trade], ctx: &AlgoContext) -> AlgoEvalResult {
         self.data_points.push(kline.clone());
 
-        let result = if self.data_points.len() >= self.sma_period {
+        let result = if let Some(sma) = ctx.indicator(Indicator::Sma(self.sma_period)) {
             let ema = self.calculate_ema(kline.clone());
-            let sma = self.calculate_sma(kline.clone());
 
             // EMA crossover signal
             let result = if ema > sma {
@@ -93,6 +93,14 @@ impl Algorithm for EmaSmaCrossover {
         &self.params
     }
 
+    fn indicators(&self) -> Vec<Indicator> {
+        vec![Indicator::Sma(self.sma_period)]
+    }
+
+    fn warmup(&self) -> usize {
+        IndicatorSet::warmup(&self.indicators()).max(self.ema_period)
+    }
+
     fn set_params(&mut self, params: Value) -> Result<(), AlgoError> {
         let ema_period = parse_usize_from_value("ema_period", &params.clone())
             .or_else(|e| Err(AlgoError::InvalidParams(e.to_string())))?;
@@ -101,12 +109,14 @@ impl Algorithm for EmaSmaCrossover {
 
         let ema = ExponentialMovingAverage::new(ema_period)
             .or_else(|e| Err(AlgoError::InvalidParams(e.to_string())))?;
-        let sma = SimpleMovingAverage::new(sma_period)
-            .or_else(|e| Err(AlgoError::InvalidParams(e.to_string())))?;
+        if sma_period == 0 {
+            return Err(AlgoError::InvalidParams(
+                "sma_period must be greater than 0".to_string(),
+            ));
+        }
 
         self.params = params;
         self.ema = ema;
-        self.sma = sma;
         self.ema_period = ema_period;
         self.sma_period = sma_period;
 
@@ -1,18 +1,23 @@
 use std::time::Duration;
 
-use serde_json::Value;
+use serde_json::{json, Value};
 
 use crate::market::kline::Kline;
 
 use crate::market::trade::Trade;
 use crate::strategy::types::AlgoError;
-use crate::strategy::{algorithm::Algorithm, types::AlgoEvalResult};
+use crate::strategy::{
+    algorithm::Algorithm,
+    indicator::{AlgoContext, Indicator},
+    types::AlgoEvalResult,
+};
 use crate::utils::number::parse_usize_from_value;
 
 pub struct SimpleMovingAverage {
     data_points: Vec<Kline>,
     period: usize,
     params: Value,
+    sma: Option<f64>,
 }
 
 impl SimpleMovingAverage {
@@ -23,41 +28,21 @@ impl SimpleMovingAverage {
             data_points: vec![],
             period,
             params,
+            sma: None,
         })
     }
-
-    fn calculate_sma(&self, period: usize) -> f64 {
-        if self.data_points.len() < period {
-            return 0.0;
-        }
-
-        let sum: f64 = self
-            .data_points
-            .iter()
-            .rev()
-            .take(period)
-            .map(|k| k.close)
-            .sum();
-
-        sum / period as f64
-    }
 }
 
 impl Algorithm for SimpleMovingAverage {
-    fn evaluate(&mut self, kline: Kline, trades: &[Trade]) -> AlgoEvalResult {
+    fn evaluate(&mut self, kline: Kline, trades: &[Trade], ctx: &AlgoContext) -> AlgoEvalResult {
         self.data_points.push(kline.clone());
+        self.sma = ctx.indicator(Indicator::Sma(self.period));
 
-        let result = if self.data_points.len() >= self.period {
-            let sma = self.calculate_sma(self.period);
-
+        let result = match self.sma {
             // Placeholder logic for buy/sell decision based on SMA
-            if kline.close > sma {
-                AlgoEvalResult::Buy
-            } else {
-                AlgoEvalResult::Sell
-            }
-        } else {
-            AlgoEvalResult::Ignore
+            Some(sma) if kline.close > sma => AlgoEvalResult::Buy,
+            Some(_) => AlgoEvalResult::Sell,
+            None => AlgoEvalResult::Ignore,
         };
 
         self.clean_data_points();
@@ -73,6 +58,14 @@ impl Algorithm for SimpleMovingAverage {
         &self.params
     }
 
+    fn indicators(&self) -> Vec<Indicator> {
+        vec![Indicator::Sma(self.period)]
+    }
+
+    fn debug_fields(&self) -> Option<Value> {
+        self.sma.map(|sma| json!({ "sma": sma }))
+    }
+
     fn set_params(&mut self, params: Value) -> Result<(), AlgoError> {
         let period = parse_usize_from_value("sma_period", &params.clone())
             .or_else(|e| Err(AlgoError::InvalidParams(e.to_string())))?;
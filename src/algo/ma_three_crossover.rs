@@ -6,7 +6,11 @@ use crate::market::kline::Kline;
 
 use crate::market::trade::Trade;
 use crate::strategy::types::AlgoError;
-use crate::strategy::{algorithm::Algorithm, types::AlgoEvalResult};
+use crate::strategy::{
+    algorithm::Algorithm,
+    indicator::{AlgoContext, Indicator},
+    types::AlgoEvalResult,
+};
 use crate::utils::number::parse_usize_from_value;
 
 pub struct ThreeMaCrossover {
@@ -34,55 +38,30 @@ impl ThreeMaCrossover {
             params,
         })
     }
-
-    fn calculate_ma(&self, period: usize) -> f64 {
-        if self.data_points.len() < period {
-            return 0.0;
-        }
-
-        let sum: f64 = self
-            .data_points
-            .iter()
-            .rev()
-            .take(period)
-            .map(|k| k.close)
-            .sum();
-
-        sum / period as f64
-    }
-
-    fn calculate_short_ma(&self) -> f64 {
-        self.calculate_ma(self.short_period)
-    }
-
-    fn calculate_medium_ma(&self) -> f64 {
-        self.calculate_ma(self.medium_period)
-    }
-
-    fn calculate_long_ma(&self) -> f64 {
-        self.calculate_ma(self.long_period)
-    }
 }
 
 impl Algorithm for ThreeMaCrossover {
-    fn evaluate(&mut self, kline: Kline, trades: &[Trade]) -> AlgoEvalResult {
+    fn evaluate(&mut self, kline: Kline, trades: &[Trade], ctx: &AlgoContext) -> AlgoEvalResult {
         self.data_points.push(kline.clone());
 
-        let result = if self.data_points.len() >= self.long_period {
-            let short_ma = self.calculate_short_ma();
-            let medium_ma = self.calculate_medium_ma();
-            let long_ma = self.calculate_long_ma();
+        let mas = (
+            ctx.indicator(Indicator::Sma(self.short_period)),
+            ctx.indicator(Indicator::Sma(self.medium_period)),
+            ctx.indicator(Indicator::Sma(self.long_period)),
+        );
 
+        let result = match mas {
             // Placeholder logic for buy/sell decision based on MA crossovers
-            if short_ma > medium_ma && medium_ma > long_ma {
-                AlgoEvalResult::Buy
-            } else if short_ma < medium_ma && medium_ma < long_ma {
-                AlgoEvalResult::Sell
-            } else {
-                AlgoEvalResult::Ignore
+            (Some(short_ma), Some(medium_ma), Some(long_ma)) => {
+                if short_ma > medium_ma && medium_ma > long_ma {
+                    AlgoEvalResult::Buy
+                } else if short_ma < medium_ma && medium_ma < long_ma {
+                    AlgoEvalResult::Sell
+                } else {
+                    AlgoEvalResult::Ignore
+                }
             }
-        } else {
-            AlgoEvalResult::Ignore
+            _ => AlgoEvalResult::Ignore,
         };
 
         self.clean_data_points();
@@ -98,6 +77,14 @@ impl Algorithm for ThreeMaCrossover {
         &self.params
     }
 
+    fn indicators(&self) -> Vec<Indicator> {
+        vec![
+            Indicator::Sma(self.short_period),
+            Indicator::Sma(self.medium_period),
+            Indicator::Sma(self.long_period),
+        ]
+    }
+
     fn set_params(&mut self, params: Value) -> Result<(), AlgoError> {
         let short_period = parse_usize_from_value("short_period", &params)
             .or_else(|e| Err(AlgoError::InvalidParams(e.to_string())))?;
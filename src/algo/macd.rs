@@ -2,6 +2,7 @@ use crate::market::kline::Kline;
 use crate::market::trade::Trade;
 use crate::strategy::{
     algorithm::Algorithm,
+    indicator::AlgoContext,
     types::{AlgoError, AlgoEvalResult},
 };
 use crate::utils::number::parse_usize_from_value;
@@ -79,7 +80,7 @@ impl Macd {
 }
 
 impl Algorithm for Macd {
-    fn evaluate(&mut self, kline: Kline, trades: &[Trade]) -> AlgoEvalResult {
+    fn evaluate(&mut self, kline: Kline, trades: &[Trade], _ctx: &AlgoContext) -> AlgoEvalResult {
         self.data_points.push(kline);
         self.update_macd_and_signal_lines();
 
@@ -112,6 +113,10 @@ impl Algorithm for Macd {
         &self.params
     }
 
+    fn warmup(&self) -> usize {
+        self.long_ema_period + self.signal_ema_period
+    }
+
     fn set_params(&mut self, params: Value) -> Result<(), AlgoError> {
         if let Ok(short_ema_period) = parse_usize_from_value("short_ema_period", &params) {
             self.short_ema_period = short_ema_period
@@ -2,6 +2,7 @@ use crate::market::kline::Kline;
 use crate::market::trade::Trade;
 use crate::strategy::{
     algorithm::Algorithm,
+    indicator::{AlgoContext, Indicator, IndicatorSet},
     types::{AlgoError, AlgoEvalResult},
 };
 use crate::utils::number::parse_usize_from_value;
@@ -72,20 +73,16 @@ impl MacdBollingerBands {
         })
     }
 
-    fn calculate_bollinger_bands(&self) -> (f64, f64, f64) {
-        let prices: Vec<f64> = self.data_points.iter().map(|kline| kline.close).collect();
-        let sma: f64 = prices.iter().sum::<f64>() / self.bollinger_period as f64;
-        let _std_dev: f64 = prices
-            .iter()
-            .map(|price| (price - sma).powf(2.0))
-            .sum::<f64>()
-            / self.bollinger_period as f64;
-        let std_dev = _std_dev.sqrt();
+    /// Upper, middle and lower Bollinger bands at the evaluated kline, `None` until the
+    /// indicators are warmed up.
+    fn bollinger_bands(&self, ctx: &AlgoContext) -> Option<(f64, f64, f64)> {
+        let sma = ctx.indicator(Indicator::Sma(self.bollinger_period))?;
+        let std_dev = ctx.indicator(Indicator::StdDev(self.bollinger_period))?;
 
         let upper_band = sma + std_dev * self.bollinger_multiplier;
         let lower_band = sma - std_dev * self.bollinger_multiplier;
 
-        (upper_band, sma, lower_band)
+        Some((upper_band, sma, lower_band))
     }
 
     fn update_macd_and_signal_lines(&mut self) {
@@ -106,29 +103,30 @@ impl MacdBollingerBands {
 }
 
 impl Algorithm for MacdBollingerBands {
-    fn evaluate(&mut self, kline: Kline, trades: &[Trade]) -> AlgoEvalResult {
+    fn evaluate(&mut self, kline: Kline, trades: &[Trade], ctx: &AlgoContext) -> AlgoEvalResult {
         self.data_points.push(kline.clone());
 
-        let (upper_band, _, lower_band) = self.calculate_bollinger_bands();
+        let bands = self.bollinger_bands(ctx);
         self.update_macd_and_signal_lines();
 
-        let result = if let (Some(&latest_macd), Some(&latest_signal)) =
-            (self.macd_line.last(), self.signal_line.last())
-        {
-            let price = kline.close;
-
-            if price < lower_band && latest_macd > latest_signal {
-                // Buy signal: price below lower Bollinger Band and MACD crosses above signal line
-                AlgoEvalResult::Buy
-            } else if price > upper_band && latest_macd < latest_signal {
-                // Sell signal: price above upper Bollinger Band and MACD crosses below signal line
-                AlgoEvalResult::Sell
+        let result =
+            if let (Some((upper_band, _, lower_band)), Some(&latest_macd), Some(&latest_signal)) =
+                (bands, self.macd_line.last(), self.signal_line.last())
+            {
+                let price = kline.close;
+
+                if price < lower_band && latest_macd > latest_signal {
+                    // Buy signal: price below lower Bollinger Band and MACD crosses above signal line
+                    AlgoEvalResult::Buy
+                } else if price > upper_band && latest_macd < latest_signal {
+                    // Sell signal: price above upper Bollinger Band and MACD crosses below signal line
+                    AlgoEvalResult::Sell
+                } else {
+                    AlgoEvalResult::Ignore
+                }
             } else {
                 AlgoEvalResult::Ignore
-            }
-        } else {
-            AlgoEvalResult::Ignore
-        };
+            };
 
         self.clean_data_points();
 
@@ -143,6 +141,17 @@ impl Algorithm for MacdBollingerBands {
         &self.params
     }
 
+    fn indicators(&self) -> Vec<Indicator> {
+        vec![
+            Indicator::Sma(self.bollinger_period),
+            Indicator::StdDev(self.bollinger_period),
+        ]
+    }
+
+    fn warmup(&self) -> usize {
+        IndicatorSet::warmup(&self.indicators()).max(self.long_ema_period + self.signal_ema_period)
+    }
+
     fn set_params(&mut self, params: Value) -> Result<(), AlgoError> {
         if let Ok(bollinger_period) = parse_usize_from_value("bollinger_period", &params) {
             self.bollinger_period = bollinger_period
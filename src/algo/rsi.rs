@@ -1,9 +1,13 @@
 use crate::market::kline::Kline;
 use crate::market::trade::Trade;
 use crate::strategy::types::AlgoError;
-use crate::strategy::{algorithm::Algorithm, types::AlgoEvalResult};
+use crate::strategy::{
+    algorithm::Algorithm,
+    indicator::{AlgoContext, Indicator},
+    types::AlgoEvalResult,
+};
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
+use serde_json::{json, Value};
 use std::time::Duration;
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -16,7 +20,7 @@ pub struct Rsi {
     data_points: Vec<Kline>,
     params: Value,
     rsi_period: usize,
-    rsi: f64, // Optional: Store the last calculated RSI value
+    rsi: Option<f64>,
 }
 
 impl Rsi {
@@ -28,57 +32,22 @@ impl Rsi {
         Ok(Self {
             data_points: vec![],
             rsi_period,
-            rsi: 0.0,
+            rsi: None,
             params,
         })
     }
-
-    fn calculate_rsi(&mut self) -> f64 {
-        if self.data_points.len() < self.rsi_period {
-            return 0.0; // Not enough data to calculate RSI
-        }
-
-        let mut gains = 0.0;
-        let mut losses = 0.0;
-
-        for i in 1..=self.rsi_period {
-            let delta = self.data_points[self.data_points.len() - i].close
-                - self.data_points[self.data_points.len() - i - 1].close;
-            if delta > 0.0 {
-                gains += delta;
-            } else {
-                losses -= delta; // Losses are positive numbers
-            }
-        }
-
-        let avg_gain = gains / self.rsi_period as f64;
-        let avg_loss = losses / self.rsi_period as f64;
-
-        if avg_loss == 0.0 {
-            return 100.0; // Prevent division by zero
-        }
-
-        let rs = avg_gain / avg_loss;
-        let rsi = 100.0 - (100.0 / (1.0 + rs));
-
-        self.rsi = rsi; // Store the calculated RSI value
-        rsi
-    }
 }
 
 impl Algorithm for Rsi {
-    fn evaluate(&mut self, kline: Kline, trades: &[Trade]) -> AlgoEvalResult {
+    fn evaluate(&mut self, kline: Kline, trades: &[Trade], ctx: &AlgoContext) -> AlgoEvalResult {
         self.data_points.push(kline);
-
-        let rsi = self.calculate_rsi();
+        self.rsi = ctx.indicator(Indicator::Rsi(self.rsi_period));
 
         // Example RSI logic: Buy if RSI < 30 (oversold), Sell if RSI > 70 (overbought), else Ignore
-        let result = if rsi < 30.0 {
-            AlgoEvalResult::Buy
-        } else if rsi > 70.0 {
-            AlgoEvalResult::Sell
-        } else {
-            AlgoEvalResult::Ignore
+        let result = match self.rsi {
+            Some(rsi) if rsi < 30.0 => AlgoEvalResult::Buy,
+            Some(rsi) if rsi > 70.0 => AlgoEvalResult::Sell,
+            _ => AlgoEvalResult::Ignore,
         };
 
         self.clean_data_points();
@@ -94,6 +63,14 @@ impl Algorithm for Rsi {
         &self.params
     }
 
+    fn indicators(&self) -> Vec<Indicator> {
+        vec![Indicator::Rsi(self.rsi_period)]
+    }
+
+    fn debug_fields(&self) -> Option<Value> {
+        self.rsi.map(|rsi| json!({ "rsi": rsi }))
+    }
+
     fn set_params(&mut self, params: Value) -> Result<(), AlgoError> {
         let rsi_params: RsiParams = serde_json::from_value(params.clone())?;
 
@@ -1,7 +1,11 @@
 use crate::market::kline::Kline;
 use crate::market::trade::Trade;
 use crate::strategy::types::AlgoError;
-use crate::strategy::{algorithm::Algorithm, types::AlgoEvalResult};
+use crate::strategy::{
+    algorithm::Algorithm,
+    indicator::{AlgoContext, Indicator, IndicatorSet},
+    types::AlgoEvalResult,
+};
 use crate::utils::number::parse_usize_from_value;
 use serde::{Deserialize, Serialize};
 use serde_json::{self, Value};
@@ -50,48 +54,6 @@ impl RsiEmaSma {
         })
     }
 
-    fn calculate_rsi(&self) -> f64 {
-        // Simplified RSI calculation, assumes calculate_gain_loss function is defined
-        if self.data_points.len() < self.rsi_period + 1 {
-            return 50.0; // Default RSI value if not enough data
-        }
-
-        let mut gains = 0.0;
-        let mut losses = 0.0;
-        for i in (1..=self.rsi_period).rev() {
-            let delta = self.data_points[self.data_points.len() - i].close
-                - self.data_points[self.data_points.len() - i - 1].close;
-            if delta > 0.0 {
-                gains += delta;
-            } else {
-                losses -= delta;
-            }
-        }
-
-        let avg_gain = gains / self.rsi_period as f64;
-        let avg_loss = losses / self.rsi_period as f64;
-
-        if avg_loss == 0.0 {
-            return 100.0;
-        }
-
-        let rs = avg_gain / avg_loss;
-        100.0 - (100.0 / (1.0 + rs))
-    }
-
-    fn calculate_sma(&self, period: usize) -> f64 {
-        if self.data_points.len() < period {
-            return 0.0; // Not enough data
-        }
-        self.data_points
-            .iter()
-            .rev()
-            .take(period)
-            .map(|k| k.close)
-            .sum::<f64>()
-            / period as f64
-    }
-
     fn calculate_ema(&mut self, period: usize) -> f64 {
         if self.data_points.is_empty() {
             return 0.0;
@@ -112,25 +74,33 @@ impl RsiEmaSma {
 }
 
 impl Algorithm for RsiEmaSma {
-    fn evaluate(&mut self, kline: Kline, trades: &[Trade]) -> AlgoEvalResult {
+    fn evaluate(&mut self, kline: Kline, trades: &[Trade], ctx: &AlgoContext) -> AlgoEvalResult {
         self.data_points.push(kline);
 
-        let rsi = self.calculate_rsi();
-        let short_sma = self.calculate_sma(self.short_sma_period);
-        let medium_sma = self.calculate_sma(self.medium_sma_period);
-        let long_sma = self.calculate_sma(self.long_sma_period);
         let ema = self.calculate_ema(self.ema_period);
-
-        let result = if rsi < 30.0
-            && short_sma > medium_sma
-            && medium_sma > long_sma
-            && short_sma > ema
-        {
-            AlgoEvalResult::Buy
-        } else if rsi > 70.0 && short_sma < medium_sma && medium_sma < long_sma && short_sma < ema {
-            AlgoEvalResult::Sell
-        } else {
-            AlgoEvalResult::Ignore
+        let values = (
+            ctx.indicator(Indicator::Rsi(self.rsi_period)),
+            ctx.indicator(Indicator::Sma(self.short_sma_period)),
+            ctx.indicator(Indicator::Sma(self.medium_sma_period)),
+            ctx.indicator(Indicator::Sma(self.long_sma_period)),
+        );
+        let result = match values {
+            (Some(rsi), Some(short_sma), Some(medium_sma), Some(long_sma)) => {
+                if rsi < 30.0 && short_sma > medium_sma && medium_sma > long_sma && short_sma > ema
+                {
+                    AlgoEvalResult::Buy
+                } else if rsi > 70.0
+                    && short_sma < medium_sma
+                    && medium_sma < long_sma
+                    && short_sma < ema
+                {
+                    AlgoEvalResult::Sell
+                } else {
+                    AlgoEvalResult::Ignore
+                }
+            }
+            // indicators aren't warmed up
+            _ => AlgoEvalResult::Ignore,
         };
 
         self.clean_data_points();
@@ -147,6 +117,19 @@ impl Algorithm for RsiEmaSma {
         &self.params
     }
 
+    fn indicators(&self) -> Vec<Indicator> {
+        vec![
+            Indicator::Rsi(self.rsi_period),
+            Indicator::Sma(self.short_sma_period),
+            Indicator::Sma(self.medium_sma_period),
+            Indicator::Sma(self.long_sma_period),
+        ]
+    }
+
+    fn warmup(&self) -> usize {
+        IndicatorSet::warmup(&self.indicators()).max(self.ema_period)
+    }
+
     fn set_params(&mut self, params: Value) -> Result<(), AlgoError> {
         let rsi_ema_sma_params: RsiEmaSmaParams = serde_json::from_value(params.clone())?;
 
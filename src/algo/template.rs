@@ -7,7 +7,7 @@ use crate::market::kline::Kline;
 
 use crate::market::trade::Trade;
 use crate::strategy::types::AlgoError;
-use crate::strategy::{algorithm::Algorithm, types::AlgoEvalResult};
+use crate::strategy::{algorithm::Algorithm, indicator::AlgoContext, types::AlgoEvalResult};
 use crate::utils::number::parse_usize_from_value;
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -43,7 +43,7 @@ impl CustomAlgo {
 }
 
 impl Algorithm for CustomAlgo {
-    fn evaluate(&mut self, kline: Kline, trades: &[Trade]) -> AlgoEvalResult {
+    fn evaluate(&mut self, kline: Kline, trades: &[Trade], _ctx: &AlgoContext) -> AlgoEvalResult {
         self.data_points.push(kline.clone());
 
         // Example logic using self.custom_param
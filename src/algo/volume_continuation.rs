@@ -1,7 +1,5 @@
 use log::info;
 use serde_json::Value;
-use ta::indicators::SimpleMovingAverage;
-use ta::Next;
 use uuid::serde;
 
 use crate::account::trade::OrderSide;
@@ -18,7 +16,7 @@ use crate::analytics::volume::{
 
 use crate::market::trade::Trade;
 use crate::strategy::types::AlgoError;
-use crate::strategy::{algorithm::Algorithm, types::AlgoEvalResult};
+use crate::strategy::{algorithm::Algorithm, indicator::AlgoContext, types::AlgoEvalResult};
 use crate::utils::number::parse_usize_from_value;
 use crate::utils::time::{
     floor_mili_ts, generate_ts, string_to_timestamp, HOUR_AS_MILI, MIN_AS_MILI,
@@ -30,21 +28,18 @@ pub struct VolumeContinuation {
     time_vol: TimeVolume,
     cur_period: AuctionPeriod,
     params: Value,
-    sma: SimpleMovingAverage,
     last_period_data: Option<LastPeriodData>,
     last_result: Option<AlgoEvalResult>,
 }
 
 impl VolumeContinuation {
     pub fn new(params: Value) -> Result<Self, AlgoError> {
-        let sma = SimpleMovingAverage::new(10).unwrap();
         Ok(Self {
             klines: vec![],
             time_vol: TimeVolume::new(Interval::Min1),
             cur_period: AuctionPeriod::Unknown,
             params,
             last_result: None,
-            sma,
             last_period_data: None,
         })
     }
@@ -115,7 +110,7 @@ impl VolumeContinuation {
 }
 
 impl Algorithm for VolumeContinuation {
-    fn evaluate(&mut self, kline: Kline, trades: &[Trade]) -> AlgoEvalResult {
+    fn evaluate(&mut self, kline: Kline, trades: &[Trade], _ctx: &AlgoContext) -> AlgoEvalResult {
         // get current period
         let new_period = determine_auction_period(&kline);
         let is_within_15 = is_within_n_min_of_next_period(15, &kline);
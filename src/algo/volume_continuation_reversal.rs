@@ -1,8 +1,6 @@
 use ::serde::{Deserialize, Serialize};
 use log::info;
 use serde_json::Value;
-use ta::indicators::SimpleMovingAverage;
-use ta::Next;
 use uuid::serde;
 
 use crate::account::trade::OrderSide;
@@ -19,7 +17,7 @@ use crate::analytics::volume::{
 };
 use crate::market::trade::Trade;
 use crate::strategy::types::AlgoError;
-use crate::strategy::{algorithm::Algorithm, types::AlgoEvalResult};
+use crate::strategy::{algorithm::Algorithm, indicator::AlgoContext, types::AlgoEvalResult};
 use crate::utils::number::parse_usize_from_value;
 use crate::utils::time::{
     floor_mili_ts, generate_ts, string_to_timestamp, HOUR_AS_MILI, MIN_AS_MILI,
@@ -37,7 +35,6 @@ pub struct VolumeContinuationReversal {
     cur_period: AuctionPeriod,
     params: Value,
     reverse: bool,
-    sma: SimpleMovingAverage,
     last_period_data: Option<LastPeriodData>,
     last_result: Option<AlgoEvalResult>,
 }
@@ -45,7 +42,6 @@ pub struct VolumeContinuationReversal {
 impl VolumeContinuationReversal {
     pub fn new(params: Value) -> Result<Self, AlgoError> {
         let _params: VolumeContinuationReversalParams = serde_json::from_value(params.clone())?;
-        let sma = SimpleMovingAverage::new(10).unwrap();
 
         Ok(Self {
             klines: vec![],
@@ -54,7 +50,6 @@ impl VolumeContinuationReversal {
             cur_period: AuctionPeriod::Unknown,
             params,
             last_result: None,
-            sma,
             last_period_data: None,
         })
     }
@@ -125,7 +120,7 @@ impl VolumeContinuationReversal {
 }
 
 impl Algorithm for VolumeContinuationReversal {
-    fn evaluate(&mut self, kline: Kline, trades: &[Trade]) -> AlgoEvalResult {
+    fn evaluate(&mut self, kline: Kline, trades: &[Trade], _ctx: &AlgoContext) -> AlgoEvalResult {
         // get current period
         let new_period = determine_auction_period(&kline);
         let is_within_15 = is_within_n_min_of_next_period(15, &kline);
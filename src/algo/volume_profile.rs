@@ -6,7 +6,7 @@ use crate::market::kline::Kline;
 use crate::analytics::volume::{PriceVolume, TradeVolume};
 use crate::market::trade::Trade;
 use crate::strategy::types::AlgoError;
-use crate::strategy::{algorithm::Algorithm, types::AlgoEvalResult};
+use crate::strategy::{algorithm::Algorithm, indicator::AlgoContext, types::AlgoEvalResult};
 use crate::utils::number::parse_usize_from_value;
 use crate::utils::time::{floor_mili_ts, generate_ts, HOUR_AS_MILI, MIN_AS_MILI};
 
@@ -37,7 +37,7 @@ impl VolumeProfile {
 }
 
 impl Algorithm for VolumeProfile {
-    fn evaluate(&mut self, kline: Kline, trades: &[Trade]) -> AlgoEvalResult {
+    fn evaluate(&mut self, kline: Kline, trades: &[Trade], _ctx: &AlgoContext) -> AlgoEvalResult {
         self.data_points.push(kline.clone());
         self.market_volume.add_trades(trades);
 
@@ -2,8 +2,9 @@ use crate::{
     account::trade::OrderSide,
     market::{interval::Interval, trade::Trade},
     utils::{
+        number::{decimal_places, from_decimal, round_decimal, to_decimal},
         time::{floor_mili_ts, generate_ts, timestamp_to_string, HOUR_AS_MILI, MIN_AS_MILI},
-        trade::{calc_min_max, calc_total_volume},
+        trade::{calc_min_max, calc_total_volume, round_bucket_volumes},
     },
 };
 
@@ -11,6 +12,9 @@ use log::{info, warn};
 use serde::Serialize;
 use std::collections::BTreeMap;
 
+/// Decimals bucket volumes are rounded to in volume results when no precision is set.
+pub const DEFAULT_VOLUME_PRECISION: u32 = 8;
+
 pub trait TradeVolume {
     fn add_trades(&mut self, trades: &[Trade]);
     fn result(&self) -> impl Serialize;
@@ -25,6 +29,10 @@ pub struct PriceVolume {
     min_price: f64,
     max_price: f64,
     fixed_price: bool,
+    /// Decimals bucket keys are formatted with, the decimals of the bucket size.
+    precision: usize,
+    /// Decimals bucket volumes are rounded to in the result.
+    volume_precision: u32,
 }
 
 impl PriceVolume {
@@ -37,9 +45,18 @@ impl PriceVolume {
             start_time: u64::MAX,
             end_time: 0,
             fixed_price,
+            precision: decimal_places(bucket_size),
+            volume_precision: DEFAULT_VOLUME_PRECISION,
         }
     }
 
+    /// Sets the decimals bucket volumes are rounded to in the result, ie. the quantity precision
+    /// of the symbol.
+
+    pub fn set_volume_precision(&mut self, decimals: u32) {
+        self.volume_precision = decimals;
+    }
+
     pub fn reset_volumes(&mut self) {
         self.buckets = BTreeMap::new();
         self.min_price = 0.0;
@@ -54,12 +71,16 @@ impl PriceVolume {
                 let key = if self.fixed_price {
                     trade.floor_price(self.bucket_size)
                 } else {
-                    let bucket_index = ((trade.price - self.min_price) / self.bucket_size).floor();
-                    let bucket_key = self.min_price + bucket_index * self.bucket_size;
-                    bucket_key
+                    let min_price = to_decimal(self.min_price);
+                    let bucket_size = to_decimal(self.bucket_size);
+                    let bucket_index = (to_decimal(trade.price) - min_price)
+                        .checked_div(bucket_size)
+                        .unwrap_or_default()
+                        .floor();
+                    from_decimal(min_price + bucket_index * bucket_size)
                 };
 
-                let bucket_key_str = format!("{:.2}", key);
+                let bucket_key_str = format!("{:.*}", self.precision, key);
 
                 let volume_entry = self
                     .buckets
@@ -140,11 +161,13 @@ impl TradeVolume for PriceVolume {
     }
 
     fn result(&self) -> PriceVolumeData {
-        let total_volume = calc_total_volume(&self.buckets);
+        // totals are summed from the rounded buckets so they reconcile with the buckets shown
+        let buckets = round_bucket_volumes(&self.buckets, self.volume_precision);
+        let total_volume = calc_total_volume(&buckets);
 
         PriceVolumeData {
-            num_buckets: self.buckets.len(),
-            buckets: self.buckets.clone(),
+            num_buckets: buckets.len(),
+            buckets,
             end_time: timestamp_to_string(self.end_time),
             start_time: timestamp_to_string(self.start_time),
             total_volume,
@@ -173,6 +196,15 @@ impl BucketVolume {
     pub fn total(&self) -> f64 {
         self.buy_volume + self.sell_volume
     }
+
+    /// Rounds the buy and sell volumes to a number of decimals.
+
+    pub fn rounded(&self, decimals: u32) -> Self {
+        Self {
+            buy_volume: round_decimal(self.buy_volume, decimals),
+            sell_volume: round_decimal(self.sell_volume, decimals),
+        }
+    }
 }
 
 #[derive(Serialize, Debug)]
@@ -211,6 +243,8 @@ pub struct TimeVolume {
     pub max_price: f64,
     start_time: u64,
     end_time: u64,
+    /// Decimals bucket volumes are rounded to in the result.
+    volume_precision: u32,
 }
 
 impl TimeVolume {
@@ -222,9 +256,17 @@ impl TimeVolume {
             end_time: 0,
             min_price: 0.0,
             max_price: 0.0,
+            volume_precision: DEFAULT_VOLUME_PRECISION,
         }
     }
 
+    /// Sets the decimals bucket volumes are rounded to in the result, ie. the quantity precision
+    /// of the symbol.
+
+    pub fn set_volume_precision(&mut self, decimals: u32) {
+        self.volume_precision = decimals;
+    }
+
     pub fn n_vol(&self, reverse: bool, n_buckets: usize) -> BucketVolume {
         let mut bucket_vol = BucketVolume::new();
 
@@ -316,15 +358,17 @@ impl TradeVolume for TimeVolume {
     }
 
     fn result(&self) -> TimeVolumeData {
-        let total_volume = calc_total_volume(&self.buckets);
+        // totals are summed from the rounded buckets so they reconcile with the buckets shown
+        let buckets = round_bucket_volumes(&self.buckets, self.volume_precision);
+        let total_volume = calc_total_volume(&buckets);
 
         TimeVolumeData {
-            num_buckets: self.buckets.len(),
+            num_buckets: buckets.len(),
             start_time: timestamp_to_string(self.start_time),
             end_time: timestamp_to_string(self.end_time),
             total_volume,
-            buckets: self.buckets.clone(),
-            average_volume: self.average_volume(),
+            buckets,
+            average_volume: self.average_volume().rounded(self.volume_precision),
             min_price: self.min_price,
             max_price: self.max_price,
         }
@@ -357,3 +401,86 @@ impl Default for TimeVolumeData {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use tokio::test;
+
+    use crate::{testutil::make_trades, utils::number::sum_decimal};
+
+    fn bucket_keys(volume: &PriceVolume) -> Vec<&str> {
+        volume.buckets.keys().map(|key| key.as_str()).collect()
+    }
+
+    #[test]
+    async fn test_price_volume_bucket_precision() {
+        let start = 1_700_000_000_000;
+
+        // BTC prices in 0.5 buckets, neighbouring prices stay in distinct buckets
+        let btc_trades = make_trades("BTCUSDT", start, 100, &[37_000.1, 37_000.6, 37_001.2]);
+        let mut volume = PriceVolume::new(0.5, true);
+        volume.add_trades(&btc_trades);
+        assert_eq!(bucket_keys(&volume), ["37000.0", "37000.5", "37001.0"]);
+
+        let mut volume = PriceVolume::new(10.0, true);
+        volume.add_trades(&btc_trades);
+        assert_eq!(bucket_keys(&volume), ["37000"]);
+
+        // sub-cent prices keep their resolution in fixed and variable buckets
+        let token_trades = make_trades("PEPEUSDT", start, 100, &[0.000012, 0.000013, 0.0000131]);
+        let mut volume = PriceVolume::new(0.000001, true);
+        volume.add_trades(&token_trades);
+        assert_eq!(bucket_keys(&volume), ["0.000012", "0.000013"]);
+        assert_eq!(volume.buckets["0.000013"].total(), 2.0);
+
+        let mut volume = PriceVolume::new(0.000001, false);
+        volume.add_trades(&token_trades);
+        assert_eq!(bucket_keys(&volume), ["0.000012", "0.000013"]);
+        assert_eq!(volume.result().poc, 0.000013);
+    }
+
+    #[test]
+    async fn test_volume_totals_reconcile_with_rounded_buckets() {
+        let start = 1_700_000_000_000;
+
+        // many small quantities spread over buckets, float sums drift from the exact totals
+        let prices: Vec<f64> = (0..3_000).map(|i| 100.0 + (i % 7) as f64).collect();
+        let trades: Vec<Trade> = make_trades("BTCUSDT", start, 100, &prices)
+            .into_iter()
+            .map(|mut trade| {
+                trade.qty = 0.00123;
+                trade
+            })
+            .collect();
+
+        let mut price_volume = PriceVolume::new(1.0, true);
+        price_volume.set_volume_precision(5);
+        price_volume.add_trades(&trades);
+        let raw_total: f64 = price_volume.buckets.values().map(|b| b.total()).sum();
+        let result = price_volume.result();
+
+        let bucket_buy = sum_decimal(result.buckets.values().map(|b| b.buy_volume));
+        let bucket_sell = sum_decimal(result.buckets.values().map(|b| b.sell_volume));
+        assert_eq!(result.total_volume.buy_volume, bucket_buy);
+        assert_eq!(result.total_volume.sell_volume, bucket_sell);
+        assert_eq!(sum_decimal([bucket_buy, bucket_sell]), 3.69);
+        assert_ne!(raw_total, 3.69);
+        assert!(result
+            .buckets
+            .values()
+            .all(|b| b.buy_volume == round_decimal(b.buy_volume, 5)));
+
+        let mut time_volume = TimeVolume::new(Interval::Min1);
+        time_volume.set_volume_precision(5);
+        time_volume.add_trades(&trades);
+        let result = time_volume.result();
+
+        let bucket_buy = sum_decimal(result.buckets.values().map(|b| b.buy_volume));
+        let bucket_sell = sum_decimal(result.buckets.values().map(|b| b.sell_volume));
+        assert_eq!(result.total_volume.buy_volume, bucket_buy);
+        assert_eq!(result.total_volume.sell_volume, bucket_sell);
+        assert_eq!(sum_decimal([bucket_buy, bucket_sell]), 3.69);
+    }
+}
@@ -1,68 +1,64 @@
-use std::sync::Arc;
+use std::collections::HashMap;
 
 use actix_web::{
     get,
     web::{self, scope, Json},
-    HttpResponse, Responder, Scope,
+    HttpResponse, Scope,
 };
 use actix_web::{post, HttpRequest};
 
-use log::info;
+use log::{info, warn};
 use serde::Deserialize;
 use serde_json::json;
 
+use crate::app::AppState;
 use crate::{
-    account::trade::{OrderSide, Position, PositionId},
-    exchange::mock::MockExchangeApi,
-    strategy::strategy::StrategyId,
+    account::{
+        account::{fills_csv, Account, AccountInfo, OrderError, OrphanAction},
+        schedule::{ScheduledOrder, ScheduledOrderId},
+        trade::{OrderSide, Position, PositionId},
+    },
+    api::error::{parse_date, parse_optional_date, ApiError, ApiResponse},
+    market::{market::Market, types::ArcMutex},
+    strategy::strategy::{StrategyId, StrategyMode},
+    utils::time::generate_ts,
 };
-use crate::{app::AppState, exchange::api::ExchangeApi};
 
 #[derive(Debug, Deserialize)]
 pub struct ClosePosParams {
     position_id: PositionId,
 }
 #[post("/close-position")]
-async fn close_position(
-    app_data: web::Data<AppState>,
-    body: Json<ClosePosParams>,
-) -> impl Responder {
+async fn close_position(app_data: web::Data<AppState>, body: Json<ClosePosParams>) -> ApiResponse {
     let account = app_data.get_account().await;
     let market = app_data.get_market().await;
-    let market = market.lock().await;
+    let market = market.lock().await.reader();
     let mut account = account.lock().await;
 
-    let pos = account.get_position(&body.position_id);
-    if pos.is_none() {
-        let json_data =
-            json!({ "error": "Unable to find position", "position_id":body.position_id });
-        return HttpResponse::ExpectationFailed().json(json_data);
-    };
+    let position = account
+        .get_position(&body.position_id)
+        .ok_or_else(|| ApiError::NotFound(format!("Position {} not found", body.position_id)))?
+        .clone();
 
-    // SAFETY: None check above
-    let position = pos.unwrap().clone();
+    let last_price = market
+        .last_price(&position.symbol)
+        .await
+        .ok_or_else(|| last_price_not_found(&position.symbol))?;
 
-    if let Some(last_price) = market.last_price(&position.symbol).await {
-        let res = account.close_position(position.id, last_price).await;
+    let trade = account
+        .close_position(position.id, last_price)
+        .await
+        .ok_or_else(|| ApiError::Upstream("Unable to close position".to_string()))?;
 
-        if let Some(trade) = res {
-            let json_data = json!({ "success": "Position Closed", "trade": trade });
-            HttpResponse::Ok().json(json_data)
-        } else {
-            let json_data = json!({ "error": "Unable to close position" });
-            HttpResponse::ExpectationFailed().json(json_data)
-        }
-    } else {
-        let json_data = json!({ "error": "Unable to close position, last price not found", "symbol": position.symbol });
-        HttpResponse::ExpectationFailed().json(json_data)
-    }
+    let json_data = json!({ "success": "Position Closed", "trade": trade });
+    Ok(HttpResponse::Ok().json(json_data))
 }
 
 #[get("/close-all-positions")]
-async fn close_all_positions(app_data: web::Data<AppState>) -> impl Responder {
+async fn close_all_positions(app_data: web::Data<AppState>) -> ApiResponse {
     let account = app_data.get_account().await;
     let market = app_data.get_market().await;
-    let market = market.lock().await;
+    let market = market.lock().await.reader();
     let mut account = account.lock().await;
 
     let mut trades = vec![];
@@ -75,13 +71,12 @@ async fn close_all_positions(app_data: web::Data<AppState>) -> impl Responder {
                 trades.push(trade.clone())
             }
         } else {
-            let json_data = json!({ "error": "Unable to close position, last price not found", "symbol": position.clone().symbol });
-            return HttpResponse::ExpectationFailed().json(json_data);
+            return Err(last_price_not_found(&position.symbol));
         }
     }
 
     let json_data = json!({ "trades": trades });
-    HttpResponse::Ok().json(json_data)
+    Ok(HttpResponse::Ok().json(json_data))
 }
 
 #[derive(Debug, Deserialize)]
@@ -91,49 +86,86 @@ pub struct OpenPosParams {
     leverage: u32,
     order_side: OrderSide,
     stop_loss: Option<f64>,
+    take_profit: Option<f64>,
     strategy_id: Option<StrategyId>,
 }
 #[post("/open-position")]
-async fn open_position(app_data: web::Data<AppState>, body: Json<OpenPosParams>) -> impl Responder {
+async fn open_position(app_data: web::Data<AppState>, body: Json<OpenPosParams>) -> ApiResponse {
     let account = app_data.get_account().await;
     let market = app_data.get_market().await;
 
-    let market = market.try_lock();
+    let market = market.lock().await.reader();
     let mut account = account.lock().await;
 
-    if let Some(market) = market {
-        if let Some(last_price) = market.last_price(&body.symbol).await {
-            let res = account
-                .open_position(
-                    &body.symbol,
-                    body.margin,
-                    body.leverage,
-                    body.order_side.clone(),
-                    last_price,
-                    body.strategy_id,
-                    body.stop_loss,
-                )
-                .await;
-
-            if let Some(res) = res {
-                let json_data = json!({ "success": "Position Opened", "position": res });
-                HttpResponse::Ok().json(json_data)
-            } else {
-                let json_data = json!({ "error": "Unable to open position" });
-                HttpResponse::ExpectationFailed().json(json_data)
+    // refused by the account rather than failed on the exchange
+    if !account.is_trading_enabled() {
+        return Err(ApiError::Conflict(
+            "Trading is halted by the kill switch until the next UTC day".to_string(),
+        ));
+    }
+
+    account
+        .validate_leverage(&body.symbol, body.leverage)
+        .await
+        .map_err(|e| ApiError::BadRequest(format!("Invalid leverage, {e}")))?;
+
+    account
+        .validate_notional(&body.symbol, body.margin * body.leverage as f64)
+        .await
+        .map_err(ApiError::BadRequest)?;
+
+    // a halt is the state of the exchange, not a failed request to it
+    account
+        .check_trading_status(&body.symbol)
+        .map_err(ApiError::Conflict)?;
+
+    let last_price = market
+        .last_price(&body.symbol)
+        .await
+        .ok_or_else(|| last_price_not_found(&body.symbol))?;
+
+    if let Some(stop_loss) = body.stop_loss {
+        Position::validate_stop_loss(body.order_side, last_price, stop_loss)
+            .map_err(ApiError::BadRequest)?;
+    }
+    if let Some(take_profit) = body.take_profit {
+        Position::validate_take_profit(body.order_side, last_price, take_profit)
+            .map_err(ApiError::BadRequest)?;
+    }
+
+    let position = account
+        .try_open_position(
+            &body.symbol,
+            body.margin,
+            body.leverage,
+            body.order_side.clone(),
+            last_price,
+            body.strategy_id,
+            body.stop_loss,
+            None,
+        )
+        .await
+        .map_err(|e| match e {
+            OrderError::Rejected(reason) => {
+                ApiError::Conflict(format!("Unable to open position, {reason}"))
             }
-        } else {
-            let json_data = json!({ "error": "Unable to open position" });
-            HttpResponse::ExpectationFailed().json(json_data)
-        }
-    } else {
-        let json_data = json!({ "error": "Unable to get market lock" });
-        HttpResponse::ExpectationFailed().json(json_data)
+            OrderError::Exchange(e) => ApiError::Upstream(format!("Unable to open position, {e}")),
+        })?;
+
+    // the position may fill beyond the take profit, it's kept open without one
+    if let Err(e) = position.set_take_profit(body.take_profit) {
+        warn!(
+            "Position on {} opened without a take profit, {e}",
+            body.symbol
+        );
     }
+
+    let json_data = json!({ "success": "Position Opened", "position": position });
+    Ok(HttpResponse::Ok().json(json_data))
 }
 
 #[get("/active-positions")]
-async fn list_active_positions(app_data: web::Data<AppState>, _req: HttpRequest) -> impl Responder {
+async fn list_active_positions(app_data: web::Data<AppState>, _req: HttpRequest) -> ApiResponse {
     let account = app_data.get_account().await;
     let mut positions = vec![];
 
@@ -143,11 +175,11 @@ async fn list_active_positions(app_data: web::Data<AppState>, _req: HttpRequest)
 
     let json_data = json!({ "positions": positions });
 
-    HttpResponse::Ok().json(json_data)
+    Ok(HttpResponse::Ok().json(json_data))
 }
 
 #[get("/trades")]
-async fn list_trades(app_data: web::Data<AppState>, _req: HttpRequest) -> impl Responder {
+async fn list_trades(app_data: web::Data<AppState>, _req: HttpRequest) -> ApiResponse {
     let account = app_data.get_account().await;
     let mut trades = vec![];
 
@@ -157,17 +189,60 @@ async fn list_trades(app_data: web::Data<AppState>, _req: HttpRequest) -> impl R
 
     let json_data = json!({ "trades": trades });
 
-    HttpResponse::Ok().json(json_data)
+    Ok(HttpResponse::Ok().json(json_data))
 }
 
 #[get("/account-info")]
-async fn account_info(app_data: web::Data<AppState>, _req: HttpRequest) -> impl Responder {
+async fn account_info(app_data: web::Data<AppState>, _req: HttpRequest) -> ApiResponse {
     let account = app_data.get_account().await;
-    let info = account.lock().await.info().await;
+    let info = converted_account_info(app_data.get_market().await, &account).await;
 
     let json_data = json!({ "account_info": info });
 
-    HttpResponse::Ok().json(json_data)
+    Ok(HttpResponse::Ok().json(json_data))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CancelOrdersParams {
+    symbol: String,
+    order_id: Option<String>,
+}
+#[post("/orders/cancel")]
+async fn cancel_orders(
+    app_data: web::Data<AppState>,
+    body: Json<CancelOrdersParams>,
+) -> ApiResponse {
+    let account = app_data.get_account().await;
+
+    let res = account
+        .lock()
+        .await
+        .cancel_orders(&body.symbol, body.order_id.as_deref())
+        .await?;
+
+    let json_data = json!({ "success": "Orders canceled", "response": res });
+    Ok(HttpResponse::Ok().json(json_data))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SetInitialBalanceParams {
+    initial_balance: f64,
+}
+#[post("/set-initial-balance")]
+async fn set_initial_balance(
+    app_data: web::Data<AppState>,
+    body: Json<SetInitialBalanceParams>,
+) -> ApiResponse {
+    let account = app_data.get_account().await;
+    account
+        .lock()
+        .await
+        .set_initial_balance(body.initial_balance);
+    let info = converted_account_info(app_data.get_market().await, &account).await;
+
+    let json_data = json!({ "updated_account": info });
+
+    Ok(HttpResponse::Ok().json(json_data))
 }
 
 #[derive(Debug, Deserialize)]
@@ -179,36 +254,359 @@ struct SetExchangeApiParams {
 async fn set_exchange_api(
     app_data: web::Data<AppState>,
     body: Json<SetExchangeApiParams>,
-) -> impl Responder {
-    let api = match body.exchange.as_str() {
-        "Bing" => app_data.get_exchange_api().await,
-        "Binance" => app_data.get_exchange_api().await,
-        "Mock" => {
-            let api: Arc<dyn ExchangeApi> = Arc::new(MockExchangeApi {});
-            api
-        }
-        _ => {
-            let json_data = json!({ "error": "Unknown exchange API" });
-            return HttpResponse::Ok().json(json_data);
-        }
-    };
+) -> ApiResponse {
+    let api = app_data
+        .bot
+        .lock()
+        .await
+        .named_exchange_api(&body.exchange)
+        .await
+        .map_err(ApiError::BadRequest)?;
 
     let account = app_data.get_account().await;
     account.lock().await.set_exchange_api(api, body.dry_run);
-    let info = account.lock().await.info().await;
+    let info = converted_account_info(app_data.get_market().await, &account).await;
 
     let json_data = json!({ "updated_account": info  });
 
-    HttpResponse::Ok().json(json_data)
+    Ok(HttpResponse::Ok().json(json_data))
+}
+
+#[derive(Debug, Deserialize)]
+struct AddAccountParams {
+    account_id: String,
+    exchange: String,
+    dry_run: bool,
+}
+#[post("/add-account")]
+async fn add_account(app_data: web::Data<AppState>, body: Json<AddAccountParams>) -> ApiResponse {
+    let api = app_data
+        .bot
+        .lock()
+        .await
+        .named_exchange_api(&body.exchange)
+        .await
+        .map_err(ApiError::BadRequest)?;
+
+    let mut bot = app_data.bot.lock().await;
+
+    if bot.get_account(&body.account_id).await.is_some() {
+        return Err(ApiError::BadRequest(format!(
+            "Account {} already exists",
+            body.account_id
+        )));
+    }
+
+    let account = bot.add_account(&body.account_id, api, body.dry_run).await;
+    let info = converted_account_info(bot.market.clone(), &account).await;
+
+    let json_data =
+        json!({ "success": "Account added", "account_id": body.account_id, "account": info });
+    Ok(HttpResponse::Ok().json(json_data))
+}
+
+#[get("/list-accounts")]
+async fn list_accounts(app_data: web::Data<AppState>) -> ApiResponse {
+    let account_ids = app_data.bot.lock().await.list_account_ids().await;
+
+    let json_data = json!({ "accounts": account_ids });
+    Ok(HttpResponse::Ok().json(json_data))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GetAccountHistoryParams {
+    from_ts: Option<String>,
+    to_ts: Option<String>,
+}
+#[get("/history")]
+async fn account_history(
+    app_data: web::Data<AppState>,
+    query: web::Query<GetAccountHistoryParams>,
+) -> ApiResponse {
+    let storage_manager = app_data.get_storage_manager().await;
+
+    let from_ts = parse_optional_date(&query.from_ts)?;
+    let to_ts = parse_optional_date(&query.to_ts)?;
+
+    let snapshots = storage_manager
+        .get_account_snapshots(from_ts, to_ts)
+        .await
+        .map_err(|e| ApiError::Internal(format!("Unable to get account history, {e}")))?;
+
+    let json_data = json!({ "account_history": snapshots });
+    Ok(HttpResponse::Ok().json(json_data))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GetTurnoverParams {
+    from_ts: Option<String>,
+    to_ts: Option<String>,
+}
+#[get("/turnover")]
+async fn account_turnover(
+    app_data: web::Data<AppState>,
+    query: web::Query<GetTurnoverParams>,
+) -> ApiResponse {
+    let from_ts = parse_optional_date(&query.from_ts)?;
+    let to_ts = parse_optional_date(&query.to_ts)?;
+
+    let account = app_data.get_account().await;
+    let report = account.lock().await.turnover_report(from_ts, to_ts);
+
+    let json_data = json!({ "turnover": report });
+    Ok(HttpResponse::Ok().json(json_data))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GetFillsParams {
+    from_ts: Option<String>,
+    to_ts: Option<String>,
+}
+#[get("/fills.csv")]
+async fn export_fills(
+    app_data: web::Data<AppState>,
+    query: web::Query<GetFillsParams>,
+) -> ApiResponse {
+    let from_ts = parse_optional_date(&query.from_ts)?;
+    let to_ts = parse_optional_date(&query.to_ts)?;
+
+    let account = app_data.get_account().await;
+    let mut trades = account.lock().await.trades();
+
+    // trades of stopped strategies are kept with their summaries, only live strategies trade
+    let storage_manager = app_data.get_storage_manager().await;
+    let strategies = storage_manager
+        .list_saved_strategies()
+        .await
+        .map_err(|e| ApiError::Internal(format!("Unable to list saved strategies, {e}")))?;
+
+    for info in strategies {
+        if info.settings.mode != StrategyMode::Live {
+            continue;
+        }
+
+        let summary = storage_manager
+            .get_strategy_summary(info.id)
+            .await
+            .map_err(|e| ApiError::Internal(format!("Unable to get strategy summary, {e}")))?;
+        trades.extend(summary.trades);
+    }
+
+    let csv = fills_csv(&trades, from_ts, to_ts)
+        .map_err(|e| ApiError::Internal(format!("Unable to write fills, {e}")))?;
+
+    Ok(HttpResponse::Ok().content_type("text/csv").body(csv))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SimulateOrderParams {
+    symbol: String,
+    order_side: OrderSide,
+    margin: f64,
+    leverage: u32,
+    entry_price: f64,
+}
+#[post("/simulate-order")]
+async fn simulate_order(
+    app_data: web::Data<AppState>,
+    body: Json<SimulateOrderParams>,
+) -> ApiResponse {
+    if body.margin <= 0.0 || body.entry_price <= 0.0 {
+        return Err(ApiError::BadRequest(
+            "Margin and entry price must be greater than 0".to_string(),
+        ));
+    }
+
+    let account = app_data.get_account().await;
+    let simulation = account
+        .lock()
+        .await
+        .simulate_order(
+            &body.symbol,
+            body.order_side.clone(),
+            body.margin,
+            body.leverage,
+            body.entry_price,
+        )
+        .await;
+
+    let json_data = json!({ "simulation": simulation });
+    Ok(HttpResponse::Ok().json(json_data))
+}
+
+#[get("/reconcile")]
+async fn get_reconciliation(app_data: web::Data<AppState>) -> ApiResponse {
+    let account = app_data.get_account().await;
+
+    // only reports the discrepancies, orphans are adopted or closed with a POST
+    let res = account.lock().await.reconcile(None, &HashMap::new()).await;
+
+    let reconciliation = res?;
+
+    let json_data = json!({ "reconciliation": reconciliation });
+    Ok(HttpResponse::Ok().json(json_data))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ReconcileParams {
+    orphan_action: Option<OrphanAction>,
+}
+#[post("/reconcile")]
+async fn reconcile_positions(
+    app_data: web::Data<AppState>,
+    body: Json<ReconcileParams>,
+) -> ApiResponse {
+    let account = app_data.get_account().await;
+    let market = app_data.get_market().await;
+
+    // orphans are closed at the last price, their symbols are only known from the exchange
+    let mut last_prices = HashMap::new();
+    if body.orphan_action == Some(OrphanAction::Close) {
+        let exchange_api = account.lock().await.exchange_api();
+        if let Ok(positions) = exchange_api.get_positions().await {
+            let market = market.lock().await.reader();
+            for position in positions {
+                if let Some(price) = market.last_price(&position.symbol).await {
+                    last_prices.insert(position.symbol, price);
+                }
+            }
+        }
+    }
+
+    let res = account
+        .lock()
+        .await
+        .reconcile(body.orphan_action, &last_prices)
+        .await;
+
+    let reconciliation = res?;
+
+    let json_data = json!({ "reconciliation": reconciliation });
+    Ok(HttpResponse::Ok().json(json_data))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ScheduleOrderParams {
+    /// Date the order is placed at, ie. `2024-03-01T20:00:00Z`.
+    at_ts: String,
+    symbol: String,
+    order_side: OrderSide,
+    margin: f64,
+    leverage: u32,
+    account_id: Option<String>,
+}
+#[post("/schedule-order")]
+async fn schedule_order(
+    app_data: web::Data<AppState>,
+    body: Json<ScheduleOrderParams>,
+) -> ApiResponse {
+    let at_ts = parse_date(&body.at_ts)?;
+
+    if body.margin <= 0.0 {
+        return Err(ApiError::BadRequest(
+            "Margin must be greater than 0".to_string(),
+        ));
+    }
+
+    let order = ScheduledOrder::new(
+        at_ts,
+        &body.symbol,
+        body.order_side.clone(),
+        body.margin,
+        body.leverage,
+        body.account_id.clone(),
+        generate_ts(),
+    );
+
+    let order = app_data
+        .bot
+        .lock()
+        .await
+        .schedule_order(order)
+        .await
+        .map_err(|e| ApiError::BadRequest(format!("Unable to schedule order, {e}")))?;
+
+    let json_data = json!({ "success": "Order scheduled", "order": order });
+    Ok(HttpResponse::Ok().json(json_data))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CancelScheduledOrderParams {
+    order_id: ScheduledOrderId,
+}
+#[post("/cancel-scheduled-order")]
+async fn cancel_scheduled_order(
+    app_data: web::Data<AppState>,
+    body: Json<CancelScheduledOrderParams>,
+) -> ApiResponse {
+    let order = app_data
+        .bot
+        .lock()
+        .await
+        .cancel_scheduled_order(&body.order_id)
+        .await;
+
+    let order = order.ok_or_else(|| {
+        ApiError::NotFound(format!("Scheduled order {} not found", body.order_id))
+    })?;
+
+    let json_data = json!({ "success": "Scheduled order cancelled", "order": order });
+    Ok(HttpResponse::Ok().json(json_data))
+}
+
+#[get("/scheduled-orders")]
+async fn list_scheduled_orders(app_data: web::Data<AppState>) -> ApiResponse {
+    let orders = app_data.bot.lock().await.list_scheduled_orders().await;
+
+    let json_data = json!({ "scheduled_orders": orders });
+    Ok(HttpResponse::Ok().json(json_data))
+}
+
+/// Error of a request which needs the last price of a symbol without one.
+fn last_price_not_found(symbol: &str) -> ApiError {
+    ApiError::NotFound(format!("Last price of {symbol} not found"))
+}
+
+/// Gets the info of an account, its equity converted at the last prices of the cross pairs.
+async fn converted_account_info(
+    market: ArcMutex<Market>,
+    account: &ArcMutex<Account>,
+) -> AccountInfo {
+    let symbols = account.lock().await.conversion_symbols();
+
+    let reader = market.lock().await.reader();
+    let mut last_prices = HashMap::new();
+    for symbol in symbols {
+        if let Some(price) = reader.last_price(&symbol).await {
+            last_prices.insert(symbol, price);
+        }
+    }
+
+    let mut account = account.lock().await;
+    account.set_conversion_prices(&last_prices);
+    account.info(&last_prices).await
 }
 
 pub fn register_account_service() -> Scope {
     scope("/account")
         .service(account_info)
         .service(set_exchange_api)
+        .service(set_initial_balance)
         .service(open_position)
+        .service(simulate_order)
+        .service(get_reconciliation)
+        .service(reconcile_positions)
         .service(close_position)
         .service(close_all_positions)
+        .service(cancel_orders)
         .service(list_active_positions)
         .service(list_trades)
+        .service(account_history)
+        .service(account_turnover)
+        .service(export_fills)
+        .service(add_account)
+        .service(list_accounts)
+        .service(schedule_order)
+        .service(cancel_scheduled_order)
+        .service(list_scheduled_orders)
 }
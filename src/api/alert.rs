@@ -0,0 +1,142 @@
+use actix_web::{
+    delete, get, post,
+    web::{self, scope, Json},
+    HttpResponse, Scope,
+};
+
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::api::error::{ApiError, ApiResponse};
+use crate::app::AppState;
+use crate::market::alert::{Alert, AlertCondition, AlertId};
+
+#[derive(Debug, Deserialize)]
+pub struct NewAlertParams {
+    symbol: String,
+    condition: AlertCondition,
+    /// Absolute alert price.
+    price: Option<f64>,
+    /// Alert price as a signed percentage change from the last price.
+    pct_change: Option<f64>,
+    one_shot: Option<bool>,
+}
+#[post("")]
+async fn new_alert(app_data: web::Data<AppState>, body: Json<NewAlertParams>) -> ApiResponse {
+    let market = app_data.get_market().await;
+    let (reader, alert_manager) = {
+        let market = market.lock().await;
+        (market.reader(), market.alert_manager())
+    };
+
+    let one_shot = body.one_shot.unwrap_or(true);
+
+    let alert = match (body.price, body.pct_change) {
+        (Some(price), None) => Alert::new(&body.symbol, body.condition, price, one_shot),
+        (None, Some(pct_change)) => {
+            let last_price = reader.last_price(&body.symbol).await.ok_or_else(|| {
+                ApiError::NotFound(format!("Last price of {} not found", body.symbol))
+            })?;
+            Alert::from_pct_change(
+                &body.symbol,
+                body.condition,
+                last_price,
+                pct_change,
+                one_shot,
+            )
+        }
+        _ => {
+            return Err(ApiError::BadRequest(
+                "Either price or pct_change is required".to_string(),
+            ))
+        }
+    };
+
+    let alert = alert_manager.lock().await.add_alert(alert).await;
+
+    let json_data = json!({ "success": "Alert created", "alert": alert });
+    Ok(HttpResponse::Ok().json(json_data))
+}
+
+#[delete("/{alert_id}")]
+async fn delete_alert(app_data: web::Data<AppState>, alert_id: web::Path<AlertId>) -> ApiResponse {
+    let market = app_data.get_market().await;
+    let alert_manager = market.lock().await.alert_manager();
+
+    let alert = alert_manager
+        .lock()
+        .await
+        .remove_alert(&alert_id)
+        .await
+        .ok_or_else(|| ApiError::NotFound(format!("Alert {} not found", *alert_id)))?;
+
+    let json_data = json!({ "success": "Alert deleted", "alert": alert });
+    Ok(HttpResponse::Ok().json(json_data))
+}
+
+#[get("")]
+async fn list_alerts(app_data: web::Data<AppState>) -> ApiResponse {
+    let market = app_data.get_market().await;
+    let alert_manager = market.lock().await.alert_manager();
+
+    let alerts = alert_manager.lock().await.alerts();
+
+    let json_data = json!({ "alerts": alerts });
+    Ok(HttpResponse::Ok().json(json_data))
+}
+
+pub fn register_alert_service() -> Scope {
+    scope("/alerts")
+        .service(new_alert)
+        .service(delete_alert)
+        .service(list_alerts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::Arc;
+
+    use actix_web::{http::StatusCode, test, App};
+    use uuid::Uuid;
+
+    use crate::{
+        exchange::mock::MockExchangeApi, market::types::ArcMutex, testutil::test_bot,
+        utils::json::ResponseFormat,
+    };
+
+    #[actix_web::test]
+    async fn test_alert_errors_use_status_codes() {
+        let bot = test_bot(Arc::new(MockExchangeApi::default())).await;
+        let app_state = web::Data::new(AppState {
+            bot: ArcMutex::new(bot),
+            response_format: ResponseFormat::default(),
+        });
+        let app = test::init_service(
+            App::new()
+                .app_data(app_state)
+                .service(register_alert_service()),
+        )
+        .await;
+
+        // exactly one of price and pct_change is given
+        for body in [
+            json!({ "symbol": "BTCUSDT", "condition": "CrossAbove" }),
+            json!({ "symbol": "BTCUSDT", "condition": "CrossAbove", "price": 100.0, "pct_change": 5.0 }),
+        ] {
+            let req = test::TestRequest::post()
+                .uri("/alerts")
+                .set_json(body)
+                .to_request();
+            let res = test::call_service(&app, req).await;
+            assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+        }
+
+        let req = test::TestRequest::delete()
+            .uri(&format!("/alerts/{}", Uuid::new_v4()))
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+    }
+}
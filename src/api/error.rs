@@ -0,0 +1,163 @@
+use std::fmt;
+
+use actix_web::{http::StatusCode, HttpResponse, ResponseError};
+use serde_json::json;
+
+use crate::{
+    exchange::types::ApiError as ExchangeApiError, strategy::types::AlgoError,
+    utils::time::string_to_timestamp,
+};
+
+/// Result returned by API handlers, errors are rendered as an error envelope.
+pub type ApiResponse = Result<HttpResponse, ApiError>;
+
+/// Error returned by an API endpoint.
+///
+/// Every error is rendered with the status of its variant and the same body,
+/// `{ "error": { "code": "not_found", "message": "Kline data not found" } }`, so clients can
+/// handle failures of all endpoints alike.
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ApiError {
+    /// The request is invalid, ie. a date which can't be parsed.
+    BadRequest(String),
+    /// The requested resource doesn't exist, ie. an unknown strategy.
+    NotFound(String),
+    /// The request conflicts with the current state, ie. a replay is already running.
+    Conflict(String),
+    /// The exchange failed or refused the request.
+    Upstream(String),
+    /// The request failed within the bot, ie. storage couldn't be read.
+    Internal(String),
+}
+
+impl ApiError {
+    /// Machine readable code of the error, included in the response body.
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            ApiError::BadRequest(_) => "bad_request",
+            ApiError::NotFound(_) => "not_found",
+            ApiError::Conflict(_) => "conflict",
+            ApiError::Upstream(_) => "upstream_error",
+            ApiError::Internal(_) => "internal_error",
+        }
+    }
+
+    /// Human readable description of the error.
+
+    pub fn message(&self) -> &str {
+        match self {
+            ApiError::BadRequest(msg)
+            | ApiError::NotFound(msg)
+            | ApiError::Conflict(msg)
+            | ApiError::Upstream(msg)
+            | ApiError::Internal(msg) => msg,
+        }
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.code(), self.message())
+    }
+}
+
+impl ResponseError for ApiError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            ApiError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            ApiError::NotFound(_) => StatusCode::NOT_FOUND,
+            ApiError::Conflict(_) => StatusCode::CONFLICT,
+            ApiError::Upstream(_) => StatusCode::BAD_GATEWAY,
+            ApiError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        HttpResponse::build(self.status_code()).json(json!({
+            "error": { "code": self.code(), "message": self.message() }
+        }))
+    }
+}
+
+impl From<ExchangeApiError> for ApiError {
+    fn from(err: ExchangeApiError) -> Self {
+        ApiError::Upstream(err.to_string())
+    }
+}
+
+impl From<AlgoError> for ApiError {
+    fn from(err: AlgoError) -> Self {
+        match err {
+            AlgoError::Exchange(e) => e.into(),
+            AlgoError::Conflict(msg) => ApiError::Conflict(msg),
+            err => ApiError::BadRequest(err.to_string()),
+        }
+    }
+}
+
+/// Parses a date of a request, ie. `2024-03-01T20:00:00Z`.
+///
+/// # Arguments
+///
+/// * `date` - The date to parse.
+///
+/// # Returns
+///
+/// The timestamp of the date in milliseconds, or a bad request error naming the date.
+
+pub fn parse_date(date: &str) -> Result<u64, ApiError> {
+    string_to_timestamp(date)
+        .map_err(|e| ApiError::BadRequest(format!("Unable to parse date '{date}', {e}")))
+}
+
+/// Parses an optional date of a request, `None` if the date isn't given.
+
+pub fn parse_optional_date(date: &Option<String>) -> Result<Option<u64>, ApiError> {
+    date.as_deref().map(parse_date).transpose()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use actix_web::body::to_bytes;
+    use serde_json::Value;
+
+    #[actix_web::test]
+    async fn test_error_envelope() {
+        let err = ApiError::NotFound("Kline data not found".to_string());
+        assert_eq!(err.status_code(), StatusCode::NOT_FOUND);
+
+        let body = to_bytes(err.error_response().into_body()).await.unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(
+            body,
+            json!({ "error": { "code": "not_found", "message": "Kline data not found" } })
+        );
+
+        let err: ApiError = ExchangeApiError::Network("timed out".to_string()).into();
+        assert_eq!(err.status_code(), StatusCode::BAD_GATEWAY);
+        assert_eq!(err.code(), "upstream_error");
+
+        let err: ApiError =
+            AlgoError::Exchange(ExchangeApiError::Network("timed out".to_string())).into();
+        assert_eq!(err.status_code(), StatusCode::BAD_GATEWAY);
+
+        let err: ApiError = AlgoError::Conflict("Strategy is already running".to_string()).into();
+        assert_eq!(err.status_code(), StatusCode::CONFLICT);
+
+        let err = ApiError::Conflict("Replay is already running".to_string());
+        assert_eq!(err.status_code(), StatusCode::CONFLICT);
+        assert_eq!(err.code(), "conflict");
+
+        let err = parse_date("not a date").unwrap_err();
+        assert_eq!(err.status_code(), StatusCode::BAD_REQUEST);
+        assert_eq!(
+            err.message(),
+            "Unable to parse date 'not a date', date format is not recognised"
+        );
+        assert_eq!(parse_optional_date(&None), Ok(None));
+    }
+}
@@ -0,0 +1,81 @@
+use actix_web::{
+    get,
+    web::{self, scope},
+    HttpResponse, Responder, Scope,
+};
+use serde_json::json;
+
+use crate::{
+    app::AppState,
+    utils::time::{generate_ts, SEC_AS_MILI},
+};
+
+/// Seconds without data after which a market data stream is reported as stale.
+const STALE_STREAM_SECS: u64 = 60;
+
+#[get("")]
+async fn health(app_data: web::Data<AppState>) -> impl Responder {
+    let data_exchange_api = app_data.get_exchange_api().await;
+    let data_exchange = data_exchange_api.health();
+    let (execution_exchange, order_halts) = {
+        let account = app_data.get_account().await;
+        let account = account.lock().await;
+        (
+            account.exchange_api().health(),
+            account.trading_halts().halted(),
+        )
+    };
+    let stream_halts = app_data
+        .get_market()
+        .await
+        .lock()
+        .await
+        .trading_halts()
+        .halted();
+
+    let circuit_open = [&data_exchange, &execution_exchange]
+        .iter()
+        .any(|health| health.as_ref().is_some_and(|health| health.circuit_open));
+
+    let now = generate_ts();
+    let streams = data_exchange_api.active_streams().await;
+    let unhealthy_streams: Vec<&String> = streams
+        .iter()
+        .filter(|meta| !meta.healthy)
+        .map(|meta| &meta.id)
+        .collect();
+    let stale_streams: Vec<&String> = streams
+        .iter()
+        .filter(|meta| meta.is_stale(now, STALE_STREAM_SECS * SEC_AS_MILI))
+        .map(|meta| &meta.id)
+        .collect();
+
+    let degraded = circuit_open
+        || !unhealthy_streams.is_empty()
+        || !stale_streams.is_empty()
+        || !order_halts.is_empty()
+        || !stream_halts.is_empty();
+
+    let json_data = json!({
+        "status": if degraded { "degraded" } else { "ok" },
+        "data_exchange": data_exchange,
+        "execution_exchange": execution_exchange,
+        "unhealthy_streams": unhealthy_streams,
+        "stale_streams": stale_streams,
+        // symbols halted by the exchange, ie. for maintenance, orders and streams resume with them
+        "halted_symbols": {
+            "data_exchange": stream_halts,
+            "execution_exchange": order_halts,
+        },
+    });
+
+    if degraded {
+        HttpResponse::ServiceUnavailable().json(json_data)
+    } else {
+        HttpResponse::Ok().json(json_data)
+    }
+}
+
+pub fn register_health_service() -> Scope {
+    scope("/health").service(health)
+}
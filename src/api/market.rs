@@ -1,69 +1,121 @@
+use std::io;
+
+use actix_web::http::header::ContentType;
 use actix_web::post;
 use actix_web::web::Json;
 use actix_web::{
     get,
     web::{self, scope},
-    HttpResponse, Responder, Scope,
+    HttpResponse, Scope,
 };
 
 use log::info;
 use serde::Deserialize;
-use serde_json::json;
+use serde_json::{json, Value};
 
-use crate::exchange::types::StreamType;
+use crate::exchange::{api::AGG_TRADES_PAGE_LIMIT, stream::build_stream_id, types::StreamType};
 
 use crate::analytics::volume::{PriceVolume, TimeVolume, TradeVolume};
+use crate::api::error::{parse_date, parse_optional_date, ApiError, ApiResponse};
 use crate::app::AppState;
+use crate::market::backfill::{BackfillRequest, MAX_BACKFILL_TRADES};
 use crate::market::interval::Interval;
-use crate::utils::time::string_to_timestamp;
+use crate::market::orderbook::DEFAULT_DEPTH_LEVELS;
+use crate::market::recorder::resolve_log_path;
+use crate::storage::fs::FsStorage;
+use crate::utils::json::{round_market_values, to_json_string};
+use crate::utils::kline::group_missing_open_times;
+
+/// Number of backfills run at the same time if not specified in a backfill batch.
+const DEFAULT_BACKFILL_CONCURRENCY: usize = 4;
+
+/// Maximum number of symbols a single last prices request may ask for.
+const MAX_LAST_PRICES_SYMBOLS: usize = 100;
+
+/// Query flags overriding the global response format of market data endpoints.
+#[derive(Debug, Deserialize)]
+pub struct ResponseFormatParams {
+    /// Keep full float precision instead of rounding to the symbol's precision.
+    raw: Option<bool>,
+    pretty: Option<bool>,
+}
+
+/// Builds a market data response, rounding prices and quantities to the symbol's precision
+/// unless disabled globally or raw precision is requested.
+async fn market_response(
+    app_data: &AppState,
+    symbol: &str,
+    format: &ResponseFormatParams,
+    mut json_data: Value,
+) -> HttpResponse {
+    let response_format = app_data.response_format;
+
+    if response_format.round_precision && !format.raw.unwrap_or(false) {
+        let market = app_data.get_market().await;
+        let symbol_info = market.lock().await.symbol_info();
+        let symbol_info = symbol_info.get(symbol).await;
+
+        match symbol_info {
+            Ok(symbol_info) => round_market_values(&mut json_data, &symbol_info),
+            Err(e) => info!("Unable to round response values for {symbol}: {e}"),
+        }
+    }
+
+    let pretty = format.pretty.unwrap_or(response_format.pretty);
+
+    HttpResponse::Ok()
+        .content_type(ContentType::json())
+        .body(to_json_string(&json_data, pretty))
+}
 
 #[derive(Debug, Deserialize)]
 pub struct GetKlineDataParams {
     symbol: String,
     interval: Interval,
+    /// Excludes the kline in progress, defaults to `true`.
+    closed_only: Option<bool>,
 }
 #[post("/kline-data")]
 async fn get_kline_data(
     app_data: web::Data<AppState>,
     body: Json<GetKlineDataParams>,
-) -> impl Responder {
+    format: web::Query<ResponseFormatParams>,
+) -> ApiResponse {
     let market = app_data.get_market().await;
 
-    let kline_data = market
-        .lock()
-        .await
-        .last_kline(&body.symbol, body.interval)
+    // the kline may be fetched from the exchange, without holding the market lock
+    let reader = market.lock().await.reader();
+    let kline_data = reader
+        .last_kline(
+            &body.symbol,
+            body.interval,
+            body.closed_only.unwrap_or(true),
+        )
         .await;
 
-    if let Some(kline_data) = kline_data {
-        // Return the stream data as JSON
-        let json_data = json!({ "last_kline": kline_data });
-        HttpResponse::Ok().json(json_data)
-    } else {
-        let json_data = json!({ "error": "Kline data not found" });
-        // Stream ID not found
-        HttpResponse::Ok().json(json_data)
-    }
+    let kline_data =
+        kline_data.ok_or_else(|| ApiError::NotFound("Kline data not found".to_string()))?;
+
+    let json_data = json!({ "last_kline": kline_data });
+    Ok(market_response(&app_data, &body.symbol, &format, json_data).await)
 }
 
 #[post("/ticker-data")]
 async fn get_ticker_data(
     app_data: web::Data<AppState>,
     body: Json<GetTickerDataParams>,
-) -> impl Responder {
+    format: web::Query<ResponseFormatParams>,
+) -> ApiResponse {
     let market = app_data.get_market().await;
 
-    let ticker_data = market.lock().await.last_ticker(&body.symbol).await;
+    let reader = market.lock().await.reader();
+    let ticker_data = reader.last_ticker(&body.symbol).await;
 
-    if let Some(ticker_data) = ticker_data {
-        // Return the stream data as JSON
-        let json_data = json!({ "ticker_data": ticker_data });
-        HttpResponse::Ok().json(json_data)
-    } else {
-        let json_data = json!({ "error": "Ticker data not found" });
-        // Stream ID not found
-        HttpResponse::Ok().json(json_data)
-    }
+    let ticker_data =
+        ticker_data.ok_or_else(|| ApiError::NotFound("Ticker data not found".to_string()))?;
+
+    let json_data = json!({ "ticker_data": ticker_data });
+    Ok(market_response(&app_data, &body.symbol, &format, json_data).await)
 }
 
 #[derive(Deserialize, Debug)]
@@ -77,31 +129,12 @@ struct GetMarketTradesParams {
 async fn get_trade_data(
     app_data: web::Data<AppState>,
     body: Json<GetMarketTradesParams>,
-) -> impl Responder {
+    format: web::Query<ResponseFormatParams>,
+) -> ApiResponse {
     let market = app_data.get_market().await;
 
-    let mut from_ts: Option<u64> = None;
-    let mut to_ts: Option<u64> = None;
-
-    if let Some(ts) = &body.to_ts {
-        let _ts = string_to_timestamp(ts);
-        if _ts.is_err() {
-            let json_data = json!({ "error": "Unable to parse dates".to_string()});
-            return HttpResponse::ExpectationFailed().json(json_data);
-        }
-        let _ts = _ts.unwrap();
-        to_ts = Some(_ts);
-    };
-
-    if let Some(ts) = &body.from_ts {
-        let _ts = string_to_timestamp(ts);
-        if _ts.is_err() {
-            let json_data = json!({ "error": "Unable to parse dates".to_string()});
-            return HttpResponse::ExpectationFailed().json(json_data);
-        }
-        let _ts = _ts.unwrap();
-        from_ts = Some(_ts);
-    };
+    let from_ts = parse_optional_date(&body.from_ts)?;
+    let to_ts = parse_optional_date(&body.to_ts)?;
 
     let trade_data = market
         .lock()
@@ -109,16 +142,13 @@ async fn get_trade_data(
         .trade_data_range(&body.symbol, from_ts, to_ts, body.limit)
         .await;
 
-    if let Some(trade_data) = trade_data {
-        let meta = trade_data.meta.clone();
-        let trades = trade_data.trades();
-        let json_data = json!({ "trade_data": {"meta": meta, "trades": trades } } );
-        HttpResponse::Ok().json(json_data)
-    } else {
-        let json_data = json!({ "error": "Trade Data data not found" });
-        // Stream ID not found
-        HttpResponse::Ok().json(json_data)
-    }
+    let trade_data =
+        trade_data.ok_or_else(|| ApiError::NotFound("Trade data not found".to_string()))?;
+
+    let meta = trade_data.meta.clone();
+    let trades = trade_data.trades();
+    let json_data = json!({ "trade_data": {"meta": meta, "trades": trades } } );
+    Ok(market_response(&app_data, &body.symbol, &format, json_data).await)
 }
 
 #[derive(Deserialize)]
@@ -130,37 +160,19 @@ struct GetMarketVolumeParams {
     bucket_size: Option<f64>,
     time_interval: Option<Interval>,
     fixed_price: Option<bool>,
+    /// Decimals bucket volumes are rounded to, the default precision if not set.
+    volume_precision: Option<u32>,
 }
 
 #[post("/trade-volume-data")]
 async fn get_volume_data(
     app_data: web::Data<AppState>,
     body: Json<GetMarketVolumeParams>,
-) -> impl Responder {
+) -> ApiResponse {
     let market = app_data.get_market().await;
 
-    let mut from_ts: Option<u64> = None;
-    let mut to_ts: Option<u64> = None;
-
-    if let Some(ts) = &body.to_ts {
-        let _ts = string_to_timestamp(ts);
-        if _ts.is_err() {
-            let json_data = json!({ "error": "Unable to parse dates".to_string()});
-            return HttpResponse::ExpectationFailed().json(json_data);
-        }
-        let _ts = _ts.unwrap();
-        to_ts = Some(_ts);
-    };
-
-    if let Some(ts) = &body.from_ts {
-        let _ts = string_to_timestamp(ts);
-        if _ts.is_err() {
-            let json_data = json!({ "error": "Unable to parse dates".to_string()});
-            return HttpResponse::ExpectationFailed().json(json_data);
-        }
-        let _ts = _ts.unwrap();
-        from_ts = Some(_ts);
-    };
+    let from_ts = parse_optional_date(&body.from_ts)?;
+    let to_ts = parse_optional_date(&body.to_ts)?;
 
     let trade_data = market
         .lock()
@@ -168,31 +180,34 @@ async fn get_volume_data(
         .trade_data_range(&body.symbol, from_ts, to_ts, body.limit)
         .await;
 
-    if let Some(trade_data) = trade_data {
-        if let Some(interval) = body.time_interval {
-            let mut market_volume = TimeVolume::new(interval);
-            market_volume.add_trades(&trade_data.trades());
-            let bucket_volume = market_volume.result();
-
-            // Return the stream data as JSON
-            let json_data = json!({ "volume_data": bucket_volume });
-            HttpResponse::Ok().json(json_data)
-        } else {
-            let bucket_size = body.bucket_size.unwrap_or_else(|| 10.0);
-            let fixed_price = body.fixed_price.unwrap_or_else(|| true);
-
-            let mut market_volume = PriceVolume::new(bucket_size, fixed_price);
-
-            market_volume.add_trades(&trade_data.trades());
-            let bucket_volume = market_volume.result();
-            // Return the stream data as JSON
-            let json_data = json!({ "volume_data": bucket_volume });
-            HttpResponse::Ok().json(json_data)
+    let trade_data =
+        trade_data.ok_or_else(|| ApiError::NotFound("Trade data not found".to_string()))?;
+
+    if let Some(interval) = body.time_interval {
+        let mut market_volume = TimeVolume::new(interval);
+        if let Some(volume_precision) = body.volume_precision {
+            market_volume.set_volume_precision(volume_precision);
         }
+        market_volume.add_trades(&trade_data.trades());
+        let bucket_volume = market_volume.result();
+
+        // Return the stream data as JSON
+        let json_data = json!({ "volume_data": bucket_volume });
+        Ok(HttpResponse::Ok().json(json_data))
     } else {
-        let json_data = json!({ "error": "Trade Data data not found" });
-        // Stream ID not found
-        HttpResponse::Ok().json(json_data)
+        let bucket_size = body.bucket_size.unwrap_or_else(|| 10.0);
+        let fixed_price = body.fixed_price.unwrap_or_else(|| true);
+
+        let mut market_volume = PriceVolume::new(bucket_size, fixed_price);
+        if let Some(volume_precision) = body.volume_precision {
+            market_volume.set_volume_precision(volume_precision);
+        }
+
+        market_volume.add_trades(&trade_data.trades());
+        let bucket_volume = market_volume.result();
+        // Return the stream data as JSON
+        let json_data = json!({ "volume_data": bucket_volume });
+        Ok(HttpResponse::Ok().json(json_data))
     }
 }
 
@@ -208,31 +223,12 @@ pub struct GetKlineDataRangeParams {
 async fn get_kline_data_range(
     app_data: web::Data<AppState>,
     body: Json<GetKlineDataRangeParams>,
-) -> impl Responder {
+    format: web::Query<ResponseFormatParams>,
+) -> ApiResponse {
     let market = app_data.get_market().await;
 
-    let mut from_ts: Option<u64> = None;
-    let mut to_ts: Option<u64> = None;
-
-    if let Some(ts) = &body.to_ts {
-        let _ts = string_to_timestamp(ts);
-        if _ts.is_err() {
-            let json_data = json!({ "error": "Unable to parse dates".to_string()});
-            return HttpResponse::ExpectationFailed().json(json_data);
-        }
-        let _ts = _ts.unwrap();
-        to_ts = Some(_ts);
-    };
-
-    if let Some(ts) = &body.from_ts {
-        let _ts = string_to_timestamp(ts);
-        if _ts.is_err() {
-            let json_data = json!({ "error": "Unable to parse dates".to_string()});
-            return HttpResponse::ExpectationFailed().json(json_data);
-        }
-        let _ts = _ts.unwrap();
-        from_ts = Some(_ts);
-    };
+    let from_ts = parse_optional_date(&body.from_ts)?;
+    let to_ts = parse_optional_date(&body.to_ts)?;
 
     let kline_data = market
         .lock()
@@ -240,15 +236,69 @@ async fn get_kline_data_range(
         .kline_data_range(&body.symbol, body.interval, from_ts, to_ts, body.limit)
         .await;
 
-    if let Some(kline_data) = kline_data {
-        // Return the stream data as JSON
-        let json_data = json!({ "kline_data": kline_data });
-        HttpResponse::Ok().json(json_data)
+    let kline_data =
+        kline_data.ok_or_else(|| ApiError::NotFound("Kline data not found".to_string()))?;
+
+    let json_data = json!({ "kline_data": kline_data });
+    Ok(market_response(&app_data, &body.symbol, &format, json_data).await)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GetKlineGapsParams {
+    symbol: String,
+    interval: Interval,
+    from_ts: String,
+    to_ts: String,
+    /// Backfills the missing klines from the exchange after they are found.
+    backfill: Option<bool>,
+}
+#[get("/gaps")]
+async fn get_kline_gaps(
+    app_data: web::Data<AppState>,
+    query: web::Query<GetKlineGapsParams>,
+) -> ApiResponse {
+    let market = app_data.get_market().await;
+
+    let from_ts = parse_date(&query.from_ts)?;
+    let to_ts = parse_date(&query.to_ts)?;
+
+    let missing = market
+        .lock()
+        .await
+        .kline_gaps(&query.symbol, query.interval, from_ts, to_ts)
+        .await
+        .map_err(ApiError::BadRequest)?;
+
+    let backfill_results = if query.backfill.unwrap_or(false) {
+        let requests: Vec<BackfillRequest> = group_missing_open_times(&missing, query.interval)
+            .into_iter()
+            .map(|(from_ts, to_ts)| BackfillRequest {
+                symbol: query.symbol.clone(),
+                interval: query.interval,
+                from_ts,
+                to_ts,
+            })
+            .collect();
+
+        // the market lock is released before the klines are fetched
+        let backfiller = market.lock().await.backfiller();
+        backfiller
+            .backfill_many(requests, DEFAULT_BACKFILL_CONCURRENCY)
+            .await
     } else {
-        let json_data = json!({ "error": "Kline data not found" });
-        // Stream ID not found
-        HttpResponse::Ok().json(json_data)
-    }
+        vec![]
+    };
+
+    let json_data = json!({
+        "symbol": query.symbol,
+        "interval": query.interval,
+        "from_ts": from_ts,
+        "to_ts": to_ts,
+        "num_missing": missing.len(),
+        "missing": missing,
+        "backfill_results": backfill_results
+    });
+    Ok(HttpResponse::Ok().json(json_data))
 }
 
 #[derive(Debug, Deserialize)]
@@ -260,38 +310,129 @@ pub struct GetTickerDataParams {
 async fn last_price(
     app_data: web::Data<AppState>,
     body: Json<GetTickerDataParams>,
-) -> impl Responder {
+    format: web::Query<ResponseFormatParams>,
+) -> ApiResponse {
     let market = app_data.get_market().await;
 
-    let last_price = market.lock().await.last_price(&body.symbol).await;
+    let reader = market.lock().await.reader();
+    let last_price = reader.last_price(&body.symbol).await;
 
-    if let Some(last_price) = last_price {
-        // Return the stream data as JSON
-        let json_data = json!({ "last_price": last_price,"symbol":body.symbol });
-        HttpResponse::Ok().json(json_data)
-    } else {
-        let json_data = json!({ "error": "Last price not found","symbol":body.symbol });
-        // Stream ID not found
-        HttpResponse::Ok().json(json_data)
+    let last_price = last_price
+        .ok_or_else(|| ApiError::NotFound(format!("Last price of {} not found", body.symbol)))?;
+
+    let json_data = json!({ "last_price": last_price,"symbol":body.symbol });
+    Ok(market_response(&app_data, &body.symbol, &format, json_data).await)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LastPricesParams {
+    symbols: Vec<String>,
+}
+#[post("/last-prices")]
+async fn last_prices(app_data: web::Data<AppState>, body: Json<LastPricesParams>) -> ApiResponse {
+    if body.symbols.len() > MAX_LAST_PRICES_SYMBOLS {
+        return Err(ApiError::BadRequest(format!(
+            "At most {MAX_LAST_PRICES_SYMBOLS} symbols can be requested at once"
+        )));
     }
+
+    let market = app_data.get_market().await;
+    let reader = market.lock().await.reader();
+    let last_prices = reader.last_prices(&body.symbols).await;
+
+    let missing: Vec<&String> = body
+        .symbols
+        .iter()
+        .filter(|symbol| !last_prices.contains_key(*symbol))
+        .collect();
+
+    let json_data = json!({ "last_prices": last_prices, "missing": missing });
+    Ok(HttpResponse::Ok().json(json_data))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GetDepthParams {
+    symbol: String,
+    levels: Option<usize>,
+}
+#[get("/depth")]
+async fn get_depth(
+    app_data: web::Data<AppState>,
+    query: web::Query<GetDepthParams>,
+) -> ApiResponse {
+    let market = app_data.get_market().await;
+
+    let depth = market
+        .lock()
+        .await
+        .order_book_depth(&query.symbol, query.levels.unwrap_or(DEFAULT_DEPTH_LEVELS))
+        .await;
+
+    let depth = depth.ok_or_else(|| {
+        ApiError::NotFound(format!(
+            "Order book of {} not found, open a depth stream first",
+            query.symbol
+        ))
+    })?;
+
+    let json_data = json!({ "depth": depth });
+    Ok(HttpResponse::Ok().json(json_data))
+}
+
+#[get("/stats")]
+async fn market_stats(app_data: web::Data<AppState>) -> ApiResponse {
+    let storage_manager = app_data.get_storage_manager().await;
+
+    let coverage = storage_manager
+        .data_coverage()
+        .await
+        .map_err(|e| ApiError::Internal(format!("Unable to get data coverage, {e}")))?;
+
+    let json_data = json!({ "klines": coverage.klines, "trades": coverage.trades });
+    Ok(HttpResponse::Ok().json(json_data))
 }
 
 #[get("/info")]
-async fn market_info(app_data: web::Data<AppState>) -> impl Responder {
+async fn market_info(app_data: web::Data<AppState>) -> ApiResponse {
     let market = app_data.get_market().await;
 
     let info = market.lock().await.info().await;
     let json_data = json!({ "market_info": info });
-    HttpResponse::Ok().json(json_data)
+    Ok(HttpResponse::Ok().json(json_data))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LatestKlinesParams {
+    interval: Interval,
+}
+#[get("/latest-klines")]
+async fn latest_klines(
+    app_data: web::Data<AppState>,
+    params: web::Query<LatestKlinesParams>,
+) -> ApiResponse {
+    let market = app_data.get_market().await;
+    let klines = market.lock().await.latest_klines(params.interval).await;
+
+    let json_data = json!({ "klines": klines });
+    Ok(HttpResponse::Ok().json(json_data))
+}
+
+#[get("/flow-stats")]
+async fn flow_stats(app_data: web::Data<AppState>) -> ApiResponse {
+    let market = app_data.get_market().await;
+    let flow_stats = market.lock().await.flow_stats().await;
+
+    let json_data = json!({ "flow_stats": flow_stats });
+    Ok(HttpResponse::Ok().json(json_data))
 }
 
 #[get("/active-streams")]
-async fn active_streams(app_data: web::Data<AppState>) -> impl Responder {
+async fn active_streams(app_data: web::Data<AppState>) -> ApiResponse {
     let market = app_data.get_market().await;
     let active_streams = market.lock().await.active_streams().await;
     // Return the stream data as JSON
     let json_data = json!({ "active_streams": active_streams });
-    HttpResponse::Ok().json(json_data)
+    Ok(HttpResponse::Ok().json(json_data))
 }
 
 #[derive(Debug, Deserialize)]
@@ -299,27 +440,26 @@ pub struct CloseStreamParams {
     stream_id: String,
 }
 #[post("/close-stream")]
-async fn close_stream(
-    app_data: web::Data<AppState>,
-    body: Json<CloseStreamParams>,
-) -> HttpResponse {
+async fn close_stream(app_data: web::Data<AppState>, body: Json<CloseStreamParams>) -> ApiResponse {
     let market = app_data.get_market().await;
 
-    let stream_meta = market.lock().await.close_stream(&body.stream_id).await;
+    // the stream stays open while strategies or websocket clients still subscribe to it
+    let unsubscribed = market
+        .lock()
+        .await
+        .unsubscribe_stream_by_id(&body.stream_id)
+        .await;
 
-    // TODO: handle error
-    match stream_meta {
-        Some(meta) => {
-            let json_data = json!({ "success": "Stream closed successfully","stream_meta":meta });
-            HttpResponse::Ok().json(json_data)
-        }
-        None => {
-            let json_data =
-                json!({ "error": format!("Stream width ID {} not found", &body.stream_id) });
-            // Stream ID not found
-            HttpResponse::Ok().json(json_data)
-        }
-    }
+    let (meta, subscribers) = unsubscribed.ok_or_else(|| {
+        ApiError::NotFound(format!("Stream with ID {} not found", body.stream_id))
+    })?;
+
+    let json_data = json!({
+        "success": "Stream unsubscribed successfully",
+        "stream_meta": meta,
+        "subscribers": subscribers
+    });
+    Ok(HttpResponse::Ok().json(json_data))
 }
 
 #[derive(Debug, Deserialize)]
@@ -329,62 +469,275 @@ pub struct OpenStreamParams {
     interval: Option<Interval>,
 }
 #[post("/open-stream")]
-async fn open_stream(
-    app_data: web::Data<AppState>,
-    body: Json<OpenStreamParams>,
-) -> impl Responder {
+async fn open_stream(app_data: web::Data<AppState>, body: Json<OpenStreamParams>) -> ApiResponse {
     let stream_type = body.stream_type.clone();
     let market = app_data.get_market().await;
 
-    // TODO: handle errors
     let symbol = body.symbol.to_string();
-
-    let stream_id = match stream_type {
-        StreamType::Kline => {
-            market
-                .lock()
-                .await
-                .open_stream(stream_type, &symbol, body.interval)
-                .await
-        }
-        StreamType::Ticker => {
-            market
-                .lock()
-                .await
-                .open_stream(stream_type, &symbol, None)
-                .await
-        }
-        StreamType::Trade => {
-            market
-                .lock()
-                .await
-                .open_stream(stream_type, &symbol, None)
-                .await
-        }
+    let interval = match stream_type {
+        StreamType::Kline => body.interval,
+        _ => None,
     };
 
-    let data = match stream_id {
-        Ok(stream_id) => {
-            json!({ "success": "Stream created","stream_id":stream_id })
-        }
-        Err(e) => {
-            json!({ "error": "Unable to open stream","msg":e.to_string() })
-        }
-    };
+    // shares the stream with strategies and websocket clients, it stays open until /close-stream
+    let subscribers = market
+        .lock()
+        .await
+        .subscribe_stream(stream_type, &symbol, interval)
+        .await?;
+
+    let stream_id = build_stream_id(&symbol, stream_type, interval);
+    let json_data = json!({
+        "success": "Stream created",
+        "stream_id": stream_id,
+        "subscribers": subscribers
+    });
+    Ok(HttpResponse::Ok().json(json_data))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BackfillParams {
+    symbol: String,
+    interval: Interval,
+    from_ts: String,
+    to_ts: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BackfillBatchParams {
+    requests: Vec<BackfillParams>,
+    concurrency: Option<usize>,
+}
+#[post("/backfill-batch")]
+async fn backfill_batch(
+    app_data: web::Data<AppState>,
+    body: Json<BackfillBatchParams>,
+) -> ApiResponse {
+    let mut requests = vec![];
+
+    for params in &body.requests {
+        requests.push(BackfillRequest {
+            symbol: params.symbol.clone(),
+            interval: params.interval,
+            from_ts: parse_date(&params.from_ts)?,
+            to_ts: parse_date(&params.to_ts)?,
+        });
+    }
+
+    let market = app_data.get_market().await;
+    let concurrency = body.concurrency.unwrap_or(DEFAULT_BACKFILL_CONCURRENCY);
+
+    let backfiller = market.lock().await.backfiller();
+    let results = backfiller.backfill_many(requests, concurrency).await;
+
+    let num_failed = results.iter().filter(|result| !result.is_success()).count();
+    let klines_saved: usize = results.iter().map(|result| result.klines_saved).sum();
+
+    let json_data = json!({
+        "num_succeeded": results.len() - num_failed,
+        "num_failed": num_failed,
+        "klines_saved": klines_saved,
+        "results": results
+    });
+    Ok(HttpResponse::Ok().json(json_data))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BackfillTradesParams {
+    symbol: String,
+    limit: Option<usize>,
+}
+#[post("/backfill-trades")]
+async fn backfill_trades(
+    app_data: web::Data<AppState>,
+    body: Json<BackfillTradesParams>,
+) -> ApiResponse {
+    let limit = body.limit.unwrap_or(AGG_TRADES_PAGE_LIMIT);
+    if limit > MAX_BACKFILL_TRADES {
+        return Err(ApiError::BadRequest(format!(
+            "At most {MAX_BACKFILL_TRADES} trades can be backfilled at once"
+        )));
+    }
+
+    let market = app_data.get_market().await;
+    let backfiller = market.lock().await.backfiller();
+    let trades_saved = backfiller
+        .backfill_trades(&body.symbol, limit)
+        .await
+        .map_err(ApiError::Upstream)?;
+
+    let json_data = json!({ "symbol": body.symbol, "trades_saved": trades_saved });
+    Ok(HttpResponse::Ok().json(json_data))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CompactKlinesParams {
+    symbol: String,
+    interval: Interval,
+}
+#[post("/compact")]
+async fn compact_klines(
+    app_data: web::Data<AppState>,
+    body: Json<CompactKlinesParams>,
+) -> ApiResponse {
+    let storage_manager = app_data.get_storage_manager().await;
+
+    let report = storage_manager
+        .compact_klines(&body.symbol, body.interval)
+        .await
+        .map_err(|e| ApiError::Internal(format!("Unable to compact klines, {e}")))?;
+
+    let json_data = json!({ "compaction": report });
+    Ok(HttpResponse::Ok().json(json_data))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ReplayParams {
+    path: String,
+    speed: Option<f64>,
+}
+#[post("/replay")]
+async fn replay_market_messages(
+    app_data: web::Data<AppState>,
+    body: Json<ReplayParams>,
+) -> ApiResponse {
+    // only logs within the data directory can be replayed
+    let data_directory = FsStorage::create_app_directory();
+    let path = resolve_log_path(&data_directory, &body.path).map_err(|e| match e.kind() {
+        io::ErrorKind::PermissionDenied => ApiError::BadRequest(e.to_string()),
+        _ => ApiError::NotFound(format!("Market message log {} not found", body.path)),
+    })?;
+
+    // replay runs in the background, messages are sent to the market as they are read
+    let status = app_data
+        .bot
+        .lock()
+        .await
+        .replay_market_messages(path, body.speed.unwrap_or(1.0))
+        .map_err(|running| {
+            ApiError::Conflict(format!(
+                "Replay of {} is already running",
+                running.path.display()
+            ))
+        })?;
+
+    let json_data = json!({ "success": "Replay started", "replay": status });
+    Ok(HttpResponse::Ok().json(json_data))
+}
 
-    HttpResponse::Ok().json(data)
+#[get("/replay")]
+async fn replay_status(app_data: web::Data<AppState>) -> ApiResponse {
+    let status = app_data
+        .bot
+        .lock()
+        .await
+        .replay_status()
+        .ok_or_else(|| ApiError::NotFound("No replay has been started".to_string()))?;
+
+    let json_data = json!({ "replay": status });
+    Ok(HttpResponse::Ok().json(json_data))
+}
+
+#[post("/replay/cancel")]
+async fn cancel_replay(app_data: web::Data<AppState>) -> ApiResponse {
+    let status = app_data
+        .bot
+        .lock()
+        .await
+        .cancel_replay()
+        .ok_or_else(|| ApiError::NotFound("No replay is running".to_string()))?;
+
+    let json_data = json!({ "success": "Replay cancelled", "replay": status });
+    Ok(HttpResponse::Ok().json(json_data))
 }
 
 pub fn register_market_service() -> Scope {
     scope("/market")
         .service(last_price)
+        .service(last_prices)
+        .service(get_depth)
         .service(close_stream)
         .service(open_stream)
         .service(get_kline_data)
         .service(get_kline_data_range)
+        .service(get_kline_gaps)
         .service(market_info)
+        .service(market_stats)
         .service(active_streams)
+        .service(latest_klines)
+        .service(flow_stats)
         .service(get_ticker_data)
         .service(get_trade_data)
         .service(get_volume_data)
+        .service(replay_market_messages)
+        .service(replay_status)
+        .service(cancel_replay)
+        .service(backfill_batch)
+        .service(backfill_trades)
+        .service(compact_klines)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::Arc;
+
+    use actix_web::{http::StatusCode, test, App};
+
+    use crate::{
+        exchange::{mock::MockExchangeApi, types::ApiError as ExchangeApiError},
+        market::types::ArcMutex,
+        testutil::test_bot,
+        utils::json::ResponseFormat,
+    };
+
+    async fn test_app_state() -> web::Data<AppState> {
+        // klines missing from the market can't be fetched from the exchange either
+        let bot = test_bot(Arc::new(MockExchangeApi::with_kline_error(
+            ExchangeApiError::Network("Connection refused".to_string()),
+        )))
+        .await;
+
+        web::Data::new(AppState {
+            bot: ArcMutex::new(bot),
+            response_format: ResponseFormat::default(),
+        })
+    }
+
+    #[actix_web::test]
+    async fn test_error_responses_use_status_codes() {
+        let app = test::init_service(
+            App::new()
+                .app_data(test_app_state().await)
+                .service(register_market_service()),
+        )
+        .await;
+
+        // missing kline is a not found error, not an empty success
+        let req = test::TestRequest::post()
+            .uri("/market/kline-data")
+            .set_json(json!({ "symbol": "BTCUSDT", "interval": "1m" }))
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+
+        let body: Value = test::read_body_json(res).await;
+        assert_eq!(body["error"]["code"], "not_found");
+        assert_eq!(body["error"]["message"], "Kline data not found");
+
+        let req = test::TestRequest::post()
+            .uri("/market/kline-data-range")
+            .set_json(json!({
+                "symbol": "BTCUSDT",
+                "interval": "1m",
+                "from_ts": "not a date"
+            }))
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+
+        let body: Value = test::read_body_json(res).await;
+        assert_eq!(body["error"]["code"], "bad_request");
+    }
 }
@@ -1,6 +1,10 @@
 pub mod account;
+pub mod alert;
+pub mod error;
 pub mod exchange;
+pub mod health;
 pub mod main;
 pub mod market;
 pub mod strategy;
 pub mod utils;
+pub mod ws;
@@ -1,18 +1,24 @@
+use actix_web::http::header::ContentType;
 use actix_web::web::Json;
 use actix_web::{
-    get, post,
+    get, post, routes,
     web::{self, scope},
-    HttpResponse, Responder, Scope,
+    HttpResponse, Scope,
 };
 
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
 
-use crate::account::trade::Position;
+use crate::account::{account::AccountId, trade::Position};
+use crate::api::error::{parse_date, ApiError, ApiResponse};
 use crate::app::AppState;
 use crate::market::interval::Interval;
-use crate::strategy::strategy::{StrategyId, StrategySettings};
-use crate::utils::time::string_to_timestamp;
+use crate::strategy::report::ReportFormat;
+use crate::strategy::signal::SignalSink;
+use crate::strategy::strategy::{
+    deserialize_stop_loss, PromotionPolicy, SizingMode, StopLoss, StrategyErrorPolicy, StrategyId,
+    StrategyMode, StrategySettings,
+};
 
 #[derive(Debug, Deserialize)]
 pub struct NewStrategyParams {
@@ -22,19 +28,51 @@ pub struct NewStrategyParams {
     interval: Interval,
     margin: Option<f64>,
     leverage: Option<u32>,
+    signal_cooldown_ms: Option<u64>,
+    min_flip_move_pct: Option<f64>,
+    sizing_mode: Option<SizingMode>,
+    #[serde(default, deserialize_with = "deserialize_stop_loss")]
+    stop_loss: Option<StopLoss>,
+    capital_allocation_usd: Option<f64>,
+    account_id: Option<AccountId>,
+    min_kline_volume: Option<f64>,
+    eval_log_size: Option<usize>,
+    eval_offset_secs: Option<u64>,
+    error_policy: Option<StrategyErrorPolicy>,
+    max_hold_secs: Option<u64>,
+    mode: Option<StrategyMode>,
+    signal_sinks: Option<Vec<SignalSink>>,
+    promote_after: Option<PromotionPolicy>,
 }
+#[routes]
 #[post("/new-strategy")]
+#[post("/start")]
 async fn new_strategy(
     app_data: web::Data<AppState>,
     body: web::Json<NewStrategyParams>,
-) -> impl Responder {
+) -> ApiResponse {
     let bot = app_data.bot.clone();
 
     let settings = StrategySettings {
         max_open_orders: 2,
         margin_usd: body.margin.unwrap_or(1000.0),
         leverage: body.leverage.unwrap_or(10),
-        stop_loss: None,
+        stop_loss: body.stop_loss,
+        signal_cooldown_ms: body.signal_cooldown_ms.unwrap_or(0),
+        min_flip_move_pct: body.min_flip_move_pct.unwrap_or(0.0),
+        sizing_mode: body.sizing_mode,
+        capital_allocation_usd: body.capital_allocation_usd,
+        account_id: body.account_id.clone(),
+        min_kline_volume: body.min_kline_volume,
+        eval_log_size: body.eval_log_size,
+        eval_offset_secs: body.eval_offset_secs,
+        error_policy: body.error_policy.unwrap_or_default(),
+        max_hold_secs: body.max_hold_secs,
+        mode: body.mode.unwrap_or_default(),
+        signal_sinks: body.signal_sinks.clone().unwrap_or_default(),
+        promote_after: body.promote_after,
+        // only back tests record an audit
+        audit_klines: false,
     };
 
     let info = bot
@@ -49,17 +87,10 @@ async fn new_strategy(
         )
         .await;
 
-    match info {
-        Ok(info) => {
-            let json_data = json!({ "success": "Strategy started", "strategy_info": info });
+    let info = info?;
+    let json_data = json!({ "success": "Strategy started", "strategy_info": info });
 
-            HttpResponse::Ok().json(json_data)
-        }
-        Err(e) => {
-            let json_data = json!({ "error": e.to_string()});
-            HttpResponse::ExpectationFailed().json(json_data)
-        }
-    }
+    Ok(HttpResponse::Ok().json(json_data))
 }
 
 #[derive(Debug, Deserialize)]
@@ -71,7 +102,7 @@ pub struct GetStrategyParams {
 async fn stop_strategy(
     app_data: web::Data<AppState>,
     body: web::Json<GetStrategyParams>,
-) -> impl Responder {
+) -> ApiResponse {
     let bot = app_data.bot.clone();
 
     let close_positions = body.close_positions.unwrap_or(true);
@@ -82,25 +113,17 @@ async fn stop_strategy(
         .stop_strategy(body.strategy_id, close_positions)
         .await;
 
-    match summary {
-        Some(summary) => {
-            let json_data = json!({ "success": "Strategy stopped","strategy_summary":summary });
+    let summary = summary.ok_or_else(|| strategy_not_found(body.strategy_id))?;
+    let json_data = json!({ "success": "Strategy stopped","strategy_summary":summary });
 
-            HttpResponse::Ok().json(json_data)
-        }
-        None => {
-            let json_data = json!({ "error": "Strategy not found","strategy_id":body.strategy_id });
-
-            HttpResponse::BadRequest().json(json_data)
-        }
-    }
+    Ok(HttpResponse::Ok().json(json_data))
 }
 
 #[post("/list-positions")]
 async fn list_strategy_positions(
     app_data: web::Data<AppState>,
     body: web::Json<GetStrategyParams>,
-) -> impl Responder {
+) -> ApiResponse {
     let account = app_data.get_account().await;
 
     let positions: Vec<Position> = account
@@ -113,46 +136,104 @@ async fn list_strategy_positions(
 
     let json_data = json!({ "strategy_positions": positions });
 
-    HttpResponse::Ok().json(json_data)
+    Ok(HttpResponse::Ok().json(json_data))
 }
 
 #[post("/summary")]
 async fn active_strategy_summary(
     app_data: web::Data<AppState>,
     body: web::Json<GetStrategyParams>,
-) -> impl Responder {
+) -> ApiResponse {
     let mut bot = app_data.bot.lock().await;
 
-    if let Some(summary) = bot.get_strategy_summary(body.strategy_id).await {
-        let json_data = json!({ "strategy_summary": summary });
-
-        return HttpResponse::Ok().json(json_data);
-    };
-
-    let json_data = json!({ "error": "Unable to find strategy", "strategy_id": body.strategy_id });
+    let summary = bot
+        .get_strategy_summary(body.strategy_id)
+        .await
+        .ok_or_else(|| strategy_not_found(body.strategy_id))?;
+    let json_data = json!({ "strategy_summary": summary });
 
-    HttpResponse::ExpectationFailed().json(json_data)
+    Ok(HttpResponse::Ok().json(json_data))
 }
 
 #[post("/info")]
 async fn strategy_info(
     app_data: web::Data<AppState>,
     body: web::Json<GetStrategyParams>,
-) -> impl Responder {
+) -> ApiResponse {
     let mut bot = app_data.bot.lock().await;
-    if let Some(info) = bot.get_strategy_info(body.strategy_id).await {
-        let json_data = json!({ "strategy_info": info });
+    let info = bot
+        .get_strategy_info(body.strategy_id)
+        .await
+        .ok_or_else(|| strategy_not_found(body.strategy_id))?;
+    let json_data = json!({ "strategy_info": info });
 
-        return HttpResponse::Ok().json(json_data);
-    };
+    Ok(HttpResponse::Ok().json(json_data))
+}
 
-    let json_data = json!({ "error": "Unable to find strategy" });
+#[post("/drawdown-timeline")]
+async fn strategy_drawdown_timeline(
+    app_data: web::Data<AppState>,
+    body: web::Json<GetStrategyParams>,
+) -> ApiResponse {
+    let mut bot = app_data.bot.lock().await;
 
-    HttpResponse::ExpectationFailed().json(json_data)
+    let (summary, timeline) = bot
+        .get_strategy_drawdown_timeline(body.strategy_id)
+        .await
+        .ok_or_else(|| strategy_not_found(body.strategy_id))?;
+    let json_data = json!({
+        "strategy_id": body.strategy_id,
+        "max_drawdown": summary.max_drawdown,
+        "max_profit": summary.max_profit,
+        "timeline": timeline
+    });
+
+    Ok(HttpResponse::Ok().json(json_data))
+}
+
+#[get("/{strategy_id}/logs")]
+async fn strategy_eval_log(
+    app_data: web::Data<AppState>,
+    strategy_id: web::Path<StrategyId>,
+) -> ApiResponse {
+    let mut bot = app_data.bot.lock().await;
+
+    let logs = bot
+        .get_strategy_eval_log(*strategy_id)
+        .await
+        .ok_or_else(|| strategy_not_found(*strategy_id))?;
+    let json_data = json!({ "strategy_id": *strategy_id, "logs": logs });
+
+    Ok(HttpResponse::Ok().json(json_data))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct StrategyReportParams {
+    format: Option<ReportFormat>,
+}
+#[get("/{strategy_id}/report")]
+async fn strategy_report(
+    app_data: web::Data<AppState>,
+    strategy_id: web::Path<StrategyId>,
+    query: web::Query<StrategyReportParams>,
+) -> ApiResponse {
+    let mut bot = app_data.bot.lock().await;
+
+    let report = bot
+        .get_strategy_report(*strategy_id)
+        .await
+        .ok_or_else(|| strategy_not_found(*strategy_id))?;
+
+    match query.format.unwrap_or_default() {
+        ReportFormat::Html => Ok(HttpResponse::Ok()
+            .content_type(ContentType::html())
+            .body(report.to_html())),
+        ReportFormat::Json => Ok(HttpResponse::Ok().json(json!({ "report": report }))),
+    }
 }
 
 #[get("/active-strategies")]
-async fn list_active_strategies(app_data: web::Data<AppState>) -> impl Responder {
+async fn list_active_strategies(app_data: web::Data<AppState>) -> ApiResponse {
     let bot = app_data.bot.clone();
 
     let strategy_ids = bot.lock().await.get_active_strategy_ids().await;
@@ -167,41 +248,64 @@ async fn list_active_strategies(app_data: web::Data<AppState>) -> impl Responder
 
     let json_data = json!({ "strategy_infos": infos });
 
-    HttpResponse::Ok().json(json_data)
+    Ok(HttpResponse::Ok().json(json_data))
+}
+
+#[get("/failed-signals")]
+async fn list_failed_signals(app_data: web::Data<AppState>) -> ApiResponse {
+    let failed_signals = app_data.bot.lock().await.get_failed_signals().await;
+
+    let json_data = json!({ "failed_signals": failed_signals });
+
+    Ok(HttpResponse::Ok().json(json_data))
 }
 
 #[get("/historical-strategies")]
-async fn list_historical_strategies(app_data: web::Data<AppState>) -> impl Responder {
+async fn list_historical_strategies(app_data: web::Data<AppState>) -> ApiResponse {
     let bot = app_data.bot.clone();
 
     let summaries = bot.lock().await.list_historical_strategies().await;
 
     let json_data = json!({ "strategy_infos": summaries });
 
-    HttpResponse::Ok().json(json_data)
+    Ok(HttpResponse::Ok().json(json_data))
 }
 
 #[post("/historical-summary")]
 async fn historical_strategy_summary(
     app_data: web::Data<AppState>,
     body: Json<GetStrategyParams>,
-) -> impl Responder {
-    if let Some(summary) = app_data
+) -> ApiResponse {
+    let summary = app_data
         .bot
         .lock()
         .await
         .get_historical_strategy_summary(body.strategy_id)
         .await
-    {
-        let json_data = json!({ "strategy_summary": summary });
+        .ok_or_else(|| {
+            ApiError::NotFound(format!(
+                "Historical data of strategy {} not found",
+                body.strategy_id
+            ))
+        })?;
+    let json_data = json!({ "strategy_summary": summary });
+
+    Ok(HttpResponse::Ok().json(json_data))
+}
 
-        HttpResponse::Ok().json(json_data)
-    } else {
-        let json_data =
-            json!({ "error": "Historical data not found", "strategy_id": body.strategy_id });
+#[post("/rerun-summary")]
+async fn rerun_strategy_summary(
+    app_data: web::Data<AppState>,
+    body: Json<GetStrategyParams>,
+) -> ApiResponse {
+    let result = app_data
+        .bot
+        .lock()
+        .await
+        .rerun_from_summary(body.strategy_id)
+        .await;
 
-        HttpResponse::Ok().json(json_data)
-    }
+    Ok(HttpResponse::Ok().json(json!({ "result": result? })))
 }
 
 #[derive(Serialize, Deserialize)]
@@ -212,7 +316,7 @@ struct StopAllStrategiesParams {
 async fn stop_all_strategies(
     app_data: web::Data<AppState>,
     body: Json<StopAllStrategiesParams>,
-) -> impl Responder {
+) -> ApiResponse {
     let bot = app_data.bot.clone();
 
     let strategies = bot.lock().await.get_active_strategy_ids().await;
@@ -225,33 +329,59 @@ async fn stop_all_strategies(
 
     let json_data = json!({ "strategies_stopped": strategies });
 
-    HttpResponse::Ok().json(json_data)
+    Ok(HttpResponse::Ok().json(json_data))
 }
 
 #[derive(Debug, Deserialize)]
 pub struct SetStrategyParams {
     strategy_id: StrategyId,
     params: Value,
+    reset_state: Option<bool>,
 }
 #[post("/set-params")]
 async fn set_strategy_params(
     app_data: web::Data<AppState>,
     body: Json<SetStrategyParams>,
-) -> impl Responder {
+) -> ApiResponse {
     let bot = app_data.bot.clone();
     let mut bot = bot.lock().await;
-    // if let Some(strategy) = app_data.bot.lock().await.get_strategy(body.strategy_id) {
-    if let Err(err) = bot
-        .set_strategy_params(body.strategy_id, body.params.clone())
-        .await
-    {
-        let json_data = json!({ "error": err.to_string() });
-        HttpResponse::Ok().json(json_data)
-    } else {
-        let updated_params = bot.get_strategy_params(body.strategy_id).await;
-        let json_data = json!({ "success": { "updated_params": updated_params } });
-        HttpResponse::Ok().json(json_data)
-    }
+    bot.set_strategy_params(
+        body.strategy_id,
+        body.params.clone(),
+        body.reset_state.unwrap_or(false),
+    )
+    .await?;
+
+    let updated_params = bot.get_strategy_params(body.strategy_id).await;
+    let json_data = json!({ "success": { "updated_params": updated_params } });
+    Ok(HttpResponse::Ok().json(json_data))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateStrategyParams {
+    params: Value,
+    reset_state: Option<bool>,
+}
+#[post("/{strategy_id}/update-params")]
+async fn update_strategy_params(
+    app_data: web::Data<AppState>,
+    strategy_id: web::Path<StrategyId>,
+    body: Json<UpdateStrategyParams>,
+) -> ApiResponse {
+    let mut bot = app_data.bot.lock().await;
+    let reset_state = body.reset_state.unwrap_or(false);
+
+    bot.set_strategy_params(*strategy_id, body.params.clone(), reset_state)
+        .await?;
+
+    let updated_params = bot.get_strategy_params(*strategy_id).await;
+    let json_data = json!({
+        "strategy_id": *strategy_id,
+        "updated_params": updated_params,
+        "reset_state": reset_state
+    });
+
+    Ok(HttpResponse::Ok().json(json_data))
 }
 
 #[derive(Debug, Deserialize)]
@@ -263,20 +393,16 @@ pub struct ChangeSettingsParams {
 async fn change_strategy_settings(
     app_data: web::Data<AppState>,
     body: Json<ChangeSettingsParams>,
-) -> impl Responder {
+) -> ApiResponse {
     let bot = app_data.bot.clone();
     let mut bot = bot.lock().await;
-    if let Some(info) = bot
+    let info = bot
         .change_strategy_settings(body.strategy_id, body.settings.clone())
         .await
-    {
-        let json_data = json!({ "success": { "updated_info": info } });
+        .ok_or_else(|| strategy_not_found(body.strategy_id))?;
+    let json_data = json!({ "success": { "updated_info": info } });
 
-        HttpResponse::Ok().json(json_data)
-    } else {
-        let json_data = json!({ "error": "Unable to find strategy" });
-        HttpResponse::Ok().json(json_data)
-    }
+    Ok(HttpResponse::Ok().json(json_data))
 }
 
 #[derive(Debug, Deserialize)]
@@ -288,32 +414,57 @@ pub struct RunBackTestParams {
     margin: Option<f64>,
     leverage: Option<u32>,
     max_open_orders: Option<u32>,
+    signal_cooldown_ms: Option<u64>,
+    min_flip_move_pct: Option<f64>,
+    sizing_mode: Option<SizingMode>,
+    #[serde(default, deserialize_with = "deserialize_stop_loss")]
+    stop_loss: Option<StopLoss>,
+    capital_allocation_usd: Option<f64>,
+    min_kline_volume: Option<f64>,
+    eval_log_size: Option<usize>,
+    audit_klines: Option<bool>,
     from_ts: String,
     to_ts: String,
 }
+
+impl RunBackTestParams {
+    /// Settings of the back tested strategy.
+    fn settings(&self) -> StrategySettings {
+        StrategySettings {
+            max_open_orders: self.max_open_orders.unwrap_or_else(|| 1),
+            margin_usd: self.margin.unwrap_or_else(|| 1000.0),
+            leverage: self.leverage.unwrap_or_else(|| 10),
+            stop_loss: self.stop_loss,
+            signal_cooldown_ms: self.signal_cooldown_ms.unwrap_or(0),
+            min_flip_move_pct: self.min_flip_move_pct.unwrap_or(0.0),
+            sizing_mode: self.sizing_mode,
+            capital_allocation_usd: self.capital_allocation_usd,
+            // back tests run on their own account
+            account_id: None,
+            min_kline_volume: self.min_kline_volume,
+            eval_log_size: self.eval_log_size,
+            // back tests evaluate every kline as soon as it is read
+            eval_offset_secs: None,
+            error_policy: StrategyErrorPolicy::default(),
+            // back test positions are opened once all klines are evaluated, so can't be expired
+            max_hold_secs: None,
+            mode: StrategyMode::default(),
+            // back test signals are only traded on the back test account
+            signal_sinks: vec![],
+            promote_after: None,
+            audit_klines: self.audit_klines.unwrap_or(false),
+        }
+    }
+}
+
 #[post("/run-back-test")]
 async fn run_back_test(
     app_data: web::Data<AppState>,
     body: Json<RunBackTestParams>,
-) -> impl Responder {
+) -> ApiResponse {
     let bot = app_data.bot.clone();
-    let settings = StrategySettings {
-        max_open_orders: body.max_open_orders.unwrap_or_else(|| 1),
-        margin_usd: body.margin.unwrap_or_else(|| 1000.0),
-        leverage: body.leverage.unwrap_or_else(|| 10),
-        stop_loss: None,
-    };
-
-    let from_ts = string_to_timestamp(&body.from_ts);
-    let to_ts = string_to_timestamp(&body.to_ts);
-    if from_ts.is_err() || to_ts.is_err() {
-        let json_data = json!({ "error": "Unable to parse dates".to_string()});
-        return HttpResponse::ExpectationFailed().json(json_data);
-    }
-
-    // SAFETY: Error check above
-    let from_ts = from_ts.unwrap();
-    let to_ts = to_ts.unwrap();
+    let from_ts = parse_date(&body.from_ts)?;
+    let to_ts = parse_date(&body.to_ts)?;
 
     let result = bot
         .lock()
@@ -324,22 +475,117 @@ async fn run_back_test(
             body.interval,
             from_ts,
             to_ts,
-            settings,
+            body.settings(),
             body.algorithm_params.clone(),
         )
         .await;
 
-    match result {
-        Ok(result) => {
-            let json_data = json!({ "result": result });
+    let json_data = json!({ "result": result? });
+
+    Ok(HttpResponse::Ok().json(json_data))
+}
+
+/// Back test over several intervals, `interval` is the stored interval the others are
+/// resampled from.
+#[derive(Debug, Deserialize)]
+pub struct RunMultiBackTestParams {
+    intervals: Vec<Interval>,
+    #[serde(flatten)]
+    back_test: RunBackTestParams,
+}
 
-            HttpResponse::Ok().json(json_data)
+#[post("/run-back-test/multi")]
+async fn run_multi_back_test(
+    app_data: web::Data<AppState>,
+    body: Json<RunMultiBackTestParams>,
+) -> ApiResponse {
+    let bot = app_data.bot.clone();
+    let params = &body.back_test;
+    let from_ts = parse_date(&params.from_ts)?;
+    let to_ts = parse_date(&params.to_ts)?;
+
+    let results = bot
+        .lock()
+        .await
+        .run_multi_interval_back_test(
+            &params.strategy_name,
+            &params.symbol,
+            params.interval,
+            body.intervals.clone(),
+            from_ts,
+            to_ts,
+            params.settings(),
+            params.algorithm_params.clone(),
+        )
+        .await?;
+
+    let json_data = json!({ "results": results });
+
+    Ok(HttpResponse::Ok().json(json_data))
+}
+
+/// Copy of a strategy, back tested between the dates if both are given, else started live.
+#[derive(Debug, Deserialize)]
+pub struct CloneStrategyParams {
+    params: Option<Value>,
+    from_ts: Option<String>,
+    to_ts: Option<String>,
+}
+
+#[post("/{strategy_id}/clone")]
+async fn clone_strategy(
+    app_data: web::Data<AppState>,
+    strategy_id: web::Path<StrategyId>,
+    body: Json<CloneStrategyParams>,
+) -> ApiResponse {
+    let mut bot = app_data.bot.lock().await;
+    let params_patch = body.params.clone().unwrap_or_else(|| json!({}));
+
+    let info = bot
+        .cloned_strategy_info(*strategy_id, &params_patch)
+        .await
+        .map_err(|_| strategy_not_found(*strategy_id))?;
+
+    let json_data = match (&body.from_ts, &body.to_ts) {
+        (Some(from_ts), Some(to_ts)) => {
+            let summary = bot
+                .run_back_test(
+                    &info.name,
+                    &info.symbol,
+                    info.interval,
+                    parse_date(from_ts)?,
+                    parse_date(to_ts)?,
+                    info.settings,
+                    info.params,
+                )
+                .await?;
+            json!({ "strategy_id": summary.info.id, "result": summary })
         }
-        Err(e) => {
-            let json_data = json!({ "error": e.to_string()});
-            HttpResponse::ExpectationFailed().json(json_data)
+        (None, None) => {
+            let info = bot
+                .start_strategy(
+                    &info.name,
+                    &info.symbol,
+                    info.interval,
+                    info.settings,
+                    info.params,
+                )
+                .await?;
+            json!({ "strategy_id": info.id, "info": info })
         }
-    }
+        _ => {
+            return Err(ApiError::BadRequest(
+                "Both from_ts and to_ts are needed to back test a clone".to_string(),
+            ))
+        }
+    };
+
+    Ok(HttpResponse::Ok().json(json_data))
+}
+
+/// Error of a request for a strategy which isn't running.
+fn strategy_not_found(strategy_id: StrategyId) -> ApiError {
+    ApiError::NotFound(format!("Strategy {strategy_id} not found"))
 }
 
 pub fn register_strategy_service() -> Scope {
@@ -348,12 +594,20 @@ pub fn register_strategy_service() -> Scope {
         .service(stop_strategy)
         .service(stop_all_strategies)
         .service(set_strategy_params)
+        .service(update_strategy_params)
         .service(change_strategy_settings)
         .service(list_active_strategies)
         .service(strategy_info)
         .service(list_strategy_positions)
         .service(active_strategy_summary)
+        .service(strategy_drawdown_timeline)
+        .service(strategy_eval_log)
+        .service(strategy_report)
         .service(list_historical_strategies)
+        .service(list_failed_signals)
         .service(historical_strategy_summary)
+        .service(rerun_strategy_summary)
         .service(run_back_test)
+        .service(run_multi_back_test)
+        .service(clone_strategy)
 }
@@ -12,6 +12,7 @@ use log::info;
 use serde::Deserialize;
 use serde_json::json;
 
+use crate::api::error::{parse_date, ApiError, ApiResponse};
 use crate::app::AppState;
 use crate::market::interval::Interval;
 use crate::utils::crypt::sign_hmac;
@@ -20,7 +21,7 @@ use crate::utils::kline::{
     load_binance_klines, save_klines,
 };
 use crate::utils::time::{calculate_kline_open_time, get_time_difference};
-use crate::utils::time::{generate_ts, year_month_day_to_ts};
+use crate::utils::time::{generate_ts, timestamp_to_string, year_month_day_to_ts};
 use crate::utils::trade::{build_market_trade_key, load_binance_agg_trades, save_trades};
 
 #[get("/timestamp")]
@@ -30,6 +31,31 @@ async fn get_ts(_app_data: web::Data<AppState>) -> HttpResponse {
     HttpResponse::Ok().json(json_data)
 }
 
+#[derive(Debug, Deserialize)]
+pub struct ConvertTimeParams {
+    date: Option<String>,
+    ts: Option<u64>,
+}
+#[get("/time/convert")]
+async fn convert_time(query: web::Query<ConvertTimeParams>) -> ApiResponse {
+    let timestamp = match (&query.date, query.ts) {
+        (Some(date), None) => parse_date(date)?,
+        (None, Some(ts)) => ts,
+        _ => {
+            return Err(ApiError::BadRequest(
+                "Either 'date' or 'ts' must be given".to_string(),
+            ))
+        }
+    };
+
+    let json_data = json!({
+        "timestamp": timestamp,
+        "date": timestamp_to_string(timestamp),
+        "now": generate_ts()
+    });
+    Ok(HttpResponse::Ok().json(json_data))
+}
+
 #[derive(Debug, Deserialize)]
 pub struct DateToTsParams {
     year: u32,
@@ -215,6 +241,7 @@ async fn calculate_open_time(
 pub fn register_utils_service() -> Scope {
     scope("/utils")
         .service(get_ts)
+        .service(convert_time)
         .service(calculate_open_time)
         .service(time_difference)
         .service(load_klines)
@@ -222,3 +249,52 @@ pub fn register_utils_service() -> Scope {
         .service(get_sign_hmac)
         .service(bootstrap_historical_trades)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use actix_web::{http::StatusCode, test, App};
+    use serde_json::Value;
+
+    use crate::utils::time::string_to_timestamp;
+
+    #[actix_web::test]
+    async fn test_convert_time_matches_internal_conversion() {
+        let app = test::init_service(App::new().service(register_utils_service())).await;
+
+        for date in [
+            "2024-03-01T20:00:00Z",
+            "2024-03-01T22:00:00+02:00",
+            "2024-03-01 20:00:00",
+        ] {
+            let req = test::TestRequest::get()
+                .uri(&format!(
+                    "/utils/time/convert?date={}",
+                    date.replace('+', "%2B").replace(' ', "%20")
+                ))
+                .to_request();
+            let body: Value = test::call_and_read_body_json(&app, req).await;
+
+            let timestamp = string_to_timestamp(date).unwrap();
+            assert_eq!(body["timestamp"], timestamp);
+            assert_eq!(body["date"], timestamp_to_string(timestamp));
+
+            // the returned date converts back to the same timestamp
+            let req = test::TestRequest::get()
+                .uri(&format!("/utils/time/convert?ts={timestamp}"))
+                .to_request();
+            let body: Value = test::call_and_read_body_json(&app, req).await;
+            assert_eq!(
+                string_to_timestamp(body["date"].as_str().unwrap()),
+                Ok(timestamp)
+            );
+        }
+
+        let req = test::TestRequest::get()
+            .uri("/utils/time/convert?date=not%20a%20date")
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    }
+}
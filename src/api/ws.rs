@@ -0,0 +1,455 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use actix::{Actor, ActorContext, ActorFutureExt, AsyncContext, StreamHandler, WrapFuture};
+use actix_web::{
+    get,
+    web::{self, scope},
+    Error, HttpRequest, HttpResponse, Scope,
+};
+use actix_web_actors::ws;
+
+use log::info;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::app::AppState;
+use crate::exchange::{stream::build_stream_id, types::StreamType};
+use crate::market::{
+    interval::Interval, market::Market, orderbook::DEFAULT_DEPTH_LEVELS, types::ArcMutex,
+};
+use crate::utils::time::{generate_ts, SEC_AS_MILI};
+
+/// Interval at which market data for subscribed streams is pushed to the client.
+const PUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Commands accepted on the control socket, sent as JSON with a `command` tag.
+///
+/// ```json
+/// { "command": "open_stream", "stream_type": "Kline", "symbol": "BTCUSDT", "interval": "1m" }
+/// { "command": "close_stream", "stream_id": "BTCUSDT@kline_1m" }
+/// { "command": "list_streams" }
+/// ```
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "command", rename_all = "snake_case")]
+pub enum ControlCommand {
+    OpenStream {
+        stream_type: StreamType,
+        symbol: String,
+        interval: Option<Interval>,
+    },
+    CloseStream {
+        stream_id: String,
+    },
+    ListStreams,
+}
+
+/// A stream opened by a control session, market data for it is pushed to the client.
+#[derive(Debug, Clone)]
+struct Subscription {
+    stream_type: StreamType,
+    symbol: String,
+    interval: Option<Interval>,
+    last_sent: Option<Value>,
+}
+
+/// WebSocket session which opens and closes market streams on behalf of a client.
+///
+/// Confirmations are sent in reply to each command, market data for streams opened by the
+/// session is pushed every `PUSH_INTERVAL` whenever it has changed. The session subscribes to
+/// the streams it opens, so streams shared with strategies or other sessions stay open, and
+/// unsubscribes from all of them when the client disconnects.
+
+pub struct StreamControlSession {
+    market: ArcMutex<Market>,
+    subscriptions: HashMap<String, Subscription>,
+}
+
+impl StreamControlSession {
+    /// Creates a new control session operating on the given market.
+    ///
+    /// # Arguments
+    ///
+    /// * `market` - The market used to open and close streams and to read market data.
+    ///
+    /// # Returns
+    ///
+    /// A new `StreamControlSession` with no subscriptions.
+
+    pub fn new(market: ArcMutex<Market>) -> Self {
+        Self {
+            market,
+            subscriptions: HashMap::new(),
+        }
+    }
+
+    // ---
+    // Private Methods
+    // ---
+
+    /// Parses and executes a command received from the client, replying with the result.
+    fn handle_command(&mut self, text: &str, ctx: &mut ws::WebsocketContext<Self>) {
+        let command: ControlCommand = match serde_json::from_str(text) {
+            Ok(command) => command,
+            Err(e) => {
+                let json_data = json!({ "error": "Unable to parse command", "msg": e.to_string() });
+                ctx.text(json_data.to_string());
+                return;
+            }
+        };
+
+        let market = self.market.clone();
+
+        match command {
+            ControlCommand::OpenStream {
+                stream_type,
+                symbol,
+                interval,
+            } => {
+                // only kline streams have an interval
+                let interval = match stream_type {
+                    StreamType::Kline => interval,
+                    _ => None,
+                };
+
+                // a session holds one subscription per stream
+                let stream_id = build_stream_id(&symbol, stream_type, interval);
+                if self.subscriptions.contains_key(&stream_id) {
+                    let json_data = json!({ "command": "open_stream", "success": "Stream created", "stream_id": stream_id });
+                    ctx.text(json_data.to_string());
+                    return;
+                }
+
+                let fut = async move {
+                    market
+                        .lock()
+                        .await
+                        .subscribe_stream(stream_type, &symbol, interval)
+                        .await
+                        .map(|_| (stream_id, symbol))
+                };
+
+                ctx.spawn(fut.into_actor(self).map(move |result, act, ctx| {
+                    let json_data = match result {
+                        Ok((stream_id, symbol)) => {
+                            act.subscriptions.insert(
+                                stream_id.clone(),
+                                Subscription {
+                                    stream_type,
+                                    symbol,
+                                    interval,
+                                    last_sent: None,
+                                },
+                            );
+                            json!({ "command": "open_stream", "success": "Stream created", "stream_id": stream_id })
+                        }
+                        Err(e) => {
+                            json!({ "command": "open_stream", "error": "Unable to open stream", "msg": e.to_string() })
+                        }
+                    };
+                    ctx.text(json_data.to_string());
+                }));
+            }
+            ControlCommand::CloseStream { stream_id } => {
+                // only streams opened by the session are closed by it
+                let Some(sub) = self.subscriptions.remove(&stream_id) else {
+                    let json_data = json!({ "command": "close_stream", "error": format!("Stream with ID {} not opened by this session", stream_id) });
+                    ctx.text(json_data.to_string());
+                    return;
+                };
+
+                let fut = async move {
+                    let subscribers = market
+                        .lock()
+                        .await
+                        .unsubscribe_stream(sub.stream_type, &sub.symbol, sub.interval)
+                        .await;
+                    (stream_id, subscribers)
+                };
+
+                ctx.spawn(
+                    fut.into_actor(self)
+                        .map(|(stream_id, subscribers), _act, ctx| {
+                            let json_data = json!({ "command": "close_stream", "success": "Stream closed successfully", "stream_id": stream_id, "subscribers": subscribers });
+                            ctx.text(json_data.to_string());
+                        }),
+                );
+            }
+            ControlCommand::ListStreams => {
+                let fut = async move { market.lock().await.active_streams().await };
+
+                ctx.spawn(fut.into_actor(self).map(|active_streams, _act, ctx| {
+                    let json_data =
+                        json!({ "command": "list_streams", "active_streams": active_streams });
+                    ctx.text(json_data.to_string());
+                }));
+            }
+        }
+    }
+
+    /// Reads the latest market data for all subscriptions and pushes any which changed.
+    fn push_market_data(&mut self, ctx: &mut ws::WebsocketContext<Self>) {
+        if self.subscriptions.is_empty() {
+            return;
+        }
+
+        let market = self.market.clone();
+        let subscriptions = self.subscriptions.clone();
+
+        let fut = async move {
+            // klines and tickers may be fetched from the exchange, without holding the market lock
+            let reader = market.lock().await.reader();
+            let mut frames = vec![];
+
+            for (stream_id, sub) in subscriptions {
+                let data = match sub.stream_type {
+                    StreamType::Kline => match sub.interval {
+                        Some(interval) => reader
+                            // subscribers follow the kline in progress
+                            .last_kline(&sub.symbol, interval, false)
+                            .await
+                            .map(|kline| json!(kline)),
+                        None => None,
+                    },
+                    StreamType::Ticker => reader
+                        .last_ticker(&sub.symbol)
+                        .await
+                        .map(|ticker| json!(ticker)),
+                    StreamType::Trade => market
+                        .lock()
+                        .await
+                        .trade_data_range(
+                            &sub.symbol,
+                            Some(generate_ts() - SEC_AS_MILI),
+                            None,
+                            None,
+                        )
+                        .await
+                        .map(|trade_data| json!(trade_data.trades())),
+                    StreamType::Depth => market
+                        .lock()
+                        .await
+                        .order_book_depth(&sub.symbol, DEFAULT_DEPTH_LEVELS)
+                        .await
+                        .map(|depth| json!(depth)),
+                    StreamType::UserData => None,
+                };
+
+                if let Some(data) = data {
+                    frames.push((stream_id, data));
+                }
+            }
+
+            frames
+        };
+
+        ctx.spawn(fut.into_actor(self).map(|frames, act, ctx| {
+            for (stream_id, data) in frames {
+                // stream may have been closed while data was fetched
+                if let Some(sub) = act.subscriptions.get_mut(&stream_id) {
+                    if sub.last_sent.as_ref() == Some(&data) {
+                        continue;
+                    }
+
+                    let json_data = json!({ "stream_id": stream_id, "data": data });
+                    ctx.text(json_data.to_string());
+                    sub.last_sent = Some(data);
+                }
+            }
+        }));
+    }
+}
+
+impl Actor for StreamControlSession {
+    type Context = ws::WebsocketContext<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        ctx.run_interval(PUSH_INTERVAL, |act, ctx| act.push_market_data(ctx));
+    }
+
+    fn stopped(&mut self, _ctx: &mut Self::Context) {
+        if self.subscriptions.is_empty() {
+            return;
+        }
+
+        // streams the client left open are released once it disconnects
+        let market = self.market.clone();
+        let subscriptions: Vec<Subscription> =
+            self.subscriptions.drain().map(|(_, sub)| sub).collect();
+
+        actix_web::rt::spawn(async move {
+            let market = market.lock().await;
+            for sub in subscriptions {
+                market
+                    .unsubscribe_stream(sub.stream_type, &sub.symbol, sub.interval)
+                    .await;
+            }
+        });
+    }
+}
+
+impl StreamHandler<Result<ws::Message, ws::ProtocolError>> for StreamControlSession {
+    fn handle(&mut self, msg: Result<ws::Message, ws::ProtocolError>, ctx: &mut Self::Context) {
+        match msg {
+            Ok(ws::Message::Text(text)) => self.handle_command(&text, ctx),
+            Ok(ws::Message::Ping(msg)) => ctx.pong(&msg),
+            Ok(ws::Message::Close(reason)) => {
+                ctx.close(reason);
+                ctx.stop();
+            }
+            Ok(_) => {}
+            Err(e) => {
+                info!("Stream control session error: {e}");
+                ctx.stop();
+            }
+        }
+    }
+}
+
+#[get("/control")]
+async fn stream_control(
+    app_data: web::Data<AppState>,
+    req: HttpRequest,
+    stream: web::Payload,
+) -> Result<HttpResponse, Error> {
+    let market = app_data.get_market().await;
+
+    ws::start(StreamControlSession::new(market), &req, stream)
+}
+
+pub fn register_ws_service() -> Scope {
+    scope("/ws").service(stream_control)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    use actix_web::{App, HttpServer};
+    use futures_util::{SinkExt, StreamExt};
+    use tokio::net::TcpStream;
+    use tokio_tungstenite::{connect_async, tungstenite::Message, MaybeTlsStream, WebSocketStream};
+
+    use crate::{
+        exchange::{api::ExchangeApi, mock::MockExchangeApi},
+        market::channel::{build_market_channel, DEFAULT_MARKET_CHANNEL_CAPACITY},
+        storage::{fs::FsStorage, manager::StorageManager},
+    };
+
+    async fn test_stream_control(
+        market: web::Data<ArcMutex<Market>>,
+        req: HttpRequest,
+        stream: web::Payload,
+    ) -> Result<HttpResponse, Error> {
+        ws::start(
+            StreamControlSession::new(market.get_ref().clone()),
+            &req,
+            stream,
+        )
+    }
+
+    async fn next_json(socket: &mut WebSocketStream<MaybeTlsStream<TcpStream>>) -> Value {
+        loop {
+            match socket.next().await {
+                Some(Ok(Message::Text(text))) => return serde_json::from_str(&text).unwrap(),
+                Some(Ok(_)) => continue,
+                other => panic!("Socket closed: {other:?}"),
+            }
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_open_stream_over_ws() {
+        let (_, market_rx) = build_market_channel(DEFAULT_MARKET_CHANNEL_CAPACITY);
+        let exchange_api: Arc<dyn ExchangeApi> = Arc::new(MockExchangeApi::default());
+        let storage_manager: Arc<dyn StorageManager> = Arc::new(FsStorage::default());
+        let market = Market::new(market_rx, exchange_api, storage_manager, false).await;
+        let market = web::Data::new(ArcMutex::new(market));
+
+        let server = HttpServer::new(move || {
+            App::new()
+                .app_data(market.clone())
+                .route("/ws/control", web::get().to(test_stream_control))
+        })
+        .workers(1)
+        .bind(("127.0.0.1", 0))
+        .unwrap();
+        let addr = server.addrs()[0];
+        let server = server.run();
+        let handle = server.handle();
+        actix_web::rt::spawn(server);
+
+        let (mut socket, _) = connect_async(format!("ws://{addr}/ws/control"))
+            .await
+            .unwrap();
+
+        let command =
+            json!({ "command": "open_stream", "stream_type": "Ticker", "symbol": "BTCUSDT" });
+        socket
+            .send(Message::Text(command.to_string()))
+            .await
+            .unwrap();
+
+        let confirmation = next_json(&mut socket).await;
+        assert_eq!(confirmation["command"], "open_stream");
+        assert_eq!(confirmation["stream_id"], "BTCUSDT@ticker");
+
+        let frame = next_json(&mut socket).await;
+        assert_eq!(frame["stream_id"], "BTCUSDT@ticker");
+        assert_eq!(frame["data"]["symbol"], "BTCUSDT");
+
+        handle.stop(false).await;
+    }
+
+    #[actix_web::test]
+    async fn test_streams_closed_on_disconnect() {
+        let (_, market_rx) = build_market_channel(DEFAULT_MARKET_CHANNEL_CAPACITY);
+        let exchange_api: Arc<dyn ExchangeApi> = Arc::new(MockExchangeApi::default());
+        let storage_manager: Arc<dyn StorageManager> = Arc::new(FsStorage::default());
+        let market = Market::new(market_rx, exchange_api, storage_manager, false).await;
+        let market = ArcMutex::new(market);
+        let app_market = web::Data::new(market.clone());
+
+        let server = HttpServer::new(move || {
+            App::new()
+                .app_data(app_market.clone())
+                .route("/ws/control", web::get().to(test_stream_control))
+        })
+        .workers(1)
+        .bind(("127.0.0.1", 0))
+        .unwrap();
+        let addr = server.addrs()[0];
+        let server = server.run();
+        let handle = server.handle();
+        actix_web::rt::spawn(server);
+
+        let (mut socket, _) = connect_async(format!("ws://{addr}/ws/control"))
+            .await
+            .unwrap();
+
+        let command =
+            json!({ "command": "open_stream", "stream_type": "Ticker", "symbol": "BTCUSDT" });
+        socket
+            .send(Message::Text(command.to_string()))
+            .await
+            .unwrap();
+        next_json(&mut socket).await;
+        assert_eq!(market.lock().await.active_streams().await.len(), 1);
+
+        socket.close(None).await.unwrap();
+
+        let mut active = 1;
+        for _ in 0..50 {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            active = market.lock().await.active_streams().await.len();
+            if active == 0 {
+                break;
+            }
+        }
+        assert_eq!(active, 0);
+
+        handle.stop(false).await;
+    }
+}
@@ -1,6 +1,7 @@
-use std::sync::Arc;
+use std::{io, sync::Arc};
 
 use actix_web::web::Data;
+use dotenv_codegen::dotenv;
 
 use crate::{
     account::account::Account,
@@ -8,6 +9,7 @@ use crate::{
     exchange::api::ExchangeApi,
     market::{market::Market, types::ArcMutex},
     storage::manager::StorageManager,
+    utils::json::ResponseFormat,
 };
 
 /// Represents the shared state of the application.
@@ -18,6 +20,8 @@ use crate::{
 pub struct AppState {
     /// A thread-safe, mutable reference to the `RaderBot` instance.
     pub bot: ArcMutex<RaderBot>,
+    /// Formatting applied to JSON responses of the market endpoints.
+    pub response_format: ResponseFormat,
 }
 
 impl AppState {
@@ -71,9 +75,17 @@ impl AppState {
 /// # Returns
 ///
 /// A `Data<AppState>` wrapper around the initialized application state, ready for integration
-/// into an Actix web application.
-pub async fn new_app_state() -> Data<AppState> {
-    let bot = ArcMutex::new(RaderBot::new().await);
+/// into an Actix web application, or an `InvalidInput` error if the bot settings are invalid.
+pub async fn new_app_state() -> io::Result<Data<AppState>> {
+    let bot = RaderBot::new()
+        .await
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    let bot = ArcMutex::new(bot);
+    let response_format =
+        ResponseFormat::from_settings(dotenv!("RESPONSE_FORMAT"), dotenv!("RESPONSE_PRECISION"));
 
-    Data::new(AppState { bot })
+    Ok(Data::new(AppState {
+        bot,
+        response_format,
+    }))
 }
@@ -1,106 +1,306 @@
 use dotenv_codegen::dotenv;
 
-use log::info;
+use log::{error, info};
 use serde_json::{json, Value};
 
-use std::{collections::HashMap, sync::Arc};
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::PathBuf,
+    sync::Arc,
+    time::Duration,
+};
 
 use crate::{
-    account::account::Account,
-    exchange::{api::ExchangeApi, binance::BinanceApi, bingx::BingXApi, mock::MockExchangeApi},
+    account::{
+        account::{Account, AccountId, DEFAULT_ACCOUNT_ID},
+        events::TradeEvent,
+        risk::DailyLossLimit,
+        schedule::{OrderScheduler, ScheduledOrder, ScheduledOrderId},
+        trade::TradeTx,
+        user_data::UserDataEvent,
+    },
+    exchange::{
+        api::ExchangeApi,
+        binance::BinanceApi,
+        bingx::{BingXApi, PollConfig},
+        halt::SYMBOL_STATUS_RECHECK_SECS,
+        http::HttpClientConfig,
+        mock::MockExchangeApi,
+        quarantine::StreamQuarantine,
+        rate_limit::RateLimiter,
+        resilient::{ResilienceConfig, ResilientExchangeApi},
+        stream::build_stream_id,
+        types::{ApiError, StreamType},
+    },
     market::{
+        channel::{self, build_market_channel, MarketReceiver, MarketSender},
+        flow::TradeFlow,
         interval::{self, Interval},
-        market::Market,
-        messages::MarketMessage,
+        kline::KlineData,
+        market::{Market, MarketWarmup},
+        recorder::{MarketRecorder, MarketReplay, ReplayStatus},
+        ticker::TickerSampling,
         types::{ArcMutex, ArcReceiver, ArcSender},
     },
+    notify::notifier::{LogNotifier, Notification, Notifier},
     storage::{
-        fs::FsStorage, influx::InfluxStorage, manager::StorageManager, mongo::MongoDbStorage,
+        builder::{build_storage_manager, StorageBackend, StorageConfig, StorageFallback},
+        fallback,
+        manager::StorageManager,
+        schema::DataFormat,
     },
     strategy::{
         backer::BackTest,
-        signal::{SignalHandler, SignalMessage},
-        strategy::{Strategy, StrategyId, StrategyInfo, StrategySettings, StrategySummary},
+        eval_log::EvalLogEntry,
+        report::StrategyReport,
+        signal::{FailedSignal, SignalHandler, SignalMessage},
+        strategy::{
+            DrawdownPoint, Strategy, StrategyErrorPolicy, StrategyId, StrategyInfo, StrategyMode,
+            StrategySettings, StrategySummary, SummaryDiff,
+        },
         types::AlgoError,
     },
-    utils::{channel::build_arc_channel, json},
+    utils::{
+        channel::build_arc_channel,
+        json,
+        time::{generate_ts, Clock, SystemClock},
+    },
 };
 
 use tokio::task::JoinHandle;
+use tokio::time;
 
 pub struct RaderBot {
     pub market: ArcMutex<Market>,
+    /// Default account, used by strategies which don't specify an account.
     pub account: ArcMutex<Account>,
+    accounts: ArcMutex<HashMap<AccountId, ArcMutex<Account>>>,
     strategy_manager: ArcMutex<StrategyManager>,
     pub exchange_api: Arc<dyn ExchangeApi>,
     pub storage_manager: Arc<dyn StorageManager>,
     strategy_tx: ArcSender<SignalMessage>,
     strategy_rx: ArcReceiver<SignalMessage>,
+    market_tx: MarketSender,
+    snapshot_interval: Duration,
+    notifier: Arc<dyn Notifier>,
+    order_scheduler: ArcMutex<OrderScheduler>,
+    /// Exchange APIs built for accounts, shared by every account on the same exchange.
+    exchange_apis: ArcMutex<HashMap<String, Arc<dyn ExchangeApi>>>,
+    /// Last market message log replayed into the market.
+    market_replay: Option<MarketReplay>,
 }
 
+/// Interval between account snapshots used if `ACCOUNT_SNAPSHOT_INTERVAL_SECS` is invalid.
+pub const DEFAULT_SNAPSHOT_INTERVAL_SECS: u64 = 60;
+
+/// Command line flag which runs the bot without persisting any data.
+const EPHEMERAL_FLAG: &str = "--ephemeral";
+
+/// Interval between checks for strategies whose task ended unexpectedly.
+const STRATEGY_SUPERVISION_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Interval between checks for scheduled orders whose time has arrived.
+const SCHEDULED_ORDER_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Interval between checks of the last prices against the stop losses and take profits of open
+/// positions.
+const POSITION_EXIT_INTERVAL: Duration = Duration::from_secs(1);
+
 impl RaderBot {
-    pub async fn new() -> Self {
-        // create new Arc of exchange API
-        let api_key = dotenv!("BINGX_API_KEY");
-        let secret_key = dotenv!("BINGX_SECRET_KEY");
+    /// Creates a new bot from the settings in the environment.
+    ///
+    /// # Returns
+    ///
+    /// A new, initialized `RaderBot`, or an error describing the invalid setting if the exchanges or
+    /// storage can't be built.
+
+    pub async fn new() -> Result<Self, String> {
         let dry_run = dotenv!("DRY_RUN");
+        let data_exchange = dotenv!("DATA_EXCHANGE");
+        let execution_exchange = dotenv!("EXECUTION_EXCHANGE");
         let mongo_uri = dotenv!("MONGO_URI");
         let influx_uri = dotenv!("INFLUX_DB_HOST");
         let influx_token = dotenv!("INFLUX_TOKEN");
         let storage_type = dotenv!("STORAGE_TYPE");
+        let storage_fallback = dotenv!("STORAGE_FALLBACK");
+        let fs_data_format = dotenv!("FS_DATA_FORMAT");
+        let snapshot_interval_secs = dotenv!("ACCOUNT_SNAPSHOT_INTERVAL_SECS");
+        let market_record_path = dotenv!("MARKET_RECORD_PATH");
+        let base_currency = dotenv!("BASE_CURRENCY");
+        let market_channel_capacity =
+            channel::capacity_from_setting(dotenv!("MARKET_CHANNEL_CAPACITY"));
+        let daily_loss_limit = DailyLossLimit::from_settings(
+            dotenv!("DAILY_LOSS_LIMIT_USD"),
+            dotenv!("DAILY_LOSS_CLOSE_POSITIONS"),
+        );
+        let market_warmup = MarketWarmup::from_settings(
+            dotenv!("MARKET_WARMUP_KLINES"),
+            dotenv!("MARKET_WARMUP_TRADES"),
+        );
+        let ticker_sampling = TickerSampling::from_setting(dotenv!("TICKER_SAMPLE_SECS"));
+        let trade_flow_window = TradeFlow::window_from_setting(dotenv!("TRADE_FLOW_WINDOW_SECS"));
+        let rest_limiter = RateLimiter::from_setting(dotenv!("REST_REQUESTS_PER_SEC"));
+        let adopt_positions = dotenv!("ADOPT_EXCHANGE_POSITIONS") == "True";
+        let min_notional = dotenv!("MIN_NOTIONAL_USD").parse::<f64>().unwrap_or(0.0);
+        let unique_strategy_names = dotenv!("UNIQUE_STRATEGY_NAMES") == "True";
+        let (http_config, resilience_config) = RaderBot::exchange_configs();
 
         // create new channel for stream handler and market to communicate
-        let (market_tx, market_rx) = build_arc_channel::<MarketMessage>();
+        let (market_tx, market_rx) = build_market_channel(market_channel_capacity);
 
-        let exchange_api: Arc<dyn ExchangeApi> = Arc::new(BinanceApi::new(
-            api_key,
-            secret_key,
+        // market data can be retrieved from a separate source to the exchange
+        // used to open and close positions
+        let data_exchange_api = RaderBot::build_exchange_api(
+            data_exchange,
             market_tx.clone(),
-            false,
-        ));
-        // let exchange_api: Arc<Box<dyn ExchangeApi>> = Arc::new(Box::new(BingXApi::new(
-        //     api_key,
-        //     secret_key,
-        //     market_tx.clone(),
-        // )));
+            http_config,
+            resilience_config,
+        )
+        .map_err(|e| format!("{e}, set DATA_EXCHANGE to BINANCE, BINGX or MOCK"))?;
+
+        let (execution_exchange_api, dry_run) = if dry_run == "True" {
+            let api: Arc<dyn ExchangeApi> = Arc::new(MockExchangeApi::default());
+            (api, true)
+        } else if execution_exchange.eq_ignore_ascii_case(data_exchange) {
+            (data_exchange_api.clone(), false)
+        } else {
+            let api = RaderBot::build_exchange_api(
+                execution_exchange,
+                market_tx.clone(),
+                http_config,
+                resilience_config,
+            )
+            .map_err(|e| format!("{e}, set EXECUTION_EXCHANGE to BINANCE, BINGX or MOCK"))?;
+            (api, false)
+        };
 
         // create new storage manager
+        // ephemeral runs keep all data in memory regardless of the configured storage
+        let backend = if std::env::args().any(|arg| arg == EPHEMERAL_FLAG) {
+            StorageBackend::Memory
+        } else {
+            StorageBackend::from_setting(storage_type)
+        };
 
-        let storage_manager: Arc<dyn StorageManager> = match storage_type {
-            "INFLUX" => {
-                info!("Using InfluxStorage as storage backend");
-                let manager: Arc<dyn StorageManager> =
-                    match InfluxStorage::new(influx_uri, influx_token).await {
-                        Ok(manager) => Arc::new(manager),
-                        Err(e) => {
-                            info!("There was an error instantiating InfluxDB: {e}");
-                            Arc::new(FsStorage::default())
-                        }
-                    };
-                manager
-            }
-            "MONGO" => {
-                info!("Using MongoDbStorage as storage backend");
-                let manager: Arc<dyn StorageManager> = match MongoDbStorage::new(mongo_uri).await {
-                    Ok(manager) => Arc::new(manager),
-                    Err(e) => {
-                        info!("There was an error instantiating MongoDB: {e}");
-                        Arc::new(FsStorage::default())
-                    }
-                };
-                manager
+        let storage_config = StorageConfig {
+            backend,
+            fallback: StorageFallback::from_setting(storage_fallback),
+            mongo_uri,
+            influx_uri,
+            influx_token,
+            fs_format: DataFormat::from_setting(fs_data_format),
+        };
+
+        let (_, storage_manager) = build_storage_manager(storage_config)
+            .await
+            .map_err(|e| format!("{e}, storage fallback is disabled"))?;
+
+        let snapshot_interval = Duration::from_secs(
+            snapshot_interval_secs
+                .parse()
+                .unwrap_or(DEFAULT_SNAPSHOT_INTERVAL_SECS),
+        );
+
+        let named_execution_api = (!dry_run).then(|| execution_exchange_api.clone());
+
+        let bot = RaderBot::from_exchanges(
+            data_exchange_api,
+            execution_exchange_api,
+            market_tx,
+            market_rx,
+            storage_manager,
+            dry_run,
+            snapshot_interval,
+        )
+        .await;
+
+        if !base_currency.is_empty() {
+            bot.account.lock().await.set_base_currency(base_currency);
+        }
+
+        // accounts added on the configured exchanges share their APIs
+        let mut exchange_apis = bot.exchange_apis.lock().await;
+        exchange_apis.insert(data_exchange.to_uppercase(), bot.exchange_api.clone());
+        if let Some(execution_api) = named_execution_api {
+            exchange_apis.insert(execution_exchange.to_uppercase(), execution_api);
+        }
+        drop(exchange_apis);
+
+        bot.account
+            .lock()
+            .await
+            .set_daily_loss_limit(daily_loss_limit);
+        bot.account.lock().await.set_min_notional(min_notional);
+        bot.strategy_manager
+            .lock()
+            .await
+            .set_unique_names(unique_strategy_names);
+
+        // positions opened before the last restart are otherwise invisible to the account
+        if adopt_positions && !dry_run {
+            match bot.account.lock().await.adopt_exchange_positions().await {
+                Ok(adopted) => info!("Adopted {} positions held on the exchange", adopted.len()),
+                Err(e) => log::warn!("Unable to adopt positions held on the exchange, {e}"),
             }
-            _ => {
-                info!("Using FsStorage as storage backend");
+        }
+
+        // strategies started before the streams fill the market data read the preloaded history
+        if market_warmup != MarketWarmup::default() {
+            bot.market.lock().await.warm_cache(market_warmup).await;
+        }
+
+        bot.market
+            .lock()
+            .await
+            .set_ticker_sampling(ticker_sampling)
+            .await;
+        bot.market
+            .lock()
+            .await
+            .set_trade_flow_window(trade_flow_window)
+            .await;
+        bot.market.lock().await.set_rest_limiter(rest_limiter);
 
-                Arc::new(FsStorage::default())
+        // market messages are only recorded when a log path is configured
+        if !market_record_path.is_empty() {
+            match MarketRecorder::new(market_record_path) {
+                Ok(recorder) => bot.market.lock().await.set_recorder(Some(recorder)).await,
+                Err(e) => info!("Unable to record market messages: {e}"),
             }
-        };
+        }
+
+        Ok(bot)
+    }
 
+    /// Creates a new bot using separate exchanges for market data and execution.
+    ///
+    /// # Arguments
+    ///
+    /// * `data_exchange_api` - Exchange used by the market to stream and fetch market data.
+    /// * `execution_exchange_api` - Exchange used by the default account to open and close positions.
+    /// * `market_tx` - Sender used by the data exchange streams to send market messages.
+    /// * `market_rx` - Receiver for market messages sent by the data exchange streams.
+    /// * `storage_manager` - Storage backend for market data, strategies and account history.
+    /// * `dry_run` - Whether the default account operates in dry run mode.
+    /// * `snapshot_interval` - Interval between account snapshots.
+    ///
+    /// # Returns
+    ///
+    /// A new, initialized `RaderBot`.
+
+    pub async fn from_exchanges(
+        data_exchange_api: Arc<dyn ExchangeApi>,
+        execution_exchange_api: Arc<dyn ExchangeApi>,
+        market_tx: MarketSender,
+        market_rx: MarketReceiver,
+        storage_manager: Arc<dyn StorageManager>,
+        dry_run: bool,
+        snapshot_interval: Duration,
+    ) -> Self {
         // create new market to hold market data
         let market = Market::new(
-            market_rx.clone(),
-            exchange_api.clone(),
+            market_rx,
+            data_exchange_api.clone(),
             storage_manager.clone(),
             true,
         )
@@ -108,34 +308,32 @@ impl RaderBot {
 
         let market = ArcMutex::new(market);
 
-        // Account can use different API from market exchange API
-        // that is to allow for retrieving market data from separate source
-        // and to open and close positions on different API source
-        let (account_exchange_api, dry_run) = if dry_run == "True" {
-            let api: Arc<dyn ExchangeApi> = Arc::new(MockExchangeApi {});
-            (api, true)
-        } else {
-            // possible to create different exchange API if needed
-            // instead of using the same API as Market
-            (exchange_api.clone(), false)
-        };
-
-        let account = Account::new(account_exchange_api, true, dry_run).await;
+        let account = ArcMutex::new(Account::new(execution_exchange_api, true, dry_run).await);
 
-        let account = ArcMutex::new(account);
+        let accounts = HashMap::from([(DEFAULT_ACCOUNT_ID.to_string(), account.clone())]);
 
         let (strategy_tx, strategy_rx) = build_arc_channel::<SignalMessage>();
 
         let strategy_manager = StrategyManager::new();
 
+        let order_scheduler =
+            OrderScheduler::new(storage_manager.clone(), Arc::new(SystemClock)).await;
+
         let mut _self = Self {
             market,
             account,
-            exchange_api: exchange_api.clone(),
+            accounts: ArcMutex::new(accounts),
+            exchange_api: data_exchange_api,
             strategy_manager: ArcMutex::new(strategy_manager),
             strategy_tx,
             strategy_rx,
             storage_manager,
+            market_tx,
+            snapshot_interval,
+            notifier: Arc::new(LogNotifier::default()),
+            order_scheduler: ArcMutex::new(order_scheduler),
+            exchange_apis: ArcMutex::new(HashMap::new()),
+            market_replay: None,
         };
 
         _self.init().await;
@@ -154,6 +352,46 @@ impl RaderBot {
         let market = self.market.clone();
         let strategy_tx = self.strategy_tx.clone();
 
+        let account = self
+            .strategy_account(settings.account_id.as_ref())
+            .await
+            .ok_or_else(|| {
+                AlgoError::InvalidParams(format!(
+                    "Unknown account: {}",
+                    settings.account_id.clone().unwrap_or_default()
+                ))
+            })?;
+
+        // validate the symbol and interval before the strategy task is spawned,
+        // otherwise the strategy runs without ever receiving market data
+        let (exchange_api, symbol_info) = {
+            let market = market.lock().await;
+            (market.exchange_api(), market.symbol_info())
+        };
+        match symbol_info.get(symbol).await {
+            Ok(_) => {}
+            Err(e @ ApiError::SymbolNotFound(_)) => {
+                return Err(AlgoError::UnknownSymbol(format!(
+                    "{symbol} is not listed on the exchange, {e}"
+                )));
+            }
+            // exchanges without symbol info, ie. BingX, can't validate the symbol
+            Err(ApiError::Unsupported(_)) => {}
+            Err(e) => return Err(AlgoError::Exchange(e)),
+        }
+        if !exchange_api.supported_intervals().contains(&interval) {
+            return Err(AlgoError::UnknownInterval(format!(
+                "{interval} is not supported by the exchange"
+            )));
+        }
+
+        account
+            .lock()
+            .await
+            .validate_leverage(symbol, settings.leverage)
+            .await
+            .map_err(AlgoError::InvalidParams)?;
+
         let mut strategy = Strategy::new(
             strategy_name,
             symbol,
@@ -164,17 +402,28 @@ impl RaderBot {
             algorithm_params,
         )?;
 
-        let handle = strategy.start().await;
+        // checked before the task is spawned so a colliding strategy never trades
+        self.strategy_manager.lock().await.check_insert(&strategy)?;
 
-        let strategy_info = strategy.info().await;
+        self.open_strategy_streams(symbol, interval).await?;
 
-        self.strategy_manager
-            .clone()
-            .lock()
-            .await
-            .insert(strategy, handle);
+        let handle = strategy.start(account.clone()).await;
+        let account = strategy.shadow_account().unwrap_or(account);
+
+        let strategy_id = strategy.id;
+        let mut strategy_manager = self.strategy_manager.lock().await;
 
-        Ok(strategy_info)
+        // a strategy started concurrently may have collided since the check above
+        if let Err(e) = strategy_manager.insert(strategy, handle) {
+            drop(strategy_manager);
+            close_strategy_streams(&self.market, symbol, interval).await;
+            return Err(e);
+        }
+
+        let strategy_info = strategy_manager.strategy_info(&strategy_id, account).await;
+
+        // SAFETY: strategy inserted above
+        Ok(strategy_info.unwrap())
     }
 
     pub async fn stop_strategy(
@@ -183,27 +432,34 @@ impl RaderBot {
         close_positions: bool,
     ) -> Option<StrategySummary> {
         let mut summary: Option<StrategySummary> = None;
-        let account = self.account.clone();
-        let strategy_manager = self.strategy_manager.clone();
 
-        // Remove strategy handles
-        if let Some((handle, strategy)) = strategy_manager.lock().await.get(&strategy_id) {
+        // Remove strategy handles and settings from signal_manager, before the task is aborted
+        // so the supervisor doesn't treat the strategy as having ended unexpectedly
+        let managed = self.strategy_manager.lock().await.take(&strategy_id);
+
+        if let Some((handle, mut strategy)) = managed {
             handle.abort();
 
+            let account = self.trading_account(&strategy).await;
+
             let _summary = strategy.stop(account.clone(), close_positions).await;
 
-            // Save summary
-            self.storage_manager
-                .save_strategy_summary(_summary.clone())
-                .await
-                .ok();
+            close_strategy_streams(&self.market, &strategy.symbol, strategy.interval).await;
+
+            // Save summary, written to a local fallback file if storage keeps failing
+            if let Err(e) = fallback::save_strategy_summary(
+                self.storage_manager.clone(),
+                _summary.clone(),
+                &fallback::fallback_directory(),
+            )
+            .await
+            {
+                error!("Summary of strategy {strategy_id} was lost, unable to write fallback file: {e}");
+            }
 
             summary = Some(_summary);
         };
 
-        // Remove all handles and settings from signal_manager
-        strategy_manager.lock().await.remove(&strategy_id);
-
         summary
     }
 
@@ -266,13 +522,157 @@ impl RaderBot {
         Ok(back_test.result().await)
     }
 
+    /// Runs a back test of a strategy on several intervals, the klines of the base interval are
+    /// read once and resampled to each interval.
+    ///
+    /// # Arguments
+    ///
+    /// * `strategy_name` - Name of the algorithm of the strategy.
+    /// * `symbol` - The symbol the strategy trades.
+    /// * `base_interval` - Interval of the stored klines the intervals are resampled from.
+    /// * `intervals` - The intervals to run the strategy on.
+    /// * `from_ts` - Start of the back test period.
+    /// * `to_ts` - End of the back test period.
+    /// * `settings` - Settings of the strategy.
+    /// * `algorithm_params` - Parameters of the algorithm.
+    ///
+    /// # Returns
+    ///
+    /// The `StrategySummary` of each interval, or an `AlgoError` if the strategy can't be built or
+    /// an interval can't be resampled from the base interval.
+
+    pub async fn run_multi_interval_back_test(
+        &mut self,
+        strategy_name: &str,
+        symbol: &str,
+        base_interval: Interval,
+        intervals: Vec<Interval>,
+        from_ts: u64,
+        to_ts: u64,
+        settings: StrategySettings,
+        algorithm_params: Value,
+    ) -> Result<BTreeMap<Interval, StrategySummary>, AlgoError> {
+        let strategy = Strategy::new(
+            strategy_name,
+            symbol,
+            base_interval,
+            self.strategy_tx.clone(),
+            self.market.clone(),
+            settings,
+            algorithm_params,
+        )?;
+
+        let kline_data = self
+            .market
+            .lock()
+            .await
+            .kline_data_range(symbol, base_interval, Some(from_ts), Some(to_ts), None)
+            .await
+            .unwrap_or_else(|| KlineData::new(symbol, base_interval));
+
+        // TODO: Get initial_balance from params
+        let initial_balance = Some(10_000.0);
+        BackTest::run_multi(
+            strategy,
+            self.market.clone(),
+            initial_balance,
+            intervals,
+            kline_data,
+        )
+        .await
+    }
+
+    /// Builds the info of a copy of a strategy, with its parameters overridden by a patch.
+    ///
+    /// # Arguments
+    ///
+    /// * `strategy_id` - ID of a running strategy, or of a saved summary if it's no longer
+    ///   running.
+    /// * `params_patch` - JSON merge patch applied to the algorithm parameters, keys set to
+    ///   `null` are removed.
+    ///
+    /// # Returns
+    ///
+    /// The `StrategyInfo` to start the copy from, or an `AlgoError` if the strategy isn't running
+    /// and has no saved summary.
+
+    pub async fn cloned_strategy_info(
+        &mut self,
+        strategy_id: StrategyId,
+        params_patch: &Value,
+    ) -> Result<StrategyInfo, AlgoError> {
+        let mut info = match self.get_strategy_info(strategy_id).await {
+            Some(info) => info,
+            None => self
+                .get_historical_strategy_summary(strategy_id)
+                .await
+                .map(|summary| summary.info)
+                .ok_or_else(|| {
+                    AlgoError::InvalidParams(format!(
+                        "Strategy {strategy_id} isn't running and has no saved summary"
+                    ))
+                })?,
+        };
+
+        json::merge_patch(&mut info.params, params_patch);
+        Ok(info)
+    }
+
+    /// Reruns a saved strategy as a back test, with the same algorithm, parameters, settings and
+    /// period as the saved run.
+    ///
+    /// # Arguments
+    ///
+    /// * `strategy_id` - ID of the strategy whose saved summary is rerun.
+    ///
+    /// # Returns
+    ///
+    /// A `SummaryDiff` comparing the rerun with the saved summary, or an `AlgoError` if no summary
+    /// is saved for the strategy or its period can't be reconstructed.
+
+    pub async fn rerun_from_summary(
+        &mut self,
+        strategy_id: StrategyId,
+    ) -> Result<SummaryDiff, AlgoError> {
+        let original = self
+            .storage_manager
+            .get_strategy_summary(strategy_id)
+            .await
+            .map_err(|e| {
+                AlgoError::InvalidParams(format!(
+                    "Unable to load summary of strategy {strategy_id}, {e}"
+                ))
+            })?;
+
+        let (from_ts, to_ts) = original.run_window().ok_or_else(|| {
+            AlgoError::InvalidParams(format!(
+                "Summary of strategy {strategy_id} has no trades or run times to rerun over"
+            ))
+        })?;
+
+        let info = original.info.clone();
+        let rerun = self
+            .run_back_test(
+                &info.name,
+                &info.symbol,
+                info.interval,
+                from_ts,
+                to_ts,
+                info.settings,
+                info.params,
+            )
+            .await?;
+
+        Ok(SummaryDiff::new(original, rerun))
+    }
+
     pub async fn get_strategy_info(&mut self, strategy_id: StrategyId) -> Option<StrategyInfo> {
         let manager = self.strategy_manager.clone();
         let mut manager = manager.lock().await;
-        if let Some((_handle, strategy)) = manager.get(&strategy_id) {
-            return Some(strategy.info().await.clone());
-        }
-        None
+        let (_handle, strategy) = manager.get(&strategy_id)?;
+        let account = self.trading_account(strategy).await;
+
+        manager.strategy_info(&strategy_id, account).await
     }
 
     pub async fn get_strategy_summary(
@@ -280,110 +680,723 @@ impl RaderBot {
         strategy_id: StrategyId,
     ) -> Option<StrategySummary> {
         let manager = self.strategy_manager.clone();
-        let account = self.account.clone();
         let mut manager = manager.lock().await;
         if let Some((_handle, strategy)) = manager.get(&strategy_id) {
+            let account = self.trading_account(strategy).await;
             return Some(strategy.summary(account).await.clone());
         }
         None
     }
 
+    pub async fn get_failed_signals(&mut self) -> Vec<FailedSignal> {
+        let strategy_manager = self.strategy_manager.clone();
+        let mut strategy_manager = strategy_manager.lock().await;
+        strategy_manager.get_signal_manager().failed_signals()
+    }
+
+    pub async fn get_strategy_drawdown_timeline(
+        &mut self,
+        strategy_id: StrategyId,
+    ) -> Option<(StrategySummary, Vec<DrawdownPoint>)> {
+        // fall back to saved summaries for strategies which are no longer running
+        let summary = match self.get_strategy_summary(strategy_id).await {
+            Some(summary) => Some(summary),
+            None => self.get_historical_strategy_summary(strategy_id).await,
+        };
+
+        summary.map(|mut summary| {
+            // saved summaries may predate the peak-to-trough calculation
+            summary.max_drawdown = Strategy::calc_max_drawdown(&summary.trades);
+            let timeline = Strategy::calc_drawdown_timeline(&summary.trades);
+            (summary, timeline)
+        })
+    }
+
+    /// Builds the shareable report of a running or saved strategy.
+    ///
+    /// # Arguments
+    ///
+    /// * `strategy_id` - ID of the strategy, saved summaries are used for strategies which are no
+    ///   longer running.
+    ///
+    /// # Returns
+    ///
+    /// The `StrategyReport` of the strategy, or `None` if it isn't running and has no saved summary.
+
+    pub async fn get_strategy_report(&mut self, strategy_id: StrategyId) -> Option<StrategyReport> {
+        let summary = match self.get_strategy_summary(strategy_id).await {
+            Some(summary) => Some(summary),
+            None => self.get_historical_strategy_summary(strategy_id).await,
+        };
+
+        summary.map(StrategyReport::new)
+    }
+
     pub async fn change_strategy_settings(
         &mut self,
         strategy_id: StrategyId,
         settings: StrategySettings,
     ) -> Option<StrategyInfo> {
+        let account = self.strategy_account(settings.account_id.as_ref()).await?;
+
         let manager = self.strategy_manager.clone();
         let mut manager = manager.lock().await;
         if let Some((_handle, strategy)) = manager.get(&strategy_id) {
-            strategy.change_settings(settings);
-            return Some(strategy.info().await);
+            let account = strategy.shadow_account().unwrap_or(account);
+            strategy.change_settings(settings.clone());
+
+            // keep signal handler in sync so new limits apply to the next signal
+            manager
+                .get_signal_manager()
+                .add_strategy_settings(&strategy_id, settings);
+
+            return manager.strategy_info(&strategy_id, account).await;
         }
         None
     }
 
-    pub async fn set_strategy_params(
+    /// Starts replaying a market message log into the market, unless a replay is running.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path of the log, resolved by `recorder::resolve_log_path`.
+    /// * `speed` - Replay speed relative to the recorded time.
+    ///
+    /// # Returns
+    ///
+    /// The status of the started replay, or the status of the replay which is still running.
+
+    pub fn replay_market_messages(
         &mut self,
-        strategy_id: StrategyId,
-        params: Value,
-    ) -> Result<(), AlgoError> {
-        let manager = self.strategy_manager.clone();
-        let mut manager = manager.lock().await;
-        if let Some((_handle, strategy)) = manager.get(&strategy_id) {
-            strategy.set_algorithm_params(params).await?
+        path: PathBuf,
+        speed: f64,
+    ) -> Result<ReplayStatus, ReplayStatus> {
+        if let Some(replay) = self.market_replay.as_ref().filter(|r| r.is_running()) {
+            return Err(replay.status());
         }
-        Ok(())
-    }
-    pub async fn get_strategy_params(&mut self, strategy_id: StrategyId) -> Option<Value> {
-        let manager = self.strategy_manager.clone();
-        let mut manager = manager.lock().await;
-        if let Some((_handle, strategy)) = manager.get(&strategy_id) {
-            // if let Ok(value) => {
 
-            // }
-            let params = strategy.get_algorithm_params().await;
-            if let Ok(val) = serde_json::to_value(params) {
-                return Some(val);
-            };
-        }
-        None
-    }
+        let replay = MarketReplay::start(path, self.market_tx.clone(), speed);
+        let status = replay.status();
+        self.market_replay = Some(replay);
 
-    // ---
-    // Private Methods
-    // ---
+        Ok(status)
+    }
 
-    async fn init(&mut self) {
-        let strategy_manager = self.strategy_manager.clone();
-        let strategy_rx = self.strategy_rx.clone();
-        let account = self.account.clone();
-        let market = self.market.clone();
+    /// Gets the status of the last market replay, `None` if no replay was started.
 
-        tokio::spawn(async move {
-            while let Some(signal) = strategy_rx.lock().await.recv().await {
-                let strategy_manager = strategy_manager.lock().await;
-                let signal_manager = strategy_manager.get_signal_manager();
-                signal_manager
-                    .handle_signal(signal, market.clone(), account.clone())
-                    .await;
-            }
-        });
+    pub fn replay_status(&self) -> Option<ReplayStatus> {
+        self.market_replay.as_ref().map(|replay| replay.status())
     }
-}
-
-/// Manages multiple trading strategies by storing their handles, settings, and providing methods for insertion, removal, and retrieval.
-pub struct StrategyManager {
-    /// A mapping of strategy IDs to their corresponding join handles for managing strategy execution.
-    strategy_handles: HashMap<StrategyId, JoinHandle<()>>,
-    /// A mapping of strategy IDs to their corresponding strategies.
-    strategies: HashMap<StrategyId, Strategy>,
-    /// Manages signals for strategies.
-    signal_manager: SignalHandler,
-}
 
-impl StrategyManager {
-    /// Constructs a new `StrategyManager`.
+    /// Cancels the running market replay.
     ///
     /// # Returns
     ///
-    /// A new instance of `StrategyManager` with an empty set of strategies and signal manager.
-    pub fn new() -> Self {
-        let signal_manager = SignalHandler::new();
+    /// The status of the cancelled replay, `None` if no replay is running.
 
-        Self {
-            signal_manager,
-            strategy_handles: HashMap::new(),
-            strategies: HashMap::new(),
-        }
+    pub fn cancel_replay(&self) -> Option<ReplayStatus> {
+        let replay = self.market_replay.as_ref()?;
+
+        replay.cancel().then(|| replay.status())
     }
 
-    /// Inserts a strategy along with its join handle into the manager.
-    ///
-    /// # Arguments
+    pub async fn add_account(
+        &mut self,
+        account_id: &str,
+        exchange_api: Arc<dyn ExchangeApi>,
+        dry_run: bool,
+    ) -> ArcMutex<Account> {
+        let mut account = Account::new(exchange_api, true, dry_run).await;
+
+        // added accounts report equity in the same currency and apply the same daily loss limit
+        // as the default account, each with its own kill switch
+        {
+            let default_account = self.account.lock().await;
+            account.set_base_currency(default_account.base_currency());
+            account.set_min_notional(default_account.min_notional());
+            account.set_daily_loss_limit(default_account.daily_loss_limit());
+        }
+
+        let account = ArcMutex::new(account);
+
+        self.accounts
+            .lock()
+            .await
+            .insert(account_id.to_string(), account.clone());
+
+        account
+    }
+
+    pub async fn get_account(&self, account_id: &str) -> Option<ArcMutex<Account>> {
+        self.accounts.lock().await.get(account_id).cloned()
+    }
+
+    pub async fn list_account_ids(&self) -> Vec<AccountId> {
+        self.accounts.lock().await.keys().cloned().collect()
+    }
+
+    /// Schedules an order to be placed at a set time, validating its account and leverage.
+    ///
+    /// # Arguments
+    ///
+    /// * `order` - The order to schedule.
+    ///
+    /// # Returns
+    ///
+    /// The scheduled order, or an error if the order is invalid or its time has passed.
+
+    pub async fn schedule_order(&self, order: ScheduledOrder) -> Result<ScheduledOrder, String> {
+        let account = self
+            .strategy_account(order.account_id.as_ref())
+            .await
+            .ok_or_else(|| {
+                format!(
+                    "Unknown account: {}",
+                    order.account_id.clone().unwrap_or_default()
+                )
+            })?;
+
+        account
+            .lock()
+            .await
+            .validate_leverage(&order.symbol, order.leverage)
+            .await?;
+
+        self.order_scheduler.lock().await.schedule(order).await
+    }
+
+    /// Cancels a scheduled order which has not been placed yet.
+
+    pub async fn cancel_scheduled_order(
+        &self,
+        order_id: &ScheduledOrderId,
+    ) -> Option<ScheduledOrder> {
+        self.order_scheduler.lock().await.cancel(order_id).await
+    }
+
+    /// Lists the scheduled orders which have not been placed yet, ordered by scheduled time.
+
+    pub async fn list_scheduled_orders(&self) -> Vec<ScheduledOrder> {
+        self.order_scheduler.lock().await.orders()
+    }
+
+    /// Updates the algorithm parameters of a running strategy without restarting it.
+    ///
+    /// # Arguments
+    ///
+    /// * `strategy_id` - The ID of the strategy.
+    /// * `params` - The new algorithm parameters.
+    /// * `reset_state` - `true` to clear the klines and indicator values the algorithm has
+    ///   accumulated.
+    ///
+    /// # Returns
+    ///
+    /// `Ok` once the parameters are applied, or an `AlgoError` if the strategy isn't running or
+    /// the parameters are invalid.
+
+    pub async fn set_strategy_params(
+        &mut self,
+        strategy_id: StrategyId,
+        params: Value,
+        reset_state: bool,
+    ) -> Result<(), AlgoError> {
+        let manager = self.strategy_manager.clone();
+        let mut manager = manager.lock().await;
+        match manager.get(&strategy_id) {
+            Some((_handle, strategy)) => strategy.set_algorithm_params(params, reset_state).await,
+            None => Err(AlgoError::InvalidParams(format!(
+                "Unknown strategy: {strategy_id}"
+            ))),
+        }
+    }
+    /// Retrieves the latest evaluations recorded by a running strategy.
+    ///
+    /// # Arguments
+    ///
+    /// * `strategy_id` - The ID of the strategy.
+    ///
+    /// # Returns
+    ///
+    /// The evaluations oldest first, or `None` if the strategy isn't running.
+
+    pub async fn get_strategy_eval_log(
+        &mut self,
+        strategy_id: StrategyId,
+    ) -> Option<Vec<EvalLogEntry>> {
+        let manager = self.strategy_manager.clone();
+        let mut manager = manager.lock().await;
+        let (_handle, strategy) = manager.get(&strategy_id)?;
+
+        Some(strategy.eval_log().await)
+    }
+
+    pub async fn get_strategy_params(&mut self, strategy_id: StrategyId) -> Option<Value> {
+        let manager = self.strategy_manager.clone();
+        let mut manager = manager.lock().await;
+        if let Some((_handle, strategy)) = manager.get(&strategy_id) {
+            // if let Ok(value) => {
+
+            // }
+            let params = strategy.get_algorithm_params().await;
+            if let Ok(val) = serde_json::to_value(params) {
+                return Some(val);
+            };
+        }
+        None
+    }
+
+    /// Gets the exchange API of an exchange by its name, `Binance`, `BingX` or `Mock`.
+    ///
+    /// Real exchanges are built on first use and shared by every account trading on them, each
+    /// mock exchange is a new instance.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - Name of the exchange, case insensitive.
+    ///
+    /// # Returns
+    ///
+    /// The exchange API, or an error if the exchange isn't supported.
+
+    pub async fn named_exchange_api(&self, name: &str) -> Result<Arc<dyn ExchangeApi>, String> {
+        let key = name.to_uppercase();
+
+        if key == "MOCK" {
+            return Ok(Arc::new(MockExchangeApi::default()));
+        }
+
+        let mut exchange_apis = self.exchange_apis.lock().await;
+        if let Some(api) = exchange_apis.get(&key) {
+            return Ok(api.clone());
+        }
+
+        let (http_config, resilience_config) = RaderBot::exchange_configs();
+        let api = RaderBot::build_exchange_api(
+            name,
+            self.market_tx.clone(),
+            http_config,
+            resilience_config,
+        )?;
+        exchange_apis.insert(key, api.clone());
+
+        Ok(api)
+    }
+
+    // ---
+    // Private Methods
+    // ---
+
+    /// Reads the HTTP client and resilience settings used by real exchange APIs.
+    fn exchange_configs() -> (HttpClientConfig, ResilienceConfig) {
+        let http_config = HttpClientConfig::from_settings(
+            dotenv!("HTTP_REQUEST_TIMEOUT_SECS"),
+            dotenv!("HTTP_CONNECT_TIMEOUT_SECS"),
+            dotenv!("HTTP_POOL_SIZE"),
+        );
+        let resilience_config = ResilienceConfig::from_settings(
+            dotenv!("EXCHANGE_MAX_RETRIES"),
+            dotenv!("EXCHANGE_RETRY_BACKOFF_MS"),
+            dotenv!("EXCHANGE_FAILURE_THRESHOLD"),
+            dotenv!("EXCHANGE_COOL_DOWN_SECS"),
+        );
+
+        (http_config, resilience_config)
+    }
+
+    /// Builds the exchange API configured by name, case insensitive, unknown names are an error.
+    /// Requests to real exchanges are retried and go through a circuit breaker.
+    fn build_exchange_api(
+        name: &str,
+        market_tx: MarketSender,
+        http_config: HttpClientConfig,
+        resilience_config: ResilienceConfig,
+    ) -> Result<Arc<dyn ExchangeApi>, String> {
+        let exchange_api: Arc<dyn ExchangeApi> = match name.to_uppercase().as_str() {
+            "BINGX" => Arc::new(BingXApi::new(
+                dotenv!("BINGX_API_KEY"),
+                dotenv!("BINGX_SECRET_KEY"),
+                market_tx,
+                http_config,
+                PollConfig::from_settings(
+                    dotenv!("BINGX_POLL_MAX_FAILURES"),
+                    dotenv!("BINGX_POLL_MAX_BACKOFF_SECS"),
+                ),
+            )),
+            "MOCK" => return Ok(Arc::new(MockExchangeApi::default())),
+            "BINANCE" => Arc::new(BinanceApi::new(
+                dotenv!("BINANCE_API_KEY"),
+                dotenv!("BINANCE_SECRET_KEY"),
+                market_tx,
+                false,
+                http_config,
+                StreamQuarantine::from_setting(dotenv!("STREAM_PARSE_FAILURE_PCT")),
+            )),
+            _ => return Err(format!("Unknown exchange API {name}")),
+        };
+
+        Ok(Arc::new(ResilientExchangeApi::new(
+            exchange_api,
+            resilience_config,
+        )))
+    }
+
+    /// Gets the account a strategy trades on, the default account if none is specified.
+    async fn strategy_account(&self, account_id: Option<&AccountId>) -> Option<ArcMutex<Account>> {
+        match account_id {
+            Some(account_id) => self.get_account(account_id).await,
+            None => Some(self.account.clone()),
+        }
+    }
+
+    /// Gets the account for running strategy settings, which were validated on start.
+    async fn settings_account(&self, settings: &StrategySettings) -> ArcMutex<Account> {
+        self.strategy_account(settings.account_id.as_ref())
+            .await
+            .unwrap_or_else(|| self.account.clone())
+    }
+
+    /// Gets the account a running strategy trades on, its simulated account in shadow mode.
+    async fn trading_account(&self, strategy: &Strategy) -> ArcMutex<Account> {
+        match strategy.shadow_account() {
+            Some(account) => account,
+            None => self.settings_account(&strategy.settings()).await,
+        }
+    }
+
+    /// Subscribes to the kline and trade streams a strategy needs, opening streams not already
+    /// active. Streams are shared by strategies trading the same symbol and interval.
+    async fn open_strategy_streams(
+        &self,
+        symbol: &str,
+        interval: Interval,
+    ) -> Result<(), AlgoError> {
+        let market = self.market.lock().await;
+        let mut subscribed = vec![];
+
+        for (stream_type, interval) in strategy_streams(interval) {
+            if let Err(e) = market.subscribe_stream(stream_type, symbol, interval).await {
+                // streams subscribed before the failure are not needed by the strategy
+                for (stream_type, interval) in subscribed {
+                    market
+                        .unsubscribe_stream(stream_type, symbol, interval)
+                        .await;
+                }
+
+                let stream_id = build_stream_id(symbol, stream_type, interval);
+                return Err(AlgoError::InvalidParams(format!(
+                    "Unable to open stream {stream_id}: {e}"
+                )));
+            }
+
+            subscribed.push((stream_type, interval));
+        }
+
+        Ok(())
+    }
+
+    async fn init(&mut self) {
+        let strategy_manager = self.strategy_manager.clone();
+        let strategy_rx = self.strategy_rx.clone();
+        let accounts = self.accounts.clone();
+        let market = self.market.clone();
+        let storage_manager = self.storage_manager.clone();
+
+        tokio::spawn(async move {
+            while let Some(signal) = strategy_rx.lock().await.recv().await {
+                // accounts lock is not held while the signal is handled
+                let accounts = accounts.lock().await.clone();
+
+                let strategy_id = signal.strategy_id;
+
+                let (dispatch, shadow_account) = {
+                    let mut strategy_manager = strategy_manager.lock().await;
+
+                    let shadow_account = strategy_manager
+                        .get(&strategy_id)
+                        .and_then(|(_, strategy)| strategy.shadow_account());
+
+                    let dispatch = strategy_manager
+                        .get_signal_manager()
+                        .prepare_signal(signal, market.clone())
+                        .await;
+
+                    (dispatch, shadow_account)
+                };
+
+                let Some(mut dispatch) = dispatch else {
+                    continue;
+                };
+
+                // the strategy manager isn't held while failed attempts wait to be retried,
+                // failed signals are logged and kept on the signal manager
+                let _ = match &shadow_account {
+                    Some(account) => dispatch.execute(market.clone(), account.clone()).await,
+                    None => dispatch.route(market.clone(), &accounts).await,
+                };
+
+                let mut strategy_manager = strategy_manager.lock().await;
+                strategy_manager
+                    .get_signal_manager()
+                    .record_failed_signals(dispatch);
+
+                if shadow_account.is_some() {
+                    save_shadow_summary(&mut strategy_manager, strategy_id, &storage_manager).await;
+                }
+            }
+        });
+
+        // order and balance updates pushed by the exchange are applied to the default account
+        let account = self.account.clone();
+        let (exchange_api, dry_run) = {
+            let account = account.lock().await;
+            (account.exchange_api(), account.is_dry_run())
+        };
+
+        if !dry_run {
+            let (account_tx, account_rx) = build_arc_channel::<UserDataEvent>();
+
+            match exchange_api.start_user_data_stream(account_tx).await {
+                Ok(stream_id) => {
+                    info!("Started user data stream: {stream_id}");
+
+                    let notifier = self.notifier.clone();
+                    account
+                        .lock()
+                        .await
+                        .on_trade(Arc::new(move |event: &TradeEvent| {
+                            let TradeEvent::Closed { trade_tx, .. } = event else {
+                                return;
+                            };
+                            if !trade_tx.liquidated {
+                                return;
+                            }
+
+                            let position = &trade_tx.position;
+                            let title = format!("Position liquidated {}", position.symbol);
+                            let message = format!(
+                                "{:?} position {} on {} was liquidated at {}, profit {}",
+                                position.order_side,
+                                position.id,
+                                position.symbol,
+                                trade_tx.close_price,
+                                trade_tx.profit
+                            );
+                            let notifier = notifier.clone();
+                            tokio::spawn(async move {
+                                notifier.notify(Notification::new(&title, &message)).await;
+                            });
+                        }));
+
+                    tokio::spawn(async move {
+                        while let Some(event) = account_rx.lock().await.recv().await {
+                            account.lock().await.handle_user_data_event(event).await;
+                        }
+                    });
+                }
+                Err(e) => info!("Unable to start user data stream: {e}"),
+            }
+        }
+
+        let strategy_manager = self.strategy_manager.clone();
+        let market = self.market.clone();
+        let accounts = self.accounts.clone();
+        let storage_manager = self.storage_manager.clone();
+        let notifier = self.notifier.clone();
+
+        // apply the error policy of strategies whose task ended unexpectedly, and promote shadow
+        // strategies which met their promotion policy
+        tokio::spawn(async move {
+            loop {
+                time::sleep(STRATEGY_SUPERVISION_INTERVAL).await;
+
+                supervise_strategies(
+                    &strategy_manager,
+                    &market,
+                    &accounts,
+                    storage_manager.clone(),
+                    notifier.clone(),
+                )
+                .await;
+
+                promote_shadow_strategies(
+                    &strategy_manager,
+                    &accounts,
+                    notifier.clone(),
+                    &SystemClock,
+                )
+                .await;
+            }
+        });
+
+        let order_scheduler = self.order_scheduler.clone();
+        let market = self.market.clone();
+        let accounts = self.accounts.clone();
+        let notifier = self.notifier.clone();
+
+        // place scheduled orders once their time arrives
+        tokio::spawn(async move {
+            loop {
+                time::sleep(SCHEDULED_ORDER_INTERVAL).await;
+
+                place_scheduled_orders(&order_scheduler, &market, &accounts, notifier.clone())
+                    .await;
+            }
+        });
+
+        let market = self.market.clone();
+        let accounts = self.accounts.clone();
+
+        // refresh the trading status of symbols, so neither orders nor stream reopens wait on
+        // the exchange to learn if a symbol is halted
+        tokio::spawn(async move {
+            loop {
+                refresh_trading_halts(&market, &accounts).await;
+
+                time::sleep(Duration::from_secs(SYMBOL_STATUS_RECHECK_SECS)).await;
+            }
+        });
+
+        let market = self.market.clone();
+        let accounts = self.accounts.clone();
+
+        // close positions once the price reaches their stop loss or take profit
+        tokio::spawn(async move {
+            loop {
+                time::sleep(POSITION_EXIT_INTERVAL).await;
+
+                close_positions_at_exits(&market, &accounts).await;
+            }
+        });
+
+        let account = self.account.clone();
+        let market = self.market.clone();
+        let storage_manager = self.storage_manager.clone();
+        let snapshot_interval = self.snapshot_interval;
+
+        // periodically record account balance for account history
+        tokio::spawn(async move {
+            loop {
+                time::sleep(snapshot_interval).await;
+
+                // prices of the open positions and of the cross pairs the equity is converted at
+                let symbols: Vec<String> = {
+                    let account = account.lock().await;
+                    account
+                        .positions()
+                        .map(|pos| pos.symbol.clone())
+                        .chain(account.conversion_symbols())
+                        .collect()
+                };
+
+                let reader = market.lock().await.reader();
+                let mut last_prices = HashMap::new();
+                for symbol in symbols {
+                    if let Some(price) = reader.last_price(&symbol).await {
+                        last_prices.insert(symbol, price);
+                    }
+                }
+
+                let snapshot = {
+                    let mut account = account.lock().await;
+                    account.set_conversion_prices(&last_prices);
+                    account.snapshot(generate_ts(), &last_prices)
+                };
+
+                if let Err(e) = storage_manager.save_account_snapshot(&snapshot).await {
+                    log::warn!("Unable to save account snapshot, {e}")
+                }
+            }
+        });
+    }
+}
+
+/// Manages multiple trading strategies by storing their handles, settings, and providing methods for insertion, removal, and retrieval.
+pub struct StrategyManager {
+    /// A mapping of strategy IDs to their corresponding join handles for managing strategy execution.
+    strategy_handles: HashMap<StrategyId, JoinHandle<()>>,
+    /// A mapping of strategy IDs to their corresponding strategies.
+    strategies: HashMap<StrategyId, Strategy>,
+    /// Manages signals for strategies.
+    signal_manager: SignalHandler,
+    /// Whether strategies running the same algorithm on the same symbol are rejected.
+    unique_names: bool,
+}
+
+impl StrategyManager {
+    /// Constructs a new `StrategyManager`.
+    ///
+    /// # Returns
+    ///
+    /// A new instance of `StrategyManager` with an empty set of strategies and signal manager.
+    pub fn new() -> Self {
+        let signal_manager = SignalHandler::new();
+
+        Self {
+            signal_manager,
+            strategy_handles: HashMap::new(),
+            strategies: HashMap::new(),
+            unique_names: false,
+        }
+    }
+
+    /// Sets whether only one strategy of each name may run on a symbol, set by
+    /// `UNIQUE_STRATEGY_NAMES`.
+    pub fn set_unique_names(&mut self, unique_names: bool) {
+        self.unique_names = unique_names;
+    }
+
+    /// Checks a strategy can be inserted without replacing a managed strategy.
+    ///
+    /// # Arguments
+    ///
+    /// * `strategy` - The strategy to check.
+    ///
+    /// # Returns
+    ///
+    /// An error if a strategy with the same id is managed, or with unique names enabled, a
+    /// strategy with the same name runs on the same symbol.
+    pub fn check_insert(&self, strategy: &Strategy) -> Result<(), AlgoError> {
+        if self.strategies.contains_key(&strategy.id) {
+            return Err(AlgoError::Conflict(format!(
+                "Strategy {} is already running",
+                strategy.id
+            )));
+        }
+
+        if self.unique_names {
+            let running = self
+                .strategies
+                .values()
+                .find(|running| running.name == strategy.name && running.symbol == strategy.symbol);
+
+            if let Some(running) = running {
+                return Err(AlgoError::Conflict(format!(
+                    "Strategy {} already runs {} on {}",
+                    running.id, strategy.name, strategy.symbol
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Inserts a strategy along with its join handle into the manager.
+    ///
+    /// # Arguments
     ///
     /// * `strategy` - The strategy to insert.
     /// * `handle` - The join handle associated with the strategy execution.
-    pub fn insert(&mut self, strategy: Strategy, handle: JoinHandle<()>) {
+    ///
+    /// # Returns
+    ///
+    /// An error if the strategy collides with a managed strategy, see `check_insert`. The task of
+    /// a rejected strategy is aborted, the managed strategy is left running.
+    pub fn insert(&mut self, strategy: Strategy, handle: JoinHandle<()>) -> Result<(), AlgoError> {
+        if let Err(e) = self.check_insert(&strategy) {
+            handle.abort();
+            return Err(e);
+        }
+
         let strategy_id = strategy.id.clone();
         let settings = strategy.settings();
 
@@ -392,18 +1405,47 @@ impl StrategyManager {
 
         self.strategy_handles.insert(strategy.id, handle);
         self.strategies.insert(strategy.id, strategy);
+
+        Ok(())
     }
 
-    /// Removes a strategy and its associated join handle from the manager.
+    /// Removes a strategy and its associated join handle from the manager, returning them.
     ///
     /// # Arguments
     ///
-    /// * `strategy_id` - The ID of the strategy to remove.
-    pub fn remove(&mut self, strategy_id: &StrategyId) {
-        self.strategy_handles.remove(&strategy_id);
-        self.strategies.remove(&strategy_id);
+    /// * `strategy_id` - The ID of the strategy to take.
+    ///
+    /// # Returns
+    ///
+    /// A tuple containing the join handle and the strategy, if found.
+    pub fn take(&mut self, strategy_id: &StrategyId) -> Option<(JoinHandle<()>, Strategy)> {
+        let handle = self.strategy_handles.remove(strategy_id);
+        let strategy = self.strategies.remove(strategy_id);
+
+        self.signal_manager.remove_strategy_settings(strategy_id);
+
+        handle.zip(strategy)
+    }
+
+    /// Removes every strategy whose task has finished without the strategy being stopped, ie.
+    /// the task panicked or was aborted.
+    ///
+    /// # Returns
+    ///
+    /// The finished strategies.
+    pub fn take_finished(&mut self) -> Vec<Strategy> {
+        let finished: Vec<StrategyId> = self
+            .strategy_handles
+            .iter()
+            .filter(|(_, handle)| handle.is_finished())
+            .map(|(strategy_id, _)| *strategy_id)
+            .collect();
 
-        self.signal_manager.remove_strategy_settings(strategy_id);
+        finished
+            .iter()
+            .filter_map(|strategy_id| self.take(strategy_id))
+            .map(|(_, strategy)| strategy)
+            .collect()
     }
 
     /// Retrieves the join handle and mutable reference to a strategy with the specified ID, if present.
@@ -425,6 +1467,30 @@ impl StrategyManager {
         None
     }
 
+    /// Retrieves info for a managed strategy, including the capital allocation it has left.
+    ///
+    /// # Arguments
+    ///
+    /// * `strategy_id` - The ID of the strategy.
+    /// * `account` - The account holding the strategy's positions.
+    ///
+    /// # Returns
+    ///
+    /// The strategy info, if the strategy is managed.
+    pub async fn strategy_info(
+        &self,
+        strategy_id: &StrategyId,
+        account: ArcMutex<Account>,
+    ) -> Option<StrategyInfo> {
+        let strategy = self.strategies.get(strategy_id)?;
+        let mut info = strategy.info().await;
+
+        let strategy_margin = account.lock().await.strategy_committed_margin(*strategy_id);
+        info.remaining_allocation_usd = info.settings.remaining_allocation(strategy_margin);
+
+        Some(info)
+    }
+
     /// Retrieves a list of strategy IDs currently managed by the manager.
     ///
     /// # Returns
@@ -443,8 +1509,1006 @@ impl StrategyManager {
     ///
     /// # Returns
     ///
-    /// A reference to the signal manager.
-    pub fn get_signal_manager(&self) -> &SignalHandler {
-        &self.signal_manager
+    /// A mutable reference to the signal manager.
+    pub fn get_signal_manager(&mut self) -> &mut SignalHandler {
+        &mut self.signal_manager
+    }
+}
+
+/// Applies the configured `StrategyErrorPolicy` to the positions of every strategy whose task
+/// ended unexpectedly, saving the strategy summary and notifying.
+///
+/// # Arguments
+///
+/// * `strategy_manager` - Manager of the running strategies.
+/// * `market` - Market the streams of the ended strategies are unsubscribed from.
+/// * `accounts` - Accounts the strategies trade on.
+/// * `storage_manager` - Storage the strategy summaries are saved to.
+/// * `notifier` - Notifier used to report the ended strategies.
+///
+/// # Returns
+///
+/// The IDs of the strategies which ended.
+
+async fn supervise_strategies(
+    strategy_manager: &ArcMutex<StrategyManager>,
+    market: &ArcMutex<Market>,
+    accounts: &ArcMutex<HashMap<AccountId, ArcMutex<Account>>>,
+    storage_manager: Arc<dyn StorageManager>,
+    notifier: Arc<dyn Notifier>,
+) -> Vec<StrategyId> {
+    let finished = strategy_manager.lock().await.take_finished();
+    let mut strategy_ids = vec![];
+
+    for mut strategy in finished {
+        let strategy_id = strategy.id;
+        let settings = strategy.settings();
+        let policy = settings.error_policy;
+        strategy_ids.push(strategy_id);
+
+        close_strategy_streams(market, &strategy.symbol, strategy.interval).await;
+
+        let account_id = settings.account_id.as_deref().unwrap_or(DEFAULT_ACCOUNT_ID);
+        let account = match strategy.shadow_account() {
+            Some(account) => Some(account),
+            None => accounts.lock().await.get(account_id).cloned(),
+        };
+        let Some(account) = account else {
+            error!("Strategy {strategy_id} ended unexpectedly, account {account_id} not found");
+            continue;
+        };
+
+        error!("Strategy {strategy_id} ended unexpectedly, applying {policy:?} policy");
+
+        let summary = strategy
+            .stop(account.clone(), policy == StrategyErrorPolicy::CloseAll)
+            .await;
+
+        if policy == StrategyErrorPolicy::ConvertToManual {
+            account.lock().await.detach_strategy_positions(strategy_id);
+        }
+
+        if let Err(e) = fallback::save_strategy_summary(
+            storage_manager.clone(),
+            summary.clone(),
+            &fallback::fallback_directory(),
+        )
+        .await
+        {
+            error!(
+                "Summary of strategy {strategy_id} was lost, unable to write fallback file: {e}"
+            );
+        }
+
+        let title = format!("Strategy ended unexpectedly {}", strategy.symbol);
+        let message = format!(
+            "{} strategy {strategy_id} on {} ended unexpectedly, {policy:?} policy applied, profit {}",
+            strategy.name, strategy.symbol, summary.profit
+        );
+        notifier.notify(Notification::new(&title, &message)).await;
+    }
+
+    strategy_ids
+}
+
+/// Switches strategies in shadow mode to live trading once their simulated trades meet their
+/// `PromotionPolicy`, restarting them on their account and notifying.
+///
+/// # Arguments
+///
+/// * `strategy_manager` - Manager of the running strategies.
+/// * `accounts` - Accounts the promoted strategies trade on.
+/// * `notifier` - Notifier used to report the promoted strategies.
+/// * `clock` - Clock the time spent in shadow mode is measured with.
+///
+/// # Returns
+///
+/// The IDs of the strategies which were promoted.
+
+async fn promote_shadow_strategies(
+    strategy_manager: &ArcMutex<StrategyManager>,
+    accounts: &ArcMutex<HashMap<AccountId, ArcMutex<Account>>>,
+    notifier: Arc<dyn Notifier>,
+    clock: &dyn Clock,
+) -> Vec<StrategyId> {
+    let mut strategy_manager = strategy_manager.lock().await;
+    let mut promoted = vec![];
+    let mut notifications = vec![];
+
+    for strategy_id in strategy_manager.list_ids() {
+        let Some((_, strategy)) = strategy_manager.get(&strategy_id) else {
+            continue;
+        };
+        let settings = strategy.settings();
+        let (Some(policy), Some(since), Some(shadow_account)) = (
+            settings.promote_after,
+            strategy.shadow_since(),
+            strategy.shadow_account(),
+        ) else {
+            continue;
+        };
+        if settings.mode != StrategyMode::Shadow {
+            continue;
+        }
+
+        let shadow_profit = shadow_account
+            .lock()
+            .await
+            .strategy_trade_aggregates(strategy_id)
+            .profit();
+        if !policy.is_met(since, clock.now(), shadow_profit) {
+            continue;
+        }
+
+        let account_id = settings.account_id.as_deref().unwrap_or(DEFAULT_ACCOUNT_ID);
+        let Some(account) = accounts.lock().await.get(account_id).cloned() else {
+            error!("Unable to promote strategy {strategy_id}, account {account_id} not found");
+            continue;
+        };
+
+        // the manager is locked throughout so no signal is routed while the strategy restarts
+        let Some((handle, mut strategy)) = strategy_manager.take(&strategy_id) else {
+            continue;
+        };
+        handle.abort();
+        strategy.promote_to_live();
+        let handle = strategy.start(account).await;
+
+        if let Err(e) = strategy_manager.insert(strategy, handle) {
+            error!("Unable to promote strategy {strategy_id}, {e}");
+            continue;
+        }
+
+        info!("Promoted strategy {strategy_id} to live trading, shadow profit {shadow_profit}");
+        let title = format!("Strategy promoted to live {strategy_id}");
+        let message = format!(
+            "Strategy {strategy_id} traded in shadow mode for at least {}s with a profit of \
+             {shadow_profit}, now trading live on account {account_id}",
+            policy.min_duration_secs
+        );
+        notifications.push(Notification::new(&title, &message));
+
+        promoted.push(strategy_id);
+    }
+
+    // slow notifiers don't hold up strategies waiting on the manager
+    drop(strategy_manager);
+    for notification in notifications {
+        notifier.notify(notification).await;
+    }
+
+    promoted
+}
+
+/// Saves the summary of a strategy in shadow mode, so its simulated trades are kept with the
+/// summaries of stopped strategies.
+///
+/// # Arguments
+///
+/// * `strategy_manager` - Manager of the running strategies.
+/// * `strategy_id` - The ID of the strategy in shadow mode.
+/// * `storage_manager` - Storage the summary is saved to.
+
+async fn save_shadow_summary(
+    strategy_manager: &mut StrategyManager,
+    strategy_id: StrategyId,
+    storage_manager: &Arc<dyn StorageManager>,
+) {
+    let Some((_, strategy)) = strategy_manager.get(&strategy_id) else {
+        return;
+    };
+    let Some(account) = strategy.shadow_account() else {
+        return;
+    };
+
+    let summary = strategy.summary(account).await;
+
+    if let Err(e) = storage_manager.save_strategy_summary(summary).await {
+        log::warn!("Unable to save shadow summary of strategy {strategy_id}, {e}");
+    }
+}
+
+/// Places the scheduled orders whose time has arrived at the last price of their symbol.
+///
+/// Due orders are only removed from the scheduler once they are placed, so an order isn't lost
+/// when placing it fails. Failed orders are retried on the next check until they expire, expired
+/// orders are reported with the notifier.
+///
+/// # Arguments
+///
+/// * `order_scheduler` - Scheduler holding the pending orders.
+/// * `market` - Market the last prices are read from.
+/// * `accounts` - Accounts the orders are placed on.
+/// * `notifier` - Notifier used to report orders which expired before they could be placed.
+///
+/// # Returns
+///
+/// The orders which were placed.
+
+async fn place_scheduled_orders(
+    order_scheduler: &ArcMutex<OrderScheduler>,
+    market: &ArcMutex<Market>,
+    accounts: &ArcMutex<HashMap<AccountId, ArcMutex<Account>>>,
+    notifier: Arc<dyn Notifier>,
+) -> Vec<ScheduledOrder> {
+    let expired = order_scheduler.lock().await.take_expired().await;
+    for order in expired {
+        error!("Scheduled order {} expired before it was placed", order.id);
+
+        let title = format!("Scheduled order failed {}", order.symbol);
+        let message = format!(
+            "{:?} order {} on {} was not placed before it expired",
+            order.side, order.id, order.symbol
+        );
+        notifier.notify(Notification::new(&title, &message)).await;
+    }
+
+    // the scheduler isn't locked while placing, so orders can still be listed or cancelled
+    let due = order_scheduler.lock().await.due();
+    let mut placed = vec![];
+
+    for order in due {
+        let account_id = order.account_id.as_deref().unwrap_or(DEFAULT_ACCOUNT_ID);
+        let account = accounts.lock().await.get(account_id).cloned();
+        let reader = market.lock().await.reader();
+        let last_price = reader.last_price(&order.symbol).await;
+
+        let result = match (account, last_price) {
+            (Some(account), Some(last_price)) => account
+                .lock()
+                .await
+                .open_position(
+                    &order.symbol,
+                    order.size,
+                    order.leverage,
+                    order.side.clone(),
+                    last_price,
+                    None,
+                    None,
+                )
+                .await
+                .map(|position| position.id)
+                .ok_or_else(|| "position could not be opened".to_string()),
+            (None, _) => Err(format!("account {account_id} not found")),
+            (_, None) => Err(format!("last price of {} not found", order.symbol)),
+        };
+
+        match result {
+            Ok(position_id) => {
+                info!(
+                    "Placed scheduled {:?} order {} on {}, opened position {position_id}",
+                    order.side, order.id, order.symbol
+                );
+                order_scheduler.lock().await.complete(&order.id).await;
+                placed.push(order);
+            }
+            Err(e) => log::warn!(
+                "Unable to place scheduled order {}, retrying, {e}",
+                order.id
+            ),
+        }
+    }
+
+    placed
+}
+
+/// Refreshes the trading status of the symbols checked by the market and every account, with
+/// the exchange each of them trades on. Neither is locked while the statuses are fetched.
+///
+/// # Arguments
+///
+/// * `market` - Market whose stream reopens are paused for halted symbols.
+/// * `accounts` - Accounts whose orders are paused for halted symbols.
+
+async fn refresh_trading_halts(
+    market: &ArcMutex<Market>,
+    accounts: &ArcMutex<HashMap<AccountId, ArcMutex<Account>>>,
+) {
+    let mut halts = vec![];
+
+    {
+        let market = market.lock().await;
+        halts.push((market.trading_halts(), market.exchange_api()));
+    }
+
+    let accounts: Vec<ArcMutex<Account>> = accounts.lock().await.values().cloned().collect();
+    for account in accounts {
+        let account = account.lock().await;
+        halts.push((account.trading_halts(), account.exchange_api()));
+    }
+
+    for (trading_halts, exchange_api) in halts {
+        trading_halts
+            .refresh(exchange_api.as_ref(), generate_ts())
+            .await;
+    }
+}
+
+/// Closes the positions of every account whose stop loss or take profit was reached.
+///
+/// Only the prices of symbols with a position holding an exit are read, the accounts aren't
+/// locked while the prices are fetched.
+///
+/// # Arguments
+///
+/// * `market` - Market the last prices are read from.
+/// * `accounts` - Accounts whose positions are checked.
+///
+/// # Returns
+///
+/// The trades of the closed positions.
+
+async fn close_positions_at_exits(
+    market: &ArcMutex<Market>,
+    accounts: &ArcMutex<HashMap<AccountId, ArcMutex<Account>>>,
+) -> Vec<TradeTx> {
+    let accounts: Vec<ArcMutex<Account>> = accounts.lock().await.values().cloned().collect();
+    let mut trades = vec![];
+
+    for account in accounts {
+        let mut symbols: Vec<String> = account
+            .lock()
+            .await
+            .positions()
+            .filter(|pos| pos.stop_loss.is_some() || pos.take_profit.is_some())
+            .map(|pos| pos.symbol.clone())
+            .collect();
+
+        if symbols.is_empty() {
+            continue;
+        }
+        symbols.sort();
+        symbols.dedup();
+
+        let reader = market.lock().await.reader();
+        let last_prices = reader.last_prices(&symbols).await;
+
+        for trade in account
+            .lock()
+            .await
+            .close_positions_at_exits(&last_prices)
+            .await
+        {
+            info!(
+                "Closed position {} on {} at {}, {}",
+                trade.position.id,
+                trade.position.symbol,
+                trade.close_price,
+                trade.close_reason.as_deref().unwrap_or_default()
+            );
+            trades.push(trade);
+        }
+    }
+
+    trades
+}
+
+/// Streams a strategy trading on an interval needs, the kline stream of the interval and the
+/// trade stream.
+fn strategy_streams(interval: Interval) -> [(StreamType, Option<Interval>); 2] {
+    [
+        (StreamType::Kline, Some(interval)),
+        (StreamType::Trade, None),
+    ]
+}
+
+/// Unsubscribes a stopped strategy from its streams, closing streams no other strategy needs.
+async fn close_strategy_streams(market: &ArcMutex<Market>, symbol: &str, interval: Interval) {
+    let market = market.lock().await;
+
+    for (stream_type, interval) in strategy_streams(interval) {
+        market
+            .unsubscribe_stream(stream_type, symbol, interval)
+            .await;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use tokio::test;
+    use uuid::Uuid;
+
+    use crate::{
+        account::trade::{OrderSide, Position},
+        algo::builder::AlgoBuilder,
+        exchange::stream::StreamMeta,
+        market::{kline::Kline, trade::Trade},
+        storage::memory::MemoryStorage,
+        strategy::{
+            algorithm::Algorithm, indicator::AlgoContext, signal::SignalMessageType,
+            strategy::PromotionPolicy, types::AlgoEvalResult,
+        },
+        testutil::{test_bot, test_bot_with, TestClock},
+        utils::{
+            kline::build_kline_key,
+            time::{generate_ts, timestamp_to_string, MIN_AS_MILI, SEC_AS_MILI},
+        },
+    };
+
+    #[test]
+    async fn test_separate_data_and_execution_exchanges() {
+        let data_exchange_api: Arc<dyn ExchangeApi> = Arc::new(MockExchangeApi::default());
+        let execution_exchange_api: Arc<dyn ExchangeApi> = Arc::new(MockExchangeApi::default());
+
+        let bot = test_bot_with(
+            data_exchange_api.clone(),
+            execution_exchange_api.clone(),
+            Arc::new(MemoryStorage::default()),
+        )
+        .await;
+
+        let market_api = bot.market.lock().await.exchange_api();
+        assert!(Arc::ptr_eq(&market_api, &data_exchange_api));
+        assert!(!Arc::ptr_eq(&market_api, &execution_exchange_api));
+
+        let account_api = bot.account.lock().await.exchange_api();
+        assert!(Arc::ptr_eq(&account_api, &execution_exchange_api));
+        assert!(!Arc::ptr_eq(&account_api, &data_exchange_api));
+    }
+
+    #[test]
+    async fn test_named_exchange_api() {
+        let bot = test_bot(Arc::new(MockExchangeApi::default())).await;
+
+        // each exchange has its own API, shared by the accounts using it
+        let binance = bot.named_exchange_api("Binance").await.unwrap();
+        let bingx = bot.named_exchange_api("BingX").await.unwrap();
+        assert!(!Arc::ptr_eq(&binance, &bingx));
+        assert!(Arc::ptr_eq(
+            &binance,
+            &bot.named_exchange_api("binance").await.unwrap()
+        ));
+
+        assert!(bot.named_exchange_api("Bing").await.is_err());
+    }
+
+    #[test]
+    async fn test_start_strategy_validates_symbol() {
+        let mut bot = test_bot(Arc::new(MockExchangeApi::default())).await;
+
+        let params = json!({ "sma_period": 5 });
+
+        // unknown symbol is rejected before the strategy is spawned
+        let result = bot
+            .start_strategy(
+                "SimpleMovingAverage",
+                "UNKNOWNUSDT",
+                Interval::Min1,
+                StrategySettings::default(),
+                params.clone(),
+            )
+            .await;
+        assert!(matches!(result, Err(AlgoError::UnknownSymbol(_))));
+        assert!(bot.get_active_strategy_ids().await.is_empty());
+
+        // valid symbol opens the needed streams and starts the strategy
+        let info = bot
+            .start_strategy(
+                "SimpleMovingAverage",
+                "ETHUSDT",
+                Interval::Min5,
+                StrategySettings::default(),
+                params,
+            )
+            .await
+            .unwrap();
+        assert_eq!(bot.get_active_strategy_ids().await, vec![info.id]);
+
+        let stream_ids: Vec<String> = bot
+            .market
+            .lock()
+            .await
+            .active_streams()
+            .await
+            .into_iter()
+            .map(|meta| meta.id)
+            .collect();
+        assert!(stream_ids.contains(&build_stream_id(
+            "ETHUSDT",
+            StreamType::Kline,
+            Some(Interval::Min5)
+        )));
+        assert!(stream_ids.contains(&build_stream_id("ETHUSDT", StreamType::Trade, None)));
+    }
+
+    #[test]
+    async fn test_start_strategy_symbol_check_errors() {
+        let params = json!({ "sma_period": 5 });
+
+        // failed requests aren't reported as unknown symbols, exchanges without symbol info
+        // skip the check
+        for (error, started) in [
+            (ApiError::Network("timed out".to_string()), false),
+            (ApiError::CircuitOpen("cooling down".to_string()), false),
+            (ApiError::Unsupported("no symbol info".to_string()), true),
+        ] {
+            let mut bot = test_bot(Arc::new(MockExchangeApi::with_symbol_info_error(error))).await;
+
+            let result = bot
+                .start_strategy(
+                    "SimpleMovingAverage",
+                    "ETHUSDT",
+                    Interval::Min5,
+                    StrategySettings::default(),
+                    params.clone(),
+                )
+                .await;
+
+            if started {
+                assert!(result.is_ok());
+            } else {
+                assert!(matches!(result, Err(AlgoError::Exchange(_))));
+            }
+        }
+    }
+
+    struct DummyAlgo {
+        params: Value,
+    }
+
+    impl Algorithm for DummyAlgo {
+        fn evaluate(
+            &mut self,
+            _kline: Kline,
+            _trades: &[Trade],
+            _ctx: &AlgoContext,
+        ) -> AlgoEvalResult {
+            AlgoEvalResult::Ignore
+        }
+
+        fn set_params(&mut self, params: Value) -> Result<(), AlgoError> {
+            self.params = params;
+            Ok(())
+        }
+
+        fn get_params(&self) -> &Value {
+            &self.params
+        }
+
+        fn data_points(&self) -> Vec<Kline> {
+            vec![]
+        }
+
+        fn clean_data_points(&mut self) {}
+    }
+
+    #[test]
+    async fn test_start_strategy_with_registered_algorithm() {
+        let mut bot = test_bot(Arc::new(MockExchangeApi::default())).await;
+
+        let result = bot
+            .start_strategy(
+                "DummyAlgo",
+                "BTCUSDT",
+                Interval::Min1,
+                StrategySettings::default(),
+                json!({}),
+            )
+            .await;
+        assert!(matches!(result, Err(AlgoError::UnkownName(_))));
+
+        AlgoBuilder::register("DummyAlgo", |params| {
+            Ok(Box::new(DummyAlgo { params }) as Box<dyn Algorithm>)
+        });
+
+        let info = bot
+            .start_strategy(
+                "DummyAlgo",
+                "BTCUSDT",
+                Interval::Min1,
+                StrategySettings::default(),
+                json!({ "dummy": true }),
+            )
+            .await
+            .unwrap();
+        assert_eq!(bot.get_active_strategy_ids().await, vec![info.id]);
+
+        // built-in algorithms are registered through the same registry
+        assert!(
+            AlgoBuilder::build_algorithm("SimpleMovingAverage", json!({ "sma_period": 5 })).is_ok()
+        );
+    }
+
+    #[test]
+    async fn test_error_policy_applied_when_strategy_ends_unexpectedly() {
+        let mut bot = test_bot(Arc::new(MockExchangeApi::default())).await;
+
+        let mut strategy_ids = vec![];
+        for (symbol, error_policy) in [
+            ("BTCUSDT", StrategyErrorPolicy::CloseAll),
+            ("ETHUSDT", StrategyErrorPolicy::ConvertToManual),
+        ] {
+            let settings = StrategySettings {
+                error_policy,
+                ..Default::default()
+            };
+            let info = bot
+                .start_strategy(
+                    "SimpleMovingAverage",
+                    symbol,
+                    Interval::Min1,
+                    settings,
+                    json!({ "sma_period": 5 }),
+                )
+                .await
+                .unwrap();
+
+            bot.account
+                .lock()
+                .await
+                .open_position(symbol, 100.0, 1, OrderSide::Buy, 100.0, Some(info.id), None)
+                .await
+                .unwrap();
+
+            strategy_ids.push(info.id);
+        }
+
+        // simulate the strategy tasks ending without the strategies being stopped
+        for strategy_id in &strategy_ids {
+            let mut manager = bot.strategy_manager.lock().await;
+            let (handle, _) = manager.get(strategy_id).unwrap();
+            handle.abort();
+            while !handle.is_finished() {
+                time::sleep(Duration::from_millis(10)).await;
+            }
+        }
+
+        let ended = supervise_strategies(
+            &bot.strategy_manager,
+            &bot.market,
+            &bot.accounts,
+            bot.storage_manager.clone(),
+            bot.notifier.clone(),
+        )
+        .await;
+        assert_eq!(ended.len(), 2);
+        assert!(bot.get_active_strategy_ids().await.is_empty());
+
+        let account = bot.account.lock().await;
+
+        // closed by the CloseAll policy
+        assert_eq!(account.trades().len(), 1);
+        assert_eq!(account.trades()[0].position.symbol, "BTCUSDT");
+
+        // left open and detached by the ConvertToManual policy
+        let positions: Vec<&Position> = account.positions().collect();
+        assert_eq!(positions.len(), 1);
+        assert_eq!(positions[0].symbol, "ETHUSDT");
+        assert_eq!(positions[0].strategy_id, None);
+    }
+
+    #[test]
+    async fn test_strategies_on_one_symbol_share_streams() {
+        let mut bot = test_bot(Arc::new(MockExchangeApi::default())).await;
+
+        let kline_stream_id = build_stream_id("ETHUSDT", StreamType::Kline, Some(Interval::Min5));
+        let count_kline_streams = |streams: Vec<StreamMeta>| {
+            streams
+                .iter()
+                .filter(|meta| meta.id == kline_stream_id)
+                .count()
+        };
+
+        let mut strategy_ids = vec![];
+        for _ in 0..2 {
+            let info = bot
+                .start_strategy(
+                    "SimpleMovingAverage",
+                    "ETHUSDT",
+                    Interval::Min5,
+                    StrategySettings::default(),
+                    json!({ "sma_period": 5 }),
+                )
+                .await
+                .unwrap();
+            strategy_ids.push(info.id);
+        }
+
+        let active_streams = bot.market.lock().await.active_streams().await;
+        assert_eq!(count_kline_streams(active_streams), 1);
+
+        // still needed by the other strategy
+        bot.stop_strategy(strategy_ids[0], false).await.unwrap();
+        let active_streams = bot.market.lock().await.active_streams().await;
+        assert_eq!(count_kline_streams(active_streams), 1);
+
+        bot.stop_strategy(strategy_ids[1], false).await.unwrap();
+        let active_streams = bot.market.lock().await.active_streams().await;
+        assert_eq!(count_kline_streams(active_streams), 0);
+    }
+
+    #[test]
+    async fn test_shadow_strategy_opens_no_account_positions() {
+        let mut bot = test_bot(Arc::new(MockExchangeApi::default())).await;
+
+        let settings = StrategySettings {
+            mode: StrategyMode::Shadow,
+            ..Default::default()
+        };
+        let info = bot
+            .start_strategy(
+                "SimpleMovingAverage",
+                "BTCUSDT",
+                Interval::Min1,
+                settings,
+                json!({ "sma_period": 5 }),
+            )
+            .await
+            .unwrap();
+
+        // signal emitted by the strategy task on a closed kline
+        bot.strategy_tx
+            .send(SignalMessage {
+                strategy_id: info.id,
+                order_side: OrderSide::Buy,
+                symbol: "BTCUSDT".to_string(),
+                price: 100.0,
+                is_back_test: false,
+                close_time: timestamp_to_string(generate_ts()),
+                interval: Some(Interval::Min1),
+                ty: SignalMessageType::Standard,
+            })
+            .unwrap();
+
+        let mut saved = None;
+        for _ in 0..100 {
+            saved = bot.get_historical_strategy_summary(info.id).await;
+            if saved.is_some() {
+                break;
+            }
+            time::sleep(Duration::from_millis(10)).await;
+        }
+
+        // the simulated fill is saved and reported by the summary
+        assert_eq!(saved.unwrap().positions.len(), 1);
+        let summary = bot.get_strategy_summary(info.id).await.unwrap();
+        assert_eq!(summary.positions.len(), 1);
+        assert_eq!(summary.positions[0].strategy_id, Some(info.id));
+
+        assert_eq!(bot.account.lock().await.positions().count(), 0);
+
+        let summary = bot.stop_strategy(info.id, true).await.unwrap();
+        assert_eq!(summary.trades.len(), 1);
+        assert_eq!(bot.account.lock().await.positions().count(), 0);
+        assert!(bot.account.lock().await.trades().is_empty());
+    }
+
+    #[test]
+    async fn test_rerun_from_summary_reproduces_profit() {
+        let exchange_api: Arc<dyn ExchangeApi> = Arc::new(MockExchangeApi::default());
+        let storage_manager: Arc<dyn StorageManager> = Arc::new(MemoryStorage::default());
+
+        let start = 1_700_000_040_000;
+        let klines: Vec<Kline> = (0..30)
+            .map(|i| {
+                let close = 100.0 + ((i * 7) % 11) as f64;
+                Kline {
+                    symbol: "BTCUSDT".to_string(),
+                    interval: Interval::Min1,
+                    open: close,
+                    high: close,
+                    low: close,
+                    close,
+                    volume: 1.0,
+                    quote_volume: 0.0,
+                    open_time: start + i * MIN_AS_MILI,
+                    close_time: start + (i + 1) * MIN_AS_MILI - 1,
+                }
+            })
+            .collect();
+        storage_manager
+            .save_klines(&klines, &build_kline_key("BTCUSDT", Interval::Min1), false)
+            .await
+            .unwrap();
+
+        let mut bot =
+            test_bot_with(exchange_api.clone(), exchange_api, storage_manager.clone()).await;
+
+        let original = bot
+            .run_back_test(
+                "SimpleMovingAverage",
+                "BTCUSDT",
+                Interval::Min1,
+                start,
+                start + 30 * MIN_AS_MILI - 1,
+                StrategySettings::default(),
+                json!({ "sma_period": 2 }),
+            )
+            .await
+            .unwrap();
+        assert!(!original.trades.is_empty());
+        storage_manager
+            .save_strategy_summary(original.clone())
+            .await
+            .unwrap();
+
+        let diff = bot.rerun_from_summary(original.info.id).await.unwrap();
+        assert_eq!(diff.original.info.id, original.info.id);
+        assert_eq!(diff.trade_count_diff, 0);
+        assert!((diff.rerun.profit - original.profit).abs() < 1e-9);
+        assert!(diff.profit_diff.abs() < 1e-9);
+
+        // strategies without a saved summary can't be rerun
+        assert!(bot.rerun_from_summary(Uuid::new_v4()).await.is_err());
+    }
+
+    #[test]
+    async fn test_clone_strategy_with_patched_params() {
+        let mut bot = test_bot(Arc::new(MockExchangeApi::default())).await;
+
+        let original = bot
+            .start_strategy(
+                "SimpleMovingAverage",
+                "ETHUSDT",
+                Interval::Min5,
+                StrategySettings::default(),
+                json!({ "sma_period": 5 }),
+            )
+            .await
+            .unwrap();
+
+        let info = bot
+            .cloned_strategy_info(original.id, &json!({ "sma_period": 8 }))
+            .await
+            .unwrap();
+        let clone = bot
+            .start_strategy(
+                &info.name,
+                &info.symbol,
+                info.interval,
+                info.settings,
+                info.params,
+            )
+            .await
+            .unwrap();
+
+        assert_ne!(clone.id, original.id);
+        assert_eq!(clone.symbol, "ETHUSDT");
+        assert_eq!(clone.interval, Interval::Min5);
+        assert_eq!(clone.params, json!({ "sma_period": 8 }));
+        assert_eq!(
+            bot.get_strategy_info(original.id).await.unwrap().params,
+            json!({ "sma_period": 5 })
+        );
+
+        // unknown strategies can't be cloned
+        assert!(bot
+            .cloned_strategy_info(Uuid::new_v4(), &json!({}))
+            .await
+            .is_err());
+    }
+
+    #[test]
+    async fn test_insert_rejects_duplicate_strategy_id() {
+        let mut bot = test_bot(Arc::new(MockExchangeApi::default())).await;
+
+        let running = bot
+            .start_strategy(
+                "SimpleMovingAverage",
+                "ETHUSDT",
+                Interval::Min5,
+                StrategySettings::default(),
+                json!({ "sma_period": 5 }),
+            )
+            .await
+            .unwrap();
+
+        let build_strategy = |symbol: &str| {
+            Strategy::new(
+                "SimpleMovingAverage",
+                symbol,
+                Interval::Min5,
+                bot.strategy_tx.clone(),
+                bot.market.clone(),
+                StrategySettings::default(),
+                json!({ "sma_period": 8 }),
+            )
+            .unwrap()
+        };
+
+        let mut duplicate = build_strategy("BTCUSDT");
+        duplicate.id = running.id;
+        let handle = tokio::spawn(time::sleep(Duration::from_secs(60)));
+
+        let mut manager = bot.strategy_manager.lock().await;
+        assert!(matches!(
+            manager.insert(duplicate, handle),
+            Err(AlgoError::Conflict(_))
+        ));
+
+        // the running strategy is kept
+        assert_eq!(manager.list_ids(), vec![running.id]);
+        let (_, strategy) = manager.get(&running.id).unwrap();
+        assert_eq!(strategy.symbol, "ETHUSDT");
+
+        // names only collide on the same symbol once unique names are enabled
+        assert!(manager.check_insert(&build_strategy("ETHUSDT")).is_ok());
+        manager.set_unique_names(true);
+        assert!(matches!(
+            manager.check_insert(&build_strategy("ETHUSDT")),
+            Err(AlgoError::Conflict(_))
+        ));
+        assert!(manager.check_insert(&build_strategy("BTCUSDT")).is_ok());
+    }
+
+    #[test]
+    async fn test_profitable_shadow_strategy_promoted_to_live() {
+        let mut bot = test_bot(Arc::new(MockExchangeApi::default())).await;
+
+        let settings = StrategySettings {
+            mode: StrategyMode::Shadow,
+            promote_after: Some(PromotionPolicy {
+                min_duration_secs: 3_600,
+                min_shadow_profit: 5.0,
+            }),
+            ..Default::default()
+        };
+        let info = bot
+            .start_strategy(
+                "SimpleMovingAverage",
+                "BTCUSDT",
+                Interval::Min1,
+                settings,
+                json!({ "sma_period": 5 }),
+            )
+            .await
+            .unwrap();
+
+        let (shadow_account, since) = {
+            let mut manager = bot.strategy_manager.lock().await;
+            let (_, strategy) = manager.get(&info.id).unwrap();
+            (
+                strategy.shadow_account().unwrap(),
+                strategy.shadow_since().unwrap(),
+            )
+        };
+
+        // profitable simulated round trip
+        {
+            let mut shadow_account = shadow_account.lock().await;
+            let position_id = shadow_account
+                .open_position(
+                    "BTCUSDT",
+                    100.0,
+                    1,
+                    OrderSide::Buy,
+                    100.0,
+                    Some(info.id),
+                    None,
+                )
+                .await
+                .unwrap()
+                .id;
+            shadow_account.close_position(position_id, 110.0).await;
+        }
+
+        let clock = TestClock::new(since + 3_599 * SEC_AS_MILI);
+        let (strategy_manager, accounts) = (bot.strategy_manager.clone(), bot.accounts.clone());
+        let notifier = bot.notifier.clone();
+        let promote = |clock| {
+            promote_shadow_strategies(&strategy_manager, &accounts, notifier.clone(), clock)
+        };
+
+        // profitable but not yet in shadow mode for long enough
+        assert!(promote(&clock).await.is_empty());
+        assert_eq!(
+            bot.get_strategy_info(info.id).await.unwrap().settings.mode,
+            StrategyMode::Shadow
+        );
+
+        clock.set(since + 3_600 * SEC_AS_MILI);
+        assert_eq!(promote(&clock).await, vec![info.id]);
+
+        let promoted = bot.get_strategy_info(info.id).await.unwrap();
+        assert_eq!(promoted.settings.mode, StrategyMode::Live);
+        let mut manager = bot.strategy_manager.lock().await;
+        let (handle, strategy) = manager.get(&info.id).unwrap();
+        assert!(strategy.shadow_account().is_none());
+        assert!(!handle.is_finished());
+        drop(manager);
+
+        // already live, nothing left to promote
+        assert!(promote(&clock).await.is_empty());
     }
 }
@@ -3,17 +3,30 @@ use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
-use std::{error::Error, fmt};
+use std::{collections::HashMap, error::Error, fmt};
+
+use url::form_urlencoded;
 
 use crate::{
-    account::trade::{OrderSide, Position, TradeTx},
+    account::{
+        trade::{ContractType, OrderSide, Position, TradeTx},
+        user_data::UserDataEvent,
+    },
     market::interval::Interval,
-    market::{kline::Kline, ticker::Ticker, types::ArcMutex},
+    market::{
+        kline::Kline,
+        orderbook::DepthSnapshot,
+        ticker::Ticker,
+        trade::AggTrade,
+        types::{ArcMutex, ArcSender},
+    },
+    utils::number::parse_f64_from_value,
 };
 
 use super::{
+    resilient::ExchangeHealth,
     stream::{StreamManager, StreamMeta},
-    types::{ApiResult, StreamType},
+    types::{self, ApiResult, StreamType},
 };
 
 /// Represents an error encountered within the API operations.
@@ -56,27 +69,24 @@ pub trait ExchangeApi: Send + Sync {
 
     async fn get_account_balance(&self) -> ApiResult<f64>;
 
-    /// Opens a new position on the exchange with the specified parameters.
+    /// Opens a position on the exchange with a market order.
     ///
     /// # Arguments
     ///
-    /// * `symbol` - A string slice representing the trading pair.
-    /// * `margin_usd` - The amount of USD to use for the margin.
-    /// * `leverage` - The leverage to apply to the position.
-    /// * `order_side` - The side of the order (`OrderSide::Buy` or `OrderSide::Sell`).
-    /// * `open_price` - The price at which to open the position.
+    /// * `position` - The position to open, the order is sized by its quantity, in the base asset
+    ///   for linear contracts and in contracts for inverse contracts.
+    /// * `client_order_id` - Optional ID the exchange dedups the order by, so an order retried
+    ///   after an error is placed once.
     ///
     /// # Returns
     ///
-    /// A `Result` containing the opened position as `Position` if successful, or an `ApiError` otherwise.
+    /// A `Result` containing the opened position with the order ID set by the exchange, or an
+    /// `ApiError` otherwise.
 
     async fn open_position(
         &self,
-        symbol: &str,
-        margin_usd: f64,
-        leverage: u32,
-        order_side: OrderSide,
-        open_price: f64,
+        position: Position,
+        client_order_id: Option<&str>,
     ) -> ApiResult<Position>;
 
     /// Closes an existing position at the specified price.
@@ -108,6 +118,51 @@ pub trait ExchangeApi: Send + Sync {
 
     async fn list_open_orders(&self) -> ApiResult<Value>;
 
+    /// Cancels a single open order.
+    ///
+    /// # Arguments
+    ///
+    /// * `symbol` - A string slice representing the trading pair of the order.
+    /// * `order_id` - The exchange identifier of the order to cancel.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the exchange response as `Value` if successful, or an `ApiError` otherwise.
+
+    async fn cancel_order(&self, symbol: &str, order_id: &str) -> ApiResult<Value>;
+
+    /// Cancels all open orders for a symbol.
+    ///
+    /// # Arguments
+    ///
+    /// * `symbol` - A string slice representing the trading pair to cancel orders on.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the exchange response as `Value` if successful, or an `ApiError` otherwise.
+
+    async fn cancel_all_orders(&self, symbol: &str) -> ApiResult<Value>;
+
+    /// Starts the user data stream which pushes order and account updates for the account.
+    ///
+    /// # Arguments
+    ///
+    /// * `account_sender` - Sender used to forward parsed user data events to the account.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the ID of the user data stream if successful, or an `ApiError`
+    /// if the stream could not be started or is not supported by the exchange.
+
+    async fn start_user_data_stream(
+        &self,
+        _account_sender: ArcSender<UserDataEvent>,
+    ) -> ApiResult<String> {
+        Err(types::ApiError::Unsupported(
+            "User data stream not supported by exchange".to_string(),
+        ))
+    }
+
     /// Retrieves the stream manager instance.
     ///
     /// # Returns
@@ -145,6 +200,31 @@ pub trait ExchangeApi: Send + Sync {
 
     async fn get_kline(&self, symbol: &str, interval: Interval) -> ApiResult<Kline>;
 
+    /// Retrieves all k-lines of a symbol and interval opened within a time range.
+    ///
+    /// # Arguments
+    ///
+    /// * `symbol` - A string slice representing the trading pair.
+    /// * `interval` - The k-line interval.
+    /// * `from_ts` - Start of the range, inclusive.
+    /// * `to_ts` - End of the range, inclusive.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the k-lines ordered by open time if successful, or an `ApiError` otherwise.
+
+    async fn get_klines(
+        &self,
+        symbol: &str,
+        _interval: Interval,
+        _from_ts: u64,
+        _to_ts: u64,
+    ) -> ApiResult<Vec<Kline>> {
+        Err(types::ApiError::Unsupported(format!(
+            "Historical klines for {symbol} not supported by exchange"
+        )))
+    }
+
     /// Retrieves the ticker information for a specific symbol.
     ///
     /// # Arguments
@@ -157,6 +237,66 @@ pub trait ExchangeApi: Send + Sync {
 
     async fn get_ticker(&self, symbol: &str) -> ApiResult<Ticker>;
 
+    /// Retrieves the last prices of many symbols, in one request where the exchange supports it.
+    ///
+    /// # Arguments
+    ///
+    /// * `symbols` - The trading pairs to get prices for.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the last price by symbol, symbols without a price are left out. By
+    /// default the ticker of each symbol is requested in turn.
+
+    async fn get_last_prices(&self, symbols: &[String]) -> ApiResult<HashMap<String, f64>> {
+        let mut prices = HashMap::new();
+
+        for symbol in symbols {
+            if let Ok(ticker) = self.get_ticker(symbol).await {
+                prices.insert(symbol.clone(), ticker.last_price);
+            }
+        }
+
+        Ok(prices)
+    }
+
+    /// Retrieves the most recent aggregate trades of a symbol.
+    ///
+    /// # Arguments
+    ///
+    /// * `symbol` - A string slice representing the trading pair.
+    /// * `limit` - Number of trades to return, at most `AGG_TRADES_PAGE_LIMIT`.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the trades ordered by id if successful, or an `ApiError` if the
+    /// request fails or trades are not supported by the exchange.
+
+    async fn get_recent_trades(&self, symbol: &str, _limit: usize) -> ApiResult<Vec<AggTrade>> {
+        Err(types::ApiError::Unsupported(format!(
+            "Recent trades for {symbol} not supported by exchange"
+        )))
+    }
+
+    /// Retrieves a page of aggregate trades of a symbol starting at a trade id, used to page
+    /// through trade history.
+    ///
+    /// # Arguments
+    ///
+    /// * `symbol` - A string slice representing the trading pair.
+    /// * `from_id` - Id of the first trade of the page, inclusive.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing up to `AGG_TRADES_PAGE_LIMIT` trades ordered by id if successful, or
+    /// an `ApiError` if the request fails or trades are not supported by the exchange.
+
+    async fn get_historical_trades(&self, symbol: &str, _from_id: u64) -> ApiResult<Vec<AggTrade>> {
+        Err(types::ApiError::Unsupported(format!(
+            "Historical trades for {symbol} not supported by exchange"
+        )))
+    }
+
     /// Retrieves information about the exchange.
     ///
     /// # Returns
@@ -165,6 +305,66 @@ pub trait ExchangeApi: Send + Sync {
 
     async fn info(&self) -> ApiResult<ExchangeInfo>;
 
+    /// Retrieves the trading rules of a symbol, ie. its tick and step size.
+    ///
+    /// # Arguments
+    ///
+    /// * `symbol` - A string slice representing the trading pair.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the symbol information if the symbol exists on the exchange, or an
+    /// `ApiError` otherwise.
+
+    async fn get_symbol_info(&self, symbol: &str) -> ApiResult<SymbolInfo> {
+        Err(types::ApiError::Unsupported(format!(
+            "Symbol info for {symbol} not supported by exchange"
+        )))
+    }
+
+    /// Retrieves a snapshot of the order book of a symbol, used to sync a local order book with
+    /// depth stream updates.
+    ///
+    /// # Arguments
+    ///
+    /// * `symbol` - A string slice representing the trading pair.
+    /// * `limit` - Number of levels to return per side.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `DepthSnapshot` if successful, or an `ApiError` if the request
+    /// fails or depth is not supported by the exchange.
+
+    async fn get_depth_snapshot(&self, symbol: &str, _limit: usize) -> ApiResult<DepthSnapshot> {
+        Err(types::ApiError::Unsupported(format!(
+            "Depth snapshot for {symbol} not supported by exchange"
+        )))
+    }
+
+    /// Retrieves the open positions held on the exchange, used to reconcile the positions
+    /// tracked by an account.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the open `ExchangePosition`s if successful, or an `ApiError` if the
+    /// request fails or positions are not supported by the exchange.
+
+    async fn get_positions(&self) -> ApiResult<Vec<ExchangePosition>> {
+        Err(types::ApiError::Unsupported(
+            "Positions not supported by exchange".to_string(),
+        ))
+    }
+
+    /// Retrieves the kline intervals which can be streamed from the exchange.
+    ///
+    /// # Returns
+    ///
+    /// A `Vec` of supported intervals, all intervals unless overridden by the exchange.
+
+    fn supported_intervals(&self) -> Vec<Interval> {
+        Interval::ALL.to_vec()
+    }
+
     /// Builds a URL for subscribing to a stream based on the specified parameters.
     ///
     /// # Arguments
@@ -183,6 +383,16 @@ pub trait ExchangeApi: Send + Sync {
         stream_type: StreamType,
         interval: Option<Interval>,
     ) -> String;
+
+    /// Reports the health of the exchange as seen by the requests made to it.
+    ///
+    /// # Returns
+    ///
+    /// The `ExchangeHealth` if requests are made through a circuit breaker, `None` otherwise.
+
+    fn health(&self) -> Option<ExchangeHealth> {
+        None
+    }
 }
 
 /// A utility for constructing query strings from key-value pairs.
@@ -213,6 +423,9 @@ impl<'a> QueryStr<'a> {
 ///
 /// This method concatenates all key-value pairs into a query string format, separating keys from values with `=` and pairs from each other with `&`. The final string does not end with `&`.
 ///
+/// Keys and values are URL-encoded, so values containing `&`, `=` or `+` can't corrupt the query.
+/// Signatures must be computed over this encoded string, which is the form sent to the exchange.
+///
 /// # Returns
 ///
 /// Returns a `String` representing the assembled query string.
@@ -222,7 +435,7 @@ impl<'a> ToString for QueryStr<'a> {
         let str_vec: Vec<String> = self
             .params
             .iter()
-            .map(|(key, val)| format!("{key}={val}&"))
+            .map(|(key, val)| format!("{}={}&", url_encode(key), url_encode(val)))
             .collect();
 
         let mut query_str = str_vec.join("");
@@ -233,6 +446,11 @@ impl<'a> ToString for QueryStr<'a> {
     }
 }
 
+/// URL-encodes a query string key or value.
+fn url_encode(value: &str) -> String {
+    form_urlencoded::byte_serialize(value.as_bytes()).collect()
+}
+
 /// Represents exchange-specific information.
 ///
 /// This structure stores metadata about an exchange, such as its name. It is intended for serialization and deserialization of data related to exchange information.
@@ -241,3 +459,181 @@ impl<'a> ToString for QueryStr<'a> {
 pub struct ExchangeInfo {
     pub name: String,
 }
+
+/// Maximum number of aggregate trades returned by a single trades request.
+pub const AGG_TRADES_PAGE_LIMIT: usize = 1000;
+
+/// Lowest leverage allowed for a symbol if the exchange doesn't provide a range.
+pub const DEFAULT_MIN_LEVERAGE: u32 = 1;
+
+/// Highest leverage allowed for a symbol if the exchange doesn't provide a range.
+pub const DEFAULT_MAX_LEVERAGE: u32 = 125;
+
+/// Trading rules of a symbol on the exchange, used to round prices and quantities.
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SymbolInfo {
+    pub symbol: String,
+    /// Minimum price movement of the symbol.
+    pub tick_size: f64,
+    /// Minimum quantity movement of the symbol.
+    pub step_size: f64,
+    pub min_leverage: u32,
+    pub max_leverage: u32,
+    /// How contracts of the symbol are sized and settled.
+    #[serde(default)]
+    pub contract_type: ContractType,
+    /// Minimum notional value of an order in the quote asset, 0 if the exchange has no minimum.
+    #[serde(default)]
+    pub min_notional: f64,
+    /// Whether the symbol can be traded, symbols are halted ie. during exchange maintenance.
+    #[serde(default)]
+    pub status: SymbolStatus,
+}
+
+/// Trading status of a symbol on the exchange.
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub enum SymbolStatus {
+    #[default]
+    Trading,
+    /// Trading is halted, with the status reported by the exchange, ie. `BREAK`.
+    Halted(String),
+}
+
+impl SymbolStatus {
+    /// Parses the status of a symbol reported by the exchange, any status other than `TRADING`
+    /// halts the symbol.
+
+    pub fn from_exchange(status: &str) -> Self {
+        if status.eq_ignore_ascii_case("TRADING") {
+            SymbolStatus::Trading
+        } else {
+            SymbolStatus::Halted(status.to_string())
+        }
+    }
+}
+
+impl SymbolInfo {
+    /// Rounds a price to the nearest multiple of the symbol's tick size.
+
+    pub fn round_price(&self, price: f64) -> f64 {
+        round_to_step(price, self.tick_size)
+    }
+
+    /// Rounds a quantity to the nearest multiple of the symbol's step size.
+
+    pub fn round_qty(&self, qty: f64) -> f64 {
+        round_to_step(qty, self.step_size)
+    }
+
+    /// Checks a leverage is within the range allowed for the symbol.
+    ///
+    /// # Arguments
+    ///
+    /// * `leverage` - The leverage to check.
+    ///
+    /// # Returns
+    ///
+    /// `Ok(())` if the leverage is allowed, otherwise an error message describing the allowed range.
+
+    pub fn validate_leverage(&self, leverage: u32) -> Result<(), String> {
+        if leverage < self.min_leverage || leverage > self.max_leverage {
+            return Err(format!(
+                "Leverage {leverage} is not allowed for {}, must be between {} and {}",
+                self.symbol, self.min_leverage, self.max_leverage
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+/// An open position held on the exchange, which may or may not be tracked by an account.
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ExchangePosition {
+    pub symbol: String,
+    pub order_side: OrderSide,
+    /// Absolute quantity of the position.
+    pub quantity: f64,
+    pub entry_price: f64,
+    pub leverage: u32,
+}
+
+impl ExchangePosition {
+    /// Parses a position from an entry of the Binance `positionRisk` response.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - The JSON entry of a single symbol.
+    ///
+    /// # Returns
+    ///
+    /// The position, `None` if the symbol has no open position, or a parsing error if a field
+    /// is missing.
+
+    pub fn from_binance_value(value: &Value) -> ApiResult<Option<Self>> {
+        let symbol = value
+            .get("symbol")
+            .and_then(|symbol| symbol.as_str())
+            .ok_or_else(|| types::ApiError::Parsing("Position symbol missing".to_string()))?;
+        let position_amt = parse_f64_from_value("positionAmt", value)?;
+
+        if position_amt == 0.0 {
+            return Ok(None);
+        }
+
+        let leverage = parse_f64_from_value("leverage", value)?;
+
+        Ok(Some(Self {
+            symbol: symbol.to_string(),
+            order_side: if position_amt > 0.0 {
+                OrderSide::Buy
+            } else {
+                OrderSide::Sell
+            },
+            quantity: position_amt.abs(),
+            entry_price: parse_f64_from_value("entryPrice", value)?,
+            leverage: leverage as u32,
+        }))
+    }
+
+    /// Builds an adopted position with the quantity and entry price held on the exchange, not
+    /// attached to a strategy.
+
+    pub fn to_position(&self) -> Position {
+        let leverage = self.leverage.max(1);
+        let margin_usd = self.quantity * self.entry_price / leverage as f64;
+
+        let mut position = Position::new(
+            &self.symbol,
+            self.entry_price,
+            self.order_side.clone(),
+            margin_usd,
+            leverage,
+            None,
+        );
+        position.quantity = self.quantity;
+        position.adopted = true;
+        position
+    }
+}
+
+/// Rounds a value to the nearest multiple of `step`, trimming float noise left by the division.
+
+fn round_to_step(value: f64, step: f64) -> f64 {
+    if step <= 0.0 || !value.is_finite() {
+        return value;
+    }
+
+    let mut decimals = 0;
+    let mut scaled = step;
+    while (scaled - scaled.round()).abs() > 1e-9 && decimals < 12 {
+        scaled *= 10.0;
+        decimals += 1;
+    }
+
+    let rounded = (value / step).round() * step;
+    format!("{rounded:.decimals$}").parse().unwrap_or(rounded)
+}
@@ -1,37 +1,71 @@
 use async_trait::async_trait;
 
-use futures_util::SinkExt;
-use log::info;
+use futures_util::{Sink, SinkExt};
+use log::{debug, info, warn};
 use reqwest::header::{HeaderMap, HeaderValue, CONTENT_TYPE};
-use reqwest::{Client, Response};
+use reqwest::{Client, Request, Response};
 // use reqwest::Client;
 
 use futures_util::StreamExt;
+use serde::Deserialize;
 use serde_json::{json, Value};
 use std::collections::HashMap;
+use std::time::Duration;
 use uuid::Uuid;
 
-use tokio_tungstenite::connect_async;
+use tokio::net::TcpStream;
+use tokio::task::JoinHandle;
 use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{connect_async, MaybeTlsStream, WebSocketStream};
 
 use hmac::{Hmac, Mac};
 use sha2::Sha256;
 
-use crate::account::trade::{OrderSide, Position, TradeTx};
+use crate::account::trade::{ContractType, OrderSide, Position, TradeTx};
+use crate::account::user_data::UserDataEvent;
 use crate::exchange::api::{ExchangeApi, QueryStr};
+use crate::exchange::http::{check_status, HttpClientConfig};
+use crate::exchange::symbol::{format_exchange_symbol, SymbolFormat};
 use crate::exchange::types::ArcEsStreamSync;
+use crate::market::channel::MarketSender;
 use crate::market::interval::Interval;
 use crate::market::messages::MarketMessage;
-use crate::market::trade::Trade;
+use crate::market::orderbook::{DepthSnapshot, DepthUpdate};
+use crate::market::trade::{AggTrade, Trade};
 use crate::market::types::{ArcMutex, ArcSender};
 use crate::market::{kline::Kline, ticker::Ticker};
-use crate::utils::number::{parse_f64_from_lookup, parse_f64_from_value, parse_usize_from_value};
+use crate::utils::number::{
+    parse_f64_from_lookup, parse_f64_from_value, parse_usize_from_value, to_decimal,
+};
 use crate::utils::time::generate_ts;
 
-use super::api::ExchangeInfo;
+use super::api::{
+    ExchangeInfo, ExchangePosition, SymbolInfo, SymbolStatus, AGG_TRADES_PAGE_LIMIT,
+    DEFAULT_MIN_LEVERAGE,
+};
 
-use super::stream::{StreamManager, StreamMeta};
-use super::types::{ApiResult, StreamType};
+use super::quarantine::StreamQuarantine;
+use super::stream::{build_stream_id, StreamManager, StreamMeta};
+use super::types::{ApiError, ApiResult, StreamType};
+
+/// Endpoint used to create and keep alive the listen key of the user data stream.
+const LISTEN_KEY_ENDPOINT: &str = "/fapi/v1/listenKey";
+
+/// Interval at which the user data stream listen key is kept alive.
+const LISTEN_KEY_KEEP_ALIVE: Duration = Duration::from_secs(30 * 60);
+
+/// Age at which the user data stream reconnects with a new listen key, ahead of Binance dropping
+/// connections after 24 hours.
+const USER_DATA_RECONNECT: Duration = Duration::from_secs(23 * 60 * 60);
+
+/// Delay before retrying to reconnect the user data stream after a failed attempt.
+const USER_DATA_RETRY_DELAY: Duration = Duration::from_secs(5);
+
+/// Maximum number of klines returned by a single klines request.
+const KLINES_PAGE_LIMIT: usize = 1500;
+
+/// Interval client pings are sent at, so quiet websocket connections aren't dropped as idle.
+const WS_PING_INTERVAL: Duration = Duration::from_secs(3 * 60);
 
 /// Represents the Binance API client for interacting with the Binance exchange.
 ///
@@ -55,7 +89,10 @@ impl BinanceApi {
     ///
     /// * `api_key` - A string slice holding the Binance API key.
     /// * `secret_key` - A string slice holding the Binance secret key.
-    /// * `market_sender` - An `ArcSender<MarketMessage>` for sending market-related messages through the system.
+    /// * `market_sender` - A `MarketSender` for sending market-related messages through the system.
+    /// * `test_net` - Whether to use the Binance testnet hosts.
+    /// * `http_config` - Timeouts and connection pool of the HTTP client.
+    /// * `quarantine` - Failure threshold of stream frames which can't be parsed.
     ///
     /// # Returns
     ///
@@ -64,8 +101,10 @@ impl BinanceApi {
     pub fn new(
         api_key: &str,
         secret_key: &str,
-        market_sender: ArcSender<MarketMessage>,
+        market_sender: MarketSender,
         test_net: bool,
+        http_config: HttpClientConfig,
+        quarantine: StreamQuarantine,
     ) -> Self {
         let (ws_host, host) = if test_net {
             let host = "https://testnet.binancefuture.com".to_string();
@@ -79,13 +118,14 @@ impl BinanceApi {
 
         // Testnet hosts
 
-        let stream_manager: ArcMutex<Box<dyn StreamManager>> =
-            ArcMutex::new(Box::new(BinanceStreamManager::new(market_sender)));
+        let stream_manager: ArcMutex<Box<dyn StreamManager>> = ArcMutex::new(Box::new(
+            BinanceStreamManager::new_combined(market_sender, quarantine),
+        ));
 
         Self {
             ws_host,
             host,
-            client: Client::builder().build().unwrap(),
+            client: http_config.build_client(),
             api_key: api_key.to_string(),
             secret_key: secret_key.to_string(),
             stream_manager,
@@ -169,6 +209,129 @@ impl BinanceApi {
             .await
     }
 
+    /// Performs a signed HTTP DELETE request to the specified endpoint.
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - A request built with `build_delete_request`.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` with the response `Response` object if the request is successful, or an error of type `reqwest::Error` otherwise.
+
+    async fn delete(&self, request: Request) -> Result<Response, reqwest::Error> {
+        self.client.execute(request).await
+    }
+
+    /// Builds a signed HTTP DELETE request for the specified endpoint.
+    ///
+    /// The query string is signed with the secret key and the signature appended as the last
+    /// query parameter, as required by Binance for `TRADE` endpoints.
+    ///
+    /// # Arguments
+    ///
+    /// * `endpoint` - A string slice specifying the endpoint for the DELETE request.
+    /// * `query_str` - A string slice containing the unsigned query string.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` with the built `Request`, or an error of type `reqwest::Error` if the URL is invalid.
+
+    fn build_delete_request(
+        &self,
+        endpoint: &str,
+        query_str: &str,
+    ) -> Result<Request, reqwest::Error> {
+        let signature = self.sign_query_str(query_str);
+        let url = format!(
+            "{}{}?{}&signature={signature}",
+            self.host, endpoint, query_str
+        );
+
+        self.client
+            .delete(&url)
+            .headers(self.build_headers(false))
+            .build()
+    }
+
+    /// Builds the signed request used to cancel orders.
+    ///
+    /// # Arguments
+    ///
+    /// * `symbol` - The market symbol of the orders.
+    /// * `order_id` - The order to cancel, `None` cancels all open orders for the symbol.
+    /// * `ts` - The request timestamp in milliseconds.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` with the built `Request`, or an error of type `reqwest::Error` if the URL is invalid.
+
+    fn build_cancel_order_request(
+        &self,
+        symbol: &str,
+        order_id: Option<&str>,
+        ts: u64,
+    ) -> Result<Request, reqwest::Error> {
+        let symbol = BinanceApi::format_binance_symbol(symbol, false);
+        let ts = ts.to_string();
+
+        let (endpoint, query_str) = match order_id {
+            Some(order_id) => (
+                "/fapi/v1/order",
+                QueryStr::new(vec![
+                    ("symbol", symbol.as_str()),
+                    ("orderId", order_id),
+                    ("timestamp", ts.as_str()),
+                ]),
+            ),
+            None => (
+                "/fapi/v1/allOpenOrders",
+                QueryStr::new(vec![
+                    ("symbol", symbol.as_str()),
+                    ("timestamp", ts.as_str()),
+                ]),
+            ),
+        };
+
+        self.build_delete_request(endpoint, &query_str.to_string())
+    }
+
+    /// Builds the unsigned query of a market order opening a position.
+    ///
+    /// # Arguments
+    ///
+    /// * `position` - The position to open, the order is sized by its quantity.
+    /// * `client_order_id` - Optional client order ID of the order.
+    /// * `ts` - The request timestamp in milliseconds.
+    ///
+    /// # Returns
+    ///
+    /// The encoded query string, to be signed before it's sent.
+
+    fn build_open_order_query(
+        position: &Position,
+        client_order_id: Option<&str>,
+        ts: u64,
+    ) -> String {
+        let symbol = BinanceApi::format_binance_symbol(&position.symbol, false);
+        let side = position.order_side.to_string().to_uppercase();
+        let quantity = to_decimal(position.quantity).normalize().to_string();
+        let ts = ts.to_string();
+
+        let mut params = vec![
+            ("symbol", symbol.as_str()),
+            ("side", side.as_str()),
+            ("type", "MARKET"),
+            ("quantity", quantity.as_str()),
+            ("timestamp", ts.as_str()),
+        ];
+        if let Some(client_order_id) = client_order_id {
+            params.push(("newClientOrderId", client_order_id));
+        }
+
+        QueryStr::new(params).to_string()
+    }
+
     /// Processes the HTTP response, extracting the relevant data based on the content type.
     ///
     /// This method checks the content type of the response and accordingly parses the response body as either plain text or JSON. It is designed to handle different response formats gracefully, ensuring that the data is correctly extracted from various API endpoints.
@@ -180,8 +343,10 @@ impl BinanceApi {
     /// # Returns
     ///
     /// Returns an `ApiResult<Value>`, which is a `Result` type that either contains the parsed data as a `serde_json::Value` or an error if the response processing fails.
+    /// Error statuses are mapped by `check_status`, so rate limits and server errors are retried.
 
     async fn handle_response(&self, response: Response) -> ApiResult<Value> {
+        let status = response.status();
         let data = match &response.headers().get("content-type") {
             Some(header) => {
                 if header.to_str().unwrap().contains("text/html") {
@@ -193,6 +358,7 @@ impl BinanceApi {
             None => json!({"text":response.text().await?}),
         };
 
+        check_status(status, &data)?;
         Ok(data)
     }
 
@@ -224,11 +390,144 @@ impl BinanceApi {
     }
 
     fn format_binance_symbol(symbol: &str, lower_case: bool) -> String {
+        let symbol = format_exchange_symbol(symbol, SymbolFormat::Binance);
+
         if lower_case {
             return symbol.to_lowercase();
         }
 
-        symbol.to_string()
+        symbol
+    }
+
+    /// Parses the first kline from a Binance klines response.
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - The JSON response, an array of kline arrays.
+    /// * `symbol` - The market symbol the klines were requested for.
+    /// * `interval` - The interval the klines were requested for.
+    ///
+    /// # Returns
+    ///
+    /// Returns an `ApiResult<Kline>`, a parsing error is returned if the response is empty or
+    /// the kline has missing or malformed fields.
+
+    fn parse_kline(data: Value, symbol: &str, interval: Interval) -> ApiResult<Kline> {
+        let arr: Vec<Vec<Value>> = serde_json::from_value(data)?;
+
+        let kline = arr
+            .first()
+            .ok_or_else(|| ApiError::Parsing(format!("No kline data for {symbol}")))?;
+
+        BinanceApi::parse_kline_row(kline, symbol, interval)
+    }
+
+    /// Parses all klines from a `/fapi/v1/klines` response.
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - The JSON response, an array of kline arrays.
+    /// * `symbol` - The symbol the klines belong to.
+    /// * `interval` - The interval of the klines.
+    ///
+    /// # Returns
+    ///
+    /// Returns an `ApiResult<Vec<Kline>>`, empty if the response contains no klines.
+
+    fn parse_klines(data: Value, symbol: &str, interval: Interval) -> ApiResult<Vec<Kline>> {
+        let arr: Vec<Vec<Value>> = serde_json::from_value(data)?;
+
+        arr.iter()
+            .map(|kline| BinanceApi::parse_kline_row(kline, symbol, interval))
+            .collect()
+    }
+
+    /// Parses a single kline array, ie. one row of a `/fapi/v1/klines` response.
+
+    fn parse_kline_row(kline: &[Value], symbol: &str, interval: Interval) -> ApiResult<Kline> {
+        if kline.len() < 7 {
+            return Err(ApiError::Parsing(format!(
+                "Expected at least 7 kline fields for {symbol}, got {}",
+                kline.len()
+            )));
+        }
+
+        let parse_u64 = |idx: usize| {
+            kline[idx]
+                .as_u64()
+                .ok_or_else(|| ApiError::Parsing(format!("Invalid kline field {idx} for {symbol}")))
+        };
+
+        let parse_f64 = |idx: usize| {
+            kline[idx]
+                .as_str()
+                .and_then(|val| val.parse::<f64>().ok())
+                .ok_or_else(|| ApiError::Parsing(format!("Invalid kline field {idx} for {symbol}")))
+        };
+
+        Ok(Kline {
+            interval,
+            symbol: symbol.to_string(),
+            open_time: parse_u64(0)?,
+            open: parse_f64(1)?,
+            high: parse_f64(2)?,
+            low: parse_f64(3)?,
+            close: parse_f64(4)?,
+            volume: parse_f64(5)?,
+            close_time: parse_u64(6)?,
+            quote_volume: match kline.get(7) {
+                Some(_) => parse_f64(7)?,
+                None => 0.0,
+            },
+        })
+    }
+
+    /// Fetches the highest leverage allowed on a symbol from its leverage brackets.
+    ///
+    /// # Arguments
+    ///
+    /// * `symbol` - The symbol in Binance format, ie. `BTCUSDT`.
+    ///
+    /// # Returns
+    ///
+    /// Returns an `ApiResult<u32>` with the initial leverage of the lowest notional bracket.
+
+    async fn get_max_leverage(&self, symbol: &str) -> ApiResult<u32> {
+        let endpoint = "/fapi/v1/leverageBracket";
+        let ts = generate_ts();
+
+        let query_str = format!("symbol={symbol}&timestamp={ts}");
+        let signature = self.sign_query_str(&query_str);
+        let query_str = format!("{}&signature={signature}", query_str);
+
+        let res = self.get(endpoint, Some(&query_str)).await?;
+        let data = self.handle_response(res).await?;
+
+        BinanceApi::parse_max_leverage(&data, symbol)
+    }
+
+    /// Parses the highest leverage of a symbol from a `/fapi/v1/leverageBracket` response, which
+    /// is either a list of symbols or a single symbol.
+
+    fn parse_max_leverage(data: &Value, symbol: &str) -> ApiResult<u32> {
+        let symbol_brackets = match data.as_array() {
+            Some(symbols) => symbols
+                .iter()
+                .find(|s| s.get("symbol").and_then(|s| s.as_str()) == Some(symbol)),
+            None => Some(data),
+        };
+
+        symbol_brackets
+            .and_then(|s| s.get("brackets"))
+            .and_then(|brackets| brackets.as_array())
+            .and_then(|brackets| {
+                brackets
+                    .iter()
+                    .filter_map(|bracket| bracket.get("initialLeverage")?.as_u64())
+                    .max()
+            })
+            .map(|leverage| leverage as u32)
+            .ok_or_else(|| ApiError::Parsing(format!("Missing leverage brackets for {symbol}")))
     }
 }
 
@@ -246,67 +545,41 @@ impl ExchangeApi for BinanceApi {
         unimplemented!()
     }
 
-    /// Opens a new trading position on the exchange with specified parameters.
+    /// Opens a new trading position on the exchange with a market order.
     ///
-    /// This method places an order to open a new trading position based on the symbol, margin used, leverage, order side (buy/sell), and the specified opening price. It constructs the request, signs it, and sends it to the exchange.
+    /// The order is sized by the quantity of the position, as the account rounded it to the step
+    /// size of the symbol. The query is signed and posted to the futures order endpoint.
     ///
     /// # Arguments
     ///
-    /// * `symbol` - The market symbol for the trading pair.
-    /// * `margin_usd` - The amount of margin in USD to be used for this position.
-    /// * `leverage` - The leverage to apply to the position.
-    /// * `order_side` - The side of the order, either `OrderSide::Buy` or `OrderSide::Sell`.
-    /// * `open_price` - The price at which to attempt to open the position.
+    /// * `position` - The position to open.
+    /// * `client_order_id` - Optional client order ID, the exchange rejects a second order with the same ID.
     ///
     /// # Returns
     ///
-    /// Returns an `ApiResult<Position>` indicating the successful creation of a trading position, or an error if the operation fails.
+    /// Returns an `ApiResult<Position>` with the order ID of the exchange set, or an error if the operation fails.
 
     async fn open_position(
         &self,
-        symbol: &str,
-        margin_usd: f64,
-        leverage: u32,
-        order_side: OrderSide,
-        open_price: f64,
+        mut position: Position,
+        client_order_id: Option<&str>,
     ) -> ApiResult<Position> {
-        let endpoint = "/api/v3/order";
-        let quantity = (margin_usd * leverage as f64) / open_price;
-
-        // format qty to 8 decimals
-        let _qty = format!("{:.1$}", quantity, 8);
-
-        let ts = &generate_ts().to_string();
-        let side = &order_side.to_string();
-        let quote_qty = 50.to_string();
-
-        let request_body = QueryStr::new(vec![
-            ("symbol", symbol),
-            ("quoteOrderQty", &quote_qty),
-            // ("quantity", &qty),
-            ("type", "MARKET"),
-            ("side", side),
-            ("timestamp", ts),
-        ]);
+        let endpoint = "/fapi/v1/order";
+        let request_body =
+            BinanceApi::build_open_order_query(&position, client_order_id, generate_ts());
 
-        let signature = self.sign_query_str(&request_body.to_string());
+        let signature = self.sign_query_str(&request_body);
 
-        let query_str = format!("{}&signature={signature}", request_body.to_string());
+        let query_str = format!("{request_body}&signature={signature}");
 
-        println!("qry_str: {query_str}");
+        debug!("Order query: {query_str}");
 
         let res = self.post(endpoint, &query_str).await?;
 
-        match self.handle_response(res).await {
-            Ok(_res) => {
-                // parse response
-                // build position from response
-                Ok(Position::new(
-                    symbol, open_price, order_side, margin_usd, leverage, None,
-                ))
-            }
-            Err(e) => Err(e),
-        }
+        let res = self.handle_response(res).await?;
+        // fills pushed on the user data stream are matched by the order ID
+        position.order_id = res.get("orderId").and_then(|id| id.as_u64());
+        Ok(position)
     }
 
     /// Closes an existing trading position on the exchange.
@@ -388,26 +661,56 @@ impl ExchangeApi for BinanceApi {
         //     ]
         // ]
 
-        let arr: Vec<Vec<Value>> = serde_json::from_value(data).unwrap();
-        let open_time = arr[0][0].as_u64().unwrap();
-        let open = arr[0][1].as_str().unwrap().parse::<f64>().unwrap();
-        let high = arr[0][2].as_str().unwrap().parse::<f64>().unwrap();
-        let low = arr[0][3].as_str().unwrap().parse::<f64>().unwrap();
-        let close = arr[0][4].as_str().unwrap().parse::<f64>().unwrap();
-        let volume = arr[0][5].as_str().unwrap().parse::<f64>().unwrap();
-        let close_time = arr[0][6].as_u64().unwrap();
+        BinanceApi::parse_kline(data, symbol, interval)
+    }
 
-        Ok(Kline {
-            interval,
-            symbol: symbol.to_string(),
-            open_time,
-            open,
-            high,
-            low,
-            close,
-            volume,
-            close_time,
-        })
+    /// Retrieves historical klines within a time range, paging through the klines endpoint.
+    ///
+    /// # Arguments
+    ///
+    /// * `symbol` - The trading symbol to fetch klines for.
+    /// * `interval` - The interval of the klines.
+    /// * `from_ts` - Start of the range, inclusive.
+    /// * `to_ts` - End of the range, inclusive.
+    ///
+    /// # Returns
+    ///
+    /// Returns an `ApiResult<Vec<Kline>>` with the klines ordered by open time.
+
+    async fn get_klines(
+        &self,
+        symbol: &str,
+        interval: Interval,
+        from_ts: u64,
+        to_ts: u64,
+    ) -> ApiResult<Vec<Kline>> {
+        let format_symbol = BinanceApi::format_binance_symbol(symbol, false);
+        let mut klines = vec![];
+        let mut start_ts = from_ts;
+
+        while start_ts <= to_ts {
+            let endpoint = format!(
+                "/fapi/v1/klines?symbol={format_symbol}&interval={interval}&startTime={start_ts}&endTime={to_ts}&limit={KLINES_PAGE_LIMIT}"
+            );
+
+            let res = self.get(&endpoint, None).await?;
+            let data = self.handle_response(res).await?;
+
+            let page = BinanceApi::parse_klines(data, symbol, interval)?;
+            let page_len = page.len();
+
+            match page.last() {
+                Some(last) => start_ts = last.open_time + interval.to_mili(),
+                None => break,
+            }
+            klines.extend(page);
+
+            if page_len < KLINES_PAGE_LIMIT {
+                break;
+            }
+        }
+
+        Ok(klines)
     }
 
     /// Retrieves the current ticker information for a specified symbol.
@@ -452,6 +755,37 @@ impl ExchangeApi for BinanceApi {
         // Ok(Ticker::default())
     }
 
+    /// Gets the last prices of all symbols with a single request, keeping the requested symbols.
+
+    async fn get_last_prices(&self, symbols: &[String]) -> ApiResult<HashMap<String, f64>> {
+        let requested: HashMap<String, &String> = symbols
+            .iter()
+            .map(|symbol| (BinanceApi::format_binance_symbol(symbol, false), symbol))
+            .collect();
+
+        let res = self.get("/fapi/v1/ticker/price", None).await?;
+        let data = self.handle_response(res).await?;
+
+        let mut prices = HashMap::new();
+
+        if let Some(items) = data.as_array() {
+            for item in items {
+                let Some(symbol) = item.get("symbol").and_then(|symbol| symbol.as_str()) else {
+                    continue;
+                };
+
+                if let Some(requested_symbol) = requested.get(symbol) {
+                    prices.insert(
+                        requested_symbol.to_string(),
+                        parse_f64_from_value("price", item)?,
+                    );
+                }
+            }
+        }
+
+        Ok(prices)
+    }
+
     /// Lists all orders associated with the account, including historical orders.
     ///
     /// This asynchronous method sends a request to the exchange to retrieve a comprehensive list of all orders placed by the account, allowing for a complete audit trail of trading activity.
@@ -494,6 +828,116 @@ impl ExchangeApi for BinanceApi {
         self.handle_response(res).await
     }
 
+    /// Cancels a single open order on the exchange.
+    ///
+    /// # Arguments
+    ///
+    /// * `symbol` - The market symbol of the order.
+    /// * `order_id` - The Binance order ID of the order to cancel.
+    ///
+    /// # Returns
+    ///
+    /// Returns an `ApiResult<Value>` with the canceled order. In case of an error, it returns an appropriate error encapsulated within `ApiResult`.
+
+    async fn cancel_order(&self, symbol: &str, order_id: &str) -> ApiResult<Value> {
+        let request = self.build_cancel_order_request(symbol, Some(order_id), generate_ts())?;
+
+        let res = self.delete(request).await?;
+
+        self.handle_response(res).await
+    }
+
+    /// Cancels all open orders for a symbol on the exchange.
+    ///
+    /// # Arguments
+    ///
+    /// * `symbol` - The market symbol to cancel all open orders on.
+    ///
+    /// # Returns
+    ///
+    /// Returns an `ApiResult<Value>` with the exchange response. In case of an error, it returns an appropriate error encapsulated within `ApiResult`.
+
+    async fn cancel_all_orders(&self, symbol: &str) -> ApiResult<Value> {
+        let request = self.build_cancel_order_request(symbol, None, generate_ts())?;
+
+        let res = self.delete(request).await?;
+
+        self.handle_response(res).await
+    }
+
+    /// Starts the Binance futures user data stream for the account.
+    ///
+    /// A listen key is created with `POST /fapi/v1/listenKey` and the `<listenKey>` websocket is
+    /// opened, `ORDER_TRADE_UPDATE` and `ACCOUNT_UPDATE` events are parsed and forwarded to the
+    /// account. The listen key is kept alive every 30 minutes while the stream is open. Binance
+    /// drops connections after 24 hours, so the stream reconnects with a new listen key before
+    /// then, or as soon as the connection is lost or the listen key can't be kept alive.
+    ///
+    /// # Arguments
+    ///
+    /// * `account_sender` - Sender used to forward parsed user data events to the account.
+    ///
+    /// # Returns
+    ///
+    /// Returns an `ApiResult<String>` containing the ID of the user data stream.
+
+    async fn start_user_data_stream(
+        &self,
+        account_sender: ArcSender<UserDataEvent>,
+    ) -> ApiResult<String> {
+        let connection = UserDataConnection {
+            client: self.client.clone(),
+            listen_key_url: format!("{}{}", self.host, LISTEN_KEY_ENDPOINT),
+            ws_host: self.ws_host.clone(),
+            headers: self.build_headers(false),
+        };
+
+        let (url, ws_stream) = connection.connect().await?;
+        let stream_id = build_stream_id("account", StreamType::UserData, None);
+
+        let stream_metas = self.stream_manager.lock().await.stream_metas();
+        stream_metas.lock().await.insert(
+            stream_id.clone(),
+            StreamMeta::new(&stream_id, &url, "account", StreamType::UserData, None),
+        );
+
+        let thread_stream_id = stream_id.clone();
+
+        tokio::spawn(async move {
+            let mut ws_stream = Some(ws_stream);
+
+            loop {
+                if let Some(ws_stream) = ws_stream.take() {
+                    let reconnect = connection
+                        .read_events(ws_stream, &account_sender, &stream_metas, &thread_stream_id)
+                        .await;
+                    if !reconnect {
+                        break;
+                    }
+                }
+
+                match connection.connect().await {
+                    Ok((url, stream)) => {
+                        info!("Reconnected user data stream {thread_stream_id}");
+                        match stream_metas.lock().await.get_mut(&thread_stream_id) {
+                            Some(stream_meta) => stream_meta.url = url,
+                            None => break,
+                        }
+                        ws_stream = Some(stream);
+                    }
+                    Err(e) => {
+                        warn!("Unable to reconnect user data stream {thread_stream_id}: {e}");
+                        tokio::time::sleep(USER_DATA_RETRY_DELAY).await;
+                    }
+                }
+            }
+
+            stream_metas.lock().await.remove(&thread_stream_id);
+        });
+
+        Ok(stream_id)
+    }
+
     // ---
     // Exchange Methods
     // ---
@@ -518,49 +962,358 @@ impl ExchangeApi for BinanceApi {
         })
     }
 
-    // ---
-    // Stream Helper methods
-    // ---
+    /// Retrieves a snapshot of the futures order book of a symbol.
+    ///
+    /// # Arguments
+    ///
+    /// * `symbol` - A string slice representing the trading pair.
+    /// * `limit` - Number of levels to return per side.
+    ///
+    /// # Returns
+    ///
+    /// Returns an `ApiResult<DepthSnapshot>`, or an error if the request fails or the response can't be parsed.
 
-    fn get_stream_manager(&self) -> ArcMutex<Box<dyn StreamManager>> {
-        self.stream_manager.clone()
+    async fn get_depth_snapshot(&self, symbol: &str, limit: usize) -> ApiResult<DepthSnapshot> {
+        let format_symbol = BinanceApi::format_binance_symbol(symbol, false);
+        let endpoint = format!("/fapi/v1/depth?symbol={format_symbol}&limit={limit}");
+
+        let res = self.get(&endpoint, None).await?;
+        let data = self.handle_response(res).await?;
+
+        DepthSnapshot::from_binance_value(symbol, &data)
     }
 
-    fn build_stream_url(
-        &self,
-        symbol: &str,
-        stream_type: StreamType,
-        interval: Option<Interval>,
-    ) -> String {
-        let url = match stream_type {
-            StreamType::Kline => {
-                format!(
-                    "{}/ws/{}@kline_{}",
-                    self.ws_host,
-                    BinanceApi::format_binance_symbol(symbol, true),
-                    interval.unwrap()
-                )
-            }
-            StreamType::Ticker => {
-                format!(
-                    "{}/ws/{}@ticker",
-                    self.ws_host,
-                    BinanceApi::format_binance_symbol(symbol, true)
-                )
-            }
-            StreamType::Trade => {
-                format!(
-                    "{}/ws/{}@aggTrade",
+    /// Retrieves the most recent aggregate trades of a symbol from the aggregate trades endpoint.
+    ///
+    /// # Arguments
+    ///
+    /// * `symbol` - A string slice representing the trading pair.
+    /// * `limit` - Number of trades to return, capped at `AGG_TRADES_PAGE_LIMIT`.
+    ///
+    /// # Returns
+    ///
+    /// Returns an `ApiResult<Vec<AggTrade>>` ordered by trade id.
+
+    async fn get_recent_trades(&self, symbol: &str, limit: usize) -> ApiResult<Vec<AggTrade>> {
+        let format_symbol = BinanceApi::format_binance_symbol(symbol, false);
+        let limit = limit.min(AGG_TRADES_PAGE_LIMIT);
+        let endpoint = format!("/fapi/v1/aggTrades?symbol={format_symbol}&limit={limit}");
+
+        let res = self.get(&endpoint, None).await?;
+        let data = self.handle_response(res).await?;
+
+        AggTrade::from_binance_value(symbol, &data)
+    }
+
+    /// Retrieves a page of aggregate trades of a symbol starting at a trade id.
+    ///
+    /// # Arguments
+    ///
+    /// * `symbol` - A string slice representing the trading pair.
+    /// * `from_id` - Id of the first trade of the page.
+    ///
+    /// # Returns
+    ///
+    /// Returns an `ApiResult<Vec<AggTrade>>` of up to `AGG_TRADES_PAGE_LIMIT` trades ordered by trade id.
+
+    async fn get_historical_trades(&self, symbol: &str, from_id: u64) -> ApiResult<Vec<AggTrade>> {
+        let format_symbol = BinanceApi::format_binance_symbol(symbol, false);
+        let endpoint = format!(
+            "/fapi/v1/aggTrades?symbol={format_symbol}&fromId={from_id}&limit={AGG_TRADES_PAGE_LIMIT}"
+        );
+
+        let res = self.get(&endpoint, None).await?;
+        let data = self.handle_response(res).await?;
+
+        AggTrade::from_binance_value(symbol, &data)
+    }
+
+    /// Retrieves the open futures positions of the account from the position risk endpoint.
+    ///
+    /// # Returns
+    ///
+    /// Returns an `ApiResult<Vec<ExchangePosition>>`, symbols without an open position are left out.
+
+    async fn get_positions(&self) -> ApiResult<Vec<ExchangePosition>> {
+        let endpoint = "/fapi/v2/positionRisk";
+        let ts = generate_ts();
+
+        let query_str = format!("timestamp={ts}");
+        let signature = self.sign_query_str(&query_str);
+        let query_str = format!("{}&signature={signature}", query_str);
+
+        let res = self.get(endpoint, Some(&query_str)).await?;
+        let data = self.handle_response(res).await?;
+
+        // an error body read as no positions would report every tracked position as missing
+        let values = data.as_array().ok_or_else(|| {
+            ApiError::Parsing(format!("Expected a list of positions, got {data}"))
+        })?;
+
+        let mut positions = vec![];
+        for value in values {
+            if let Some(position) = ExchangePosition::from_binance_value(value)? {
+                positions.push(position);
+            }
+        }
+
+        Ok(positions)
+    }
+
+    /// Retrieves the tick and step size of a symbol from the futures exchange info, and its
+    /// leverage range from the leverage brackets of the account.
+    ///
+    /// # Arguments
+    ///
+    /// * `symbol` - A string slice representing the trading pair.
+    ///
+    /// # Returns
+    ///
+    /// Returns an `ApiResult<SymbolInfo>`, or a parsing error if the symbol is not listed on the exchange.
+
+    async fn get_symbol_info(&self, symbol: &str) -> ApiResult<SymbolInfo> {
+        let format_symbol = BinanceApi::format_binance_symbol(symbol, false);
+        let endpoint = "/fapi/v1/exchangeInfo";
+
+        let res = self.get(endpoint, None).await?;
+        let data = self.handle_response(res).await?;
+
+        let symbol_data = data
+            .get("symbols")
+            .and_then(|symbols| symbols.as_array())
+            .and_then(|symbols| {
+                symbols
+                    .iter()
+                    .find(|s| s.get("symbol").and_then(|s| s.as_str()) == Some(&format_symbol))
+            })
+            .ok_or_else(|| ApiError::SymbolNotFound(format!("Unknown symbol {symbol}")))?;
+
+        let filter = |filter_type: &str| {
+            symbol_data
+                .get("filters")
+                .and_then(|filters| filters.as_array())
+                .and_then(|filters| {
+                    filters
+                        .iter()
+                        .find(|f| f.get("filterType").and_then(|t| t.as_str()) == Some(filter_type))
+                })
+                .ok_or_else(|| {
+                    ApiError::Parsing(format!("Missing {filter_type} filter for {symbol}"))
+                })
+        };
+
+        Ok(SymbolInfo {
+            symbol: symbol.to_string(),
+            tick_size: parse_f64_from_value("tickSize", filter("PRICE_FILTER")?)?,
+            step_size: parse_f64_from_value("stepSize", filter("LOT_SIZE")?)?,
+            min_leverage: DEFAULT_MIN_LEVERAGE,
+            max_leverage: self.get_max_leverage(&format_symbol).await?,
+            contract_type: ContractType::Linear,
+            min_notional: match filter("MIN_NOTIONAL") {
+                Ok(filter) => parse_f64_from_value("notional", filter)?,
+                Err(_) => 0.0,
+            },
+            status: symbol_data
+                .get("status")
+                .and_then(|status| status.as_str())
+                .map_or(SymbolStatus::Trading, SymbolStatus::from_exchange),
+        })
+    }
+
+    // ---
+    // Stream Helper methods
+    // ---
+
+    fn get_stream_manager(&self) -> ArcMutex<Box<dyn StreamManager>> {
+        self.stream_manager.clone()
+    }
+
+    fn build_stream_url(
+        &self,
+        symbol: &str,
+        stream_type: StreamType,
+        interval: Option<Interval>,
+    ) -> String {
+        let url = match stream_type {
+            StreamType::Kline => {
+                format!(
+                    "{}/ws/{}@kline_{}",
+                    self.ws_host,
+                    BinanceApi::format_binance_symbol(symbol, true),
+                    interval.unwrap()
+                )
+            }
+            StreamType::Ticker => {
+                format!(
+                    "{}/ws/{}@ticker",
+                    self.ws_host,
+                    BinanceApi::format_binance_symbol(symbol, true)
+                )
+            }
+            StreamType::Trade => {
+                format!(
+                    "{}/ws/{}@aggTrade",
+                    self.ws_host,
+                    BinanceApi::format_binance_symbol(symbol, true)
+                )
+            }
+            StreamType::Depth => {
+                format!(
+                    "{}/ws/{}@depth@100ms",
                     self.ws_host,
                     BinanceApi::format_binance_symbol(symbol, true)
                 )
             }
+            // symbol is the listen key for user data streams
+            StreamType::UserData => {
+                format!("{}/ws/{}", self.ws_host, symbol)
+            }
         };
 
         url
     }
 }
 
+/// Connection details of the Binance user data stream, kept to renew its listen key and
+/// reconnect.
+
+#[derive(Clone)]
+struct UserDataConnection {
+    client: Client,
+    listen_key_url: String,
+    ws_host: String,
+    headers: HeaderMap,
+}
+
+impl UserDataConnection {
+    /// Creates a new listen key and connects to its websocket, returning the URL and the stream.
+    async fn connect(&self) -> ApiResult<(String, WebSocketStream<MaybeTlsStream<TcpStream>>)> {
+        let res = self
+            .client
+            .post(&self.listen_key_url)
+            .headers(self.headers.clone())
+            .send()
+            .await?;
+        let status = res.status();
+        let data: Value = res.json().await?;
+
+        let listen_key = data
+            .get("listenKey")
+            .and_then(|key| key.as_str())
+            .filter(|_| status.is_success())
+            .ok_or_else(|| ApiError::Parsing(format!("Unable to get listen key: {data}")))?;
+
+        let url = format!("{}/ws/{}", self.ws_host, listen_key);
+        let (ws_stream, _) = connect_async(url.to_string())
+            .await
+            .map_err(|e| ApiError::Network(e.to_string()))?;
+
+        Ok((url, ws_stream))
+    }
+
+    /// Extends the validity of the current listen key, an error if the exchange refused it.
+    async fn keep_alive(&self) -> ApiResult<()> {
+        let res = self
+            .client
+            .put(&self.listen_key_url)
+            .headers(self.headers.clone())
+            .send()
+            .await?;
+
+        let status = res.status();
+        if status.is_success() {
+            return Ok(());
+        }
+
+        let body = res.text().await.unwrap_or_default();
+        Err(ApiError::Network(format!(
+            "Listen key keep alive failed with status {status}: {body}"
+        )))
+    }
+
+    /// Reads events of a user data connection and forwards them to the account.
+    ///
+    /// Returns `true` if the stream should reconnect, because the connection was lost, the
+    /// listen key couldn't be kept alive or is about to expire. Returns `false` once the stream
+    /// was closed or the account stopped receiving events.
+    async fn read_events(
+        &self,
+        ws_stream: WebSocketStream<MaybeTlsStream<TcpStream>>,
+        account_sender: &ArcSender<UserDataEvent>,
+        stream_metas: &ArcMutex<HashMap<String, StreamMeta>>,
+        stream_id: &str,
+    ) -> bool {
+        let (sync, mut ws_stream) = ws_stream.split();
+        let sync = ArcMutex::new(sync);
+        let ping_handle = spawn_keep_alive_pings(sync.clone());
+
+        let expiry = tokio::time::sleep(USER_DATA_RECONNECT);
+        tokio::pin!(expiry);
+        let mut keep_alive = tokio::time::interval_at(
+            tokio::time::Instant::now() + LISTEN_KEY_KEEP_ALIVE,
+            LISTEN_KEY_KEEP_ALIVE,
+        );
+
+        let reconnect = loop {
+            let result = tokio::select! {
+                _ = &mut expiry => {
+                    info!("Renewing listen key of user data stream {stream_id}");
+                    break true;
+                }
+                _ = keep_alive.tick() => {
+                    if let Err(e) = self.keep_alive().await {
+                        warn!("Unable to keep user data listen key alive: {e}");
+                        break true;
+                    }
+                    continue;
+                }
+                result = ws_stream.next() => match result {
+                    Some(result) => result,
+                    None => break true,
+                },
+            };
+
+            // the stream was closed by the market
+            match stream_metas.lock().await.get_mut(stream_id) {
+                Some(stream_meta) if result.is_ok() => stream_meta.last_update = generate_ts(),
+                Some(_) => {}
+                None => break false,
+            }
+
+            match result {
+                Ok(Message::Text(text)) => {
+                    let value: Value = match serde_json::from_str(&text) {
+                        Ok(value) => value,
+                        Err(e) => {
+                            warn!("Unable to decode user data event: {e}");
+                            continue;
+                        }
+                    };
+
+                    match UserDataEvent::from_binance_value(&value) {
+                        Ok(Some(event)) => {
+                            if account_sender.send(event).is_err() {
+                                break false;
+                            }
+                        }
+                        Ok(None) => {}
+                        Err(e) => warn!("Unable to parse user data event: {e}"),
+                    }
+                }
+                Ok(Message::Close(_frame)) => break true,
+                Ok(_) => {}
+                Err(e) => {
+                    warn!("Error receiving user data message on {stream_id}: {:?}", e);
+                    break true;
+                }
+            }
+        };
+
+        ping_handle.abort();
+        reconnect
+    }
+}
+
 /// Represents a manager responsible for handling streams from Binance.
 ///
 /// This struct is tasked with managing WebSocket streams for market data such as klines and tickers. It keeps track of active streams, dispatches market messages to a receiver, and manages the lifecycle of each stream.
@@ -573,8 +1326,30 @@ impl ExchangeApi for BinanceApi {
 
 pub struct BinanceStreamManager {
     streams: HashMap<String, ArcEsStreamSync>,
-    market_sender: ArcSender<MarketMessage>,
+    market_sender: MarketSender,
     stream_metas: ArcMutex<HashMap<String, StreamMeta>>,
+    combined: bool,
+    combined_sockets: ArcMutex<HashMap<String, CombinedSocket>>,
+    quarantine: StreamQuarantine,
+}
+
+/// A single websocket connection carrying multiple Binance streams.
+///
+/// `routes` maps the Binance stream name, eg. `btcusdt@kline_1m`, found in the `stream` field of
+/// each combined frame to the id of the `StreamMeta` it belongs to.
+
+struct CombinedSocket {
+    sync: ArcEsStreamSync,
+    routes: ArcMutex<HashMap<String, String>>,
+    request_id: u64,
+}
+
+/// Wrapper frame sent by Binance on combined stream connections.
+
+#[derive(Deserialize)]
+struct CombinedFrame {
+    stream: String,
+    data: HashMap<String, Value>,
 }
 
 impl BinanceStreamManager {
@@ -584,18 +1359,401 @@ impl BinanceStreamManager {
     ///
     /// # Arguments
     ///
-    /// * `market_sender` - An `ArcSender<MarketMessage>` used to send market updates to a receiver.
+    /// * `market_sender` - A `MarketSender` used to send market updates to a receiver.
+    /// * `quarantine` - Failure threshold of frames which can't be parsed.
     ///
     /// # Returns
     ///
     /// Returns a new instance of `BinanceStreamManager` with initialized fields.
 
-    pub fn new(market_sender: ArcSender<MarketMessage>) -> Self {
+    pub fn new(market_sender: MarketSender, quarantine: StreamQuarantine) -> Self {
         Self {
             streams: HashMap::new(),
             market_sender,
             stream_metas: ArcMutex::new(HashMap::new()),
+            combined: false,
+            combined_sockets: ArcMutex::new(HashMap::new()),
+            quarantine,
+        }
+    }
+
+    /// Constructs a stream manager which multiplexes all streams of a symbol over one connection.
+    ///
+    /// The first stream opened for a symbol connects to the combined `/stream` endpoint, further
+    /// streams for the same symbol are added to that connection with a `SUBSCRIBE` request.
+    ///
+    /// # Arguments
+    ///
+    /// * `market_sender` - A `MarketSender` used to send market updates to a receiver.
+    /// * `quarantine` - Failure threshold of frames which can't be parsed.
+    ///
+    /// # Returns
+    ///
+    /// Returns a new instance of `BinanceStreamManager` in combined stream mode.
+
+    pub fn new_combined(market_sender: MarketSender, quarantine: StreamQuarantine) -> Self {
+        Self {
+            combined: true,
+            ..BinanceStreamManager::new(market_sender, quarantine)
+        }
+    }
+
+    // ---
+    // Combined Stream Methods
+    // ---
+
+    /// Opens a stream on the combined connection for the stream's symbol, connecting if needed.
+    ///
+    /// # Arguments
+    ///
+    /// * `stream_meta` - A `StreamMeta` object containing the details of the stream to open.
+    ///
+    /// # Returns
+    ///
+    /// Returns an `ApiResult<String>` containing the stream ID if the stream is successfully opened.
+
+    async fn open_combined_stream(&mut self, stream_meta: StreamMeta) -> ApiResult<String> {
+        let stream_name = binance_stream_name(&stream_meta.url);
+        let socket_key = stream_meta.symbol.to_lowercase();
+        let mut combined_sockets = self.combined_sockets.lock().await;
+
+        // existing connection for symbol, subscribe to the new stream on it
+        if let Some(socket) = combined_sockets.get_mut(&socket_key) {
+            socket.request_id += 1;
+            let request = json!({
+                "method": "SUBSCRIBE",
+                "params": [stream_name],
+                "id": socket.request_id
+            });
+
+            socket
+                .sync
+                .lock()
+                .await
+                .send(Message::Text(request.to_string()))
+                .await
+                .map_err(|e| ApiError::Network(e.to_string()))?;
+
+            socket
+                .routes
+                .lock()
+                .await
+                .insert(stream_name, stream_meta.id.clone());
+
+            self.stream_metas
+                .lock()
+                .await
+                .insert(stream_meta.id.clone(), stream_meta.clone());
+
+            return Ok(stream_meta.id);
+        }
+
+        let url = stream_meta.url.replace("/ws/", "/stream?streams=");
+        let (ws_stream, _) = connect_async(url)
+            .await
+            .map_err(|e| ApiError::Network(e.to_string()))?;
+
+        let (sync, mut ws_stream) = ws_stream.split();
+
+        let routes = ArcMutex::new(HashMap::from([(stream_name, stream_meta.id.clone())]));
+
+        self.stream_metas
+            .lock()
+            .await
+            .insert(stream_meta.id.clone(), stream_meta.clone());
+
+        let sync = ArcMutex::new(sync);
+        combined_sockets.insert(
+            socket_key.clone(),
+            CombinedSocket {
+                sync: sync.clone(),
+                routes: routes.clone(),
+                request_id: 0,
+            },
+        );
+        drop(combined_sockets);
+
+        let stream_metas = self.stream_metas();
+        let combined_sockets = self.combined_sockets.clone();
+        let market_sender = self.market_sender.clone();
+        let quarantine = self.quarantine;
+        let ping_handle = spawn_keep_alive_pings(sync.clone());
+
+        tokio::spawn(async move {
+            // pings are answered by the websocket when the next frame is read
+            while let Some(result) = ws_stream.next().await {
+                match result {
+                    Ok(Message::Text(text)) => {
+                        let route = route_combined_frame(&text, &*routes.lock().await);
+
+                        // subscription responses have no stream field and are skipped
+                        if let Some((stream_id, lookup)) = route {
+                            let stream_type =
+                                stream_metas
+                                    .lock()
+                                    .await
+                                    .get_mut(&stream_id)
+                                    .map(|stream_meta| {
+                                        stream_meta.last_update = generate_ts();
+                                        stream_meta.stream_type
+                                    });
+
+                            // the lock is released first as sending waits while the market
+                            // channel is full
+                            if let Some(stream_type) = stream_type {
+                                let parsed =
+                                    handle_stream_lookup(stream_type, lookup, &market_sender).await;
+
+                                if let Some(stream_meta) =
+                                    stream_metas.lock().await.get_mut(&stream_id)
+                                {
+                                    quarantine.record(stream_meta, &text, &parsed);
+                                }
+                            }
+                        } else if let Err(e) = serde_json::from_str::<Value>(&text) {
+                            // frames which aren't json can't be routed, they count against
+                            // every stream of the connection
+                            let parsed = Err(ApiError::Parsing(e.to_string()));
+                            let mut stream_metas = stream_metas.lock().await;
+                            for stream_id in routes.lock().await.values() {
+                                if let Some(stream_meta) = stream_metas.get_mut(stream_id) {
+                                    quarantine.record(stream_meta, &text, &parsed);
+                                }
+                            }
+                        }
+                    }
+                    Ok(Message::Close(_frame)) => {
+                        let mut stream_metas = stream_metas.lock().await;
+                        for stream_id in routes.lock().await.values() {
+                            stream_metas.remove(stream_id);
+                        }
+                        break;
+                    }
+                    Ok(Message::Ping(_)) | Ok(Message::Pong(_)) => {
+                        // the connection is alive although no stream data was received
+                        let mut stream_metas = stream_metas.lock().await;
+                        for stream_id in routes.lock().await.values() {
+                            if let Some(stream_meta) = stream_metas.get_mut(stream_id) {
+                                stream_meta.last_update = generate_ts();
+                            }
+                        }
+                    }
+                    Ok(_) => {}
+                    Err(e) => {
+                        // the connection is broken, its streams are reopened by the market
+                        warn!("Error receiving message on combined stream {socket_key}: {e:?}");
+                        let mut stream_metas = stream_metas.lock().await;
+                        for stream_id in routes.lock().await.values() {
+                            if let Some(stream_meta) = stream_metas.get_mut(stream_id) {
+                                stream_meta.healthy = false;
+                            }
+                        }
+                        break;
+                    }
+                }
+            }
+
+            ping_handle.abort();
+
+            // the next stream opened for the symbol connects again, a newer connection already
+            // replacing this one is kept
+            let mut combined_sockets = combined_sockets.lock().await;
+            if combined_sockets
+                .get(&socket_key)
+                .is_some_and(|socket| std::ptr::eq(&*socket.routes, &*routes))
+            {
+                combined_sockets.remove(&socket_key);
+            }
+        });
+
+        Ok(stream_meta.id)
+    }
+
+    /// Closes a stream on a combined connection, closing the connection with its last stream.
+    ///
+    /// # Arguments
+    ///
+    /// * `stream_id` - A string slice representing the ID of the stream to close.
+    ///
+    /// # Returns
+    ///
+    /// Returns an `Option<StreamMeta>` containing the metadata of the closed stream, or `None` if not found.
+
+    async fn close_combined_stream(&mut self, stream_id: &str) -> Option<StreamMeta> {
+        let stream_meta = self.stream_metas.lock().await.remove(stream_id)?;
+
+        let stream_name = binance_stream_name(&stream_meta.url);
+        let socket_key = stream_meta.symbol.to_lowercase();
+
+        let mut combined_sockets = self.combined_sockets.lock().await;
+        if let Some(socket) = combined_sockets.get_mut(&socket_key) {
+            let mut routes = socket.routes.lock().await;
+            routes.remove(&stream_name);
+
+            if routes.is_empty() {
+                drop(routes);
+                let _ = socket.sync.lock().await.close().await;
+                combined_sockets.remove(&socket_key);
+            } else {
+                socket.request_id += 1;
+                let request = json!({
+                    "method": "UNSUBSCRIBE",
+                    "params": [stream_name],
+                    "id": socket.request_id
+                });
+                let _ = socket
+                    .sync
+                    .lock()
+                    .await
+                    .send(Message::Text(request.to_string()))
+                    .await;
+            }
         }
+
+        Some(stream_meta)
+    }
+}
+
+/// Spawns a task sending a ping on a websocket connection every `WS_PING_INTERVAL`, the task ends
+/// once a ping can't be sent.
+///
+/// # Arguments
+///
+/// * `sync` - The sending side of the websocket connection.
+///
+/// # Returns
+///
+/// The `JoinHandle` of the task, aborted once the connection is closed.
+
+fn spawn_keep_alive_pings<S>(sync: ArcMutex<S>) -> JoinHandle<()>
+where
+    S: Sink<Message> + Unpin + Send + 'static,
+{
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(WS_PING_INTERVAL);
+        // the first tick completes immediately
+        interval.tick().await;
+
+        loop {
+            interval.tick().await;
+
+            if sync.lock().await.send(Message::Ping(vec![])).await.is_err() {
+                break;
+            }
+        }
+    })
+}
+
+/// Extracts the Binance stream name, eg. `btcusdt@kline_1m`, from a raw stream URL.
+
+fn binance_stream_name(url: &str) -> String {
+    url.rsplit('/').next().unwrap_or(url).to_string()
+}
+
+/// Resolves a combined stream frame to the stream it belongs to.
+///
+/// # Arguments
+///
+/// * `text` - The raw text frame received on a combined connection.
+/// * `routes` - Mapping of Binance stream names to `StreamMeta` ids.
+///
+/// # Returns
+///
+/// The `StreamMeta` id and the unwrapped data lookup, or `None` if the frame is not a stream
+/// payload or the stream is not routed.
+
+fn route_combined_frame(
+    text: &str,
+    routes: &HashMap<String, String>,
+) -> Option<(String, HashMap<String, Value>)> {
+    let frame: CombinedFrame = serde_json::from_str(text).ok()?;
+    let stream_id = routes.get(&frame.stream)?;
+
+    Some((stream_id.to_string(), frame.data))
+}
+
+/// Parses a stream payload according to its stream type and forwards it to the market.
+///
+/// # Arguments
+///
+/// * `stream_type` - The type of stream the payload was received on.
+/// * `lookup` - The decoded JSON payload.
+/// * `market_sender` - Sender used to forward the parsed market message.
+///
+/// # Returns
+///
+/// An error if the payload isn't a valid event of the stream type.
+
+async fn handle_stream_lookup(
+    stream_type: StreamType,
+    lookup: HashMap<String, Value>,
+    market_sender: &MarketSender,
+) -> ApiResult<()> {
+    let message = match stream_type {
+        StreamType::Kline => {
+            let closed = Kline::is_closed_in_binance_lookup(&lookup);
+            let kline = Kline::from_binance_lookup(lookup)?;
+
+            if closed {
+                MarketMessage::UpdateKline(kline)
+            } else {
+                MarketMessage::UpdatePartialKline(kline)
+            }
+        }
+        StreamType::Ticker => MarketMessage::UpdateTicker(Ticker::from_binance_lookup(lookup)?),
+        StreamType::Trade => MarketMessage::UpdateMarketTrade(Trade::from_binance_lookup(lookup)?),
+        StreamType::Depth => MarketMessage::UpdateDepth(DepthUpdate::from_binance_lookup(lookup)?),
+        StreamType::UserData => {
+            // user data is sent to the account by the user data stream, not the market
+            return Ok(());
+        }
+    };
+
+    let _ = market_sender.send(message).await;
+
+    Ok(())
+}
+
+/// Parses a text frame received on a single stream connection and forwards it to the market,
+/// frames which can't be parsed are quarantined rather than ending the stream task.
+///
+/// # Arguments
+///
+/// * `text` - The raw text frame.
+/// * `stream_id` - Id of the stream the frame was received on.
+/// * `stream_metas` - Metadata of the streams, the stream's last update and frame counts are
+///   updated.
+/// * `market_sender` - Sender used to forward the parsed market message.
+/// * `quarantine` - Failure threshold of frames which can't be parsed.
+
+async fn handle_stream_text(
+    text: &str,
+    stream_id: &str,
+    stream_metas: &ArcMutex<HashMap<String, StreamMeta>>,
+    market_sender: &MarketSender,
+    quarantine: StreamQuarantine,
+) {
+    let stream_type = stream_metas
+        .lock()
+        .await
+        .get_mut(stream_id)
+        .map(|stream_meta| {
+            stream_meta.last_update = generate_ts();
+            stream_meta.stream_type
+        });
+
+    let Some(stream_type) = stream_type else {
+        return;
+    };
+
+    // the lock is released first as sending waits while the market channel is full
+    let parsed = match serde_json::from_str::<HashMap<String, Value>>(text) {
+        Ok(lookup) => handle_stream_lookup(stream_type, lookup, market_sender).await,
+        Err(e) => Err(ApiError::Parsing(e.to_string())),
+    };
+
+    if let Some(stream_meta) = stream_metas.lock().await.get_mut(stream_id) {
+        quarantine.record(stream_meta, text, &parsed);
     }
 }
 
@@ -614,6 +1772,10 @@ impl StreamManager for BinanceStreamManager {
     /// Returns an `ApiResult<String>` containing the stream ID if the stream is successfully opened, or an error in case of failure.
 
     async fn open_stream(&mut self, stream_meta: StreamMeta) -> ApiResult<String> {
+        if self.combined {
+            return self.open_combined_stream(stream_meta).await;
+        }
+
         let (ws_stream, _) = connect_async(stream_meta.url.to_string())
             .await
             .unwrap_or_else(|_| {
@@ -634,11 +1796,13 @@ impl StreamManager for BinanceStreamManager {
             .insert(stream_meta.id.to_string(), stream_meta.clone());
 
         let sync = ArcMutex::new(sync);
-        self.streams.insert(stream_meta.id.clone(), sync);
+        self.streams.insert(stream_meta.id.clone(), sync.clone());
 
         let market_sender = self.market_sender.clone();
+        let quarantine = self.quarantine;
 
         let thread_stream_id = stream_meta.id.clone();
+        let ping_handle = spawn_keep_alive_pings(sync.clone());
 
         // Spawn client web socket to listen for kline
         tokio::spawn(async move {
@@ -649,40 +1813,14 @@ impl StreamManager for BinanceStreamManager {
                         // Handle received message
                         // If text message then can create new Kline
                         Message::Text(text) => {
-                            if let Some(stream_meta) =
-                                stream_metas.lock().await.get_mut(&thread_stream_id)
-                            {
-                                stream_meta.last_update = generate_ts();
-                                match stream_meta.stream_type {
-                                    StreamType::Kline => {
-                                        let lookup: HashMap<String, Value> =
-                                            serde_json::from_str(&text).unwrap();
-
-                                        if let Ok(kline) = Kline::from_binance_lookup(lookup) {
-                                            let _ = market_sender
-                                                .send(MarketMessage::UpdateKline(kline));
-                                        }
-                                    }
-                                    StreamType::Ticker => {
-                                        let lookup: HashMap<String, Value> =
-                                            serde_json::from_str(&text).unwrap();
-
-                                        if let Ok(ticker) = Ticker::from_binance_lookup(lookup) {
-                                            let _ = market_sender
-                                                .send(MarketMessage::UpdateTicker(ticker));
-                                        }
-                                    }
-                                    StreamType::Trade => {
-                                        let lookup: HashMap<String, Value> =
-                                            serde_json::from_str(&text).unwrap();
-
-                                        if let Ok(trade) = Trade::from_binance_lookup(lookup) {
-                                            let _ = market_sender
-                                                .send(MarketMessage::UpdateMarketTrade(trade));
-                                        }
-                                    }
-                                }
-                            };
+                            handle_stream_text(
+                                &text,
+                                &thread_stream_id,
+                                &stream_metas,
+                                &market_sender,
+                                quarantine,
+                            )
+                            .await;
                         }
 
                         Message::Close(_frame) => {
@@ -690,21 +1828,39 @@ impl StreamManager for BinanceStreamManager {
                         }
 
                         Message::Ping(_data) => {
-                            // ignore Ping Pong Messages
+                            // answered by the websocket when the next frame is read
+                            if let Some(stream_meta) =
+                                stream_metas.lock().await.get_mut(&thread_stream_id)
+                            {
+                                stream_meta.last_update = generate_ts();
+                            }
                         }
                         Message::Pong(_data) => {
-                            // ignore Ping Pong Messages
+                            // answer to a client ping, the connection is alive
+                            if let Some(stream_meta) =
+                                stream_metas.lock().await.get_mut(&thread_stream_id)
+                            {
+                                stream_meta.last_update = generate_ts();
+                            }
                         }
                         _ => {
-                            println!("Received unexpected data: {:?}", msg);
+                            warn!("Received unexpected data on stream {thread_stream_id}: {msg:?}");
                         }
                     },
                     Err(e) => {
-                        // Handle error
-                        eprintln!("Error receiving message: {:?}", e);
+                        // the connection is broken, the stream is reopened by the market
+                        warn!("Error receiving message on stream {thread_stream_id}: {e:?}");
+                        if let Some(stream_meta) =
+                            stream_metas.lock().await.get_mut(&thread_stream_id)
+                        {
+                            stream_meta.healthy = false;
+                        }
+                        break;
                     }
                 }
             }
+
+            ping_handle.abort();
         });
 
         Ok(stream_meta.id.to_string())
@@ -723,6 +1879,10 @@ impl StreamManager for BinanceStreamManager {
     /// Returns an `Option<StreamMeta>` containing the metadata of the closed stream if found and successfully closed, or `None` if the stream ID does not match any active streams.
 
     async fn close_stream(&mut self, stream_id: &str) -> Option<StreamMeta> {
+        if self.combined {
+            return self.close_combined_stream(stream_id).await;
+        }
+
         let mut infos = self.stream_metas.lock().await;
 
         if let Some(stream_meta) = infos.get_mut(stream_id) {
@@ -746,8 +1906,91 @@ impl StreamManager for BinanceStreamManager {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::exchange::quarantine::MIN_QUARANTINE_FRAMES;
+    use crate::exchange::resilient::{ResilienceConfig, ResilientExchangeApi};
+    use crate::market::channel::{build_market_channel, DEFAULT_MARKET_CHANNEL_CAPACITY};
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
     use tokio::test;
 
+    #[test]
+    async fn test_stream_quarantines_unparsable_frames() {
+        let (market_tx, market_rx) = build_market_channel(DEFAULT_MARKET_CHANNEL_CAPACITY);
+        let stream_id = build_stream_id("BTCUSDT", StreamType::Kline, Some(Interval::Min1));
+        let stream_metas = ArcMutex::new(HashMap::from([(
+            stream_id.clone(),
+            StreamMeta::new(
+                &stream_id,
+                "wss://fstream.binance.com/ws/btcusdt@kline_1m",
+                "BTCUSDT",
+                StreamType::Kline,
+                Some(Interval::Min1),
+            ),
+        )]));
+
+        let kline_frame = json!({
+            "e": "kline",
+            "E": 1700000001000u64,
+            "s": "BTCUSDT",
+            "k": {
+                "t": 1700000000000u64,
+                "T": 1700000059999u64,
+                "s": "BTCUSDT",
+                "i": "1m",
+                "o": "37000.10",
+                "c": "37010.20",
+                "h": "37020.00",
+                "l": "36990.00",
+                "v": "12.5",
+                "x": true
+            }
+        })
+        .to_string();
+
+        // garbage and an unexpected event on the stream are followed by a valid kline
+        let frames = vec![
+            "<html>502 Bad Gateway</html>".to_string(),
+            json!({ "e": "listStatus", "s": "BTCUSDT" }).to_string(),
+            kline_frame,
+        ];
+        let task = tokio::spawn({
+            let stream_id = stream_id.clone();
+            let stream_metas = stream_metas.clone();
+            let quarantine = StreamQuarantine::default();
+            async move {
+                for text in frames {
+                    handle_stream_text(&text, &stream_id, &stream_metas, &market_tx, quarantine)
+                        .await;
+                }
+            }
+        });
+        assert!(task.await.is_ok());
+
+        match market_rx.recv().await {
+            Some(MarketMessage::UpdateKline(kline)) => assert_eq!(kline.close, 37010.20),
+            message => panic!("expected a kline, got {message:?}"),
+        }
+
+        let mut stream_metas = stream_metas.lock().await;
+        let stream_meta = stream_metas.get_mut(&stream_id).unwrap();
+        assert_eq!(stream_meta.frame_count, 3);
+        assert_eq!(stream_meta.parse_failures, 2);
+        // too few frames for the failure rate to count
+        assert!(stream_meta.healthy);
+
+        let quarantine = StreamQuarantine::from_setting("50");
+        for _ in 0..MIN_QUARANTINE_FRAMES {
+            quarantine.record(
+                stream_meta,
+                "garbage",
+                &Err(ApiError::Parsing("eof".into())),
+            );
+        }
+        assert!(!stream_meta.healthy);
+    }
+
     #[test]
     async fn test_format_binance_symbol() {
         let symbol = "BTC-USDT";
@@ -756,4 +1999,372 @@ mod tests {
         let formatted_symbol = BinanceApi::format_binance_symbol(symbol, false);
         assert_eq!(formatted_symbol, "BTCUSDT");
     }
+
+    fn build_test_api() -> BinanceApi {
+        let (market_tx, _) = build_market_channel(DEFAULT_MARKET_CHANNEL_CAPACITY);
+        BinanceApi::new(
+            "api_key",
+            "secret_key",
+            market_tx,
+            false,
+            HttpClientConfig::default(),
+            StreamQuarantine::default(),
+        )
+    }
+
+    #[test]
+    async fn test_build_cancel_all_orders_request() {
+        let api = build_test_api();
+
+        let request = api
+            .build_cancel_order_request("BTCUSDT", None, 1_700_000_000_000)
+            .unwrap();
+
+        let query_str = "symbol=BTCUSDT&timestamp=1700000000000";
+        let signature = api.sign_query_str(query_str);
+
+        assert_eq!(request.method(), reqwest::Method::DELETE);
+        assert_eq!(request.url().path(), "/fapi/v1/allOpenOrders");
+        assert_eq!(
+            request.url().query(),
+            Some(format!("{query_str}&signature={signature}").as_str())
+        );
+        assert_eq!(request.headers().get("X-MBX-APIKEY").unwrap(), "api_key");
+    }
+
+    #[test]
+    async fn test_build_cancel_order_request() {
+        let api = build_test_api();
+
+        let request = api
+            .build_cancel_order_request("BTCUSDT", Some("283194212"), 1_700_000_000_000)
+            .unwrap();
+
+        let query_str = "symbol=BTCUSDT&orderId=283194212&timestamp=1700000000000";
+        let signature = api.sign_query_str(query_str);
+
+        assert_eq!(request.method(), reqwest::Method::DELETE);
+        assert_eq!(request.url().path(), "/fapi/v1/order");
+        assert_eq!(
+            request.url().query(),
+            Some(format!("{query_str}&signature={signature}").as_str())
+        );
+    }
+
+    #[test]
+    async fn test_build_open_order_query() {
+        let mut position = Position::new("BTCUSDT", 50000.0, OrderSide::Sell, 100.0, 10, None);
+        position.quantity = 0.02;
+
+        assert_eq!(
+            BinanceApi::build_open_order_query(&position, Some("order-1"), 1_700_000_000_000),
+            "symbol=BTCUSDT&side=SELL&type=MARKET&quantity=0.02&timestamp=1700000000000&newClientOrderId=order-1"
+        );
+
+        // inverse positions are sized in whole contracts
+        position.set_contract_type(ContractType::Inverse {
+            contract_size: 100.0,
+        });
+        assert_eq!(
+            BinanceApi::build_open_order_query(&position, None, 1_700_000_000_000),
+            "symbol=BTCUSDT&side=SELL&type=MARKET&quantity=10&timestamp=1700000000000"
+        );
+    }
+
+    #[test]
+    async fn test_query_values_encoded_before_signing() {
+        let api = build_test_api();
+
+        let request = api
+            .build_cancel_order_request("BTCUSDT", Some("id&x=1+2 3"), 1_700_000_000_000)
+            .unwrap();
+
+        let query_str = "symbol=BTCUSDT&orderId=id%26x%3D1%2B2+3&timestamp=1700000000000";
+        let signature = api.sign_query_str(query_str);
+
+        // the encoded query is sent and signed as is
+        assert_eq!(
+            request.url().query(),
+            Some(format!("{query_str}&signature={signature}").as_str())
+        );
+
+        let repeated = api
+            .build_cancel_order_request("BTCUSDT", Some("id&x=1+2 3"), 1_700_000_000_000)
+            .unwrap();
+        assert_eq!(repeated.url().query(), request.url().query());
+
+        // plain values are unchanged
+        let query_str = QueryStr::new(vec![("symbol", "BTC-USDT"), ("quantity", "0.001")]);
+        assert_eq!(query_str.to_string(), "symbol=BTC-USDT&quantity=0.001");
+    }
+
+    #[test]
+    async fn test_parse_kline() {
+        let data = json!([[
+            1499040000000u64,
+            "0.01634790",
+            "0.80000000",
+            "0.01575800",
+            "0.01577100",
+            "148976.11427815",
+            1499644799999u64,
+            "2434.19055334",
+            308,
+            "1756.87402397",
+            "28.46694368",
+            "17928899.62484339"
+        ]]);
+
+        let kline = BinanceApi::parse_kline(data, "BTCUSDT", Interval::Min1).unwrap();
+        assert_eq!(kline.symbol, "BTCUSDT");
+        assert_eq!(kline.open_time, 1499040000000);
+        assert_eq!(kline.close, 0.01577100);
+        assert_eq!(kline.volume, 148976.11427815);
+        assert_eq!(kline.close_time, 1499644799999);
+        assert_eq!(kline.quote_volume, 2434.19055334);
+    }
+
+    /// Serves one response per connection with the given statuses in turn, returning the address
+    /// of the server and the number of requests it received.
+    async fn serve_statuses(statuses: Vec<u16>) -> (String, Arc<AtomicUsize>) {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(AtomicUsize::new(0));
+        let served = requests.clone();
+
+        tokio::spawn(async move {
+            for status in statuses {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let mut buf = [0; 4096];
+                let _ = socket.read(&mut buf).await;
+                served.fetch_add(1, Ordering::SeqCst);
+
+                let body = match status {
+                    200 => "[]".to_string(),
+                    _ => format!(r#"{{"code":-1,"msg":"status {status}"}}"#),
+                };
+                let response = format!(
+                    "HTTP/1.1 {status} Status\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                    body.len()
+                );
+                let _ = socket.write_all(response.as_bytes()).await;
+            }
+        });
+
+        (addr, requests)
+    }
+
+    #[test]
+    async fn test_error_statuses_retried_by_resilience_layer() {
+        let config = ResilienceConfig {
+            max_retries: 3,
+            backoff: Duration::from_millis(1),
+            failure_threshold: 10,
+            cool_down: Duration::from_secs(60),
+        };
+
+        // rate limited then unavailable, the third attempt succeeds
+        let (host, requests) = serve_statuses(vec![429, 503, 200]).await;
+        let inner = BinanceApi {
+            host,
+            ..build_test_api()
+        };
+        let exchange_api = ResilientExchangeApi::new(Arc::new(inner), config);
+
+        let positions = exchange_api.get_positions().await.unwrap();
+        assert!(positions.is_empty());
+        assert_eq!(requests.load(Ordering::SeqCst), 3);
+
+        // a rejected request isn't retried
+        let (host, requests) = serve_statuses(vec![401, 200]).await;
+        let inner = BinanceApi {
+            host,
+            ..build_test_api()
+        };
+        let exchange_api = ResilientExchangeApi::new(Arc::new(inner), config);
+
+        let res = exchange_api.get_positions().await;
+        assert!(matches!(res, Err(ApiError::Rejected(_))), "{res:?}");
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    async fn test_parse_max_leverage() {
+        let brackets = json!({
+            "symbol": "ETHUSDT",
+            "brackets": [
+                {"bracket": 1, "initialLeverage": 75, "notionalCap": 10000},
+                {"bracket": 2, "initialLeverage": 50, "notionalCap": 100000}
+            ]
+        });
+
+        assert_eq!(
+            BinanceApi::parse_max_leverage(&brackets, "ETHUSDT").unwrap(),
+            75
+        );
+        assert_eq!(
+            BinanceApi::parse_max_leverage(&json!([brackets]), "ETHUSDT").unwrap(),
+            75
+        );
+        assert!(matches!(
+            BinanceApi::parse_max_leverage(&json!([brackets]), "BTCUSDT"),
+            Err(ApiError::Parsing(_))
+        ));
+    }
+
+    #[test]
+    async fn test_kline_frame_quote_volume() {
+        let mut frame = json!({
+            "e": "kline",
+            "s": "BTCUSDT",
+            "k": {
+                "t": 1700000000000u64,
+                "T": 1700000059999u64,
+                "s": "BTCUSDT",
+                "i": "1m",
+                "o": "37000.10",
+                "c": "37010.20",
+                "h": "37020.00",
+                "l": "36990.00",
+                "v": "12.5",
+                "q": "462625.75",
+                "x": true
+            }
+        });
+
+        let lookup: HashMap<String, Value> = serde_json::from_value(frame.clone()).unwrap();
+        let kline = Kline::from_binance_lookup(lookup).unwrap();
+        assert_eq!(kline.volume, 12.5);
+        assert_eq!(kline.quote_volume, 462625.75);
+
+        // frames without a quote volume default it
+        frame["k"].as_object_mut().unwrap().remove("q");
+        let lookup: HashMap<String, Value> = serde_json::from_value(frame).unwrap();
+        assert_eq!(
+            Kline::from_binance_lookup(lookup).unwrap().quote_volume,
+            0.0
+        );
+    }
+
+    #[test]
+    async fn test_parse_kline_empty_or_short() {
+        let res = BinanceApi::parse_kline(json!([]), "BTCUSDT", Interval::Min1);
+        assert!(matches!(res, Err(ApiError::Parsing(_))));
+
+        let res = BinanceApi::parse_kline(
+            json!([[1499040000000u64, "0.01"]]),
+            "BTCUSDT",
+            Interval::Min1,
+        );
+        assert!(matches!(res, Err(ApiError::Parsing(_))));
+
+        let data = json!([[
+            1499040000000u64,
+            "abc",
+            "0.8",
+            "0.01",
+            "0.01",
+            "1.0",
+            1499644799999u64
+        ]]);
+        let res = BinanceApi::parse_kline(data, "BTCUSDT", Interval::Min1);
+        assert!(matches!(res, Err(ApiError::Parsing(_))));
+    }
+
+    #[test]
+    async fn test_route_combined_frame() {
+        let routes = HashMap::from([
+            (
+                "btcusdt@kline_1m".to_string(),
+                "BTCUSDT@kline_1m".to_string(),
+            ),
+            (
+                "btcusdt@kline_5m".to_string(),
+                "BTCUSDT@kline_5m".to_string(),
+            ),
+        ]);
+
+        let frame = r#"{
+            "stream": "btcusdt@kline_5m",
+            "data": {
+                "e": "kline",
+                "E": 1700000001000,
+                "s": "BTCUSDT",
+                "k": {
+                    "t": 1700000000000,
+                    "T": 1700000299999,
+                    "s": "BTCUSDT",
+                    "i": "5m",
+                    "o": "37000.10",
+                    "c": "37010.20",
+                    "h": "37020.00",
+                    "l": "36990.00",
+                    "v": "12.5",
+                    "x": false
+                }
+            }
+        }"#;
+
+        let (stream_id, lookup) = route_combined_frame(frame, &routes).unwrap();
+        assert_eq!(stream_id, "BTCUSDT@kline_5m");
+        assert!(!Kline::is_closed_in_binance_lookup(&lookup));
+
+        let kline = Kline::from_binance_lookup(lookup).unwrap();
+        assert_eq!(kline.interval, Interval::Min5);
+        assert_eq!(kline.open_time, 1_700_000_000_000);
+        assert_eq!(kline.close, 37010.20);
+
+        // subscription responses and unrouted streams are ignored
+        assert!(route_combined_frame(r#"{"result":null,"id":1}"#, &routes).is_none());
+        let unrouted = frame.replace("btcusdt@kline_5m", "ethusdt@kline_5m");
+        assert!(route_combined_frame(&unrouted, &routes).is_none());
+    }
+
+    #[test]
+    async fn test_binance_stream_name() {
+        let api = build_test_api();
+        let url = api.build_stream_url("BTCUSDT", StreamType::Kline, Some(Interval::Min1));
+
+        assert_eq!(binance_stream_name(&url), "btcusdt@kline_1m");
+        assert_eq!(
+            url.replace("/ws/", "/stream?streams="),
+            "wss://fstream.binance.com/stream?streams=btcusdt@kline_1m"
+        );
+    }
+
+    #[test]
+    async fn test_hung_request_times_out() {
+        // accepts connections but never responds
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let _server = tokio::spawn(async move {
+            let mut connections = vec![];
+            while let Ok((socket, _)) = listener.accept().await {
+                connections.push(socket);
+            }
+        });
+
+        let (market_tx, _) = build_market_channel(DEFAULT_MARKET_CHANNEL_CAPACITY);
+        let http_config = HttpClientConfig {
+            request_timeout: Duration::from_millis(300),
+            ..Default::default()
+        };
+        let mut api = BinanceApi::new(
+            "api_key",
+            "secret_key",
+            market_tx,
+            false,
+            http_config,
+            StreamQuarantine::default(),
+        );
+        api.host = format!("http://{addr}");
+
+        let started = std::time::Instant::now();
+        let result = api.get_kline("BTCUSDT", Interval::Min1).await;
+
+        assert!(matches!(result, Err(ApiError::Network(_))));
+        assert!(started.elapsed() < Duration::from_secs(2));
+    }
 }
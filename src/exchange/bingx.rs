@@ -1,7 +1,7 @@
 use async_trait::async_trait;
 
 use futures_util::SinkExt;
-use log::warn;
+use log::{error, warn};
 
 use reqwest::header::{HeaderMap, HeaderValue, CONTENT_TYPE};
 use reqwest::{Client, Response};
@@ -9,6 +9,7 @@ use reqwest::{Client, Response};
 
 use serde_json::{json, Value};
 use std::collections::HashMap;
+use std::future::Future;
 
 use std::time::Duration;
 use tokio::task::JoinHandle;
@@ -16,26 +17,43 @@ use tokio::task::JoinHandle;
 use hmac::{Hmac, Mac};
 use sha2::Sha256;
 
-use crate::account::trade::{OrderSide, Position, TradeTx};
+use crate::account::trade::{Position, TradeTx};
 use crate::exchange::api::{ExchangeApi, QueryStr};
 
+use crate::market::channel::MarketSender;
 use crate::market::messages::MarketMessage;
-use crate::market::trade::Trade;
-use crate::market::types::{ArcMutex, ArcSender};
+use crate::market::trade::{BingXTradeCursor, Trade};
+use crate::market::types::ArcMutex;
 use crate::market::{kline::Kline, ticker::Ticker};
 
 use crate::market::interval::{self, Interval};
+use crate::utils::number::to_decimal;
 use crate::utils::time::generate_ts;
 
 use super::api::ExchangeInfo;
+use super::http::{check_status, HttpClientConfig};
 
 use super::stream::{StreamManager, StreamMeta};
-use super::types::{ApiResult, StreamType};
+use super::symbol::{canonical_symbol, format_exchange_symbol, SymbolFormat};
+use super::types::{ApiError, ApiResult, StreamType};
 
 const BING_X_WS_HOST_URL: &str = "wss://open-api-swap.bingx.com/swap-market";
 const BING_X_HOST_URL: &str = "https://open-api.bingx.com";
 const API_VERSION: &str = "v3";
 
+/// Delay between polls of a BingX stream while its requests succeed, in milliseconds.
+const DEFAULT_POLL_INTERVAL_MS: u64 = 1_000;
+
+/// Longest delay between polls of a failing BingX stream, in seconds.
+const DEFAULT_POLL_MAX_BACKOFF_SECS: u64 = 60;
+
+/// Consecutive failed polls after which a BingX stream stops and is reported as unhealthy.
+const DEFAULT_POLL_MAX_FAILURES: u32 = 10;
+
+/// Most trades BingX returns per request, each poll fetches as many so the trades of a busy
+/// market between two polls aren't missed.
+const TRADES_LIMIT: &str = "1000";
+
 pub struct BingXApi {
     ws_host: String,
     host: String,
@@ -46,19 +64,28 @@ pub struct BingXApi {
 }
 
 impl BingXApi {
-    pub fn new(api_key: &str, secret_key: &str, market_sender: ArcSender<MarketMessage>) -> Self {
+    pub fn new(
+        api_key: &str,
+        secret_key: &str,
+        market_sender: MarketSender,
+        http_config: HttpClientConfig,
+        poll_config: PollConfig,
+    ) -> Self {
         let ws_host = BING_X_WS_HOST_URL.to_string();
         let host = BING_X_HOST_URL.to_string();
 
         // Testnet hosts
 
-        let stream_manager: ArcMutex<Box<dyn StreamManager>> =
-            ArcMutex::new(Box::new(BingXStreamManager::new(market_sender)));
+        // streams poll with the client of the api, sharing its connection pool
+        let client = http_config.build_client();
+        let stream_manager: ArcMutex<Box<dyn StreamManager>> = ArcMutex::new(Box::new(
+            BingXStreamManager::new(market_sender, poll_config, client.clone()),
+        ));
 
         Self {
             ws_host,
             host,
-            client: Client::builder().build().unwrap(),
+            client,
             api_key: api_key.to_string(),
             secret_key: secret_key.to_string(),
             stream_manager,
@@ -149,6 +176,27 @@ impl BingXApi {
             .await
     }
 
+    /// Performs an HTTP DELETE request to the specified endpoint.
+    ///
+    /// # Arguments
+    ///
+    /// * `endpoint` - A string slice specifying the endpoint for the DELETE request.
+    /// * `query_str` - A string slice containing the signed query string.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` with the response `Response` object if the request is successful, or an error of type `reqwest::Error` otherwise.
+
+    async fn delete(&self, endpoint: &str, query_str: &str) -> Result<Response, reqwest::Error> {
+        let url = format!("{}{}?{}", self.host, endpoint, query_str);
+
+        self.client
+            .delete(&url)
+            .headers(self.build_headers(false))
+            .send()
+            .await
+    }
+
     /// Processes the HTTP response, extracting the relevant data based on the content type.
     ///
     /// This method checks the content type of the response and accordingly parses the response body as either plain text or JSON. It is designed to handle different response formats gracefully, ensuring that the data is correctly extracted from various API endpoints.
@@ -160,8 +208,10 @@ impl BingXApi {
     /// # Returns
     ///
     /// Returns an `ApiResult<Value>`, which is a `Result` type that either contains the parsed data as a `serde_json::Value` or an error if the response processing fails.
+    /// Error statuses are mapped by `check_status`, so rate limits and server errors are retried.
 
     async fn handle_response(&self, response: Response) -> ApiResult<Value> {
+        let status = response.status();
         let data = match &response.headers().get("content-type") {
             Some(header) => {
                 if header.to_str().unwrap().contains("text/html") {
@@ -173,6 +223,7 @@ impl BingXApi {
             None => json!({"text":response.text().await?}),
         };
 
+        check_status(status, &data)?;
         Ok(data)
     }
 
@@ -204,7 +255,7 @@ impl BingXApi {
     }
 
     fn format_bingx_symbol(symbol: &str, lower_case: bool) -> String {
-        let symbol: String = symbol.replace("USDT", "-USDT");
+        let symbol = format_exchange_symbol(symbol, SymbolFormat::BingX);
 
         if lower_case {
             return symbol.to_lowercase();
@@ -242,7 +293,7 @@ impl ExchangeApi for BingXApi {
     /// Returns an `ApiResult<Kline>`, encapsulating the latest k-line data. In case of an error, it returns an appropriate error encapsulated within `ApiResult`.
 
     async fn get_kline(&self, symbol: &str, interval: Interval) -> ApiResult<Kline> {
-        get_bingx_kline(symbol, interval).await
+        get_bingx_kline(&self.client, symbol, interval).await
     }
 
     /// Retrieves the current ticker information for a specified symbol.
@@ -258,52 +309,46 @@ impl ExchangeApi for BingXApi {
     /// Returns an `ApiResult<Ticker>`, providing the current market ticker data. If the operation fails, it returns an error within `ApiResult`.
 
     async fn get_ticker(&self, symbol: &str) -> ApiResult<Ticker> {
-        get_bingx_ticker(symbol).await
+        get_bingx_ticker(&self.client, symbol).await
     }
 
-    /// Opens a new trading position on the exchange with specified parameters.
+    /// Opens a new trading position on the exchange with a market order.
     ///
-    /// This method places an order to open a new trading position based on the symbol, margin used, leverage, order side (buy/sell), and the specified opening price. It constructs the request, signs it, and sends it to the exchange.
+    /// The order is sized by the quantity of the position, as the account rounded it to the step
+    /// size of the symbol. The query is signed and posted to the perpetual swap order endpoint.
     ///
     /// # Arguments
     ///
-    /// * `symbol` - The market symbol for the trading pair.
-    /// * `margin_usd` - The amount of margin in USD to be used for this position.
-    /// * `leverage` - The leverage to apply to the position.
-    /// * `order_side` - The side of the order, either `OrderSide::Buy` or `OrderSide::Sell`.
-    /// * `open_price` - The price at which to attempt to open the position.
+    /// * `position` - The position to open.
+    /// * `client_order_id` - Optional client order ID, the exchange rejects a second order with the same ID.
     ///
     /// # Returns
     ///
-    /// Returns an `ApiResult<Position>` indicating the successful creation of a trading position, or an error if the operation fails.
+    /// Returns an `ApiResult<Position>` with the order ID of the exchange set, or an error if the operation fails.
 
     async fn open_position(
         &self,
-        symbol: &str,
-        margin_usd: f64,
-        leverage: u32,
-        order_side: OrderSide,
-        open_price: f64,
+        mut position: Position,
+        client_order_id: Option<&str>,
     ) -> ApiResult<Position> {
-        let quantity = (margin_usd * leverage as f64) / open_price;
+        let endpoint = "/openApi/swap/v2/trade/order";
 
-        let endpoint = "/api/v3/order";
-
-        // format qty to 8 decimals
-        let _qty = format!("{:.1$}", quantity, 8);
-
-        let ts = &generate_ts().to_string();
-        let side = &order_side.to_string();
-        let quote_qty = quantity.to_string();
+        let ts = generate_ts().to_string();
+        let side = position.order_side.to_string().to_uppercase();
+        let quantity = to_decimal(position.quantity).normalize().to_string();
+        let bingx_symbol = BingXApi::format_bingx_symbol(&position.symbol, false);
 
-        let request_body = QueryStr::new(vec![
-            ("symbol", symbol),
-            ("quoteOrderQty", &quote_qty),
-            // ("quantity", &qty),
+        let mut params: Vec<(&str, &str)> = vec![
+            ("symbol", &bingx_symbol),
+            ("side", &side),
             ("type", "MARKET"),
-            ("side", side),
-            ("timestamp", ts),
-        ]);
+            ("quantity", &quantity),
+            ("timestamp", &ts),
+        ];
+        if let Some(client_order_id) = client_order_id {
+            params.push(("clientOrderID", client_order_id));
+        }
+        let request_body = QueryStr::new(params);
 
         let signature = self.sign_query_str(&request_body.to_string());
 
@@ -311,16 +356,12 @@ impl ExchangeApi for BingXApi {
 
         let res = self.post(endpoint, &query_str).await?;
 
-        match self.handle_response(res).await {
-            Ok(_res) => {
-                // parse response
-                // build position from response
-                Ok(Position::new(
-                    symbol, open_price, order_side, margin_usd, leverage, None,
-                ))
-            }
-            Err(e) => Err(e),
-        }
+        let res = self.handle_response(res).await?;
+        // the order ID is nested in the order of the response data, ie. `{"data": {"order": ..}}`
+        position.order_id = res
+            .pointer("/data/order/orderId")
+            .and_then(|id| id.as_u64());
+        Ok(position)
     }
 
     /// Closes an existing trading position on the exchange.
@@ -414,6 +455,64 @@ impl ExchangeApi for BingXApi {
         self.handle_response(res).await
     }
 
+    /// Cancels a single open order on the exchange.
+    ///
+    /// # Arguments
+    ///
+    /// * `symbol` - The market symbol of the order.
+    /// * `order_id` - The BingX order ID of the order to cancel.
+    ///
+    /// # Returns
+    ///
+    /// Returns an `ApiResult<Value>` with the canceled order. In case of an error, it returns an appropriate error encapsulated within `ApiResult`.
+
+    async fn cancel_order(&self, symbol: &str, order_id: &str) -> ApiResult<Value> {
+        let endpoint = "/openApi/swap/v2/trade/order";
+        let symbol = BingXApi::format_bingx_symbol(symbol, false);
+        let ts = generate_ts().to_string();
+
+        let query_str = QueryStr::new(vec![
+            ("symbol", symbol.as_str()),
+            ("orderId", order_id),
+            ("timestamp", ts.as_str()),
+        ])
+        .to_string();
+        let signature = self.sign_query_str(&query_str);
+        let query_str = format!("{}&signature={signature}", query_str);
+
+        let res = self.delete(endpoint, &query_str).await?;
+
+        self.handle_response(res).await
+    }
+
+    /// Cancels all open orders for a symbol on the exchange.
+    ///
+    /// # Arguments
+    ///
+    /// * `symbol` - The market symbol to cancel all open orders on.
+    ///
+    /// # Returns
+    ///
+    /// Returns an `ApiResult<Value>` with the exchange response. In case of an error, it returns an appropriate error encapsulated within `ApiResult`.
+
+    async fn cancel_all_orders(&self, symbol: &str) -> ApiResult<Value> {
+        let endpoint = "/openApi/swap/v2/trade/allOpenOrders";
+        let symbol = BingXApi::format_bingx_symbol(symbol, false);
+        let ts = generate_ts().to_string();
+
+        let query_str = QueryStr::new(vec![
+            ("symbol", symbol.as_str()),
+            ("timestamp", ts.as_str()),
+        ])
+        .to_string();
+        let signature = self.sign_query_str(&query_str);
+        let query_str = format!("{}&signature={signature}", query_str);
+
+        let res = self.delete(endpoint, &query_str).await?;
+
+        self.handle_response(res).await
+    }
+
     // ---
     // Exchange Methods
     // ---
@@ -465,12 +564,15 @@ impl ExchangeApi for BingXApi {
 /// - `kline_streams`: Similar to `ticker_streams`, but specifically for kline (candlestick data) streams, facilitating the tracking and management of multiple kline data feeds.
 /// - `market_sender`: A channel sender used to dispatch market data messages (e.g., new klines or tickers) to a designated receiver for further processing.
 /// - `stream_metas`: A thread-safe structure storing metadata for each stream, including details like the stream's symbol, type, and last update time.
+/// - `poll_config`: How often streams are polled and when failing streams give up.
 
 pub struct BingXStreamManager {
     ticker_streams: HashMap<String, JoinHandle<()>>,
     kline_streams: HashMap<String, JoinHandle<()>>,
-    market_sender: ArcSender<MarketMessage>,
+    market_sender: MarketSender,
     stream_metas: ArcMutex<HashMap<String, StreamMeta>>,
+    poll_config: PollConfig,
+    client: Client,
 }
 
 impl BingXStreamManager {
@@ -478,18 +580,85 @@ impl BingXStreamManager {
     ///
     /// # Arguments
     ///
-    /// * `market_sender`: An `ArcSender` for `MarketMessage` used to send market data updates.
+    /// * `market_sender`: A `MarketSender` used to send market data updates.
+    /// * `poll_config`: How often streams are polled and when failing streams give up.
+    /// * `client`: HTTP client the streams are polled with.
     ///
     /// # Returns
     ///
     /// Returns a new instance of `BingXStreamManager`, ready to manage streaming connections for both ticker and kline data from BingX.
 
-    pub fn new(market_sender: ArcSender<MarketMessage>) -> Self {
+    pub fn new(market_sender: MarketSender, poll_config: PollConfig, client: Client) -> Self {
         Self {
             ticker_streams: HashMap::new(),
             kline_streams: HashMap::new(),
             market_sender,
             stream_metas: ArcMutex::new(HashMap::new()),
+            poll_config,
+            client,
+        }
+    }
+}
+
+/// Polling of the REST endpoints BingX streams are served from.
+
+#[derive(Debug, Clone, Copy)]
+pub struct PollConfig {
+    /// Delay between polls while requests succeed.
+    pub interval: Duration,
+    /// Longest delay between polls, the delay doubles on each consecutive failure up to this.
+    pub max_backoff: Duration,
+    /// Consecutive failures after which the stream stops and is marked unhealthy.
+    pub max_failures: u32,
+}
+
+impl PollConfig {
+    /// Parses the poll config from settings, invalid or empty values use the defaults.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_failures` - The `BINGX_POLL_MAX_FAILURES` setting.
+    /// * `max_backoff_secs` - The `BINGX_POLL_MAX_BACKOFF_SECS` setting.
+    ///
+    /// # Returns
+    ///
+    /// The parsed `PollConfig`.
+
+    pub fn from_settings(max_failures: &str, max_backoff_secs: &str) -> Self {
+        Self {
+            max_failures: max_failures.parse().unwrap_or(DEFAULT_POLL_MAX_FAILURES),
+            max_backoff: Duration::from_secs(
+                max_backoff_secs
+                    .parse()
+                    .unwrap_or(DEFAULT_POLL_MAX_BACKOFF_SECS),
+            ),
+            ..Default::default()
+        }
+    }
+
+    /// Calculates the delay before the next poll.
+    ///
+    /// # Arguments
+    ///
+    /// * `failures` - Number of consecutive failed polls.
+    ///
+    /// # Returns
+    ///
+    /// The poll interval doubled for each failure, capped at `max_backoff`.
+
+    pub fn delay(&self, failures: u32) -> Duration {
+        self.interval
+            .saturating_mul(2u32.saturating_pow(failures))
+            .min(self.max_backoff)
+    }
+}
+
+impl Default for PollConfig {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_millis(DEFAULT_POLL_INTERVAL_MS),
+            max_backoff: Duration::from_secs(DEFAULT_POLL_MAX_BACKOFF_SECS),
+            max_failures: DEFAULT_POLL_MAX_FAILURES,
         }
     }
 }
@@ -525,43 +694,53 @@ impl StreamManager for BingXStreamManager {
         match stream_meta.stream_type {
             StreamType::Ticker => {
                 let market_sender = self.market_sender.clone();
-
-                let thread_handle = tokio::spawn(async move {
-                    loop {
-                        let ticker = get_bingx_ticker(&stream_meta.symbol).await;
-
-                        if let Ok(ticker) = ticker {
-                            let _ = market_sender.send(MarketMessage::UpdateTicker(ticker));
-                        } else {
-                            warn!("Unable to get ticker from BingX API");
+                let poll_config = self.poll_config;
+                let symbol = stream_meta.symbol.clone();
+                let client = self.client.clone();
+
+                let thread_handle = tokio::spawn(poll_stream(
+                    stream_meta.id.clone(),
+                    stream_metas,
+                    market_sender,
+                    poll_config,
+                    move || {
+                        let symbol = symbol.clone();
+                        let client = client.clone();
+                        async move {
+                            get_bingx_ticker(&client, &symbol)
+                                .await
+                                .map(|ticker| vec![MarketMessage::UpdateTicker(ticker)])
                         }
-
-                        tokio::time::sleep(Duration::from_secs(1)).await;
-                    }
-                });
+                    },
+                ));
 
                 self.ticker_streams
                     .insert(stream_meta.id.clone(), thread_handle);
             }
             StreamType::Kline => {
                 let market_sender = self.market_sender.clone();
-
-                let thread_handle = tokio::spawn(async move {
-                    loop {
-                        if let Some(interval) = stream_meta.interval {
-                            let kline = get_bingx_kline(&stream_meta.symbol, interval).await;
-
-                            if let Ok(kline) = kline {
-                                // let ticker = BingXApi::parse_ticker(&ticker_str);
-                                let _ = market_sender.send(MarketMessage::UpdateKline(kline));
-                            } else {
-                                warn!("Unable to get kline from BingX API");
-                            }
+                let poll_config = self.poll_config;
+                let symbol = stream_meta.symbol.clone();
+                let interval = stream_meta
+                    .interval
+                    .ok_or_else(|| format!("Kline stream {} has no interval", stream_meta.id))?;
+                let client = self.client.clone();
+
+                let thread_handle = tokio::spawn(poll_stream(
+                    stream_meta.id.clone(),
+                    stream_metas,
+                    market_sender,
+                    poll_config,
+                    move || {
+                        let symbol = symbol.clone();
+                        let client = client.clone();
+                        async move {
+                            get_bingx_kline(&client, &symbol, interval)
+                                .await
+                                .map(|kline| vec![MarketMessage::UpdateKline(kline)])
                         }
-
-                        tokio::time::sleep(Duration::from_secs(1)).await;
-                    }
-                });
+                    },
+                ));
 
                 self.kline_streams
                     .insert(stream_meta.id.clone(), thread_handle);
@@ -569,19 +748,44 @@ impl StreamManager for BingXStreamManager {
             StreamType::Trade => {
                 let market_sender = self.market_sender.clone();
 
-                let thread_handle = tokio::spawn(async move {
-                    loop {
-                        // TODO: Implement get market trade
-                        let trade = Trade::default();
-                        let _ = market_sender.send(MarketMessage::UpdateMarketTrade(trade));
-
-                        tokio::time::sleep(Duration::from_secs(1)).await;
-                    }
-                });
+                let poll_config = self.poll_config;
+                let symbol = stream_meta.symbol.clone();
+                let client = self.client.clone();
+                // trades already sent, only the new trades of each poll are sent
+                let cursor = ArcMutex::new(BingXTradeCursor::default());
+
+                let thread_handle = tokio::spawn(poll_stream(
+                    stream_meta.id.clone(),
+                    stream_metas,
+                    market_sender,
+                    poll_config,
+                    move || {
+                        let symbol = symbol.clone();
+                        let client = client.clone();
+                        let cursor = cursor.clone();
+                        async move {
+                            let mut cursor = cursor.lock().await;
+                            let trades = get_bingx_trades(&client, &symbol, &mut cursor).await?;
+
+                            Ok(trades
+                                .into_iter()
+                                .map(MarketMessage::UpdateMarketTrade)
+                                .collect())
+                        }
+                    },
+                ));
 
                 self.kline_streams
                     .insert(stream_meta.id.clone(), thread_handle);
             }
+            // BingX streams are polled over REST, which has no user data or depth endpoints
+            StreamType::UserData | StreamType::Depth => {
+                stream_metas.lock().await.remove(&stream_meta.id);
+                return Err(ApiError::Unsupported(format!(
+                    "{} streams are not supported by BingX",
+                    stream_meta.stream_type
+                )));
+            }
         };
 
         Ok(stream_meta.id.to_string())
@@ -632,12 +836,72 @@ impl StreamManager for BingXStreamManager {
     }
 }
 
+/// Polls the REST endpoint of a stream, sending each result to the market.
+///
+/// The delay between polls backs off exponentially on consecutive failures. The stream's
+/// `last_update` is set on each successful poll, after `max_failures` consecutive failures the
+/// stream is marked unhealthy and polling stops.
+///
+/// # Arguments
+///
+/// * `stream_id` - The ID of the polled stream.
+/// * `stream_metas` - Metadata of the streams, updated with the state of the stream.
+/// * `market_sender` - Sender the fetched market messages are sent to.
+/// * `poll_config` - How often the stream is polled and when it gives up.
+/// * `fetch` - Fetches the next market messages of the stream.
+
+async fn poll_stream<F, Fut>(
+    stream_id: String,
+    stream_metas: ArcMutex<HashMap<String, StreamMeta>>,
+    market_sender: MarketSender,
+    poll_config: PollConfig,
+    mut fetch: F,
+) where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = ApiResult<Vec<MarketMessage>>>,
+{
+    let mut failures = 0;
+
+    loop {
+        match fetch().await {
+            Ok(messages) => {
+                failures = 0;
+
+                if let Some(stream_meta) = stream_metas.lock().await.get_mut(&stream_id) {
+                    stream_meta.last_update = generate_ts();
+                }
+
+                for message in messages {
+                    let _ = market_sender.send(message).await;
+                }
+            }
+            Err(e) => {
+                failures += 1;
+
+                if failures >= poll_config.max_failures {
+                    error!("Stopping BingX stream {stream_id} after {failures} failed polls, {e}");
+
+                    if let Some(stream_meta) = stream_metas.lock().await.get_mut(&stream_id) {
+                        stream_meta.healthy = false;
+                    }
+                    return;
+                }
+
+                warn!("Unable to poll BingX stream {stream_id}, {failures} failed polls, {e}");
+            }
+        }
+
+        tokio::time::sleep(poll_config.delay(failures)).await;
+    }
+}
+
 /// Fetches the latest Kline data for a given symbol and interval from BingX's open API.
 ///
 /// This function adjusts the interval format to match BingX API requirements, constructs the query string, and sends a GET request to the BingX kline endpoint.
 ///
 /// # Arguments
 ///
+/// * `client` - The HTTP client the request is sent with.
 /// * `symbol` - A string slice representing the trading symbol (e.g., "BTC-USDT").
 /// * `interval` - A string slice representing the candlestick chart interval (e.g., "1min", "5min").
 ///
@@ -645,16 +909,19 @@ impl StreamManager for BingXStreamManager {
 ///
 /// Returns an `ApiResult<Kline>`, which is either the latest Kline data for the symbol and interval if successful, or an error message if the request fails or data is incomplete.
 
-pub async fn get_bingx_kline(symbol: &str, interval: Interval) -> ApiResult<Kline> {
-    let symbol = BingXApi::format_bingx_symbol(symbol, false);
+pub async fn get_bingx_kline(
+    client: &Client,
+    symbol: &str,
+    interval: Interval,
+) -> ApiResult<Kline> {
+    let bingx_symbol = BingXApi::format_bingx_symbol(symbol, false);
     // remove last two letters from interval if interval is {number}min
     // api accepts interval as {number}m
     let ts = generate_ts().to_string();
     let str_interval = interval.to_string();
 
-    let client = reqwest::Client::new();
     let query_str = QueryStr::new(vec![
-        ("symbol", &symbol),
+        ("symbol", &bingx_symbol),
         ("interval", &str_interval),
         ("timestamp", &ts),
         ("limit", "1"),
@@ -667,8 +934,11 @@ pub async fn get_bingx_kline(symbol: &str, interval: Interval) -> ApiResult<Klin
     );
 
     let res = client.get(url).send().await?;
+    let status = res.status();
+    let data = res.json::<Value>().await?;
+    check_status(status, &data)?;
 
-    let kline_str = res.json::<Value>().await?.to_string();
+    let kline_str = data.to_string();
 
     // build kline from hashmap
     let lookup: HashMap<String, Value> = serde_json::from_str(&kline_str).unwrap();
@@ -682,6 +952,8 @@ pub async fn get_bingx_kline(symbol: &str, interval: Interval) -> ApiResult<Klin
     let data = data[0].clone();
     let data: HashMap<String, Value> = serde_json::from_value(data.to_owned())?;
 
+    // klines are keyed by the canonical symbol, not the BingX symbol
+    let symbol = canonical_symbol(&bingx_symbol, SymbolFormat::BingX);
     let kline = Kline::from_bingx_lookup(data, &symbol, interval)?;
 
     Ok(kline)
@@ -693,14 +965,14 @@ pub async fn get_bingx_kline(symbol: &str, interval: Interval) -> ApiResult<Klin
 ///
 /// # Arguments
 ///
+/// * `client` - The HTTP client the request is sent with.
 /// * `symbol` - A string slice representing the trading symbol (e.g., "BTC-USDT").
 ///
 /// # Returns
 ///
 /// Returns an `ApiResult<Ticker>`, which is either the latest ticker data for the symbol if successful, or an error message if the request fails or data is incomplete.
 
-pub async fn get_bingx_ticker(symbol: &str) -> ApiResult<Ticker> {
-    let client = reqwest::Client::new();
+pub async fn get_bingx_ticker(client: &Client, symbol: &str) -> ApiResult<Ticker> {
     let ts = generate_ts().to_string();
     let symbol = BingXApi::format_bingx_symbol(symbol, false);
     let query_str = QueryStr::new(vec![("symbol", &symbol), ("timestamp", &ts)]);
@@ -711,8 +983,11 @@ pub async fn get_bingx_ticker(symbol: &str) -> ApiResult<Ticker> {
     );
 
     let res = client.get(url).send().await?;
+    let status = res.status();
+    let data = res.json::<Value>().await?;
+    check_status(status, &data)?;
 
-    let ticker_str = res.json::<Value>().await?.to_string();
+    let ticker_str = data.to_string();
 
     let lookup: HashMap<String, Value> = serde_json::from_str(&ticker_str).unwrap();
     let data = lookup.get("data").ok_or_else(|| {
@@ -722,7 +997,133 @@ pub async fn get_bingx_ticker(symbol: &str) -> ApiResult<Ticker> {
     let data: HashMap<String, Value> = serde_json::from_value(data.to_owned()).unwrap();
 
     // build kline from hashmap
-    let ticker = Ticker::from_bingx_lookup(data)?;
+    let mut ticker = Ticker::from_bingx_lookup(data)?;
+    ticker.symbol = canonical_symbol(&ticker.symbol, SymbolFormat::BingX);
 
     Ok(ticker)
 }
+
+/// Fetches the recent trades of a symbol from BingX's open API.
+///
+/// # Arguments
+///
+/// * `client` - The HTTP client the request is sent with.
+/// * `symbol` - The trading symbol, ie. "BTCUSDT".
+/// * `cursor` - The trades already received, only new trades are returned.
+///
+/// # Returns
+///
+/// Returns an `ApiResult<Vec<Trade>>` of the new trades in timestamp order, or an error if the
+/// request fails or the response can't be parsed.
+
+pub async fn get_bingx_trades(
+    client: &Client,
+    symbol: &str,
+    cursor: &mut BingXTradeCursor,
+) -> ApiResult<Vec<Trade>> {
+    let bingx_symbol = BingXApi::format_bingx_symbol(symbol, false);
+    let query_str = QueryStr::new(vec![("symbol", &bingx_symbol), ("limit", TRADES_LIMIT)]);
+    let url = format!(
+        "{}/openApi/swap/v2/quote/trades?{}",
+        BING_X_HOST_URL,
+        query_str.to_string()
+    );
+
+    let res = client.get(url).send().await?;
+    let status = res.status();
+    let lookup = res.json::<Value>().await?;
+    check_status(status, &lookup)?;
+
+    let data = lookup
+        .get("data")
+        .ok_or_else(|| "Missing 'data' key from data trades lookup".to_string())?;
+
+    let symbol = canonical_symbol(&bingx_symbol, SymbolFormat::BingX);
+    Trade::from_bingx_value(&symbol, data, cursor)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+    use std::time::Instant;
+
+    use tokio::test;
+
+    use crate::market::channel::{build_market_channel, DEFAULT_MARKET_CHANNEL_CAPACITY};
+
+    #[test]
+    async fn test_unsupported_streams_not_opened() {
+        let (market_tx, _market_rx) = build_market_channel(DEFAULT_MARKET_CHANNEL_CAPACITY);
+        let mut stream_manager =
+            BingXStreamManager::new(market_tx, PollConfig::default(), Client::new());
+
+        for stream_type in [StreamType::Depth, StreamType::UserData] {
+            let stream_meta = StreamMeta::new(
+                &format!("BTCUSDT@{stream_type}"),
+                BING_X_WS_HOST_URL,
+                "BTCUSDT",
+                stream_type,
+                None,
+            );
+
+            let result = stream_manager.open_stream(stream_meta).await;
+            assert!(matches!(result, Err(ApiError::Unsupported(_))));
+        }
+        assert!(stream_manager.active_streams().await.is_empty());
+    }
+
+    #[test]
+    async fn test_failing_poll_backs_off_and_marks_stream_unhealthy() {
+        let (market_tx, _market_rx) = build_market_channel(DEFAULT_MARKET_CHANNEL_CAPACITY);
+        let poll_config = PollConfig {
+            interval: Duration::from_millis(10),
+            max_backoff: Duration::from_millis(50),
+            max_failures: 4,
+        };
+
+        assert_eq!(poll_config.delay(0), Duration::from_millis(10));
+        assert_eq!(poll_config.delay(2), Duration::from_millis(40));
+        assert_eq!(poll_config.delay(3), Duration::from_millis(50));
+
+        let stream_meta = StreamMeta::new(
+            "BTCUSDT@ticker",
+            BING_X_WS_HOST_URL,
+            "BTCUSDT",
+            StreamType::Ticker,
+            None,
+        );
+        let last_update = stream_meta.last_update;
+        let stream_metas = ArcMutex::new(HashMap::from([(
+            stream_meta.id.clone(),
+            stream_meta.clone(),
+        )]));
+
+        let polls = Arc::new(Mutex::new(vec![]));
+        let fetch_polls = polls.clone();
+
+        poll_stream(
+            stream_meta.id.clone(),
+            stream_metas.clone(),
+            market_tx,
+            poll_config,
+            move || {
+                fetch_polls.lock().unwrap().push(Instant::now());
+                async { Err(ApiError::Network("BingX unavailable".to_string())) }
+            },
+        )
+        .await;
+
+        // polling stops after max failures, each delay longer than the last
+        let polls = polls.lock().unwrap();
+        assert_eq!(polls.len(), 4);
+        for (i, gap) in polls.windows(2).map(|w| w[1] - w[0]).enumerate() {
+            assert!(gap >= poll_config.delay(i as u32 + 1));
+        }
+
+        let stream_meta = stream_metas.lock().await[&stream_meta.id].clone();
+        assert!(!stream_meta.healthy);
+        assert_eq!(stream_meta.last_update, last_update);
+        assert!(stream_meta.is_stale(generate_ts(), 100));
+    }
+}
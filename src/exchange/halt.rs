@@ -0,0 +1,180 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use log::{info, warn};
+use serde::Serialize;
+
+use crate::{
+    exchange::{
+        api::{ExchangeApi, SymbolStatus},
+        types::ApiError,
+    },
+    utils::time::SEC_AS_MILI,
+};
+
+/// Seconds between refreshes of the trading status of symbols, and before a symbol halted by an
+/// error code is retried.
+pub const SYMBOL_STATUS_RECHECK_SECS: u64 = 30;
+
+/// A symbol whose trading is halted by the exchange.
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct TradingHalt {
+    /// Status reported by the exchange, ie. `BREAK`.
+    pub status: String,
+    /// Time the halt was first seen in milliseconds.
+    pub since: u64,
+}
+
+/// Last known status of a symbol.
+#[derive(Debug, Default)]
+struct SymbolState {
+    halt: Option<TradingHalt>,
+    /// Whether the halt was reported by an error code rather than the status of the symbol, the
+    /// symbol is retried once it's due a recheck as its status may not be available.
+    from_error: bool,
+}
+
+/// Trading status of symbols on an exchange, used to pause orders and streams of symbols halted
+/// for maintenance rather than have the exchange reject every attempt.
+///
+/// Checks only read the last known status, the status of every checked symbol is fetched with
+/// its symbol info by `refresh`, run by the bot every `SYMBOL_STATUS_RECHECK_SECS`, so halted
+/// symbols resume once the exchange reports them as trading. Exchanges without symbol info, ie. BingX, halt symbols
+/// by the error codes of their orders. Clones share the same statuses.
+
+#[derive(Debug, Clone, Default)]
+pub struct TradingHalts {
+    symbols: Arc<Mutex<HashMap<String, SymbolState>>>,
+    /// Set once the exchange doesn't support symbol info, statuses aren't fetched again.
+    status_unsupported: Arc<AtomicBool>,
+}
+
+impl TradingHalts {
+    /// Checks a symbol can be traded by its last known status, the symbol's status is refreshed
+    /// from then on. Unknown symbols are assumed to be trading.
+    ///
+    /// # Arguments
+    ///
+    /// * `symbol` - The symbol to check.
+    /// * `now` - The current time in milliseconds.
+    ///
+    /// # Returns
+    ///
+    /// `Ok(())` if the symbol is trading, otherwise an error message with the halted status.
+
+    pub fn check(&self, symbol: &str, now: u64) -> Result<(), String> {
+        let mut symbols = self.symbols.lock().unwrap();
+        let state = symbols.entry(symbol.to_string()).or_default();
+
+        // halts seen in errors lift once due a recheck, the next attempt tells if they still hold
+        if state.from_error
+            && state.halt.as_ref().is_some_and(|halt| {
+                now.saturating_sub(halt.since) >= SYMBOL_STATUS_RECHECK_SECS * SEC_AS_MILI
+            })
+        {
+            info!("Retrying trading on {symbol} halted by the exchange");
+            state.halt = None;
+            state.from_error = false;
+        }
+
+        match &state.halt {
+            Some(halt) => Err(format!(
+                "trading on {symbol} is halted by the exchange, status {}",
+                halt.status
+            )),
+            None => Ok(()),
+        }
+    }
+
+    /// Records a symbol as halted by an error the exchange answered a request with.
+    ///
+    /// # Arguments
+    ///
+    /// * `symbol` - The halted symbol.
+    /// * `error` - The error of the exchange, only `ApiError::Halted` halts the symbol.
+    /// * `now` - The current time in milliseconds.
+
+    pub fn record_error(&self, symbol: &str, error: &ApiError, now: u64) {
+        let ApiError::Halted(msg) = error else {
+            return;
+        };
+
+        let mut symbols = self.symbols.lock().unwrap();
+        let state = symbols.entry(symbol.to_string()).or_default();
+
+        if state.halt.is_none() {
+            warn!("Trading on {symbol} halted by the exchange, {msg}");
+            state.halt = Some(TradingHalt {
+                status: "MAINTENANCE".to_string(),
+                since: now,
+            });
+            state.from_error = true;
+        }
+    }
+
+    /// Fetches the status of every checked symbol, halting and resuming symbols by the status the
+    /// exchange reports. Symbols whose status can't be fetched keep their last known status.
+    ///
+    /// # Arguments
+    ///
+    /// * `exchange_api` - Exchange the statuses are fetched from.
+    /// * `now` - The current time in milliseconds.
+
+    pub async fn refresh(&self, exchange_api: &dyn ExchangeApi, now: u64) {
+        if self.status_unsupported.load(Ordering::Relaxed) {
+            return;
+        }
+
+        // the statuses aren't locked while they are fetched
+        let symbols: Vec<String> = self.symbols.lock().unwrap().keys().cloned().collect();
+
+        for symbol in symbols {
+            let status = match exchange_api.get_symbol_info(&symbol).await {
+                Ok(info) => info.status,
+                Err(ApiError::Unsupported(_)) => {
+                    info!("Symbol statuses not supported by the exchange, halts are detected by error codes");
+                    self.status_unsupported.store(true, Ordering::Relaxed);
+                    return;
+                }
+                Err(e) => {
+                    warn!("Unable to refresh the trading status of {symbol}, {e}");
+                    continue;
+                }
+            };
+
+            let mut symbols = self.symbols.lock().unwrap();
+            let state = symbols.entry(symbol.clone()).or_default();
+
+            match status {
+                SymbolStatus::Halted(status) if state.halt.is_none() => {
+                    warn!("Trading on {symbol} halted by the exchange, status {status}");
+                    state.halt = Some(TradingHalt { status, since: now });
+                    state.from_error = false;
+                }
+                SymbolStatus::Trading if state.halt.is_some() => {
+                    info!("Trading on {symbol} resumed");
+                    state.halt = None;
+                    state.from_error = false;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Symbols last seen halted, by symbol.
+
+    pub fn halted(&self) -> BTreeMap<String, TradingHalt> {
+        self.symbols
+            .lock()
+            .unwrap()
+            .iter()
+            .filter_map(|(symbol, state)| Some((symbol.clone(), state.halt.clone()?)))
+            .collect()
+    }
+}
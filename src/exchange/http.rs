@@ -0,0 +1,162 @@
+use std::time::Duration;
+
+use reqwest::{Client, StatusCode};
+use serde_json::Value;
+
+use crate::exchange::types::{ApiError, ApiResult};
+
+/// Time allowed for a whole exchange request by default, including reading the response.
+pub const DEFAULT_REQUEST_TIMEOUT_SECS: u64 = 10;
+
+/// Time allowed to connect to an exchange by default.
+pub const DEFAULT_CONNECT_TIMEOUT_SECS: u64 = 5;
+
+/// Number of idle connections kept open per exchange host by default.
+pub const DEFAULT_POOL_SIZE: usize = 10;
+
+/// Settings of the HTTP client used to make exchange requests.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HttpClientConfig {
+    pub request_timeout: Duration,
+    pub connect_timeout: Duration,
+    pub pool_size: usize,
+}
+
+impl HttpClientConfig {
+    /// Parses the HTTP client settings, invalid values use the defaults.
+    ///
+    /// # Arguments
+    ///
+    /// * `request_timeout_secs` - The `HTTP_REQUEST_TIMEOUT_SECS` setting.
+    /// * `connect_timeout_secs` - The `HTTP_CONNECT_TIMEOUT_SECS` setting.
+    /// * `pool_size` - The `HTTP_POOL_SIZE` setting.
+    ///
+    /// # Returns
+    ///
+    /// The parsed `HttpClientConfig`.
+
+    pub fn from_settings(
+        request_timeout_secs: &str,
+        connect_timeout_secs: &str,
+        pool_size: &str,
+    ) -> Self {
+        Self {
+            request_timeout: Duration::from_secs(
+                request_timeout_secs
+                    .parse()
+                    .unwrap_or(DEFAULT_REQUEST_TIMEOUT_SECS),
+            ),
+            connect_timeout: Duration::from_secs(
+                connect_timeout_secs
+                    .parse()
+                    .unwrap_or(DEFAULT_CONNECT_TIMEOUT_SECS),
+            ),
+            pool_size: pool_size.parse().unwrap_or(DEFAULT_POOL_SIZE),
+        }
+    }
+
+    /// Builds an HTTP client with the configured timeouts and connection pool.
+    ///
+    /// # Returns
+    ///
+    /// A `Client` whose requests fail instead of hanging once the timeouts are reached.
+
+    pub fn build_client(&self) -> Client {
+        Client::builder()
+            .timeout(self.request_timeout)
+            .connect_timeout(self.connect_timeout)
+            .pool_max_idle_per_host(self.pool_size)
+            .build()
+            .expect("Unable to build HTTP client")
+    }
+}
+
+/// Error codes exchanges answer with while trading is halted, ie. for maintenance.
+///
+/// * `-1122` - Binance, the symbol isn't trading.
+/// * `-4140` - Binance, the symbol doesn't accept new positions.
+/// * `80012` - BingX, the service is unavailable during maintenance.
+pub const HALTED_ERROR_CODES: [i64; 3] = [-1122, -4140, 80012];
+
+/// Checks the status an exchange answered a request with.
+///
+/// Error codes of halted trading are mapped to `ApiError::Halted`, whatever the status as some
+/// exchanges answer errors with a success status. Rate limits and server errors are mapped to
+/// `ApiError::Network` so the request is retried, other error statuses to `ApiError::Rejected`.
+///
+/// # Arguments
+///
+/// * `status` - Status of the response.
+/// * `body` - Body of the response, included in the error.
+///
+/// # Returns
+///
+/// `Ok` if the status is a success, the error of the status otherwise.
+
+pub fn check_status(status: StatusCode, body: &Value) -> ApiResult<()> {
+    let code = body.get("code").and_then(|code| code.as_i64());
+    if code.is_some_and(|code| HALTED_ERROR_CODES.contains(&code)) {
+        return Err(ApiError::Halted(format!(
+            "Trading halted by the exchange, status {status}: {body}"
+        )));
+    }
+
+    if status.is_success() {
+        return Ok(());
+    }
+
+    let message = format!("Request failed with status {status}: {body}");
+
+    if status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error() {
+        Err(ApiError::Network(message))
+    } else {
+        Err(ApiError::Rejected(message))
+    }
+}
+
+impl Default for HttpClientConfig {
+    fn default() -> Self {
+        Self {
+            request_timeout: Duration::from_secs(DEFAULT_REQUEST_TIMEOUT_SECS),
+            connect_timeout: Duration::from_secs(DEFAULT_CONNECT_TIMEOUT_SECS),
+            pool_size: DEFAULT_POOL_SIZE,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use tokio::test;
+
+    #[test]
+    async fn test_check_status() {
+        let body = json!({"code": -1003, "msg": "Too many requests"});
+
+        assert!(check_status(StatusCode::OK, &body).is_ok());
+        assert!(check_status(StatusCode::TOO_MANY_REQUESTS, &body)
+            .unwrap_err()
+            .is_transient());
+        assert!(check_status(StatusCode::SERVICE_UNAVAILABLE, &body)
+            .unwrap_err()
+            .is_transient());
+        assert!(matches!(
+            check_status(StatusCode::UNAUTHORIZED, &body),
+            Err(ApiError::Rejected(_))
+        ));
+
+        // maintenance codes halt trading, also when answered with a success status
+        let body = json!({"code": 80012, "msg": "Service unavailable"});
+        assert!(matches!(
+            check_status(StatusCode::OK, &body),
+            Err(ApiError::Halted(_))
+        ));
+        let body = json!({"code": -4140, "msg": "Invalid symbol status for opening position"});
+        assert!(matches!(
+            check_status(StatusCode::BAD_REQUEST, &body),
+            Err(ApiError::Halted(_))
+        ));
+    }
+}
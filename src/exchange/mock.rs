@@ -1,49 +1,298 @@
-use crate::account::trade::{OrderSide, Position, TradeTx};
+use crate::account::trade::{ContractType, OrderSide, Position, TradeTx};
 use crate::exchange::api::ExchangeApi;
-use crate::exchange::stream::StreamManager;
-use crate::exchange::types::{ApiResult, StreamType};
+use crate::exchange::stream::{build_stream_id, StreamManager, StreamMeta};
+use crate::exchange::types::{ApiError, ApiResult, StreamType};
 use crate::market::interval::Interval;
 use crate::market::kline::Kline;
+use crate::market::orderbook::{DepthLevel, DepthSnapshot};
 use crate::market::ticker::Ticker;
+use crate::market::trade::{AggTrade, Trade};
 use crate::market::types::ArcMutex;
-use crate::utils::time::generate_ts;
+use crate::utils::time::{floor_mili_ts, generate_ts};
 use async_trait::async_trait;
-use serde_json::Value;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::ops::Range;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::time;
 
-use super::api::ExchangeInfo;
+use super::api::{
+    ExchangeInfo, ExchangePosition, SymbolInfo, SymbolStatus, AGG_TRADES_PAGE_LIMIT,
+    DEFAULT_MAX_LEVERAGE, DEFAULT_MIN_LEVERAGE,
+};
 
-pub struct MockExchangeApi {}
+/// Symbols listed on the mock exchange.
+pub const MOCK_SYMBOLS: [&str; 3] = ["BTCUSDT", "ETHUSDT", "SOLUSDT"];
+
+pub struct MockExchangeApi {
+    /// Number of upcoming `open_position` calls which are rejected, used to simulate exchange failures.
+    open_failures: AtomicUsize,
+    /// Number of upcoming `open_position` calls refused with an error which isn't transient.
+    open_rejections: AtomicUsize,
+    /// Client order IDs of every `open_position` call, including failed calls.
+    client_order_ids: Mutex<Vec<Option<String>>>,
+    /// Exchange order ID of the next position opened.
+    next_order_id: AtomicU64,
+    /// Number of `get_klines` calls currently in progress.
+    kline_requests: AtomicUsize,
+    /// Highest number of `get_klines` calls in progress at the same time.
+    max_kline_requests: AtomicUsize,
+    /// Number of `get_depth_snapshot` calls made.
+    depth_snapshot_requests: AtomicUsize,
+    /// Number of upcoming `get_ticker` calls which fail with a network error.
+    ticker_failures: AtomicUsize,
+    /// Number of `get_ticker` calls made.
+    ticker_requests: AtomicUsize,
+    /// Number of `get_last_prices` calls made.
+    last_prices_requests: AtomicUsize,
+    /// Number of `get_kline` calls made.
+    last_kline_requests: AtomicUsize,
+    /// Number of `get_recent_trades` and `get_historical_trades` calls made.
+    trade_requests: AtomicUsize,
+    /// Number of `get_symbol_info` calls made.
+    symbol_info_requests: AtomicUsize,
+    /// Error returned by every `get_symbol_info` call, if set.
+    symbol_info_error: Mutex<Option<ApiError>>,
+    /// Error returned by every `get_kline` and `get_klines` call, if set.
+    kline_error: Mutex<Option<ApiError>>,
+    /// Positions reported as held on the exchange.
+    positions: Mutex<Vec<ExchangePosition>>,
+    /// Trading status of symbols, symbols without a status are trading.
+    symbol_status: Mutex<HashMap<String, SymbolStatus>>,
+    /// Contract type of symbols, symbols without a contract type are linear.
+    contract_types: Mutex<HashMap<String, ContractType>>,
+    /// Price positions are filled at, positions are filled at their open price when not set.
+    fill_price: Mutex<Option<f64>>,
+    stream_manager: ArcMutex<Box<dyn StreamManager>>,
+}
+
+impl MockExchangeApi {
+    /// Creates a mock exchange which rejects the next `failures` attempts to open a position.
+    ///
+    /// # Arguments
+    ///
+    /// * `failures` - Number of `open_position` calls to reject before succeeding.
+    ///
+    /// # Returns
+    ///
+    /// A new `MockExchangeApi` instance.
+
+    pub fn with_open_failures(failures: usize) -> Self {
+        Self {
+            open_failures: AtomicUsize::new(failures),
+            ..Default::default()
+        }
+    }
+
+    /// Creates a mock exchange which refuses the next `rejections` attempts to open a position with
+    /// an error which isn't transient, ie. an order rejected for its parameters.
+    ///
+    /// # Arguments
+    ///
+    /// * `rejections` - Number of `open_position` calls to refuse before succeeding.
+    ///
+    /// # Returns
+    ///
+    /// A new `MockExchangeApi` instance.
+
+    pub fn with_open_rejections(rejections: usize) -> Self {
+        Self {
+            open_rejections: AtomicUsize::new(rejections),
+            ..Default::default()
+        }
+    }
+
+    /// Returns the client order IDs of every attempt to open a position, in order.
+
+    pub fn client_order_ids(&self) -> Vec<Option<String>> {
+        self.client_order_ids.lock().unwrap().clone()
+    }
+
+    /// Creates a mock exchange whose next `failures` ticker requests fail with a network error.
+    ///
+    /// # Arguments
+    ///
+    /// * `failures` - Number of `get_ticker` calls to fail before succeeding.
+    ///
+    /// # Returns
+    ///
+    /// A new `MockExchangeApi` instance.
+
+    pub fn with_ticker_failures(failures: usize) -> Self {
+        Self {
+            ticker_failures: AtomicUsize::new(failures),
+            ..Default::default()
+        }
+    }
+
+    /// Creates a mock exchange whose symbol info requests all fail with an error, ie. a network
+    /// error or an exchange which doesn't provide symbol info.
+    ///
+    /// # Arguments
+    ///
+    /// * `error` - The error returned by `get_symbol_info`.
+    ///
+    /// # Returns
+    ///
+    /// A new `MockExchangeApi` instance.
+
+    pub fn with_symbol_info_error(error: ApiError) -> Self {
+        Self {
+            symbol_info_error: Mutex::new(Some(error)),
+            ..Default::default()
+        }
+    }
+
+    /// Creates a mock exchange whose kline requests all fail with an error.
+    ///
+    /// # Arguments
+    ///
+    /// * `error` - The error returned by `get_kline` and `get_klines`.
+    ///
+    /// # Returns
+    ///
+    /// A new `MockExchangeApi` instance.
+
+    pub fn with_kline_error(error: ApiError) -> Self {
+        Self {
+            kline_error: Mutex::new(Some(error)),
+            ..Default::default()
+        }
+    }
+
+    /// Returns the number of ticker requests made, including failed requests.
+
+    pub fn ticker_requests(&self) -> usize {
+        self.ticker_requests.load(Ordering::SeqCst)
+    }
+
+    /// Returns the number of batched last price requests made.
+
+    pub fn last_prices_requests(&self) -> usize {
+        self.last_prices_requests.load(Ordering::SeqCst)
+    }
+
+    /// Returns the number of single kline requests made.
+
+    pub fn last_kline_requests(&self) -> usize {
+        self.last_kline_requests.load(Ordering::SeqCst)
+    }
+
+    /// Returns the number of recent and historical trade pages requested.
+
+    pub fn trade_requests(&self) -> usize {
+        self.trade_requests.load(Ordering::SeqCst)
+    }
+
+    /// Returns the number of symbol info requests made, including unknown symbols.
+
+    pub fn symbol_info_requests(&self) -> usize {
+        self.symbol_info_requests.load(Ordering::SeqCst)
+    }
+
+    /// Returns the highest number of `get_klines` calls which were in progress at the same time.
+
+    pub fn max_concurrent_kline_requests(&self) -> usize {
+        self.max_kline_requests.load(Ordering::SeqCst)
+    }
+
+    /// Returns the number of depth snapshots requested.
+
+    pub fn depth_snapshot_requests(&self) -> usize {
+        self.depth_snapshot_requests.load(Ordering::SeqCst)
+    }
+
+    /// Sets the positions reported as held on the exchange, ie. positions opened manually.
+
+    pub fn set_positions(&self, positions: Vec<ExchangePosition>) {
+        *self.positions.lock().unwrap() = positions;
+    }
+
+    /// Sets the trading status of a symbol reported with its symbol info.
+
+    pub fn set_symbol_status(&self, symbol: &str, status: SymbolStatus) {
+        self.symbol_status
+            .lock()
+            .unwrap()
+            .insert(symbol.to_string(), status);
+    }
+
+    /// Sets the contract type of a symbol reported with its symbol info.
+
+    pub fn set_contract_type(&self, symbol: &str, contract_type: ContractType) {
+        self.contract_types
+            .lock()
+            .unwrap()
+            .insert(symbol.to_string(), contract_type);
+    }
+
+    /// Sets the price the next positions are filled at, ie. to simulate slippage.
+
+    pub fn set_fill_price(&self, fill_price: Option<f64>) {
+        *self.fill_price.lock().unwrap() = fill_price;
+    }
+}
 
 #[async_trait]
 impl ExchangeApi for MockExchangeApi {
     /// Simulates opening a position on the exchange for testing purposes.
     ///
-    /// This function mimics the behavior of opening a position based on the provided parameters. It's
-    /// used for testing scenarios without interacting with a real exchange. It constructs and returns
-    /// a mock position object.
+    /// This function mimics the behavior of opening a position. It's used for testing scenarios
+    /// without interacting with a real exchange, the position is filled as requested.
     ///
     /// # Arguments
     ///
-    /// * `symbol` - A string slice representing the trading pair or market symbol.
-    /// * `margin_usd` - A floating-point number representing the amount of margin used for the position in USD.
-    /// * `leverage` - An unsigned 32-bit integer representing the leverage applied to the position.
-    /// * `order_side` - An `OrderSide` enum value indicating whether the position is a buy or sell.
-    /// * `open_price` - A floating-point number representing the price at which the position is opened.
+    /// * `position` - The position to open.
+    /// * `client_order_id` - Optional client order ID, recorded for tests.
     ///
     /// # Returns
     ///
     /// Returns an `ApiResult<Position>`, which is a custom result type. On success, it contains the
-    /// mock `Position` object. On failure, it contains an error.
+    /// position with a mock order ID. On failure, it contains an error.
 
     async fn open_position(
         &self,
-        symbol: &str,
-        margin_usd: f64,
-        leverage: u32,
-        order_side: OrderSide,
-        open_price: f64,
+        mut position: Position,
+        client_order_id: Option<&str>,
     ) -> ApiResult<Position> {
-        let position = Position::new(symbol, open_price, order_side, margin_usd, leverage, None);
+        self.client_order_ids
+            .lock()
+            .unwrap()
+            .push(client_order_id.map(|id| id.to_string()));
+
+        let refused = self
+            .open_rejections
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |rejections| {
+                rejections.checked_sub(1)
+            })
+            .is_ok();
+
+        if refused {
+            return Err(ApiError::Parsing(
+                "Mock exchange refused order parameters".to_string(),
+            ));
+        }
+
+        let rejected = self
+            .open_failures
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |failures| {
+                failures.checked_sub(1)
+            })
+            .is_ok();
+
+        if rejected {
+            return Err(ApiError::Network(
+                "Mock exchange rejected order".to_string(),
+            ));
+        }
+
+        if let Some(fill_price) = *self.fill_price.lock().unwrap() {
+            position.open_price = fill_price;
+        }
+
+        position.order_id = Some(self.next_order_id.fetch_add(1, Ordering::SeqCst));
         Ok(position)
     }
 
@@ -92,6 +341,114 @@ impl ExchangeApi for MockExchangeApi {
         })
     }
 
+    /// Simulates fetching symbol info, only symbols in `MOCK_SYMBOLS` are listed.
+
+    async fn get_symbol_info(&self, symbol: &str) -> ApiResult<SymbolInfo> {
+        self.symbol_info_requests.fetch_add(1, Ordering::SeqCst);
+
+        if let Some(e) = self.symbol_info_error.lock().unwrap().clone() {
+            return Err(e);
+        }
+
+        if !MOCK_SYMBOLS.contains(&symbol) {
+            return Err(ApiError::SymbolNotFound(format!("Unknown symbol {symbol}")));
+        }
+
+        Ok(SymbolInfo {
+            symbol: symbol.to_string(),
+            tick_size: 0.01,
+            step_size: 0.001,
+            min_leverage: DEFAULT_MIN_LEVERAGE,
+            max_leverage: DEFAULT_MAX_LEVERAGE,
+            contract_type: self
+                .contract_types
+                .lock()
+                .unwrap()
+                .get(symbol)
+                .copied()
+                .unwrap_or_default(),
+            min_notional: MOCK_MIN_NOTIONAL,
+            status: self
+                .symbol_status
+                .lock()
+                .unwrap()
+                .get(symbol)
+                .cloned()
+                .unwrap_or_default(),
+        })
+    }
+
+    /// Returns a two level book around `MOCK_PRICE`, the last update ID is `100` times the number
+    /// of snapshots requested so each snapshot is newer than the last.
+
+    async fn get_depth_snapshot(&self, symbol: &str, _limit: usize) -> ApiResult<DepthSnapshot> {
+        let requests = self.depth_snapshot_requests.fetch_add(1, Ordering::SeqCst) + 1;
+
+        Ok(DepthSnapshot {
+            symbol: symbol.to_string(),
+            last_update_id: requests as u64 * 100,
+            bids: vec![
+                DepthLevel {
+                    price: MOCK_PRICE - 1.0,
+                    qty: 1.0,
+                },
+                DepthLevel {
+                    price: MOCK_PRICE - 2.0,
+                    qty: 2.0,
+                },
+            ],
+            asks: vec![
+                DepthLevel {
+                    price: MOCK_PRICE + 1.0,
+                    qty: 1.0,
+                },
+                DepthLevel {
+                    price: MOCK_PRICE + 2.0,
+                    qty: 2.0,
+                },
+            ],
+        })
+    }
+
+    /// Returns the latest trades of the mock trade history.
+
+    async fn get_recent_trades(&self, symbol: &str, limit: usize) -> ApiResult<Vec<AggTrade>> {
+        self.trade_requests.fetch_add(1, Ordering::SeqCst);
+
+        let limit = limit.min(AGG_TRADES_PAGE_LIMIT) as u64;
+        Ok(mock_agg_trades(
+            symbol,
+            MOCK_AGG_TRADES.saturating_sub(limit)..MOCK_AGG_TRADES,
+        ))
+    }
+
+    /// Returns a page of the mock trade history starting at `from_id`.
+
+    async fn get_historical_trades(&self, symbol: &str, from_id: u64) -> ApiResult<Vec<AggTrade>> {
+        self.trade_requests.fetch_add(1, Ordering::SeqCst);
+
+        let to_id = (from_id + AGG_TRADES_PAGE_LIMIT as u64).min(MOCK_AGG_TRADES);
+        Ok(mock_agg_trades(symbol, from_id..to_id))
+    }
+
+    /// Returns the positions set with `set_positions`, none by default.
+
+    async fn get_positions(&self) -> ApiResult<Vec<ExchangePosition>> {
+        Ok(self.positions.lock().unwrap().clone())
+    }
+
+    /// Simulates canceling an order, the mock exchange never has resting orders.
+
+    async fn cancel_order(&self, _symbol: &str, _order_id: &str) -> ApiResult<Value> {
+        Ok(json!([]))
+    }
+
+    /// Simulates canceling all orders for a symbol, the mock exchange never has resting orders.
+
+    async fn cancel_all_orders(&self, _symbol: &str) -> ApiResult<Value> {
+        Ok(json!([]))
+    }
+
     // ---
     // All Other methods not used on this mock MockExchangeApi
     // Will fail if called
@@ -109,28 +466,223 @@ impl ExchangeApi for MockExchangeApi {
         unimplemented!()
     }
     fn get_stream_manager(&self) -> ArcMutex<Box<dyn StreamManager>> {
-        unimplemented!()
+        self.stream_manager.clone()
     }
-    async fn get_kline(&self, _symbol: &str, _interval: Interval) -> ApiResult<Kline> {
-        unimplemented!()
+
+    /// Returns a flat kline for the current interval.
+
+    async fn get_kline(&self, symbol: &str, interval: Interval) -> ApiResult<Kline> {
+        self.last_kline_requests.fetch_add(1, Ordering::SeqCst);
+        if let Some(e) = self.kline_error.lock().unwrap().clone() {
+            return Err(e);
+        }
+        let open_time = floor_mili_ts(generate_ts(), interval.to_mili());
+
+        Ok(Kline {
+            symbol: symbol.to_string(),
+            interval,
+            open: MOCK_PRICE,
+            high: MOCK_PRICE,
+            low: MOCK_PRICE,
+            close: MOCK_PRICE,
+            volume: 0.0,
+            quote_volume: 0.0,
+            open_time,
+            close_time: open_time + interval.to_mili() - 1,
+        })
     }
-    async fn get_ticker(&self, _symbol: &str) -> ApiResult<Ticker> {
-        unimplemented!()
+
+    /// Returns flat klines for every interval open time in the range, each request takes
+    /// `MOCK_REQUEST_DELAY` so concurrent requests overlap.
+
+    async fn get_klines(
+        &self,
+        symbol: &str,
+        interval: Interval,
+        from_ts: u64,
+        to_ts: u64,
+    ) -> ApiResult<Vec<Kline>> {
+        if let Some(e) = self.kline_error.lock().unwrap().clone() {
+            return Err(e);
+        }
+
+        let in_flight = self.kline_requests.fetch_add(1, Ordering::SeqCst) + 1;
+        self.max_kline_requests
+            .fetch_max(in_flight, Ordering::SeqCst);
+
+        time::sleep(MOCK_REQUEST_DELAY).await;
+
+        let step = interval.to_mili();
+        let mut open_time = floor_mili_ts(from_ts, step);
+        if open_time < from_ts {
+            open_time += step;
+        }
+
+        let mut klines = vec![];
+        while open_time <= to_ts {
+            klines.push(Kline {
+                symbol: symbol.to_string(),
+                interval,
+                open: MOCK_PRICE,
+                high: MOCK_PRICE,
+                low: MOCK_PRICE,
+                close: MOCK_PRICE,
+                volume: 0.0,
+                quote_volume: 0.0,
+                open_time,
+                close_time: open_time + step - 1,
+            });
+            open_time += step;
+        }
+
+        self.kline_requests.fetch_sub(1, Ordering::SeqCst);
+
+        Ok(klines)
+    }
+
+    /// Returns a flat ticker at the current time, unless a failure is pending.
+
+    async fn get_ticker(&self, symbol: &str) -> ApiResult<Ticker> {
+        self.ticker_requests.fetch_add(1, Ordering::SeqCst);
+
+        let failed = self
+            .ticker_failures
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |failures| {
+                failures.checked_sub(1)
+            })
+            .is_ok();
+
+        if failed {
+            return Err(ApiError::Network(
+                "Mock exchange request failed".to_string(),
+            ));
+        }
+
+        Ok(Ticker {
+            time: generate_ts(),
+            symbol: symbol.to_string(),
+            high: MOCK_PRICE,
+            low: MOCK_PRICE,
+            traded_vol: 0.0,
+            last_price: MOCK_PRICE,
+            open_price: MOCK_PRICE,
+        })
+    }
+
+    /// Returns the flat price for every symbol with a single request.
+
+    async fn get_last_prices(&self, symbols: &[String]) -> ApiResult<HashMap<String, f64>> {
+        self.last_prices_requests.fetch_add(1, Ordering::SeqCst);
+
+        Ok(symbols
+            .iter()
+            .map(|symbol| (symbol.clone(), MOCK_PRICE))
+            .collect())
     }
 
     fn build_stream_url(
         &self,
-        _symbol: &str,
-        _stream_type: StreamType,
-        _interval: Option<Interval>,
+        symbol: &str,
+        stream_type: StreamType,
+        interval: Option<Interval>,
     ) -> String {
-        todo!()
+        format!("mock://{}", build_stream_id(symbol, stream_type, interval))
+    }
+}
+
+/// Minimum notional of orders of symbols listed by `MockExchangeApi`.
+pub const MOCK_MIN_NOTIONAL: f64 = 5.0;
+
+/// Price returned for all market data by `MockExchangeApi`.
+pub const MOCK_PRICE: f64 = 100.0;
+
+/// Number of aggregate trades in the trade history of each symbol of the mock exchange.
+pub const MOCK_AGG_TRADES: u64 = 2_500;
+
+/// Timestamp of the first trade of the mock trade history, trades are `100` ms apart.
+pub const MOCK_TRADES_START: u64 = 1_700_000_000_000;
+
+/// Builds the trades of the mock trade history with ids in a range, sides alternate.
+fn mock_agg_trades(symbol: &str, ids: Range<u64>) -> Vec<AggTrade> {
+    ids.map(|id| AggTrade {
+        id,
+        trade: Trade {
+            symbol: symbol.to_string(),
+            timestamp: MOCK_TRADES_START + id * 100,
+            qty: 1.0,
+            price: MOCK_PRICE,
+            order_side: if id % 2 == 0 {
+                OrderSide::Buy
+            } else {
+                OrderSide::Sell
+            },
+            id: Some(id),
+        },
+    })
+    .collect()
+}
+
+/// Time taken by requests to the mock exchange which simulate network latency.
+const MOCK_REQUEST_DELAY: Duration = Duration::from_millis(50);
+
+/// Stream manager used by `MockExchangeApi`, keeps track of opened streams without connecting.
+
+pub struct MockStreamManager {
+    stream_metas: ArcMutex<HashMap<String, StreamMeta>>,
+}
+
+impl Default for MockStreamManager {
+    fn default() -> Self {
+        Self {
+            stream_metas: ArcMutex::new(HashMap::new()),
+        }
+    }
+}
+
+#[async_trait]
+impl StreamManager for MockStreamManager {
+    async fn open_stream(&mut self, stream_meta: StreamMeta) -> ApiResult<String> {
+        let stream_id = stream_meta.id.clone();
+        self.stream_metas
+            .lock()
+            .await
+            .insert(stream_id.clone(), stream_meta);
+        Ok(stream_id)
+    }
+
+    async fn close_stream(&mut self, stream_id: &str) -> Option<StreamMeta> {
+        self.stream_metas.lock().await.remove(stream_id)
+    }
+
+    fn stream_metas(&self) -> ArcMutex<HashMap<String, StreamMeta>> {
+        self.stream_metas.clone()
     }
 }
 
 impl Default for MockExchangeApi {
     fn default() -> Self {
-        Self {}
+        Self {
+            open_failures: AtomicUsize::new(0),
+            open_rejections: AtomicUsize::new(0),
+            client_order_ids: Mutex::new(vec![]),
+            next_order_id: AtomicU64::new(1),
+            kline_requests: AtomicUsize::new(0),
+            max_kline_requests: AtomicUsize::new(0),
+            depth_snapshot_requests: AtomicUsize::new(0),
+            ticker_failures: AtomicUsize::new(0),
+            ticker_requests: AtomicUsize::new(0),
+            last_prices_requests: AtomicUsize::new(0),
+            last_kline_requests: AtomicUsize::new(0),
+            trade_requests: AtomicUsize::new(0),
+            symbol_info_requests: AtomicUsize::new(0),
+            symbol_info_error: Mutex::new(None),
+            kline_error: Mutex::new(None),
+            positions: Mutex::new(vec![]),
+            symbol_status: Mutex::new(HashMap::new()),
+            contract_types: Mutex::new(HashMap::new()),
+            fill_price: Mutex::new(None),
+            stream_manager: ArcMutex::new(Box::new(MockStreamManager::default())),
+        }
     }
 }
 
@@ -151,7 +703,10 @@ mod test {
         let open_price = 50000.0;
 
         let result = api
-            .open_position(symbol, margin_usd, leverage, order_side, open_price)
+            .open_position(
+                Position::new(symbol, open_price, order_side, margin_usd, leverage, None),
+                None,
+            )
             .await;
 
         assert!(result.is_ok());
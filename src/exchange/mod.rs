@@ -1,6 +1,12 @@
 pub mod api;
 pub mod binance;
 pub mod bingx;
+pub mod halt;
+pub mod http;
 pub mod mock;
+pub mod quarantine;
+pub mod rate_limit;
+pub mod resilient;
 pub mod stream;
+pub mod symbol;
 pub mod types;
@@ -0,0 +1,85 @@
+use log::warn;
+
+use crate::exchange::{stream::StreamMeta, types::ApiResult};
+
+/// Percent of frames of a stream which may fail to parse when `STREAM_PARSE_FAILURE_PCT` isn't
+/// set.
+pub const DEFAULT_PARSE_FAILURE_PCT: f64 = 10.0;
+
+/// Frames a stream receives before its failure rate can mark it unhealthy, so a single bad frame
+/// after connecting doesn't.
+pub const MIN_QUARANTINE_FRAMES: u64 = 20;
+
+/// Number of characters of a quarantined frame written to the log.
+const QUARANTINE_TEXT_LEN: usize = 256;
+
+/// Quarantines stream frames which can't be parsed, rather than have a malformed or unexpected
+/// frame end the stream task.
+///
+/// Failed frames are counted on the `StreamMeta` of their stream and logged, truncated. A stream
+/// whose failure rate exceeds the threshold is marked unhealthy so it's reopened by the market.
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StreamQuarantine {
+    /// Percent of frames which may fail to parse before the stream is marked unhealthy.
+    pub max_failure_pct: f64,
+}
+
+impl StreamQuarantine {
+    /// Parses the `STREAM_PARSE_FAILURE_PCT` setting, empty or invalid values use the default
+    /// threshold.
+
+    pub fn from_setting(setting: &str) -> Self {
+        let max_failure_pct = match setting.parse::<f64>() {
+            Ok(pct) if (0.0..=100.0).contains(&pct) => pct,
+            _ => DEFAULT_PARSE_FAILURE_PCT,
+        };
+
+        Self { max_failure_pct }
+    }
+
+    /// Records a frame received on a stream, quarantining it if it couldn't be parsed.
+    ///
+    /// # Arguments
+    ///
+    /// * `stream_meta` - Metadata of the stream the frame was received on.
+    /// * `text` - The raw text of the frame.
+    /// * `parsed` - Result of parsing the frame and forwarding it to the market.
+
+    pub fn record(&self, stream_meta: &mut StreamMeta, text: &str, parsed: &ApiResult<()>) {
+        stream_meta.frame_count += 1;
+
+        let Err(e) = parsed else {
+            return;
+        };
+
+        stream_meta.parse_failures += 1;
+        let truncated: String = text.chars().take(QUARANTINE_TEXT_LEN).collect();
+        warn!(
+            "Quarantined frame on stream {}, {e}: {truncated}",
+            stream_meta.id
+        );
+
+        let failure_pct =
+            stream_meta.parse_failures as f64 / stream_meta.frame_count as f64 * 100.0;
+
+        if stream_meta.healthy
+            && stream_meta.frame_count >= MIN_QUARANTINE_FRAMES
+            && failure_pct > self.max_failure_pct
+        {
+            warn!(
+                "Stream {} marked unhealthy, {failure_pct:.1}% of frames failed to parse",
+                stream_meta.id
+            );
+            stream_meta.healthy = false;
+        }
+    }
+}
+
+impl Default for StreamQuarantine {
+    fn default() -> Self {
+        Self {
+            max_failure_pct: DEFAULT_PARSE_FAILURE_PCT,
+        }
+    }
+}
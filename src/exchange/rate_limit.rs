@@ -0,0 +1,82 @@
+use std::{sync::Arc, time::Duration};
+
+use tokio::{sync::Mutex, time::Instant};
+
+/// Number of REST requests sent to the exchange per second when `REST_REQUESTS_PER_SEC` isn't
+/// set.
+pub const DEFAULT_REST_REQUESTS_PER_SEC: u32 = 10;
+
+/// Spaces out the REST requests sent to an exchange, so backfills and live fetches sharing a
+/// limiter stay within the request limits of the exchange together.
+///
+/// Clones of a limiter share the same schedule.
+
+#[derive(Debug, Clone)]
+pub struct RateLimiter {
+    spacing: Duration,
+    next_slot: Arc<Mutex<Instant>>,
+}
+
+impl RateLimiter {
+    /// Creates a limiter allowing `requests_per_sec` requests per second, at least one.
+
+    pub fn new(requests_per_sec: u32) -> Self {
+        Self {
+            spacing: Duration::from_secs(1) / requests_per_sec.max(1),
+            next_slot: Arc::new(Mutex::new(Instant::now())),
+        }
+    }
+
+    /// Parses the `REST_REQUESTS_PER_SEC` setting, empty or invalid values use the default rate.
+
+    pub fn from_setting(setting: &str) -> Self {
+        match setting.parse::<u32>() {
+            Ok(requests_per_sec) if requests_per_sec > 0 => Self::new(requests_per_sec),
+            _ => Self::default(),
+        }
+    }
+
+    /// Waits until a request may be sent, reserving its slot so the next caller waits for the
+    /// following one.
+
+    pub async fn acquire(&self) {
+        let slot = {
+            let mut next_slot = self.next_slot.lock().await;
+            let slot = (*next_slot).max(Instant::now());
+            *next_slot = slot + self.spacing;
+            slot
+        };
+
+        tokio::time::sleep_until(slot).await;
+    }
+}
+
+impl Default for RateLimiter {
+    fn default() -> Self {
+        Self::new(DEFAULT_REST_REQUESTS_PER_SEC)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_clones_share_schedule() {
+        let limiter = RateLimiter::new(20);
+        let shared = limiter.clone();
+        let start = Instant::now();
+
+        limiter.acquire().await;
+        shared.acquire().await;
+        limiter.acquire().await;
+
+        // the first request is sent right away, the next two wait 50ms each
+        assert!(start.elapsed() >= Duration::from_millis(100));
+
+        assert_eq!(
+            RateLimiter::from_setting("").spacing,
+            RateLimiter::default().spacing
+        );
+    }
+}
@@ -0,0 +1,438 @@
+use std::{
+    collections::HashMap,
+    future::Future,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use log::warn;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::time;
+
+use crate::{
+    account::{
+        trade::{Position, TradeTx},
+        user_data::UserDataEvent,
+    },
+    market::{
+        interval::Interval,
+        kline::Kline,
+        orderbook::DepthSnapshot,
+        ticker::Ticker,
+        trade::AggTrade,
+        types::{ArcMutex, ArcSender},
+    },
+    utils::time::{generate_ts, timestamp_to_string},
+};
+
+use super::{
+    api::{ExchangeApi, ExchangeInfo, ExchangePosition, SymbolInfo},
+    stream::{StreamManager, StreamMeta},
+    types::{ApiError, ApiResult, StreamType},
+};
+
+/// Number of times a failed exchange read is retried by default.
+pub const DEFAULT_MAX_RETRIES: u32 = 2;
+
+/// Delay before the first retry of a failed exchange read by default.
+pub const DEFAULT_RETRY_BACKOFF_MS: u64 = 200;
+
+/// Number of consecutive failed exchange requests which open the circuit by default.
+pub const DEFAULT_FAILURE_THRESHOLD: usize = 5;
+
+/// Time the circuit stays open by default.
+pub const DEFAULT_COOL_DOWN_SECS: u64 = 30;
+
+/// Retry and circuit breaker settings of a `ResilientExchangeApi`.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResilienceConfig {
+    /// Number of times a failed read is retried, orders are never retried.
+    pub max_retries: u32,
+    /// Delay before the first retry, doubled on each further retry.
+    pub backoff: Duration,
+    /// Number of consecutive failed requests which open the circuit.
+    pub failure_threshold: usize,
+    /// Time the circuit stays open before requests are let through again.
+    pub cool_down: Duration,
+}
+
+impl ResilienceConfig {
+    /// Parses the retry and circuit breaker settings, invalid values use the defaults.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_retries` - The `EXCHANGE_MAX_RETRIES` setting.
+    /// * `backoff_ms` - The `EXCHANGE_RETRY_BACKOFF_MS` setting.
+    /// * `failure_threshold` - The `EXCHANGE_FAILURE_THRESHOLD` setting.
+    /// * `cool_down_secs` - The `EXCHANGE_COOL_DOWN_SECS` setting.
+    ///
+    /// # Returns
+    ///
+    /// The parsed `ResilienceConfig`.
+
+    pub fn from_settings(
+        max_retries: &str,
+        backoff_ms: &str,
+        failure_threshold: &str,
+        cool_down_secs: &str,
+    ) -> Self {
+        Self {
+            max_retries: max_retries.parse().unwrap_or(DEFAULT_MAX_RETRIES),
+            backoff: Duration::from_millis(backoff_ms.parse().unwrap_or(DEFAULT_RETRY_BACKOFF_MS)),
+            failure_threshold: failure_threshold
+                .parse()
+                .unwrap_or(DEFAULT_FAILURE_THRESHOLD),
+            cool_down: Duration::from_secs(
+                cool_down_secs.parse().unwrap_or(DEFAULT_COOL_DOWN_SECS),
+            ),
+        }
+    }
+}
+
+impl Default for ResilienceConfig {
+    fn default() -> Self {
+        Self {
+            max_retries: DEFAULT_MAX_RETRIES,
+            backoff: Duration::from_millis(DEFAULT_RETRY_BACKOFF_MS),
+            failure_threshold: DEFAULT_FAILURE_THRESHOLD,
+            cool_down: Duration::from_secs(DEFAULT_COOL_DOWN_SECS),
+        }
+    }
+}
+
+/// Health of an exchange, as seen by the requests made to it.
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ExchangeHealth {
+    pub circuit_open: bool,
+    pub consecutive_failures: usize,
+    /// Time requests are let through again, while the circuit is open.
+    pub open_until: Option<u64>,
+    /// Error of the last failed request, cleared by a successful request.
+    pub last_error: Option<String>,
+}
+
+/// Wraps an exchange, retrying failed reads and failing fast while the exchange is down.
+///
+/// Reads which fail with a network error are retried with an exponential backoff. After
+/// `failure_threshold` consecutive failed requests the circuit opens, all requests then fail with
+/// `ApiError::CircuitOpen` until the cool down has passed. The next request is let through, the
+/// circuit closes if it succeeds and opens again if it fails.
+///
+/// Orders are sent once, retrying them could open a position twice. Errors the exchange responds
+/// with, ie. a rejected order, don't count as failures.
+
+pub struct ResilientExchangeApi {
+    inner: Arc<dyn ExchangeApi>,
+    config: ResilienceConfig,
+    breaker: Mutex<CircuitBreaker>,
+}
+
+impl ResilientExchangeApi {
+    /// Wraps an exchange with retries and a circuit breaker.
+    ///
+    /// # Arguments
+    ///
+    /// * `inner` - The exchange requests are made to.
+    /// * `config` - The retry and circuit breaker settings.
+    ///
+    /// # Returns
+    ///
+    /// A new `ResilientExchangeApi`.
+
+    pub fn new(inner: Arc<dyn ExchangeApi>, config: ResilienceConfig) -> Self {
+        Self {
+            inner,
+            config,
+            breaker: Mutex::new(CircuitBreaker::default()),
+        }
+    }
+
+    // ---
+    // Private Methods
+    // ---
+
+    /// Makes a request through the circuit breaker, retrying network errors if `retry` is set.
+    async fn request<T, F, Fut>(&self, retry: bool, request: F) -> ApiResult<T>
+    where
+        F: Fn() -> Fut + Send,
+        Fut: Future<Output = ApiResult<T>> + Send,
+        T: Send,
+    {
+        self.check_circuit()?;
+
+        let max_retries = if retry { self.config.max_retries } else { 0 };
+        let mut attempt = 0;
+
+        loop {
+            let result = request().await;
+
+            if let Err(e) = &result {
+                if e.is_transient() && attempt < max_retries {
+                    let backoff = self
+                        .config
+                        .backoff
+                        .saturating_mul(2u32.saturating_pow(attempt));
+                    attempt += 1;
+
+                    warn!("Exchange request failed, retry {attempt} in {backoff:?}: {e}");
+                    time::sleep(backoff).await;
+                    continue;
+                }
+            }
+
+            self.record_result(&result);
+            return result;
+        }
+    }
+
+    /// Fails fast while the circuit is open.
+    fn check_circuit(&self) -> ApiResult<()> {
+        let breaker = self.breaker.lock().unwrap();
+
+        match breaker.open_until {
+            Some(open_until) if generate_ts() < open_until => Err(ApiError::CircuitOpen(format!(
+                "Exchange failed {} times in a row, requests resume at {}",
+                breaker.consecutive_failures,
+                timestamp_to_string(open_until)
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    /// Updates the circuit breaker with the result of a request, opening the circuit once the
+    /// failure threshold is reached.
+    fn record_result<T>(&self, result: &ApiResult<T>) {
+        let mut breaker = self.breaker.lock().unwrap();
+
+        match result {
+            Ok(_) => *breaker = CircuitBreaker::default(),
+            Err(e) if e.is_transient() => {
+                breaker.consecutive_failures += 1;
+                breaker.last_error = Some(e.to_string());
+
+                if breaker.consecutive_failures >= self.config.failure_threshold {
+                    let open_until = generate_ts() + self.config.cool_down.as_millis() as u64;
+                    breaker.open_until = Some(open_until);
+
+                    warn!(
+                        "Exchange failed {} times in a row, circuit open until {}",
+                        breaker.consecutive_failures,
+                        timestamp_to_string(open_until)
+                    );
+                }
+            }
+            // the exchange responded, it is reachable
+            Err(_) => {}
+        }
+    }
+}
+
+#[async_trait]
+impl ExchangeApi for ResilientExchangeApi {
+    async fn get_account(&self) -> ApiResult<Value> {
+        self.request(true, || self.inner.get_account()).await
+    }
+
+    async fn get_account_balance(&self) -> ApiResult<f64> {
+        self.request(true, || self.inner.get_account_balance())
+            .await
+    }
+
+    async fn open_position(
+        &self,
+        position: Position,
+        client_order_id: Option<&str>,
+    ) -> ApiResult<Position> {
+        self.request(false, || {
+            self.inner.open_position(position.clone(), client_order_id)
+        })
+        .await
+    }
+
+    async fn close_position(&self, position: Position, close_price: f64) -> ApiResult<TradeTx> {
+        self.request(false, || {
+            self.inner.close_position(position.clone(), close_price)
+        })
+        .await
+    }
+
+    async fn all_orders(&self) -> ApiResult<Value> {
+        self.request(true, || self.inner.all_orders()).await
+    }
+
+    async fn list_open_orders(&self) -> ApiResult<Value> {
+        self.request(true, || self.inner.list_open_orders()).await
+    }
+
+    async fn cancel_order(&self, symbol: &str, order_id: &str) -> ApiResult<Value> {
+        self.request(false, || self.inner.cancel_order(symbol, order_id))
+            .await
+    }
+
+    async fn cancel_all_orders(&self, symbol: &str) -> ApiResult<Value> {
+        self.request(false, || self.inner.cancel_all_orders(symbol))
+            .await
+    }
+
+    async fn start_user_data_stream(
+        &self,
+        account_sender: ArcSender<UserDataEvent>,
+    ) -> ApiResult<String> {
+        self.inner.start_user_data_stream(account_sender).await
+    }
+
+    fn get_stream_manager(&self) -> ArcMutex<Box<dyn StreamManager>> {
+        self.inner.get_stream_manager()
+    }
+
+    async fn active_streams(&self) -> Vec<StreamMeta> {
+        self.inner.active_streams().await
+    }
+
+    async fn get_kline(&self, symbol: &str, interval: Interval) -> ApiResult<Kline> {
+        self.request(true, || self.inner.get_kline(symbol, interval))
+            .await
+    }
+
+    async fn get_klines(
+        &self,
+        symbol: &str,
+        interval: Interval,
+        from_ts: u64,
+        to_ts: u64,
+    ) -> ApiResult<Vec<Kline>> {
+        self.request(true, || {
+            self.inner.get_klines(symbol, interval, from_ts, to_ts)
+        })
+        .await
+    }
+
+    async fn get_ticker(&self, symbol: &str) -> ApiResult<Ticker> {
+        self.request(true, || self.inner.get_ticker(symbol)).await
+    }
+
+    async fn get_recent_trades(&self, symbol: &str, limit: usize) -> ApiResult<Vec<AggTrade>> {
+        self.request(true, || self.inner.get_recent_trades(symbol, limit))
+            .await
+    }
+
+    async fn get_historical_trades(&self, symbol: &str, from_id: u64) -> ApiResult<Vec<AggTrade>> {
+        self.request(true, || self.inner.get_historical_trades(symbol, from_id))
+            .await
+    }
+
+    async fn get_last_prices(&self, symbols: &[String]) -> ApiResult<HashMap<String, f64>> {
+        self.request(true, || self.inner.get_last_prices(symbols))
+            .await
+    }
+
+    async fn info(&self) -> ApiResult<ExchangeInfo> {
+        self.request(true, || self.inner.info()).await
+    }
+
+    async fn get_symbol_info(&self, symbol: &str) -> ApiResult<SymbolInfo> {
+        self.request(true, || self.inner.get_symbol_info(symbol))
+            .await
+    }
+
+    async fn get_depth_snapshot(&self, symbol: &str, limit: usize) -> ApiResult<DepthSnapshot> {
+        self.request(true, || self.inner.get_depth_snapshot(symbol, limit))
+            .await
+    }
+
+    async fn get_positions(&self) -> ApiResult<Vec<ExchangePosition>> {
+        self.request(true, || self.inner.get_positions()).await
+    }
+
+    fn supported_intervals(&self) -> Vec<Interval> {
+        self.inner.supported_intervals()
+    }
+
+    fn build_stream_url(
+        &self,
+        symbol: &str,
+        stream_type: StreamType,
+        interval: Option<Interval>,
+    ) -> String {
+        self.inner.build_stream_url(symbol, stream_type, interval)
+    }
+
+    fn health(&self) -> Option<ExchangeHealth> {
+        let breaker = self.breaker.lock().unwrap();
+
+        Some(ExchangeHealth {
+            circuit_open: breaker
+                .open_until
+                .is_some_and(|open_until| generate_ts() < open_until),
+            consecutive_failures: breaker.consecutive_failures,
+            open_until: breaker.open_until,
+            last_error: breaker.last_error.clone(),
+        })
+    }
+}
+
+/// Consecutive failures of the requests made to an exchange.
+
+#[derive(Default)]
+struct CircuitBreaker {
+    consecutive_failures: usize,
+    open_until: Option<u64>,
+    last_error: Option<String>,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use tokio::test;
+
+    use crate::exchange::mock::MockExchangeApi;
+
+    fn config(max_retries: u32, failure_threshold: usize) -> ResilienceConfig {
+        ResilienceConfig {
+            max_retries,
+            backoff: Duration::from_millis(1),
+            failure_threshold,
+            cool_down: Duration::from_secs(60),
+        }
+    }
+
+    #[test]
+    async fn test_retry_then_success() {
+        let mock = Arc::new(MockExchangeApi::with_ticker_failures(2));
+        let exchange_api = ResilientExchangeApi::new(mock.clone(), config(3, 5));
+
+        let ticker = exchange_api.get_ticker("BTCUSDT").await.unwrap();
+
+        assert_eq!(ticker.symbol, "BTCUSDT");
+        assert_eq!(mock.ticker_requests(), 3);
+
+        let health = exchange_api.health().unwrap();
+        assert!(!health.circuit_open);
+        assert_eq!(health.consecutive_failures, 0);
+        assert_eq!(health.last_error, None);
+    }
+
+    #[test]
+    async fn test_breaker_trips_after_threshold() {
+        let mock = Arc::new(MockExchangeApi::with_ticker_failures(100));
+        let exchange_api = ResilientExchangeApi::new(mock.clone(), config(0, 3));
+
+        for _ in 0..3 {
+            let res = exchange_api.get_ticker("BTCUSDT").await;
+            assert!(matches!(res, Err(ApiError::Network(_))));
+        }
+
+        let health = exchange_api.health().unwrap();
+        assert!(health.circuit_open);
+        assert_eq!(health.consecutive_failures, 3);
+
+        // requests fail fast without reaching the exchange while the circuit is open
+        let res = exchange_api.get_ticker("BTCUSDT").await;
+        assert!(matches!(res, Err(ApiError::CircuitOpen(_))));
+        assert_eq!(mock.ticker_requests(), 3);
+    }
+}
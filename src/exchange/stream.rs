@@ -48,7 +48,8 @@ pub trait StreamManager: Send + Sync {
     ///
     /// # Returns
     ///
-    /// Returns metadata of all active streams as a vector of `StreamMeta` structs.
+    /// Returns metadata of all active streams as a vector of `StreamMeta` structs, sorted by
+    /// stream ID so listings are stable across calls.
 
     async fn active_streams(&self) -> Vec<StreamMeta> {
         let metas = self.stream_metas();
@@ -61,6 +62,7 @@ pub trait StreamManager: Send + Sync {
             metas.push(meta);
         }
 
+        metas.sort_by(|a, b| a.id.cmp(&b.id));
         metas
     }
 
@@ -89,6 +91,13 @@ pub struct StreamMeta {
     pub symbol: String,
     /// The interval of the stream, if applicable.
     pub interval: Option<Interval>,
+    /// Whether the stream is still receiving data, cleared when a stream gives up after repeated
+    /// failures. Unhealthy streams are reopened by the market.
+    pub healthy: bool,
+    /// Number of frames received on the stream.
+    pub frame_count: u64,
+    /// Number of frames received which couldn't be parsed, see `StreamQuarantine`.
+    pub parse_failures: u64,
 }
 
 impl StreamMeta {
@@ -120,8 +129,17 @@ impl StreamMeta {
             last_update: generate_ts(),
             symbol: symbol.to_string(),
             interval,
+            healthy: true,
+            frame_count: 0,
+            parse_failures: 0,
         }
     }
+
+    /// Checks whether the stream has not received data for longer than `max_age` milliseconds.
+
+    pub fn is_stale(&self, now: u64, max_age: u64) -> bool {
+        now.saturating_sub(self.last_update) > max_age
+    }
 }
 
 impl Default for StreamMeta {
@@ -134,6 +152,9 @@ impl Default for StreamMeta {
             last_update: 123,
             symbol: "unknown".to_string(),
             interval: None,
+            healthy: true,
+            frame_count: 0,
+            parse_failures: 0,
         }
     }
 }
@@ -167,5 +188,11 @@ pub fn build_stream_id(
         StreamType::Trade => {
             format!("{}@trade", symbol)
         }
+        StreamType::UserData => {
+            format!("{}@userData", symbol)
+        }
+        StreamType::Depth => {
+            format!("{}@depth", symbol)
+        }
     }
 }
@@ -0,0 +1,270 @@
+use std::fmt::{self, Display, Formatter};
+
+use serde::{Deserialize, Serialize};
+
+use crate::exchange::types::{ApiError, ApiResult};
+
+/// Quote assets recognised when splitting a symbol without a separator, longest first so
+/// `BTCUSDT` is split as `BTC` / `USDT` rather than `BTCUSD` / `T`.
+const QUOTE_ASSETS: [&str; 8] = ["USDT", "USDC", "BUSD", "FDUSD", "USD", "EUR", "BTC", "ETH"];
+
+/// Assets which Kraken lists under a different code, as `(canonical, kraken)` pairs.
+const KRAKEN_ASSETS: [(&str, &str); 2] = [("BTC", "XBT"), ("DOGE", "XDG")];
+
+/// Symbol formats used by the supported exchanges.
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymbolFormat {
+    /// Base and quote without a separator, ie. `BTCUSDT`.
+    Binance,
+    /// Base and quote separated by a dash, ie. `BTC-USDT`.
+    BingX,
+    /// Base and quote without a separator, using Kraken asset codes, ie. `XBTUSD`.
+    Kraken,
+}
+
+/// A trading pair made up of a base and quote asset.
+///
+/// The canonical form of a symbol is the base followed by the quote without a separator, ie.
+/// `BTCUSDT`. Canonical symbols are used throughout the bot, ie. for storage keys and strategy
+/// symbols, and are only converted to an exchange format when talking to the exchange.
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Symbol {
+    pub base: String,
+    pub quote: String,
+}
+
+impl Symbol {
+    /// Creates a new symbol from its base and quote assets.
+    ///
+    /// # Arguments
+    ///
+    /// * `base` - The base asset, ie. `BTC`.
+    /// * `quote` - The quote asset, ie. `USDT`.
+    ///
+    /// # Returns
+    ///
+    /// A new `Symbol` with upper case assets.
+
+    pub fn new(base: &str, quote: &str) -> Self {
+        Self {
+            base: base.to_uppercase(),
+            quote: quote.to_uppercase(),
+        }
+    }
+
+    /// Parses a canonical symbol, ie. `BTCUSDT`.
+    ///
+    /// Symbols with a `-`, `/` or `_` separator between the base and quote are also accepted, as
+    /// long as the quote after the last separator is recognised and the base holds no separator,
+    /// so contract suffixes like `BTCUSD_PERP` are rejected rather than parsed as a quote.
+    ///
+    /// # Arguments
+    ///
+    /// * `symbol` - The symbol to parse, in any case.
+    ///
+    /// # Returns
+    ///
+    /// The parsed `Symbol`, or a parsing error if the quote asset is not recognised.
+
+    pub fn from_canonical(symbol: &str) -> ApiResult<Self> {
+        let symbol = symbol.trim().to_uppercase();
+
+        if let Some((base, quote)) = symbol.rsplit_once(['-', '/', '_']) {
+            let is_base = !base.is_empty() && base.chars().all(|c| c.is_ascii_alphanumeric());
+
+            return match QUOTE_ASSETS.contains(&quote) && is_base {
+                true => Ok(Self::new(base, quote)),
+                false => Err(ApiError::Parsing(format!(
+                    "Unable to parse symbol {symbol}"
+                ))),
+            };
+        }
+
+        QUOTE_ASSETS
+            .iter()
+            .find_map(|quote| {
+                symbol
+                    .strip_suffix(quote)
+                    .filter(|base| !base.is_empty())
+                    .map(|base| Self::new(base, quote))
+            })
+            .ok_or_else(|| ApiError::Parsing(format!("Unable to parse symbol {symbol}")))
+    }
+
+    /// Parses a symbol in the format used by an exchange.
+    ///
+    /// # Arguments
+    ///
+    /// * `symbol` - The symbol as returned by the exchange.
+    /// * `format` - The symbol format of the exchange.
+    ///
+    /// # Returns
+    ///
+    /// The parsed `Symbol`, or a parsing error if the symbol is not in the exchange format.
+
+    pub fn from_exchange_format(symbol: &str, format: SymbolFormat) -> ApiResult<Self> {
+        match format {
+            SymbolFormat::Binance => Self::from_canonical(symbol),
+            SymbolFormat::BingX => match symbol.split_once('-') {
+                Some((base, quote)) if !base.is_empty() && !quote.is_empty() => {
+                    Ok(Self::new(base, quote))
+                }
+                _ => Err(ApiError::Parsing(format!(
+                    "Unable to parse BingX symbol {symbol}"
+                ))),
+            },
+            SymbolFormat::Kraken => {
+                let symbol = Self::from_canonical(symbol)?;
+                Ok(Self {
+                    base: from_kraken_asset(&symbol.base),
+                    quote: from_kraken_asset(&symbol.quote),
+                })
+            }
+        }
+    }
+
+    /// Formats the symbol in the format used by an exchange.
+    ///
+    /// # Arguments
+    ///
+    /// * `format` - The symbol format of the exchange.
+    ///
+    /// # Returns
+    ///
+    /// The symbol as expected by the exchange, ie. `BTC-USDT` for BingX.
+
+    pub fn to_exchange_format(&self, format: SymbolFormat) -> String {
+        match format {
+            SymbolFormat::Binance => self.canonical(),
+            SymbolFormat::BingX => format!("{}-{}", self.base, self.quote),
+            SymbolFormat::Kraken => format!(
+                "{}{}",
+                to_kraken_asset(&self.base),
+                to_kraken_asset(&self.quote)
+            ),
+        }
+    }
+
+    /// Returns the canonical form of the symbol, ie. `BTCUSDT`.
+
+    pub fn canonical(&self) -> String {
+        format!("{}{}", self.base, self.quote)
+    }
+}
+
+impl Display for Symbol {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.canonical())
+    }
+}
+
+/// Converts a symbol to an exchange format, symbols which can't be parsed are upper cased and
+/// passed through so the exchange can reject them.
+///
+/// # Arguments
+///
+/// * `symbol` - The symbol, canonical or with a separator.
+/// * `format` - The symbol format of the exchange.
+///
+/// # Returns
+///
+/// The symbol in the exchange format.
+
+pub fn format_exchange_symbol(symbol: &str, format: SymbolFormat) -> String {
+    match Symbol::from_canonical(symbol) {
+        Ok(symbol) => symbol.to_exchange_format(format),
+        Err(_) => symbol.to_uppercase(),
+    }
+}
+
+/// Converts a symbol returned by an exchange to its canonical form, symbols which can't be parsed
+/// are passed through unchanged.
+///
+/// # Arguments
+///
+/// * `symbol` - The symbol as returned by the exchange.
+/// * `format` - The symbol format of the exchange.
+///
+/// # Returns
+///
+/// The canonical symbol.
+
+pub fn canonical_symbol(symbol: &str, format: SymbolFormat) -> String {
+    match Symbol::from_exchange_format(symbol, format) {
+        Ok(symbol) => symbol.canonical(),
+        Err(_) => symbol.to_string(),
+    }
+}
+
+fn to_kraken_asset(asset: &str) -> String {
+    KRAKEN_ASSETS
+        .iter()
+        .find(|(canonical, _)| *canonical == asset)
+        .map_or(asset, |(_, kraken)| kraken)
+        .to_string()
+}
+
+fn from_kraken_asset(asset: &str) -> String {
+    KRAKEN_ASSETS
+        .iter()
+        .find(|(_, kraken)| *kraken == asset)
+        .map_or(asset, |(canonical, _)| canonical)
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::test;
+
+    #[test]
+    async fn test_symbol_round_trip() {
+        let symbol = Symbol::from_canonical("BTCUSDT").unwrap();
+        assert_eq!(symbol, Symbol::new("BTC", "USDT"));
+
+        let cases = [
+            (SymbolFormat::Binance, "BTCUSDT"),
+            (SymbolFormat::BingX, "BTC-USDT"),
+            (SymbolFormat::Kraken, "XBTUSDT"),
+        ];
+
+        for (format, expected) in cases {
+            let exchange_symbol = symbol.to_exchange_format(format);
+            assert_eq!(exchange_symbol, expected);
+
+            let parsed = Symbol::from_exchange_format(&exchange_symbol, format).unwrap();
+            assert_eq!(parsed, symbol);
+            assert_eq!(parsed.canonical(), "BTCUSDT");
+        }
+
+        assert_eq!(
+            Symbol::from_exchange_format("XBTUSD", SymbolFormat::Kraken).unwrap(),
+            Symbol::new("BTC", "USD")
+        );
+        assert!(Symbol::from_canonical("UNKNOWN").is_err());
+    }
+
+    #[test]
+    async fn test_separated_symbols() {
+        assert_eq!(
+            Symbol::from_canonical("1000pepe_usdt").unwrap(),
+            Symbol::new("1000PEPE", "USDT")
+        );
+        assert_eq!(
+            Symbol::from_canonical("ETH/BTC").unwrap(),
+            Symbol::new("ETH", "BTC")
+        );
+
+        for symbol in [
+            "BTCUSD_PERP",
+            "ETHUSDT_240628",
+            "BTC-USDT-SWAP",
+            "_USDT",
+            "BTC-",
+        ] {
+            assert!(Symbol::from_canonical(symbol).is_err(), "{symbol}");
+        }
+    }
+}
@@ -10,7 +10,7 @@ use crate::market::types::ArcMutex;
 /// Custom error type for API-related errors.
 ///
 /// This enum represents various types of errors that can occur during API operations.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum ApiError {
     /// Represents a network-related error with a descriptive message.
     Network(String),
@@ -18,6 +18,25 @@ pub enum ApiError {
     Parsing(String),
     /// Represents a Reqwest error with a descriptive message.
     Reqwest(String),
+    /// The exchange doesn't support the request.
+    Unsupported(String),
+    /// The request was not sent as the exchange failed too many times in a row.
+    CircuitOpen(String),
+    /// The exchange doesn't list the symbol.
+    SymbolNotFound(String),
+    /// The exchange rejected the request with an error status, ie. an invalid parameter or
+    /// signature.
+    Rejected(String),
+    /// The exchange refused the request as trading is halted, ie. for maintenance.
+    Halted(String),
+}
+
+impl ApiError {
+    /// Whether the error may go away when the request is retried, ie. a network error.
+
+    pub fn is_transient(&self) -> bool {
+        matches!(self, ApiError::Network(_) | ApiError::Reqwest(_))
+    }
 }
 
 /// Implementation of the `Display` trait for `ApiError`.
@@ -35,6 +54,11 @@ impl fmt::Display for ApiError {
             ApiError::Network(msg) => write!(f, "Network error: {}", msg),
             ApiError::Parsing(msg) => write!(f, "Parsing error: {}", msg),
             ApiError::Reqwest(msg) => write!(f, "Reqwest error: {}", msg),
+            ApiError::Unsupported(msg) => write!(f, "Unsupported: {}", msg),
+            ApiError::CircuitOpen(msg) => write!(f, "Circuit open: {}", msg),
+            ApiError::SymbolNotFound(msg) => write!(f, "Symbol not found: {}", msg),
+            ApiError::Rejected(msg) => write!(f, "Rejected: {}", msg),
+            ApiError::Halted(msg) => write!(f, "Halted: {}", msg),
         }
     }
 }
@@ -100,7 +124,8 @@ impl From<serde_json::Error> for ApiError {
 
 /// Conversion implementation for `reqwest::Error` into `ApiError`.
 ///
-/// This implementation allows conversion from `reqwest::Error` to `ApiError::Reqwest`.
+/// This implementation allows conversion from `reqwest::Error` to `ApiError::Reqwest`, requests
+/// which timed out or couldn't connect are converted to `ApiError::Network`.
 impl From<reqwest::Error> for ApiError {
     /// Converts a `reqwest::Error` into an `ApiError`.
     ///
@@ -108,6 +133,10 @@ impl From<reqwest::Error> for ApiError {
     ///
     /// * `e` - The `reqwest::Error` to convert.
     fn from(e: reqwest::Error) -> Self {
+        if e.is_timeout() || e.is_connect() {
+            return ApiError::Network(e.to_string());
+        }
+
         ApiError::Reqwest(e.to_string())
     }
 }
@@ -142,6 +171,10 @@ pub enum StreamType {
     /// Represents a Ticker stream type.
     Ticker,
     Trade,
+    /// Represents an account user data stream, ie. order and balance updates.
+    UserData,
+    /// Represents an order book depth stream of incremental updates.
+    Depth,
 }
 
 /// Implementation of the `Display` trait for `StreamType`.
@@ -159,6 +192,8 @@ impl Display for StreamType {
             StreamType::Trade => write!(f, "trade"),
             StreamType::Kline => write!(f, "kline"),
             StreamType::Ticker => write!(f, "ticker"),
+            StreamType::UserData => write!(f, "userData"),
+            StreamType::Depth => write!(f, "depth"),
         }
     }
 }
@@ -17,9 +17,10 @@ use actix_web::middleware::Logger;
 use actix_web::{App, HttpServer};
 
 use api::{
-    account::register_account_service, exchange::register_exchange_service,
+    account::register_account_service, alert::register_alert_service,
+    exchange::register_exchange_service, health::register_health_service,
     main::register_main_service, market::register_market_service,
-    strategy::register_strategy_service, utils::register_utils_service,
+    strategy::register_strategy_service, utils::register_utils_service, ws::register_ws_service,
 };
 
 #[allow(unused_must_use)]
@@ -31,8 +32,11 @@ mod app;
 mod bot;
 mod exchange;
 mod market;
+mod notify;
 mod storage;
 mod strategy;
+#[cfg(test)]
+mod testutil;
 mod utils;
 
 /// Server host configuration (IP address and port).
@@ -44,8 +48,8 @@ const SERVER_HOST: (&str, u16) = ("127.0.0.1", 3000);
 ///
 /// # Errors
 ///
-/// This function will return an `io::Error` if there's an issue binding the server to the specified address
-/// or if any other issue occurs while starting the server.
+/// This function will return an `io::Error` if the bot settings are invalid, if there's an issue binding
+/// the server to the specified address or if any other issue occurs while starting the server.
 ///
 /// # Examples
 ///
@@ -68,7 +72,7 @@ async fn main() -> io::Result<()> {
         SERVER_HOST.0, SERVER_HOST.1
     );
 
-    let app_state = new_app_state().await;
+    let app_state = new_app_state().await?;
 
     // Make new HTTP server
     HttpServer::new(move || {
@@ -82,6 +86,9 @@ async fn main() -> io::Result<()> {
             .service(register_utils_service())
             .service(register_account_service())
             .service(register_strategy_service())
+            .service(register_ws_service())
+            .service(register_alert_service())
+            .service(register_health_service())
     })
     // .listen(listener)?
     .bind(SERVER_HOST)?
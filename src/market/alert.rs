@@ -0,0 +1,320 @@
+use std::{collections::HashMap, sync::Arc};
+
+use log::warn;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    market::messages::MarketMessage,
+    notify::notifier::{Notification, Notifier},
+    storage::manager::StorageManager,
+    utils::time::generate_ts,
+};
+
+pub type AlertId = Uuid;
+
+/// Direction the price must cross the alert price in for the alert to fire.
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum AlertCondition {
+    CrossAbove,
+    CrossBelow,
+}
+
+/// A price alert on a symbol, fired when the market price crosses the alert price.
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Alert {
+    pub id: AlertId,
+    pub symbol: String,
+    pub condition: AlertCondition,
+    pub price: f64,
+    /// One-shot alerts are disarmed after firing, others fire on every cross.
+    pub one_shot: bool,
+    pub armed: bool,
+    pub created_at: u64,
+    pub triggered_at: Option<u64>,
+}
+
+impl Alert {
+    /// Creates a new armed alert at an absolute price.
+    ///
+    /// # Arguments
+    ///
+    /// * `symbol` - The symbol to watch.
+    /// * `condition` - Direction the price must cross `price` in.
+    /// * `price` - The alert price.
+    /// * `one_shot` - Disarm the alert after it fires once.
+    ///
+    /// # Returns
+    ///
+    /// A new `Alert`.
+
+    pub fn new(symbol: &str, condition: AlertCondition, price: f64, one_shot: bool) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            symbol: symbol.to_string(),
+            condition,
+            price,
+            one_shot,
+            armed: true,
+            created_at: generate_ts(),
+            triggered_at: None,
+        }
+    }
+
+    /// Creates a new armed alert at a percentage change from a reference price.
+    ///
+    /// # Arguments
+    ///
+    /// * `symbol` - The symbol to watch.
+    /// * `condition` - Direction the price must cross the alert price in.
+    /// * `reference_price` - Price the change is relative to, ie. the last price.
+    /// * `pct_change` - Signed percentage change, ie. `-5.0` for 5% below the reference price.
+    /// * `one_shot` - Disarm the alert after it fires once.
+    ///
+    /// # Returns
+    ///
+    /// A new `Alert`.
+
+    pub fn from_pct_change(
+        symbol: &str,
+        condition: AlertCondition,
+        reference_price: f64,
+        pct_change: f64,
+        one_shot: bool,
+    ) -> Self {
+        let price = reference_price * (1.0 + pct_change / 100.0);
+        Self::new(symbol, condition, price, one_shot)
+    }
+
+    /// Returns `true` if moving from `prev_price` to `price` crosses the alert price in the
+    /// direction of the alert condition.
+
+    pub fn is_crossed(&self, prev_price: f64, price: f64) -> bool {
+        match self.condition {
+            AlertCondition::CrossAbove => prev_price < self.price && price >= self.price,
+            AlertCondition::CrossBelow => prev_price > self.price && price <= self.price,
+        }
+    }
+}
+
+/// Registry of price alerts, evaluated against incoming market messages.
+///
+/// Alerts are persisted with the storage manager whenever they change, so they survive restarts.
+
+pub struct AlertManager {
+    alerts: HashMap<AlertId, Alert>,
+    last_prices: HashMap<String, f64>,
+    storage_manager: Arc<dyn StorageManager>,
+    notifier: Arc<dyn Notifier>,
+}
+
+impl AlertManager {
+    /// Creates an alert manager, loading alerts previously saved to storage.
+    ///
+    /// # Arguments
+    ///
+    /// * `storage_manager` - Storage the alerts are persisted to.
+    /// * `notifier` - Notifier used when an alert fires.
+    ///
+    /// # Returns
+    ///
+    /// A new `AlertManager`.
+
+    pub async fn new(
+        storage_manager: Arc<dyn StorageManager>,
+        notifier: Arc<dyn Notifier>,
+    ) -> Self {
+        let alerts = match storage_manager.get_alerts().await {
+            Ok(alerts) => alerts.into_iter().map(|alert| (alert.id, alert)).collect(),
+            Err(e) => {
+                warn!("Unable to load alerts, {e}");
+                HashMap::new()
+            }
+        };
+
+        Self {
+            alerts,
+            last_prices: HashMap::new(),
+            storage_manager,
+            notifier,
+        }
+    }
+
+    /// Registers a new alert.
+    ///
+    /// # Arguments
+    ///
+    /// * `alert` - The alert to register.
+    ///
+    /// # Returns
+    ///
+    /// The registered alert.
+
+    pub async fn add_alert(&mut self, alert: Alert) -> Alert {
+        self.alerts.insert(alert.id, alert.clone());
+        self.save().await;
+        alert
+    }
+
+    /// Removes an alert.
+    ///
+    /// # Arguments
+    ///
+    /// * `alert_id` - ID of the alert to remove.
+    ///
+    /// # Returns
+    ///
+    /// The removed alert, or `None` if no alert has the ID.
+
+    pub async fn remove_alert(&mut self, alert_id: &AlertId) -> Option<Alert> {
+        let alert = self.alerts.remove(alert_id);
+        if alert.is_some() {
+            self.save().await;
+        }
+        alert
+    }
+
+    /// Lists all registered alerts, ordered by creation time.
+
+    pub fn alerts(&self) -> Vec<Alert> {
+        let mut alerts: Vec<Alert> = self.alerts.values().cloned().collect();
+        alerts.sort_by_key(|alert| alert.created_at);
+        alerts
+    }
+
+    /// Evaluates alerts against the price carried by a ticker or kline update.
+    ///
+    /// # Arguments
+    ///
+    /// * `message` - The market message received by the market.
+
+    pub async fn handle_message(&mut self, message: &MarketMessage) {
+        match message {
+            MarketMessage::UpdateTicker(ticker) => {
+                self.update_price(&ticker.symbol, ticker.last_price).await;
+            }
+            MarketMessage::UpdateKline(kline) | MarketMessage::UpdatePartialKline(kline) => {
+                self.update_price(&kline.symbol, kline.close).await;
+            }
+            _ => {}
+        }
+    }
+
+    /// Updates the price of a symbol, firing all armed alerts crossed since the previous price.
+    ///
+    /// # Arguments
+    ///
+    /// * `symbol` - The symbol the price belongs to.
+    /// * `price` - The latest price of the symbol.
+    ///
+    /// # Returns
+    ///
+    /// The alerts which fired.
+
+    pub async fn update_price(&mut self, symbol: &str, price: f64) -> Vec<Alert> {
+        let prev_price = self.last_prices.insert(symbol.to_string(), price);
+
+        // an alert can only be crossed once a previous price is known
+        let prev_price = match prev_price {
+            Some(prev_price) => prev_price,
+            None => return vec![],
+        };
+
+        let mut fired = vec![];
+        for alert in self.alerts.values_mut() {
+            if alert.armed && alert.symbol == symbol && alert.is_crossed(prev_price, price) {
+                alert.triggered_at = Some(generate_ts());
+                if alert.one_shot {
+                    alert.armed = false;
+                }
+                fired.push(alert.clone());
+            }
+        }
+
+        for alert in &fired {
+            let title = format!("Price alert {symbol}");
+            let message = format!(
+                "{symbol} crossed {:?} {}, last price {price}",
+                alert.condition, alert.price
+            );
+            self.notifier
+                .notify(Notification::new(&title, &message))
+                .await;
+        }
+
+        if !fired.is_empty() {
+            self.save().await;
+        }
+
+        fired
+    }
+
+    // ---
+    // Private Methods
+    // ---
+
+    /// Persists all alerts, failures are logged as alerts are still held in memory.
+    async fn save(&self) {
+        if let Err(e) = self.storage_manager.save_alerts(&self.alerts()).await {
+            warn!("Unable to save alerts, {e}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use async_trait::async_trait;
+    use tokio::test;
+
+    use crate::{market::types::ArcMutex, storage::fs::FsStorage};
+
+    struct TestNotifier {
+        notifications: ArcMutex<Vec<Notification>>,
+    }
+
+    #[async_trait]
+    impl Notifier for TestNotifier {
+        async fn notify(&self, notification: Notification) {
+            self.notifications.lock().await.push(notification);
+        }
+    }
+
+    #[test]
+    async fn test_cross_above_alert_fires_once() {
+        let storage_manager: Arc<dyn StorageManager> =
+            Arc::new(FsStorage::new(format!("test-alerts-{}", Uuid::new_v4())));
+        let notifier = Arc::new(TestNotifier {
+            notifications: ArcMutex::new(vec![]),
+        });
+
+        let mut alert_manager = AlertManager::new(storage_manager.clone(), notifier.clone()).await;
+        let alert = alert_manager
+            .add_alert(Alert::new(
+                "BTCUSDT",
+                AlertCondition::CrossAbove,
+                105.0,
+                true,
+            ))
+            .await;
+
+        let mut fired = vec![];
+        for price in [100.0, 104.0, 106.0, 103.0, 108.0] {
+            fired.extend(alert_manager.update_price("BTCUSDT", price).await);
+        }
+
+        assert_eq!(fired.len(), 1);
+        assert_eq!(fired[0].id, alert.id);
+        assert_eq!(notifier.notifications.lock().await.len(), 1);
+
+        // disarmed alert is persisted across restarts
+        let alert_manager = AlertManager::new(storage_manager, notifier).await;
+        let alerts = alert_manager.alerts();
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(alerts[0].id, alert.id);
+        assert!(!alerts[0].armed);
+    }
+}
@@ -0,0 +1,265 @@
+use std::{collections::BTreeMap, sync::Arc};
+
+use futures::future::join_all;
+use log::info;
+use serde::{Deserialize, Serialize};
+use tokio::sync::Semaphore;
+
+use crate::{
+    exchange::{
+        api::{ExchangeApi, AGG_TRADES_PAGE_LIMIT},
+        rate_limit::RateLimiter,
+    },
+    market::{interval::Interval, trade::Trade},
+    storage::manager::StorageManager,
+    utils::{kline::build_kline_key, trade::build_market_trade_key},
+};
+
+/// Most trades a single trade backfill fetches, larger requests are capped.
+pub const MAX_BACKFILL_TRADES: usize = 50_000;
+
+/// A range of klines of a symbol and interval to fetch from the exchange and save to storage.
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BackfillRequest {
+    pub symbol: String,
+    pub interval: Interval,
+    pub from_ts: u64,
+    pub to_ts: u64,
+}
+
+/// Outcome of a single backfill request.
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BackfillResult {
+    pub request: BackfillRequest,
+    pub klines_saved: usize,
+    /// Reason the backfill failed, `None` if it succeeded.
+    pub error: Option<String>,
+}
+
+impl BackfillResult {
+    /// Returns `true` if the backfill completed without error.
+
+    pub fn is_success(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
+/// Number of klines fetched per request while backfilling, so each page waits for its own slot of
+/// the rate limiter.
+pub const BACKFILL_PAGE_KLINES: u64 = 1000;
+
+/// Fetches history from the exchange and saves it to storage, independently of the market.
+///
+/// A backfiller is cloned out of the market so long backfills don't hold the market lock, its
+/// REST requests share the rate limiter of the market's live fetches.
+
+#[derive(Clone)]
+pub struct Backfiller {
+    exchange_api: Arc<dyn ExchangeApi>,
+    storage_manager: Arc<dyn StorageManager>,
+    rate_limiter: RateLimiter,
+}
+
+impl Backfiller {
+    /// Creates a backfiller.
+    ///
+    /// # Arguments
+    ///
+    /// * `exchange_api` - Exchange the history is fetched from.
+    /// * `storage_manager` - Storage the history is saved to, merged with existing data.
+    /// * `rate_limiter` - Limiter every REST request waits for.
+    ///
+    /// # Returns
+    ///
+    /// A new `Backfiller`.
+
+    pub fn new(
+        exchange_api: Arc<dyn ExchangeApi>,
+        storage_manager: Arc<dyn StorageManager>,
+        rate_limiter: RateLimiter,
+    ) -> Self {
+        Self {
+            exchange_api,
+            storage_manager,
+            rate_limiter,
+        }
+    }
+
+    /// Fetches the klines of a backfill request from the exchange and saves them to storage.
+    ///
+    /// The range is fetched in pages of `BACKFILL_PAGE_KLINES` klines.
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - The range of klines to backfill.
+    ///
+    /// # Returns
+    ///
+    /// The `BackfillResult` with the number of klines saved or the error which stopped the backfill.
+
+    pub async fn backfill(&self, request: BackfillRequest) -> BackfillResult {
+        let kline_key = build_kline_key(&request.symbol, request.interval);
+        let page_span = request.interval.to_mili() * BACKFILL_PAGE_KLINES;
+        let mut klines_saved = 0;
+        let mut page_from = request.from_ts;
+
+        while page_from <= request.to_ts {
+            let page_to = (page_from + page_span - 1).min(request.to_ts);
+
+            self.rate_limiter.acquire().await;
+            let page = self
+                .exchange_api
+                .get_klines(&request.symbol, request.interval, page_from, page_to)
+                .await
+                .map_err(|e| e.to_string());
+
+            let saved = match page {
+                Ok(klines) if klines.is_empty() => Ok(0),
+                Ok(klines) => self
+                    .storage_manager
+                    .save_klines(&klines, &kline_key, true)
+                    .await
+                    .map(|_| klines.len())
+                    .map_err(|e| e.to_string()),
+                Err(e) => Err(e),
+            };
+
+            match saved {
+                Ok(saved) => klines_saved += saved,
+                Err(error) => {
+                    return BackfillResult {
+                        request,
+                        klines_saved,
+                        error: Some(error),
+                    }
+                }
+            }
+
+            page_from += page_span;
+        }
+
+        BackfillResult {
+            request,
+            klines_saved,
+            error: None,
+        }
+    }
+
+    /// Backfills klines for many symbols and intervals concurrently.
+    ///
+    /// At most `concurrency` backfills run at the same time, each one fetches its klines from the
+    /// exchange and saves them to storage.
+    ///
+    /// # Arguments
+    ///
+    /// * `requests` - The ranges of klines to backfill.
+    /// * `concurrency` - Maximum number of backfills running at the same time, at least one.
+    ///
+    /// # Returns
+    ///
+    /// A `Vec<BackfillResult>` in the same order as `requests`, with the number of klines saved
+    /// or the error of each backfill.
+
+    pub async fn backfill_many(
+        &self,
+        requests: Vec<BackfillRequest>,
+        concurrency: usize,
+    ) -> Vec<BackfillResult> {
+        let semaphore = Arc::new(Semaphore::new(concurrency.max(1)));
+
+        let backfills = requests.into_iter().map(|request| {
+            let semaphore = semaphore.clone();
+
+            async move {
+                // SAFETY: semaphore is never closed
+                let _permit = semaphore.acquire().await.unwrap();
+                self.backfill(request).await
+            }
+        });
+
+        join_all(backfills).await
+    }
+
+    /// Fetches the most recent trades of a symbol from the exchange and saves them to storage, so
+    /// trade history is available before live trades are received.
+    ///
+    /// Trades are paged backwards from the latest trade until `count` trades are fetched or the
+    /// start of the trade history is reached. Trades are merged with the stored trades by their
+    /// exchange ID, so a repeated backfill doesn't store them twice.
+    ///
+    /// # Arguments
+    ///
+    /// * `symbol` - The symbol to fetch trades for.
+    /// * `count` - Number of trades to fetch, at most `MAX_BACKFILL_TRADES`.
+    ///
+    /// # Returns
+    ///
+    /// The number of trades saved, or the error which stopped the backfill.
+
+    pub async fn backfill_trades(&self, symbol: &str, count: usize) -> Result<usize, String> {
+        let count = count.min(MAX_BACKFILL_TRADES);
+
+        self.rate_limiter.acquire().await;
+        let recent = self
+            .exchange_api
+            .get_recent_trades(symbol, count.min(AGG_TRADES_PAGE_LIMIT))
+            .await
+            .map_err(|e| e.to_string())?;
+
+        let mut oldest_id = recent.first().map(|agg_trade| agg_trade.id);
+        let mut trades: BTreeMap<u64, Trade> = recent
+            .into_iter()
+            .map(|agg_trade| (agg_trade.id, agg_trade.trade))
+            .collect();
+
+        while trades.len() < count {
+            let before_id = match oldest_id {
+                Some(id) if id > 0 => id,
+                _ => break,
+            };
+
+            self.rate_limiter.acquire().await;
+            let page = self
+                .exchange_api
+                .get_historical_trades(
+                    symbol,
+                    before_id.saturating_sub(AGG_TRADES_PAGE_LIMIT as u64),
+                )
+                .await
+                .map_err(|e| e.to_string())?;
+
+            let page: Vec<_> = page
+                .into_iter()
+                .filter(|agg_trade| agg_trade.id < before_id)
+                .collect();
+            if page.is_empty() {
+                break;
+            }
+
+            oldest_id = page.first().map(|agg_trade| agg_trade.id);
+            trades.extend(
+                page.into_iter()
+                    .map(|agg_trade| (agg_trade.id, agg_trade.trade)),
+            );
+        }
+
+        // the last page may reach further back than needed
+        let mut trades: Vec<Trade> = trades.into_values().collect();
+        let mut trades = trades.split_off(trades.len().saturating_sub(count));
+        trades.sort_by_key(|trade| trade.timestamp);
+
+        if trades.is_empty() {
+            return Ok(0);
+        }
+
+        self.storage_manager
+            .save_trades(&trades, &build_market_trade_key(symbol), true)
+            .await
+            .map_err(|e| e.to_string())?;
+
+        info!("Backfilled {} trades for {symbol}", trades.len());
+        Ok(trades.len())
+    }
+}
@@ -0,0 +1,303 @@
+use std::collections::VecDeque;
+use std::sync::{
+    atomic::{AtomicU64, AtomicUsize, Ordering},
+    Arc, Mutex,
+};
+
+use tokio::sync::{mpsc::error::SendError, Notify};
+
+use crate::market::messages::MarketMessage;
+
+/// Number of market messages buffered when `MARKET_CHANNEL_CAPACITY` isn't set.
+pub const DEFAULT_MARKET_CHANNEL_CAPACITY: usize = 10_000;
+
+/// What a sender does with a market message once the channel is full.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    /// The oldest buffered message which can be dropped is replaced, the message itself is dropped
+    /// if none can be. Used for messages superseded by the next one, ie. tickers.
+    DropOldest,
+    /// The sender waits until the receiver frees a slot. Used for messages which can't be lost,
+    /// ie. closed klines.
+    Block,
+}
+
+/// Parses the `MARKET_CHANNEL_CAPACITY` setting, invalid or zero values use the default capacity.
+
+pub fn capacity_from_setting(setting: &str) -> usize {
+    match setting.parse() {
+        Ok(capacity) if capacity > 0 => capacity,
+        _ => DEFAULT_MARKET_CHANNEL_CAPACITY,
+    }
+}
+
+/// Creates a bounded channel for market messages, the overflow policy of each message is used
+/// once `capacity` messages are buffered.
+///
+/// # Arguments
+///
+/// * `capacity` - Maximum number of buffered messages.
+///
+/// # Returns
+///
+/// A tuple of the `MarketSender` and `MarketReceiver` of the channel.
+
+pub fn build_market_channel(capacity: usize) -> (MarketSender, MarketReceiver) {
+    let shared = Arc::new(Shared {
+        messages: Mutex::new(VecDeque::with_capacity(capacity)),
+        capacity: capacity.max(1),
+        senders: AtomicUsize::new(1),
+        receivers: AtomicUsize::new(1),
+        drops: AtomicU64::new(0),
+        received: Notify::new(),
+        freed: Notify::new(),
+    });
+
+    (
+        MarketSender {
+            shared: shared.clone(),
+        },
+        MarketReceiver { shared },
+    )
+}
+
+/// State shared by both sides of a market channel.
+#[derive(Debug)]
+struct Shared {
+    messages: Mutex<VecDeque<MarketMessage>>,
+    capacity: usize,
+    senders: AtomicUsize,
+    receivers: AtomicUsize,
+    drops: AtomicU64,
+    /// Notified when a message is buffered or the last sender is dropped.
+    received: Notify,
+    /// Notified when a slot is freed or the last receiver is dropped.
+    freed: Notify,
+}
+
+/// Sending side of a market channel, cloned for each exchange stream.
+
+#[derive(Debug)]
+pub struct MarketSender {
+    shared: Arc<Shared>,
+}
+
+impl MarketSender {
+    /// Buffers a market message, applying its overflow policy if the channel is full.
+    ///
+    /// # Arguments
+    ///
+    /// * `message` - The market message to send.
+    ///
+    /// # Returns
+    ///
+    /// `Ok` once the message is buffered or dropped by its overflow policy, or the message back if
+    /// every receiver has been dropped.
+
+    pub async fn send(&self, message: MarketMessage) -> Result<(), SendError<MarketMessage>> {
+        let policy = message.overflow_policy();
+        let mut message = Some(message);
+
+        loop {
+            let freed = self.shared.freed.notified();
+            tokio::pin!(freed);
+            // register before checking for space so a slot freed in between isn't missed
+            freed.as_mut().enable();
+
+            {
+                let mut messages = self.shared.messages.lock().unwrap();
+
+                if self.shared.receivers.load(Ordering::SeqCst) == 0 {
+                    return Err(SendError(message.take().unwrap()));
+                }
+
+                if messages.len() < self.shared.capacity {
+                    messages.push_back(message.take().unwrap());
+                    self.shared.received.notify_one();
+                    return Ok(());
+                }
+
+                if policy == OverflowPolicy::DropOldest {
+                    self.shared.drops.fetch_add(1, Ordering::Relaxed);
+
+                    let oldest = messages
+                        .iter()
+                        .position(|buffered| buffered.overflow_policy() == policy);
+
+                    if let Some(index) = oldest {
+                        messages.remove(index);
+                        messages.push_back(message.take().unwrap());
+                        self.shared.received.notify_one();
+                    }
+                    return Ok(());
+                }
+            }
+
+            freed.await;
+        }
+    }
+}
+
+impl Clone for MarketSender {
+    fn clone(&self) -> Self {
+        self.shared.senders.fetch_add(1, Ordering::SeqCst);
+        Self {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl Drop for MarketSender {
+    fn drop(&mut self) {
+        if self.shared.senders.fetch_sub(1, Ordering::SeqCst) == 1 {
+            // wake the receiver so it sees the channel is closed
+            let _messages = self.shared.messages.lock().unwrap();
+            self.shared.received.notify_waiters();
+            self.shared.received.notify_one();
+        }
+    }
+}
+
+/// Receiving side of a market channel.
+
+#[derive(Debug)]
+pub struct MarketReceiver {
+    shared: Arc<Shared>,
+}
+
+impl MarketReceiver {
+    /// Receives the next buffered market message, waiting for one to be sent.
+    ///
+    /// # Returns
+    ///
+    /// The oldest buffered message, or `None` once every sender has been dropped and the channel
+    /// is empty.
+
+    pub async fn recv(&self) -> Option<MarketMessage> {
+        loop {
+            let received = self.shared.received.notified();
+            tokio::pin!(received);
+            received.as_mut().enable();
+
+            {
+                let mut messages = self.shared.messages.lock().unwrap();
+
+                if let Some(message) = messages.pop_front() {
+                    self.shared.freed.notify_waiters();
+                    return Some(message);
+                }
+
+                if self.shared.senders.load(Ordering::SeqCst) == 0 {
+                    return None;
+                }
+            }
+
+            received.await;
+        }
+    }
+
+    /// Number of messages currently buffered.
+
+    pub fn buffered(&self) -> usize {
+        self.shared.messages.lock().unwrap().len()
+    }
+
+    /// Number of messages dropped by the overflow policy since the channel was created.
+
+    pub fn drops(&self) -> u64 {
+        self.shared.drops.load(Ordering::Relaxed)
+    }
+}
+
+impl Clone for MarketReceiver {
+    fn clone(&self) -> Self {
+        self.shared.receivers.fetch_add(1, Ordering::SeqCst);
+        Self {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl Drop for MarketReceiver {
+    fn drop(&mut self) {
+        if self.shared.receivers.fetch_sub(1, Ordering::SeqCst) == 1 {
+            // wake blocked senders so they see the channel is closed
+            let _messages = self.shared.messages.lock().unwrap();
+            self.shared.freed.notify_waiters();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::time::Duration;
+
+    use tokio::{test, time::timeout};
+
+    use crate::{
+        market::{kline::Kline, ticker::Ticker},
+        utils::time::generate_ts,
+    };
+
+    fn ticker(last_price: f64) -> MarketMessage {
+        MarketMessage::UpdateTicker(Ticker {
+            time: generate_ts(),
+            symbol: "BTCUSDT".to_string(),
+            high: last_price,
+            low: last_price,
+            traded_vol: 0.0,
+            last_price,
+            open_price: last_price,
+        })
+    }
+
+    fn kline(open_time: u64) -> MarketMessage {
+        MarketMessage::UpdateKline(Kline {
+            symbol: "BTCUSDT".to_string(),
+            open_time,
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    async fn test_flooded_channel_applies_overflow_policy() {
+        let capacity = 10;
+        let (market_tx, market_rx) = build_market_channel(capacity);
+
+        // tickers beyond capacity replace the oldest buffered tickers
+        for i in 0..100 {
+            market_tx.send(ticker(i as f64)).await.unwrap();
+        }
+        assert_eq!(market_rx.buffered(), capacity);
+        assert_eq!(market_rx.drops(), 90);
+
+        match market_rx.recv().await {
+            Some(MarketMessage::UpdateTicker(ticker)) => assert_eq!(ticker.last_price, 90.0),
+            message => panic!("Expected a ticker, got {message:?}"),
+        }
+
+        // klines fill the freed slot, then block until the receiver frees another
+        market_tx.send(kline(0)).await.unwrap();
+        let blocked = timeout(Duration::from_millis(50), market_tx.send(kline(1))).await;
+        assert!(blocked.is_err());
+        assert_eq!(market_rx.buffered(), capacity);
+
+        let sender = market_tx.clone();
+        let handle = tokio::spawn(async move { sender.send(kline(2)).await });
+        market_rx.recv().await.unwrap();
+        handle.await.unwrap().unwrap();
+
+        // klines are never dropped
+        assert_eq!(market_rx.drops(), 90);
+        let mut klines = 0;
+        while market_rx.buffered() > 0 {
+            if let Some(MarketMessage::UpdateKline(_)) = market_rx.recv().await {
+                klines += 1;
+            }
+        }
+        assert_eq!(klines, 2);
+    }
+}
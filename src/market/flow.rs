@@ -0,0 +1,230 @@
+use std::collections::{BTreeMap, HashMap, VecDeque};
+
+use serde::Serialize;
+
+use crate::{
+    account::trade::OrderSide,
+    market::{messages::MarketMessage, trade::Trade},
+    utils::time::SEC_AS_MILI,
+};
+
+/// Length of the trade flow window when `TRADE_FLOW_WINDOW_SECS` isn't set, in seconds.
+pub const DEFAULT_FLOW_WINDOW_SECS: u64 = 60;
+
+/// Trade flow of a symbol over the rolling window.
+
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+pub struct FlowStats {
+    pub buy_volume: f64,
+    pub sell_volume: f64,
+    pub trade_count: usize,
+    /// Average quantity of the trades within the window, 0 if there are none.
+    pub avg_trade_size: f64,
+    /// Length of the window in milliseconds.
+    pub window: u64,
+}
+
+/// Trades of a symbol within the window, with running volume totals.
+#[derive(Debug, Default)]
+struct SymbolFlow {
+    trades: VecDeque<(u64, OrderSide, f64)>,
+    buy_volume: f64,
+    sell_volume: f64,
+}
+
+impl SymbolFlow {
+    /// Removes trades made before a timestamp from the totals.
+    fn evict(&mut self, before_ts: u64) {
+        while let Some(&(timestamp, side, qty)) = self.trades.front() {
+            if timestamp >= before_ts {
+                break;
+            }
+
+            self.trades.pop_front();
+            match side {
+                OrderSide::Buy => self.buy_volume -= qty,
+                OrderSide::Sell => self.sell_volume -= qty,
+            }
+        }
+
+        // running totals are reset so float error doesn't accumulate
+        if self.trades.is_empty() {
+            self.buy_volume = 0.0;
+            self.sell_volume = 0.0;
+        }
+    }
+}
+
+/// Rolling buy and sell volume of each symbol, fed by the trades streamed to the market.
+///
+/// Trades are evicted as the window moves, rather than the window being recalculated, so stats
+/// are served without reading storage.
+
+#[derive(Debug)]
+pub struct TradeFlow {
+    window: u64,
+    symbols: HashMap<String, SymbolFlow>,
+}
+
+impl TradeFlow {
+    /// Creates an empty trade flow.
+    ///
+    /// # Arguments
+    ///
+    /// * `window` - Length of the rolling window in milliseconds.
+    ///
+    /// # Returns
+    ///
+    /// A new `TradeFlow`.
+
+    pub fn new(window: u64) -> Self {
+        Self {
+            window: window.max(1),
+            symbols: HashMap::new(),
+        }
+    }
+
+    /// Parses the `TRADE_FLOW_WINDOW_SECS` setting, empty or invalid values use the default
+    /// window.
+
+    pub fn window_from_setting(setting: &str) -> u64 {
+        match setting.parse::<u64>() {
+            Ok(secs) if secs > 0 => secs * SEC_AS_MILI,
+            _ => DEFAULT_FLOW_WINDOW_SECS * SEC_AS_MILI,
+        }
+    }
+
+    /// Sets the length of the rolling window in milliseconds, trades outside a shorter window are
+    /// evicted with the next trade of their symbol.
+
+    pub fn set_window(&mut self, window: u64) {
+        self.window = window.max(1);
+    }
+
+    /// Adds the trades of market messages to the flow.
+
+    pub fn handle_message(&mut self, message: &MarketMessage) {
+        if let MarketMessage::UpdateMarketTrade(trade) = message {
+            self.add_trade(trade);
+        }
+    }
+
+    /// Adds a trade to the flow of its symbol, evicting trades which fell out of the window
+    /// ending at the trade. Trades older than the window are ignored.
+
+    pub fn add_trade(&mut self, trade: &Trade) {
+        let flow = self.symbols.entry(trade.symbol.clone()).or_default();
+
+        let newest = flow
+            .trades
+            .back()
+            .map_or(trade.timestamp, |&(timestamp, _, _)| {
+                timestamp.max(trade.timestamp)
+            });
+        let window_start = newest.saturating_sub(self.window);
+        flow.evict(window_start);
+
+        if trade.timestamp < window_start {
+            return;
+        }
+
+        match trade.order_side {
+            OrderSide::Buy => flow.buy_volume += trade.qty,
+            OrderSide::Sell => flow.sell_volume += trade.qty,
+        }
+
+        // trades arrive in order, late trades are inserted so eviction stays in time order
+        let index = flow
+            .trades
+            .partition_point(|&(timestamp, _, _)| timestamp <= trade.timestamp);
+        flow.trades
+            .insert(index, (trade.timestamp, trade.order_side, trade.qty));
+    }
+
+    /// Trade flow of each symbol over the window ending at a timestamp.
+    ///
+    /// # Arguments
+    ///
+    /// * `now` - End of the window, ie. the current time.
+    ///
+    /// # Returns
+    ///
+    /// The stats of each symbol with trades within the window, by symbol.
+
+    pub fn stats(&mut self, now: u64) -> BTreeMap<String, FlowStats> {
+        let window_start = now.saturating_sub(self.window);
+        let window = self.window;
+
+        self.symbols.retain(|_, flow| {
+            flow.evict(window_start);
+            !flow.trades.is_empty()
+        });
+
+        self.symbols
+            .iter()
+            .map(|(symbol, flow)| {
+                let trade_count = flow.trades.len();
+                let stats = FlowStats {
+                    buy_volume: flow.buy_volume,
+                    sell_volume: flow.sell_volume,
+                    trade_count,
+                    avg_trade_size: (flow.buy_volume + flow.sell_volume) / trade_count as f64,
+                    window,
+                };
+                (symbol.clone(), stats)
+            })
+            .collect()
+    }
+}
+
+impl Default for TradeFlow {
+    fn default() -> Self {
+        Self::new(DEFAULT_FLOW_WINDOW_SECS * SEC_AS_MILI)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use tokio::test;
+
+    use crate::testutil::make_trades;
+
+    #[test]
+    async fn test_trade_flow_evicts_trades_outside_window() {
+        let start = 1_700_000_000_000;
+        let mut flow = TradeFlow::new(60 * SEC_AS_MILI);
+
+        // buy, buy, sell 20 seconds apart
+        for trade in make_trades("BTCUSDT", start, 20 * SEC_AS_MILI, &[100.0, 101.0, 100.5]) {
+            flow.handle_message(&MarketMessage::UpdateMarketTrade(trade));
+        }
+        let mut trade = make_trades("ETHUSDT", start, 0, &[10.0]).remove(0);
+        trade.qty = 4.0;
+        flow.add_trade(&trade);
+
+        let stats = flow.stats(start + 40 * SEC_AS_MILI);
+        assert_eq!(stats["BTCUSDT"].trade_count, 3);
+        assert_eq!(stats["BTCUSDT"].buy_volume, 2.0);
+        assert_eq!(stats["BTCUSDT"].sell_volume, 1.0);
+        assert_eq!(stats["BTCUSDT"].avg_trade_size, 1.0);
+        assert_eq!(stats["ETHUSDT"].avg_trade_size, 4.0);
+
+        // a trade past the window evicts the first trade of its symbol
+        let mut trade = make_trades("BTCUSDT", start + 70 * SEC_AS_MILI, 0, &[99.0]).remove(0);
+        trade.order_side = OrderSide::Sell;
+        trade.qty = 2.0;
+        flow.add_trade(&trade);
+
+        let stats = flow.stats(start + 70 * SEC_AS_MILI);
+        assert_eq!(stats["BTCUSDT"].trade_count, 3);
+        assert_eq!(stats["BTCUSDT"].buy_volume, 1.0);
+        assert_eq!(stats["BTCUSDT"].sell_volume, 3.0);
+        // symbols without trades in the window are dropped
+        assert!(!stats.contains_key("ETHUSDT"));
+
+        // once the window moves past every trade nothing is left
+        assert!(flow.stats(start + 200 * SEC_AS_MILI).is_empty());
+    }
+}
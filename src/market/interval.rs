@@ -4,6 +4,12 @@ use serde::{Deserialize, Serialize};
 
 use crate::utils::time::{DAY_AS_MILI, HOUR_AS_MILI, MIN_AS_MILI};
 
+/// Interval of a kline.
+///
+/// Intervals are serialized as the same string as their `Display`, ie. `"5m"`, in every context
+/// including the API, CSV files and Mongo documents. Unknown strings are rejected with an error
+/// by both serde and `TryFrom<&str>`.
+
 #[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub enum Interval {
     #[serde(rename = "1m")]
@@ -19,25 +25,74 @@ pub enum Interval {
 }
 
 impl Interval {
+    /// All supported intervals, ordered from shortest to longest.
+    pub const ALL: [Interval; 5] = [
+        Interval::Min1,
+        Interval::Min5,
+        Interval::Min15,
+        Interval::Hour1,
+        Interval::Day1,
+    ];
+
     pub fn to_duration(&self) -> Duration {
-        match self {
-            Interval::Min1 => Duration::from_millis(MIN_AS_MILI),
-            Interval::Min5 => Duration::from_millis(MIN_AS_MILI * 5),
-            Interval::Min15 => Duration::from_millis(MIN_AS_MILI * 15),
-            Interval::Hour1 => Duration::from_millis(HOUR_AS_MILI),
-            Interval::Day1 => Duration::from_millis(DAY_AS_MILI),
-        }
+        Duration::from_millis(self.to_mili())
     }
 
     pub fn to_mili(&self) -> u64 {
         match self {
             Interval::Min1 => MIN_AS_MILI,
-            Interval::Min5 => MIN_AS_MILI,
+            Interval::Min5 => MIN_AS_MILI * 5,
             Interval::Min15 => MIN_AS_MILI * 15,
             Interval::Hour1 => HOUR_AS_MILI,
             Interval::Day1 => DAY_AS_MILI,
         }
     }
+
+    /// Finds the interval matching an exact length in milliseconds.
+    ///
+    /// # Arguments
+    ///
+    /// * `millis` - The interval length in milliseconds.
+    ///
+    /// # Returns
+    ///
+    /// `Some(Interval)` if a supported interval has the given length, otherwise `None`.
+
+    pub fn from_millis(millis: u64) -> Option<Interval> {
+        Interval::ALL
+            .into_iter()
+            .find(|interval| interval.to_mili() == millis)
+    }
+
+    /// Counts the number of whole intervals between two timestamps.
+    ///
+    /// # Arguments
+    ///
+    /// * `from_ts` - The start timestamp in milliseconds.
+    /// * `to_ts` - The end timestamp in milliseconds.
+    ///
+    /// # Returns
+    ///
+    /// The number of complete intervals in the range, 0 if `from_ts` is after `to_ts`.
+
+    pub fn count_between(&self, from_ts: u64, to_ts: u64) -> u64 {
+        to_ts.saturating_sub(from_ts) / self.to_mili()
+    }
+
+    /// Checks whether this interval is a whole multiple of another interval,
+    /// ie. klines of `other` can be combined into klines of this interval.
+    ///
+    /// # Arguments
+    ///
+    /// * `other` - The shorter interval to compare against.
+    ///
+    /// # Returns
+    ///
+    /// `true` if this interval length divides evenly by `other`.
+
+    pub fn is_multiple_of(&self, other: Interval) -> bool {
+        self.to_mili() % other.to_mili() == 0
+    }
 }
 
 impl TryFrom<&str> for Interval {
@@ -72,3 +127,88 @@ impl Display for Interval {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_mili() {
+        assert_eq!(Interval::Min1.to_mili(), 60_000);
+        assert_eq!(Interval::Min5.to_mili(), 300_000);
+        assert_eq!(Interval::Min15.to_mili(), 900_000);
+        assert_eq!(Interval::Hour1.to_mili(), 3_600_000);
+        assert_eq!(Interval::Day1.to_mili(), 86_400_000);
+    }
+
+    #[test]
+    fn test_from_millis() {
+        for interval in Interval::ALL {
+            assert_eq!(Interval::from_millis(interval.to_mili()), Some(interval));
+        }
+
+        assert_eq!(Interval::from_millis(0), None);
+        assert_eq!(Interval::from_millis(MIN_AS_MILI * 2), None);
+        assert_eq!(Interval::from_millis(MIN_AS_MILI * 5 + 1), None);
+    }
+
+    #[test]
+    fn test_count_between() {
+        let from_ts = 1_700_000_000_000;
+
+        assert_eq!(Interval::Min1.count_between(from_ts, from_ts), 0);
+        assert_eq!(
+            Interval::Min1.count_between(from_ts, from_ts + HOUR_AS_MILI),
+            60
+        );
+        assert_eq!(
+            Interval::Min5.count_between(from_ts, from_ts + HOUR_AS_MILI),
+            12
+        );
+        assert_eq!(
+            Interval::Min15.count_between(from_ts, from_ts + HOUR_AS_MILI + MIN_AS_MILI * 14),
+            4
+        );
+        assert_eq!(
+            Interval::Hour1.count_between(from_ts, from_ts + DAY_AS_MILI),
+            24
+        );
+        assert_eq!(
+            Interval::Day1.count_between(from_ts, from_ts + DAY_AS_MILI - 1),
+            0
+        );
+
+        // reversed range
+        assert_eq!(
+            Interval::Min1.count_between(from_ts + HOUR_AS_MILI, from_ts),
+            0
+        );
+    }
+
+    #[test]
+    fn test_is_multiple_of() {
+        assert!(Interval::Min5.is_multiple_of(Interval::Min1));
+        assert!(Interval::Min15.is_multiple_of(Interval::Min5));
+        assert!(Interval::Hour1.is_multiple_of(Interval::Min15));
+        assert!(Interval::Day1.is_multiple_of(Interval::Hour1));
+        assert!(Interval::Min5.is_multiple_of(Interval::Min5));
+
+        assert!(!Interval::Min1.is_multiple_of(Interval::Min5));
+        assert!(!Interval::Hour1.is_multiple_of(Interval::Day1));
+    }
+
+    #[test]
+    fn test_serde_matches_display() {
+        for interval in Interval::ALL {
+            let json = serde_json::to_string(&interval).unwrap();
+            assert_eq!(json, format!("\"{interval}\""));
+            assert_eq!(serde_json::from_str::<Interval>(&json).unwrap(), interval);
+            assert_eq!(Interval::try_from(interval.to_string()), Ok(interval));
+        }
+
+        assert!(serde_json::from_str::<Interval>("\"2m\"").is_err());
+        assert!(serde_json::from_str::<Interval>("\"Min1\"").is_err());
+        assert!(Interval::try_from("2m").is_err());
+        assert!(Interval::try_from("").is_err());
+    }
+}
@@ -1,5 +1,6 @@
 // #![feature(btree_extract_if)]
 use std::collections::{BTreeMap, HashMap};
+use std::io::Read;
 
 use log::info;
 use mongodb::{
@@ -15,9 +16,13 @@ use crate::{
     account::trade::OrderSide,
     exchange::types::ApiResult,
     market::{interval::Interval, market::MarketDataSymbol},
+    storage::schema::DataRow,
     utils::{
         number::parse_f64_from_lookup,
-        time::{calculate_kline_open_time, generate_ts, timestamp_to_string},
+        time::{
+            calculate_kline_open_time, floor_mili_ts, generate_ts, interval_to_millis,
+            timestamp_to_string,
+        },
     },
 };
 
@@ -56,6 +61,9 @@ impl KlineMeta {
 pub struct KlineData {
     pub meta: KlineMeta,
     klines: BTreeMap<u64, Kline>,
+    /// Open time of the kline still in progress, which is kept in memory until it closes.
+    #[serde(skip)]
+    in_progress: Option<u64>,
 }
 
 impl KlineData {
@@ -67,6 +75,7 @@ impl KlineData {
         Self {
             meta: KlineMeta::new(symbol, interval),
             klines: BTreeMap::new(),
+            in_progress: None,
         }
     }
 
@@ -76,30 +85,134 @@ impl KlineData {
     pub fn add_kline(&mut self, kline: Kline) {
         self.meta.last_update = generate_ts();
 
+        // the kline in progress is complete once it or a later kline closes
+        if self
+            .in_progress
+            .is_some_and(|open_time| open_time <= kline.open_time)
+        {
+            self.in_progress = None;
+        }
+
         self.klines.insert(kline.open_time, kline.clone());
         self.meta.len = self.klines.len();
     }
 
+    /// Adds a kline whose interval is still in progress, replacing any existing kline with the
+    /// same open time.
+    ///
+    /// The kline is kept in memory but not drained for backup until a closed update of it, or of
+    /// a later kline, is added.
+
+    pub fn add_partial_kline(&mut self, kline: Kline) {
+        let open_time = kline.open_time;
+        self.add_kline(kline);
+        self.in_progress = Some(open_time);
+    }
+
     pub fn klines(&self) -> Vec<Kline> {
         self.klines.values().cloned().collect()
     }
 
+    /// The latest kline of the data set, closed unless it's the kline still in progress.
+
+    pub fn latest(&self) -> Option<LatestKline> {
+        self.klines.values().last().map(|kline| LatestKline {
+            kline: kline.clone(),
+            closed: self.in_progress != Some(kline.open_time),
+        })
+    }
+
+    /// Klines opened before a timestamp, excluding the kline in progress, which `drain_klines`
+    /// would remove.
+
+    pub fn klines_before(&self, before_ts: u64) -> Vec<Kline> {
+        self.klines
+            .values()
+            .filter(|kline| {
+                kline.open_time < before_ts && Some(kline.open_time) != self.in_progress
+            })
+            .cloned()
+            .collect()
+    }
+
     pub fn drain_klines(&mut self, before_ts: u64) -> Vec<Kline> {
         // info!(
         //     "Removing all klines before {} ...",
         //     timestamp_to_string(before_ts)
         // );
+        let in_progress = self.in_progress;
         let mut klines = vec![];
         for kline in self.klines.values() {
-            if kline.open_time < before_ts {
+            if kline.open_time < before_ts && Some(kline.open_time) != in_progress {
                 klines.push(kline.clone())
             }
         }
-        self.klines.retain(|k, _v| k >= &before_ts);
+        self.klines
+            .retain(|k, _v| k >= &before_ts || Some(*k) == in_progress);
         self.meta.len = self.klines.len();
 
         klines
     }
+
+    /// Combines the klines into klines of a longer interval.
+    ///
+    /// Klines of the longer interval are only built where every kline within it is present, so a
+    /// gap in the data doesn't produce a kline with a misleading range or volume.
+    ///
+    /// # Arguments
+    ///
+    /// * `interval` - The interval to resample to, a whole multiple of the interval of the data.
+    ///
+    /// # Returns
+    ///
+    /// The resampled `KlineData`, or `None` if the interval can't be built from the klines.
+
+    pub fn resample(&self, interval: Interval) -> Option<KlineData> {
+        let base = self.meta.interval;
+        if interval < base || !interval.is_multiple_of(base) {
+            return None;
+        }
+
+        let per_kline = (interval.to_mili() / base.to_mili()) as usize;
+        let mut buckets: BTreeMap<u64, Vec<&Kline>> = BTreeMap::new();
+        for kline in self.klines.values() {
+            buckets
+                .entry(floor_mili_ts(kline.open_time, interval.to_mili()))
+                .or_default()
+                .push(kline);
+        }
+
+        let mut resampled = KlineData::new(&self.meta.symbol, interval);
+        for (open_time, klines) in buckets {
+            if klines.len() < per_kline {
+                continue;
+            }
+
+            resampled.add_kline(Kline {
+                symbol: self.meta.symbol.clone(),
+                interval,
+                open: klines[0].open,
+                high: klines.iter().map(|k| k.high).fold(f64::MIN, f64::max),
+                low: klines.iter().map(|k| k.low).fold(f64::MAX, f64::min),
+                close: klines[klines.len() - 1].close,
+                volume: klines.iter().map(|k| k.volume).sum(),
+                open_time,
+                close_time: open_time + interval.to_mili() - 1,
+                quote_volume: klines.iter().map(|k| k.quote_volume).sum(),
+            });
+        }
+
+        Some(resampled)
+    }
+}
+
+/// The latest kline of a symbol and whether its interval has closed.
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct LatestKline {
+    #[serde(flatten)]
+    pub kline: Kline,
+    pub closed: bool,
 }
 
 /// Represents a single kline or candlestick data point, including open, high, low, close, and volume information.
@@ -117,6 +230,9 @@ pub struct Kline {
     pub volume: f64,
     pub open_time: u64,
     pub close_time: u64,
+    /// Volume in the quote asset, ie. USDT, 0 for exchanges and records which don't provide it.
+    #[serde(default)]
+    pub quote_volume: f64,
 }
 
 impl Default for Kline {
@@ -131,6 +247,7 @@ impl Default for Kline {
             close: 42.2,
             volume: 42.2,
             close_time: 42,
+            quote_volume: 0.0,
         }
     }
 }
@@ -147,6 +264,7 @@ impl Kline {
             qty: self.volume / 2.0,
             price: self.close,
             order_side: OrderSide::Sell,
+            id: None,
         };
         let trade_buy = Trade {
             symbol: self.symbol.to_string(),
@@ -154,6 +272,7 @@ impl Kline {
             qty: self.volume / 2.0,
             price: self.close,
             order_side: OrderSide::Buy,
+            id: None,
         };
         vec![trade_buy, trade_sell]
     }
@@ -224,6 +343,7 @@ impl Kline {
         let low = parse_f64_from_lookup("l", &_kline)?;
 
         let volume = parse_f64_from_lookup("v", &_kline)?;
+        let quote_volume = parse_f64_from_lookup("q", &_kline).unwrap_or(0.0);
 
         Ok(Self {
             interval: interval.try_into()?,
@@ -235,9 +355,24 @@ impl Kline {
             close,
             volume,
             close_time,
+            quote_volume,
         })
     }
 
+    /// Parses whether the kline of a Binance kline stream frame is closed, from its `x` flag.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the kline's interval has closed, frames without the flag are treated as closed.
+
+    pub fn is_closed_in_binance_lookup(lookup: &HashMap<String, Value>) -> bool {
+        lookup
+            .get("k")
+            .and_then(|kline| kline.get("x"))
+            .and_then(|closed| closed.as_bool())
+            .unwrap_or(true)
+    }
+
     /// Constructs a kline from a lookup hashmap containing kline data from BingX.
     ///
     /// This method is similar to `from_binance_lookup` but tailored for parsing kline data specific to BingX's API.
@@ -288,6 +423,8 @@ impl Kline {
             close,
             volume,
             close_time,
+            // BingX klines have no quote volume
+            quote_volume: 0.0,
         })
     }
 
@@ -320,7 +457,7 @@ impl Kline {
 
         let close_time = data.get("T").unwrap().as_u64().unwrap();
 
-        let open_time = calculate_kline_open_time(close_time, interval.clone().try_into()?);
+        let open_time = (close_time + 1).saturating_sub(interval_to_millis(&interval)?);
 
         let open = parse_f64_from_lookup("o", &data)?;
         let close = parse_f64_from_lookup("c", &data)?;
@@ -340,6 +477,7 @@ impl Kline {
             close,
             volume,
             close_time,
+            quote_volume: 0.0,
         })
     }
 }
@@ -354,6 +492,48 @@ impl MarketDataSymbol for Kline {
     }
 }
 
+/// Layout of klines in bincode files written before schema version 2, without the quote volume.
+#[derive(Deserialize)]
+struct BincodeKlineV1 {
+    symbol: String,
+    interval: Interval,
+    open: f64,
+    high: f64,
+    low: f64,
+    close: f64,
+    volume: f64,
+    open_time: u64,
+    close_time: u64,
+}
+
+impl DataRow for Kline {
+    type Key = u64;
+
+    fn key(&self) -> u64 {
+        self.open_time
+    }
+
+    fn read_bincode_row<R: Read>(reader: R, version: u32) -> bincode::Result<Self> {
+        if version >= 2 {
+            return bincode::deserialize_from(reader);
+        }
+
+        let kline: BincodeKlineV1 = bincode::deserialize_from(reader)?;
+        Ok(Kline {
+            symbol: kline.symbol,
+            interval: kline.interval,
+            open: kline.open,
+            high: kline.high,
+            low: kline.low,
+            close: kline.close,
+            volume: kline.volume,
+            open_time: kline.open_time,
+            close_time: kline.close_time,
+            quote_volume: 0.0,
+        })
+    }
+}
+
 /// Represents a kline data structure specifically formatted to match Binance's API response.
 ///
 /// This struct is tailored to match the kline data format returned by Binance's API, including additional fields like quote volume and trade count.
@@ -1,21 +1,28 @@
 use futures::StreamExt;
 
-use log::info;
+use log::{info, warn};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
 use std::collections::BTreeMap;
 use std::time::{Duration, SystemTime};
-use std::{collections::HashMap, sync::Arc};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
 
 // use tokio::time::{self, Duration};
 
 use crate::exchange::api::ExchangeInfo;
+use crate::exchange::halt::TradingHalts;
+use crate::exchange::rate_limit::RateLimiter;
 use crate::exchange::stream::build_stream_id;
-use crate::exchange::types::{ApiResult, StreamType};
+use crate::exchange::types::{ApiError, ApiResult, StreamType};
 use crate::market::interval::Interval;
-use crate::utils::kline::{build_kline_key, build_ticker_key};
-use crate::utils::time::{floor_mili_ts, interval_to_millis, MIN_AS_MILI, SEC_AS_MILI};
+use crate::utils::kline::{
+    build_kline_key, build_ticker_key, find_missing_open_times, MAX_GAP_CHECK_KLINES,
+};
+use crate::utils::time::{floor_mili_ts, DAY_AS_MILI, HOUR_AS_MILI, MIN_AS_MILI, SEC_AS_MILI};
 use crate::utils::trade::build_market_trade_key;
 use crate::{
     exchange::{
@@ -23,11 +30,20 @@ use crate::{
         stream::{StreamManager, StreamMeta},
     },
     market::{
-        kline::{Kline, KlineData, KlineMeta},
+        alert::AlertManager,
+        backfill::Backfiller,
+        channel::MarketReceiver,
+        flow::{FlowStats, TradeFlow},
+        kline::{Kline, KlineData, KlineMeta, LatestKline},
         messages::MarketMessage,
-        ticker::{Ticker, TickerData, TickerMeta},
-        types::ArcReceiver,
+        orderbook::{OrderBookDepth, OrderBookManager},
+        reader::MarketReader,
+        recorder::MarketRecorder,
+        subscription::StreamSubscriptions,
+        symbol_info::SymbolInfoCache,
+        ticker::{Ticker, TickerData, TickerMeta, TickerSample, TickerSampling},
     },
+    notify::notifier::LogNotifier,
     storage::manager::StorageManager,
     utils::time::generate_ts,
 };
@@ -38,11 +54,18 @@ use super::types::ArcMutex;
 /// Represents the main market data structure for a trading application, managing market data streams, and integrating with exchange APIs.
 
 pub struct Market {
-    market_receiver: ArcReceiver<MarketMessage>,
+    market_receiver: MarketReceiver,
     data: ArcMutex<MarketData>,
     exchange_api: Arc<dyn ExchangeApi>,
     pub storage_manager: Arc<dyn StorageManager>,
-    needed_streams: ArcMutex<Vec<StreamMeta>>,
+    needed_streams: ArcMutex<StreamSubscriptions>,
+    recorder: ArcMutex<Option<MarketRecorder>>,
+    symbol_info: SymbolInfoCache,
+    alert_manager: ArcMutex<AlertManager>,
+    order_books: OrderBookManager,
+    trade_flow: ArcMutex<TradeFlow>,
+    trading_halts: TradingHalts,
+    rest_limiter: RateLimiter,
 }
 
 impl Market {
@@ -67,17 +90,27 @@ impl Market {
     /// An instance of `Market`, ready to process market data and interact with the exchange API.
 
     pub async fn new(
-        market_receiver: ArcReceiver<MarketMessage>,
+        market_receiver: MarketReceiver,
         exchange_api: Arc<dyn ExchangeApi>,
         storage_manager: Arc<dyn StorageManager>,
         init_workers: bool,
     ) -> Self {
+        let alert_manager =
+            AlertManager::new(storage_manager.clone(), Arc::new(LogNotifier::default())).await;
+
         let mut _self = Self {
             data: ArcMutex::new(MarketData::new(storage_manager.clone())),
             storage_manager: storage_manager.clone(),
             market_receiver,
-            exchange_api,
-            needed_streams: ArcMutex::new(vec![]),
+            exchange_api: exchange_api.clone(),
+            needed_streams: ArcMutex::new(StreamSubscriptions::default()),
+            recorder: ArcMutex::new(None),
+            symbol_info: SymbolInfoCache::new(exchange_api.clone()),
+            alert_manager: ArcMutex::new(alert_manager),
+            order_books: OrderBookManager::new(exchange_api.clone()),
+            trade_flow: ArcMutex::new(TradeFlow::default()),
+            trading_halts: TradingHalts::default(),
+            rest_limiter: RateLimiter::default(),
         };
 
         if init_workers {
@@ -105,9 +138,24 @@ impl Market {
     /// An `Option<f64>` representing the latest price of the symbol if available; otherwise, `None`.
 
     pub async fn last_price(&self, symbol: &str) -> Option<f64> {
-        let ticker = self.last_ticker(symbol).await;
+        self.reader().last_price(symbol).await
+    }
+
+    /// Fetches the latest prices of many symbols.
+    ///
+    /// Prices are served from tickers updated within the last second, the prices of all other
+    /// symbols are fetched from the exchange together.
+    ///
+    /// # Parameters
+    ///
+    /// - `symbols`: The trading symbols for which the latest prices are requested.
+    ///
+    /// # Returns
+    ///
+    /// The latest price by symbol, symbols without a price are left out.
 
-        ticker.map(|ticker| ticker.last_price)
+    pub async fn last_prices(&self, symbols: &[String]) -> HashMap<String, f64> {
+        self.reader().last_prices(symbols).await
     }
 
     /// Retrieves the most recent kline data for a specified symbol and interval.
@@ -119,70 +167,38 @@ impl Market {
     ///
     /// - `symbol`: The trading symbol for which kline data is requested.
     /// - `interval`: The time interval for the kline data.
+    /// - `closed_only`: Excludes the kline in progress, whose close and volume are still incomplete.
     ///
     /// # Returns
     ///
     /// An `Option<Kline>` containing the most recent kline data if available; otherwise, `None`.
 
-    pub async fn last_kline(&self, symbol: &str, interval: Interval) -> Option<Kline> {
-        let last_open_time = generate_ts() - interval.to_mili();
-
-        let kline = match self
-            .data
-            .lock()
-            .await
-            .kline_data(symbol, interval, Some(last_open_time), None, None)
+    pub async fn last_kline(
+        &self,
+        symbol: &str,
+        interval: Interval,
+        closed_only: bool,
+    ) -> Option<Kline> {
+        self.reader()
+            .last_kline(symbol, interval, closed_only)
             .await
-        {
-            Some(kline_data) => {
-                // info!("Getting Kline from kline_data on on Market");
-                kline_data.klines().last().cloned()
-            }
-            None => {
-                // info!("Getting kline from remote API, kline_data doesn't exist on Market");
-                let kline = match self.exchange_api.get_kline(symbol, interval).await {
-                    Ok(kline) => Some(kline),
-                    Err(_e) => None,
-                };
-                kline
-            }
-        };
-
-        kline
     }
 
-    /// Retrieves the most recent ticker data for a specified symbol.
+    /// Retrieves the best bid and ask along with the top levels of the order book of a symbol.
     ///
-    /// This method fetches the latest ticker data, which includes the last trade price among other
-    /// information, crucial for real-time market analysis.
+    /// The order book is only maintained while a depth stream for the symbol is open.
     ///
     /// # Parameters
     ///
-    /// - `symbol`: The trading symbol for which ticker data is requested.
+    /// - `symbol`: The trading symbol of the order book.
+    /// - `levels`: The number of levels to return per side.
     ///
     /// # Returns
     ///
-    /// An `Option<Ticker>` containing the most recent ticker data if available; otherwise, `None`.
+    /// An `Option<OrderBookDepth>` if an order book is maintained for the symbol; otherwise, `None`.
 
-    pub async fn last_ticker(&self, symbol: &str) -> Option<Ticker> {
-        // must be within the last second
-        let last_sec = generate_ts() - SEC_AS_MILI;
-        let ticker = match self.data.lock().await.ticker_data(symbol, last_sec) {
-            Some(ticker_data) => {
-                // info!("Getting Ticker from ticker_data on on Market");
-
-                ticker_data.tickers().last().cloned()
-            }
-            None => {
-                // info!("Getting Ticker remote API ticker_data older than 1 second or not found on Market");
-                let ticker = match self.exchange_api.get_ticker(symbol).await {
-                    Ok(ticker) => Some(ticker),
-                    Err(_) => None,
-                };
-                ticker
-            }
-        };
-        ticker
+    pub async fn order_book_depth(&self, symbol: &str, levels: usize) -> Option<OrderBookDepth> {
+        self.order_books.depth(symbol, levels).await
     }
 
     /// Fetches a range of Kline data for a specified symbol and interval, optionally filtered by timestamps and limited in size.
@@ -216,6 +232,107 @@ impl Market {
             .await
     }
 
+    /// Retrieves the latest kline of a symbol and interval however old it is, ie. to price a
+    /// position once the last price of its symbol can't be fetched.
+    ///
+    /// # Parameters
+    ///
+    /// - `symbol`: The trading symbol to retrieve the kline for.
+    /// - `interval`: The interval of the kline.
+    ///
+    /// # Returns
+    ///
+    /// The latest kline of in-memory or stored market data, or `None` if there are no klines.
+
+    pub async fn latest_kline(&self, symbol: &str, interval: Interval) -> Option<Kline> {
+        self.kline_data_range(symbol, interval, None, None, None)
+            .await
+            .and_then(|kline_data| kline_data.klines().pop())
+    }
+
+    /// Retrieves the most recent closed klines for a symbol and interval.
+    ///
+    /// The kline currently in progress is excluded, klines are read from both in-memory and
+    /// stored market data.
+    ///
+    /// # Parameters
+    ///
+    /// - `symbol`: The trading symbol to retrieve klines for.
+    /// - `interval`: The interval of the klines.
+    /// - `n`: The number of closed klines to return.
+    ///
+    /// # Returns
+    ///
+    /// A `Vec<Kline>` of at most `n` closed klines ordered by ascending open time.
+
+    pub async fn last_n_klines(&self, symbol: &str, interval: Interval, n: usize) -> Vec<Kline> {
+        if n == 0 {
+            return vec![];
+        }
+
+        // open time of the kline in progress, all klines before it are closed
+        let current_open_time = floor_mili_ts(generate_ts(), interval.to_mili());
+        let from_ts = current_open_time.saturating_sub(interval.to_mili() * n as u64);
+
+        let mut klines = match self
+            .kline_data_range(
+                symbol,
+                interval,
+                Some(from_ts),
+                Some(current_open_time - 1),
+                None,
+            )
+            .await
+        {
+            Some(kline_data) => kline_data.klines(),
+            None => vec![],
+        };
+
+        klines.retain(|kline| kline.open_time < current_open_time);
+
+        let skip = klines.len().saturating_sub(n);
+        klines.split_off(skip)
+    }
+
+    /// Finds klines missing from stored market data within a time range.
+    ///
+    /// # Parameters
+    ///
+    /// - `symbol`: The trading symbol to check.
+    /// - `interval`: The interval of the klines.
+    /// - `from_ts`: The start timestamp of the range.
+    /// - `to_ts`: The end timestamp of the range.
+    ///
+    /// # Returns
+    ///
+    /// A `Vec<u64>` of open times for every expected kline which is not stored, or an error if
+    /// the range holds more than `MAX_GAP_CHECK_KLINES` klines.
+
+    pub async fn kline_gaps(
+        &self,
+        symbol: &str,
+        interval: Interval,
+        from_ts: u64,
+        to_ts: u64,
+    ) -> Result<Vec<u64>, String> {
+        let expected = interval.count_between(from_ts, to_ts);
+        if expected > MAX_GAP_CHECK_KLINES {
+            return Err(format!(
+                "Range holds {expected} {interval} klines, at most {MAX_GAP_CHECK_KLINES} can be checked"
+            ));
+        }
+
+        let klines = match self
+            .kline_data_range(symbol, interval, Some(from_ts), Some(to_ts), None)
+            .await
+        {
+            Some(kline_data) => kline_data.klines(),
+            None => vec![],
+        };
+
+        Ok(find_missing_open_times(&klines, interval, from_ts, to_ts))
+    }
+
     // TODO: docs
     pub async fn trade_data_range(
         &self,
@@ -243,10 +360,110 @@ impl Market {
         self.data.clone()
     }
 
+    /// Returns the cache of the trading rules of symbols, used to fetch symbol info without
+    /// holding the market lock.
+
+    pub fn symbol_info(&self) -> SymbolInfoCache {
+        self.symbol_info.clone()
+    }
+
+    /// Returns a backfiller sharing the exchange, storage and rate limiter of the market, used to
+    /// backfill history without holding the market lock.
+
+    pub fn backfiller(&self) -> Backfiller {
+        Backfiller::new(
+            self.exchange_api.clone(),
+            self.storage_manager.clone(),
+            self.rest_limiter.clone(),
+        )
+    }
+
+    /// Returns a reader of the latest market data sharing the data, exchange and rate limiter of
+    /// the market, used to read prices and klines without holding the market lock while they are
+    /// fetched from the exchange.
+
+    pub fn reader(&self) -> MarketReader {
+        MarketReader::new(
+            self.data.clone(),
+            self.exchange_api.clone(),
+            self.rest_limiter.clone(),
+        )
+    }
+
     // ---
     // Stream Methods
     // ---
 
+    /// Returns the exchange API used to stream and fetch market data.
+
+    pub fn exchange_api(&self) -> Arc<dyn ExchangeApi> {
+        self.exchange_api.clone()
+    }
+
+    /// Returns the registry of price alerts evaluated against received market messages.
+
+    pub fn alert_manager(&self) -> ArcMutex<AlertManager> {
+        self.alert_manager.clone()
+    }
+
+    /// Sets the recorder used to log all received market messages, `None` stops recording.
+    ///
+    /// # Parameters
+    ///
+    /// - `recorder`: The recorder which received market messages are appended to.
+
+    pub async fn set_recorder(&self, recorder: Option<MarketRecorder>) {
+        *self.recorder.lock().await = recorder;
+    }
+
+    /// Sets the rate limit of the REST requests sent by the market and its backfillers.
+    ///
+    /// # Parameters
+    ///
+    /// - `rest_limiter`: Limiter shared by live fetches and backfills.
+
+    pub fn set_rest_limiter(&mut self, rest_limiter: RateLimiter) {
+        self.rest_limiter = rest_limiter;
+    }
+
+    /// Sets how tickers are sampled before they are persisted on backup.
+    ///
+    /// # Parameters
+    ///
+    /// - `sampling`: The ticker sampling policy, `TickerSampling::Disabled` keeps tickers in memory only.
+
+    pub async fn set_ticker_sampling(&self, sampling: TickerSampling) {
+        self.data.lock().await.ticker_sampling = sampling;
+    }
+
+    /// Sets the length of the rolling window of trade flow stats.
+    ///
+    /// # Parameters
+    ///
+    /// - `window`: Length of the window in milliseconds.
+
+    pub async fn set_trade_flow_window(&self, window: u64) {
+        self.trade_flow.lock().await.set_window(window);
+    }
+
+    /// Retrieves the rolling buy and sell volume of each symbol with streamed trades, served from
+    /// memory.
+    ///
+    /// # Returns
+    ///
+    /// The `FlowStats` of each symbol with trades within the window, by symbol.
+
+    pub async fn flow_stats(&self) -> BTreeMap<String, FlowStats> {
+        self.trade_flow.lock().await.stats(generate_ts())
+    }
+
+    /// Returns the symbols halted by the data exchange, whose streams aren't reopened until
+    /// trading resumes.
+
+    pub fn trading_halts(&self) -> TradingHalts {
+        self.trading_halts.clone()
+    }
+
     /// Retrieves a list of currently active streams within the market data instance.
     ///
     /// This method compiles a list of all streams that have been established and are actively being monitored or interacted with, providing visibility into the real-time data streams.
@@ -259,6 +476,35 @@ impl Market {
         self.exchange_api.active_streams().await
     }
 
+    /// Retrieves the current kline of every symbol with an active kline stream of an interval.
+    ///
+    /// Klines are served from in-memory market data only, symbols without a kline in memory are
+    /// left out rather than fetched from the exchange.
+    ///
+    /// # Parameters
+    ///
+    /// - `interval`: The interval of the kline streams.
+    ///
+    /// # Returns
+    ///
+    /// The latest kline by symbol in symbol order, including whether it has closed.
+
+    pub async fn latest_klines(&self, interval: Interval) -> BTreeMap<String, LatestKline> {
+        let streams = self.active_streams().await;
+        let data = self.data.lock().await;
+
+        streams
+            .into_iter()
+            .filter(|stream| {
+                matches!(stream.stream_type, StreamType::Kline) && stream.interval == Some(interval)
+            })
+            .filter_map(|stream| {
+                let latest = data.latest_kline(&stream.symbol, interval)?;
+                Some((stream.symbol, latest))
+            })
+            .collect()
+    }
+
     /// Initiates a new stream based on the specified parameters and adds it to the list of active streams.
     ///
     /// This method constructs a new stream URL and metadata for a given symbol, stream type, and optionally an interval, then requests the stream manager to open and monitor this stream.
@@ -279,6 +525,13 @@ impl Market {
         symbol: &str,
         interval: Option<Interval>,
     ) -> ApiResult<String> {
+        // user data is not market data, it is streamed to the account
+        if let StreamType::UserData = stream_type {
+            return Err(ApiError::Parsing(
+                "User data streams can not be opened as market streams".to_string(),
+            ));
+        }
+
         let url = self
             .exchange_api
             .build_stream_url(symbol, stream_type.clone(), interval);
@@ -309,12 +562,54 @@ impl Market {
     /// An `Option<StreamMeta>` containing the metadata of the closed stream if successful, or `None` if the stream could not be found or closed.
 
     pub async fn close_stream(&self, stream_id: &str) -> Option<StreamMeta> {
-        self.exchange_api
+        let closed = self
+            .exchange_api
             .get_stream_manager()
             .lock()
             .await
             .close_stream(stream_id)
-            .await
+            .await;
+
+        // order books are only maintained while their depth stream is open
+        if let Some(meta) = closed
+            .as_ref()
+            .filter(|meta| matches!(meta.stream_type, StreamType::Depth))
+        {
+            self.order_books.remove(&meta.symbol).await;
+        }
+
+        closed
+    }
+
+    /// Loads the most recent klines and trades of the initial streams from storage into memory.
+    ///
+    /// # Parameters
+    ///
+    /// - `warmup`: The number of klines and trades to load for each stream.
+    ///
+    /// # Returns
+    ///
+    /// The number of klines and trades loaded.
+
+    pub async fn warm_cache(&self, warmup: MarketWarmup) -> usize {
+        let mut data = self.data.lock().await;
+        let mut loaded = 0;
+
+        for (symbol, stream_type, interval) in INITIAL_STREAMS {
+            loaded += match (stream_type, interval) {
+                (StreamType::Kline, Some(interval)) if warmup.klines > 0 => {
+                    data.preload_klines(symbol, interval, warmup.klines).await
+                }
+                (StreamType::Trade, _) if warmup.trades > 0 => {
+                    data.preload_trades(symbol, warmup.trades).await
+                }
+                _ => 0,
+            };
+        }
+
+        info!("Warmed market cache with {loaded} klines and trades from storage");
+
+        loaded
     }
 
     // ---
@@ -330,12 +625,9 @@ impl Market {
 
     async fn init(&self) {
         // Add initial needed streams
-        self.add_needed_stream("BTCUSDT", StreamType::Ticker, None)
-            .await;
-        self.add_needed_stream("BTCUSDT", StreamType::Trade, None)
-            .await;
-        self.add_needed_stream("BTCUSDT", StreamType::Kline, Some(Interval::Min1))
-            .await;
+        for (symbol, stream_type, interval) in INITIAL_STREAMS {
+            self.add_needed_stream(symbol, stream_type, interval).await;
+        }
 
         self.init_market_receivers().await;
         self.init_active_stream_monitor().await;
@@ -344,25 +636,32 @@ impl Market {
     async fn init_market_receivers(&self) {
         let market_receiver = self.market_receiver.clone();
         let market_data = self.data.clone();
+        let recorder = self.recorder.clone();
+        let alert_manager = self.alert_manager.clone();
+        let order_books = self.order_books.clone();
+        let trade_flow = self.trade_flow.clone();
 
         // let active_streams = self.active_streams.clone();
 
         // spawn thread to handle stream_manager messages
         tokio::spawn(async move {
-            while let Some(message) = market_receiver.lock().await.recv().await {
+            while let Some(message) = market_receiver.recv().await {
                 // println!("{message:?}");
 
-                match message {
-                    MarketMessage::UpdateKline(kline) => {
-                        market_data.lock().await.update_kline(kline).await;
-                    }
-                    MarketMessage::UpdateTicker(ticker) => {
-                        market_data.lock().await.update_ticker(ticker).await;
-                    }
-                    MarketMessage::UpdateMarketTrade(mut trade) => {
-                        market_data.lock().await.update_trade(&mut trade).await;
+                if let Some(recorder) = recorder.lock().await.as_mut() {
+                    if let Err(e) = recorder.record(&message) {
+                        warn!("Unable to record market message, {e}");
                     }
                 }
+
+                alert_manager.lock().await.handle_message(&message).await;
+                trade_flow.lock().await.handle_message(&message);
+
+                if let MarketMessage::UpdateDepth(update) = &message {
+                    order_books.handle_update(update).await;
+                }
+
+                market_data.lock().await.handle_message(message).await;
             }
         });
     }
@@ -371,28 +670,52 @@ impl Market {
         let stream_manager = self.exchange_api.get_stream_manager();
         let exchange_api = self.exchange_api.clone();
         let needed_streams = self.needed_streams.clone();
+        let trading_halts = self.trading_halts.clone();
 
         tokio::spawn(async move {
             loop {
                 tokio::time::sleep(Duration::from_secs(3)).await;
                 let active_streams = stream_manager.lock().await.active_streams().await;
-                for needed_stream_meta in needed_streams.lock().await.iter() {
+                let streams = needed_streams.lock().await.streams();
+                for needed_stream_meta in streams {
                     let active_stream_meta = active_streams
                         .iter()
-                        .find(|&meta| meta.symbol == needed_stream_meta.symbol);
+                        .find(|&meta| meta.id == needed_stream_meta.id);
+
+                    // streams of halted symbols aren't reopened until trading resumes
+                    if !active_stream_meta.is_some_and(|meta| meta.healthy)
+                        && trading_halts
+                            .check(&needed_stream_meta.symbol, generate_ts())
+                            .is_err()
+                    {
+                        continue;
+                    }
 
                     match active_stream_meta {
+                        // streams which gave up are reopened, keeping the time of their last
+                        // data so they are reported as stale until data arrives again
+                        Some(meta) if !meta.healthy => {
+                            warn!("Reopening unhealthy stream {}", meta.id);
+
+                            let mut stream_manager = stream_manager.lock().await;
+                            stream_manager.close_stream(&meta.id).await;
+
+                            let _ = stream_manager
+                                .open_stream(StreamMeta {
+                                    last_update: meta.last_update,
+                                    ..needed_stream_meta
+                                })
+                                .await;
+                        }
                         Some(_meta) => {
                             continue;
                         }
                         None => {
-                            let need_stream = needed_stream_meta.clone();
-
                             let _ = exchange_api
                                 .get_stream_manager()
                                 .lock()
                                 .await
-                                .open_stream(need_stream)
+                                .open_stream(needed_stream_meta)
                                 .await;
                         }
                     }
@@ -407,48 +730,165 @@ impl Market {
     /// the stream metadata including its unique identifier, URL, symbol, and type, and then
     /// appends this metadata to the internal list of streams that need to be established.
     ///
+    /// A stream which is already needed is not added again, instead its reference count is
+    /// incremented so it stays open until every caller has removed it.
+    ///
     /// # Parameters
     ///
     /// - `symbol`: A `&str` specifying the trading pair or market symbol the stream is associated with.
     /// - `stream_type`: A `StreamType` indicating the type of stream to be opened (e.g., Ticker, Kline).
     /// - `interval`: An `Option<&str>` specifying the interval for Kline streams. This parameter is ignored for Ticker streams.
+    ///
+    /// # Returns
+    ///
+    /// The number of callers which need the stream, `1` if it was not needed before.
 
     pub async fn add_needed_stream(
         &self,
         symbol: &str,
         stream_type: StreamType,
         interval: Option<Interval>,
-    ) {
-        let mut needed_streams = self.needed_streams.lock().await;
+    ) -> usize {
         let url = self
             .exchange_api
             .build_stream_url(symbol, stream_type, interval);
         let stream_id = build_stream_id(symbol, stream_type, interval);
-        let stream_meta = StreamMeta::new(&stream_id, &url, symbol, stream_type, None);
+        let stream_meta = StreamMeta::new(&stream_id, &url, symbol, stream_type, interval);
 
-        needed_streams.push(stream_meta);
+        self.needed_streams.lock().await.subscribe(stream_meta)
     }
 
     /// Removes a specified stream from the list of necessary streams.
     ///
     /// This method deletes the stream metadata based on the specified parameters from the internal list of streams that need to be monitored or interacted with. It ensures that no further actions or data processing occur for the removed stream.
     ///
+    /// The reference count of the stream is decremented, the stream is only removed once it is no
+    /// longer needed by any caller.
+    ///
     /// # Parameters
     ///
     /// - `symbol`: A `&str` specifying the trading pair or market symbol the stream is associated with.
     /// - `stream_type`: A `StreamType` indicating the type of stream to be removed. This parameter is currently not used but reserved for future functionality.
     /// - `interval`: An `Option<&str>` specifying the interval for Kline streams. This parameter helps identify the correct stream to remove and is ignored for Ticker streams.
+    ///
+    /// # Returns
+    ///
+    /// The number of callers which still need the stream.
 
     pub async fn remove_needed_stream(
         &self,
         symbol: &str,
         stream_type: StreamType,
         interval: Option<Interval>,
-    ) {
-        let mut needed_streams = self.needed_streams.lock().await;
+    ) -> usize {
+        let stream_id = build_stream_id(symbol, stream_type, interval);
+
+        self.needed_streams.lock().await.unsubscribe(&stream_id)
+    }
+
+    /// Subscribes to a stream, opening it on the first subscription if it is not already active.
+    ///
+    /// The stream is kept open by the active stream monitor until every subscriber has
+    /// unsubscribed, so strategies trading the same symbol and interval share one stream.
+    ///
+    /// # Parameters
+    ///
+    /// - `stream_type`: The `StreamType` of the stream.
+    /// - `symbol`: The trading pair the stream is associated with.
+    /// - `interval`: The interval of Kline streams, ignored for other stream types.
+    ///
+    /// # Returns
+    ///
+    /// An `ApiResult` with the number of subscribers of the stream, or the error the stream
+    /// failed to open with, in which case the subscription is not kept.
+
+    pub async fn subscribe_stream(
+        &self,
+        stream_type: StreamType,
+        symbol: &str,
+        interval: Option<Interval>,
+    ) -> ApiResult<usize> {
+        let subscribers = self.add_needed_stream(symbol, stream_type, interval).await;
+
         let stream_id = build_stream_id(symbol, stream_type, interval);
+        let is_active = self
+            .active_streams()
+            .await
+            .iter()
+            .any(|meta| meta.id == stream_id);
+
+        if !is_active {
+            if let Err(e) = self.open_stream(stream_type, symbol, interval).await {
+                self.remove_needed_stream(symbol, stream_type, interval)
+                    .await;
+                return Err(e);
+            }
+        }
+
+        Ok(subscribers)
+    }
+
+    /// Unsubscribes from a stream, closing it once it has no subscribers left.
+    ///
+    /// # Parameters
+    ///
+    /// - `stream_type`: The `StreamType` of the stream.
+    /// - `symbol`: The trading pair the stream is associated with.
+    /// - `interval`: The interval of Kline streams, ignored for other stream types.
+    ///
+    /// # Returns
+    ///
+    /// The number of remaining subscribers of the stream.
+
+    pub async fn unsubscribe_stream(
+        &self,
+        stream_type: StreamType,
+        symbol: &str,
+        interval: Option<Interval>,
+    ) -> usize {
+        let subscribers = self
+            .remove_needed_stream(symbol, stream_type, interval)
+            .await;
+
+        if subscribers == 0 {
+            let stream_id = build_stream_id(symbol, stream_type, interval);
+            self.close_stream(&stream_id).await;
+        }
 
-        needed_streams.retain(|x| x.id != stream_id);
+        subscribers
+    }
+
+    /// Unsubscribes from a stream by its ID, ie. a stream opened through the API.
+    ///
+    /// Streams which are active without being subscribed to are closed right away.
+    ///
+    /// # Parameters
+    ///
+    /// - `stream_id`: The unique identifier of the stream.
+    ///
+    /// # Returns
+    ///
+    /// The metadata of the stream and its number of remaining subscribers, or `None` if the stream
+    /// is neither subscribed to nor active.
+
+    pub async fn unsubscribe_stream_by_id(&self, stream_id: &str) -> Option<(StreamMeta, usize)> {
+        let subscribed = self
+            .needed_streams
+            .lock()
+            .await
+            .streams()
+            .into_iter()
+            .find(|meta| meta.id == stream_id);
+
+        match subscribed {
+            Some(meta) => {
+                let subscribers = self
+                    .unsubscribe_stream(meta.stream_type, &meta.symbol, meta.interval)
+                    .await;
+                Some((meta, subscribers))
+            }
+            None => self.close_stream(stream_id).await.map(|meta| (meta, 0)),
+        }
     }
 
     /// Provides a summary of the current market status, including exchange information and stream details.
@@ -458,12 +898,43 @@ impl Market {
     ///
     /// # Returns
     ///
-    /// A `MarketInfo` structure containing details about the exchange and the number of active streams.
+    /// A `MarketInfo` structure containing details about the exchange, the number of active streams
+    /// and the number of market messages dropped by the market channel.
 
     pub async fn info(&self) -> MarketInfo {
         MarketInfo {
             exchange_info: self.exchange_api.info().await.ok(),
             num_active_streams: self.active_streams().await.len(),
+            buffered_messages: self.market_receiver.buffered(),
+            dropped_messages: self.market_receiver.drops(),
+        }
+    }
+}
+
+/// Streams opened when the market starts, the kline and trade streams are warmed from storage.
+const INITIAL_STREAMS: [(&str, StreamType, Option<Interval>); 3] = [
+    ("BTCUSDT", StreamType::Ticker, None),
+    ("BTCUSDT", StreamType::Trade, None),
+    ("BTCUSDT", StreamType::Kline, Some(Interval::Min1)),
+];
+
+/// Number of the most recent klines and trades of each initial stream loaded from storage into
+/// memory when the market starts, so strategies have history before the streams fill it.
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MarketWarmup {
+    pub klines: usize,
+    pub trades: usize,
+}
+
+impl MarketWarmup {
+    /// Builds the warmup from the `MARKET_WARMUP_KLINES` and `MARKET_WARMUP_TRADES` settings,
+    /// invalid or empty values disable the warmup of that data.
+
+    pub fn from_settings(klines: &str, trades: &str) -> Self {
+        Self {
+            klines: klines.parse().unwrap_or(0),
+            trades: trades.parse().unwrap_or(0),
         }
     }
 }
@@ -477,6 +948,10 @@ impl Market {
 pub struct MarketInfo {
     exchange_info: Option<ExchangeInfo>,
     num_active_streams: usize,
+    /// Number of market messages waiting to be processed.
+    buffered_messages: usize,
+    /// Number of market messages dropped while the market channel was full.
+    dropped_messages: u64,
 }
 
 /// A trait defining a common interface for market data symbols.
@@ -499,11 +974,23 @@ pub struct MarketData {
     all_trades: HashMap<String, TradeData>,
     storage_manager: Arc<dyn StorageManager>,
     last_backup: u64,
+    /// Latest open time or timestamp by data key of the data preloaded from storage, which
+    /// isn't saved again on backup.
+    stored_until: HashMap<String, u64>,
+    /// Data keys whose last backup failed, their next backup is merged with the stored data so
+    /// rows saved before the failure aren't saved twice.
+    failed_backups: HashSet<String>,
+    /// How tickers are sampled before they are persisted.
+    ticker_sampling: TickerSampling,
 }
 
 /// Specifies the interval in seconds between consecutive backups of market data.
 const BACKUP_INTERVAL_SECS: u64 = MIN_AS_MILI * 1; // 5min
 
+/// Age of the tickers kept in memory while their samples can't be saved, older tickers are
+/// dropped so a storage which keeps failing doesn't grow memory without bound.
+const MAX_UNSAVED_TICKER_AGE: u64 = HOUR_AS_MILI;
+
 impl MarketData {
     /// Initializes a new instance of MarketData, creating a central repository for both kline and ticker data managed throughout the application lifecycle.
     ///
@@ -524,6 +1011,9 @@ impl MarketData {
             all_tickers: HashMap::new(),
             all_trades: HashMap::new(),
             last_backup: generate_ts(),
+            stored_until: HashMap::new(),
+            failed_backups: HashSet::new(),
+            ticker_sampling: TickerSampling::default(),
         }
     }
 
@@ -531,22 +1021,28 @@ impl MarketData {
     ///
     /// This method also triggers a backup operation to persist klines to disk based on a predefined interval, ensuring data durability and recoverability.
     ///
+    /// Klines whose interval is still in progress update the kline in memory but are only persisted
+    /// once they close, so a backup mid-candle doesn't store incomplete volume.
+    ///
     /// # Parameters
     ///
     /// - kline: The Kline instance representing the new market data to be added.
+    /// - closed: Whether the interval of the kline has closed.
     ///
-    pub async fn update_kline(&mut self, kline: Kline) {
+    pub async fn update_kline(&mut self, kline: Kline, closed: bool) {
         // get kline key eg. BTCUSDT@kline_1m
         let kline_key = build_kline_key(&kline.symbol, kline.interval);
 
-        // add new kline to data if key found for kline symbol
-        if let Some(kline_data) = self.all_klines.get_mut(&kline_key) {
+        // add new kline to the data of the kline key, creating it if not found
+        let kline_data = self
+            .all_klines
+            .entry(kline_key)
+            .or_insert_with(|| KlineData::new(&kline.symbol, kline.interval));
+
+        if closed {
             kline_data.add_kline(kline);
         } else {
-            let mut new_kline_data = KlineData::new(&kline.symbol, kline.interval);
-            new_kline_data.add_kline(kline);
-            self.all_klines
-                .insert(kline_key.to_string(), new_kline_data);
+            kline_data.add_partial_kline(kline);
         }
 
         self.handle_data_backup().await;
@@ -574,6 +1070,23 @@ impl MarketData {
         self.handle_data_backup().await;
     }
 
+    /// Applies a market message to the market data.
+    ///
+    /// # Parameters
+    ///
+    /// - message: The kline, ticker or trade update to apply.
+    ///
+    pub async fn handle_message(&mut self, message: MarketMessage) {
+        match message {
+            MarketMessage::UpdateKline(kline) => self.update_kline(kline, true).await,
+            MarketMessage::UpdatePartialKline(kline) => self.update_kline(kline, false).await,
+            MarketMessage::UpdateTicker(ticker) => self.update_ticker(ticker).await,
+            MarketMessage::UpdateMarketTrade(mut trade) => self.update_trade(&mut trade).await,
+            // order books are maintained by the market
+            MarketMessage::UpdateDepth(_) => {}
+        }
+    }
+
     // TODO: write docs
     pub async fn update_trade(&mut self, trade: &mut Trade) {
         let trade_key = build_market_trade_key(&trade.symbol);
@@ -592,6 +1105,85 @@ impl MarketData {
         self.handle_data_backup().await;
     }
 
+    /// Loads the most recent closed klines of a symbol and interval from storage into memory.
+    ///
+    /// # Parameters
+    ///
+    /// - symbol: The market symbol of the klines.
+    /// - interval: The interval of the klines.
+    /// - n: The maximum number of klines to load.
+    ///
+    /// # Returns
+    ///
+    /// The number of klines loaded.
+    pub async fn preload_klines(&mut self, symbol: &str, interval: Interval, n: usize) -> usize {
+        let current_open_time = floor_mili_ts(generate_ts(), interval.to_mili());
+        let from_ts = current_open_time.saturating_sub(interval.to_mili() * n as u64);
+
+        let mut klines = self
+            .storage_manager
+            .get_klines(symbol, interval, Some(from_ts), Some(current_open_time - 1))
+            .await;
+        klines.retain(|kline| kline.open_time >= from_ts && kline.open_time < current_open_time);
+        klines.sort_by_key(|kline| kline.open_time);
+        let klines = klines.split_off(klines.len().saturating_sub(n));
+
+        let kline_key = build_kline_key(symbol, interval);
+        if let Some(last) = klines.last() {
+            self.stored_until.insert(kline_key.clone(), last.open_time);
+        }
+
+        let kline_data = self
+            .all_klines
+            .entry(kline_key)
+            .or_insert_with(|| KlineData::new(symbol, interval));
+
+        let loaded = klines.len();
+        for kline in klines {
+            kline_data.add_kline(kline);
+        }
+
+        loaded
+    }
+
+    /// Loads the most recent trades of a symbol within the last day from storage into memory.
+    ///
+    /// # Parameters
+    ///
+    /// - symbol: The market symbol of the trades.
+    /// - n: The maximum number of trades to load.
+    ///
+    /// # Returns
+    ///
+    /// The number of trades loaded.
+    pub async fn preload_trades(&mut self, symbol: &str, n: usize) -> usize {
+        let now = generate_ts();
+
+        let mut trades = self
+            .storage_manager
+            .get_trades(symbol, Some(now.saturating_sub(DAY_AS_MILI)), Some(now))
+            .await;
+        trades.sort_by_key(|trade| trade.timestamp);
+        let trades = trades.split_off(trades.len().saturating_sub(n));
+
+        let trade_key = build_market_trade_key(symbol);
+        if let Some(last) = trades.last() {
+            self.stored_until.insert(trade_key.clone(), last.timestamp);
+        }
+
+        let trade_data = self
+            .all_trades
+            .entry(trade_key)
+            .or_insert_with(|| TradeData::new(symbol));
+
+        let loaded = trades.len();
+        for mut trade in trades {
+            trade_data.add_trade(&mut trade);
+        }
+
+        loaded
+    }
+
     /// Retrieves a range of kline data for a specific symbol and interval, optionally filtered by a start and end timestamp, with a limit on the number of klines returned. This method aggregates data from both in-memory storage and persistent storage, providing a comprehensive view of historical market data.
     ///
     /// # Parameters
@@ -673,6 +1265,14 @@ impl MarketData {
         }
     }
 
+    /// The latest in-memory kline of a symbol and interval, stored klines aren't read.
+
+    pub fn latest_kline(&self, symbol: &str, interval: Interval) -> Option<LatestKline> {
+        self.all_klines
+            .get(&build_kline_key(symbol, interval))
+            .and_then(KlineData::latest)
+    }
+
     /// Provides a snapshot of the latest ticker data for a given symbol. This method retrieves the most recent ticker information, offering insights into current market conditions such as the latest price, volume, and price changes.
     ///
     /// # Parameters
@@ -759,38 +1359,69 @@ impl MarketData {
         let now = generate_ts();
 
         if self.last_backup + BACKUP_INTERVAL_SECS < now {
-            // clear all klines
+            // data is only cleared from memory once saved, so a failed or interrupted save
+            // leaves it to be saved by the next backup
             for (key, kline_data) in self.all_klines.iter_mut() {
-                let klines = kline_data.drain_klines(self.last_backup);
+                let mut klines = kline_data.klines_before(self.last_backup);
+                if let Some(stored_until) = self.stored_until.get(key) {
+                    klines.retain(|kline| kline.open_time > *stored_until);
+                }
                 if klines.len() > 0 {
-                    match self.storage_manager.save_klines(&klines, key, false).await {
-                        Ok(_res) => {}
-                        Err(e) => {
-                            info!("Unable to save Klines: {e}");
-                        }
+                    let merge = self.failed_backups.contains(key);
+                    if let Err(e) = self.storage_manager.save_klines(&klines, key, merge).await {
+                        warn!("Unable to save Klines, keeping them for the next backup: {e}");
+                        self.failed_backups.insert(key.clone());
+                        continue;
                     }
                 }
+                self.failed_backups.remove(key);
+                kline_data.drain_klines(self.last_backup);
             }
 
-            // Clear trade_data
             for (key, trade_data) in self.all_trades.iter_mut() {
-                let trades = trade_data.drain_trades(self.last_backup);
+                let mut trades = trade_data.trades_before(self.last_backup);
+                if let Some(stored_until) = self.stored_until.get(key) {
+                    trades.retain(|trade| trade.timestamp > *stored_until);
+                }
                 if trades.len() > 0 {
-                    self.storage_manager
-                        .save_trades(&trades, key, false)
-                        .await
-                        .expect("Unable to save trades");
+                    let merge = self.failed_backups.contains(key);
+                    if let Err(e) = self.storage_manager.save_trades(&trades, key, merge).await {
+                        warn!("Unable to save trades, keeping them for the next backup: {e}");
+                        self.failed_backups.insert(key.clone());
+                        continue;
+                    }
                 }
+                self.failed_backups.remove(key);
+                trade_data.drain_trades(self.last_backup);
             }
 
-            // Clear ticker_data
             for (key, ticker_data) in self.all_tickers.iter_mut() {
-                let tickers = ticker_data.drain_tickers(self.last_backup);
-                // TODO: write tickers to storage
-                // self.storage_manager
-                //     .save_trades(&trades, key, false)
-                //     .await
-                //     .expect("Unable to save Klines");
+                let Some(interval) = self.ticker_sampling.interval() else {
+                    ticker_data.drain_tickers(self.last_backup);
+                    continue;
+                };
+
+                // the bucket in progress is sampled by a later backup, once all its frames are in
+                let before_ts = floor_mili_ts(self.last_backup, interval);
+                let samples =
+                    TickerSample::from_tickers(&ticker_data.tickers_before(before_ts), interval);
+                if samples.len() > 0 {
+                    if let Err(e) = self
+                        .storage_manager
+                        .save_ticker_samples(&samples, key)
+                        .await
+                    {
+                        warn!("Unable to save tickers, keeping them for the next backup: {e}");
+
+                        let expired_ts = before_ts.saturating_sub(MAX_UNSAVED_TICKER_AGE);
+                        let dropped = ticker_data.drain_tickers(expired_ts).len();
+                        if dropped > 0 {
+                            warn!("Dropped {dropped} unsaved tickers of {key} older than an hour");
+                        }
+                        continue;
+                    }
+                }
+                ticker_data.drain_tickers(before_ts);
             }
 
             // Update the last backup time
@@ -798,3 +1429,768 @@ impl MarketData {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        account::trade::OrderSide,
+        exchange::mock::{MockExchangeApi, MOCK_AGG_TRADES, MOCK_PRICE, MOCK_TRADES_START},
+        market::backfill::BackfillRequest,
+        market::channel::{build_market_channel, DEFAULT_MARKET_CHANNEL_CAPACITY},
+        storage::{
+            fs::FsStorage,
+            memory::MemoryStorage,
+            schema::{self, DataFormat},
+        },
+        testutil::{make_klines, test_market},
+        utils::trade::build_market_trade_filename,
+    };
+    use tokio::test;
+
+    #[test]
+    async fn test_backfill_many_bounded_concurrency() {
+        let (_, market_rx) = build_market_channel(DEFAULT_MARKET_CHANNEL_CAPACITY);
+        let mock_api = Arc::new(MockExchangeApi::default());
+        let exchange_api: Arc<dyn ExchangeApi> = mock_api.clone();
+        let storage_manager: Arc<dyn StorageManager> =
+            Arc::new(FsStorage::new(format!("test-backfill-{}", Uuid::new_v4())));
+        let market = Market::new(market_rx, exchange_api, storage_manager, false).await;
+
+        let from_ts = 1_700_000_000_000;
+        let requests: Vec<BackfillRequest> = (0..3)
+            .map(|i| BackfillRequest {
+                symbol: format!("BACKFILL{i}"),
+                interval: Interval::Hour1,
+                from_ts,
+                to_ts: from_ts + Interval::Hour1.to_mili() * (i + 1),
+            })
+            .collect();
+
+        let results = market.backfiller().backfill_many(requests.clone(), 2).await;
+
+        assert_eq!(results.len(), 3);
+        for (i, result) in results.iter().enumerate() {
+            assert!(result.is_success(), "{:?}", result.error);
+            assert_eq!(result.request, requests[i]);
+            assert_eq!(result.klines_saved, i + 1);
+        }
+        assert!(mock_api.max_concurrent_kline_requests() <= 2);
+        assert!(mock_api.max_concurrent_kline_requests() > 0);
+    }
+
+    #[test]
+    async fn test_backfill_pages_long_ranges() {
+        let (_, market_rx) = build_market_channel(DEFAULT_MARKET_CHANNEL_CAPACITY);
+        let exchange_api: Arc<dyn ExchangeApi> = Arc::new(MockExchangeApi::default());
+        let storage_manager: Arc<dyn StorageManager> = Arc::new(MemoryStorage::default());
+        let market = Market::new(market_rx, exchange_api, storage_manager.clone(), false).await;
+        let backfiller = market.backfiller();
+
+        // three pages, the market lock isn't needed while they're fetched
+        let from_ts = 1_699_999_980_000;
+        let result = backfiller
+            .backfill(BackfillRequest {
+                symbol: "BTCUSDT".to_string(),
+                interval: Interval::Min1,
+                from_ts,
+                to_ts: from_ts + Interval::Min1.to_mili() * 2_500,
+            })
+            .await;
+
+        assert!(result.is_success(), "{:?}", result.error);
+        assert_eq!(result.klines_saved, 2_501);
+
+        let klines = storage_manager
+            .get_klines("BTCUSDT", Interval::Min1, Some(from_ts), None)
+            .await;
+        assert_eq!(klines.len(), 2_501);
+        assert!(klines
+            .windows(2)
+            .all(|pair| pair[1].open_time - pair[0].open_time == Interval::Min1.to_mili()));
+    }
+
+    #[test]
+    async fn test_backfill_trades_pages_history() {
+        let (_, market_rx) = build_market_channel(DEFAULT_MARKET_CHANNEL_CAPACITY);
+        let mock_api = Arc::new(MockExchangeApi::default());
+        let exchange_api: Arc<dyn ExchangeApi> = mock_api.clone();
+        let storage_manager: Arc<dyn StorageManager> = Arc::new(MemoryStorage::default());
+        let market = Market::new(market_rx, exchange_api, storage_manager.clone(), false).await;
+
+        // recent page followed by two historical pages
+        let saved = market
+            .backfiller()
+            .backfill_trades("BTCUSDT", 2_200)
+            .await
+            .unwrap();
+        assert_eq!(saved, 2_200);
+        assert_eq!(mock_api.trade_requests(), 3);
+
+        let trades = storage_manager
+            .get_trades("BTCUSDT", Some(MOCK_TRADES_START), None)
+            .await;
+        assert_eq!(trades.len(), 2_200);
+        assert!(trades
+            .windows(2)
+            .all(|pair| pair[0].timestamp < pair[1].timestamp));
+
+        // the latest trades are kept
+        let first_id = MOCK_AGG_TRADES - 2_200;
+        assert_eq!(trades[0].timestamp, MOCK_TRADES_START + first_id * 100);
+        assert_eq!(
+            trades.last().unwrap().timestamp,
+            MOCK_TRADES_START + (MOCK_AGG_TRADES - 1) * 100
+        );
+
+        // paging stops at the start of the trade history
+        let saved = market
+            .backfiller()
+            .backfill_trades("ETHUSDT", 10_000)
+            .await
+            .unwrap();
+        assert_eq!(saved, MOCK_AGG_TRADES as usize);
+    }
+
+    #[test]
+    async fn test_warm_cache_preloads_stored_history() {
+        let (_, market_rx) = build_market_channel(DEFAULT_MARKET_CHANNEL_CAPACITY);
+        let mock_api = Arc::new(MockExchangeApi::default());
+        let exchange_api: Arc<dyn ExchangeApi> = mock_api.clone();
+        let storage_manager: Arc<dyn StorageManager> = Arc::new(MemoryStorage::default());
+
+        // twenty closed klines of the initial kline stream are stored
+        let (symbol, interval) = ("BTCUSDT", Interval::Min1);
+        let interval_ms = interval.to_mili();
+        let current_open_time = floor_mili_ts(generate_ts(), interval_ms);
+        let klines: Vec<Kline> = (1..=20)
+            .rev()
+            .map(|i| {
+                let open_time = current_open_time - interval_ms * i;
+                Kline {
+                    symbol: symbol.to_string(),
+                    interval,
+                    open_time,
+                    close_time: open_time + interval_ms - 1,
+                    close: 100.0 + i as f64,
+                    ..Default::default()
+                }
+            })
+            .collect();
+        storage_manager
+            .save_klines(&klines, &build_kline_key(symbol, interval), false)
+            .await
+            .unwrap();
+
+        let market = Market::new(market_rx, exchange_api, storage_manager, false).await;
+        let loaded = market
+            .warm_cache(MarketWarmup {
+                klines: 10,
+                trades: 0,
+            })
+            .await;
+        assert_eq!(loaded, 10);
+
+        // the most recent klines are held in memory
+        let in_memory =
+            market.data.lock().await.all_klines[&build_kline_key(symbol, interval)].klines();
+        assert_eq!(in_memory, klines[10..].to_vec());
+
+        let last_n = market.last_n_klines(symbol, interval, 10).await;
+        assert_eq!(last_n, klines[10..].to_vec());
+        let last = market.last_kline(symbol, interval, true).await;
+        assert_eq!(last, klines.last().cloned());
+        assert_eq!(mock_api.last_kline_requests(), 0);
+    }
+
+    #[test]
+    async fn test_failed_backup_keeps_data_for_next_backup() {
+        let test_dir = std::env::temp_dir().join(format!("raderbot-{}", Uuid::new_v4()));
+        let data_dir = test_dir.join("data");
+        std::fs::create_dir_all(&data_dir).unwrap();
+        let storage_manager: Arc<dyn StorageManager> = Arc::new(FsStorage::new(&data_dir));
+
+        // a file in place of the market directory makes every save fail
+        std::fs::write(data_dir.join("market"), "").unwrap();
+
+        let symbol = "BTCUSDT";
+        let interval = Interval::Min1;
+        let kline_key = build_kline_key(symbol, interval);
+        let trade_key = build_market_trade_key(symbol);
+        let open_time = floor_mili_ts(generate_ts(), interval.to_mili()) - 60 * MIN_AS_MILI;
+        let kline = |i: u64| Kline {
+            symbol: symbol.to_string(),
+            interval,
+            open_time: open_time + i * MIN_AS_MILI,
+            close_time: open_time + (i + 1) * MIN_AS_MILI - 1,
+            close: 100.0 + i as f64,
+            ..Default::default()
+        };
+
+        let mut market_data = MarketData::new(storage_manager.clone());
+        market_data.last_backup = open_time + 10 * MIN_AS_MILI;
+        market_data
+            .update_trade(&mut Trade {
+                symbol: symbol.to_string(),
+                timestamp: open_time,
+                qty: 1.0,
+                price: 100.0,
+                order_side: OrderSide::Buy,
+                id: None,
+            })
+            .await;
+        market_data.update_kline(kline(0), true).await;
+
+        // the failed save keeps the drained data in memory
+        assert_eq!(market_data.all_klines[&kline_key].klines(), vec![kline(0)]);
+        assert_eq!(market_data.all_trades[&trade_key].trades().len(), 1);
+        assert!(storage_manager
+            .get_klines(symbol, interval, Some(open_time), None)
+            .await
+            .is_empty());
+
+        // the next backup saves the kept data along with newer data
+        std::fs::remove_file(data_dir.join("market")).unwrap();
+        market_data.last_backup = open_time + 10 * MIN_AS_MILI;
+        market_data.update_kline(kline(1), true).await;
+
+        assert!(market_data.all_klines[&kline_key].klines().is_empty());
+        assert!(market_data.all_trades[&trade_key].trades().is_empty());
+        assert_eq!(
+            storage_manager
+                .get_klines(symbol, interval, Some(open_time), None)
+                .await,
+            vec![kline(0), kline(1)]
+        );
+        assert_eq!(
+            storage_manager
+                .get_trades(symbol, Some(open_time), None)
+                .await
+                .len(),
+            1
+        );
+
+        std::fs::remove_dir_all(test_dir).unwrap();
+    }
+
+    #[test]
+    async fn test_backup_retry_doesnt_duplicate_saved_rows() {
+        let test_dir = std::env::temp_dir().join(format!("raderbot-{}", Uuid::new_v4()));
+        let storage_manager: Arc<dyn StorageManager> = Arc::new(FsStorage::new(&test_dir));
+
+        let symbol = "BTCUSDT";
+        let trade_key = build_market_trade_key(symbol);
+        let day_ts = floor_mili_ts(generate_ts(), DAY_AS_MILI) - DAY_AS_MILI;
+        let trade = |timestamp: u64| Trade {
+            symbol: symbol.to_string(),
+            timestamp,
+            qty: 1.0,
+            price: 100.0,
+            order_side: OrderSide::Buy,
+            id: None,
+        };
+
+        // the file of the second day can't be written, the first day is saved before the failure
+        let trades_dir = test_dir.join("market").join("trades");
+        let blocked_dir = trades_dir
+            .join(build_market_trade_filename(&trade_key, day_ts))
+            .with_extension(DataFormat::default().extension());
+        std::fs::create_dir_all(&blocked_dir).unwrap();
+
+        let mut market_data = MarketData::new(storage_manager.clone());
+        market_data
+            .update_trade(&mut trade(day_ts - DAY_AS_MILI))
+            .await;
+        market_data.update_trade(&mut trade(day_ts)).await;
+
+        market_data.last_backup = day_ts + DAY_AS_MILI;
+        market_data.handle_data_backup().await;
+        assert_eq!(market_data.all_trades[&trade_key].trades().len(), 2);
+        assert_eq!(
+            storage_manager
+                .get_trades(symbol, Some(day_ts - DAY_AS_MILI), None)
+                .await
+                .len(),
+            1
+        );
+
+        // the retry merges the first day rather than appending it again
+        std::fs::remove_dir(&blocked_dir).unwrap();
+        market_data.last_backup = day_ts + DAY_AS_MILI;
+        market_data.handle_data_backup().await;
+
+        assert!(market_data.all_trades[&trade_key].trades().is_empty());
+        assert_eq!(
+            storage_manager
+                .get_trades(symbol, Some(day_ts - DAY_AS_MILI), None)
+                .await,
+            vec![trade(day_ts - DAY_AS_MILI), trade(day_ts)]
+        );
+        let first_day_file = trades_dir
+            .join(build_market_trade_filename(
+                &trade_key,
+                day_ts - DAY_AS_MILI,
+            ))
+            .with_extension(DataFormat::default().extension());
+        assert_eq!(
+            schema::read_rows::<Trade>(&first_day_file).unwrap(),
+            vec![trade(day_ts - DAY_AS_MILI)]
+        );
+
+        std::fs::remove_dir_all(test_dir).unwrap();
+    }
+
+    #[test]
+    async fn test_last_n_klines() {
+        let market = test_market(Arc::new(MockExchangeApi::default())).await;
+
+        let symbol = format!("LASTN{}", Uuid::new_v4().simple());
+        let interval = Interval::Day1;
+        let interval_ms = interval.to_mili();
+        let current_open_time = floor_mili_ts(generate_ts(), interval_ms);
+
+        // ten closed klines followed by the kline in progress
+        let market_data = market.market_data().await;
+        for i in (0..=10).rev() {
+            let open_time = current_open_time - interval_ms * i;
+            let kline = Kline {
+                symbol: symbol.clone(),
+                interval,
+                open_time,
+                close_time: open_time + interval_ms - 1,
+                close: 100.0 + i as f64,
+                ..Default::default()
+            };
+            market_data.lock().await.update_kline(kline, true).await;
+        }
+
+        let klines = market.last_n_klines(&symbol, interval, 5).await;
+
+        let open_times: Vec<u64> = klines.iter().map(|kline| kline.open_time).collect();
+        let expected: Vec<u64> = (1..=5)
+            .rev()
+            .map(|i| current_open_time - interval_ms * i)
+            .collect();
+        assert_eq!(open_times, expected);
+
+        // fewer closed klines than requested returns all closed klines
+        let klines = market.last_n_klines(&symbol, interval, 20).await;
+        assert_eq!(klines.len(), 10);
+        assert!(klines
+            .iter()
+            .all(|kline| kline.open_time < current_open_time));
+    }
+
+    #[test]
+    async fn test_last_kline_excludes_kline_in_progress() {
+        let market = test_market(Arc::new(MockExchangeApi::default())).await;
+
+        let symbol = format!("PARTIAL{}", Uuid::new_v4().simple());
+        let interval = Interval::Hour1;
+        let interval_ms = interval.to_mili();
+        let current_open_time = floor_mili_ts(generate_ts(), interval_ms);
+
+        // last closed kline followed by the kline in progress
+        let market_data = market.market_data().await;
+        for open_time in [current_open_time - interval_ms, current_open_time] {
+            let kline = Kline {
+                symbol: symbol.clone(),
+                interval,
+                open_time,
+                close_time: open_time + interval_ms - 1,
+                ..Default::default()
+            };
+            market_data.lock().await.update_kline(kline, true).await;
+        }
+
+        let kline = market.last_kline(&symbol, interval, true).await.unwrap();
+        assert_eq!(kline.open_time, current_open_time - interval_ms);
+
+        let kline = market.last_kline(&symbol, interval, false).await.unwrap();
+        assert_eq!(kline.open_time, current_open_time);
+
+        // klines fetched from the exchange are filtered the same way
+        let remote_symbol = format!("REMOTE{}", Uuid::new_v4().simple());
+        let kline = market
+            .last_kline(&remote_symbol, interval, true)
+            .await
+            .unwrap();
+        assert_eq!(kline.open_time, current_open_time - interval_ms);
+
+        let kline = market
+            .last_kline(&remote_symbol, interval, false)
+            .await
+            .unwrap();
+        assert_eq!(kline.open_time, current_open_time);
+
+        // a reader fetches from the exchange while the market is locked
+        let market = ArcMutex::new(market);
+        let reader = market.lock().await.reader();
+        let _market_lock = market.lock().await;
+        let kline = tokio::time::timeout(
+            Duration::from_secs(1),
+            reader.last_kline(&remote_symbol, interval, true),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+        assert_eq!(kline.open_time, current_open_time - interval_ms);
+    }
+
+    #[test]
+    async fn test_only_closed_klines_are_backed_up() {
+        let storage_manager: Arc<dyn StorageManager> = Arc::new(MemoryStorage::default());
+        let mut market_data = MarketData::new(storage_manager.clone());
+
+        let open_time = 1_700_000_040_000;
+        let kline = |volume: f64| Kline {
+            symbol: "BTCUSDT".to_string(),
+            interval: Interval::Min1,
+            open_time,
+            close_time: open_time + MIN_AS_MILI - 1,
+            volume,
+            ..Default::default()
+        };
+        let kline_key = build_kline_key("BTCUSDT", Interval::Min1);
+
+        // backup is due while the candle is still open
+        market_data.last_backup = open_time + MIN_AS_MILI;
+        market_data.update_kline(kline(5.0), false).await;
+
+        assert!(storage_manager
+            .get_klines("BTCUSDT", Interval::Min1, None, None)
+            .await
+            .is_empty());
+        assert_eq!(
+            market_data.all_klines[&kline_key].klines(),
+            vec![kline(5.0)]
+        );
+
+        // closed frame completes the volume and is backed up
+        market_data.last_backup = open_time + MIN_AS_MILI;
+        market_data.update_kline(kline(10.0), true).await;
+
+        assert_eq!(
+            storage_manager
+                .get_klines("BTCUSDT", Interval::Min1, None, None)
+                .await,
+            vec![kline(10.0)]
+        );
+    }
+
+    #[test]
+    async fn test_needed_streams_are_deduped_and_ref_counted() {
+        let market = test_market(Arc::new(MockExchangeApi::default())).await;
+
+        // needed at startup and by a strategy
+        for _ in 0..2 {
+            market
+                .add_needed_stream("ETHUSDT", StreamType::Kline, Some(Interval::Min5))
+                .await;
+        }
+        market
+            .add_needed_stream("ETHUSDT", StreamType::Ticker, None)
+            .await;
+        assert_eq!(market.needed_streams.lock().await.streams().len(), 2);
+
+        // still needed by the other caller
+        market
+            .remove_needed_stream("ETHUSDT", StreamType::Kline, Some(Interval::Min5))
+            .await;
+        let stream_id = build_stream_id("ETHUSDT", StreamType::Kline, Some(Interval::Min5));
+        assert!(market
+            .needed_streams
+            .lock()
+            .await
+            .streams()
+            .iter()
+            .any(|meta| meta.id == stream_id));
+
+        market
+            .remove_needed_stream("ETHUSDT", StreamType::Kline, Some(Interval::Min5))
+            .await;
+        let needed_streams = market.needed_streams.lock().await.streams();
+        assert_eq!(needed_streams.len(), 1);
+        assert_eq!(
+            needed_streams[0].id,
+            build_stream_id("ETHUSDT", StreamType::Ticker, None)
+        );
+    }
+
+    #[test]
+    async fn test_unsubscribe_stream_by_id() {
+        let (_, market_rx) = build_market_channel(DEFAULT_MARKET_CHANNEL_CAPACITY);
+        let exchange_api: Arc<dyn ExchangeApi> = Arc::new(MockExchangeApi::default());
+        let storage_manager: Arc<dyn StorageManager> = Arc::new(MemoryStorage::default());
+        let market = Market::new(market_rx, exchange_api, storage_manager, false).await;
+        let stream_id = build_stream_id("ETHUSDT", StreamType::Trade, None);
+        let is_active = || async {
+            market
+                .active_streams()
+                .await
+                .iter()
+                .any(|meta| meta.id == stream_id)
+        };
+
+        // opened through the API and by a strategy
+        for expected in 1..=2 {
+            let subscribers = market
+                .subscribe_stream(StreamType::Trade, "ETHUSDT", None)
+                .await
+                .unwrap();
+            assert_eq!(subscribers, expected);
+        }
+
+        // closing it through the API keeps it open for the strategy
+        let (meta, subscribers) = market.unsubscribe_stream_by_id(&stream_id).await.unwrap();
+        assert_eq!(meta.id, stream_id);
+        assert_eq!(subscribers, 1);
+        assert!(is_active().await);
+
+        let (_, subscribers) = market.unsubscribe_stream_by_id(&stream_id).await.unwrap();
+        assert_eq!(subscribers, 0);
+        assert!(!is_active().await);
+        assert!(market.unsubscribe_stream_by_id(&stream_id).await.is_none());
+
+        // streams opened without a subscription are closed right away
+        market
+            .open_stream(StreamType::Trade, "ETHUSDT", None)
+            .await
+            .unwrap();
+        let (_, subscribers) = market.unsubscribe_stream_by_id(&stream_id).await.unwrap();
+        assert_eq!(subscribers, 0);
+        assert!(!is_active().await);
+    }
+
+    #[test]
+    async fn test_last_prices_batches_stale_symbols() {
+        let (_, market_rx) = build_market_channel(DEFAULT_MARKET_CHANNEL_CAPACITY);
+        let mock_api = Arc::new(MockExchangeApi::default());
+        let exchange_api: Arc<dyn ExchangeApi> = mock_api.clone();
+        let storage_manager: Arc<dyn StorageManager> = Arc::new(MemoryStorage::default());
+        let market = Market::new(market_rx, exchange_api, storage_manager, false).await;
+
+        for (symbol, price) in [("BTCUSDT", 50_000.0), ("ETHUSDT", 3_000.0)] {
+            market
+                .data
+                .lock()
+                .await
+                .update_ticker(Ticker {
+                    time: generate_ts(),
+                    symbol: symbol.to_string(),
+                    high: price,
+                    low: price,
+                    traded_vol: 0.0,
+                    last_price: price,
+                    open_price: price,
+                })
+                .await;
+        }
+
+        let symbols: Vec<String> = ["BTCUSDT", "ETHUSDT", "SOLUSDT"]
+            .iter()
+            .map(|symbol| symbol.to_string())
+            .collect();
+        let prices = market.last_prices(&symbols).await;
+
+        assert_eq!(prices.len(), 3);
+        assert_eq!(prices["BTCUSDT"], 50_000.0);
+        assert_eq!(prices["ETHUSDT"], 3_000.0);
+        assert_eq!(prices["SOLUSDT"], MOCK_PRICE);
+
+        // only the stale symbol is fetched, in a single request
+        assert_eq!(mock_api.last_prices_requests(), 1);
+        assert_eq!(mock_api.ticker_requests(), 0);
+    }
+
+    #[test]
+    async fn test_backup_persists_one_ticker_sample_per_bucket() {
+        let storage_manager: Arc<dyn StorageManager> = Arc::new(MemoryStorage::default());
+        let mut market_data = MarketData::new(storage_manager.clone());
+        market_data.ticker_sampling = TickerSampling::from_setting("1");
+
+        let symbol = "BTCUSDT";
+        let ticker_key = build_ticker_key(symbol);
+        let start = floor_mili_ts(generate_ts(), MIN_AS_MILI) - 10 * MIN_AS_MILI;
+
+        // 10 frames a second over 3 seconds, no backup is due while they are received
+        market_data.last_backup = generate_ts();
+        for i in 0..30 {
+            market_data
+                .update_ticker(Ticker {
+                    time: start + i * 100,
+                    symbol: symbol.to_string(),
+                    high: 0.0,
+                    low: 0.0,
+                    traded_vol: i as f64,
+                    last_price: 100.0 + (i % 10) as f64,
+                    open_price: 100.0,
+                })
+                .await;
+        }
+
+        // a backup within the third second only persists the two completed buckets
+        market_data.last_backup = start + 2_500;
+        market_data.handle_data_backup().await;
+
+        let samples = storage_manager
+            .get_ticker_samples(symbol, None, None)
+            .await
+            .unwrap();
+        assert_eq!(samples.len(), 2);
+        assert_eq!(market_data.all_tickers[&ticker_key].tickers().len(), 10);
+
+        market_data.last_backup = start + 3_000;
+        market_data.handle_data_backup().await;
+
+        let samples = storage_manager
+            .get_ticker_samples(symbol, None, None)
+            .await
+            .unwrap();
+        let times: Vec<u64> = samples.iter().map(|sample| sample.time).collect();
+        assert_eq!(times, vec![start, start + 1_000, start + 2_000]);
+        assert!(market_data.all_tickers[&ticker_key].tickers().is_empty());
+
+        let first = &samples[0];
+        assert_eq!(
+            (first.open, first.high, first.low, first.close),
+            (100.0, 109.0, 100.0, 109.0)
+        );
+        assert_eq!(first.traded_vol, 9.0);
+    }
+
+    #[test]
+    async fn test_unsaved_tickers_dropped_after_max_age() {
+        let test_dir = std::env::temp_dir().join(format!("raderbot-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&test_dir).unwrap();
+        let storage_manager: Arc<dyn StorageManager> = Arc::new(FsStorage::new(&test_dir));
+
+        // a file in place of the market directory makes every save fail
+        std::fs::write(test_dir.join("market"), "").unwrap();
+
+        let mut market_data = MarketData::new(storage_manager);
+        market_data.ticker_sampling = TickerSampling::from_setting("1");
+
+        let symbol = "BTCUSDT";
+        let ticker_key = build_ticker_key(symbol);
+        let start = floor_mili_ts(generate_ts(), MIN_AS_MILI) - 3 * HOUR_AS_MILI;
+
+        // a ticker every 30 minutes over 2 hours
+        market_data.last_backup = generate_ts();
+        for i in 0..5 {
+            market_data
+                .update_ticker(Ticker {
+                    time: start + i * 30 * MIN_AS_MILI,
+                    symbol: symbol.to_string(),
+                    high: 0.0,
+                    low: 0.0,
+                    traded_vol: 0.0,
+                    last_price: 100.0,
+                    open_price: 100.0,
+                })
+                .await;
+        }
+
+        // tickers up to an hour older than the backup are kept for the next backup
+        market_data.last_backup = start + 2 * HOUR_AS_MILI + 1;
+        market_data.handle_data_backup().await;
+
+        let times: Vec<u64> = market_data.all_tickers[&ticker_key]
+            .tickers()
+            .iter()
+            .map(|ticker| ticker.time)
+            .collect();
+        assert_eq!(
+            times,
+            vec![
+                start + HOUR_AS_MILI,
+                start + 90 * MIN_AS_MILI,
+                start + 2 * HOUR_AS_MILI
+            ]
+        );
+
+        std::fs::remove_dir_all(test_dir).unwrap();
+    }
+
+    #[test]
+    async fn test_latest_klines_of_streamed_symbols() {
+        let (_, market_rx) = build_market_channel(DEFAULT_MARKET_CHANNEL_CAPACITY);
+        let mock_api = Arc::new(MockExchangeApi::default());
+        let exchange_api: Arc<dyn ExchangeApi> = mock_api.clone();
+        let storage_manager: Arc<dyn StorageManager> = Arc::new(MemoryStorage::default());
+        let market = Market::new(market_rx, exchange_api, storage_manager, false).await;
+
+        for symbol in ["BTCUSDT", "ETHUSDT"] {
+            market
+                .open_stream(StreamType::Kline, symbol, Some(Interval::Min1))
+                .await
+                .unwrap();
+        }
+        // klines of other intervals or without a kline stream are left out
+        market
+            .open_stream(StreamType::Kline, "SOLUSDT", Some(Interval::Min5))
+            .await
+            .unwrap();
+
+        let start = generate_ts() - Interval::Min1.to_mili() * 2;
+        {
+            let mut data = market.data.lock().await;
+            for (symbol, prices) in [("BTCUSDT", [100.0, 101.0]), ("ETHUSDT", [10.0, 11.0])] {
+                let klines = make_klines(symbol, Interval::Min1, start, &prices);
+                data.update_kline(klines[0].clone(), true).await;
+                data.update_kline(klines[1].clone(), symbol == "ETHUSDT")
+                    .await;
+            }
+            for kline in make_klines("SOLUSDT", Interval::Min5, start, &[1.0]) {
+                data.update_kline(kline, true).await;
+            }
+            for kline in make_klines("XRPUSDT", Interval::Min1, start, &[1.0]) {
+                data.update_kline(kline, true).await;
+            }
+        }
+
+        let latest = market.latest_klines(Interval::Min1).await;
+
+        assert_eq!(latest.len(), 2);
+        assert_eq!(latest["BTCUSDT"].kline.close, 101.0);
+        assert!(!latest["BTCUSDT"].closed);
+        assert_eq!(latest["ETHUSDT"].kline.close, 11.0);
+        assert!(latest["ETHUSDT"].closed);
+
+        // served from memory only
+        assert_eq!(mock_api.last_kline_requests(), 0);
+    }
+
+    #[test]
+    async fn test_active_streams_sorted() {
+        let (_, market_rx) = build_market_channel(DEFAULT_MARKET_CHANNEL_CAPACITY);
+        let exchange_api: Arc<dyn ExchangeApi> = Arc::new(MockExchangeApi::default());
+        let storage_manager: Arc<dyn StorageManager> = Arc::new(MemoryStorage::default());
+        let market = Market::new(market_rx, exchange_api, storage_manager, false).await;
+
+        for symbol in ["SOLUSDT", "BTCUSDT", "XRPUSDT", "ETHUSDT", "ADAUSDT"] {
+            market
+                .open_stream(StreamType::Kline, symbol, Some(Interval::Min1))
+                .await
+                .unwrap();
+            market
+                .open_stream(StreamType::Ticker, symbol, None)
+                .await
+                .unwrap();
+        }
+
+        let ids = |streams: Vec<StreamMeta>| -> Vec<String> {
+            streams.into_iter().map(|stream| stream.id).collect()
+        };
+
+        let first = ids(market.active_streams().await);
+        let mut sorted = first.clone();
+        sorted.sort();
+        assert_eq!(first.len(), 10);
+        assert_eq!(first, sorted);
+
+        for _ in 0..5 {
+            assert_eq!(ids(market.active_streams().await), first);
+        }
+    }
+}
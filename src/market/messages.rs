@@ -1,4 +1,8 @@
-use crate::market::{kline::Kline, ticker::Ticker};
+use serde::{Deserialize, Serialize};
+
+use crate::market::{
+    channel::OverflowPolicy, kline::Kline, orderbook::DepthUpdate, ticker::Ticker,
+};
 
 use super::trade::Trade;
 
@@ -9,10 +13,33 @@ use super::trade::Trade;
 /// - UpdateTicker(Ticker): Carries a Ticker instance representing the latest ticker information to be updated in the market data.
 ///
 /// - UpdateKline(Kline): Contains a Kline instance representing a new or updated kline data point to be incorporated into the market data.
+///
+/// - UpdatePartialKline(Kline): Contains a kline whose interval is still in progress, its volume is incomplete so it is not persisted until it closes.
+///
+/// - UpdateDepth(DepthUpdate): Carries incremental order book changes applied to the order book of the symbol.
 
-#[derive(Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub enum MarketMessage {
     UpdateTicker(Ticker),
     UpdateKline(Kline),
+    UpdatePartialKline(Kline),
     UpdateMarketTrade(Trade),
+    UpdateDepth(DepthUpdate),
+}
+
+impl MarketMessage {
+    /// Overflow policy of the message once the market channel is full, tickers and partial klines
+    /// are superseded by the next update so can be dropped, other messages would leave gaps in the
+    /// market data if lost.
+
+    pub fn overflow_policy(&self) -> OverflowPolicy {
+        match self {
+            MarketMessage::UpdateTicker(_) | MarketMessage::UpdatePartialKline(_) => {
+                OverflowPolicy::DropOldest
+            }
+            MarketMessage::UpdateKline(_)
+            | MarketMessage::UpdateMarketTrade(_)
+            | MarketMessage::UpdateDepth(_) => OverflowPolicy::Block,
+        }
+    }
 }
@@ -1,7 +1,16 @@
+pub mod alert;
+pub mod backfill;
+pub mod channel;
+pub mod flow;
 pub mod interval;
 pub mod kline;
 pub mod market;
 pub mod messages;
+pub mod orderbook;
+pub mod reader;
+pub mod recorder;
+pub mod subscription;
+pub mod symbol_info;
 pub mod ticker;
 pub mod trade;
 pub mod types;
@@ -0,0 +1,524 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
+use log::warn;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    exchange::{
+        api::ExchangeApi,
+        types::{ApiError, ApiResult},
+    },
+    market::types::ArcMutex,
+};
+
+/// Number of levels requested per side when fetching a depth snapshot.
+pub const DEPTH_SNAPSHOT_LIMIT: usize = 1000;
+
+/// Number of levels per side returned when the caller doesn't specify a depth.
+pub const DEFAULT_DEPTH_LEVELS: usize = 10;
+
+/// A price level of an order book.
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct DepthLevel {
+    pub price: f64,
+    pub qty: f64,
+}
+
+/// Full order book of a symbol at a point in time, fetched from the exchange REST API.
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DepthSnapshot {
+    pub symbol: String,
+    pub last_update_id: u64,
+    pub bids: Vec<DepthLevel>,
+    pub asks: Vec<DepthLevel>,
+}
+
+impl DepthSnapshot {
+    /// Parses a snapshot returned by the Binance depth endpoint.
+    ///
+    /// # Arguments
+    ///
+    /// * `symbol` - The symbol the snapshot was requested for.
+    /// * `data` - The response of the depth endpoint.
+    ///
+    /// # Returns
+    ///
+    /// The parsed `DepthSnapshot`, or a parsing error if a field is missing.
+
+    pub fn from_binance_value(symbol: &str, data: &Value) -> ApiResult<Self> {
+        Ok(Self {
+            symbol: symbol.to_string(),
+            last_update_id: parse_update_id("lastUpdateId", data)?,
+            bids: parse_levels("bids", data)?,
+            asks: parse_levels("asks", data)?,
+        })
+    }
+}
+
+/// Incremental order book changes received on a depth stream.
+///
+/// A quantity of `0` removes the price level from the book.
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DepthUpdate {
+    pub symbol: String,
+    pub event_time: u64,
+    /// First update ID in the event, `U` on Binance.
+    pub first_update_id: u64,
+    /// Final update ID in the event, `u` on Binance.
+    pub final_update_id: u64,
+    /// Final update ID of the previous event, `pu` on Binance futures, `None` if the exchange
+    /// doesn't provide it.
+    pub prev_final_update_id: Option<u64>,
+    pub bids: Vec<DepthLevel>,
+    pub asks: Vec<DepthLevel>,
+}
+
+impl DepthUpdate {
+    /// Parses a depth update event received on a Binance depth stream.
+    ///
+    /// # Arguments
+    ///
+    /// * `lookup` - The decoded stream payload.
+    ///
+    /// # Returns
+    ///
+    /// The parsed `DepthUpdate`, or a parsing error if a field is missing.
+
+    pub fn from_binance_lookup(lookup: HashMap<String, Value>) -> ApiResult<Self> {
+        let data = Value::Object(lookup.into_iter().collect());
+
+        let symbol = data
+            .get("s")
+            .and_then(|symbol| symbol.as_str())
+            .ok_or_else(|| ApiError::Parsing("Depth update symbol missing".to_string()))?;
+
+        Ok(Self {
+            symbol: symbol.to_string(),
+            event_time: parse_update_id("E", &data)?,
+            first_update_id: parse_update_id("U", &data)?,
+            final_update_id: parse_update_id("u", &data)?,
+            prev_final_update_id: data.get("pu").and_then(|pu| pu.as_u64()),
+            bids: parse_levels("b", &data)?,
+            asks: parse_levels("a", &data)?,
+        })
+    }
+}
+
+/// Outcome of applying a depth update to an order book.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DepthUpdateResult {
+    /// The update was applied to the book.
+    Applied,
+    /// The update is older than the book and was ignored.
+    Stale,
+    /// Updates were missed between the book and the update, the book must be re-snapshotted.
+    Gap,
+}
+
+/// Best bid and ask levels along with the top levels of each side of an order book.
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct OrderBookDepth {
+    pub symbol: String,
+    pub last_update_id: u64,
+    pub best_bid: Option<DepthLevel>,
+    pub best_ask: Option<DepthLevel>,
+    /// Bids ordered from the highest price.
+    pub bids: Vec<DepthLevel>,
+    /// Asks ordered from the lowest price.
+    pub asks: Vec<DepthLevel>,
+}
+
+/// Local order book of a symbol, built from a snapshot and kept in sync with depth updates.
+///
+/// Levels are keyed by the bits of their price, the bits of positive prices sort in the same
+/// order as the prices themselves.
+
+#[derive(Debug, Clone)]
+pub struct OrderBook {
+    pub symbol: String,
+    last_update_id: u64,
+    /// Whether a depth update has been applied since the snapshot.
+    synced: bool,
+    bids: BTreeMap<u64, DepthLevel>,
+    asks: BTreeMap<u64, DepthLevel>,
+}
+
+impl OrderBook {
+    /// Builds an order book from a snapshot.
+
+    pub fn from_snapshot(snapshot: DepthSnapshot) -> Self {
+        let mut book = Self {
+            symbol: snapshot.symbol,
+            last_update_id: snapshot.last_update_id,
+            synced: false,
+            bids: BTreeMap::new(),
+            asks: BTreeMap::new(),
+        };
+
+        update_levels(&mut book.bids, &snapshot.bids);
+        update_levels(&mut book.asks, &snapshot.asks);
+
+        book
+    }
+
+    /// Applies a depth update following the Binance order book sync procedure.
+    ///
+    /// Updates which end before the snapshot are ignored. The first update applied must span the
+    /// snapshot update ID, every following update must continue from the previous update.
+    ///
+    /// # Arguments
+    ///
+    /// * `update` - The depth update received on the stream.
+    ///
+    /// # Returns
+    ///
+    /// Whether the update was applied, ignored, or revealed a gap in the updates.
+
+    pub fn apply_update(&mut self, update: &DepthUpdate) -> DepthUpdateResult {
+        if update.final_update_id < self.last_update_id
+            || (self.synced && update.final_update_id == self.last_update_id)
+        {
+            return DepthUpdateResult::Stale;
+        }
+
+        let is_continuous = if !self.synced {
+            update.first_update_id <= self.last_update_id
+        } else {
+            match update.prev_final_update_id {
+                Some(prev_final_update_id) => prev_final_update_id == self.last_update_id,
+                None => update.first_update_id == self.last_update_id + 1,
+            }
+        };
+
+        if !is_continuous {
+            return DepthUpdateResult::Gap;
+        }
+
+        update_levels(&mut self.bids, &update.bids);
+        update_levels(&mut self.asks, &update.asks);
+        self.last_update_id = update.final_update_id;
+        self.synced = true;
+
+        DepthUpdateResult::Applied
+    }
+
+    /// Returns the ID of the last update applied to the book.
+
+    pub fn last_update_id(&self) -> u64 {
+        self.last_update_id
+    }
+
+    /// Returns the highest bid.
+
+    pub fn best_bid(&self) -> Option<DepthLevel> {
+        self.bids.values().next_back().copied()
+    }
+
+    /// Returns the lowest ask.
+
+    pub fn best_ask(&self) -> Option<DepthLevel> {
+        self.asks.values().next().copied()
+    }
+
+    /// Returns the top levels of each side of the book.
+    ///
+    /// # Arguments
+    ///
+    /// * `levels` - Number of levels to return per side.
+    ///
+    /// # Returns
+    ///
+    /// An `OrderBookDepth` with bids from the highest price and asks from the lowest price.
+
+    pub fn depth(&self, levels: usize) -> OrderBookDepth {
+        OrderBookDepth {
+            symbol: self.symbol.clone(),
+            last_update_id: self.last_update_id,
+            best_bid: self.best_bid(),
+            best_ask: self.best_ask(),
+            bids: self.bids.values().rev().take(levels).copied().collect(),
+            asks: self.asks.values().take(levels).copied().collect(),
+        }
+    }
+}
+
+/// State of the order book of a symbol.
+
+#[derive(Debug, Clone)]
+enum BookState {
+    /// A snapshot is being fetched, updates received meanwhile are buffered to be applied to it.
+    Snapshotting(Vec<DepthUpdate>),
+    Synced(OrderBook),
+}
+
+/// Maintains an order book per symbol from depth stream updates, fetching a snapshot from the
+/// exchange when a book is first needed or a gap in the updates is detected.
+///
+/// Snapshots are fetched in a background task so the market keeps receiving messages meanwhile,
+/// clones of a manager share the same books.
+
+#[derive(Clone)]
+pub struct OrderBookManager {
+    exchange_api: Arc<dyn ExchangeApi>,
+    books: ArcMutex<HashMap<String, BookState>>,
+}
+
+impl OrderBookManager {
+    /// Creates an order book manager without any books.
+    ///
+    /// # Arguments
+    ///
+    /// * `exchange_api` - Exchange used to fetch depth snapshots.
+    ///
+    /// # Returns
+    ///
+    /// A new `OrderBookManager`.
+
+    pub fn new(exchange_api: Arc<dyn ExchangeApi>) -> Self {
+        Self {
+            exchange_api,
+            books: ArcMutex::new(HashMap::new()),
+        }
+    }
+
+    /// Applies a depth update to the book of its symbol.
+    ///
+    /// A snapshot is fetched in the background if the book doesn't exist or the update reveals a
+    /// gap. Updates which arrive while the snapshot is fetched are buffered and applied against
+    /// the new snapshot.
+    ///
+    /// # Arguments
+    ///
+    /// * `update` - The depth update received on the stream.
+
+    pub async fn handle_update(&self, update: &DepthUpdate) {
+        let mut books = self.books.lock().await;
+
+        match books.get_mut(&update.symbol) {
+            Some(BookState::Snapshotting(pending)) => {
+                pending.push(update.clone());
+                return;
+            }
+            Some(BookState::Synced(book)) => {
+                if book.apply_update(update) != DepthUpdateResult::Gap {
+                    return;
+                }
+
+                warn!(
+                    "Gap in {} depth updates after update {}, fetching a new snapshot",
+                    update.symbol,
+                    book.last_update_id()
+                );
+            }
+            None => {}
+        }
+
+        books.insert(
+            update.symbol.clone(),
+            BookState::Snapshotting(vec![update.clone()]),
+        );
+        self.spawn_snapshot(&update.symbol);
+    }
+
+    /// Gets the top levels of the order book of a symbol.
+    ///
+    /// # Arguments
+    ///
+    /// * `symbol` - The symbol of the order book.
+    /// * `levels` - Number of levels to return per side.
+    ///
+    /// # Returns
+    ///
+    /// The `OrderBookDepth` of the book, `None` if the book doesn't exist or its snapshot is
+    /// still being fetched.
+
+    pub async fn depth(&self, symbol: &str, levels: usize) -> Option<OrderBookDepth> {
+        match self.books.lock().await.get(symbol) {
+            Some(BookState::Synced(book)) => Some(book.depth(levels)),
+            _ => None,
+        }
+    }
+
+    /// Removes the book of a symbol, ie. once its depth stream is closed, discarding a snapshot
+    /// still being fetched.
+
+    pub async fn remove(&self, symbol: &str) {
+        self.books.lock().await.remove(symbol);
+    }
+
+    // ---
+    // Private Methods
+    // ---
+
+    /// Fetches a snapshot of the book of a symbol in the background and applies the updates
+    /// buffered meanwhile, the book is removed if the snapshot can't be fetched or the buffered
+    /// updates don't continue from it, so the next update fetches a new one.
+    fn spawn_snapshot(&self, symbol: &str) {
+        let manager = self.clone();
+        let symbol = symbol.to_string();
+
+        tokio::spawn(async move {
+            let snapshot = manager
+                .exchange_api
+                .get_depth_snapshot(&symbol, DEPTH_SNAPSHOT_LIMIT)
+                .await;
+
+            let mut books = manager.books.lock().await;
+
+            // the book was removed while the snapshot was fetched
+            let Some(BookState::Snapshotting(pending)) = books.remove(&symbol) else {
+                return;
+            };
+
+            let mut book = match snapshot {
+                Ok(snapshot) => OrderBook::from_snapshot(snapshot),
+                Err(e) => {
+                    warn!("Unable to fetch {symbol} depth snapshot, {e}");
+                    return;
+                }
+            };
+
+            for update in &pending {
+                if book.apply_update(update) == DepthUpdateResult::Gap {
+                    warn!("Buffered {symbol} depth updates don't continue from the snapshot");
+                    return;
+                }
+            }
+
+            books.insert(symbol, BookState::Synced(book));
+        });
+    }
+}
+
+/// Sets the quantity of each level, levels with a quantity of `0` are removed.
+fn update_levels(side: &mut BTreeMap<u64, DepthLevel>, levels: &[DepthLevel]) {
+    for level in levels {
+        if level.qty == 0.0 {
+            side.remove(&level.price.to_bits());
+        } else {
+            side.insert(level.price.to_bits(), *level);
+        }
+    }
+}
+
+fn parse_update_id(key: &str, data: &Value) -> ApiResult<u64> {
+    data.get(key)
+        .and_then(|id| id.as_u64())
+        .ok_or_else(|| ApiError::Parsing(format!("Depth field {key} missing")))
+}
+
+/// Parses `[price, qty]` string pairs into depth levels.
+fn parse_levels(key: &str, data: &Value) -> ApiResult<Vec<DepthLevel>> {
+    let levels = data
+        .get(key)
+        .and_then(|levels| levels.as_array())
+        .ok_or_else(|| ApiError::Parsing(format!("Depth field {key} missing")))?;
+
+    levels
+        .iter()
+        .map(|level| {
+            let price = level.get(0).and_then(|price| price.as_str());
+            let qty = level.get(1).and_then(|qty| qty.as_str());
+
+            match (price, qty) {
+                (Some(price), Some(qty)) => Ok(DepthLevel {
+                    price: price.parse()?,
+                    qty: qty.parse()?,
+                }),
+                _ => Err(ApiError::Parsing(format!("Invalid depth level {level}"))),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::test;
+
+    use crate::exchange::mock::MockExchangeApi;
+
+    fn level(price: f64, qty: f64) -> DepthLevel {
+        DepthLevel { price, qty }
+    }
+
+    fn update(
+        first_update_id: u64,
+        final_update_id: u64,
+        prev_final_update_id: u64,
+        bids: Vec<DepthLevel>,
+        asks: Vec<DepthLevel>,
+    ) -> DepthUpdate {
+        DepthUpdate {
+            symbol: "BTCUSDT".to_string(),
+            event_time: 1_700_000_000_000,
+            first_update_id,
+            final_update_id,
+            prev_final_update_id: Some(prev_final_update_id),
+            bids,
+            asks,
+        }
+    }
+
+    /// Waits for the snapshot fetched in the background to be applied to the book.
+    async fn synced_depth(
+        manager: &OrderBookManager,
+        last_update_id: u64,
+    ) -> Option<OrderBookDepth> {
+        for _ in 0..100 {
+            match manager.depth("BTCUSDT", 2).await {
+                Some(depth) if depth.last_update_id == last_update_id => return Some(depth),
+                _ => tokio::time::sleep(std::time::Duration::from_millis(10)).await,
+            }
+        }
+        None
+    }
+
+    #[test]
+    async fn test_order_book_snapshot_and_diffs() {
+        let mock_api = Arc::new(MockExchangeApi::default());
+        let exchange_api: Arc<dyn ExchangeApi> = mock_api.clone();
+        let manager = OrderBookManager::new(exchange_api);
+
+        // mock snapshot has last update ID 100, bids 99, 98 and asks 101, 102, both updates
+        // arrive before the snapshot is fetched
+        let first = update(95, 105, 94, vec![level(99.0, 3.0)], vec![level(101.0, 0.0)]);
+        manager.handle_update(&first).await;
+        assert!(manager.depth("BTCUSDT", 2).await.is_none());
+
+        let second = update(
+            106,
+            110,
+            105,
+            vec![level(99.5, 1.0)],
+            vec![level(101.5, 2.0)],
+        );
+        manager.handle_update(&second).await;
+
+        let depth = synced_depth(&manager, 110).await.unwrap();
+        assert_eq!(depth.best_bid, Some(level(99.5, 1.0)));
+        assert_eq!(depth.best_ask, Some(level(101.5, 2.0)));
+        assert_eq!(depth.bids, vec![level(99.5, 1.0), level(99.0, 3.0)]);
+        assert_eq!(depth.asks, vec![level(101.5, 2.0), level(102.0, 2.0)]);
+        assert_eq!(mock_api.depth_snapshot_requests(), 1);
+
+        // updates 111 to 119 were missed, the next snapshot has last update ID 200
+        let gap = update(120, 125, 119, vec![level(99.9, 1.0)], vec![]);
+        manager.handle_update(&gap).await;
+
+        let depth = synced_depth(&manager, 200).await.unwrap();
+        assert_eq!(mock_api.depth_snapshot_requests(), 2);
+        assert_eq!(depth.best_bid, Some(level(99.0, 1.0)));
+
+        // the book is dropped with its stream
+        manager.remove("BTCUSDT").await;
+        assert!(manager.depth("BTCUSDT", 2).await.is_none());
+    }
+}
@@ -0,0 +1,202 @@
+use std::{collections::HashMap, sync::Arc};
+
+use log::warn;
+
+use crate::{
+    exchange::{api::ExchangeApi, rate_limit::RateLimiter},
+    market::{
+        interval::Interval, kline::Kline, market::MarketData, ticker::Ticker, types::ArcMutex,
+    },
+    utils::time::{generate_ts, SEC_AS_MILI},
+};
+
+/// Reads the latest prices, tickers and klines of the market, falling back to the exchange when
+/// the market holds no recent data.
+///
+/// A reader is cloned out of the market so REST fallbacks don't hold the market lock, its
+/// requests share the rate limiter of the market. The market data lock is only held while
+/// reading cached data, never across a request.
+
+#[derive(Clone)]
+pub struct MarketReader {
+    data: ArcMutex<MarketData>,
+    exchange_api: Arc<dyn ExchangeApi>,
+    rate_limiter: RateLimiter,
+}
+
+impl MarketReader {
+    /// Creates a reader of market data.
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - The market data kept up to date by the market streams.
+    /// * `exchange_api` - Exchange the latest data is fetched from when not held by the market.
+    /// * `rate_limiter` - Limiter shared with the market's other REST requests.
+    ///
+    /// # Returns
+    ///
+    /// A new `MarketReader`.
+
+    pub fn new(
+        data: ArcMutex<MarketData>,
+        exchange_api: Arc<dyn ExchangeApi>,
+        rate_limiter: RateLimiter,
+    ) -> Self {
+        Self {
+            data,
+            exchange_api,
+            rate_limiter,
+        }
+    }
+
+    /// Fetches the latest price of a symbol.
+    ///
+    /// # Arguments
+    ///
+    /// * `symbol` - The trading symbol.
+    ///
+    /// # Returns
+    ///
+    /// The latest price of the symbol, or `None` if it's not available.
+
+    pub async fn last_price(&self, symbol: &str) -> Option<f64> {
+        self.last_ticker(symbol)
+            .await
+            .map(|ticker| ticker.last_price)
+    }
+
+    /// Fetches the latest prices of many symbols.
+    ///
+    /// Prices are served from tickers updated within the last second, the prices of all other
+    /// symbols are fetched from the exchange together.
+    ///
+    /// # Arguments
+    ///
+    /// * `symbols` - The trading symbols.
+    ///
+    /// # Returns
+    ///
+    /// The latest price by symbol, symbols without a price are left out.
+
+    pub async fn last_prices(&self, symbols: &[String]) -> HashMap<String, f64> {
+        let last_sec = generate_ts() - SEC_AS_MILI;
+        let mut prices = HashMap::new();
+        let mut stale = vec![];
+
+        {
+            let data = self.data.lock().await;
+
+            for symbol in symbols {
+                let cached = data
+                    .ticker_data(symbol, last_sec)
+                    .and_then(|ticker_data| ticker_data.tickers().last().cloned());
+
+                match cached {
+                    Some(ticker) => {
+                        prices.insert(symbol.clone(), ticker.last_price);
+                    }
+                    None if !stale.contains(symbol) => stale.push(symbol.clone()),
+                    None => {}
+                }
+            }
+        }
+
+        if !stale.is_empty() {
+            self.rate_limiter.acquire().await;
+            match self.exchange_api.get_last_prices(&stale).await {
+                Ok(fetched) => prices.extend(fetched),
+                Err(e) => warn!("Unable to fetch last prices of {}, {e}", stale.join(", ")),
+            }
+        }
+
+        prices
+    }
+
+    /// Fetches the latest kline of a symbol and interval.
+    ///
+    /// # Arguments
+    ///
+    /// * `symbol` - The trading symbol.
+    /// * `interval` - The interval of the kline.
+    /// * `closed_only` - Excludes the kline in progress, whose close and volume are still
+    ///   incomplete.
+    ///
+    /// # Returns
+    ///
+    /// The latest kline, or `None` if it's not available.
+
+    pub async fn last_kline(
+        &self,
+        symbol: &str,
+        interval: Interval,
+        closed_only: bool,
+    ) -> Option<Kline> {
+        let now = generate_ts();
+
+        // the last closed kline opened up to two intervals ago
+        let last_open_time = if closed_only {
+            now - interval.to_mili() * 2
+        } else {
+            now - interval.to_mili()
+        };
+
+        let is_included = |kline: &Kline| !closed_only || kline.close_time <= now;
+
+        let kline_data = self
+            .data
+            .lock()
+            .await
+            .kline_data(symbol, interval, Some(last_open_time), None, None)
+            .await;
+
+        if let Some(kline_data) = kline_data {
+            return kline_data.klines().into_iter().rev().find(is_included);
+        }
+
+        self.rate_limiter.acquire().await;
+
+        // the latest kline is in progress, the last closed kline is fetched with it
+        let klines = match closed_only {
+            true => self
+                .exchange_api
+                .get_klines(symbol, interval, last_open_time, now)
+                .await
+                .ok(),
+            false => self
+                .exchange_api
+                .get_kline(symbol, interval)
+                .await
+                .ok()
+                .map(|kline| vec![kline]),
+        };
+
+        klines.and_then(|klines| klines.into_iter().rev().find(is_included))
+    }
+
+    /// Fetches the latest ticker of a symbol, tickers must be updated within the last second.
+    ///
+    /// # Arguments
+    ///
+    /// * `symbol` - The trading symbol.
+    ///
+    /// # Returns
+    ///
+    /// The latest ticker, or `None` if it's not available.
+
+    pub async fn last_ticker(&self, symbol: &str) -> Option<Ticker> {
+        let last_sec = generate_ts() - SEC_AS_MILI;
+        let cached = self
+            .data
+            .lock()
+            .await
+            .ticker_data(symbol, last_sec)
+            .and_then(|ticker_data| ticker_data.tickers().last().cloned());
+
+        if cached.is_some() {
+            return cached;
+        }
+
+        self.rate_limiter.acquire().await;
+        self.exchange_api.get_ticker(symbol).await.ok()
+    }
+}
@@ -0,0 +1,469 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use log::info;
+use serde::{Deserialize, Serialize};
+use tokio::{task::JoinHandle, time};
+
+use crate::{
+    market::{channel::MarketSender, messages::MarketMessage},
+    utils::time::generate_ts,
+};
+
+/// A market message along with the time it was received, one entry of the market message log.
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RecordedMessage {
+    pub timestamp: u64,
+    pub message: MarketMessage,
+}
+
+/// Appends every market message received by the market to an on-disk log.
+///
+/// The log contains one JSON encoded `RecordedMessage` per line, so it can be replayed with
+/// `replay` to reproduce the exact market input a strategy saw.
+
+pub struct MarketRecorder {
+    file: File,
+}
+
+impl MarketRecorder {
+    /// Opens a market message log for appending, creating it if it doesn't exist.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path of the log file.
+    ///
+    /// # Returns
+    ///
+    /// A new `MarketRecorder`, or an IO error if the log could not be opened.
+
+    pub fn new(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref();
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+
+        info!("Recording market messages to {}", path.display());
+
+        Ok(Self { file })
+    }
+
+    /// Appends a market message to the log, timestamped with the current time.
+    ///
+    /// # Arguments
+    ///
+    /// * `message` - The market message to record.
+
+    pub fn record(&mut self, message: &MarketMessage) -> io::Result<()> {
+        let recorded = RecordedMessage {
+            timestamp: generate_ts(),
+            message: message.clone(),
+        };
+
+        let line = serde_json::to_string(&recorded)?;
+        writeln!(self.file, "{line}")
+    }
+}
+
+/// Replays a market message log into a market channel.
+///
+/// Messages are sent in the order they were recorded, the time between messages is the recorded
+/// time divided by `speed`. A `speed` of `0.0` or less replays without any delay.
+///
+/// # Arguments
+///
+/// * `path` - Path of the log file written by `MarketRecorder`.
+/// * `market_tx` - Sender of the market channel to replay into.
+/// * `speed` - Replay speed relative to the recorded time, ie. `2.0` replays twice as fast.
+/// * `count` - Counter of the messages replayed, incremented as each message is sent.
+///
+/// # Returns
+///
+/// The number of messages replayed, or an IO error if the log could not be read or parsed.
+
+pub async fn replay(
+    path: impl AsRef<Path>,
+    market_tx: MarketSender,
+    speed: f64,
+    count: &AtomicUsize,
+) -> io::Result<usize> {
+    let reader = BufReader::new(File::open(path)?);
+
+    let mut last_timestamp: Option<u64> = None;
+
+    for line in reader.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let recorded: RecordedMessage = serde_json::from_str(&line)?;
+
+        if let Some(last_timestamp) = last_timestamp {
+            if speed > 0.0 {
+                let delay = recorded.timestamp.saturating_sub(last_timestamp) as f64 / speed;
+                time::sleep(Duration::from_millis(delay as u64)).await;
+            }
+        }
+        last_timestamp = Some(recorded.timestamp);
+
+        market_tx
+            .send(recorded.message)
+            .await
+            .map_err(|e| io::Error::new(io::ErrorKind::BrokenPipe, e.to_string()))?;
+
+        count.fetch_add(1, Ordering::Relaxed);
+    }
+
+    Ok(count.load(Ordering::Relaxed))
+}
+
+/// Resolves the path of a market message log to replay, which must be a file within the data
+/// directory.
+///
+/// # Arguments
+///
+/// * `data_directory` - Directory replayed logs are read from.
+/// * `path` - Path of the log, relative paths are resolved within the data directory.
+///
+/// # Returns
+///
+/// The canonical path of the log, a `NotFound` error if it doesn't exist or a
+/// `PermissionDenied` error if it's outside the data directory.
+
+pub fn resolve_log_path(data_directory: &Path, path: impl AsRef<Path>) -> io::Result<PathBuf> {
+    let data_directory = data_directory.canonicalize()?;
+    let log_path = data_directory.join(path).canonicalize()?;
+
+    if !log_path.starts_with(&data_directory) {
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!(
+                "{} is outside the data directory {}",
+                log_path.display(),
+                data_directory.display()
+            ),
+        ));
+    }
+
+    if !log_path.is_file() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("{} is not a file", log_path.display()),
+        ));
+    }
+
+    Ok(log_path)
+}
+
+/// State of a market replay.
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(tag = "state")]
+pub enum ReplayState {
+    Running,
+    Finished,
+    Cancelled,
+    Failed { error: String },
+}
+
+/// Status of a market replay, reported by the API.
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ReplayStatus {
+    pub path: PathBuf,
+    pub speed: f64,
+    pub started_at: u64,
+    #[serde(flatten)]
+    pub state: ReplayState,
+    /// Number of messages replayed so far.
+    pub messages: usize,
+}
+
+/// A market message log being replayed in the background, which can be cancelled.
+
+pub struct MarketReplay {
+    path: PathBuf,
+    speed: f64,
+    started_at: u64,
+    state: Arc<Mutex<ReplayState>>,
+    count: Arc<AtomicUsize>,
+    handle: JoinHandle<()>,
+}
+
+impl MarketReplay {
+    /// Starts replaying a market message log into a market channel, see `replay`.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path of the log file written by `MarketRecorder`.
+    /// * `market_tx` - Sender of the market channel to replay into.
+    /// * `speed` - Replay speed relative to the recorded time.
+    ///
+    /// # Returns
+    ///
+    /// The running `MarketReplay`.
+
+    pub fn start(path: PathBuf, market_tx: MarketSender, speed: f64) -> Self {
+        let state = Arc::new(Mutex::new(ReplayState::Running));
+        let count = Arc::new(AtomicUsize::new(0));
+
+        let task_path = path.clone();
+        let task_state = state.clone();
+        let task_count = count.clone();
+        let handle = tokio::spawn(async move {
+            let result = replay(&task_path, market_tx, speed, &task_count).await;
+
+            // a cancelled replay keeps its state
+            let mut state = task_state.lock().unwrap();
+            if *state == ReplayState::Running {
+                *state = match result {
+                    Ok(count) => {
+                        info!(
+                            "Replayed {count} market messages from {}",
+                            task_path.display()
+                        );
+                        ReplayState::Finished
+                    }
+                    Err(e) => ReplayState::Failed {
+                        error: e.to_string(),
+                    },
+                };
+            }
+        });
+
+        Self {
+            path,
+            speed,
+            started_at: generate_ts(),
+            state,
+            count,
+            handle,
+        }
+    }
+
+    /// Whether the replay is still sending messages.
+
+    pub fn is_running(&self) -> bool {
+        *self.state.lock().unwrap() == ReplayState::Running
+    }
+
+    /// Gets the status of the replay.
+
+    pub fn status(&self) -> ReplayStatus {
+        ReplayStatus {
+            path: self.path.clone(),
+            speed: self.speed,
+            started_at: self.started_at,
+            state: self.state.lock().unwrap().clone(),
+            messages: self.count.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Cancels the replay if it's still running.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the replay was cancelled, `false` if it had already ended.
+
+    pub fn cancel(&self) -> bool {
+        let mut state = self.state.lock().unwrap();
+        if *state != ReplayState::Running {
+            return false;
+        }
+
+        self.handle.abort();
+        *state = ReplayState::Cancelled;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    use tokio::test;
+    use uuid::Uuid;
+
+    use crate::{
+        account::trade::OrderSide,
+        market::{
+            channel::{build_market_channel, DEFAULT_MARKET_CHANNEL_CAPACITY},
+            interval::Interval,
+            kline::Kline,
+            market::MarketData,
+            ticker::Ticker,
+            trade::Trade,
+        },
+        storage::{fs::FsStorage, manager::StorageManager},
+    };
+
+    fn build_messages(symbol: &str) -> Vec<MarketMessage> {
+        let open_time = 1_700_000_000_000;
+        let kline = |close: f64| Kline {
+            symbol: symbol.to_string(),
+            interval: Interval::Min1,
+            open: 100.0,
+            high: close.max(100.0),
+            low: close.min(100.0),
+            close,
+            volume: 10.0,
+            quote_volume: 0.0,
+            open_time,
+            close_time: open_time + 59_999,
+        };
+        let ticker = |time: u64, last_price: f64| Ticker {
+            time,
+            symbol: symbol.to_string(),
+            high: 101.0,
+            low: 99.0,
+            traded_vol: 10.0,
+            last_price,
+            open_price: 100.0,
+        };
+        let trade = |timestamp: u64, qty: f64| Trade {
+            symbol: symbol.to_string(),
+            timestamp,
+            qty,
+            price: 100.0,
+            order_side: OrderSide::Buy,
+            id: None,
+        };
+
+        vec![
+            MarketMessage::UpdateKline(kline(100.5)),
+            MarketMessage::UpdateTicker(ticker(open_time, 100.5)),
+            MarketMessage::UpdateMarketTrade(trade(open_time, 1.0)),
+            MarketMessage::UpdateKline(kline(101.0)),
+            MarketMessage::UpdateTicker(ticker(open_time + 1_000, 101.0)),
+            MarketMessage::UpdateMarketTrade(trade(open_time + 1_000, 2.0)),
+            MarketMessage::UpdateMarketTrade(trade(open_time + 1_200, 0.5)),
+        ]
+    }
+
+    async fn market_state(
+        market_data: &mut MarketData,
+        symbol: &str,
+    ) -> (Vec<Kline>, serde_json::Value, Vec<Trade>) {
+        let klines = market_data
+            .kline_data(symbol, Interval::Min1, None, None, None)
+            .await
+            .unwrap()
+            .klines();
+        let tickers = market_data.ticker_data(symbol, 0).unwrap().tickers();
+        let trades = market_data
+            .trade_data(symbol, None, None, None)
+            .await
+            .unwrap()
+            .trades();
+
+        (klines, serde_json::to_value(tickers).unwrap(), trades)
+    }
+
+    #[test]
+    async fn test_record_and_replay() {
+        let symbol = format!("REPLAY{}", Uuid::new_v4().simple());
+        let path = std::env::temp_dir().join(format!("market-{}.log", Uuid::new_v4()));
+        let storage_manager: Arc<dyn StorageManager> = Arc::new(FsStorage::default());
+
+        // record messages while applying them to the original market data
+        let mut original = MarketData::new(storage_manager.clone());
+        let mut recorder = MarketRecorder::new(&path).unwrap();
+        for message in build_messages(&symbol) {
+            recorder.record(&message).unwrap();
+            original.handle_message(message).await;
+        }
+
+        // replay into a fresh market data
+        let (market_tx, market_rx) = build_market_channel(DEFAULT_MARKET_CHANNEL_CAPACITY);
+        let count = replay(&path, market_tx, 0.0, &AtomicUsize::new(0))
+            .await
+            .unwrap();
+        assert_eq!(count, 7);
+
+        let mut replayed = MarketData::new(storage_manager);
+        for _ in 0..count {
+            let message = market_rx.recv().await.unwrap();
+            replayed.handle_message(message).await;
+        }
+
+        let original_state = market_state(&mut original, &symbol).await;
+        let replayed_state = market_state(&mut replayed, &symbol).await;
+        assert_eq!(original_state, replayed_state);
+        assert_eq!(original_state.0.len(), 1);
+        assert_eq!(original_state.0[0].close, 101.0);
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    async fn test_resolve_log_path_within_data_directory() {
+        let data_directory = std::env::temp_dir().join(format!("replay-{}", Uuid::new_v4()));
+        fs::create_dir_all(data_directory.join("logs")).unwrap();
+        fs::write(data_directory.join("logs/market.log"), "").unwrap();
+
+        let resolved = resolve_log_path(&data_directory, "logs/market.log").unwrap();
+        assert!(resolved.ends_with("logs/market.log"));
+
+        let err = resolve_log_path(&data_directory, "logs/missing.log").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+
+        // paths escaping the data directory are refused
+        let outside = std::env::temp_dir().join(format!("market-{}.log", Uuid::new_v4()));
+        fs::write(&outside, "").unwrap();
+        let err = resolve_log_path(&data_directory, &outside).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+        let escaping = format!("../{}", outside.file_name().unwrap().to_str().unwrap());
+        let err = resolve_log_path(&data_directory, escaping).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+
+        fs::remove_file(outside).unwrap();
+        fs::remove_dir_all(data_directory).unwrap();
+    }
+
+    #[test]
+    async fn test_cancel_market_replay() {
+        let path = std::env::temp_dir().join(format!("market-{}.log", Uuid::new_v4()));
+
+        // messages recorded a minute apart, so the replay waits after the first
+        let lines: Vec<String> = build_messages("BTCUSDT")
+            .into_iter()
+            .enumerate()
+            .map(|(i, message)| {
+                let recorded = RecordedMessage {
+                    timestamp: i as u64 * 60_000,
+                    message,
+                };
+                serde_json::to_string(&recorded).unwrap()
+            })
+            .collect();
+        fs::write(&path, lines.join("\n")).unwrap();
+
+        let (market_tx, _market_rx) = build_market_channel(DEFAULT_MARKET_CHANNEL_CAPACITY);
+        let replay = MarketReplay::start(path.clone(), market_tx, 1.0);
+        time::sleep(Duration::from_millis(50)).await;
+
+        assert!(replay.is_running());
+        assert!(replay.cancel());
+        assert!(!replay.cancel());
+
+        let status = replay.status();
+        assert_eq!(status.state, ReplayState::Cancelled);
+        assert_eq!(status.messages, 1);
+
+        fs::remove_file(path).unwrap();
+    }
+}
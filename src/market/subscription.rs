@@ -0,0 +1,91 @@
+use crate::exchange::stream::StreamMeta;
+
+/// Reference counts the streams needed by the market and the strategies trading on it.
+///
+/// A stream is subscribed once per caller which needs it, ie. each strategy trading a symbol and
+/// interval. The stream only needs to be opened on its first subscription and can only be closed
+/// once its last subscriber has unsubscribed.
+
+#[derive(Debug, Default)]
+pub struct StreamSubscriptions {
+    subscriptions: Vec<Subscription>,
+}
+
+impl StreamSubscriptions {
+    /// Adds a subscriber to a stream.
+    ///
+    /// # Arguments
+    ///
+    /// * `stream_meta` - Metadata of the stream, kept while the stream has subscribers.
+    ///
+    /// # Returns
+    ///
+    /// The number of subscribers of the stream, `1` if it is the first subscription.
+
+    pub fn subscribe(&mut self, stream_meta: StreamMeta) -> usize {
+        match self
+            .subscriptions
+            .iter_mut()
+            .find(|subscription| subscription.meta.id == stream_meta.id)
+        {
+            Some(subscription) => {
+                subscription.subscribers += 1;
+                subscription.subscribers
+            }
+            None => {
+                self.subscriptions.push(Subscription {
+                    meta: stream_meta,
+                    subscribers: 1,
+                });
+                1
+            }
+        }
+    }
+
+    /// Removes a subscriber from a stream, the stream is dropped once it has no subscribers.
+    ///
+    /// # Arguments
+    ///
+    /// * `stream_id` - The ID of the stream.
+    ///
+    /// # Returns
+    ///
+    /// The number of remaining subscribers, `0` if the stream is no longer needed or was never
+    /// subscribed.
+
+    pub fn unsubscribe(&mut self, stream_id: &str) -> usize {
+        let remaining = match self
+            .subscriptions
+            .iter_mut()
+            .find(|subscription| subscription.meta.id == stream_id)
+        {
+            Some(subscription) => {
+                subscription.subscribers = subscription.subscribers.saturating_sub(1);
+                subscription.subscribers
+            }
+            None => 0,
+        };
+
+        self.subscriptions
+            .retain(|subscription| subscription.subscribers > 0);
+
+        remaining
+    }
+
+    /// Lists the metadata of all subscribed streams.
+
+    pub fn streams(&self) -> Vec<StreamMeta> {
+        self.subscriptions
+            .iter()
+            .map(|subscription| subscription.meta.clone())
+            .collect()
+    }
+}
+
+/// A subscribed stream and its number of subscribers.
+
+#[derive(Debug)]
+struct Subscription {
+    meta: StreamMeta,
+    subscribers: usize,
+}
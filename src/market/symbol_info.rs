@@ -0,0 +1,117 @@
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use tokio::time::Instant;
+
+use crate::{
+    exchange::{
+        api::{ExchangeApi, SymbolInfo},
+        types::{ApiError, ApiResult},
+    },
+    market::types::ArcMutex,
+};
+
+/// Time a symbol the exchange doesn't list is remembered, so repeated requests for it aren't sent
+/// to the exchange.
+pub const UNKNOWN_SYMBOL_TTL: Duration = Duration::from_secs(5 * 60);
+
+/// Cache of the trading rules of symbols, fetched from the exchange once.
+///
+/// The cache is cloned out of the market, so symbol info is fetched without holding the market
+/// lock. Symbols the exchange doesn't list are cached for `UNKNOWN_SYMBOL_TTL`, other failures
+/// aren't cached.
+
+#[derive(Clone)]
+pub struct SymbolInfoCache {
+    exchange_api: Arc<dyn ExchangeApi>,
+    known: ArcMutex<HashMap<String, SymbolInfo>>,
+    /// Symbols the exchange doesn't list, with the time and error of the last lookup.
+    unknown: ArcMutex<HashMap<String, (Instant, ApiError)>>,
+}
+
+impl SymbolInfoCache {
+    /// Creates an empty cache of the symbols of an exchange.
+    ///
+    /// # Arguments
+    ///
+    /// * `exchange_api` - The exchange symbol info is fetched from.
+    ///
+    /// # Returns
+    ///
+    /// A new `SymbolInfoCache`.
+
+    pub fn new(exchange_api: Arc<dyn ExchangeApi>) -> Self {
+        Self {
+            exchange_api,
+            known: ArcMutex::new(HashMap::new()),
+            unknown: ArcMutex::new(HashMap::new()),
+        }
+    }
+
+    /// Retrieves the trading rules of a symbol, fetched from the exchange if it isn't cached.
+    ///
+    /// # Arguments
+    ///
+    /// * `symbol` - The trading symbol to get information for.
+    ///
+    /// # Returns
+    ///
+    /// The `SymbolInfo` of the symbol, `ApiError::SymbolNotFound` if the exchange doesn't list
+    /// the symbol, or the error of the request.
+
+    pub async fn get(&self, symbol: &str) -> ApiResult<SymbolInfo> {
+        if let Some(info) = self.known.lock().await.get(symbol) {
+            return Ok(info.clone());
+        }
+
+        if let Some((checked_at, e)) = self.unknown.lock().await.get(symbol) {
+            if checked_at.elapsed() < UNKNOWN_SYMBOL_TTL {
+                return Err(e.clone());
+            }
+        }
+
+        match self.exchange_api.get_symbol_info(symbol).await {
+            Ok(info) => {
+                self.unknown.lock().await.remove(symbol);
+                self.known
+                    .lock()
+                    .await
+                    .insert(symbol.to_string(), info.clone());
+                Ok(info)
+            }
+            Err(e @ ApiError::SymbolNotFound(_)) => {
+                self.unknown
+                    .lock()
+                    .await
+                    .insert(symbol.to_string(), (Instant::now(), e.clone()));
+                Err(e)
+            }
+            Err(e) => Err(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::test;
+
+    use crate::exchange::mock::MockExchangeApi;
+
+    #[test]
+    async fn test_unknown_symbols_cached() {
+        let mock = Arc::new(MockExchangeApi::default());
+        let exchange_api: Arc<dyn ExchangeApi> = mock.clone();
+        let cache = SymbolInfoCache::new(exchange_api);
+
+        assert_eq!(cache.get("BTCUSDT").await.unwrap().symbol, "BTCUSDT");
+        assert!(cache.get("BTCUSDT").await.is_ok());
+        assert_eq!(mock.symbol_info_requests(), 1);
+
+        // unknown symbols aren't requested again until the entry expires
+        for _ in 0..3 {
+            let res = cache.get("UNKNOWNUSDT").await;
+            assert!(matches!(res, Err(ApiError::SymbolNotFound(_))));
+        }
+        assert_eq!(mock.symbol_info_requests(), 2);
+    }
+}
@@ -7,9 +7,10 @@ use serde_json::Value;
 use crate::{
     exchange::types::ApiResult,
     market::market::MarketDataSymbol,
+    storage::schema::DataRow,
     utils::{
         number::{generate_random_id, parse_f64_from_lookup},
-        time::{generate_ts, timestamp_to_string},
+        time::{floor_mili_ts, generate_ts, timestamp_to_string},
     },
 };
 
@@ -82,6 +83,15 @@ impl TickerData {
         self.tickers.insert(ticker.time, ticker);
     }
 
+    /// Returns the tickers received before a timestamp, without removing them.
+
+    pub fn tickers_before(&self, before_ts: u64) -> Vec<Ticker> {
+        self.tickers
+            .range(..before_ts)
+            .map(|(_, ticker)| ticker.clone())
+            .collect()
+    }
+
     pub fn drain_tickers(&mut self, before_ts: u64) -> Vec<Ticker> {
         // info!(
         //     "Removing all tickers before {} ...",
@@ -101,6 +111,108 @@ impl TickerData {
     }
 }
 
+/// How ticker frames are sampled before they are persisted.
+///
+/// Exchanges push several ticker frames a second, sampling keeps a single record of the price of
+/// each bucket of the sampling interval rather than every frame.
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TickerSampling {
+    /// Tickers are only held in memory.
+    #[default]
+    Disabled,
+    /// Tickers are persisted as one sample per bucket of the interval, in milliseconds.
+    Interval(u64),
+}
+
+impl TickerSampling {
+    /// Parses the `TICKER_SAMPLE_SECS` setting, empty or invalid values disable ticker
+    /// persistence.
+
+    pub fn from_setting(setting: &str) -> Self {
+        match setting.parse::<u64>() {
+            Ok(secs) if secs > 0 => TickerSampling::Interval(secs * 1000),
+            _ => TickerSampling::Disabled,
+        }
+    }
+
+    /// Sampling interval in milliseconds, `None` if tickers aren't persisted.
+
+    pub fn interval(&self) -> Option<u64> {
+        match self {
+            TickerSampling::Disabled => None,
+            TickerSampling::Interval(interval) => Some(*interval),
+        }
+    }
+}
+
+/// Price of a symbol over a sampling bucket, summarizing the ticker frames received within it.
+///
+/// # Attributes
+/// - `time`: Start of the sampling bucket.
+/// - `open`, `high`, `low`, `close`: Last prices of the first, highest, lowest and last frames.
+/// - `traded_vol`: Traded volume of the last frame.
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TickerSample {
+    pub time: u64,
+    pub symbol: String,
+    pub open: f64,
+    pub high: f64,
+    pub low: f64,
+    pub close: f64,
+    pub traded_vol: f64,
+}
+
+impl DataRow for TickerSample {
+    type Key = u64;
+
+    fn key(&self) -> u64 {
+        self.time
+    }
+}
+
+impl TickerSample {
+    /// Samples ticker frames to one record per bucket of an interval.
+    ///
+    /// # Parameters
+    /// - `tickers`: Ticker frames of a single symbol, ordered by time.
+    /// - `interval`: Length of a sampling bucket in milliseconds.
+    ///
+    /// # Returns
+    ///
+    /// A sample for every bucket with at least one frame, ordered by time.
+
+    pub fn from_tickers(tickers: &[Ticker], interval: u64) -> Vec<Self> {
+        let mut samples: Vec<TickerSample> = vec![];
+
+        for ticker in tickers {
+            let time = floor_mili_ts(ticker.time, interval);
+            let price = ticker.last_price;
+
+            match samples.last_mut() {
+                Some(sample) if sample.time == time => {
+                    sample.high = sample.high.max(price);
+                    sample.low = sample.low.min(price);
+                    sample.close = price;
+                    sample.traded_vol = ticker.traded_vol;
+                }
+                _ => samples.push(TickerSample {
+                    time,
+                    symbol: ticker.symbol.clone(),
+                    open: price,
+                    high: price,
+                    low: price,
+                    close: price,
+                    traded_vol: ticker.traded_vol,
+                }),
+            }
+        }
+
+        samples
+    }
+}
+
 /// Represents the current state of a market ticker, including price information and volume.
 ///
 /// # Attributes
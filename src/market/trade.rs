@@ -1,6 +1,7 @@
-use std::collections::{BTreeMap, HashMap};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::Read;
 
-use log::info;
+use log::warn;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use uuid::Uuid;
@@ -8,8 +9,9 @@ use uuid::Uuid;
 use crate::{
     account::trade::OrderSide,
     exchange::types::ApiResult,
+    storage::schema::DataRow,
     utils::{
-        number::parse_f64_from_lookup,
+        number::{from_decimal, parse_f64_from_lookup, to_decimal},
         time::{floor_mili_ts, generate_ts, timestamp_to_string, SEC_AS_MILI},
     },
 };
@@ -68,6 +70,16 @@ impl TradeData {
         self.trades.values().cloned().collect()
     }
 
+    /// Trades made before a timestamp, which `drain_trades` would remove.
+
+    pub fn trades_before(&self, before_ts: u64) -> Vec<Trade> {
+        self.trades
+            .values()
+            .filter(|trade| trade.timestamp < before_ts)
+            .cloned()
+            .collect()
+    }
+
     pub fn drain_trades(&mut self, before_ts: u64) -> Vec<Trade> {
         // info!(
         //     "Removing all trades before {} ...",
@@ -93,11 +105,70 @@ pub struct Trade {
     pub qty: f64,
     pub price: f64,
     pub order_side: OrderSide,
+    /// Aggregate trade ID of the exchange, trades of feeds without IDs have none.
+    #[serde(default)]
+    pub id: Option<u64>,
+}
+
+/// Tells apart the trades of the same millisecond, by their exchange ID if they have one.
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum TradeKey {
+    Id(u64),
+    /// Trades without an ID are told apart by their price, quantity and side.
+    Fill(u64, u64, OrderSide),
+}
+
+/// Layout of trades in bincode files written before schema version 3, without the trade ID.
+#[derive(Deserialize)]
+struct BincodeTradeV2 {
+    symbol: String,
+    timestamp: u64,
+    qty: f64,
+    price: f64,
+    order_side: OrderSide,
+}
+
+impl DataRow for Trade {
+    type Key = (u64, TradeKey);
+
+    fn key(&self) -> Self::Key {
+        let trade_key = match self.id {
+            Some(id) => TradeKey::Id(id),
+            None => TradeKey::Fill(self.price.to_bits(), self.qty.to_bits(), self.order_side),
+        };
+
+        (self.timestamp, trade_key)
+    }
+
+    fn read_bincode_row<R: Read>(reader: R, version: u32) -> bincode::Result<Self> {
+        if version >= 3 {
+            return bincode::deserialize_from(reader);
+        }
+
+        let trade: BincodeTradeV2 = bincode::deserialize_from(reader)?;
+        Ok(Trade {
+            symbol: trade.symbol,
+            timestamp: trade.timestamp,
+            qty: trade.qty,
+            price: trade.price,
+            order_side: trade.order_side,
+            id: None,
+        })
+    }
 }
 
 impl Trade {
+    /// Floors the price of the trade to a multiple of `to`, the price is returned as is if `to`
+    /// isn't positive.
+
     pub fn floor_price(&self, to: f64) -> f64 {
-        (self.price / to).floor() * 10.0
+        if to <= 0.0 {
+            return self.price;
+        }
+
+        let to = to_decimal(to);
+        from_decimal((to_decimal(self.price) / to).floor() * to)
     }
     pub fn from_binance_lookup(lookup: HashMap<String, Value>) -> ApiResult<Self> {
         // {
@@ -175,10 +246,233 @@ impl Trade {
             qty,
             price,
             order_side,
+            id: Some(id),
         })
     }
 }
 
+impl Trade {
+    /// Parses a trade of a BingX `trades` response.
+    ///
+    /// BingX tells the aggressor side by `isBuyerMaker`, if it's missing the side is inferred by
+    /// the tick rule against the previous trade.
+    ///
+    /// # Arguments
+    ///
+    /// * `lookup` - The trade of the response.
+    /// * `symbol` - The symbol the trades were requested for, it isn't included in the response.
+    /// * `previous` - The trade before this one, if any.
+    ///
+    /// # Returns
+    ///
+    /// The parsed trade, or a parsing error.
+
+    pub fn from_bingx_lookup(
+        lookup: HashMap<String, Value>,
+        symbol: &str,
+        previous: Option<&Trade>,
+    ) -> ApiResult<Self> {
+        // {
+        //     "time": 1672025549368,
+        //     "isBuyerMaker": true,
+        //     "price": "16885.0",
+        //     "qty": "3.3002",
+        //     "quoteQty": "55723.87"
+        // }
+
+        let timestamp = lookup
+            .get("time")
+            .and_then(|time| time.as_u64())
+            .ok_or_else(|| "Missing 'time' key from data trade lookup".to_string())?;
+
+        let qty = parse_f64_from_lookup("qty", &lookup)?;
+        let price = parse_f64_from_lookup("price", &lookup)?;
+
+        // the ID is sent as a number or a string, some feeds leave it out
+        let id = lookup.get("id").and_then(|id| match id {
+            Value::String(id) => id.parse().ok(),
+            id => id.as_u64(),
+        });
+
+        let order_side = match lookup.get("isBuyerMaker").and_then(|m| m.as_bool()) {
+            Some(true) => OrderSide::Sell,
+            Some(false) => OrderSide::Buy,
+            None => tick_rule_side(price, previous),
+        };
+
+        Ok(Self {
+            symbol: symbol.to_string(),
+            timestamp,
+            qty,
+            price,
+            order_side,
+            id,
+        })
+    }
+
+    /// Parses the trades of a BingX `trades` response which weren't received yet.
+    ///
+    /// A trade is new if it's no older than the last trade received and isn't one of the trades
+    /// received at that millisecond, told apart by their ID or their price and quantity. A
+    /// response which doesn't reach back to the last trade received is logged, trades between
+    /// the polls were missed.
+    ///
+    /// # Arguments
+    ///
+    /// * `symbol` - The symbol the trades were requested for.
+    /// * `value` - The `data` of the response, an array of recent trades.
+    /// * `cursor` - The trades already received, advanced past the new trades.
+    ///
+    /// # Returns
+    ///
+    /// The new trades in timestamp order, or a parsing error.
+
+    pub fn from_bingx_value(
+        symbol: &str,
+        value: &Value,
+        cursor: &mut BingXTradeCursor,
+    ) -> ApiResult<Vec<Self>> {
+        let mut lookups: Vec<HashMap<String, Value>> = serde_json::from_value(value.clone())?;
+        // the newest trade is listed first, trades of the same millisecond keep their order
+        lookups.reverse();
+        lookups.sort_by_key(|lookup| lookup.get("time").and_then(|time| time.as_u64()));
+
+        let mut trades: Vec<Trade> = vec![];
+        let mut reaches_last_trade = false;
+        for lookup in lookups {
+            let previous = trades.last().or(cursor.last_trade.as_ref());
+            let trade = Trade::from_bingx_lookup(lookup, symbol, previous)?;
+
+            let last_ts = cursor.last_trade.as_ref().map(|last| last.timestamp);
+            if last_ts.is_some_and(|last_ts| trade.timestamp <= last_ts) {
+                reaches_last_trade = true;
+            }
+
+            let is_new = last_ts.is_none_or(|last_ts| trade.timestamp >= last_ts)
+                && !cursor.last_keys.contains(&bingx_trade_key(&trade));
+
+            if is_new {
+                trades.push(trade);
+            }
+        }
+
+        if cursor.last_trade.is_some() && !reaches_last_trade && !trades.is_empty() {
+            warn!("Trades of {symbol} may have been missed between polls of the BingX feed");
+        }
+
+        for trade in &trades {
+            cursor.advance(trade);
+        }
+
+        Ok(trades)
+    }
+}
+
+/// Trades of a polled BingX `trades` feed already received, the feed returns the latest trades
+/// on each poll so they overlap.
+
+#[derive(Debug, Default, Clone)]
+pub struct BingXTradeCursor {
+    /// The newest trade received.
+    last_trade: Option<Trade>,
+    /// Keys of the trades received at the millisecond of the newest trade.
+    last_keys: HashSet<(Option<u64>, u64, u64)>,
+}
+
+impl BingXTradeCursor {
+    /// Creates a cursor past a trade already received.
+
+    pub fn new(last_trade: Trade) -> Self {
+        let mut cursor = Self::default();
+        cursor.advance(&last_trade);
+        cursor
+    }
+
+    /// Moves the cursor past a newer trade.
+    fn advance(&mut self, trade: &Trade) {
+        if self
+            .last_trade
+            .as_ref()
+            .is_none_or(|last| trade.timestamp > last.timestamp)
+        {
+            self.last_keys.clear();
+        }
+
+        self.last_keys.insert(bingx_trade_key(trade));
+        self.last_trade = Some(trade.clone());
+    }
+}
+
+/// Tells apart the BingX trades of a millisecond. The side is left out, it's inferred for trades
+/// without one and may differ between polls.
+fn bingx_trade_key(trade: &Trade) -> (Option<u64>, u64, u64) {
+    (trade.id, trade.price.to_bits(), trade.qty.to_bits())
+}
+
+/// Infers the aggressor side of a trade by the tick rule, for feeds which don't include it.
+///
+/// A trade above the previous price is a buy and below it a sell, a trade at the previous price
+/// keeps the side of the previous trade.
+///
+/// # Arguments
+///
+/// * `price` - The price of the trade.
+/// * `previous` - The trade before it, the first trade of a feed is taken as a buy.
+///
+/// # Returns
+///
+/// The inferred side of the trade.
+
+pub fn tick_rule_side(price: f64, previous: Option<&Trade>) -> OrderSide {
+    match previous {
+        Some(previous) if price > previous.price => OrderSide::Buy,
+        Some(previous) if price < previous.price => OrderSide::Sell,
+        Some(previous) => previous.order_side,
+        None => OrderSide::Buy,
+    }
+}
+
+/// Aggregate trade fetched from an exchange, its id is used to page through trade history.
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AggTrade {
+    pub id: u64,
+    pub trade: Trade,
+}
+
+impl AggTrade {
+    /// Parses the aggregate trades of a Binance `aggTrades` response.
+    ///
+    /// # Arguments
+    ///
+    /// * `symbol` - The symbol the trades were requested for, it isn't included in the response.
+    /// * `value` - The response body, an array of aggregate trades.
+    ///
+    /// # Returns
+    ///
+    /// The aggregate trades in the order of the response, or a parsing error.
+
+    pub fn from_binance_value(symbol: &str, value: &Value) -> ApiResult<Vec<Self>> {
+        let lookups: Vec<HashMap<String, Value>> = serde_json::from_value(value.clone())?;
+
+        lookups
+            .into_iter()
+            .map(|mut lookup| {
+                let id = lookup
+                    .get("a")
+                    .and_then(|id| id.as_u64())
+                    .ok_or_else(|| "Missing 'a' key from aggregate trade lookup".to_string())?;
+                lookup.insert("s".to_string(), Value::String(symbol.to_string()));
+
+                Ok(Self {
+                    id,
+                    trade: Trade::from_binance_lookup(lookup)?,
+                })
+            })
+            .collect()
+    }
+}
+
 impl Default for Trade {
     fn default() -> Self {
         Self {
@@ -187,6 +481,132 @@ impl Default for Trade {
             qty: 42.2,
             price: 42.2,
             order_side: OrderSide::Buy,
+            id: None,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serde_json::json;
+    use tokio::test;
+
+    #[test]
+    async fn test_bingx_trades_infer_side_by_tick_rule() {
+        // the feed leaves out the aggressor side and lists the newest trade first
+        let value = json!([
+            { "time": 1_700_000_003_000u64, "price": "100.5", "qty": "1.0" },
+            { "time": 1_700_000_002_000u64, "price": "100.5", "qty": "1.0" },
+            { "time": 1_700_000_001_000u64, "price": "101.0", "qty": "2.0" },
+            { "time": 1_700_000_000_000u64, "price": "100.0", "qty": "1.0" },
+        ]);
+        let previous = Trade {
+            symbol: "BTCUSDT".to_string(),
+            timestamp: 1_699_999_999_000,
+            qty: 1.0,
+            price: 99.0,
+            order_side: OrderSide::Sell,
+            id: None,
+        };
+
+        let mut cursor = BingXTradeCursor::new(previous.clone());
+        let trades = Trade::from_bingx_value("BTCUSDT", &value, &mut cursor).unwrap();
+        let sides: Vec<OrderSide> = trades.iter().map(|trade| trade.order_side).collect();
+
+        // rising then falling prices, an unchanged price keeps the last side
+        assert_eq!(
+            sides,
+            vec![
+                OrderSide::Buy,
+                OrderSide::Buy,
+                OrderSide::Sell,
+                OrderSide::Sell
+            ]
+        );
+        assert_eq!(trades[1].qty, 2.0);
+
+        // trades already received are skipped
+        let mut cursor = BingXTradeCursor::new(trades[1].clone());
+        let trades = Trade::from_bingx_value("BTCUSDT", &value, &mut cursor).unwrap();
+        assert_eq!(trades.len(), 2);
+        assert_eq!(trades[0].order_side, OrderSide::Sell);
+
+        // an explicit side is used as is
+        let value = json!([
+            { "time": 1_700_000_000_000u64, "isBuyerMaker": true, "price": "101.0", "qty": "1.0" },
+        ]);
+        let mut cursor = BingXTradeCursor::new(previous);
+        let trades = Trade::from_bingx_value("BTCUSDT", &value, &mut cursor).unwrap();
+        assert_eq!(trades[0].order_side, OrderSide::Sell);
+    }
+
+    #[test]
+    async fn test_bingx_trades_deduped_across_polls() {
+        let first_poll = json!([
+            { "time": 1_700_000_001_000u64, "id": "12", "price": "101.0", "qty": "1.0" },
+            { "time": 1_700_000_001_000u64, "id": "11", "price": "101.0", "qty": "1.0" },
+            { "time": 1_700_000_000_000u64, "id": "10", "price": "100.0", "qty": "1.0" },
+        ]);
+        let mut cursor = BingXTradeCursor::default();
+        let trades = Trade::from_bingx_value("BTCUSDT", &first_poll, &mut cursor).unwrap();
+        assert_eq!(trades.len(), 3);
+        assert_eq!(trades[2].id, Some(12));
+
+        // a trade of the same millisecond as the last poll isn't dropped, nor are the
+        // overlapping trades sent again
+        let second_poll = json!([
+            { "time": 1_700_000_002_000u64, "id": 14, "price": "102.0", "qty": "1.0" },
+            { "time": 1_700_000_001_000u64, "id": 13, "price": "101.0", "qty": "1.0" },
+            { "time": 1_700_000_001_000u64, "id": 12, "price": "101.0", "qty": "1.0" },
+            { "time": 1_700_000_001_000u64, "id": 11, "price": "101.0", "qty": "1.0" },
+        ]);
+        let trades = Trade::from_bingx_value("BTCUSDT", &second_poll, &mut cursor).unwrap();
+        let ids: Vec<Option<u64>> = trades.iter().map(|trade| trade.id).collect();
+        assert_eq!(ids, vec![Some(13), Some(14)]);
+
+        // polling again without new trades sends nothing
+        let trades = Trade::from_bingx_value("BTCUSDT", &second_poll, &mut cursor).unwrap();
+        assert!(trades.is_empty());
+    }
+
+    #[test]
+    async fn test_trades_keyed_by_exchange_id() {
+        let trade = Trade {
+            symbol: "BTCUSDT".to_string(),
+            timestamp: 1_700_000_000_000,
+            qty: 1.0,
+            price: 100.0,
+            order_side: OrderSide::Buy,
+            id: Some(1),
+        };
+        let next = Trade {
+            id: Some(2),
+            ..trade.clone()
+        };
+
+        // identical fills of the same millisecond are told apart by their ID
+        assert_ne!(trade.key(), next.key());
+        assert_eq!(
+            trade.key(),
+            Trade {
+                qty: 2.0,
+                ..trade.clone()
+            }
+            .key()
+        );
+
+        // trades of bincode files written before the ID was added are read without one
+        let row = bincode::serialize(&(
+            "BTCUSDT",
+            1_700_000_000_000u64,
+            1.0f64,
+            100.0f64,
+            OrderSide::Buy,
+        ))
+        .unwrap();
+        let read = Trade::read_bincode_row(row.as_slice(), 2).unwrap();
+        assert_eq!(read, Trade { id: None, ..trade });
+    }
+}
@@ -0,0 +1,60 @@
+use async_trait::async_trait;
+use log::info;
+use serde::{Deserialize, Serialize};
+
+use crate::utils::time::generate_ts;
+
+/// A message sent to the user about something which happened in the bot, ie. an alert firing.
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Notification {
+    pub title: String,
+    pub message: String,
+    pub timestamp: u64,
+}
+
+impl Notification {
+    /// Creates a new notification timestamped with the current time.
+    ///
+    /// # Arguments
+    ///
+    /// * `title` - Short title of the notification.
+    /// * `message` - Body of the notification.
+    ///
+    /// # Returns
+    ///
+    /// A new `Notification`.
+
+    pub fn new(title: &str, message: &str) -> Self {
+        Self {
+            title: title.to_string(),
+            message: message.to_string(),
+            timestamp: generate_ts(),
+        }
+    }
+}
+
+/// Delivers notifications to the user.
+
+#[async_trait]
+pub trait Notifier: Send + Sync {
+    /// Sends a notification, failures to deliver are handled by the notifier.
+    ///
+    /// # Arguments
+    ///
+    /// * `notification` - The notification to send.
+
+    async fn notify(&self, notification: Notification);
+}
+
+/// Notifier which writes notifications to the application log.
+
+#[derive(Default)]
+pub struct LogNotifier {}
+
+#[async_trait]
+impl Notifier for LogNotifier {
+    async fn notify(&self, notification: Notification) {
+        info!("{}: {}", notification.title, notification.message);
+    }
+}
@@ -0,0 +1,62 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+use reqwest::Client;
+use serde_json::Value;
+
+/// Time a webhook may take to respond before the post fails.
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Posts JSON payloads to webhook URLs, ie. to forward signals to external systems.
+
+#[async_trait]
+pub trait WebhookClient: Send + Sync {
+    /// Posts a payload to a webhook.
+    ///
+    /// # Arguments
+    ///
+    /// * `url` - URL of the webhook.
+    /// * `payload` - JSON body of the post.
+    ///
+    /// # Returns
+    ///
+    /// `Ok` if the webhook accepted the payload, otherwise a description of the failure.
+
+    async fn post(&self, url: &str, payload: &Value) -> Result<(), String>;
+}
+
+/// Webhook client posting over HTTP, responses without a success status are failures.
+
+pub struct HttpWebhookClient {
+    client: Client,
+}
+
+impl Default for HttpWebhookClient {
+    fn default() -> Self {
+        Self {
+            client: Client::builder()
+                .timeout(WEBHOOK_TIMEOUT)
+                .build()
+                .unwrap_or_default(),
+        }
+    }
+}
+
+#[async_trait]
+impl WebhookClient for HttpWebhookClient {
+    async fn post(&self, url: &str, payload: &Value) -> Result<(), String> {
+        let response = self
+            .client
+            .post(url)
+            .json(payload)
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+
+        if !response.status().is_success() {
+            return Err(format!("Webhook responded with {}", response.status()));
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,175 @@
+use std::sync::Arc;
+
+use log::{error, info};
+
+use crate::storage::{
+    fs::FsStorage, influx::InfluxStorage, manager::StorageManager, memory::MemoryStorage,
+    mongo::MongoDbStorage, schema::DataFormat,
+};
+
+/// Storage backends selectable with the `STORAGE_TYPE` setting.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StorageBackend {
+    Fs,
+    Mongo,
+    Influx,
+    /// Nothing is persisted, all data is lost when the bot stops.
+    Memory,
+}
+
+impl StorageBackend {
+    /// Parses the `STORAGE_TYPE` setting, unknown values use the file system.
+
+    pub fn from_setting(setting: &str) -> Self {
+        match setting {
+            "MONGO" => StorageBackend::Mongo,
+            "INFLUX" => StorageBackend::Influx,
+            "MEMORY" => StorageBackend::Memory,
+            _ => StorageBackend::Fs,
+        }
+    }
+}
+
+/// What to do when the configured storage backend can't be reached at startup, selected with the
+/// `STORAGE_FALLBACK` setting.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StorageFallback {
+    /// Fall back to file system storage so the bot can still serve market data.
+    Fs,
+    /// Fail startup.
+    None,
+}
+
+impl StorageFallback {
+    /// Parses the `STORAGE_FALLBACK` setting, anything other than `NONE` falls back to the file
+    /// system.
+
+    pub fn from_setting(setting: &str) -> Self {
+        match setting {
+            "NONE" => StorageFallback::None,
+            _ => StorageFallback::Fs,
+        }
+    }
+}
+
+/// Connection settings for the storage backends.
+
+pub struct StorageConfig<'a> {
+    pub backend: StorageBackend,
+    pub fallback: StorageFallback,
+    pub mongo_uri: &'a str,
+    pub influx_uri: &'a str,
+    pub influx_token: &'a str,
+    /// Format kline and trade files are written in by file system storage.
+    pub fs_format: DataFormat,
+}
+
+/// Builds the configured storage manager, falling back to file system storage if the backend
+/// can't be reached and the fallback is enabled.
+///
+/// # Arguments
+///
+/// * `config` - The storage settings.
+///
+/// # Returns
+///
+/// The backend in use along with its storage manager, or an error message if the backend can't
+/// be reached and the fallback is disabled.
+
+pub async fn build_storage_manager(
+    config: StorageConfig<'_>,
+) -> Result<(StorageBackend, Arc<dyn StorageManager>), String> {
+    let manager: Result<Arc<dyn StorageManager>, String> = match config.backend {
+        StorageBackend::Mongo => {
+            info!("Using MongoDbStorage as storage backend");
+            match MongoDbStorage::new(config.mongo_uri).await {
+                Ok(manager) => Ok(Arc::new(manager)),
+                Err(e) => Err(format!("Unable to connect to MongoDB: {e}")),
+            }
+        }
+        StorageBackend::Influx => {
+            info!("Using InfluxStorage as storage backend");
+            match InfluxStorage::new(config.influx_uri, config.influx_token).await {
+                Ok(manager) => Ok(Arc::new(manager)),
+                Err(e) => Err(format!("Unable to connect to InfluxDB: {e}")),
+            }
+        }
+        StorageBackend::Fs => {
+            info!("Using FsStorage as storage backend");
+            Ok(build_fs_storage(config.fs_format))
+        }
+        StorageBackend::Memory => {
+            info!("Using MemoryStorage as storage backend, no data will be persisted");
+            Ok(Arc::new(MemoryStorage::default()))
+        }
+    };
+
+    match manager {
+        Ok(manager) => Ok((config.backend, manager)),
+        Err(e) => match config.fallback {
+            StorageFallback::Fs => {
+                error!(
+                    "{e}, falling back to FsStorage, data will not be saved to {:?} storage",
+                    config.backend
+                );
+                Ok((StorageBackend::Fs, build_fs_storage(config.fs_format)))
+            }
+            StorageFallback::None => Err(e),
+        },
+    }
+}
+
+/// Builds file system storage writing data files in a format, migrating data files written with
+/// an older schema.
+fn build_fs_storage(format: DataFormat) -> Arc<dyn StorageManager> {
+    let mut storage = FsStorage::default();
+    storage.set_format(format);
+
+    match storage.migrate_data_files() {
+        Ok(0) => {}
+        Ok(migrated) => info!("Migrated {migrated} data files to the current schema"),
+        Err(e) => error!("Unable to migrate data files, {e}"),
+    }
+
+    Arc::new(storage)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use tokio::test;
+
+    use crate::{
+        exchange::{api::ExchangeApi, mock::MockExchangeApi},
+        testutil::test_bot_with,
+    };
+
+    #[test]
+    async fn test_unreachable_mongo_falls_back_to_fs() {
+        let config = |fallback| StorageConfig {
+            backend: StorageBackend::Mongo,
+            fallback,
+            // nothing listens on port 1, server selection fails quickly
+            mongo_uri: "mongodb://127.0.0.1:1/?serverSelectionTimeoutMS=200",
+            influx_uri: "",
+            influx_token: "",
+            fs_format: DataFormat::default(),
+        };
+
+        let (backend, storage_manager) = build_storage_manager(config(StorageFallback::Fs))
+            .await
+            .unwrap();
+        assert_eq!(backend, StorageBackend::Fs);
+
+        // bot starts on the fallback storage
+        let exchange_api: Arc<dyn ExchangeApi> = Arc::new(MockExchangeApi::default());
+        let bot = test_bot_with(exchange_api.clone(), exchange_api, storage_manager).await;
+        assert!(bot.storage_manager.list_saved_strategies().await.is_ok());
+
+        let result = build_storage_manager(config(StorageFallback::None)).await;
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,123 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+use directories::UserDirs;
+use log::{error, warn};
+use tokio::time;
+
+use crate::strategy::strategy::StrategySummary;
+
+use super::manager::StorageManager;
+
+/// Number of attempts made to save a strategy summary before writing it to the fallback file.
+const SAVE_ATTEMPTS: u32 = 3;
+
+/// Delay before the first retry, doubled on every following retry.
+const SAVE_RETRY_BACKOFF: Duration = Duration::from_millis(100);
+
+/// Returns the local directory records which could not be saved to storage are written to.
+
+pub fn fallback_directory() -> PathBuf {
+    let user_dirs = UserDirs::new().expect("Failed to get user directories");
+    user_dirs.home_dir().join(".raderbot").join("fallback")
+}
+
+/// Saves a strategy summary, retrying with backoff on failure.
+///
+/// If every attempt fails the summary is written as JSON to `fallback_dir` so the record is not
+/// lost, the file can be imported once storage is available again.
+///
+/// # Arguments
+///
+/// * `storage_manager` - Storage the summary is saved to.
+/// * `summary` - The strategy summary to save.
+/// * `fallback_dir` - Directory the summary is written to if saving fails.
+///
+/// # Returns
+///
+/// `Ok(None)` if the summary was saved to storage, `Ok(Some(path))` with the fallback file if
+/// it was written to the fallback directory instead, or an IO error if both failed.
+
+pub async fn save_strategy_summary(
+    storage_manager: Arc<dyn StorageManager>,
+    summary: StrategySummary,
+    fallback_dir: &Path,
+) -> io::Result<Option<PathBuf>> {
+    let strategy_id = summary.info.id;
+    let mut backoff = SAVE_RETRY_BACKOFF;
+
+    for attempt in 1..=SAVE_ATTEMPTS {
+        match storage_manager.save_strategy_summary(summary.clone()).await {
+            Ok(()) => return Ok(None),
+            Err(e) => {
+                warn!("Unable to save summary of strategy {strategy_id}, attempt {attempt} of {SAVE_ATTEMPTS}: {e}");
+            }
+        }
+
+        if attempt < SAVE_ATTEMPTS {
+            time::sleep(backoff).await;
+            backoff *= 2;
+        }
+    }
+
+    let dir = fallback_dir.join("strategies");
+    fs::create_dir_all(&dir)?;
+
+    let filepath = dir.join(format!("{strategy_id}.json"));
+    fs::write(&filepath, serde_json::to_string(&summary)?)?;
+
+    error!(
+        "Failed to save summary of strategy {strategy_id} to storage, written to fallback file {}",
+        filepath.display()
+    );
+
+    Ok(Some(filepath))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use tokio::test;
+    use uuid::Uuid;
+
+    use crate::storage::fs::FsStorage;
+
+    #[test]
+    async fn test_failed_summary_save_writes_fallback() {
+        let test_dir = std::env::temp_dir().join(format!("raderbot-{}", Uuid::new_v4()));
+        let storage_manager: Arc<dyn StorageManager> =
+            Arc::new(FsStorage::new(test_dir.join("data")));
+
+        // a file in place of the strategies directory makes every save fail
+        fs::write(test_dir.join("data").join("strategies"), "").unwrap();
+
+        let mut summary = StrategySummary::default();
+        summary.info.id = Uuid::new_v4();
+        summary.profit = 42.0;
+
+        let fallback_dir = test_dir.join("fallback");
+        let filepath = save_strategy_summary(storage_manager, summary.clone(), &fallback_dir)
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(
+            filepath,
+            fallback_dir
+                .join("strategies")
+                .join(format!("{}.json", summary.info.id))
+        );
+
+        let saved: StrategySummary =
+            serde_json::from_str(&fs::read_to_string(&filepath).unwrap()).unwrap();
+        assert_eq!(saved.info.id, summary.info.id);
+        assert_eq!(saved.profit, 42.0);
+
+        fs::remove_dir_all(test_dir).unwrap();
+    }
+}
@@ -1,9 +1,9 @@
 use async_trait::async_trait;
 use csv::ReaderBuilder;
 use directories::UserDirs;
-use log::info;
+use log::{info, warn};
 use serde::{Deserialize, Serialize};
-use std::collections::{BTreeMap, HashMap};
+use std::collections::BTreeMap;
 use std::error::Error;
 use std::fs;
 use std::fs::{File, OpenOptions};
@@ -11,20 +11,29 @@ use std::io::Read;
 use std::io::Write;
 use std::io::{self};
 use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
 
+use crate::account::account::AccountSnapshot;
+use crate::account::schedule::ScheduledOrder;
+use crate::market::alert::Alert;
 use crate::market::interval::Interval;
 use crate::market::kline::Kline;
+use crate::market::ticker::TickerSample;
 use crate::market::trade::Trade;
 use crate::strategy::strategy::{StrategyId, StrategyInfo, StrategySummary};
 use crate::utils::kline::{
-    build_kline_filename, build_kline_key, generate_kline_filenames_in_range, get_min_max_open_time,
+    build_kline_filename, build_kline_key, build_ticker_filename, build_ticker_key,
+    generate_kline_filenames_in_range, get_min_max_open_time,
 };
 use crate::utils::time::{floor_mili_ts, floor_month_ts, generate_ts, DAY_AS_MILI};
 use crate::utils::trade::{
     build_market_trade_filename, build_market_trade_key, generate_trade_filenames_in_range,
 };
 
-use super::manager::StorageManager;
+use super::manager::{
+    CompactionReport, DataCoverage, KlineCoverage, StorageManager, TradeCoverage,
+};
+use super::schema::{self, DataFormat, DataRow};
 
 /// Represents a file system-based storage manager for managing klines and strategy summaries.
 
@@ -32,6 +41,13 @@ use super::manager::StorageManager;
 pub struct FsStorage {
     app_directory: PathBuf,
     data_directory: PathBuf,
+    /// Format new kline and trade files are written in, files in any format are read.
+    #[serde(default)]
+    format: DataFormat,
+    /// Held while kline files are written, so a compaction doesn't rewrite a month file a save
+    /// is appending to. Clones of the storage share the lock.
+    #[serde(skip)]
+    kline_lock: Arc<Mutex<()>>,
 }
 
 impl FsStorage {
@@ -52,9 +68,21 @@ impl FsStorage {
         Self {
             app_directory,
             data_directory,
+            format: DataFormat::default(),
+            kline_lock: Arc::default(),
         }
     }
 
+    /// Sets the format new kline and trade files are written in.
+    ///
+    /// # Arguments
+    ///
+    /// * `format` - The format of new data files, existing files keep their format.
+
+    pub fn set_format(&mut self, format: DataFormat) {
+        self.format = format;
+    }
+
     /// Loads klines from a specified file.
     ///
     /// # Arguments
@@ -68,52 +96,16 @@ impl FsStorage {
     fn _load_klines(&self, filename: &str) -> Option<Vec<Kline>> {
         let mut market_dir = self.data_directory.join("market");
         market_dir.push("klines");
-        let file_path = market_dir.join(filename);
-
-        if let Ok(file) = fs::File::open(file_path) {
-            let mut reader = ReaderBuilder::new().has_headers(false).from_reader(file);
-
-            let mut klines: Vec<Kline> = Vec::new();
 
-            for result in reader.deserialize() {
-                if let Ok(kline) = result {
-                    klines.push(kline);
-                } else {
-                    // Handle error while deserializing kline
-                    return None;
-                }
-            }
-
-            Some(klines)
-        } else {
-            None
-        }
+        Self::load_data_file(&market_dir.join(filename))
     }
 
     // TODO: docs
     fn _load_trades(&self, filename: &str) -> Option<Vec<Trade>> {
         let mut market_dir = self.data_directory.join("market");
         market_dir.push("trades");
-        let file_path = market_dir.join(filename);
-
-        if let Ok(file) = fs::File::open(file_path) {
-            let mut reader = ReaderBuilder::new().has_headers(false).from_reader(file);
-
-            let mut trades: Vec<Trade> = Vec::new();
-
-            for result in reader.deserialize() {
-                if let Ok(kline) = result {
-                    trades.push(kline);
-                } else {
-                    // Handle error while deserializing kline
-                    return None;
-                }
-            }
 
-            Some(trades)
-        } else {
-            None
-        }
+        Self::load_data_file(&market_dir.join(filename))
     }
 
     // TODO: docs
@@ -162,7 +154,7 @@ impl FsStorage {
     ///
     /// Returns the path to the application directory.
 
-    fn create_app_directory() -> PathBuf {
+    pub fn create_app_directory() -> PathBuf {
         let user_dirs = UserDirs::new().expect("Failed to get user directories");
         let home_dir = user_dirs.home_dir();
         let app_directory = home_dir.join(".raderbot");
@@ -197,6 +189,214 @@ impl FsStorage {
 
         Ok(filepath)
     }
+
+    /// Constructs the file path of the account snapshot history.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` containing the `PathBuf` of the snapshot file if successful, or an error if not.
+
+    fn account_snapshots_filepath(&self) -> Result<PathBuf, Box<dyn Error>> {
+        let data_dir = self.data_directory.join("account");
+        std::fs::create_dir_all(&data_dir)?;
+
+        Ok(data_dir.join("snapshots.csv"))
+    }
+
+    /// Constructs the file path of the saved price alerts.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` containing the `PathBuf` of the alerts file if successful, or an error if not.
+
+    fn alerts_filepath(&self) -> Result<PathBuf, Box<dyn Error>> {
+        let data_dir = self.data_directory.join("alerts");
+        std::fs::create_dir_all(&data_dir)?;
+
+        Ok(data_dir.join("alerts.json"))
+    }
+
+    /// Constructs the file path of the saved scheduled orders.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` containing the `PathBuf` of the scheduled orders file if successful, or an error if not.
+
+    fn scheduled_orders_filepath(&self) -> Result<PathBuf, Box<dyn Error>> {
+        let data_dir = self.data_directory.join("account");
+        std::fs::create_dir_all(&data_dir)?;
+
+        Ok(data_dir.join("scheduled_orders.json"))
+    }
+
+    /// Loads the rows of a data file written in any format, the format of the file is detected
+    /// from its extension and rows of the same file written in several formats are combined.
+    ///
+    /// # Arguments
+    ///
+    /// * `file_path` - Path of the data file, its extension is replaced by each format's extension.
+    ///
+    /// # Returns
+    ///
+    /// Returns the rows ordered by key without duplicates if a file exists in any format;
+    /// otherwise `None`.
+
+    fn load_data_file<T: DataRow>(file_path: &Path) -> Option<Vec<T>> {
+        let mut rows: Option<BTreeMap<T::Key, T>> = None;
+
+        for format in DataFormat::ALL {
+            let file_path = file_path.with_extension(format.extension());
+
+            if !file_path.exists() {
+                continue;
+            }
+
+            // files written before schema versioning are read positionally
+            if let Ok(file_rows) = schema::read_rows::<T>(&file_path) {
+                rows.get_or_insert_with(BTreeMap::new)
+                    .extend(file_rows.into_iter().map(|row| (row.key(), row)));
+            }
+        }
+
+        rows.map(|rows| rows.into_values().collect())
+    }
+
+    /// Locks the kline files against concurrent writes, a lock poisoned by a panicked writer is
+    /// taken over as the files it wrote are replaced atomically.
+    fn lock_klines(&self) -> MutexGuard<'_, ()> {
+        self.kline_lock
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Merges rows into a data file written in any format, rewriting it in the current format.
+    ///
+    /// Rows of the file in every format are combined with the new rows, which replace existing
+    /// rows with the same key, and files in other formats are removed once the merged file is
+    /// written. Nothing is written if an existing file can't be read.
+    ///
+    /// # Arguments
+    ///
+    /// * `file_path` - Path of the data file in the current format.
+    /// * `rows` - The rows to merge into the file.
+
+    fn merge_data_file<T: DataRow>(&self, file_path: &Path, rows: Vec<T>) -> io::Result<()> {
+        let mut merged: BTreeMap<T::Key, T> = BTreeMap::new();
+
+        for format in DataFormat::ALL {
+            let format_path = file_path.with_extension(format.extension());
+            if format_path.exists() {
+                let existing = schema::read_rows::<T>(&format_path)?;
+                merged.extend(existing.into_iter().map(|row| (row.key(), row)));
+            }
+        }
+        merged.extend(rows.into_iter().map(|row| (row.key(), row)));
+
+        let merged: Vec<T> = merged.into_values().collect();
+        schema::write_rows(file_path, &merged)?;
+
+        for format in DataFormat::ALL {
+            let other_path = file_path.with_extension(format.extension());
+            if format != self.format && other_path.exists() {
+                fs::remove_file(other_path)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Groups the data files of a market directory by their kline or trade key.
+    ///
+    /// # Arguments
+    ///
+    /// * `dir` - The market data directory, ie. `market/klines`.
+    /// * `date_len` - Length of the `-YYYY-MM` or `-YYYY-MM-DD` date suffix of the filenames.
+    ///
+    /// # Returns
+    ///
+    /// Returns the file paths of each key ordered by date, empty if the directory doesn't exist.
+
+    fn data_files_by_key(
+        dir: &Path,
+        date_len: usize,
+    ) -> io::Result<BTreeMap<String, Vec<PathBuf>>> {
+        let mut files_by_key: BTreeMap<String, Vec<PathBuf>> = BTreeMap::new();
+
+        if !dir.exists() {
+            return Ok(files_by_key);
+        }
+
+        for entry in fs::read_dir(dir)?.flatten() {
+            let path = entry.path();
+            if !Self::is_data_file(&path) {
+                continue;
+            }
+
+            let stem = match path.file_stem().and_then(|stem| stem.to_str()) {
+                Some(stem) if stem.len() > date_len => stem.to_string(),
+                _ => continue,
+            };
+
+            let key = stem[..stem.len() - date_len].to_string();
+            files_by_key.entry(key).or_default().push(path);
+        }
+
+        // dates in filenames sort lexicographically
+        for files in files_by_key.values_mut() {
+            files.sort();
+        }
+
+        Ok(files_by_key)
+    }
+
+    /// Rewrites the kline, trade and ticker files written with an older schema with the current
+    /// schema, files which can't be migrated are logged and left as they are.
+    ///
+    /// Reading never rewrites files, so this runs as an explicit step when the storage is built.
+    ///
+    /// # Returns
+    ///
+    /// Returns the number of migrated files, or an error if a data directory can't be listed.
+
+    pub fn migrate_data_files(&self) -> io::Result<usize> {
+        let market_dir = self.data_directory.join("market");
+
+        Ok(Self::migrate_dir::<Kline>(&market_dir.join("klines"))?
+            + Self::migrate_dir::<Trade>(&market_dir.join("trades"))?
+            + Self::migrate_dir::<TickerSample>(&market_dir.join("tickers"))?)
+    }
+
+    /// Migrates the data files of a market directory holding rows of `T`.
+    fn migrate_dir<T: DataRow>(dir: &Path) -> io::Result<usize> {
+        if !dir.exists() {
+            return Ok(0);
+        }
+
+        let mut migrated = 0;
+        for entry in fs::read_dir(dir)?.flatten() {
+            let path = entry.path();
+            if !Self::is_data_file(&path) {
+                continue;
+            }
+
+            match schema::migrate_rows::<T>(&path) {
+                Ok(true) => migrated += 1,
+                Ok(false) => {}
+                Err(e) => warn!("Unable to migrate {}, {e}", path.display()),
+            }
+        }
+
+        Ok(migrated)
+    }
+
+    /// Whether a path has the extension of a data format, ie. not a temporary file.
+    fn is_data_file(path: &Path) -> bool {
+        let extension = path.extension().and_then(|extension| extension.to_str());
+
+        DataFormat::ALL
+            .iter()
+            .any(|format| extension == Some(format.extension()))
+    }
 }
 
 impl Default for FsStorage {
@@ -212,6 +412,8 @@ impl Default for FsStorage {
         Self {
             app_directory,
             data_directory,
+            format: DataFormat::default(),
+            kline_lock: Arc::default(),
         }
     }
 }
@@ -224,6 +426,7 @@ impl StorageManager for FsStorage {
     ///
     /// * `klines` - A slice of `Kline` to be saved.
     /// * `kline_key` - A string slice that represents the key associated with the klines.
+    /// * `is_bootstrap` - Merges the klines with the stored klines rather than appending them.
     ///
     /// # Returns
     ///
@@ -240,8 +443,10 @@ impl StorageManager for FsStorage {
         market_dir.push("klines");
         std::fs::create_dir_all(&market_dir)?;
 
-        // sort klines into month buckets
-        let mut klines_by_month: HashMap<u64, Vec<Kline>> = HashMap::new();
+        let _kline_lock = self.lock_klines();
+
+        // sort klines into month buckets, saved in order so a failed save leaves earlier months
+        let mut klines_by_month: BTreeMap<u64, Vec<Kline>> = BTreeMap::new();
         for kline in klines {
             let month_ts = floor_month_ts(kline.open_time);
 
@@ -253,48 +458,18 @@ impl StorageManager for FsStorage {
         }
 
         for (month_ts, klines) in klines_by_month {
-            let mut klines_to_save = BTreeMap::new();
-
             let kline_filename = build_kline_filename(kline_key, month_ts);
-            let file_path = market_dir.join(kline_filename);
-
-            // read existing klines from file if is bootstrap, otherwise
-            // only append to file
-            if file_path.exists() && is_bootstrap {
-                let mut reader = csv::ReaderBuilder::new()
-                    .has_headers(false)
-                    .from_path(&file_path)?;
-
-                // Read existing klines into a vector
-                let existing_klines: Vec<Kline> =
-                    reader.deserialize().collect::<Result<Vec<Kline>, _>>()?;
-
-                // add existing klines to klines to save
-                for kline in existing_klines {
-                    klines_to_save.insert(kline.open_time, kline);
-                }
-            }
-
-            // append any left over klines in month, ensure no duplicates with BTreeMap
-            for kline in klines {
-                klines_to_save.insert(kline.open_time, kline);
-            }
-
-            let file = OpenOptions::new()
-                .append(!is_bootstrap)
-                .write(true)
-                .create(true)
-                .open(&file_path)?;
-
-            let mut writer = csv::WriterBuilder::new()
-                .has_headers(false)
-                .from_writer(file);
-
-            for kline in klines_to_save.values() {
-                writer.serialize(kline)?
+            let file_path = market_dir
+                .join(kline_filename)
+                .with_extension(self.format.extension());
+
+            // only append to file unless is bootstrap, then existing klines of every format are
+            // merged without duplicates
+            if is_bootstrap {
+                self.merge_data_file(&file_path, klines)?;
+            } else {
+                schema::append_rows(&file_path, &klines)?;
             }
-
-            writer.flush()?
         }
 
         Ok(())
@@ -372,6 +547,79 @@ impl StorageManager for FsStorage {
         Ok(())
     }
 
+    /// Appends ticker samples to the daily ticker files of a ticker key.
+    ///
+    /// # Arguments
+    ///
+    /// * `samples` - The ticker samples to save.
+    /// * `ticker_key` - The key associated with the samples, ie. `BTCUSDT@ticker`.
+
+    async fn save_ticker_samples(
+        &self,
+        samples: &[TickerSample],
+        ticker_key: &str,
+    ) -> Result<(), Box<dyn Error>> {
+        let market_dir = self.data_directory.join("market").join("tickers");
+        fs::create_dir_all(&market_dir)?;
+
+        let mut samples_by_day: BTreeMap<u64, Vec<TickerSample>> = BTreeMap::new();
+        for sample in samples {
+            samples_by_day
+                .entry(floor_mili_ts(sample.time, DAY_AS_MILI))
+                .or_default()
+                .push(sample.clone());
+        }
+
+        for (day_ts, samples) in samples_by_day {
+            let file_path = market_dir
+                .join(build_ticker_filename(ticker_key, day_ts))
+                .with_extension(self.format.extension());
+
+            schema::append_rows(&file_path, &samples)?;
+        }
+
+        Ok(())
+    }
+
+    /// Retrieves the ticker samples of a symbol within optional timestamp bounds.
+    ///
+    /// # Arguments
+    ///
+    /// * `symbol` - The symbol of the samples.
+    /// * `from_ts` - Optional start timestamp for filtering.
+    /// * `to_ts` - Optional end timestamp for filtering.
+    ///
+    /// # Returns
+    ///
+    /// Returns the samples ordered by time, or an error if a ticker file can't be read.
+
+    async fn get_ticker_samples(
+        &self,
+        symbol: &str,
+        from_ts: Option<u64>,
+        to_ts: Option<u64>,
+    ) -> Result<Vec<TickerSample>, Box<dyn Error>> {
+        let market_dir = self.data_directory.join("market").join("tickers");
+        let files_by_key = Self::data_files_by_key(&market_dir, 11)?;
+
+        let mut samples = vec![];
+        for file in files_by_key
+            .get(&build_ticker_key(symbol))
+            .into_iter()
+            .flatten()
+        {
+            samples.extend(schema::read_rows::<TickerSample>(file)?);
+        }
+
+        samples.retain(|sample: &TickerSample| {
+            from_ts.is_none_or(|from_ts| sample.time >= from_ts)
+                && to_ts.is_none_or(|to_ts| sample.time <= to_ts)
+        });
+        samples.sort_by_key(|sample| sample.time);
+
+        Ok(samples)
+    }
+
     /// Lists all saved strategy summaries.
     ///
     /// # Returns
@@ -428,6 +676,296 @@ impl StorageManager for FsStorage {
         Ok(data)
     }
 
+    /// Appends an account snapshot to the snapshot history file.
+    ///
+    /// # Arguments
+    ///
+    /// * `snapshot` - The `AccountSnapshot` to be saved.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` indicating the outcome of the operation.
+
+    async fn save_account_snapshot(
+        &self,
+        snapshot: &AccountSnapshot,
+    ) -> Result<(), Box<dyn Error>> {
+        let filepath = self.account_snapshots_filepath()?;
+
+        let file = OpenOptions::new()
+            .append(true)
+            .create(true)
+            .open(&filepath)?;
+
+        let mut writer = csv::WriterBuilder::new()
+            .has_headers(false)
+            .from_writer(file);
+
+        writer.serialize(snapshot)?;
+        writer.flush()?;
+
+        Ok(())
+    }
+
+    /// Retrieves account snapshots within optional timestamp bounds.
+    ///
+    /// # Arguments
+    ///
+    /// * `from_ts` - Optional start timestamp for filtering.
+    /// * `to_ts` - Optional end timestamp for filtering.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` containing the snapshots ordered by timestamp, or an error if not.
+
+    async fn get_account_snapshots(
+        &self,
+        from_ts: Option<u64>,
+        to_ts: Option<u64>,
+    ) -> Result<Vec<AccountSnapshot>, Box<dyn Error>> {
+        let filepath = self.account_snapshots_filepath()?;
+
+        if !filepath.exists() {
+            return Ok(vec![]);
+        }
+
+        let mut reader = ReaderBuilder::new()
+            .has_headers(false)
+            .from_path(&filepath)?;
+
+        let mut snapshots: Vec<AccountSnapshot> = reader
+            .deserialize()
+            .collect::<Result<Vec<AccountSnapshot>, _>>()?;
+
+        snapshots.retain(|snapshot| {
+            from_ts.is_none_or(|from_ts| snapshot.timestamp >= from_ts)
+                && to_ts.is_none_or(|to_ts| snapshot.timestamp <= to_ts)
+        });
+        snapshots.sort_by_key(|snapshot| snapshot.timestamp);
+
+        Ok(snapshots)
+    }
+
+    /// Writes all price alerts to the alerts file, replacing its contents.
+    ///
+    /// # Arguments
+    ///
+    /// * `alerts` - The alerts to be saved.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` indicating the outcome of the operation.
+
+    async fn save_alerts(&self, alerts: &[Alert]) -> Result<(), Box<dyn Error>> {
+        let filepath = self.alerts_filepath()?;
+
+        let json_str = serde_json::to_string(alerts)?;
+        fs::write(filepath, json_str)?;
+
+        Ok(())
+    }
+
+    /// Reads all price alerts from the alerts file.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` containing the saved alerts, empty if none have been saved.
+
+    async fn get_alerts(&self) -> Result<Vec<Alert>, Box<dyn Error>> {
+        let filepath = self.alerts_filepath()?;
+
+        if !filepath.exists() {
+            return Ok(vec![]);
+        }
+
+        let json_str = fs::read_to_string(filepath)?;
+        let alerts: Vec<Alert> = serde_json::from_str(&json_str)?;
+
+        Ok(alerts)
+    }
+
+    /// Writes all pending scheduled orders to the scheduled orders file, replacing its contents.
+    ///
+    /// # Arguments
+    ///
+    /// * `orders` - The scheduled orders to be saved.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` indicating the outcome of the operation.
+
+    async fn save_scheduled_orders(&self, orders: &[ScheduledOrder]) -> Result<(), Box<dyn Error>> {
+        let filepath = self.scheduled_orders_filepath()?;
+
+        let json_str = serde_json::to_string(orders)?;
+        fs::write(filepath, json_str)?;
+
+        Ok(())
+    }
+
+    /// Reads all scheduled orders from the scheduled orders file.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` containing the saved orders, empty if none have been saved.
+
+    async fn get_scheduled_orders(&self) -> Result<Vec<ScheduledOrder>, Box<dyn Error>> {
+        let filepath = self.scheduled_orders_filepath()?;
+
+        if !filepath.exists() {
+            return Ok(vec![]);
+        }
+
+        let json_str = fs::read_to_string(filepath)?;
+        let orders: Vec<ScheduledOrder> = serde_json::from_str(&json_str)?;
+
+        Ok(orders)
+    }
+
+    /// Summarizes stored klines and trades from the month and day data files.
+    ///
+    /// Ranges are read from the first and last file of each series, counts from the rows of all
+    /// files.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` containing the `DataCoverage` of all series, or an error if a file can't be read.
+
+    async fn data_coverage(&self) -> Result<DataCoverage, Box<dyn Error>> {
+        let market_dir = self.data_directory.join("market");
+        let mut coverage = DataCoverage::default();
+
+        // kline files are named {symbol}@kline_{interval}-YYYY-MM.csv, or .bin for bincode
+        for (kline_key, files) in Self::data_files_by_key(&market_dir.join("klines"), 8)? {
+            let (symbol, interval) = match kline_key.split_once("@kline_") {
+                Some((symbol, interval)) => match Interval::try_from(interval) {
+                    Ok(interval) => (symbol.to_string(), interval),
+                    Err(_) => continue,
+                },
+                None => continue,
+            };
+
+            let first: Vec<Kline> = schema::read_rows(&files[0])?;
+            let last: Vec<Kline> = schema::read_rows(&files[files.len() - 1])?;
+            let earliest = first.iter().map(|kline| kline.open_time).min();
+            let latest = last.iter().map(|kline| kline.open_time).max();
+
+            let mut count = 0;
+            for file in &files {
+                count += schema::count_data_rows::<Kline>(file)?;
+            }
+
+            if let (Some(earliest), Some(latest)) = (earliest, latest) {
+                coverage.klines.push(KlineCoverage::new(
+                    &symbol, interval, earliest, latest, count,
+                ));
+            }
+        }
+
+        // trade files are named {symbol}@trade-YYYY-MM-DD.csv, or .bin for bincode
+        for (trade_key, files) in Self::data_files_by_key(&market_dir.join("trades"), 11)? {
+            let symbol = match trade_key.strip_suffix("@trade") {
+                Some(symbol) => symbol.to_string(),
+                None => continue,
+            };
+
+            let first: Vec<Trade> = schema::read_rows(&files[0])?;
+            let last: Vec<Trade> = schema::read_rows(&files[files.len() - 1])?;
+            let earliest = first.iter().map(|trade| trade.timestamp).min();
+            let latest = last.iter().map(|trade| trade.timestamp).max();
+
+            let mut count = 0;
+            for file in &files {
+                count += schema::count_data_rows::<Trade>(file)?;
+            }
+
+            if let (Some(earliest), Some(latest)) = (earliest, latest) {
+                coverage.trades.push(TradeCoverage::new(
+                    &symbol,
+                    earliest,
+                    latest,
+                    count,
+                    files.len(),
+                ));
+            }
+        }
+
+        Ok(coverage)
+    }
+
+    /// Rewrites the month files of a kline series, keeping a single kline per open time.
+    ///
+    /// Klines of all month files, in any format, are merged by open time and written back to
+    /// month files in the configured format, files in another format are removed once merged.
+    /// Saves of klines wait for the compaction, and each month file is replaced atomically.
+    ///
+    /// # Arguments
+    ///
+    /// * `symbol` - The symbol of the kline series.
+    /// * `interval` - The interval of the kline series.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `CompactionReport` with the number of duplicate klines removed.
+
+    async fn compact_klines(
+        &self,
+        symbol: &str,
+        interval: Interval,
+    ) -> Result<CompactionReport, Box<dyn Error>> {
+        let kline_key = build_kline_key(symbol, interval);
+        let klines_dir = self.data_directory.join("market").join("klines");
+
+        let _kline_lock = self.lock_klines();
+        let files = Self::data_files_by_key(&klines_dir, 8)?
+            .remove(&kline_key)
+            .unwrap_or_default();
+
+        let mut rows = 0;
+        let mut klines = BTreeMap::new();
+        for file in &files {
+            for kline in schema::read_rows::<Kline>(file)? {
+                rows += 1;
+                klines.insert(kline.open_time, kline);
+            }
+        }
+        let kline_count = klines.len();
+
+        let mut klines_by_month: BTreeMap<u64, Vec<Kline>> = BTreeMap::new();
+        for kline in klines.into_values() {
+            klines_by_month
+                .entry(floor_month_ts(kline.open_time))
+                .or_default()
+                .push(kline);
+        }
+
+        let mut written = vec![];
+        for (month_ts, klines) in klines_by_month {
+            let file_path = klines_dir
+                .join(build_kline_filename(&kline_key, month_ts))
+                .with_extension(self.format.extension());
+
+            schema::write_rows(&file_path, &klines)?;
+            written.push(file_path);
+        }
+
+        for file in files.iter().filter(|file| !written.contains(file)) {
+            fs::remove_file(file)?;
+        }
+
+        info!(
+            "Compacted {kline_key}, removed {} duplicate klines",
+            rows - kline_count
+        );
+
+        Ok(CompactionReport {
+            symbol: symbol.to_string(),
+            interval,
+            klines: kline_count,
+            duplicates_removed: rows - kline_count,
+        })
+    }
+
     // TODO: Docs
     async fn get_trades(
         &self,
@@ -477,7 +1015,7 @@ impl StorageManager for FsStorage {
         market_dir.push("trades");
         std::fs::create_dir_all(&market_dir)?;
 
-        let mut trades_by_day: HashMap<u64, Vec<Trade>> = HashMap::new();
+        let mut trades_by_day: BTreeMap<u64, Vec<Trade>> = BTreeMap::new();
 
         for trade in trades {
             let ts = floor_mili_ts(trade.timestamp, DAY_AS_MILI);
@@ -489,49 +1027,470 @@ impl StorageManager for FsStorage {
         }
 
         for (trade_ts, trades) in trades_by_day {
-            let mut trades_to_save = BTreeMap::new();
-
             // Build file path
             let trade_filename = build_market_trade_filename(trade_key, trade_ts);
 
-            let file_path = market_dir.join(trade_filename);
+            let file_path = market_dir
+                .join(trade_filename)
+                .with_extension(self.format.extension());
+
+            // only append to file unless is bootstrap, then existing trades of every format are
+            // merged without duplicates
+            if is_bootstrap {
+                self.merge_data_file(&file_path, trades)?;
+            } else {
+                schema::append_rows(&file_path, &trades)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::account::trade::OrderSide;
+    use crate::testutil::make_klines;
+    use tokio::test;
+    use uuid::Uuid;
+
+    #[test]
+    async fn test_account_snapshots_in_range() {
+        let storage = FsStorage::new(format!("test-{}", Uuid::new_v4()));
+        let start = 1_700_000_000_000;
+
+        for i in 0..5 {
+            let snapshot = AccountSnapshot {
+                timestamp: start + i * 60_000,
+                balance: 1000.0 + i as f64,
+                equity: 1000.0 + i as f64,
+                open_positions: i as usize,
+                unrealized_pnl: 0.0,
+            };
+            storage.save_account_snapshot(&snapshot).await.unwrap();
+        }
+
+        let all = storage.get_account_snapshots(None, None).await.unwrap();
+        assert_eq!(all.len(), 5);
+
+        let snapshots = storage
+            .get_account_snapshots(Some(start + 60_000), Some(start + 180_000))
+            .await
+            .unwrap();
+
+        let timestamps: Vec<u64> = snapshots.iter().map(|s| s.timestamp).collect();
+        assert_eq!(
+            timestamps,
+            vec![start + 60_000, start + 120_000, start + 180_000]
+        );
+        assert_eq!(snapshots[0].balance, 1001.0);
+
+        fs::remove_dir_all(&storage.data_directory).unwrap();
+    }
+
+    #[test]
+    async fn test_data_coverage() {
+        let storage = FsStorage::new(format!("test-{}", Uuid::new_v4()));
+        let start = 1_699_999_200_000;
+        let hour = Interval::Hour1.to_mili();
+        let minute = Interval::Min1.to_mili();
+
+        let build_kline = |symbol: &str, interval: Interval, open_time: u64| Kline {
+            symbol: symbol.to_string(),
+            interval,
+            open_time,
+            close_time: open_time + interval.to_mili() - 1,
+            ..Default::default()
+        };
+
+        // BTCUSDT is missing its third kline, ETHUSDT is complete
+        let btc_klines: Vec<Kline> = [0, 1, 3, 4]
+            .iter()
+            .map(|i| build_kline("BTCUSDT", Interval::Hour1, start + hour * i))
+            .collect();
+        let eth_klines: Vec<Kline> = (0..3)
+            .map(|i| build_kline("ETHUSDT", Interval::Min1, start + minute * i))
+            .collect();
+
+        storage
+            .save_klines(
+                &btc_klines,
+                &build_kline_key("BTCUSDT", Interval::Hour1),
+                true,
+            )
+            .await
+            .unwrap();
+        storage
+            .save_klines(
+                &eth_klines,
+                &build_kline_key("ETHUSDT", Interval::Min1),
+                true,
+            )
+            .await
+            .unwrap();
+
+        // trades on the first and third day only
+        let trades: Vec<Trade> = [0, 1, DAY_AS_MILI * 2]
+            .iter()
+            .map(|offset| Trade {
+                symbol: "BTCUSDT".to_string(),
+                timestamp: start + offset,
+                qty: 1.0,
+                price: 100.0,
+                order_side: OrderSide::Buy,
+                id: None,
+            })
+            .collect();
+        storage
+            .save_trades(&trades, &build_market_trade_key("BTCUSDT"), true)
+            .await
+            .unwrap();
+
+        let coverage = storage.data_coverage().await.unwrap();
+
+        assert_eq!(
+            coverage.klines,
+            vec![
+                KlineCoverage {
+                    symbol: "BTCUSDT".to_string(),
+                    interval: Interval::Hour1,
+                    earliest_open_time: start,
+                    latest_open_time: start + hour * 4,
+                    count: 4,
+                    estimated_gaps: 1,
+                },
+                KlineCoverage {
+                    symbol: "ETHUSDT".to_string(),
+                    interval: Interval::Min1,
+                    earliest_open_time: start,
+                    latest_open_time: start + minute * 2,
+                    count: 3,
+                    estimated_gaps: 0,
+                },
+            ]
+        );
+        assert_eq!(
+            coverage.trades,
+            vec![TradeCoverage {
+                symbol: "BTCUSDT".to_string(),
+                earliest_timestamp: start,
+                latest_timestamp: start + DAY_AS_MILI * 2,
+                count: 3,
+                estimated_gaps: 1,
+            }]
+        );
+
+        fs::remove_dir_all(&storage.data_directory).unwrap();
+    }
 
-            if file_path.exists() {
-                let mut reader = csv::ReaderBuilder::new()
-                    .has_headers(false)
-                    .from_path(&file_path)?;
+    #[test]
+    async fn test_compact_klines_removes_duplicates() {
+        let storage = FsStorage::new(format!("test-{}", Uuid::new_v4()));
+        let kline_key = build_kline_key("BTCUSDT", Interval::Hour1);
+        let klines_dir = storage.data_directory.join("market").join("klines");
+
+        // two months of klines, the first month appended twice
+        let first_month = 1_698_796_800_000;
+        let second_month = 1_701_388_800_000;
+        let first_klines = make_klines("BTCUSDT", Interval::Hour1, first_month, &[1.0, 2.0, 3.0]);
+        let second_klines = make_klines("BTCUSDT", Interval::Hour1, second_month, &[4.0, 5.0]);
+
+        for klines in [&first_klines, &first_klines[1..].to_vec(), &second_klines] {
+            storage
+                .save_klines(klines, &kline_key, false)
+                .await
+                .unwrap();
+        }
 
-                // Read existing klines into a vector
-                let existing_trades: Vec<Trade> =
-                    reader.deserialize().collect::<Result<Vec<Trade>, _>>()?;
+        let report = storage
+            .compact_klines("BTCUSDT", Interval::Hour1)
+            .await
+            .unwrap();
+        assert_eq!(report.klines, 5);
+        assert_eq!(report.duplicates_removed, 2);
+
+        let first_path = klines_dir
+            .join(build_kline_filename(&kline_key, first_month))
+            .with_extension(storage.format.extension());
+        let rows: Vec<Kline> = schema::read_rows(&first_path).unwrap();
+        assert_eq!(rows, first_klines);
+
+        // compacting again finds nothing to remove
+        let report = storage
+            .compact_klines("BTCUSDT", Interval::Hour1)
+            .await
+            .unwrap();
+        assert_eq!(report.duplicates_removed, 0);
+
+        fs::remove_dir_all(&storage.data_directory).unwrap();
+    }
 
-                for trade in existing_trades {
-                    trades_to_save.insert(trade.timestamp, trade);
+    #[test]
+    async fn test_compact_klines_keeps_concurrent_saves() {
+        let storage = FsStorage::new(format!("test-{}", Uuid::new_v4()));
+        let kline_key = build_kline_key("BTCUSDT", Interval::Min1);
+        let closes: Vec<f64> = (0..200).map(|i| i as f64).collect();
+        let klines = make_klines("BTCUSDT", Interval::Min1, 1_698_796_800_000, &closes);
+
+        // klines are appended one at a time on another thread while the series is compacted
+        let saver = {
+            let storage = storage.clone();
+            let klines = klines.clone();
+            let kline_key = kline_key.clone();
+            std::thread::spawn(move || {
+                for kline in klines {
+                    futures::executor::block_on(storage.save_klines(&[kline], &kline_key, false))
+                        .unwrap();
                 }
-            }
+            })
+        };
 
-            let file = OpenOptions::new()
-                .append(!is_bootstrap)
-                .write(true)
-                .create(true)
-                .open(&file_path)?;
+        while !saver.is_finished() {
+            storage
+                .compact_klines("BTCUSDT", Interval::Min1)
+                .await
+                .unwrap();
+        }
+        saver.join().unwrap();
 
-            let mut writer = csv::WriterBuilder::new()
-                .has_headers(false)
-                .from_writer(file);
+        let report = storage
+            .compact_klines("BTCUSDT", Interval::Min1)
+            .await
+            .unwrap();
+        assert_eq!(report.klines, klines.len());
 
-            // append any left over trades in day, ensure no duplicates with BTreeMap
-            for trade in trades {
-                trades_to_save.insert(trade.timestamp, trade);
-            }
+        fs::remove_dir_all(&storage.data_directory).unwrap();
+    }
 
-            for trade in trades_to_save.values() {
-                writer.serialize(trade)?
-            }
+    #[test]
+    async fn test_read_headerless_and_versioned_kline_files() {
+        let storage = FsStorage::new(format!("test-{}", Uuid::new_v4()));
+        let kline_key = build_kline_key("BTCUSDT", Interval::Hour1);
+        let market_dir = storage.data_directory.join("market").join("klines");
+        fs::create_dir_all(&market_dir).unwrap();
+
+        // first month in the old headerless format, second month in the versioned format
+        let old_month = 1_698_796_800_000;
+        let new_month = 1_701_388_800_000;
+        let build_kline = |open_time: u64, close: f64| Kline {
+            symbol: "BTCUSDT".to_string(),
+            interval: Interval::Hour1,
+            close,
+            open_time,
+            close_time: open_time + Interval::Hour1.to_mili() - 1,
+            ..Default::default()
+        };
+        let old_klines = vec![
+            build_kline(old_month, 100.0),
+            build_kline(old_month + 3_600_000, 101.0),
+        ];
+        let new_klines = vec![build_kline(new_month, 102.0)];
+
+        let old_path = market_dir.join(build_kline_filename(&kline_key, old_month));
+        let mut writer = csv::WriterBuilder::new()
+            .has_headers(false)
+            .from_path(&old_path)
+            .unwrap();
+        for kline in &old_klines {
+            writer.serialize(kline).unwrap();
+        }
+        writer.flush().unwrap();
+        assert_eq!(schema::schema_version(&old_path).unwrap(), Some(0));
+
+        storage
+            .save_klines(&new_klines, &kline_key, true)
+            .await
+            .unwrap();
+        let new_path = market_dir.join(build_kline_filename(&kline_key, new_month));
+        assert_eq!(
+            schema::schema_version(&new_path).unwrap(),
+            Some(schema::DATA_SCHEMA_VERSION)
+        );
+
+        let klines = storage
+            .get_klines(
+                "BTCUSDT",
+                Interval::Hour1,
+                Some(old_month),
+                Some(new_month + 1),
+            )
+            .await;
+        assert_eq!(klines, [old_klines.clone(), new_klines].concat());
+
+        // reading leaves the old file as it is, it's migrated by the explicit step
+        assert_eq!(schema::schema_version(&old_path).unwrap(), Some(0));
+        assert_eq!(storage.migrate_data_files().unwrap(), 1);
+        assert_eq!(storage.migrate_data_files().unwrap(), 0);
+
+        // appending keeps the versioned format
+        assert_eq!(
+            schema::schema_version(&old_path).unwrap(),
+            Some(schema::DATA_SCHEMA_VERSION)
+        );
+        let appended = build_kline(old_month + 7_200_000, 103.0);
+        storage
+            .save_klines(&[appended.clone()], &kline_key, false)
+            .await
+            .unwrap();
+        let rows: Vec<Kline> = schema::read_rows(&old_path).unwrap();
+        assert_eq!(rows, [old_klines, vec![appended]].concat());
+        assert_eq!(schema::count_data_rows::<Kline>(&old_path).unwrap(), 3);
+
+        fs::remove_dir_all(&storage.data_directory).unwrap();
+    }
 
-            writer.flush()?
+    #[test]
+    async fn test_bincode_files_smaller_and_round_trip_as_csv() {
+        let mut csv_storage = FsStorage::new(format!("test-{}", Uuid::new_v4()));
+        csv_storage.set_format(DataFormat::Csv);
+        let mut bincode_storage = FsStorage::new(format!("test-{}", Uuid::new_v4()));
+        bincode_storage.set_format(DataFormat::Bincode);
+
+        let start = 1_700_006_400_000;
+        let trade_key = build_market_trade_key("BTCUSDT");
+        let trades: Vec<Trade> = (0..1000)
+            .map(|i| Trade {
+                symbol: "BTCUSDT".to_string(),
+                timestamp: start + i * 37,
+                qty: 0.001 * (i % 17) as f64 + 0.0005,
+                price: 37_000.0 + (i % 101) as f64 * 0.1,
+                order_side: if i % 2 == 0 {
+                    OrderSide::Buy
+                } else {
+                    OrderSide::Sell
+                },
+                id: Some(3_000_000_000 + i),
+            })
+            .collect();
+
+        // written in two batches, bincode files are appended to like csv files
+        for storage in [&csv_storage, &bincode_storage] {
+            storage
+                .save_trades(&trades[..500], &trade_key, false)
+                .await
+                .unwrap();
+            storage
+                .save_trades(&trades[500..], &trade_key, false)
+                .await
+                .unwrap();
         }
 
-        Ok(())
+        let file_path = |storage: &FsStorage| {
+            storage
+                .data_directory
+                .join("market")
+                .join("trades")
+                .join(build_market_trade_filename(&trade_key, start))
+                .with_extension(storage.format.extension())
+        };
+        let csv_size = fs::metadata(file_path(&csv_storage)).unwrap().len();
+        let bincode_size = fs::metadata(file_path(&bincode_storage)).unwrap().len();
+        assert!(bincode_size < csv_size, "{bincode_size} >= {csv_size}");
+
+        let to_ts = start + DAY_AS_MILI - 1;
+        let csv_trades = csv_storage
+            .get_trades("BTCUSDT", Some(start), Some(to_ts))
+            .await;
+        let bincode_trades = bincode_storage
+            .get_trades("BTCUSDT", Some(start), Some(to_ts))
+            .await;
+        assert_eq!(csv_trades, trades);
+        assert_eq!(bincode_trades, trades);
+        assert_eq!(
+            schema::count_data_rows::<Trade>(&file_path(&bincode_storage)).unwrap(),
+            1000
+        );
+
+        // files keep being read after switching format
+        bincode_storage.set_format(DataFormat::Csv);
+        let extra = Trade {
+            timestamp: start + 50_000,
+            ..trades[0].clone()
+        };
+        bincode_storage
+            .save_trades(&[extra.clone()], &trade_key, false)
+            .await
+            .unwrap();
+        let mixed_trades = bincode_storage
+            .get_trades("BTCUSDT", Some(start), Some(to_ts))
+            .await;
+        assert_eq!(mixed_trades.len(), 1001);
+        assert!(mixed_trades.contains(&extra));
+
+        // bootstrapping merges the files of both formats into the current one without duplicates
+        bincode_storage
+            .save_trades(&trades[..10], &trade_key, true)
+            .await
+            .unwrap();
+        let merged_trades = bincode_storage
+            .get_trades("BTCUSDT", Some(start), Some(to_ts))
+            .await;
+        assert_eq!(merged_trades, [trades.clone(), vec![extra]].concat());
+        assert!(!file_path(&bincode_storage)
+            .with_extension(DataFormat::Bincode.extension())
+            .exists());
+
+        fs::remove_dir_all(&csv_storage.data_directory).unwrap();
+        fs::remove_dir_all(&bincode_storage.data_directory).unwrap();
+    }
+
+    #[test]
+    async fn test_bincode_klines_without_quote_volume_migrated() {
+        let mut storage = FsStorage::new(format!("test-{}", Uuid::new_v4()));
+        storage.set_format(DataFormat::Bincode);
+        let kline_key = build_kline_key("BTCUSDT", Interval::Min1);
+        let market_dir = storage.data_directory.join("market").join("klines");
+        fs::create_dir_all(&market_dir).unwrap();
+
+        let start = 1_701_388_800_000;
+        let klines: Vec<Kline> = make_klines("BTCUSDT", Interval::Min1, start, &[100.0, 101.0]);
+
+        // version 1 rows are the kline fields without the quote volume
+        let path = market_dir
+            .join(build_kline_filename(&kline_key, start))
+            .with_extension(DataFormat::Bincode.extension());
+        let mut bytes = 1u32.to_le_bytes().to_vec();
+        for k in &klines {
+            let row = (
+                &k.symbol,
+                k.interval,
+                k.open,
+                k.high,
+                k.low,
+                k.close,
+                k.volume,
+                k.open_time,
+                k.close_time,
+            );
+            bytes.extend(bincode::serialize(&row).unwrap());
+        }
+        fs::write(&path, bytes).unwrap();
+
+        let to_ts = start + 2 * Interval::Min1.to_mili();
+        let read = storage
+            .get_klines("BTCUSDT", Interval::Min1, Some(start), Some(to_ts))
+            .await;
+        assert_eq!(read, klines);
+        assert!(read.iter().all(|kline| kline.quote_volume == 0.0));
+        assert_eq!(storage.migrate_data_files().unwrap(), 1);
+        assert_eq!(
+            schema::schema_version(&path).unwrap(),
+            Some(schema::DATA_SCHEMA_VERSION)
+        );
+
+        // appended klines keep their quote volume
+        let appended = Kline {
+            quote_volume: 250.0,
+            ..make_klines("BTCUSDT", Interval::Min1, start + 2 * 60_000, &[102.0]).remove(0)
+        };
+        storage
+            .save_klines(&[appended.clone()], &kline_key, false)
+            .await
+            .unwrap();
+        let rows: Vec<Kline> = schema::read_rows(&path).unwrap();
+        assert_eq!(rows, [klines, vec![appended]].concat());
+
+        fs::remove_dir_all(&storage.data_directory).unwrap();
     }
 }
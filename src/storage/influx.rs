@@ -7,8 +7,12 @@ use std::io;
 use std::{any, error::Error};
 use uuid::Uuid;
 
-use super::manager::StorageManager;
+use super::manager::{CompactionReport, DataCoverage, StorageManager};
+use crate::account::account::AccountSnapshot;
+use crate::account::schedule::ScheduledOrder;
+use crate::market::alert::Alert;
 use crate::market::interval::Interval;
+use crate::market::ticker::TickerSample;
 use crate::{
     account::trade::OrderSide,
     market::{kline::Kline, trade::Trade},
@@ -60,8 +64,14 @@ impl InfluxStorage {
             .iter()
             .map(|kline| {
                 format!(
-                    "symbol={} open={},high={},low={},close={},volume={}",
-                    kline.symbol, kline.open, kline.high, kline.low, kline.close, kline.volume,
+                    "symbol={} open={},high={},low={},close={},volume={},quote_volume={}",
+                    kline.symbol,
+                    kline.open,
+                    kline.high,
+                    kline.low,
+                    kline.close,
+                    kline.volume,
+                    kline.quote_volume,
                 )
             })
             .collect::<Vec<_>>()
@@ -117,6 +127,22 @@ impl StorageManager for InfluxStorage {
         unimplemented!()
     }
 
+    async fn save_ticker_samples(
+        &self,
+        _samples: &[TickerSample],
+        _ticker_key: &str,
+    ) -> Result<(), Box<dyn Error>> {
+        Err("Ticker samples are not supported by InfluxStorage".into())
+    }
+    async fn get_ticker_samples(
+        &self,
+        _symbol: &str,
+        _from_ts: Option<u64>,
+        _to_ts: Option<u64>,
+    ) -> Result<Vec<TickerSample>, Box<dyn Error>> {
+        Err("Ticker samples are not supported by InfluxStorage".into())
+    }
+
     async fn list_saved_strategies(&self) -> Result<Vec<StrategyInfo>, Box<dyn Error>> {
         unimplemented!()
     }
@@ -131,4 +157,42 @@ impl StorageManager for InfluxStorage {
         // TODO: Implement get strategy summary on DBStorageManager
         unimplemented!()
     }
+    async fn save_account_snapshot(
+        &self,
+        _snapshot: &AccountSnapshot,
+    ) -> Result<(), Box<dyn Error>> {
+        Err("Account snapshots are not supported by InfluxStorage".into())
+    }
+    async fn get_account_snapshots(
+        &self,
+        _from_ts: Option<u64>,
+        _to_ts: Option<u64>,
+    ) -> Result<Vec<AccountSnapshot>, Box<dyn Error>> {
+        Err("Account snapshots are not supported by InfluxStorage".into())
+    }
+    async fn save_alerts(&self, _alerts: &[Alert]) -> Result<(), Box<dyn Error>> {
+        Err("Alerts are not supported by InfluxStorage".into())
+    }
+    async fn get_alerts(&self) -> Result<Vec<Alert>, Box<dyn Error>> {
+        Err("Alerts are not supported by InfluxStorage".into())
+    }
+    async fn save_scheduled_orders(
+        &self,
+        _orders: &[ScheduledOrder],
+    ) -> Result<(), Box<dyn Error>> {
+        Err("Scheduled orders are not supported by InfluxStorage".into())
+    }
+    async fn get_scheduled_orders(&self) -> Result<Vec<ScheduledOrder>, Box<dyn Error>> {
+        Err("Scheduled orders are not supported by InfluxStorage".into())
+    }
+    async fn data_coverage(&self) -> Result<DataCoverage, Box<dyn Error>> {
+        Err("Data coverage is not supported by InfluxStorage".into())
+    }
+    async fn compact_klines(
+        &self,
+        _symbol: &str,
+        _interval: Interval,
+    ) -> Result<CompactionReport, Box<dyn Error>> {
+        Err("Kline compaction is not supported by InfluxStorage".into())
+    }
 }
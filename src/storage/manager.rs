@@ -1,15 +1,115 @@
 use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
 use std::error::Error;
 use std::io::{self};
 
+use crate::account::account::AccountSnapshot;
+use crate::account::schedule::ScheduledOrder;
+use crate::market::alert::Alert;
 use crate::market::interval::Interval;
+use crate::market::ticker::TickerSample;
 use crate::market::trade::Trade;
 use crate::strategy::strategy::StrategyInfo;
+use crate::utils::time::{floor_mili_ts, DAY_AS_MILI};
 use crate::{
     market::kline::Kline,
     strategy::strategy::{StrategyId, StrategySummary},
 };
 
+/// Range of stored klines of a symbol and interval.
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct KlineCoverage {
+    pub symbol: String,
+    pub interval: Interval,
+    pub earliest_open_time: u64,
+    pub latest_open_time: u64,
+    pub count: usize,
+    /// Number of klines missing between the earliest and latest open time.
+    pub estimated_gaps: usize,
+}
+
+impl KlineCoverage {
+    /// Creates the coverage of a kline series, estimating gaps from the expected kline count.
+
+    pub fn new(
+        symbol: &str,
+        interval: Interval,
+        earliest_open_time: u64,
+        latest_open_time: u64,
+        count: usize,
+    ) -> Self {
+        let expected =
+            (latest_open_time.saturating_sub(earliest_open_time) / interval.to_mili()) as usize + 1;
+
+        Self {
+            symbol: symbol.to_string(),
+            interval,
+            earliest_open_time,
+            latest_open_time,
+            count,
+            estimated_gaps: expected.saturating_sub(count),
+        }
+    }
+}
+
+/// Range of stored market trades of a symbol.
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TradeCoverage {
+    pub symbol: String,
+    pub earliest_timestamp: u64,
+    pub latest_timestamp: u64,
+    pub count: usize,
+    /// Number of days without any trades between the earliest and latest trade.
+    pub estimated_gaps: usize,
+}
+
+impl TradeCoverage {
+    /// Creates the coverage of a trade series, estimating gaps from the days holding trades.
+
+    pub fn new(
+        symbol: &str,
+        earliest_timestamp: u64,
+        latest_timestamp: u64,
+        count: usize,
+        days_with_trades: usize,
+    ) -> Self {
+        let expected_days = ((floor_mili_ts(latest_timestamp, DAY_AS_MILI)
+            - floor_mili_ts(earliest_timestamp, DAY_AS_MILI))
+            / DAY_AS_MILI) as usize
+            + 1;
+
+        Self {
+            symbol: symbol.to_string(),
+            earliest_timestamp,
+            latest_timestamp,
+            count,
+            estimated_gaps: expected_days.saturating_sub(days_with_trades),
+        }
+    }
+}
+
+/// Summary of all market data held by a storage backend.
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct DataCoverage {
+    pub klines: Vec<KlineCoverage>,
+    pub trades: Vec<TradeCoverage>,
+}
+
+/// Outcome of compacting the stored klines of a symbol and interval.
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CompactionReport {
+    pub symbol: String,
+    pub interval: Interval,
+    /// Number of klines stored after compaction.
+    pub klines: usize,
+    /// Number of klines removed as they duplicated the open time of another kline.
+    pub duplicates_removed: usize,
+}
+
 /// Defines operations for managing storage of trading data and strategy summaries.
 ///
 /// Includes methods for saving and retrieving kline data, listing saved strategies,
@@ -19,7 +119,9 @@ use crate::{
 pub trait StorageManager: Send + Sync {
     /// Saves kline data to storage.
     ///
-    /// Takes an array of `Kline` objects and a key for identification. Returns an `io::Result<()>` indicating success or failure.
+    /// Takes an array of `Kline` objects and a key for identification. Bootstrap saves replace
+    /// stored klines with the same open time, so they can be retried. Returns an `io::Result<()>`
+    /// indicating success or failure.
     async fn save_klines(
         &self,
         klines: &[Kline],
@@ -53,6 +155,25 @@ pub trait StorageManager: Send + Sync {
         is_bootstrap: bool,
     ) -> io::Result<()>;
 
+    /// Saves ticker samples under a ticker key.
+    ///
+    /// Appends the `TickerSample`s to the stored samples, returning success or error.
+    async fn save_ticker_samples(
+        &self,
+        samples: &[TickerSample],
+        ticker_key: &str,
+    ) -> Result<(), Box<dyn Error>>;
+
+    /// Retrieves the ticker samples of a symbol within optional timestamp bounds.
+    ///
+    /// Returns the samples ordered by time, or an error if retrieval fails.
+    async fn get_ticker_samples(
+        &self,
+        symbol: &str,
+        from_ts: Option<u64>,
+        to_ts: Option<u64>,
+    ) -> Result<Vec<TickerSample>, Box<dyn Error>>;
+
     /// Lists saved strategy information.
     ///
     /// Returns a list of `StrategyInfo` detailing saved strategies or an error if retrieval fails.
@@ -70,4 +191,56 @@ pub trait StorageManager: Send + Sync {
         &self,
         strategy_id: StrategyId,
     ) -> Result<StrategySummary, Box<dyn Error>>;
+
+    /// Saves a snapshot of the account balance.
+    ///
+    /// Appends the `AccountSnapshot` to the account history, returning success or error.
+    async fn save_account_snapshot(&self, snapshot: &AccountSnapshot)
+        -> Result<(), Box<dyn Error>>;
+
+    /// Retrieves account snapshots within optional timestamp bounds.
+    ///
+    /// Returns the snapshots ordered by timestamp, or an error if retrieval fails.
+    async fn get_account_snapshots(
+        &self,
+        from_ts: Option<u64>,
+        to_ts: Option<u64>,
+    ) -> Result<Vec<AccountSnapshot>, Box<dyn Error>>;
+
+    /// Saves all price alerts, replacing previously saved alerts.
+    ///
+    /// Persists the `Alert` registry so it survives restarts, returning success or error.
+    async fn save_alerts(&self, alerts: &[Alert]) -> Result<(), Box<dyn Error>>;
+
+    /// Retrieves all saved price alerts.
+    ///
+    /// Returns the saved alerts, or an error if retrieval fails.
+    async fn get_alerts(&self) -> Result<Vec<Alert>, Box<dyn Error>>;
+
+    /// Saves all pending scheduled orders, replacing previously saved orders.
+    ///
+    /// Persists the `ScheduledOrder` registry so it survives restarts, returning success or error.
+    async fn save_scheduled_orders(&self, orders: &[ScheduledOrder]) -> Result<(), Box<dyn Error>>;
+
+    /// Retrieves all saved scheduled orders.
+    ///
+    /// Returns the saved orders, or an error if retrieval fails.
+    async fn get_scheduled_orders(&self) -> Result<Vec<ScheduledOrder>, Box<dyn Error>>;
+
+    /// Summarizes the stored kline and trade data.
+    ///
+    /// Returns the range, count and estimated gaps of every stored series, or an error if the
+    /// storage could not be read.
+    async fn data_coverage(&self) -> Result<DataCoverage, Box<dyn Error>>;
+
+    /// Rewrites the stored klines of a symbol and interval ordered by open time, keeping a single
+    /// kline per open time.
+    ///
+    /// Returns a `CompactionReport` with the number of duplicates removed, or an error if the
+    /// klines could not be rewritten.
+    async fn compact_klines(
+        &self,
+        symbol: &str,
+        interval: Interval,
+    ) -> Result<CompactionReport, Box<dyn Error>>;
 }
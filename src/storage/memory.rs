@@ -0,0 +1,497 @@
+use async_trait::async_trait;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::error::Error;
+use std::io;
+use std::sync::RwLock;
+
+use crate::account::account::AccountSnapshot;
+use crate::account::schedule::ScheduledOrder;
+use crate::market::alert::Alert;
+use crate::market::interval::Interval;
+use crate::market::kline::Kline;
+use crate::market::ticker::TickerSample;
+use crate::market::trade::{Trade, TradeKey};
+use crate::strategy::strategy::{StrategyId, StrategyInfo, StrategySummary};
+use crate::utils::kline::{build_kline_key, build_ticker_key};
+use crate::utils::time::{floor_mili_ts, DAY_AS_MILI};
+use crate::utils::trade::build_market_trade_key;
+
+use super::manager::{
+    CompactionReport, DataCoverage, KlineCoverage, StorageManager, TradeCoverage,
+};
+use super::schema::DataRow;
+
+/// Storage manager which holds all data in memory, nothing is written to disk.
+///
+/// Used for unit tests and ephemeral runs. Klines are keyed by open time and trades by timestamp
+/// and `TradeKey`, as on the disk backends, so trades of the same millisecond are all kept and
+/// saving a kline or trade which is already stored replaces it, as a bootstrap save does.
+
+#[derive(Default)]
+pub struct MemoryStorage {
+    klines: RwLock<HashMap<String, BTreeMap<u64, Kline>>>,
+    trades: RwLock<HashMap<String, BTreeMap<(u64, TradeKey), Trade>>>,
+    ticker_samples: RwLock<HashMap<String, BTreeMap<u64, TickerSample>>>,
+    strategy_summaries: RwLock<HashMap<StrategyId, StrategySummary>>,
+    account_snapshots: RwLock<Vec<AccountSnapshot>>,
+    alerts: RwLock<Vec<Alert>>,
+    scheduled_orders: RwLock<Vec<ScheduledOrder>>,
+}
+
+#[async_trait]
+impl StorageManager for MemoryStorage {
+    /// Saves klines under a kline key.
+    ///
+    /// # Arguments
+    ///
+    /// * `klines` - A slice of `Kline` to be saved.
+    /// * `kline_key` - The key associated with the klines.
+    /// * `_is_bootstrap` - Unused, existing klines with the same open time are always replaced.
+    ///
+    /// # Returns
+    ///
+    /// Returns an `io::Result<()>`, saving to memory doesn't fail.
+
+    async fn save_klines(
+        &self,
+        klines: &[Kline],
+        kline_key: &str,
+        _is_bootstrap: bool,
+    ) -> io::Result<()> {
+        let mut stored = self.klines.write().unwrap();
+        let series = stored.entry(kline_key.to_string()).or_default();
+
+        for kline in klines {
+            series.insert(kline.open_time, kline.clone());
+        }
+
+        Ok(())
+    }
+
+    /// Retrieves klines opened from `from_ts` and closed by `to_ts`, ordered by open time.
+    ///
+    /// # Arguments
+    ///
+    /// * `symbol` - The symbol associated with the klines.
+    /// * `interval` - The interval of the klines.
+    /// * `from_ts` - Optional start timestamp for filtering.
+    /// * `to_ts` - Optional end timestamp for filtering.
+    ///
+    /// # Returns
+    ///
+    /// Returns a vector of `Kline` that match the criteria.
+
+    async fn get_klines(
+        &self,
+        symbol: &str,
+        interval: Interval,
+        from_ts: Option<u64>,
+        to_ts: Option<u64>,
+    ) -> Vec<Kline> {
+        let stored = self.klines.read().unwrap();
+
+        match stored.get(&build_kline_key(symbol, interval)) {
+            Some(series) => series
+                .range(from_ts.unwrap_or(0)..)
+                .map(|(_, kline)| kline)
+                .filter(|kline| to_ts.is_none_or(|to_ts| kline.close_time <= to_ts))
+                .cloned()
+                .collect(),
+            None => vec![],
+        }
+    }
+
+    /// Retrieves trades within optional timestamp bounds, ordered by timestamp.
+    ///
+    /// # Arguments
+    ///
+    /// * `symbol` - The symbol associated with the trades.
+    /// * `from_ts` - Optional start timestamp for filtering.
+    /// * `to_ts` - Optional end timestamp for filtering.
+    ///
+    /// # Returns
+    ///
+    /// Returns a vector of `Trade` that match the criteria.
+
+    async fn get_trades(
+        &self,
+        symbol: &str,
+        from_ts: Option<u64>,
+        to_ts: Option<u64>,
+    ) -> Vec<Trade> {
+        let stored = self.trades.read().unwrap();
+
+        let to_ts = to_ts.unwrap_or(u64::MAX);
+
+        // `TradeKey::Id(0)` is the lowest key of a millisecond
+        match stored.get(&build_market_trade_key(symbol)) {
+            Some(series) => series
+                .range((from_ts.unwrap_or(0), TradeKey::Id(0))..)
+                .take_while(|((timestamp, _), _)| *timestamp <= to_ts)
+                .map(|(_, trade)| trade.clone())
+                .collect(),
+            None => vec![],
+        }
+    }
+
+    /// Saves trades under a trade key.
+    ///
+    /// # Arguments
+    ///
+    /// * `trades` - A slice of `Trade` to be saved.
+    /// * `trade_key` - The key associated with the trades.
+    /// * `_is_bootstrap` - Unused, existing trades with the same key are always replaced.
+    ///
+    /// # Returns
+    ///
+    /// Returns an `io::Result<()>`, saving to memory doesn't fail.
+
+    async fn save_trades(
+        &self,
+        trades: &[Trade],
+        trade_key: &str,
+        _is_bootstrap: bool,
+    ) -> io::Result<()> {
+        let mut stored = self.trades.write().unwrap();
+        let series = stored.entry(trade_key.to_string()).or_default();
+
+        for trade in trades {
+            series.insert(trade.key(), trade.clone());
+        }
+
+        Ok(())
+    }
+
+    /// Saves ticker samples under a ticker key, replacing any sample of the same bucket.
+
+    async fn save_ticker_samples(
+        &self,
+        samples: &[TickerSample],
+        ticker_key: &str,
+    ) -> Result<(), Box<dyn Error>> {
+        let mut stored = self.ticker_samples.write().unwrap();
+        let series = stored.entry(ticker_key.to_string()).or_default();
+
+        for sample in samples {
+            series.insert(sample.time, sample.clone());
+        }
+
+        Ok(())
+    }
+
+    /// Retrieves the ticker samples of a symbol within optional timestamp bounds.
+
+    async fn get_ticker_samples(
+        &self,
+        symbol: &str,
+        from_ts: Option<u64>,
+        to_ts: Option<u64>,
+    ) -> Result<Vec<TickerSample>, Box<dyn Error>> {
+        let stored = self.ticker_samples.read().unwrap();
+
+        Ok(match stored.get(&build_ticker_key(symbol)) {
+            Some(series) => series
+                .range(from_ts.unwrap_or(0)..=to_ts.unwrap_or(u64::MAX))
+                .map(|(_, sample)| sample.clone())
+                .collect(),
+            None => vec![],
+        })
+    }
+
+    /// Lists the info of all saved strategy summaries.
+
+    async fn list_saved_strategies(&self) -> Result<Vec<StrategyInfo>, Box<dyn Error>> {
+        let summaries = self.strategy_summaries.read().unwrap();
+
+        Ok(summaries
+            .values()
+            .map(|summary| summary.info.clone())
+            .collect())
+    }
+
+    /// Saves a strategy summary, replacing any summary saved for the same strategy.
+
+    async fn save_strategy_summary(&self, summary: StrategySummary) -> Result<(), Box<dyn Error>> {
+        self.strategy_summaries
+            .write()
+            .unwrap()
+            .insert(summary.info.id, summary);
+
+        Ok(())
+    }
+
+    /// Retrieves a strategy summary, an error is returned if none is saved for the strategy.
+
+    async fn get_strategy_summary(
+        &self,
+        strategy_id: StrategyId,
+    ) -> Result<StrategySummary, Box<dyn Error>> {
+        self.strategy_summaries
+            .read()
+            .unwrap()
+            .get(&strategy_id)
+            .cloned()
+            .ok_or_else(|| format!("Strategy summary {strategy_id} not found").into())
+    }
+
+    /// Appends an account snapshot to the snapshot history.
+
+    async fn save_account_snapshot(
+        &self,
+        snapshot: &AccountSnapshot,
+    ) -> Result<(), Box<dyn Error>> {
+        self.account_snapshots
+            .write()
+            .unwrap()
+            .push(snapshot.clone());
+
+        Ok(())
+    }
+
+    /// Retrieves account snapshots within optional timestamp bounds, ordered by timestamp.
+
+    async fn get_account_snapshots(
+        &self,
+        from_ts: Option<u64>,
+        to_ts: Option<u64>,
+    ) -> Result<Vec<AccountSnapshot>, Box<dyn Error>> {
+        let mut snapshots: Vec<AccountSnapshot> = self
+            .account_snapshots
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|snapshot| {
+                from_ts.is_none_or(|from_ts| snapshot.timestamp >= from_ts)
+                    && to_ts.is_none_or(|to_ts| snapshot.timestamp <= to_ts)
+            })
+            .cloned()
+            .collect();
+        snapshots.sort_by_key(|snapshot| snapshot.timestamp);
+
+        Ok(snapshots)
+    }
+
+    /// Saves all price alerts, replacing previously saved alerts.
+
+    async fn save_alerts(&self, alerts: &[Alert]) -> Result<(), Box<dyn Error>> {
+        *self.alerts.write().unwrap() = alerts.to_vec();
+
+        Ok(())
+    }
+
+    /// Retrieves all saved price alerts.
+
+    async fn get_alerts(&self) -> Result<Vec<Alert>, Box<dyn Error>> {
+        Ok(self.alerts.read().unwrap().clone())
+    }
+
+    /// Saves all pending scheduled orders, replacing previously saved orders.
+
+    async fn save_scheduled_orders(&self, orders: &[ScheduledOrder]) -> Result<(), Box<dyn Error>> {
+        *self.scheduled_orders.write().unwrap() = orders.to_vec();
+
+        Ok(())
+    }
+
+    /// Retrieves all saved scheduled orders.
+
+    async fn get_scheduled_orders(&self) -> Result<Vec<ScheduledOrder>, Box<dyn Error>> {
+        Ok(self.scheduled_orders.read().unwrap().clone())
+    }
+
+    /// Summarizes stored klines and trades, series are ordered by key as on the disk backends.
+
+    async fn data_coverage(&self) -> Result<DataCoverage, Box<dyn Error>> {
+        let mut coverage = DataCoverage::default();
+
+        let klines = self.klines.read().unwrap();
+        let kline_series: BTreeMap<&String, &BTreeMap<u64, Kline>> = klines.iter().collect();
+        for series in kline_series.values() {
+            if let (Some((earliest, kline)), Some((latest, _))) =
+                (series.first_key_value(), series.last_key_value())
+            {
+                coverage.klines.push(KlineCoverage::new(
+                    &kline.symbol,
+                    kline.interval,
+                    *earliest,
+                    *latest,
+                    series.len(),
+                ));
+            }
+        }
+
+        let trades = self.trades.read().unwrap();
+        let trade_series: BTreeMap<&String, &BTreeMap<(u64, TradeKey), Trade>> =
+            trades.iter().collect();
+        for series in trade_series.values() {
+            if let (Some(((earliest, _), trade)), Some(((latest, _), _))) =
+                (series.first_key_value(), series.last_key_value())
+            {
+                let days: BTreeSet<u64> = series
+                    .keys()
+                    .map(|(timestamp, _)| floor_mili_ts(*timestamp, DAY_AS_MILI))
+                    .collect();
+
+                coverage.trades.push(TradeCoverage::new(
+                    &trade.symbol,
+                    *earliest,
+                    *latest,
+                    series.len(),
+                    days.len(),
+                ));
+            }
+        }
+
+        Ok(coverage)
+    }
+
+    /// Reports the stored klines of a series, klines are keyed by open time so are never
+    /// duplicated.
+
+    async fn compact_klines(
+        &self,
+        symbol: &str,
+        interval: Interval,
+    ) -> Result<CompactionReport, Box<dyn Error>> {
+        let stored = self.klines.read().unwrap();
+
+        Ok(CompactionReport {
+            symbol: symbol.to_string(),
+            interval,
+            klines: stored
+                .get(&build_kline_key(symbol, interval))
+                .map_or(0, |series| series.len()),
+            duplicates_removed: 0,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::Arc;
+
+    use tokio::test;
+    use uuid::Uuid;
+
+    use crate::account::trade::OrderSide;
+
+    #[test]
+    async fn test_memory_storage_round_trip() {
+        // used through the same trait object as the disk backends
+        let storage: Arc<dyn StorageManager> = Arc::new(MemoryStorage::default());
+        let start = 1_700_000_040_000;
+        let minute = Interval::Min1.to_mili();
+
+        let build_kline = |i: u64, close: f64| Kline {
+            symbol: "BTCUSDT".to_string(),
+            interval: Interval::Min1,
+            close,
+            open_time: start + minute * i,
+            close_time: start + minute * (i + 1) - 1,
+            ..Default::default()
+        };
+
+        let kline_key = build_kline_key("BTCUSDT", Interval::Min1);
+        let klines: Vec<Kline> = (0..5).map(|i| build_kline(i, 100.0)).collect();
+        storage
+            .save_klines(&klines, &kline_key, true)
+            .await
+            .unwrap();
+
+        // saving an existing kline replaces it
+        storage
+            .save_klines(&[build_kline(2, 105.0)], &kline_key, true)
+            .await
+            .unwrap();
+
+        let all = storage
+            .get_klines("BTCUSDT", Interval::Min1, None, None)
+            .await;
+        assert_eq!(all.len(), 5);
+        assert_eq!(all[2].close, 105.0);
+
+        let range = storage
+            .get_klines(
+                "BTCUSDT",
+                Interval::Min1,
+                Some(start + minute),
+                Some(start + minute * 4 - 1),
+            )
+            .await;
+        let open_times: Vec<u64> = range.iter().map(|kline| kline.open_time).collect();
+        assert_eq!(
+            open_times,
+            vec![start + minute, start + minute * 2, start + minute * 3]
+        );
+
+        let trades: Vec<Trade> = (0..3)
+            .map(|i| Trade {
+                symbol: "BTCUSDT".to_string(),
+                timestamp: start + i * 1_000,
+                qty: 1.0,
+                price: 100.0,
+                order_side: OrderSide::Buy,
+                id: None,
+            })
+            .collect();
+        storage
+            .save_trades(&trades, &build_market_trade_key("BTCUSDT"), false)
+            .await
+            .unwrap();
+        let range = storage
+            .get_trades("BTCUSDT", Some(start + 1_000), Some(start + 2_000))
+            .await;
+        assert_eq!(range, trades[1..].to_vec());
+
+        let mut summary = StrategySummary::default();
+        summary.info.id = Uuid::new_v4();
+        summary.profit = 42.0;
+        storage
+            .save_strategy_summary(summary.clone())
+            .await
+            .unwrap();
+
+        let saved = storage.get_strategy_summary(summary.info.id).await.unwrap();
+        assert_eq!(saved.profit, 42.0);
+        assert_eq!(storage.list_saved_strategies().await.unwrap().len(), 1);
+        assert!(storage.get_strategy_summary(Uuid::new_v4()).await.is_err());
+
+        let coverage = storage.data_coverage().await.unwrap();
+        assert_eq!(coverage.klines[0].count, 5);
+        assert_eq!(coverage.trades[0].count, 3);
+    }
+
+    #[test]
+    async fn test_same_millisecond_trades_kept() {
+        let storage = MemoryStorage::default();
+        let trade_key = build_market_trade_key("BTCUSDT");
+        let trade = Trade {
+            symbol: "BTCUSDT".to_string(),
+            timestamp: 1_700_000_040_000,
+            qty: 1.0,
+            price: 100.0,
+            order_side: OrderSide::Buy,
+            id: None,
+        };
+        let fill = Trade {
+            price: 101.0,
+            ..trade.clone()
+        };
+
+        storage
+            .save_trades(&[trade.clone(), fill.clone()], &trade_key, false)
+            .await
+            .unwrap();
+        // saving a stored trade again replaces it
+        storage
+            .save_trades(&[trade.clone()], &trade_key, false)
+            .await
+            .unwrap();
+
+        let trades = storage
+            .get_trades("BTCUSDT", Some(trade.timestamp), Some(trade.timestamp))
+            .await;
+        assert_eq!(trades, vec![trade, fill]);
+    }
+}
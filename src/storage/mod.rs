@@ -1,4 +1,8 @@
+pub mod builder;
+pub mod fallback;
 pub mod fs;
 pub mod influx;
 pub mod manager;
+pub mod memory;
 pub mod mongo;
+pub mod schema;
@@ -1,11 +1,13 @@
-use super::manager::StorageManager;
+use super::manager::{
+    CompactionReport, DataCoverage, KlineCoverage, StorageManager, TradeCoverage,
+};
 use crate::{
-    account::trade::OrderSide,
-    market::{interval::Interval, kline::Kline, trade::Trade},
+    account::{account::AccountSnapshot, schedule::ScheduledOrder, trade::OrderSide},
+    market::{alert::Alert, interval::Interval, kline::Kline, ticker::TickerSample, trade::Trade},
     strategy::strategy::{StrategyId, StrategyInfo, StrategySummary},
     utils::{
         bson::{build_bson_kline_meta, build_bson_trade_meta},
-        kline::build_kline_key,
+        kline::{build_kline_key, build_ticker_key},
         time::{elapsed_time, start_timer, timestamp_to_datetime},
         trade::build_market_trade_key,
     },
@@ -17,7 +19,7 @@ use uuid::Uuid;
 use async_trait::async_trait;
 use futures::{TryFutureExt, TryStreamExt};
 use futures_util::StreamExt;
-use log::info;
+use log::{info, warn};
 use mongodb::{
     bson::{self, doc, to_document},
     IndexModel,
@@ -30,14 +32,19 @@ use mongodb::{
 use mongodb::{
     bson::{DateTime, Uuid as BsonUuid},
     options::{
-        CreateCollectionOptions, DeleteOptions, InsertOneOptions, TimeseriesOptions, UpdateOptions,
+        ClientOptions, CreateCollectionOptions, DeleteOptions, FindOptions, InsertOneOptions,
+        ReplaceOptions, TimeseriesOptions, UpdateOptions,
     },
 };
 use mongodb::{Client, Collection};
 use serde::{Deserialize, Serialize};
 use std::error::Error;
+use std::time::Duration;
 use std::{fmt, io};
 
+/// Time to wait for a MongoDB server to be selected before the connection is deemed failed.
+const MONGO_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
 #[derive(Debug)]
 struct MongoErrorWrapper(String);
 
@@ -55,7 +62,18 @@ pub struct MongoDbStorage {
 
 impl MongoDbStorage {
     pub async fn new(uri: &str) -> Result<Self, Box<dyn Error>> {
-        let client = Client::with_uri_str(uri).await?;
+        let mut options = ClientOptions::parse(uri).await?;
+        if options.server_selection_timeout.is_none() {
+            options.server_selection_timeout = Some(MONGO_CONNECT_TIMEOUT);
+        }
+        let client = Client::with_options(options)?;
+
+        // the client connects lazily, ping to make sure the server is reachable
+        client
+            .database("admin")
+            .run_command(doc! { "ping": 1 }, None)
+            .await?;
+
         let mut _self = MongoDbStorage { client };
 
         // _self.init().await.ok();
@@ -88,6 +106,28 @@ impl MongoDbStorage {
         self.client.database("trading_db").collection("strategy")
     }
 
+    fn account_snapshot_collection(&self) -> Collection<AccountSnapshot> {
+        self.client
+            .database("trading_db")
+            .collection("account_snapshots")
+    }
+
+    fn alert_collection(&self) -> Collection<Alert> {
+        self.client.database("trading_db").collection("alerts")
+    }
+
+    fn ticker_sample_collection(&self) -> Collection<TickerSample> {
+        self.client
+            .database("trading_db")
+            .collection("ticker_samples")
+    }
+
+    fn scheduled_order_collection(&self) -> Collection<ScheduledOrder> {
+        self.client
+            .database("trading_db")
+            .collection("scheduled_orders")
+    }
+
     async fn init_timeseries_collection(
         &self,
         collection_name: &str,
@@ -158,7 +198,10 @@ impl StorageManager for MongoDbStorage {
             let mut klines: Vec<Kline> = Vec::new();
             while let Some(result) = cursor.next().await {
                 if let Ok(bson_kline) = result {
-                    klines.push(bson_kline.into());
+                    match Kline::try_from(bson_kline) {
+                        Ok(kline) => klines.push(kline),
+                        Err(e) => warn!("Skipping kline in {collection_name}, {e}"),
+                    }
                 }
             }
             return klines;
@@ -391,6 +434,338 @@ impl StorageManager for MongoDbStorage {
             Err(Box::new(MongoErrorWrapper("No strategy found".to_string())))
         }
     }
+
+    async fn save_account_snapshot(
+        &self,
+        snapshot: &AccountSnapshot,
+    ) -> Result<(), Box<dyn Error>> {
+        let collection = self.account_snapshot_collection();
+
+        collection.insert_one(snapshot, None).await?;
+
+        Ok(())
+    }
+
+    async fn get_account_snapshots(
+        &self,
+        from_ts: Option<u64>,
+        to_ts: Option<u64>,
+    ) -> Result<Vec<AccountSnapshot>, Box<dyn Error>> {
+        let collection = self.account_snapshot_collection();
+
+        let mut range = doc! {};
+        if let Some(from_ts) = from_ts {
+            range.insert("$gte", from_ts as i64);
+        }
+        if let Some(to_ts) = to_ts {
+            range.insert("$lte", to_ts as i64);
+        }
+
+        let filter = if range.is_empty() {
+            doc! {}
+        } else {
+            doc! {"timestamp": range}
+        };
+
+        let options = FindOptions::builder().sort(doc! {"timestamp": 1}).build();
+
+        let snapshots: Vec<AccountSnapshot> = collection
+            .find(filter, options)
+            .await?
+            .try_collect()
+            .await?;
+
+        Ok(snapshots)
+    }
+
+    async fn save_ticker_samples(
+        &self,
+        samples: &[TickerSample],
+        _ticker_key: &str,
+    ) -> Result<(), Box<dyn Error>> {
+        // samples of all symbols share a collection, queried by symbol. Samples are upserted by
+        // symbol and time so a retried save doesn't store them twice
+        let collection = self.ticker_sample_collection();
+        let options = ReplaceOptions::builder().upsert(true).build();
+
+        for sample in samples {
+            collection
+                .replace_one(
+                    doc! { "symbol": &sample.symbol, "time": sample.time as i64 },
+                    sample,
+                    options.clone(),
+                )
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn get_ticker_samples(
+        &self,
+        symbol: &str,
+        from_ts: Option<u64>,
+        to_ts: Option<u64>,
+    ) -> Result<Vec<TickerSample>, Box<dyn Error>> {
+        let mut filter = doc! { "symbol": symbol };
+
+        let mut range = doc! {};
+        if let Some(from_ts) = from_ts {
+            range.insert("$gte", from_ts as i64);
+        }
+        if let Some(to_ts) = to_ts {
+            range.insert("$lte", to_ts as i64);
+        }
+        if !range.is_empty() {
+            filter.insert("time", range);
+        }
+
+        let options = FindOptions::builder().sort(doc! {"time": 1}).build();
+
+        let samples: Vec<TickerSample> = self
+            .ticker_sample_collection()
+            .find(filter, options)
+            .await?
+            .try_collect()
+            .await?;
+
+        Ok(samples)
+    }
+
+    async fn save_alerts(&self, alerts: &[Alert]) -> Result<(), Box<dyn Error>> {
+        replace_by_id(&self.alert_collection(), alerts).await
+    }
+
+    async fn get_alerts(&self) -> Result<Vec<Alert>, Box<dyn Error>> {
+        let collection = self.alert_collection();
+
+        let alerts: Vec<Alert> = collection.find(doc! {}, None).await?.try_collect().await?;
+
+        Ok(alerts)
+    }
+
+    async fn save_scheduled_orders(&self, orders: &[ScheduledOrder]) -> Result<(), Box<dyn Error>> {
+        replace_by_id(&self.scheduled_order_collection(), orders).await
+    }
+
+    async fn get_scheduled_orders(&self) -> Result<Vec<ScheduledOrder>, Box<dyn Error>> {
+        let collection = self.scheduled_order_collection();
+
+        let orders: Vec<ScheduledOrder> =
+            collection.find(doc! {}, None).await?.try_collect().await?;
+
+        Ok(orders)
+    }
+
+    async fn data_coverage(&self) -> Result<DataCoverage, Box<dyn Error>> {
+        let db = self.client.database("trading_db");
+        let mut coverage = DataCoverage::default();
+
+        for collection_name in db.list_collection_names(None).await? {
+            // kline collections are named {symbol}_kline_{interval}, trade collections {symbol}_trade
+            if let Some((symbol, interval)) = collection_name.split_once("_kline_") {
+                let interval = match Interval::try_from(interval) {
+                    Ok(interval) => interval,
+                    Err(_) => continue,
+                };
+
+                let pipeline = vec![doc! {
+                    "$group": {
+                        "_id": null,
+                        "earliest": { "$min": "$open_time" },
+                        "latest": { "$max": "$open_time" },
+                        "count": { "$sum": 1 }
+                    }
+                }];
+
+                let collection = db.collection::<BsonKline>(&collection_name);
+                let groups: Vec<bson::Document> = collection
+                    .aggregate(pipeline, None)
+                    .await?
+                    .try_collect()
+                    .await?;
+
+                if let Some(group) = groups.first() {
+                    coverage.klines.push(KlineCoverage::new(
+                        symbol,
+                        interval,
+                        group.get_datetime("earliest")?.timestamp_millis() as u64,
+                        group.get_datetime("latest")?.timestamp_millis() as u64,
+                        group_count(group)?,
+                    ));
+                }
+            } else if let Some(symbol) = collection_name.strip_suffix("_trade") {
+                // group by day to find the days holding trades
+                let pipeline = vec![doc! {
+                    "$group": {
+                        "_id": { "$dateToString": { "format": "%Y-%m-%d", "date": "$timestamp" } },
+                        "earliest": { "$min": "$timestamp" },
+                        "latest": { "$max": "$timestamp" },
+                        "count": { "$sum": 1 }
+                    }
+                }];
+
+                let collection = db.collection::<BsonMarketTrade>(&collection_name);
+                let days: Vec<bson::Document> = collection
+                    .aggregate(pipeline, None)
+                    .await?
+                    .try_collect()
+                    .await?;
+
+                let mut earliest: Option<u64> = None;
+                let mut latest: Option<u64> = None;
+                let mut count = 0;
+                for day in &days {
+                    let day_earliest = day.get_datetime("earliest")?.timestamp_millis() as u64;
+                    let day_latest = day.get_datetime("latest")?.timestamp_millis() as u64;
+                    earliest = Some(earliest.map_or(day_earliest, |ts| ts.min(day_earliest)));
+                    latest = Some(latest.map_or(day_latest, |ts| ts.max(day_latest)));
+                    count += group_count(day)?;
+                }
+
+                if let (Some(earliest), Some(latest)) = (earliest, latest) {
+                    coverage.trades.push(TradeCoverage::new(
+                        symbol,
+                        earliest,
+                        latest,
+                        count,
+                        days.len(),
+                    ));
+                }
+            }
+        }
+
+        Ok(coverage)
+    }
+
+    async fn compact_klines(
+        &self,
+        symbol: &str,
+        interval: Interval,
+    ) -> Result<CompactionReport, Box<dyn Error>> {
+        let kline_key = build_kline_key(symbol, interval);
+        let collection = self.kline_collection(&kline_key).await?;
+
+        // time series collections can't be written to in a transaction, the kept copies are
+        // staged first so klines deleted by an interrupted compaction are restored by the next
+        let staging: Collection<BsonKline> = self
+            .client
+            .database("trading_db")
+            .collection(&format!("{}_compaction", kline_key.replace("@", "_")));
+        restore_staged_klines(&collection, &staging).await?;
+
+        // time series collections can only delete by metadata, so every copy of a duplicated
+        // kline is deleted and the most recently saved copy, holding its final values, inserted
+        // again
+        let pipeline = vec![
+            doc! { "$sort": { "_id": -1 } },
+            doc! {
+                "$group": {
+                    "_id": "$metadata",
+                    "kline": { "$first": "$$ROOT" },
+                    "count": { "$sum": 1 }
+                }
+            },
+            doc! { "$match": { "count": { "$gt": 1 } } },
+        ];
+
+        let groups: Vec<bson::Document> = collection
+            .aggregate(pipeline, None)
+            .await?
+            .try_collect()
+            .await?;
+
+        let mut duplicates_removed = 0;
+        let mut metas = vec![];
+        let mut klines: Vec<BsonKline> = vec![];
+        for group in groups {
+            duplicates_removed += group_count(&group)? - 1;
+            metas.push(group.get_str("_id")?.to_string());
+            klines.push(bson::from_document(group.get_document("kline")?.clone())?);
+        }
+
+        if !klines.is_empty() {
+            staging.insert_many(&klines, None).await?;
+            collection
+                .delete_many(doc! { "metadata": { "$in": metas } }, None)
+                .await?;
+            collection.insert_many(&klines, None).await?;
+            staging.drop(None).await?;
+        }
+
+        Ok(CompactionReport {
+            symbol: symbol.to_string(),
+            interval,
+            klines: collection.count_documents(None, None).await? as usize,
+            duplicates_removed,
+        })
+    }
+}
+
+/// Inserts the staged klines of an interrupted compaction which are missing from their kline
+/// collection, then clears the staging collection.
+async fn restore_staged_klines(
+    collection: &Collection<BsonKline>,
+    staging: &Collection<BsonKline>,
+) -> Result<(), Box<dyn Error>> {
+    let staged: Vec<BsonKline> = staging.find(None, None).await?.try_collect().await?;
+    if staged.is_empty() {
+        return Ok(());
+    }
+
+    let mut restored = 0;
+    for kline in staged {
+        let filter = doc! { "metadata": &kline.metadata };
+        if collection.count_documents(filter, None).await? == 0 {
+            collection.insert_one(kline, None).await?;
+            restored += 1;
+        }
+    }
+    info!("Restored {restored} klines of an interrupted compaction");
+
+    staging.drop(None).await?;
+    Ok(())
+}
+
+/// Replaces the documents of a collection with `items`, matched by their `id` field.
+///
+/// Items are upserted before the documents of removed items are deleted, so a failed or retried
+/// save never leaves the collection empty or holding duplicates.
+async fn replace_by_id<T: Serialize>(
+    collection: &Collection<T>,
+    items: &[T],
+) -> Result<(), Box<dyn Error>> {
+    let mut ids = vec![];
+
+    for item in items {
+        let id = to_document(item)?
+            .get("id")
+            .cloned()
+            .ok_or_else(|| MongoErrorWrapper("Document without an id".to_string()))?;
+
+        let options = ReplaceOptions::builder().upsert(true).build();
+        collection
+            .replace_one(doc! { "id": id.clone() }, item, options)
+            .await?;
+        ids.push(id);
+    }
+
+    collection
+        .delete_many(doc! { "id": { "$nin": ids } }, None)
+        .await?;
+
+    Ok(())
+}
+
+/// Reads the `count` of an aggregation group, which is an `i32` or `i64` depending on its size.
+fn group_count(group: &bson::Document) -> Result<usize, Box<dyn Error>> {
+    match group.get("count") {
+        Some(Bson::Int32(count)) => Ok(*count as usize),
+        Some(Bson::Int64(count)) => Ok(*count as usize),
+        _ => Err(Box::new(MongoErrorWrapper(
+            "Invalid count in aggregation result".to_string(),
+        ))),
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -405,6 +780,8 @@ pub struct BsonKline {
     pub volume: f64,
     pub open_time: DateTime,
     pub close_time: DateTime,
+    #[serde(default)]
+    pub quote_volume: f64,
 }
 
 impl From<Kline> for BsonKline {
@@ -420,19 +797,20 @@ impl From<Kline> for BsonKline {
             volume: kline.volume,
             open_time: DateTime::from_millis(kline.open_time as i64),
             close_time: DateTime::from_millis(kline.close_time as i64),
+            quote_volume: kline.quote_volume,
         }
     }
 }
 
-impl From<BsonKline> for Kline {
-    fn from(bson_kline: BsonKline) -> Self {
-        Kline {
+impl TryFrom<BsonKline> for Kline {
+    type Error = String;
+    fn try_from(bson_kline: BsonKline) -> Result<Self, Self::Error> {
+        let interval = Interval::try_from(bson_kline.interval.as_str())
+            .map_err(|e| format!("{e}: {}", bson_kline.interval))?;
+
+        Ok(Kline {
             symbol: bson_kline.symbol,
-            interval: bson_kline
-                .interval
-                .try_into()
-                .map_err(|e| "X".to_string())
-                .unwrap(),
+            interval,
             open: bson_kline.open,
             high: bson_kline.high,
             low: bson_kline.low,
@@ -440,7 +818,8 @@ impl From<BsonKline> for Kline {
             volume: bson_kline.volume,
             open_time: bson_kline.open_time.timestamp_millis() as u64,
             close_time: bson_kline.close_time.timestamp_millis() as u64,
-        }
+            quote_volume: bson_kline.quote_volume,
+        })
     }
 }
 
@@ -452,6 +831,8 @@ pub struct BsonMarketTrade {
     pub qty: f64,
     pub price: f64,
     pub order_side: OrderSide,
+    #[serde(default)]
+    pub id: Option<u64>,
 }
 
 impl From<Trade> for BsonMarketTrade {
@@ -463,6 +844,7 @@ impl From<Trade> for BsonMarketTrade {
             qty: trade.qty,
             price: trade.price,
             order_side: trade.order_side,
+            id: trade.id,
         }
     }
 }
@@ -475,6 +857,7 @@ impl From<BsonMarketTrade> for Trade {
             qty: bson_trade.qty,
             price: bson_trade.price,
             order_side: bson_trade.order_side,
+            id: bson_trade.id,
         }
     }
 }
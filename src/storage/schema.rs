@@ -0,0 +1,350 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use csv::{ReaderBuilder, WriterBuilder};
+use log::{info, warn};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::utils::csv::count_rows;
+
+/// Version of the layout of the kline and trade CSV files, written on the first line of each file.
+///
+/// Files written before versioning have no marker and no header, their columns are positional
+/// and are read as version `0`. Version `2` added the quote volume of klines, version `3` the
+/// exchange ID of trades.
+pub const DATA_SCHEMA_VERSION: u32 = 3;
+
+/// Prefix of the schema marker line, read as a comment by the CSV reader.
+const SCHEMA_MARKER: &str = "#schema_version=";
+
+/// Serialization format of the kline and trade data files.
+///
+/// CSV is human readable and can be opened by other tools, bincode is faster to read and write
+/// and smaller on disk. The format of a file is detected from its extension, so files written in
+/// either format can be read whichever format new files are written in.
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DataFormat {
+    #[default]
+    Csv,
+    Bincode,
+}
+
+impl DataFormat {
+    /// All supported formats.
+    pub const ALL: [DataFormat; 2] = [DataFormat::Csv, DataFormat::Bincode];
+
+    /// Parses the `FS_DATA_FORMAT` setting, unknown values use CSV.
+
+    pub fn from_setting(setting: &str) -> Self {
+        match setting {
+            "BINCODE" => DataFormat::Bincode,
+            _ => DataFormat::Csv,
+        }
+    }
+
+    /// Detects the format of a data file from its extension, files without the bincode
+    /// extension are read as CSV.
+
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("bin") => DataFormat::Bincode,
+            _ => DataFormat::Csv,
+        }
+    }
+
+    /// Extension of the data files written in the format.
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            DataFormat::Csv => "csv",
+            DataFormat::Bincode => "bin",
+        }
+    }
+}
+
+/// Row of a kline, trade or ticker data file.
+///
+/// CSV rows are read by column name, so fields added with a serde default are read from older
+/// files as is. Bincode rows are read by position, so rows whose layout changed read older bincode
+/// files with their previous layout.
+pub trait DataRow: Serialize + DeserializeOwned {
+    /// Key rows of a data file are ordered by, rows with the same key are duplicates.
+    type Key: Ord;
+
+    /// Returns the key of the row, ie. the open time of a kline.
+
+    fn key(&self) -> Self::Key;
+
+    /// Reads a row of a bincode file written with a schema version, rows whose layout never
+    /// changed are read with the current layout.
+
+    fn read_bincode_row<R: Read>(reader: R, _version: u32) -> bincode::Result<Self> {
+        bincode::deserialize_from(reader)
+    }
+}
+
+/// Reads the schema version of a data file.
+///
+/// Bincode files start with the version as a 4 byte integer, rather than a marker line.
+///
+/// # Arguments
+///
+/// * `path` - Path of the data file.
+///
+/// # Returns
+///
+/// The schema version, `0` for headerless files, or `None` if the file doesn't exist or is empty.
+
+pub fn schema_version(path: &Path) -> io::Result<Option<u32>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    if DataFormat::from_path(path) == DataFormat::Bincode {
+        let mut version = [0u8; 4];
+        return match File::open(path)?.read_exact(&mut version) {
+            Ok(()) => Ok(Some(u32::from_le_bytes(version))),
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => Ok(None),
+            Err(e) => Err(e),
+        };
+    }
+
+    let first_line = match BufReader::new(File::open(path)?).lines().next() {
+        Some(line) => line?,
+        None => return Ok(None),
+    };
+
+    match first_line.strip_prefix(SCHEMA_MARKER) {
+        Some(version) => version.trim().parse().map(Some).map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Invalid schema marker in {}", path.display()),
+            )
+        }),
+        None if first_line.trim().is_empty() => Ok(None),
+        None => Ok(Some(0)),
+    }
+}
+
+/// Reads all rows of a data file in the format of its extension, headerless CSV files are read
+/// positionally.
+///
+/// Files written with an older schema are read as they are, they are only rewritten by
+/// `migrate_rows`.
+///
+/// # Arguments
+///
+/// * `path` - Path of the data file.
+///
+/// # Returns
+///
+/// The rows of the file, empty if it doesn't exist, or an error if it can't be read or was
+/// written by a newer schema.
+
+pub fn read_rows<T: DataRow>(path: &Path) -> io::Result<Vec<T>> {
+    read_versioned_rows(path, schema_version(path)?)
+}
+
+/// Rewrites a data file written with an older schema with the current schema.
+///
+/// # Arguments
+///
+/// * `path` - Path of the data file.
+///
+/// # Returns
+///
+/// Whether the file was migrated, or an error if it can't be read or rewritten, in which case
+/// the file is left as it was.
+
+pub fn migrate_rows<T: DataRow>(path: &Path) -> io::Result<bool> {
+    let version = match schema_version(path)? {
+        Some(version) if version < DATA_SCHEMA_VERSION => version,
+        _ => return Ok(false),
+    };
+
+    info!(
+        "Migrating {} from schema version {version} to {DATA_SCHEMA_VERSION}",
+        path.display()
+    );
+
+    let rows: Vec<T> = read_versioned_rows(path, Some(version))?;
+    write_rows(path, &rows)?;
+
+    Ok(true)
+}
+
+/// Writes all rows to a data file with the current schema in the format of its extension,
+/// replacing its contents.
+///
+/// Rows are written to a temporary file which is synced to disk and then renamed over the data
+/// file, so the file holds either its previous or its new rows if writing is interrupted.
+///
+/// # Arguments
+///
+/// * `path` - Path of the data file.
+/// * `rows` - The rows to write.
+
+pub fn write_rows<T: Serialize>(path: &Path, rows: &[T]) -> io::Result<()> {
+    let file_name = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Invalid data file path {}", path.display()),
+            )
+        })?;
+    let temp_path = path.with_file_name(format!(".{file_name}.tmp"));
+
+    let written = write_temp_rows(&temp_path, DataFormat::from_path(path), rows)
+        .and_then(|_| fs::rename(&temp_path, path));
+
+    if written.is_err() {
+        if let Err(e) = fs::remove_file(&temp_path) {
+            warn!("Unable to remove {}, {e}", temp_path.display());
+        }
+    }
+
+    written
+}
+
+/// Appends rows to a data file, files written with an older schema are migrated first.
+///
+/// # Arguments
+///
+/// * `path` - Path of the data file, created if it doesn't exist.
+/// * `rows` - The rows to append.
+
+pub fn append_rows<T: DataRow + Clone>(path: &Path, rows: &[T]) -> io::Result<()> {
+    match schema_version(path)? {
+        Some(DATA_SCHEMA_VERSION) if DataFormat::from_path(path) == DataFormat::Bincode => {
+            let file = OpenOptions::new().append(true).open(path)?;
+            let mut writer = BufWriter::new(file);
+            write_bincode_rows(&mut writer, rows)?;
+            writer.flush()
+        }
+        Some(DATA_SCHEMA_VERSION) => {
+            let file = OpenOptions::new().append(true).open(path)?;
+            let mut writer = WriterBuilder::new().has_headers(false).from_writer(file);
+
+            for row in rows {
+                writer.serialize(row)?;
+            }
+            writer.flush()
+        }
+        Some(version) => {
+            let mut existing: Vec<T> = read_versioned_rows(path, Some(version))?;
+            existing.extend_from_slice(rows);
+            write_rows(path, &existing)
+        }
+        None => write_rows(path, rows),
+    }
+}
+
+/// Counts the rows of a data file, excluding the schema marker and header.
+///
+/// Bincode rows have no fixed size so are counted by reading them as `T`, CSV rows are counted
+/// by line.
+///
+/// # Arguments
+///
+/// * `path` - Path of the data file.
+
+pub fn count_data_rows<T: DataRow>(path: &Path) -> io::Result<usize> {
+    if DataFormat::from_path(path) == DataFormat::Bincode {
+        let version = schema_version(path)?.unwrap_or(DATA_SCHEMA_VERSION);
+        return Ok(read_bincode_rows::<T>(path, version)?.len());
+    }
+
+    let lines = count_rows(path)?;
+
+    match schema_version(path)? {
+        Some(0) | None => Ok(lines),
+        Some(_) => Ok(lines.saturating_sub(2)),
+    }
+}
+
+/// Reads the rows of a data file written with a known schema version.
+fn read_versioned_rows<T: DataRow>(path: &Path, version: Option<u32>) -> io::Result<Vec<T>> {
+    if version.is_some_and(|version| version > DATA_SCHEMA_VERSION) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "{} was written with schema version {}, newer than {DATA_SCHEMA_VERSION}",
+                path.display(),
+                version.unwrap_or_default()
+            ),
+        ));
+    }
+
+    if let (Some(version), DataFormat::Bincode) = (version, DataFormat::from_path(path)) {
+        return read_bincode_rows(path, version);
+    }
+
+    let mut reader = match version {
+        None => return Ok(vec![]),
+        Some(0) => ReaderBuilder::new().has_headers(false).from_path(path)?,
+        Some(_) => ReaderBuilder::new()
+            .has_headers(true)
+            .comment(Some(b'#'))
+            .from_path(path)?,
+    };
+
+    Ok(reader.deserialize().collect::<Result<Vec<T>, _>>()?)
+}
+
+/// Reads the rows of a bincode data file written with a schema version, following the version
+/// header.
+fn read_bincode_rows<T: DataRow>(path: &Path, version: u32) -> io::Result<Vec<T>> {
+    let mut reader = BufReader::new(File::open(path)?);
+    reader.read_exact(&mut [0u8; 4])?;
+
+    let mut rows = vec![];
+    while !reader.fill_buf()?.is_empty() {
+        let row = T::read_bincode_row(&mut reader, version)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        rows.push(row);
+    }
+
+    Ok(rows)
+}
+
+/// Writes rows with the current schema to a temporary file in a format and syncs it to disk.
+fn write_temp_rows<T: Serialize>(
+    temp_path: &Path,
+    format: DataFormat,
+    rows: &[T],
+) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(temp_path)?);
+
+    match format {
+        DataFormat::Bincode => {
+            writer.write_all(&DATA_SCHEMA_VERSION.to_le_bytes())?;
+            write_bincode_rows(&mut writer, rows)?;
+        }
+        DataFormat::Csv => {
+            writeln!(writer, "{SCHEMA_MARKER}{DATA_SCHEMA_VERSION}")?;
+
+            let mut csv_writer = WriterBuilder::new()
+                .has_headers(true)
+                .from_writer(&mut writer);
+            for row in rows {
+                csv_writer.serialize(row)?;
+            }
+            csv_writer.flush()?;
+        }
+    }
+
+    writer.into_inner().map_err(|e| e.into_error())?.sync_all()
+}
+
+/// Writes rows one after another so a bincode file can be appended to.
+fn write_bincode_rows<T: Serialize>(writer: &mut impl Write, rows: &[T]) -> io::Result<()> {
+    for row in rows {
+        bincode::serialize_into(&mut *writer, row)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    }
+    Ok(())
+}
@@ -9,7 +9,10 @@ use crate::{
         macd_bollinger::MacdBollingerBands, rsi::Rsi,
     },
     market::{kline::Kline, trade::Trade},
-    strategy::types::{AlgoError, AlgoEvalResult},
+    strategy::{
+        indicator::{AlgoContext, Indicator, IndicatorSet},
+        types::{AlgoError, AlgoEvalResult},
+    },
     utils::time::build_interval,
 };
 
@@ -26,12 +29,14 @@ pub trait Algorithm: Send + Sync {
     /// # Arguments
     ///
     /// * `kline` - A `Kline` struct representing the k-line data to evaluate.
+    /// * `trades` - Trades within the k-line, if the algorithm needs trades.
+    /// * `ctx` - Values of the indicators declared by the algorithm at the k-line.
     ///
     /// # Returns
     ///
     /// An `AlgoEvalResult` indicating the trading signal generated by the algorithm.
 
-    fn evaluate(&mut self, kline: Kline, trades: &[Trade]) -> AlgoEvalResult;
+    fn evaluate(&mut self, kline: Kline, trades: &[Trade], ctx: &AlgoContext) -> AlgoEvalResult;
 
     /// Sets the algorithm's parameters based on a JSON `Value`.
     ///
@@ -86,4 +91,41 @@ pub trait Algorithm: Send + Sync {
     fn needs_trades(&self) -> bool {
         false
     }
+
+    /// Indicators the strategy calculates for the algorithm, their values are passed to
+    /// `evaluate` in the `AlgoContext`.
+    ///
+    /// # Returns
+    ///
+    /// The declared indicators, ie. `[Indicator::Sma(20), Indicator::Rsi(14)]`.
+    /// Defaults to returning no indicators
+
+    fn indicators(&self) -> Vec<Indicator> {
+        vec![]
+    }
+
+    /// Number of k-lines the algorithm needs to evaluate before its indicators are seeded.
+    ///
+    /// Back tests feed the first `warmup` k-lines to `evaluate` to update the algorithm state
+    /// without acting on the results.
+    ///
+    /// # Returns
+    ///
+    /// The number of warmup k-lines.
+    /// Defaults to the warmup of the declared indicators
+
+    fn warmup(&self) -> usize {
+        IndicatorSet::warmup(&self.indicators())
+    }
+
+    /// Key indicator values from the latest evaluation, recorded in the strategy evaluation log.
+    ///
+    /// # Returns
+    ///
+    /// A JSON object of indicator values, ie. `{ "sma": 101.5 }`.
+    /// Defaults to returning `None`
+
+    fn debug_fields(&self) -> Option<Value> {
+        None
+    }
 }
@@ -1,7 +1,8 @@
-use std::sync::Arc;
+use std::{collections::BTreeMap, sync::Arc};
 
 use actix_web::rt::signal;
 use log::info;
+use serde::{Deserialize, Serialize};
 
 use crate::{
     account::{
@@ -9,12 +10,18 @@ use crate::{
         trade::{OrderSide, PositionId, TradeTx},
     },
     exchange::{api::ExchangeApi, mock::MockExchangeApi},
-    market::{kline::KlineData, market::Market, messages::MarketMessage, types::ArcMutex},
-    storage::{fs::FsStorage, manager::StorageManager, mongo::MongoDbStorage},
+    market::{
+        channel::{build_market_channel, DEFAULT_MARKET_CHANNEL_CAPACITY},
+        interval::Interval,
+        kline::{Kline, KlineData},
+        market::Market,
+        types::ArcMutex,
+    },
+    storage::{manager::StorageManager, mongo::MongoDbStorage},
     strategy::{
         signal::{SignalHandler, SignalMessage, SignalMessageType},
-        strategy::{Strategy, StrategySummary},
-        types::AlgoEvalResult,
+        strategy::{Strategy, StrategySummary, TradeAggregates},
+        types::{AlgoError, AlgoEvalResult},
     },
     utils::{
         channel::build_arc_channel,
@@ -22,6 +29,80 @@ use crate::{
     },
 };
 
+/// Why a back test kline wasn't acted on.
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum KlineSkipReason {
+    /// Volume below the `min_kline_volume` of the strategy, the kline isn't evaluated.
+    BelowMinVolume,
+    /// Evaluated to seed the algorithm indicators, the result is discarded.
+    Warmup,
+}
+
+/// Whether a back test kline was acted on.
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct KlineAudit {
+    pub open_time: u64,
+    pub close_time: u64,
+    /// Whether the result of the evaluation was acted on.
+    pub evaluated: bool,
+    /// Result of the algorithm, `None` if the kline wasn't evaluated.
+    pub result: Option<AlgoEvalResult>,
+    pub skip_reason: Option<KlineSkipReason>,
+}
+
+/// Audit trail of the klines of a back test, used to debug why a back test did or didn't trade.
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct BackTestAudit {
+    /// Number of klines whose result was acted on.
+    pub evaluated_count: usize,
+    /// Number of klines skipped, by reason.
+    pub skip_counts: BTreeMap<KlineSkipReason, usize>,
+    /// Every kline of the back test, oldest first, only recorded with `audit_klines` set.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub klines: Vec<KlineAudit>,
+    #[serde(skip)]
+    record_klines: bool,
+}
+
+impl BackTestAudit {
+    /// Creates an audit, `record_klines` keeps every kline rather than only the counts.
+    fn new(record_klines: bool) -> Self {
+        Self {
+            record_klines,
+            ..Default::default()
+        }
+    }
+
+    /// Records a kline, `skip_reason` is `None` for klines whose result was acted on.
+    fn record(
+        &mut self,
+        kline: &Kline,
+        result: Option<AlgoEvalResult>,
+        skip_reason: Option<KlineSkipReason>,
+    ) {
+        match skip_reason {
+            Some(reason) => *self.skip_counts.entry(reason).or_default() += 1,
+            None => self.evaluated_count += 1,
+        }
+
+        if !self.record_klines {
+            return;
+        }
+
+        self.klines.push(KlineAudit {
+            open_time: kline.open_time,
+            close_time: kline.close_time,
+            evaluated: skip_reason.is_none(),
+            result,
+            skip_reason,
+        });
+    }
+}
+
 /// Represents a backtest environment for a trading strategy.
 ///
 /// This struct encapsulates the logic to simulate the execution of a trading strategy over
@@ -38,6 +119,7 @@ pub struct BackTest {
     end_price: f64,
     start_time: Option<String>,
     end_time: Option<String>,
+    audit: BackTestAudit,
 }
 
 impl BackTest {
@@ -46,7 +128,7 @@ impl BackTest {
     /// # Arguments
     ///
     /// * `strategy` - The trading strategy to backtest.
-    /// * `_initial_balance` - An optional initial balance for the backtest account (not currently used).
+    /// * `initial_balance` - An optional initial balance for the backtest account, used as the base of its equity.
     ///
     /// # Returns
     ///
@@ -55,9 +137,9 @@ impl BackTest {
     pub async fn new(
         strategy: Strategy,
         market: ArcMutex<Market>,
-        _initial_balance: Option<f64>,
+        initial_balance: Option<f64>,
     ) -> Self {
-        let (_, market_rx) = build_arc_channel::<MarketMessage>();
+        let (_, market_rx) = build_market_channel(DEFAULT_MARKET_CHANNEL_CAPACITY);
         let exchange_api: Arc<dyn ExchangeApi> = Arc::new(MockExchangeApi::default());
 
         let storage_manager: Arc<dyn StorageManager> = market.lock().await.storage_manager.clone();
@@ -73,10 +155,13 @@ impl BackTest {
         );
 
         // create new storage manager
-        let account = ArcMutex::new(Account::new(exchange_api.clone(), false, true).await);
+        let mut account = Account::new(exchange_api.clone(), false, true).await;
+        account.set_initial_balance(initial_balance.unwrap_or(0.0));
+        let account = ArcMutex::new(account);
 
         let mut signal_manager = SignalHandler::new();
         signal_manager.add_strategy_settings(&strategy.id, strategy.settings());
+        let audit = BackTestAudit::new(strategy.settings().audit_klines);
 
         Self {
             strategy,
@@ -88,6 +173,7 @@ impl BackTest {
             start_price: 0.0,
             start_time: None,
             end_time: None,
+            audit,
         }
     }
 
@@ -107,7 +193,21 @@ impl BackTest {
             self.end_price = last.close
         }
 
-        for kline in kline_data.klines() {
+        let warmup = self.strategy.algorithm.lock().await.warmup();
+        let settings = self.strategy.settings();
+
+        for (i, kline) in kline_data.klines().into_iter().enumerate() {
+            if settings.is_below_min_volume(&kline) {
+                info!(
+                    "Skipping back test kline at {}, volume {} below minimum",
+                    timestamp_to_string(kline.open_time),
+                    kline.volume
+                );
+                self.audit
+                    .record(&kline, None, Some(KlineSkipReason::BelowMinVolume));
+                continue;
+            }
+
             let algo_needs_trades = self.strategy.algorithm.lock().await.needs_trades();
 
             // only get trades if needed by the algorithm
@@ -133,12 +233,16 @@ impl BackTest {
                 vec![]
             };
 
-            let eval_result = self
-                .strategy
-                .algorithm
-                .lock()
-                .await
-                .evaluate(kline.clone(), &trades);
+            let eval_result = self.strategy.evaluate(&kline, &trades).await;
+
+            // warmup klines only seed the algorithm indicators
+            if i < warmup {
+                self.audit
+                    .record(&kline, Some(eval_result), Some(KlineSkipReason::Warmup));
+                continue;
+            }
+
+            self.audit.record(&kline, Some(eval_result), None);
 
             let order_side = match eval_result {
                 AlgoEvalResult::Buy => OrderSide::Buy,
@@ -155,6 +259,7 @@ impl BackTest {
                 price: kline.close.clone(),
                 is_back_test: true,
                 close_time: timestamp_to_string(kline.close_time),
+                interval: Some(self.strategy.interval),
                 ty: SignalMessageType::Standard,
                 // kline: kline.clone(),
             };
@@ -163,6 +268,51 @@ impl BackTest {
         }
     }
 
+    /// Runs a strategy against several intervals from a single set of klines, resampling the
+    /// klines to each interval.
+    ///
+    /// # Arguments
+    ///
+    /// * `strategy` - The strategy to backtest, a strategy with the same algorithm, parameters and
+    ///   settings is run on each interval.
+    /// * `market` - Shared access to market data.
+    /// * `initial_balance` - An optional initial balance for the account of each backtest.
+    /// * `intervals` - The intervals to run the strategy on.
+    /// * `kline_data` - Historical k-line data of the base interval, each interval must be a whole
+    ///   multiple of it.
+    ///
+    /// # Returns
+    ///
+    /// The `StrategySummary` of each interval, or an `AlgoError` if an interval can't be
+    /// resampled from the base interval.
+
+    pub async fn run_multi(
+        strategy: Strategy,
+        market: ArcMutex<Market>,
+        initial_balance: Option<f64>,
+        intervals: Vec<Interval>,
+        kline_data: KlineData,
+    ) -> Result<BTreeMap<Interval, StrategySummary>, AlgoError> {
+        let base = kline_data.meta.interval;
+        let mut summaries = BTreeMap::new();
+
+        for interval in intervals {
+            let interval_data = kline_data.resample(interval).ok_or_else(|| {
+                AlgoError::InvalidParams(format!(
+                    "Interval {interval} can't be resampled from {base} klines"
+                ))
+            })?;
+
+            let strategy = strategy.with_interval(interval).await?;
+            let mut back_test = BackTest::new(strategy, market.clone(), initial_balance).await;
+            back_test.run(interval_data).await;
+
+            summaries.insert(interval, back_test.result().await);
+        }
+
+        Ok(summaries)
+    }
+
     /// Computes and returns a summary of the backtest results.
     ///
     /// # Returns
@@ -172,9 +322,13 @@ impl BackTest {
 
     pub async fn result(&mut self) -> StrategySummary {
         for signal in &self.strategy.get_signals().await {
-            self.signal_manager
+            if let Err(e) = self
+                .signal_manager
                 .handle_signal(signal.clone(), self.market.clone(), self.account.clone())
                 .await
+            {
+                info!("Back test signal failed: {e}");
+            }
         }
 
         let mut info = self.strategy.info().await;
@@ -202,7 +356,8 @@ impl BackTest {
                     symbol: trade.position.symbol,
                     price: trade.close_price,
                     is_back_test: true,
-                    close_time: trade.close_time,
+                    close_time: timestamp_to_string(trade.close_time),
+                    interval: None,
                     ty: SignalMessageType::ForcedClose("Closed Remaining Positions".to_string()),
                 };
 
@@ -221,25 +376,330 @@ impl BackTest {
             }
         }
 
-        let max_profit = Strategy::calc_max_profit(&trades);
-        let max_drawdown = Strategy::calc_max_drawdown(&trades);
-        let long_trade_count = Strategy::calc_trade_count(&trades, OrderSide::Buy);
-        let short_trade_count = Strategy::calc_trade_count(&trades, OrderSide::Sell);
-        let profit: f64 = Strategy::calc_profit(&trades);
+        let aggregates = TradeAggregates::from_trades(&trades);
 
-        StrategySummary {
+        let mut summary = StrategySummary {
             info,
-            profit,
+            profit: aggregates.profit(),
             trades,
             positions: vec![],
-            long_trade_count,
-            short_trade_count,
+            long_trade_count: aggregates.long_trade_count(),
+            short_trade_count: aggregates.short_trade_count(),
             symbol: self.strategy.symbol.to_string(),
             end_price: self.end_price,
             start_price: self.start_price,
-            max_drawdown,
-            max_profit,
-            // signals: self.strategy.get_signals().await,
+            max_drawdown: aggregates.max_drawdown(),
+            max_profit: aggregates.max_profit(),
+            audit: Some(self.audit.clone()),
+            ..Default::default()
+        };
+        summary.calc_returns();
+
+        summary
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        market::interval::Interval,
+        strategy::strategy::StrategySettings,
+        testutil::{make_klines, test_market},
+        utils::time::MIN_AS_MILI,
+    };
+    use serde_json::json;
+    use tokio::test;
+
+    #[test]
+    async fn test_back_test_warmup() {
+        let market = ArcMutex::new(test_market(Arc::new(MockExchangeApi::default())).await);
+        let (strategy_tx, _) = build_arc_channel::<SignalMessage>();
+
+        let period = 5;
+        let strategy = Strategy::new(
+            "SimpleMovingAverage",
+            "BTCUSDT",
+            Interval::Min1,
+            strategy_tx,
+            market.clone(),
+            StrategySettings::default(),
+            json!({ "sma_period": period }),
+        )
+        .unwrap();
+
+        let mut kline_data = KlineData::new("BTCUSDT", Interval::Min1);
+        let prices: Vec<f64> = (0..20).map(|i| 100.0 + i as f64).collect();
+        for kline in make_klines("BTCUSDT", Interval::Min1, 1_700_000_040_000, &prices) {
+            kline_data.add_kline(kline);
+        }
+        let klines = kline_data.klines();
+
+        let mut back_test = BackTest::new(strategy, market, Some(10_000.0)).await;
+        back_test.run(kline_data).await;
+
+        // sma is available from the 5th kline, signals only start after the warmup
+        let signals = back_test.strategy.get_signals().await;
+        assert_eq!(signals.len(), 20 - period);
+        assert_eq!(
+            signals[0].close_time,
+            timestamp_to_string(klines[period].close_time)
+        );
+
+        // no position is opened at a warmup kline price
+        let summary = back_test.result().await;
+        assert!(!summary.trades.is_empty());
+        assert!(summary
+            .trades
+            .iter()
+            .all(|trade| trade.position.open_price >= klines[period].close));
+    }
+
+    #[test]
+    async fn test_back_test_skips_low_volume_klines() {
+        let market = ArcMutex::new(test_market(Arc::new(MockExchangeApi::default())).await);
+        let (strategy_tx, _) = build_arc_channel::<SignalMessage>();
+
+        let settings = StrategySettings {
+            min_kline_volume: Some(5.0),
+            ..Default::default()
+        };
+        let strategy = Strategy::new(
+            "SimpleMovingAverage",
+            "BTCUSDT",
+            Interval::Min1,
+            strategy_tx,
+            market.clone(),
+            settings,
+            json!({ "sma_period": 2 }),
+        )
+        .unwrap();
+
+        let start = 1_700_000_040_000;
+        let kline = |i: u64, volume: f64| Kline {
+            symbol: "BTCUSDT".to_string(),
+            interval: Interval::Min1,
+            open: 100.0,
+            high: 100.0 + i as f64,
+            low: 100.0,
+            close: 100.0 + i as f64,
+            volume,
+            quote_volume: 0.0,
+            open_time: start + i * MIN_AS_MILI,
+            close_time: start + (i + 1) * MIN_AS_MILI - 1,
+        };
+
+        let mut kline_data = KlineData::new("BTCUSDT", Interval::Min1);
+        for i in 0..4 {
+            kline_data.add_kline(kline(i, 10.0));
+        }
+        // illiquid kline is skipped, the next normal kline is evaluated
+        kline_data.add_kline(kline(4, 1.0));
+        kline_data.add_kline(kline(5, 10.0));
+
+        let mut back_test = BackTest::new(strategy, market, Some(10_000.0)).await;
+        back_test.run(kline_data).await;
+
+        let close_times: Vec<String> = back_test
+            .strategy
+            .get_signals()
+            .await
+            .into_iter()
+            .map(|signal| signal.close_time)
+            .collect();
+
+        assert!(!close_times.contains(&timestamp_to_string(kline(4, 1.0).close_time)));
+        assert!(close_times.contains(&timestamp_to_string(kline(5, 10.0).close_time)));
+    }
+
+    #[test]
+    async fn test_back_test_audit_records_skipped_klines() {
+        let market = ArcMutex::new(test_market(Arc::new(MockExchangeApi::default())).await);
+        let (strategy_tx, _) = build_arc_channel::<SignalMessage>();
+
+        let settings = StrategySettings {
+            min_kline_volume: Some(5.0),
+            audit_klines: true,
+            ..Default::default()
+        };
+        let strategy = Strategy::new(
+            "SimpleMovingAverage",
+            "BTCUSDT",
+            Interval::Min1,
+            strategy_tx,
+            market.clone(),
+            settings,
+            json!({ "sma_period": 2 }),
+        )
+        .unwrap();
+
+        let prices: Vec<f64> = (0..6).map(|i| 100.0 + i as f64).collect();
+        let mut kline_data = KlineData::new("BTCUSDT", Interval::Min1);
+        for (i, mut kline) in make_klines("BTCUSDT", Interval::Min1, 1_700_000_040_000, &prices)
+            .into_iter()
+            .enumerate()
+        {
+            kline.volume = if i == 3 { 1.0 } else { 10.0 };
+            kline_data.add_kline(kline);
+        }
+        let klines = kline_data.klines();
+
+        let mut back_test = BackTest::new(strategy, market, Some(10_000.0)).await;
+        back_test.run(kline_data).await;
+        let audit = back_test.result().await.audit.unwrap();
+
+        // first 2 klines seed the sma, the illiquid 4th kline is never evaluated
+        let skip_reasons: Vec<Option<KlineSkipReason>> =
+            audit.klines.iter().map(|kline| kline.skip_reason).collect();
+        assert_eq!(
+            skip_reasons,
+            vec![
+                Some(KlineSkipReason::Warmup),
+                Some(KlineSkipReason::Warmup),
+                None,
+                Some(KlineSkipReason::BelowMinVolume),
+                None,
+                None,
+            ]
+        );
+        assert_eq!(audit.evaluated_count, 3);
+        assert_eq!(audit.skip_counts[&KlineSkipReason::Warmup], 2);
+        assert_eq!(audit.skip_counts[&KlineSkipReason::BelowMinVolume], 1);
+
+        let skipped = &audit.klines[3];
+        assert_eq!(skipped.open_time, klines[3].open_time);
+        assert!(!skipped.evaluated);
+        assert_eq!(skipped.result, None);
+
+        // rising prices close above the sma once it's available
+        let evaluated = &audit.klines[4];
+        assert!(evaluated.evaluated);
+        assert_eq!(evaluated.result, Some(AlgoEvalResult::Buy));
+        assert!(audit.klines[..2].iter().all(|kline| kline.result.is_some()));
+
+        // only the counts are kept unless klines are audited
+        let mut counts_only = BackTestAudit::new(false);
+        counts_only.record(&klines[0], Some(AlgoEvalResult::Buy), None);
+        counts_only.record(&klines[1], None, Some(KlineSkipReason::BelowMinVolume));
+        assert_eq!(counts_only.evaluated_count, 1);
+        assert_eq!(counts_only.skip_counts[&KlineSkipReason::BelowMinVolume], 1);
+        assert!(counts_only.klines.is_empty());
+    }
+
+    #[test]
+    async fn test_back_test_records_eval_log() {
+        let market = ArcMutex::new(test_market(Arc::new(MockExchangeApi::default())).await);
+        let (strategy_tx, _) = build_arc_channel::<SignalMessage>();
+
+        let settings = StrategySettings {
+            eval_log_size: Some(3),
+            ..Default::default()
+        };
+        let strategy = Strategy::new(
+            "SimpleMovingAverage",
+            "BTCUSDT",
+            Interval::Min1,
+            strategy_tx,
+            market.clone(),
+            settings,
+            json!({ "sma_period": 2 }),
+        )
+        .unwrap();
+
+        let start = 1_700_000_040_000;
+        let mut kline_data = KlineData::new("BTCUSDT", Interval::Min1);
+        for i in 0..5 {
+            let close = 100.0 + i as f64;
+            kline_data.add_kline(Kline {
+                symbol: "BTCUSDT".to_string(),
+                interval: Interval::Min1,
+                open: close,
+                high: close,
+                low: close,
+                close,
+                volume: 1.0,
+                quote_volume: 0.0,
+                open_time: start + i * MIN_AS_MILI,
+                close_time: start + (i + 1) * MIN_AS_MILI - 1,
+            });
         }
+        let klines = kline_data.klines();
+
+        let mut back_test = BackTest::new(strategy, market, Some(10_000.0)).await;
+        back_test.run(kline_data).await;
+
+        // log is bounded, only the latest evaluations are kept
+        let logs = back_test.strategy.eval_log().await;
+        assert_eq!(logs.len(), 3);
+        assert_eq!(logs[0].kline.open_time, klines[2].open_time);
+
+        let last = &logs[2];
+        assert_eq!(last.kline.open_time, klines[4].open_time);
+        assert_eq!(last.result, AlgoEvalResult::Buy);
+        assert_eq!(last.debug_fields, Some(json!({ "sma": 103.5 })));
+    }
+
+    #[test]
+    async fn test_back_test_run_multi_resamples_intervals() {
+        let market = ArcMutex::new(test_market(Arc::new(MockExchangeApi::default())).await);
+        let (strategy_tx, _) = build_arc_channel::<SignalMessage>();
+
+        let strategy = Strategy::new(
+            "SimpleMovingAverage",
+            "BTCUSDT",
+            Interval::Min1,
+            strategy_tx,
+            market.clone(),
+            StrategySettings::default(),
+            json!({ "sma_period": 2 }),
+        )
+        .unwrap();
+
+        // 20 minutes of 1m klines, starting at a 5m boundary
+        let mut kline_data = KlineData::new("BTCUSDT", Interval::Min1);
+        let prices: Vec<f64> = (0..20).map(|i| 100.0 + i as f64).collect();
+        for kline in make_klines("BTCUSDT", Interval::Min1, 1_700_000_100_000, &prices) {
+            kline_data.add_kline(kline);
+        }
+        let klines = kline_data.klines();
+
+        let resampled = kline_data.resample(Interval::Min5).unwrap().klines();
+        assert_eq!(resampled.len(), 4);
+        assert_eq!(resampled[0].open_time, klines[0].open_time);
+        assert_eq!(resampled[0].close_time, klines[4].close_time);
+        assert_eq!((resampled[0].open, resampled[0].close), (100.0, 104.0));
+        assert_eq!(resampled[0].volume, 5.0);
+
+        let summaries = BackTest::run_multi(
+            strategy,
+            market,
+            Some(10_000.0),
+            vec![Interval::Min1, Interval::Min5],
+            kline_data.clone(),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(summaries.len(), 2);
+        let min1 = &summaries[&Interval::Min1];
+        let min5 = &summaries[&Interval::Min5];
+        assert_eq!(min1.info.interval, Interval::Min1);
+        assert_eq!(min5.info.interval, Interval::Min5);
+        assert_eq!(
+            min5.info.start_time,
+            Some(timestamp_to_string(resampled[0].close_time))
+        );
+        assert_eq!(
+            min1.info.start_time,
+            Some(timestamp_to_string(klines[0].close_time))
+        );
+        assert_eq!(min5.end_price, 119.0);
+
+        // shorter intervals can't be built from the base interval
+        assert!(kline_data
+            .resample(Interval::Min5)
+            .unwrap()
+            .resample(Interval::Min1)
+            .is_none());
     }
 }
@@ -0,0 +1,83 @@
+use std::collections::VecDeque;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{market::kline::Kline, strategy::types::AlgoEvalResult, utils::time::generate_ts};
+
+/// Maximum number of evaluations a strategy evaluation log can hold.
+pub const MAX_EVAL_LOG_SIZE: usize = 1000;
+
+/// A single evaluation of a kline by a strategy algorithm.
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct EvalLogEntry {
+    /// Time the evaluation was made at.
+    pub timestamp: u64,
+    pub kline: Kline,
+    pub result: AlgoEvalResult,
+    /// Indicator values reported by the algorithm after the evaluation, if it reports any.
+    pub debug_fields: Option<Value>,
+}
+
+/// Ring buffer of the latest evaluations of a strategy, used to debug why it did or didn't trade.
+///
+/// A log with a capacity of `0` is disabled and records nothing.
+
+#[derive(Debug, Clone, Default)]
+pub struct EvalLog {
+    entries: VecDeque<EvalLogEntry>,
+    capacity: usize,
+}
+
+impl EvalLog {
+    /// Creates an empty evaluation log.
+    ///
+    /// # Arguments
+    ///
+    /// * `capacity` - Number of evaluations to keep, capped at `MAX_EVAL_LOG_SIZE`.
+    ///
+    /// # Returns
+    ///
+    /// A new `EvalLog`.
+
+    pub fn new(capacity: usize) -> Self {
+        let capacity = capacity.min(MAX_EVAL_LOG_SIZE);
+
+        Self {
+            entries: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    /// Records an evaluation, dropping the oldest evaluation if the log is full.
+    ///
+    /// # Arguments
+    ///
+    /// * `kline` - The evaluated kline.
+    /// * `result` - The result of the evaluation.
+    /// * `debug_fields` - Indicator values reported by the algorithm.
+
+    pub fn record(&mut self, kline: &Kline, result: AlgoEvalResult, debug_fields: Option<Value>) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        if self.entries.len() == self.capacity {
+            self.entries.pop_front();
+        }
+
+        self.entries.push_back(EvalLogEntry {
+            timestamp: generate_ts(),
+            kline: kline.clone(),
+            result,
+            debug_fields,
+        });
+    }
+
+    /// Lists the recorded evaluations, oldest first.
+
+    pub fn entries(&self) -> Vec<EvalLogEntry> {
+        self.entries.iter().cloned().collect()
+    }
+}
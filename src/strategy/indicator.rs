@@ -0,0 +1,227 @@
+use std::collections::{BTreeMap, VecDeque};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{market::kline::Kline, utils::kline::calc_atr};
+
+/// An indicator an algorithm declares, computed by the strategy from each closed kline.
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Indicator {
+    /// Simple moving average of the close over a period.
+    Sma(usize),
+    /// Relative strength index of the close over a period.
+    Rsi(usize),
+    /// Average true range over a period.
+    Atr(usize),
+    /// Population standard deviation of the close over a period, ie. the width of Bollinger
+    /// bands.
+    StdDev(usize),
+}
+
+impl Indicator {
+    /// Number of klines the indicator needs before it has a value.
+
+    pub fn warmup(&self) -> usize {
+        match self {
+            Indicator::Sma(period) | Indicator::StdDev(period) => *period,
+            // each change needs the close before it
+            Indicator::Rsi(period) | Indicator::Atr(period) => period + 1,
+        }
+    }
+
+    /// Calculates the indicator at the last of a series of klines.
+    ///
+    /// # Arguments
+    ///
+    /// * `klines` - The klines the indicator is calculated from, oldest first.
+    ///
+    /// # Returns
+    ///
+    /// The value of the indicator, or `None` if there are fewer klines than its warmup.
+
+    pub fn calculate(&self, klines: &[Kline]) -> Option<f64> {
+        if klines.len() < self.warmup() || self.warmup() == 0 {
+            return None;
+        }
+
+        match *self {
+            Indicator::Sma(period) => {
+                let sum: f64 = klines.iter().rev().take(period).map(|k| k.close).sum();
+                Some(sum / period as f64)
+            }
+            Indicator::Rsi(period) => {
+                let (gains, losses) = klines[klines.len() - period - 1..].windows(2).fold(
+                    (0.0, 0.0),
+                    |(gains, losses), pair| {
+                        let delta = pair[1].close - pair[0].close;
+                        if delta > 0.0 {
+                            (gains + delta, losses)
+                        } else {
+                            (gains, losses - delta)
+                        }
+                    },
+                );
+
+                if losses == 0.0 {
+                    return Some(100.0);
+                }
+
+                Some(100.0 - 100.0 / (1.0 + gains / losses))
+            }
+            Indicator::Atr(period) => calc_atr(klines, period),
+            Indicator::StdDev(period) => {
+                let closes = klines.iter().rev().take(period).map(|k| k.close);
+                let mean = closes.clone().sum::<f64>() / period as f64;
+                let variance =
+                    closes.map(|close| (close - mean).powi(2)).sum::<f64>() / period as f64;
+                Some(variance.sqrt())
+            }
+        }
+    }
+}
+
+/// Indicator values passed to an algorithm with each kline it evaluates.
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AlgoContext {
+    indicators: BTreeMap<Indicator, f64>,
+}
+
+impl AlgoContext {
+    /// Value of an indicator at the evaluated kline, `None` if the indicator isn't declared by
+    /// the algorithm or isn't warmed up yet.
+
+    pub fn indicator(&self, indicator: Indicator) -> Option<f64> {
+        self.indicators.get(&indicator).copied()
+    }
+}
+
+/// Indicators declared by an algorithm, updated by the strategy with each closed kline.
+///
+/// Only the klines needed by the longest indicator are kept.
+
+#[derive(Debug, Clone, Default)]
+pub struct IndicatorSet {
+    indicators: Vec<Indicator>,
+    klines: VecDeque<Kline>,
+    context: AlgoContext,
+}
+
+impl IndicatorSet {
+    /// Creates an indicator set without klines.
+    ///
+    /// # Arguments
+    ///
+    /// * `indicators` - The indicators to calculate.
+    ///
+    /// # Returns
+    ///
+    /// A new `IndicatorSet`.
+
+    pub fn new(indicators: Vec<Indicator>) -> Self {
+        Self {
+            indicators,
+            ..Default::default()
+        }
+    }
+
+    /// Number of klines needed before every indicator of the set has a value.
+
+    pub fn warmup(indicators: &[Indicator]) -> usize {
+        indicators
+            .iter()
+            .map(Indicator::warmup)
+            .max()
+            .unwrap_or_default()
+    }
+
+    /// Replaces the indicators of the set, ie. once the algorithm parameters change. The klines
+    /// already added seed the new indicators.
+
+    pub fn set_indicators(&mut self, indicators: Vec<Indicator>) {
+        self.indicators = indicators;
+    }
+
+    /// Adds a closed kline and recalculates every indicator.
+    ///
+    /// # Arguments
+    ///
+    /// * `kline` - The closed kline.
+    ///
+    /// # Returns
+    ///
+    /// The `AlgoContext` with the indicator values at the kline.
+
+    pub fn update(&mut self, kline: &Kline) -> &AlgoContext {
+        self.klines.push_back(kline.clone());
+
+        let max_len = Self::warmup(&self.indicators);
+        while self.klines.len() > max_len {
+            self.klines.pop_front();
+        }
+
+        let klines = self.klines.make_contiguous();
+        self.context.indicators = self
+            .indicators
+            .iter()
+            .filter_map(|indicator| Some((*indicator, indicator.calculate(klines)?)))
+            .collect();
+
+        &self.context
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use tokio::test;
+
+    use crate::{market::interval::Interval, testutil::make_klines};
+
+    #[test]
+    async fn test_indicator_set_updates_per_kline() {
+        let klines = make_klines(
+            "BTCUSDT",
+            Interval::Min1,
+            1_700_000_000_000,
+            &[10.0, 11.0, 12.0, 11.0, 13.0],
+        );
+        let sma = Indicator::Sma(2);
+        let rsi = Indicator::Rsi(3);
+        let mut indicators = IndicatorSet::new(vec![sma, rsi]);
+        assert_eq!(IndicatorSet::warmup(&[sma, rsi]), 4);
+
+        let values: Vec<(Option<f64>, Option<f64>)> = klines
+            .iter()
+            .map(|kline| {
+                let ctx = indicators.update(kline);
+                (ctx.indicator(sma), ctx.indicator(rsi))
+            })
+            .collect();
+
+        assert_eq!(
+            values,
+            vec![
+                (None, None),
+                (Some(10.5), None),
+                (Some(11.5), None),
+                // gains of 1 and 1 against a loss of 1
+                (Some(11.5), Some(100.0 - 100.0 / 3.0)),
+                // gains of 1 and 2 against a loss of 1
+                (Some(12.0), Some(75.0)),
+            ]
+        );
+
+        // undeclared indicators have no value
+        assert_eq!(
+            indicators.update(&klines[4]).indicator(Indicator::Atr(2)),
+            None
+        );
+
+        // closes of 11 and 13 are 1 from their mean
+        assert_eq!(Indicator::StdDev(2).calculate(&klines), Some(1.0));
+        assert_eq!(Indicator::StdDev(6).calculate(&klines), None);
+    }
+}
@@ -1,5 +1,8 @@
 pub mod algorithm;
 pub mod backer;
+pub mod eval_log;
+pub mod indicator;
+pub mod report;
 pub mod signal;
 pub mod strategy;
 pub mod types;
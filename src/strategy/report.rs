@@ -0,0 +1,281 @@
+use std::fmt::Write;
+
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    account::trade::TradeTx,
+    strategy::strategy::{DrawdownPoint, Strategy, StrategyInfo, StrategySummary},
+    utils::number::{from_decimal, to_decimal},
+};
+
+/// Stylesheet linked by HTML reports, served from the `static/` directory.
+const REPORT_STYLESHEET: &str = "/static/report.css";
+
+/// Width and height of the equity curve chart of HTML reports, in pixels.
+const CHART_WIDTH: f64 = 800.0;
+const CHART_HEIGHT: f64 = 240.0;
+
+/// Format a strategy report is rendered in.
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ReportFormat {
+    #[default]
+    Html,
+    Json,
+}
+
+/// Shareable report of a strategy run, built from its summary.
+///
+/// The equity curve is the drawdown timeline of the trades, each point holds the cumulative
+/// profit after a trade along with its drawdown from the running peak.
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct StrategyReport {
+    pub info: StrategyInfo,
+    pub profit: f64,
+    pub max_drawdown: f64,
+    pub max_profit: f64,
+    pub trade_count: usize,
+    pub winning_trades: usize,
+    pub losing_trades: usize,
+    /// Share of trades closed in profit, in percent.
+    pub win_rate: f64,
+    pub strategy_return: f64,
+    pub buy_and_hold_return: f64,
+    pub alpha: f64,
+    pub equity_curve: Vec<DrawdownPoint>,
+    pub trades: Vec<TradeTx>,
+}
+
+impl StrategyReport {
+    /// Builds the report of a strategy summary.
+    ///
+    /// # Arguments
+    ///
+    /// * `summary` - The summary of the strategy run.
+    ///
+    /// # Returns
+    ///
+    /// The `StrategyReport` of the run.
+
+    pub fn new(summary: StrategySummary) -> Self {
+        let equity_curve = Strategy::calc_drawdown_timeline(&summary.trades);
+        let trade_count = summary.trades.len();
+        let winning_trades = summary
+            .trades
+            .iter()
+            .filter(|tx| tx.profit > Decimal::ZERO)
+            .count();
+        let losing_trades = summary
+            .trades
+            .iter()
+            .filter(|tx| tx.profit < Decimal::ZERO)
+            .count();
+
+        let win_rate = if trade_count > 0 {
+            from_decimal(
+                to_decimal(winning_trades as f64) / to_decimal(trade_count as f64)
+                    * to_decimal(100.0),
+            )
+        } else {
+            0.0
+        };
+
+        Self {
+            info: summary.info,
+            profit: summary.profit,
+            // saved summaries may predate the peak-to-trough calculation
+            max_drawdown: Strategy::calc_max_drawdown(&summary.trades),
+            max_profit: summary.max_profit,
+            trade_count,
+            winning_trades,
+            losing_trades,
+            win_rate,
+            strategy_return: summary.strategy_return,
+            buy_and_hold_return: summary.buy_and_hold_return,
+            alpha: summary.alpha,
+            equity_curve,
+            trades: summary.trades,
+        }
+    }
+
+    /// Renders the report as a static HTML page, styled by the stylesheet served from `static/`.
+
+    pub fn to_html(&self) -> String {
+        let title = format!(
+            "{} {} {}",
+            escape_html(&self.info.name),
+            escape_html(&self.info.symbol),
+            self.info.interval
+        );
+
+        let mut html = String::new();
+
+        // SAFETY: writing to a String can't fail
+        let _ = write!(
+            html,
+            r#"<!DOCTYPE html>
+<html lang="en">
+  <head>
+    <meta charset="utf-8" />
+    <title>Strategy report - {title}</title>
+    <link rel="stylesheet" href="{REPORT_STYLESHEET}" />
+  </head>
+  <body>
+    <h1>{title}</h1>
+    <p>Strategy {id}, {start} to {end}</p>
+    <table class="metrics">
+      <tr><th>Profit</th><td>{profit:.2}</td></tr>
+      <tr><th>Max drawdown</th><td>{max_drawdown:.2}</td></tr>
+      <tr><th>Max profit</th><td>{max_profit:.2}</td></tr>
+      <tr><th>Trades</th><td>{trade_count}</td></tr>
+      <tr><th>Win rate</th><td>{win_rate:.2}%</td></tr>
+      <tr><th>Strategy return</th><td>{strategy_return:.2}%</td></tr>
+      <tr><th>Buy and hold return</th><td>{buy_and_hold_return:.2}%</td></tr>
+      <tr><th>Alpha</th><td>{alpha:.2}</td></tr>
+    </table>
+    <h2>Equity curve</h2>
+    {chart}
+    <h2>Trades</h2>
+    <table class="trades">
+      <tr><th>Close time</th><th>Side</th><th>Open price</th><th>Close price</th><th>Profit</th><th>Balance</th><th>Drawdown</th></tr>
+"#,
+            id = self.info.id,
+            start = escape_html(self.info.start_time.as_deref().unwrap_or("-")),
+            end = escape_html(self.info.end_time.as_deref().unwrap_or("-")),
+            profit = self.profit,
+            max_drawdown = self.max_drawdown,
+            max_profit = self.max_profit,
+            trade_count = self.trade_count,
+            win_rate = self.win_rate,
+            strategy_return = self.strategy_return,
+            buy_and_hold_return = self.buy_and_hold_return,
+            alpha = self.alpha,
+            chart = self.equity_chart(),
+        );
+
+        for point in &self.equity_curve {
+            let _ = writeln!(
+                html,
+                "      <tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{:.2}</td><td>{:.2}</td><td>{:.2}</td></tr>",
+                escape_html(&point.close_time),
+                point.order_side,
+                point.open_price,
+                point.close_price,
+                point.profit,
+                point.balance,
+                point.drawdown
+            );
+        }
+
+        html.push_str("    </table>\n  </body>\n</html>\n");
+        html
+    }
+
+    // ---
+    // Private Methods
+    // ---
+
+    /// Renders the equity curve as an inline SVG line chart starting from a balance of zero.
+    fn equity_chart(&self) -> String {
+        if self.equity_curve.is_empty() {
+            return "<p>No trades</p>".to_string();
+        }
+
+        let balances: Vec<f64> = std::iter::once(0.0)
+            .chain(self.equity_curve.iter().map(|point| point.balance))
+            .collect();
+
+        let min = balances.iter().cloned().fold(f64::INFINITY, f64::min);
+        let max = balances.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+        let range = if max > min { max - min } else { 1.0 };
+        let step = CHART_WIDTH / (balances.len() - 1) as f64;
+
+        let points: Vec<String> = balances
+            .iter()
+            .enumerate()
+            .map(|(i, balance)| {
+                let x = i as f64 * step;
+                let y = CHART_HEIGHT - (balance - min) / range * CHART_HEIGHT;
+                format!("{x:.1},{y:.1}")
+            })
+            .collect();
+
+        format!(
+            r#"<svg class="equity-curve" viewBox="0 0 {CHART_WIDTH} {CHART_HEIGHT}"><polyline points="{}" /></svg>"#,
+            points.join(" ")
+        )
+    }
+}
+
+/// Escapes text included in an HTML report.
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use tokio::test;
+
+    use crate::account::trade::{OrderSide, Position};
+
+    #[test]
+    async fn test_html_report_contains_metrics() {
+        let trades: Vec<TradeTx> = [(100.0, 110.0), (100.0, 95.0), (100.0, 120.0)]
+            .iter()
+            .enumerate()
+            .map(|(i, &(open, close))| {
+                let position = Position::new("BTCUSDT", open, OrderSide::Buy, 100.0, 1, None);
+                let mut trade =
+                    TradeTx::new(close, 1_700_000_000_000 + i as u64 * 60_000, position);
+                trade.profit = to_decimal(close - open);
+                trade
+            })
+            .collect();
+
+        let mut summary = StrategySummary {
+            info: StrategyInfo {
+                name: "<SMA>".to_string(),
+                symbol: "BTCUSDT".to_string(),
+                ..Default::default()
+            },
+            profit: 25.0,
+            max_profit: 20.0,
+            trades,
+            start_price: 100.0,
+            end_price: 110.0,
+            ..Default::default()
+        };
+        summary.calc_returns();
+
+        let report = StrategyReport::new(summary);
+        assert_eq!(report.trade_count, 3);
+        assert_eq!(report.winning_trades, 2);
+        assert_eq!(report.losing_trades, 1);
+        assert!((report.win_rate - 200.0 / 3.0).abs() < 1e-9);
+        assert_eq!(report.max_drawdown, 5.0);
+        assert_eq!(report.equity_curve.last().unwrap().balance, 25.0);
+
+        let html = report.to_html();
+        assert!(html.contains(REPORT_STYLESHEET));
+        assert!(html.contains("<tr><th>Profit</th><td>25.00</td></tr>"));
+        assert!(html.contains("<tr><th>Max drawdown</th><td>5.00</td></tr>"));
+        assert!(html.contains("<tr><th>Trades</th><td>3</td></tr>"));
+        assert!(html.contains("<tr><th>Win rate</th><td>66.67%</td></tr>"));
+        assert!(html.contains("<tr><th>Strategy return</th><td>25.00%</td></tr>"));
+        assert!(html.contains("<tr><th>Buy and hold return</th><td>10.00%</td></tr>"));
+        assert!(html.contains("<polyline"));
+        assert_eq!(html.matches("<td>Buy</td>").count(), 3);
+
+        // user provided names are escaped
+        assert!(html.contains("&lt;SMA&gt;"));
+        assert!(!html.contains("<SMA>"));
+    }
+}
@@ -1,17 +1,27 @@
 use serde::{Deserialize, Serialize};
-use std::{collections::HashMap, marker};
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap, VecDeque},
+    fmt,
+    hash::{Hash, Hasher},
+    marker,
+    sync::Arc,
+    time::Duration,
+};
 
-use log::info;
+use log::{info, warn};
+use tokio::time;
 
 use crate::{
     account::{
-        account::Account,
-        trade::{OrderSide, Position},
+        account::{Account, AccountId, OrderError, DEFAULT_ACCOUNT_ID},
+        trade::{OrderSide, Position, PositionId},
     },
-    market::{market::Market, types::ArcMutex},
+    market::{interval::Interval, market::Market, types::ArcMutex},
+    notify::webhook::{HttpWebhookClient, WebhookClient},
+    utils::time::{generate_ts, string_to_timestamp, timestamp_to_string},
 };
 
-use super::strategy::{StrategyId, StrategySettings};
+use super::strategy::{StopLoss, StrategyId, StrategySettings};
 
 /// Manages the handling of trading signals for active trading strategies.
 ///
@@ -21,8 +31,22 @@ use super::strategy::{StrategyId, StrategySettings};
 
 pub struct SignalHandler {
     active_strategy_settings: HashMap<StrategyId, StrategySettings>,
+    last_accepted_signals: HashMap<StrategyId, AcceptedSignal>,
+    /// Signals which failed after all attempts, oldest first, at most `MAX_FAILED_SIGNALS`.
+    failed_signals: VecDeque<FailedSignal>,
+    /// Client used by webhook sinks to post signals.
+    webhook_client: Arc<dyn WebhookClient>,
 }
 
+/// Number of times a signal is attempted before it is moved to the failed signals list.
+const MAX_SIGNAL_ATTEMPTS: u32 = 3;
+
+/// Base delay between signal attempts, multiplied by the attempt number.
+const SIGNAL_RETRY_DELAY_MILI: u64 = 500;
+
+/// Number of failed signals kept, the oldest are dropped first.
+const MAX_FAILED_SIGNALS: usize = 1000;
+
 impl SignalHandler {
     /// Initializes a new `SignalHandler` with references to the account and market.
     ///
@@ -38,6 +62,9 @@ impl SignalHandler {
     pub fn new() -> Self {
         Self {
             active_strategy_settings: HashMap::new(),
+            last_accepted_signals: HashMap::new(),
+            failed_signals: VecDeque::new(),
+            webhook_client: Arc::new(HttpWebhookClient::default()),
         }
     }
 
@@ -48,128 +75,124 @@ impl SignalHandler {
     /// * `signal` - The trading signal to process.
     ///
     /// This method considers the current active positions, the strategy settings, and the nature of the signal
-    /// to decide on the appropriate trading action.
+    /// to decide on the appropriate trading action. Failed account actions are retried up to
+    /// `MAX_SIGNAL_ATTEMPTS` times, signals which still fail are moved to the failed signals list.
+    ///
+    /// # Returns
+    ///
+    /// `Ok` if the signal was executed or ignored, or the last `SignalError` if every attempt failed.
 
     pub async fn handle_signal(
-        &self,
+        &mut self,
         signal: SignalMessage,
         market: ArcMutex<Market>,
         account: ArcMutex<Account>,
-    ) {
-        let account = account.clone();
-        let market = market.clone();
-        let active_positions: Vec<Position> = account
-            .lock()
-            .await
-            .strategy_positions(signal.strategy_id)
-            .iter()
-            .map(|&el| el.clone())
-            .collect();
-
-        // get trigger price used in all account actions
-        // from market or signal if signal.is_back_test
-        let trigger_price = if signal.is_back_test {
-            Some(signal.price)
-        } else {
-            market.lock().await.last_price(&signal.symbol).await
+    ) -> Result<(), SignalError> {
+        // signals for unknown strategies are ignored, ie. strategy was stopped
+        // before the signal was handled
+        let Some(mut dispatch) = self.prepare_signal(signal, market.clone()).await else {
+            return Ok(());
         };
 
-        if self
-            .active_strategy_settings
-            .get(&signal.strategy_id)
-            .is_none()
-        {
-            return;
-        }
+        let result = dispatch.execute(market, account).await;
+        self.record_failed_signals(dispatch);
 
-        // SAFETY: None check above, used to make method more clear
-        let settings = self
-            .active_strategy_settings
-            .get(&signal.strategy_id)
-            .unwrap();
+        result
+    }
 
-        // get last open position
-        if let Some(last) = active_positions.last() {
-            // if last.signal is different to new signal then close all positions
-            if signal.order_side != last.order_side {
-                if let Some(close_price) = trigger_price {
-                    for position in &active_positions {
-                        let mut account = account.lock().await;
+    /// Routes a trading signal to every sink of the emitting strategy.
+    ///
+    /// # Arguments
+    ///
+    /// * `signal` - The trading signal to process.
+    /// * `market` - Market used to get the trigger price.
+    /// * `accounts` - All accounts managed by the bot, keyed by account ID.
+    ///
+    /// Strategies without sinks in their settings trade on their account, `DEFAULT_ACCOUNT_ID`
+    /// if the settings don't name one. A sink failing doesn't stop the signal reaching the
+    /// other sinks. Webhooks are posted to in the background, only a signal which can't be
+    /// serialized fails a webhook sink.
+    ///
+    /// # Returns
+    ///
+    /// `Ok` if every sink handled the signal, otherwise the error of the first sink which failed,
+    /// ie. `SignalError::UnknownAccount` if an account sink names an account which is not managed.
 
-                        let tx = account.close_position(position.id, close_price).await;
+    pub async fn route_signal(
+        &mut self,
+        signal: SignalMessage,
+        market: ArcMutex<Market>,
+        accounts: &HashMap<AccountId, ArcMutex<Account>>,
+    ) -> Result<(), SignalError> {
+        let Some(mut dispatch) = self.prepare_signal(signal, market.clone()).await else {
+            return Ok(());
+        };
 
-                        let position_id = if let Some(tx) = tx {
-                            Some(tx.position.id)
-                        } else {
-                            None
-                        };
+        let result = dispatch.route(market, accounts).await;
+        self.record_failed_signals(dispatch);
 
-                        if let Some(id) = position_id {
-                            account.add_position_meta(id, &signal)
-                        }
-                    }
-                }
+        result
+    }
 
-            // if is same signal as last position and settings allow more than one
-            // open position
-            } else if active_positions.len() < settings.max_open_orders as usize {
-                if let Some(close_price) = trigger_price {
-                    let mut account = account.lock().await;
+    /// Prepares a signal to be executed, without executing it.
+    ///
+    /// The returned dispatch is executed without borrowing the handler, so callers holding the
+    /// handler behind a lock can release it while failed attempts wait to be retried.
+    ///
+    /// # Arguments
+    ///
+    /// * `signal` - The trading signal to process.
+    /// * `market` - Market used to get the trigger price the signal is debounced at.
+    ///
+    /// # Returns
+    ///
+    /// The dispatch of the signal, or `None` if the strategy isn't active or the signal is
+    /// debounced.
 
-                    let position = account
-                        .open_position(
-                            &signal.symbol,
-                            settings.margin_usd,
-                            settings.leverage,
-                            signal.order_side.clone(),
-                            close_price,
-                            Some(signal.strategy_id),
-                            None,
-                        )
-                        .await;
-
-                    let position_id = if let Some(position) = position {
-                        Some(position.id)
-                    } else {
-                        None
-                    };
-
-                    if let Some(id) = position_id {
-                        account.add_position_meta(id, &signal)
-                    }
-                }
-            }
+    pub async fn prepare_signal(
+        &mut self,
+        signal: SignalMessage,
+        market: ArcMutex<Market>,
+    ) -> Option<SignalDispatch> {
+        let settings = self
+            .active_strategy_settings
+            .get(&signal.strategy_id)?
+            .clone();
 
-        // no open positions yet for given strategy
-        } else {
-            if let Some(last_price) = trigger_price {
-                let mut account = account.lock().await;
+        if self.debounce(&signal, &settings, market).await {
+            return None;
+        }
 
-                let position = account
-                    .open_position(
-                        &signal.symbol,
-                        settings.margin_usd,
-                        settings.leverage,
-                        signal.order_side.clone(),
-                        last_price,
-                        Some(signal.strategy_id),
-                        None,
-                    )
-                    .await;
+        Some(SignalDispatch {
+            signal,
+            settings,
+            webhook_client: self.webhook_client.clone(),
+            failed_signals: vec![],
+        })
+    }
 
-                let position_id = if let Some(position) = position {
-                    Some(position.id)
-                } else {
-                    None
-                };
+    /// Records the signals of an executed dispatch which failed after all attempts, dropping the
+    /// oldest failed signals past `MAX_FAILED_SIGNALS`.
 
-                if let Some(id) = position_id {
-                    account.add_position_meta(id, &signal)
-                }
+    pub fn record_failed_signals(&mut self, dispatch: SignalDispatch) {
+        for failed_signal in dispatch.failed_signals {
+            if self.failed_signals.len() >= MAX_FAILED_SIGNALS {
+                self.failed_signals.pop_front();
             }
+            self.failed_signals.push_back(failed_signal);
         }
     }
 
+    /// Returns the signals which failed after all retry attempts.
+    ///
+    /// # Returns
+    ///
+    /// A list of failed signals in the order they failed.
+
+    pub fn failed_signals(&self) -> Vec<FailedSignal> {
+        self.failed_signals.iter().cloned().collect()
+    }
+
     /// Adds settings for a trading strategy to the manager.
     ///
     /// # Arguments
@@ -194,9 +217,513 @@ impl SignalHandler {
 
     pub fn remove_strategy_settings(&mut self, strategy_id: &StrategyId) {
         self.active_strategy_settings.remove(&strategy_id);
+        self.last_accepted_signals.remove(&strategy_id);
+    }
+
+    // ---
+    // Private Methods
+    // ---
+
+    /// Checks whether a signal is debounced at its trigger price, logging ignored signals.
+    async fn debounce(
+        &mut self,
+        signal: &SignalMessage,
+        settings: &StrategySettings,
+        market: ArcMutex<Market>,
+    ) -> bool {
+        let trigger_price = trigger_price(signal, market).await;
+
+        if self.is_debounced(signal, trigger_price, settings) {
+            info!(
+                "Ignoring debounced {} signal for strategy {}",
+                signal.order_side, signal.strategy_id
+            );
+            return true;
+        }
+
+        false
+    }
+
+    /// Checks a standard signal against the strategy's debounce settings.
+    ///
+    /// A signal on the same side as the last accepted signal is ignored while within
+    /// `signal_cooldown_ms`, a signal on the opposite side is ignored until price has moved at
+    /// least `min_flip_move_pct` away from the last accepted signal price. Accepted signals are
+    /// recorded as the new reference for the strategy.
+    ///
+    /// # Arguments
+    ///
+    /// * `signal` - The incoming signal.
+    /// * `trigger_price` - The price the signal would be executed at.
+    /// * `settings` - The settings of the strategy which emitted the signal.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the signal must be ignored.
+
+    fn is_debounced(
+        &mut self,
+        signal: &SignalMessage,
+        trigger_price: Option<f64>,
+        settings: &StrategySettings,
+    ) -> bool {
+        // only algorithm signals are debounced, forced closes and stop losses always pass
+        if !matches!(signal.ty, SignalMessageType::Standard) {
+            return false;
+        }
+
+        let timestamp = string_to_timestamp(&signal.close_time).unwrap_or_else(|_| generate_ts());
+        let price = trigger_price.unwrap_or(signal.price);
+
+        if let Some(last) = self.last_accepted_signals.get(&signal.strategy_id) {
+            if last.order_side == signal.order_side {
+                if timestamp.saturating_sub(last.timestamp) < settings.signal_cooldown_ms {
+                    return true;
+                }
+            } else if settings.min_flip_move_pct > 0.0 && last.price > 0.0 {
+                let move_pct = ((price - last.price) / last.price).abs() * 100.0;
+                if move_pct < settings.min_flip_move_pct {
+                    return true;
+                }
+            }
+        }
+
+        self.last_accepted_signals.insert(
+            signal.strategy_id,
+            AcceptedSignal {
+                order_side: signal.order_side,
+                timestamp,
+                price,
+            },
+        );
+
+        false
+    }
+}
+
+/// A signal prepared by the `SignalHandler`, executed without borrowing the handler so failed
+/// attempts can wait to be retried while the handler is free.
+
+pub struct SignalDispatch {
+    signal: SignalMessage,
+    settings: StrategySettings,
+    webhook_client: Arc<dyn WebhookClient>,
+    /// Sinks of the signal which failed after all attempts, recorded on the handler by
+    /// `SignalHandler::record_failed_signals`.
+    failed_signals: Vec<FailedSignal>,
+}
+
+impl SignalDispatch {
+    /// Executes the signal on an account, retrying attempts which failed with a transient error up
+    /// to `MAX_SIGNAL_ATTEMPTS` times.
+    ///
+    /// # Arguments
+    ///
+    /// * `market` - Market used to get the trigger price.
+    /// * `account` - Account on which positions are opened and closed.
+    ///
+    /// # Returns
+    ///
+    /// `Ok` if the signal was executed or ignored, or the last `SignalError` if it failed.
+
+    pub async fn execute(
+        &mut self,
+        market: ArcMutex<Market>,
+        account: ArcMutex<Account>,
+    ) -> Result<(), SignalError> {
+        let mut attempts = 0;
+
+        loop {
+            attempts += 1;
+
+            let result = self.execute_signal(market.clone(), account.clone()).await;
+
+            match result {
+                Ok(()) => return Ok(()),
+                Err(e) if e.is_transient() && attempts < MAX_SIGNAL_ATTEMPTS => {
+                    warn!(
+                        "Signal for strategy {} failed on attempt {attempts}, retrying: {e}",
+                        self.signal.strategy_id
+                    );
+                    time::sleep(Duration::from_millis(
+                        SIGNAL_RETRY_DELAY_MILI * attempts as u64,
+                    ))
+                    .await;
+                }
+                Err(e) => {
+                    warn!(
+                        "Signal for strategy {} failed after {attempts} attempts: {e}",
+                        self.signal.strategy_id
+                    );
+                    self.failed_signals.push(FailedSignal {
+                        signal: self.signal.clone(),
+                        error: e.to_string(),
+                        attempts,
+                        failed_at: timestamp_to_string(generate_ts()),
+                    });
+                    return Err(e);
+                }
+            }
+        }
+    }
+
+    /// Routes the signal to every sink of the emitting strategy, see
+    /// `SignalHandler::route_signal`.
+
+    pub async fn route(
+        &mut self,
+        market: ArcMutex<Market>,
+        accounts: &HashMap<AccountId, ArcMutex<Account>>,
+    ) -> Result<(), SignalError> {
+        let mut result = Ok(());
+
+        for sink in self.settings.signal_sinks() {
+            let sink_result = match sink {
+                SignalSink::Account { account_id } => {
+                    let account_id = account_id.unwrap_or_else(|| DEFAULT_ACCOUNT_ID.to_string());
+
+                    match accounts.get(&account_id) {
+                        Some(account) => self.execute(market.clone(), account.clone()).await,
+                        None => {
+                            warn!(
+                                "Signal for strategy {} routed to unknown account {account_id}",
+                                self.signal.strategy_id
+                            );
+                            Err(SignalError::UnknownAccount(account_id))
+                        }
+                    }
+                }
+                SignalSink::Webhook { url } => match serde_json::to_value(&self.signal) {
+                    Ok(payload) => {
+                        // delivered in the background so a slow webhook doesn't hold up the
+                        // other sinks, failed deliveries are logged
+                        let webhook_client = self.webhook_client.clone();
+                        let strategy_id = self.signal.strategy_id;
+                        tokio::spawn(async move {
+                            if let Err(e) = webhook_client.post(&url, &payload).await {
+                                warn!(
+                                    "Unable to post signal for strategy {strategy_id} to webhook {url}, {e}"
+                                );
+                            }
+                        });
+                        Ok(())
+                    }
+                    Err(e) => {
+                        warn!(
+                            "Unable to serialize signal for strategy {} to webhook {url}, {e}",
+                            self.signal.strategy_id
+                        );
+                        Err(SignalError::WebhookFailed(url, e.to_string()))
+                    }
+                },
+                SignalSink::Log => {
+                    info!(
+                        "{} signal for strategy {} on {} at {}",
+                        self.signal.order_side,
+                        self.signal.strategy_id,
+                        self.signal.symbol,
+                        self.signal.price
+                    );
+                    Ok(())
+                }
+            };
+
+            if result.is_ok() {
+                result = sink_result;
+            }
+        }
+
+        result
+    }
+
+    /// Executes a single attempt of the signal against the account.
+    ///
+    /// Active positions are read on each attempt, so positions closed by a previous partially
+    /// failed attempt are not closed twice. New positions are ordered with the client order ID of
+    /// the signal, so an order which reached the exchange before an attempt failed isn't placed
+    /// again.
+    ///
+    /// # Arguments
+    ///
+    /// * `market` - Market used to get the trigger price.
+    /// * `account` - Account on which positions are opened and closed.
+    ///
+    /// # Returns
+    ///
+    /// `Ok` if all account actions succeeded, otherwise the first `SignalError` encountered.
+
+    async fn execute_signal(
+        &self,
+        market: ArcMutex<Market>,
+        account: ArcMutex<Account>,
+    ) -> Result<(), SignalError> {
+        let signal = &self.signal;
+        let settings = &self.settings;
+
+        let active_positions: Vec<Position> = account
+            .lock()
+            .await
+            .strategy_positions(signal.strategy_id)
+            .iter()
+            .map(|&el| el.clone())
+            .collect();
+
+        // if last position is on the opposite side of the signal then close all positions
+        if let Some(last) = active_positions.last() {
+            if signal.order_side != last.order_side {
+                let close_price = trigger_price(signal, market)
+                    .await
+                    .ok_or_else(|| SignalError::NoTriggerPrice(signal.symbol.clone()))?;
+
+                for position in &active_positions {
+                    let mut account = account.lock().await;
+
+                    match account.try_close_position(position.id, close_price).await {
+                        Ok(tx) => {
+                            let position_id = tx.position.id;
+                            account.add_position_meta(position_id, signal)
+                        }
+                        Err(e) => return Err(SignalError::ClosePositionFailed(position.id, e)),
+                    }
+                }
+
+                return Ok(());
+            }
+
+            // same side as last position, only open if settings allow more open positions
+            if active_positions.len() >= settings.max_open_orders as usize {
+                info!(
+                    "Ignoring {} signal of strategy {}, {} of {} positions already open",
+                    signal.order_side,
+                    signal.strategy_id,
+                    active_positions.len(),
+                    settings.max_open_orders
+                );
+                return Ok(());
+            }
+        }
+
+        // size new entry from the strategy settings and current account equity
+        let (margin_usd, remaining_allocation) = {
+            let account = account.lock().await;
+            (
+                settings.entry_margin(account.equity(), account.committed_margin()),
+                settings
+                    .remaining_allocation(account.strategy_committed_margin(signal.strategy_id)),
+            )
+        };
+
+        let margin_usd = match margin_usd {
+            Some(margin_usd) => margin_usd,
+            None => {
+                info!(
+                    "No equity available to size entry for strategy {}",
+                    signal.strategy_id
+                );
+                return Ok(());
+            }
+        };
+
+        // strategies sharing an account may not exceed their own capital allocation
+        if let Some(remaining_allocation) = remaining_allocation {
+            if margin_usd > remaining_allocation {
+                info!(
+                    "Entry of {margin_usd} USD for strategy {} exceeds remaining allocation of {remaining_allocation} USD",
+                    signal.strategy_id
+                );
+                return Ok(());
+            }
+        }
+
+        let open_price = trigger_price(signal, market.clone())
+            .await
+            .ok_or_else(|| SignalError::NoTriggerPrice(signal.symbol.clone()))?;
+
+        let stop_loss = match &settings.stop_loss {
+            Some(stop_loss) => self.resolve_stop_loss(stop_loss, open_price, market).await,
+            None => None,
+        };
+
+        let mut account = account.lock().await;
+
+        let position_id = account
+            .try_open_position(
+                &signal.symbol,
+                margin_usd,
+                settings.leverage,
+                signal.order_side,
+                open_price,
+                Some(signal.strategy_id),
+                stop_loss,
+                Some(&signal.client_order_id()),
+            )
+            .await
+            .map(|position| position.id)
+            .map_err(|e| SignalError::OpenPositionFailed(signal.symbol.clone(), e))?;
+
+        account.add_position_meta(position_id, signal);
+
+        Ok(())
+    }
+
+    /// Resolves a strategy stop loss to a price for a new position.
+    ///
+    /// Stop losses based on klines use the klines leading up to the signal close time, so back
+    /// tests resolve against the same data as a live strategy would have seen.
+    ///
+    /// # Arguments
+    ///
+    /// * `stop_loss` - The stop loss from the strategy settings.
+    /// * `open_price` - The price the position is opened at.
+    /// * `market` - Market used to get recent klines.
+    ///
+    /// # Returns
+    ///
+    /// The stop loss price, or `None` if it could not be resolved.
+
+    async fn resolve_stop_loss(
+        &self,
+        stop_loss: &StopLoss,
+        open_price: f64,
+        market: ArcMutex<Market>,
+    ) -> Option<f64> {
+        let signal = &self.signal;
+        let klines_needed = stop_loss.klines_needed();
+        let klines = match (klines_needed, signal.interval) {
+            (0, _) => vec![],
+            (_, Some(interval)) => {
+                let close_time = string_to_timestamp(&signal.close_time).ok()?;
+                let from_ts =
+                    (close_time + 1).saturating_sub(interval.to_mili() * klines_needed as u64);
+
+                match market
+                    .lock()
+                    .await
+                    .kline_data_range(
+                        &signal.symbol,
+                        interval,
+                        Some(from_ts),
+                        Some(close_time),
+                        None,
+                    )
+                    .await
+                {
+                    Some(kline_data) => kline_data.klines(),
+                    None => vec![],
+                }
+            }
+            (_, None) => vec![],
+        };
+
+        let price = stop_loss.resolve(signal.order_side, open_price, &klines);
+
+        if price.is_none() {
+            warn!(
+                "Unable to resolve stop loss {:?} for strategy {}, opening without stop loss",
+                stop_loss, signal.strategy_id
+            );
+        }
+
+        price
+    }
+}
+
+/// Gets the price used for all account actions of a signal.
+///
+/// Back test signals carry their own price, live signals use the last market price.
+
+async fn trigger_price(signal: &SignalMessage, market: ArcMutex<Market>) -> Option<f64> {
+    if signal.is_back_test {
+        Some(signal.price)
+    } else {
+        let reader = market.lock().await.reader();
+        reader.last_price(&signal.symbol).await
     }
 }
 
+/// The last signal accepted for a strategy, used as reference when debouncing new signals.
+
+struct AcceptedSignal {
+    order_side: OrderSide,
+    timestamp: u64,
+    price: f64,
+}
+
+/// Destination a strategy's signals are sent to, a strategy may send each signal to many sinks.
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SignalSink {
+    /// Trades the signal on an account, `None` for the default account.
+    Account {
+        #[serde(default)]
+        account_id: Option<AccountId>,
+    },
+    /// Posts the signal as JSON to a webhook, ie. for copy trading.
+    Webhook { url: String },
+    /// Only logs the signal.
+    Log,
+}
+
+/// Errors which can occur while executing a signal against the account.
+
+#[derive(Debug, Clone)]
+pub enum SignalError {
+    NoTriggerPrice(String),
+    OpenPositionFailed(String, OrderError),
+    ClosePositionFailed(PositionId, OrderError),
+    UnknownAccount(AccountId),
+    /// The signal couldn't be posted to the webhook of the URL, with the reason.
+    WebhookFailed(String, String),
+}
+
+impl SignalError {
+    /// Whether the signal may succeed when it's retried, ie. the order failed with a network
+    /// error rather than being refused.
+
+    pub fn is_transient(&self) -> bool {
+        match self {
+            SignalError::NoTriggerPrice(_) => true,
+            SignalError::OpenPositionFailed(_, e) | SignalError::ClosePositionFailed(_, e) => {
+                e.is_transient()
+            }
+            SignalError::UnknownAccount(_) | SignalError::WebhookFailed(..) => false,
+        }
+    }
+}
+
+impl fmt::Display for SignalError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SignalError::NoTriggerPrice(symbol) => {
+                write!(f, "Unable to get trigger price for symbol: {symbol}")
+            }
+            SignalError::OpenPositionFailed(symbol, e) => {
+                write!(f, "Unable to open position for symbol: {symbol}, {e}")
+            }
+            SignalError::ClosePositionFailed(position_id, e) => {
+                write!(f, "Unable to close position: {position_id}, {e}")
+            }
+            SignalError::UnknownAccount(account_id) => {
+                write!(f, "Unknown account: {account_id}")
+            }
+            SignalError::WebhookFailed(url, reason) => {
+                write!(f, "Unable to post signal to webhook: {url}, {reason}")
+            }
+        }
+    }
+}
+
+/// A signal which could not be executed after all retry attempts.
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FailedSignal {
+    pub signal: SignalMessage,
+    pub error: String,
+    pub attempts: u32,
+    pub failed_at: String,
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub enum SignalMessageType {
     Standard,
@@ -218,7 +745,540 @@ pub struct SignalMessage {
     pub price: f64,
     pub is_back_test: bool,
     pub close_time: String,
+    /// Interval of the kline which generated the signal, `None` for signals not generated from klines.
+    #[serde(default)]
+    pub interval: Option<Interval>,
     #[serde(rename = "type")]
     pub ty: SignalMessageType,
     // pub kline: Kline,
 }
+
+impl SignalMessage {
+    /// Returns the client order ID of orders placed for the signal, the same for every attempt of
+    /// the signal so the exchange dedups an order which is retried.
+
+    pub fn client_order_id(&self) -> String {
+        let mut hasher = DefaultHasher::new();
+        self.strategy_id.hash(&mut hasher);
+        self.symbol.hash(&mut hasher);
+        self.order_side.hash(&mut hasher);
+        self.close_time.hash(&mut hasher);
+
+        format!("rb-{:016x}", hasher.finish())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::strategy::strategy::SizingMode;
+    use crate::{
+        exchange::mock::MockExchangeApi, testutil::test_market, utils::time::timestamp_to_string,
+    };
+    use std::sync::Arc;
+    use tokio::test;
+    use uuid::Uuid;
+
+    async fn setup() -> (ArcMutex<Market>, ArcMutex<Account>) {
+        let (market, account, _) = setup_with_exchange(MockExchangeApi::default()).await;
+        (market, account)
+    }
+
+    async fn setup_with_exchange(
+        exchange_api: MockExchangeApi,
+    ) -> (ArcMutex<Market>, ArcMutex<Account>, Arc<MockExchangeApi>) {
+        let exchange_api = Arc::new(exchange_api);
+
+        let market = test_market(exchange_api.clone()).await;
+        let account = Account::new(exchange_api.clone(), false, true).await;
+
+        (ArcMutex::new(market), ArcMutex::new(account), exchange_api)
+    }
+
+    fn build_signal(
+        strategy_id: StrategyId,
+        order_side: OrderSide,
+        price: f64,
+        timestamp: u64,
+    ) -> SignalMessage {
+        SignalMessage {
+            strategy_id,
+            order_side,
+            symbol: "BTCUSDT".to_string(),
+            price,
+            is_back_test: true,
+            close_time: timestamp_to_string(timestamp),
+            interval: None,
+            ty: SignalMessageType::Standard,
+        }
+    }
+
+    #[test]
+    async fn test_signal_debounce() {
+        let (market, account) = setup().await;
+        let strategy_id = Uuid::new_v4();
+
+        let settings = StrategySettings {
+            max_open_orders: 10,
+            signal_cooldown_ms: 60_000,
+            min_flip_move_pct: 1.0,
+            ..Default::default()
+        };
+
+        let mut handler = SignalHandler::new();
+        handler.add_strategy_settings(&strategy_id, settings);
+
+        let start = 1_700_000_000_000;
+
+        // first signal opens, rapid repeats within cooldown are ignored
+        for (offset, price) in [(0, 100.0), (10_000, 100.1), (20_000, 100.2)] {
+            let signal = build_signal(strategy_id, OrderSide::Buy, price, start + offset);
+            handler
+                .handle_signal(signal, market.clone(), account.clone())
+                .await
+                .unwrap();
+        }
+        assert_eq!(
+            account.lock().await.strategy_positions(strategy_id).len(),
+            1
+        );
+
+        // same side after cooldown opens another position
+        let signal = build_signal(strategy_id, OrderSide::Buy, 100.5, start + 70_000);
+        handler
+            .handle_signal(signal, market.clone(), account.clone())
+            .await
+            .unwrap();
+        assert_eq!(
+            account.lock().await.strategy_positions(strategy_id).len(),
+            2
+        );
+
+        // flip without enough price movement is ignored
+        let signal = build_signal(strategy_id, OrderSide::Sell, 100.9, start + 80_000);
+        handler
+            .handle_signal(signal, market.clone(), account.clone())
+            .await
+            .unwrap();
+        assert_eq!(
+            account.lock().await.strategy_positions(strategy_id).len(),
+            2
+        );
+        assert_eq!(account.lock().await.strategy_trades(strategy_id).len(), 0);
+
+        // flip with enough price movement closes all positions
+        let signal = build_signal(strategy_id, OrderSide::Sell, 102.0, start + 90_000);
+        handler
+            .handle_signal(signal, market.clone(), account.clone())
+            .await
+            .unwrap();
+        assert_eq!(
+            account.lock().await.strategy_positions(strategy_id).len(),
+            0
+        );
+        assert_eq!(account.lock().await.strategy_trades(strategy_id).len(), 2);
+    }
+
+    #[test]
+    async fn test_percent_equity_sizing() {
+        let (market, account) = setup().await;
+        let strategy_id = Uuid::new_v4();
+
+        account.lock().await.set_initial_balance(1000.0);
+
+        let settings = StrategySettings {
+            sizing_mode: Some(SizingMode::PercentEquity(10.0)),
+            ..Default::default()
+        };
+
+        let mut handler = SignalHandler::new();
+        handler.add_strategy_settings(&strategy_id, settings);
+
+        let start = 1_700_000_000_000;
+
+        // 10% of 1000 equity
+        let signal = build_signal(strategy_id, OrderSide::Buy, 100.0, start);
+        handler
+            .handle_signal(signal, market.clone(), account.clone())
+            .await
+            .unwrap();
+        let first_margin = account.lock().await.strategy_positions(strategy_id)[0].margin_usd;
+        assert_eq!(first_margin, 100.0);
+
+        // close with 1000 profit, doubling equity
+        let signal = build_signal(strategy_id, OrderSide::Sell, 1100.0, start + 60_000);
+        handler
+            .handle_signal(signal, market.clone(), account.clone())
+            .await
+            .unwrap();
+        assert_eq!(account.lock().await.equity(), 2000.0);
+
+        // 10% of 2000 equity
+        let signal = build_signal(strategy_id, OrderSide::Buy, 1100.0, start + 120_000);
+        handler
+            .handle_signal(signal, market.clone(), account.clone())
+            .await
+            .unwrap();
+        let second_margin = account.lock().await.strategy_positions(strategy_id)[0].margin_usd;
+        assert_eq!(second_margin, 200.0);
+        assert!(second_margin > first_margin);
+    }
+
+    #[test]
+    async fn test_signal_retry_succeeds() {
+        let (market, account, _) =
+            setup_with_exchange(MockExchangeApi::with_open_failures(1)).await;
+        let strategy_id = Uuid::new_v4();
+
+        let mut handler = SignalHandler::new();
+        handler.add_strategy_settings(&strategy_id, StrategySettings::default());
+
+        let signal = build_signal(strategy_id, OrderSide::Buy, 100.0, 1_700_000_000_000);
+        let result = handler
+            .handle_signal(signal, market.clone(), account.clone())
+            .await;
+
+        assert!(result.is_ok());
+        assert_eq!(
+            account.lock().await.strategy_positions(strategy_id).len(),
+            1
+        );
+        assert!(handler.failed_signals().is_empty());
+    }
+
+    #[test]
+    async fn test_signal_retry_dead_letter() {
+        let (market, account, _) = setup_with_exchange(MockExchangeApi::with_open_failures(
+            MAX_SIGNAL_ATTEMPTS as usize,
+        ))
+        .await;
+        let strategy_id = Uuid::new_v4();
+
+        let mut handler = SignalHandler::new();
+        handler.add_strategy_settings(&strategy_id, StrategySettings::default());
+
+        let signal = build_signal(strategy_id, OrderSide::Buy, 100.0, 1_700_000_000_000);
+        let result = handler
+            .handle_signal(signal, market.clone(), account.clone())
+            .await;
+
+        assert!(matches!(result, Err(SignalError::OpenPositionFailed(_, _))));
+        assert_eq!(
+            account.lock().await.strategy_positions(strategy_id).len(),
+            0
+        );
+
+        let failed_signals = handler.failed_signals();
+        assert_eq!(failed_signals.len(), 1);
+        assert_eq!(failed_signals[0].attempts, MAX_SIGNAL_ATTEMPTS);
+        assert_eq!(failed_signals[0].signal.strategy_id, strategy_id);
+    }
+
+    #[test]
+    async fn test_signal_refused_by_exchange_not_retried() {
+        let (market, account, exchange_api) =
+            setup_with_exchange(MockExchangeApi::with_open_rejections(1)).await;
+        let strategy_id = Uuid::new_v4();
+
+        let mut handler = SignalHandler::new();
+        handler.add_strategy_settings(&strategy_id, StrategySettings::default());
+
+        let signal = build_signal(strategy_id, OrderSide::Buy, 100.0, 1_700_000_000_000);
+        let result = handler
+            .handle_signal(signal.clone(), market.clone(), account.clone())
+            .await;
+
+        // refused orders fail on the first attempt
+        assert!(matches!(result, Err(SignalError::OpenPositionFailed(_, _))));
+        assert_eq!(handler.failed_signals()[0].attempts, 1);
+        assert_eq!(
+            exchange_api.client_order_ids(),
+            vec![Some(signal.client_order_id())]
+        );
+    }
+
+    #[test]
+    async fn test_signal_retries_reuse_client_order_id() {
+        let (market, account, exchange_api) =
+            setup_with_exchange(MockExchangeApi::with_open_failures(2)).await;
+        let strategy_id = Uuid::new_v4();
+
+        let mut handler = SignalHandler::new();
+        handler.add_strategy_settings(&strategy_id, StrategySettings::default());
+
+        let signal = build_signal(strategy_id, OrderSide::Buy, 100.0, 1_700_000_000_000);
+        handler
+            .handle_signal(signal.clone(), market.clone(), account.clone())
+            .await
+            .unwrap();
+
+        let client_order_id = Some(signal.client_order_id());
+        assert_eq!(
+            exchange_api.client_order_ids(),
+            vec![client_order_id.clone(); 3]
+        );
+
+        // a signal of the next kline is a different order
+        let next = build_signal(strategy_id, OrderSide::Buy, 100.0, 1_700_000_060_000);
+        assert_ne!(Some(next.client_order_id()), client_order_id);
+    }
+
+    #[test]
+    async fn test_failed_signals_capped() {
+        let mut handler = SignalHandler::new();
+        let strategy_id = Uuid::new_v4();
+        let webhook_client: Arc<dyn WebhookClient> = Arc::new(RecordingWebhookClient::default());
+
+        for i in 0..MAX_FAILED_SIGNALS + 5 {
+            let signal = build_signal(strategy_id, OrderSide::Buy, 100.0, i as u64);
+            handler.record_failed_signals(SignalDispatch {
+                signal: signal.clone(),
+                settings: StrategySettings::default(),
+                webhook_client: webhook_client.clone(),
+                failed_signals: vec![FailedSignal {
+                    signal,
+                    error: "failed".to_string(),
+                    attempts: 1,
+                    failed_at: timestamp_to_string(i as u64),
+                }],
+            });
+        }
+
+        let failed_signals = handler.failed_signals();
+        assert_eq!(failed_signals.len(), MAX_FAILED_SIGNALS);
+        // the oldest were dropped
+        assert_eq!(failed_signals[0].failed_at, timestamp_to_string(5));
+    }
+
+    #[test]
+    async fn test_stop_loss_stored_on_position() {
+        let (market, account) = setup().await;
+        let strategy_id = Uuid::new_v4();
+
+        let settings = StrategySettings {
+            stop_loss: Some(StopLoss::Percent(1.0)),
+            ..Default::default()
+        };
+
+        let mut handler = SignalHandler::new();
+        handler.add_strategy_settings(&strategy_id, settings);
+
+        let signal = build_signal(strategy_id, OrderSide::Sell, 200.0, 1_700_000_000_000);
+        handler
+            .handle_signal(signal, market.clone(), account.clone())
+            .await
+            .unwrap();
+
+        let account = account.lock().await;
+        let positions = account.strategy_positions(strategy_id);
+        assert_eq!(positions[0].stop_loss, Some(202.0));
+    }
+
+    #[test]
+    async fn test_capital_allocation() {
+        let (market, account) = setup().await;
+        let capped_id = Uuid::new_v4();
+        let other_id = Uuid::new_v4();
+
+        let settings = StrategySettings {
+            max_open_orders: 5,
+            margin_usd: 100.0,
+            capital_allocation_usd: Some(150.0),
+            ..Default::default()
+        };
+
+        let mut handler = SignalHandler::new();
+        handler.add_strategy_settings(&capped_id, settings.clone());
+        handler.add_strategy_settings(&other_id, settings);
+
+        let start = 1_700_000_000_000;
+
+        // second entry would hold 200 USD of a 150 USD allocation
+        for offset in [0, 60_000] {
+            let signal = build_signal(capped_id, OrderSide::Buy, 100.0, start + offset);
+            handler
+                .handle_signal(signal, market.clone(), account.clone())
+                .await
+                .unwrap();
+        }
+        assert_eq!(account.lock().await.strategy_positions(capped_id).len(), 1);
+
+        // allocation is per strategy, other strategy on the same account can still trade
+        let signal = build_signal(other_id, OrderSide::Buy, 100.0, start + 120_000);
+        handler
+            .handle_signal(signal, market.clone(), account.clone())
+            .await
+            .unwrap();
+        assert_eq!(account.lock().await.strategy_positions(other_id).len(), 1);
+
+        let account = account.lock().await;
+        let settings = handler.active_strategy_settings.get(&capped_id).unwrap();
+        assert_eq!(
+            settings.remaining_allocation(account.strategy_committed_margin(capped_id)),
+            Some(50.0)
+        );
+    }
+
+    #[test]
+    async fn test_max_open_positions() {
+        let (market, account) = setup().await;
+        let strategy_id = Uuid::new_v4();
+
+        let settings = StrategySettings {
+            max_open_orders: 1,
+            margin_usd: 100.0,
+            ..Default::default()
+        };
+
+        let mut handler = SignalHandler::new();
+        handler.add_strategy_settings(&strategy_id, settings);
+
+        let start = 1_700_000_000_000;
+
+        // the second concurrent entry is refused, closing is still allowed after which a new
+        // entry is accepted
+        let steps = [
+            (OrderSide::Buy, 1),
+            (OrderSide::Buy, 1),
+            (OrderSide::Sell, 0),
+            (OrderSide::Buy, 1),
+        ];
+        for (i, (order_side, open_positions)) in steps.into_iter().enumerate() {
+            let signal = build_signal(strategy_id, order_side, 100.0, start + i as u64 * 60_000);
+            handler
+                .handle_signal(signal, market.clone(), account.clone())
+                .await
+                .unwrap();
+
+            assert_eq!(
+                account.lock().await.strategy_positions(strategy_id).len(),
+                open_positions
+            );
+        }
+    }
+
+    #[test]
+    async fn test_route_signal_to_strategy_account() {
+        let (market, default_account) = setup().await;
+        let (_, hedge_account) = setup().await;
+        let default_id = Uuid::new_v4();
+        let hedge_id = Uuid::new_v4();
+
+        let accounts = HashMap::from([
+            (DEFAULT_ACCOUNT_ID.to_string(), default_account.clone()),
+            ("hedge".to_string(), hedge_account.clone()),
+        ]);
+
+        let mut handler = SignalHandler::new();
+        handler.add_strategy_settings(&default_id, StrategySettings::default());
+        handler.add_strategy_settings(
+            &hedge_id,
+            StrategySettings {
+                account_id: Some("hedge".to_string()),
+                ..Default::default()
+            },
+        );
+
+        let start = 1_700_000_000_000;
+
+        let signal = build_signal(hedge_id, OrderSide::Sell, 100.0, start);
+        handler
+            .route_signal(signal, market.clone(), &accounts)
+            .await
+            .unwrap();
+        let signal = build_signal(default_id, OrderSide::Buy, 100.0, start);
+        handler
+            .route_signal(signal, market.clone(), &accounts)
+            .await
+            .unwrap();
+
+        let hedge_account = hedge_account.lock().await;
+        assert_eq!(hedge_account.strategy_positions(hedge_id).len(), 1);
+        assert_eq!(hedge_account.strategy_positions(default_id).len(), 0);
+
+        let default_account = default_account.lock().await;
+        assert_eq!(default_account.strategy_positions(default_id).len(), 1);
+        assert_eq!(default_account.strategy_positions(hedge_id).len(), 0);
+
+        // strategies on unknown accounts are rejected
+        let unknown_id = Uuid::new_v4();
+        handler.add_strategy_settings(
+            &unknown_id,
+            StrategySettings {
+                account_id: Some("missing".to_string()),
+                ..Default::default()
+            },
+        );
+        let signal = build_signal(unknown_id, OrderSide::Buy, 100.0, start);
+        let result = handler
+            .route_signal(signal, market.clone(), &accounts)
+            .await;
+        assert!(matches!(result, Err(SignalError::UnknownAccount(_))));
+    }
+
+    /// Webhook client recording the payloads posted to it.
+    #[derive(Default)]
+    struct RecordingWebhookClient {
+        posts: std::sync::Mutex<Vec<(String, serde_json::Value)>>,
+    }
+
+    #[async_trait::async_trait]
+    impl WebhookClient for RecordingWebhookClient {
+        async fn post(&self, url: &str, payload: &serde_json::Value) -> Result<(), String> {
+            self.posts
+                .lock()
+                .unwrap()
+                .push((url.to_string(), payload.clone()));
+            Ok(())
+        }
+    }
+
+    #[test]
+    async fn test_route_signal_to_every_sink() {
+        let (market, account) = setup().await;
+        let strategy_id = Uuid::new_v4();
+        let accounts = HashMap::from([(DEFAULT_ACCOUNT_ID.to_string(), account.clone())]);
+
+        let webhook_client = Arc::new(RecordingWebhookClient::default());
+        let mut handler = SignalHandler::new();
+        handler.webhook_client = webhook_client.clone();
+        handler.add_strategy_settings(
+            &strategy_id,
+            StrategySettings {
+                signal_sinks: vec![
+                    SignalSink::Account { account_id: None },
+                    SignalSink::Webhook {
+                        url: "http://copy-trader/signals".to_string(),
+                    },
+                ],
+                ..Default::default()
+            },
+        );
+
+        let signal = build_signal(strategy_id, OrderSide::Buy, 100.0, 1_700_000_000_000);
+        handler
+            .route_signal(signal, market.clone(), &accounts)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            account.lock().await.strategy_positions(strategy_id).len(),
+            1
+        );
+
+        // the webhook is posted to in the background
+        time::timeout(Duration::from_secs(1), async {
+            while webhook_client.posts.lock().unwrap().is_empty() {
+                time::sleep(Duration::from_millis(5)).await;
+            }
+        })
+        .await
+        .unwrap();
+
+        let posts = webhook_client.posts.lock().unwrap();
+        assert_eq!(posts.len(), 1);
+        assert_eq!(posts[0].0, "http://copy-trader/signals");
+        assert_eq!(posts[0].1["strategy_id"], strategy_id.to_string());
+        assert_eq!(posts[0].1["order_side"], "Buy");
+    }
+}
@@ -1,7 +1,8 @@
-use std::{collections::HashMap, time::Duration};
+use std::{sync::Arc, time::Duration};
 
-use log::info;
-use serde::{Deserialize, Serialize};
+use log::{error, info, warn};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Deserializer, Serialize};
 use serde_json::{json, Value};
 use tokio::task::JoinHandle;
 use tokio::time;
@@ -9,26 +10,44 @@ use uuid::Uuid;
 
 use crate::{
     account::{
-        account::Account,
-        trade::{OrderSide, Position, PositionId, TradeTx},
+        account::{Account, AccountId},
+        trade::{OrderSide, Position, TradeTx},
     },
     algo::builder::AlgoBuilder,
+    exchange::mock::MockExchangeApi,
     market::{
         interval::Interval,
         kline::{self, Kline},
         market::Market,
+        trade::Trade,
         types::{ArcMutex, ArcSender},
     },
     strategy::{
         algorithm::Algorithm,
-        signal::{SignalMessage, SignalMessageType},
+        backer::BackTestAudit,
+        eval_log::{EvalLog, EvalLogEntry},
+        indicator::IndicatorSet,
+        signal::{SignalMessage, SignalMessageType, SignalSink},
         types::{AlgoError, AlgoEvalResult, FirstLastEnum},
     },
-    utils::time::{floor_mili_ts, generate_ts, timestamp_to_string, MIN_AS_MILI, SEC_AS_MILI},
+    utils::{
+        kline::calc_atr,
+        number::{from_decimal, sum_decimal, to_decimal},
+        time::{
+            floor_mili_ts, generate_ts, string_to_timestamp, timestamp_to_string, MIN_AS_MILI,
+            SEC_AS_MILI,
+        },
+    },
 };
 
 pub type StrategyId = Uuid;
 
+/// Seconds after an interval close a strategy is evaluated at by default.
+pub const DEFAULT_EVAL_OFFSET_SECS: u64 = 5;
+
+/// Balance of the simulated account a strategy in shadow mode trades on.
+pub const SHADOW_INITIAL_BALANCE: f64 = 10_000.0;
+
 pub struct StrategySignals {
     pub signals: Vec<SignalMessage>,
 }
@@ -63,6 +82,11 @@ pub struct Strategy {
     kline_manager: ArcMutex<StrategyKlineManager>,
     running: bool,
     signals: ArcMutex<StrategySignals>,
+    eval_log: ArcMutex<EvalLog>,
+    indicators: ArcMutex<IndicatorSet>,
+    shadow_account: Option<ArcMutex<Account>>,
+    /// Time the strategy started trading in shadow mode, in milliseconds.
+    shadow_since: Option<u64>,
 }
 
 impl Strategy {
@@ -92,6 +116,8 @@ impl Strategy {
         algorithm_params: Value,
     ) -> Result<Self, AlgoError> {
         let algorithm = AlgoBuilder::build_algorithm(strategy_name, algorithm_params)?;
+        let eval_log = EvalLog::new(settings.eval_log_size.unwrap_or(0));
+        let indicators = IndicatorSet::new(algorithm.indicators());
 
         Ok(Self {
             id: Uuid::new_v4(),
@@ -107,16 +133,56 @@ impl Strategy {
             kline_manager: ArcMutex::new(StrategyKlineManager::new()),
             running: false,
             signals: ArcMutex::new(StrategySignals::new()),
+            eval_log: ArcMutex::new(eval_log),
+            indicators: ArcMutex::new(indicators),
+            shadow_account: None,
+            shadow_since: None,
         })
     }
 
+    /// Creates a strategy with the same algorithm, parameters and settings on another interval,
+    /// ie. to compare the strategy across intervals.
+    ///
+    /// # Arguments
+    ///
+    /// * `interval` - The interval of the new strategy.
+    ///
+    /// # Returns
+    ///
+    /// A result containing the new `Strategy` instance or an `AlgoError` if an error occurs.
+
+    pub async fn with_interval(&self, interval: Interval) -> Result<Self, AlgoError> {
+        let params = self.algorithm.lock().await.get_params().clone();
+
+        Strategy::new(
+            &self.name,
+            &self.symbol,
+            interval,
+            self.strategy_tx.clone(),
+            self.market.clone(),
+            self.settings.clone(),
+            params,
+        )
+    }
+
     /// Starts the execution of the strategy in an asynchronous task.
     ///
+    /// # Arguments
+    ///
+    /// * `account` - The account the strategy trades on, used to close positions held longer
+    ///   than the max hold duration. Strategies in shadow mode trade on their own simulated
+    ///   account instead.
+    ///
     /// # Returns
     ///
     /// A handle to the spawned asynchronous task running the strategy.
 
-    pub async fn start(&mut self) -> JoinHandle<()> {
+    pub async fn start(&mut self, account: ArcMutex<Account>) -> JoinHandle<()> {
+        let account = match self.settings.mode {
+            StrategyMode::Shadow => self.init_shadow_account().await,
+            _ => account,
+        };
+
         self.running = true;
         self.start_time = Some(timestamp_to_string(generate_ts()));
         // let market = self.market.clone();
@@ -130,31 +196,30 @@ impl Strategy {
         let market = self.market.clone();
         let kline_manager = self.kline_manager.clone();
         let signals = self.signals.clone();
+        let settings = self.settings.clone();
+        let eval_log = self.eval_log.clone();
+        let indicators = self.indicators.clone();
 
         tokio::spawn(async move {
             // let market = market.clone();
-            // wait until last 5 seconds of minute, to ensure getting latest kline
-            // data from market, ie. each request for fresh kline will
-            // the no older than last minute + 55 seconds, very close
-            // to any kline interval closing time
-            let next_minute_minus_5_sec =
-                (floor_mili_ts(generate_ts(), MIN_AS_MILI) + MIN_AS_MILI) - SEC_AS_MILI * 5;
-            loop {
-                let now = generate_ts();
-                if now > next_minute_minus_5_sec {
-                    break;
-                } else {
-                    time::sleep(Duration::from_secs(1)).await;
-                }
-            }
+            let eval_offset_secs = settings.eval_offset_secs();
+            let mut last_wake = 0;
 
             loop {
-                // wait for duration of strategy interval first,
-                // to ensure at least one kline of data is populated in the market
-                time::sleep(interval.to_duration()).await;
-
-                // get the latest kline from the market
-                let kline = market.lock().await.last_kline(&symbol, interval).await;
+                // wait for the next kline of the strategy interval to close, backing off
+                // a few seconds so the closed kline is populated in the market
+                let wake_time =
+                    next_eval_time(generate_ts(), last_wake, interval, eval_offset_secs);
+                last_wake = wake_time;
+                time::sleep(Duration::from_millis(
+                    wake_time.saturating_sub(generate_ts()),
+                ))
+                .await;
+
+                // get the latest kline from the market, without holding the market lock while
+                // it's fetched from the exchange
+                let reader = market.lock().await.reader();
+                let kline = reader.last_kline(&symbol, interval, true).await;
 
                 // perform some house keeping with klines before evaluating the data
                 // check kline is fresh otherwise continue to next interval
@@ -163,6 +228,20 @@ impl Strategy {
                     if kline_manager.lock().await.must_continue(kline) {
                         continue;
                     }
+
+                    if let Some(max_hold_secs) = settings.max_hold_secs {
+                        close_expired_positions(&account, id, max_hold_secs, kline).await;
+                    }
+
+                    // skip illiquid klines, signals on them are mostly noise
+                    if settings.is_below_min_volume(kline) {
+                        info!(
+                            "Skipping {symbol} kline at {}, volume {} below minimum",
+                            timestamp_to_string(kline.open_time),
+                            kline.volume
+                        );
+                        continue;
+                    }
                 }
 
                 // get trades within the span of the kline open_time and close_time
@@ -199,7 +278,8 @@ impl Strategy {
                     // ---
                     // Main evaluation done here
                     // ---
-                    let order_side = algorithm.lock().await.evaluate(kline.clone(), &trades);
+                    let order_side =
+                        evaluate_kline(&algorithm, &indicators, &eval_log, &kline, &trades).await;
 
                     let order_side = match order_side {
                         AlgoEvalResult::Buy => OrderSide::Buy,
@@ -216,11 +296,17 @@ impl Strategy {
                         price: kline.close,
                         is_back_test: false,
                         close_time: timestamp_to_string(kline.close_time),
+                        interval: Some(interval),
                         ty: SignalMessageType::Standard,
                     };
 
                     signals.lock().await.add_signal(&signal);
 
+                    // dry run signals are only recorded on the strategy
+                    if settings.mode == StrategyMode::DryRun {
+                        continue;
+                    }
+
                     if strategy_tx.is_closed() {
                         break;
                     }
@@ -236,6 +322,29 @@ impl Strategy {
         })
     }
 
+    /// Price the remaining positions of the strategy are closed at once it stops, the last price
+    /// of the symbol or else the close of its latest stored kline.
+    async fn exit_price(&self, symbol: &str) -> Option<f64> {
+        let reader = self.market.lock().await.reader();
+
+        if let Some(price) = reader.last_price(symbol).await {
+            return Some(price);
+        }
+
+        let kline = self
+            .market
+            .lock()
+            .await
+            .latest_kline(symbol, self.interval)
+            .await?;
+        warn!(
+            "No last price of {symbol}, closing at {} the close of the kline opened at {}",
+            kline.close,
+            timestamp_to_string(kline.open_time)
+        );
+        Some(kline.close)
+    }
+
     /// Stops the execution of the strategy and optionally closes all open positions associated with it.
     ///
     /// # Arguments
@@ -265,59 +374,40 @@ impl Strategy {
         // Close all positions on account attached to this strategy
         if close_positions {
             for position in positions {
-                if let Some(close_price) = self
-                    .market
-                    .clone()
-                    .lock()
-                    .await
-                    .last_price(&position.symbol)
-                    .await
-                {
-                    let mut account = account.lock().await;
-                    let trade = account.close_position(position.id, close_price).await;
-
-                    if let Some(trade) = trade.cloned() {
-                        let signal = SignalMessage {
-                            strategy_id: self.id,
-                            order_side: trade.position.order_side,
-                            symbol: trade.position.symbol,
-                            price: trade.close_price,
-                            is_back_test: true,
-                            close_time: trade.close_time,
-                            ty: SignalMessageType::ForcedClose(
-                                "Closed Remaining Positions".to_string(),
-                            ),
-                        };
-
-                        account.add_position_meta(trade.position.id, &signal)
+                match self.exit_price(&position.symbol).await {
+                    Some(close_price) => {
+                        let mut account = account.lock().await;
+                        let trade = account.close_position(position.id, close_price).await;
+
+                        if let Some(trade) = trade.cloned() {
+                            let signal = SignalMessage {
+                                strategy_id: self.id,
+                                order_side: trade.position.order_side,
+                                symbol: trade.position.symbol,
+                                price: trade.close_price,
+                                is_back_test: true,
+                                close_time: timestamp_to_string(trade.close_time),
+                                interval: None,
+                                ty: SignalMessageType::ForcedClose(
+                                    "Closed Remaining Positions".to_string(),
+                                ),
+                            };
+
+                            account.add_position_meta(trade.position.id, &signal)
+                        }
                     }
+                    None => error!(
+                        "Unable to close position {} of strategy {}, no price of {} is available",
+                        position.id, self.id, position.symbol
+                    ),
                 }
             }
         }
 
-        let (positions, trades) = account.lock().await.strategy_positions_trades(self.id);
-
         self.end_time = Some(timestamp_to_string(generate_ts()));
         self.running = false;
 
-        let signals = Strategy::get_position_meta(account, &positions).await;
-
-        self.calc_summary(&trades, &positions, &signals).await
-    }
-
-    pub async fn get_position_meta(
-        account: ArcMutex<Account>,
-        positions: &Vec<Position>,
-    ) -> HashMap<PositionId, Vec<SignalMessage>> {
-        let mut signals = HashMap::new();
-
-        for position in positions {
-            if let Some(pos_signals) = account.lock().await.get_position_meta(position.id) {
-                signals.insert(position.id, pos_signals);
-            }
-        }
-
-        signals
+        self.summary(account).await
     }
 
     /// Generates a summary of the strategy's performance.
@@ -331,10 +421,25 @@ impl Strategy {
     /// A summary of the strategy's performance including trades, positions, and profit.
 
     pub async fn summary(&self, account: ArcMutex<Account>) -> StrategySummary {
-        let (positions, trades) = account.lock().await.strategy_positions_trades(self.id);
+        // trades and their aggregates are read under one lock, so they always agree
+        let (positions, trades, aggregates) = {
+            let account = account.lock().await;
+            let (positions, mut trades) = account.strategy_positions_trades(self.id);
+
+            for trade in trades.iter_mut() {
+                for signal in account
+                    .get_position_meta(trade.position.id)
+                    .unwrap_or_default()
+                {
+                    trade.add_signal(&signal);
+                }
+            }
+
+            let aggregates = account.strategy_trade_aggregates(self.id);
+            (positions, trades, aggregates)
+        };
 
-        let signals = Strategy::get_position_meta(account, &positions).await;
-        self.calc_summary(&trades, &positions, &signals).await
+        self.calc_summary(trades, positions, &aggregates).await
     }
 
     /// Retrieves the settings for the strategy.
@@ -369,16 +474,40 @@ impl Strategy {
 
     /// Sets the parameters for the algorithm used by the strategy.
     ///
+    /// The algorithm is locked for the whole update so a running strategy never evaluates a kline
+    /// in between, invalid parameters leave the algorithm as it was.
+    ///
     /// # Arguments
     ///
     /// * `params` - The new parameters for the algorithm as a JSON `Value`.
+    /// * `reset_state` - `true` to clear the klines and indicator values the algorithm has
+    ///   accumulated, the algorithm is rebuilt before the new parameters are applied.
     ///
     /// # Returns
     ///
     /// A result indicating success or containing an `AlgoError`.
 
-    pub async fn set_algorithm_params(&self, params: Value) -> Result<(), AlgoError> {
-        self.algorithm.lock().await.set_params(params)
+    pub async fn set_algorithm_params(
+        &self,
+        params: Value,
+        reset_state: bool,
+    ) -> Result<(), AlgoError> {
+        let mut algorithm = self.algorithm.lock().await;
+        let mut indicators = self.indicators.lock().await;
+
+        if reset_state {
+            // rebuilt from the current params as new params may only set some of the fields
+            let mut reset_algorithm =
+                AlgoBuilder::build_algorithm(&self.name, algorithm.get_params().clone())?;
+            reset_algorithm.set_params(params)?;
+            *algorithm = reset_algorithm;
+            *indicators = IndicatorSet::new(algorithm.indicators());
+        } else {
+            algorithm.set_params(params)?;
+            indicators.set_indicators(algorithm.indicators());
+        }
+
+        Ok(())
     }
 
     /// Provides information about the strategy including its identifier, name, and configuration.
@@ -398,9 +527,39 @@ impl Strategy {
             running: self.running,
             start_time: self.start_time.clone(),
             end_time: self.end_time.clone(),
+            remaining_allocation_usd: None,
         }
     }
 
+    /// Evaluates a kline with the strategy algorithm, recording the evaluation in the
+    /// evaluation log.
+    ///
+    /// # Arguments
+    ///
+    /// * `kline` - The kline to evaluate.
+    /// * `trades` - Trades within the kline, if needed by the algorithm.
+    ///
+    /// # Returns
+    ///
+    /// The result of the evaluation.
+
+    pub async fn evaluate(&self, kline: &Kline, trades: &[Trade]) -> AlgoEvalResult {
+        evaluate_kline(
+            &self.algorithm,
+            &self.indicators,
+            &self.eval_log,
+            kline,
+            trades,
+        )
+        .await
+    }
+
+    /// Lists the latest evaluations recorded in the evaluation log, oldest first.
+
+    pub async fn eval_log(&self) -> Vec<EvalLogEntry> {
+        self.eval_log.lock().await.entries()
+    }
+
     pub async fn get_signals(&self) -> Vec<SignalMessage> {
         self.signals.lock().await.signals.clone()
     }
@@ -409,16 +568,60 @@ impl Strategy {
         self.signals.lock().await.add_signal(signal);
     }
 
+    /// Returns the simulated account of a strategy in shadow mode, `None` for other modes or if
+    /// the strategy has not started.
+
+    pub fn shadow_account(&self) -> Option<ArcMutex<Account>> {
+        self.shadow_account.clone()
+    }
+
+    /// Returns the time in milliseconds a strategy in shadow mode started trading on its
+    /// simulated account, `None` for other modes or if the strategy has not started.
+
+    pub fn shadow_since(&self) -> Option<u64> {
+        self.shadow_since
+    }
+
+    /// Switches a strategy in shadow mode to live trading, its signals are traded on its account
+    /// rather than the simulated account. The strategy must be started again to trade live.
+
+    pub fn promote_to_live(&mut self) {
+        self.settings.mode = StrategyMode::Live;
+        self.shadow_account = None;
+        self.shadow_since = None;
+    }
+
+    // ---
+    // Private Methods
+    // ---
+
+    /// Creates the simulated account a strategy in shadow mode trades on, kept across restarts
+    /// of the strategy.
+    async fn init_shadow_account(&mut self) -> ArcMutex<Account> {
+        if let Some(account) = &self.shadow_account {
+            return account.clone();
+        }
+
+        let mut account = Account::new(Arc::new(MockExchangeApi::default()), false, true).await;
+        account.set_initial_balance(SHADOW_INITIAL_BALANCE);
+
+        let account = ArcMutex::new(account);
+        self.shadow_account = Some(account.clone());
+        self.shadow_since = Some(generate_ts());
+        account
+    }
+
     /// Calculates the summary of the strategy's performance including profit, drawdown, trade counts, and more.
     ///
-    /// This private method aggregates the results of the strategy's trades and positions to compute key performance
-    /// indicators such as total profit, maximum drawdown, and trade counts. It's used internally to generate
-    /// a comprehensive summary of the strategy's outcome after its execution.
+    /// This private method combines the running aggregates of the strategy's trades with its trades and
+    /// positions, so key performance indicators such as total profit, maximum drawdown, and trade counts
+    /// aren't recalculated from every trade each time a summary is requested.
     ///
     /// # Arguments
     ///
-    /// * `trades` - A reference to a vector of `TradeTx` instances representing executed trades.
-    /// * `positions` - A reference to a vector of `Position` instances representing open positions.
+    /// * `trades` - The executed trades, with the signals of their positions.
+    /// * `positions` - The open positions.
+    /// * `aggregates` - Running aggregates of the trades, recorded by the account.
     ///
     /// # Returns
     ///
@@ -426,15 +629,15 @@ impl Strategy {
 
     async fn calc_summary(
         &self,
-        trades: &Vec<TradeTx>,
-        positions: &Vec<Position>,
-        signals: &HashMap<PositionId, Vec<SignalMessage>>,
+        trades: Vec<TradeTx>,
+        positions: Vec<Position>,
+        aggregates: &TradeAggregates,
     ) -> StrategySummary {
-        let max_profit = Strategy::calc_max_profit(&trades);
-        let max_drawdown = Strategy::calc_max_drawdown(&trades);
-        let long_trade_count = Strategy::calc_trade_count(&trades, OrderSide::Buy);
-        let short_trade_count = Strategy::calc_trade_count(&trades, OrderSide::Sell);
-        let profit: f64 = Strategy::calc_profit(&trades);
+        let max_profit = aggregates.max_profit();
+        let max_drawdown = aggregates.max_drawdown();
+        let long_trade_count = aggregates.long_trade_count();
+        let short_trade_count = aggregates.short_trade_count();
+        let profit = aggregates.profit();
 
         let start_price = match self
             .kline_manager
@@ -455,21 +658,11 @@ impl Strategy {
             None => 0.0,
         };
 
-        let mut trades = trades.clone();
-
-        for trade in trades.iter_mut() {
-            if let Some(pos_meta) = signals.get(&trade.position.id) {
-                for signal in pos_meta {
-                    trade.add_signal(signal);
-                }
-            }
-        }
-
-        StrategySummary {
+        let mut summary = StrategySummary {
             info: self.info().await,
             profit: profit,
             trades: trades,
-            positions: positions.clone(),
+            positions: positions,
             long_trade_count,
             short_trade_count,
             symbol: self.symbol.to_string(),
@@ -477,8 +670,11 @@ impl Strategy {
             start_price: start_price,
             max_drawdown,
             max_profit,
-            // signals: self.get_signals().await,
-        }
+            ..Default::default()
+        };
+        summary.calc_returns();
+
+        summary
     }
 
     // ---
@@ -499,18 +695,18 @@ impl Strategy {
     /// Returns a `f64` representing the maximum cumulative profit achieved.
 
     pub fn calc_max_profit(trades: &Vec<TradeTx>) -> f64 {
-        let mut max_balance = 0.0;
-        let mut current_balance = 0.0;
+        let mut max_balance = Decimal::ZERO;
+        let mut current_balance = Decimal::ZERO;
 
         for trade_tx in trades {
-            current_balance += trade_tx.profit;
+            current_balance += trade_tx.quote_profit();
 
             if current_balance > max_balance {
                 max_balance = current_balance;
             }
         }
 
-        max_balance
+        from_decimal(max_balance)
     }
 
     /// Computes the maximum drawdown experienced by the strategy.
@@ -527,21 +723,50 @@ impl Strategy {
     /// Returns a `f64` representing the maximum drawdown experienced.
 
     pub fn calc_max_drawdown(trades: &Vec<TradeTx>) -> f64 {
-        let mut min_balance = if trades.is_empty() { 0.0 } else { f64::MAX };
-        let mut current_balance = 0.0;
+        Strategy::calc_drawdown_timeline(trades)
+            .iter()
+            .fold(0.0, |max, point| f64::max(max, point.drawdown))
+    }
 
+    /// Builds the cumulative balance and running drawdown after each trade.
+    ///
+    /// Trades are ordered by close time, the running peak starts at a balance of zero so a
+    /// strategy which loses from the first trade reports the loss as drawdown.
+    ///
+    /// # Arguments
+    ///
+    /// * `trades` - A reference to a vector of `TradeTx` instances representing executed trades.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `DrawdownPoint` for each trade, in close time order.
+
+    pub fn calc_drawdown_timeline(trades: &Vec<TradeTx>) -> Vec<DrawdownPoint> {
         let mut trades = trades.clone();
         trades.sort_by(|a, b| a.close_time.cmp(&b.close_time));
 
-        for trade_tx in trades {
-            current_balance += trade_tx.profit;
+        let mut balance = Decimal::ZERO;
+        let mut peak = Decimal::ZERO;
+        let mut timeline = vec![];
 
-            if current_balance <= min_balance {
-                min_balance = current_balance;
-            }
+        for trade_tx in trades {
+            balance += trade_tx.quote_profit();
+            peak = peak.max(balance);
+
+            timeline.push(DrawdownPoint {
+                trade_id: trade_tx.id,
+                close_time: timestamp_to_string(trade_tx.close_time),
+                order_side: trade_tx.position.order_side,
+                open_price: trade_tx.position.open_price,
+                close_price: trade_tx.close_price,
+                profit: from_decimal(trade_tx.quote_profit()),
+                balance: from_decimal(balance),
+                peak: from_decimal(peak),
+                drawdown: from_decimal(peak - balance),
+            })
         }
 
-        min_balance
+        timeline
     }
 
     /// Calculates the number of trades executed by the strategy for a specific order side.
@@ -579,7 +804,87 @@ impl Strategy {
     /// Returns a `f64` representing the total profit or loss.
 
     pub fn calc_profit(trades: &Vec<TradeTx>) -> f64 {
-        trades.iter().map(|trade| trade.profit).sum()
+        from_decimal(trades.iter().map(|trade| trade.quote_profit()).sum())
+    }
+}
+
+/// Running aggregates of the trades of a strategy, updated as each trade is recorded so a summary
+/// doesn't rescan every trade.
+///
+/// Trades are expected in close time order, the order an account records them in. Aggregates
+/// built with `from_trades` are a full recompute, used to verify the running totals.
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TradeAggregates {
+    balance: Decimal,
+    peak: Decimal,
+    max_drawdown: Decimal,
+    long_trade_count: usize,
+    short_trade_count: usize,
+}
+
+impl TradeAggregates {
+    /// Adds a trade to the aggregates.
+
+    pub fn add_trade(&mut self, trade_tx: &TradeTx) {
+        self.balance += trade_tx.quote_profit();
+        self.peak = self.peak.max(self.balance);
+        self.max_drawdown = self.max_drawdown.max(self.peak - self.balance);
+
+        match trade_tx.position.order_side {
+            OrderSide::Buy => self.long_trade_count += 1,
+            OrderSide::Sell => self.short_trade_count += 1,
+        }
+    }
+
+    /// Recomputes the aggregates from all trades.
+    ///
+    /// # Arguments
+    ///
+    /// * `trades` - The trades of the strategy.
+    ///
+    /// # Returns
+    ///
+    /// The aggregates calculated by rescanning the trades.
+
+    pub fn from_trades(trades: &Vec<TradeTx>) -> Self {
+        Self {
+            balance: to_decimal(Strategy::calc_profit(trades)),
+            peak: to_decimal(Strategy::calc_max_profit(trades)),
+            max_drawdown: to_decimal(Strategy::calc_max_drawdown(trades)),
+            long_trade_count: Strategy::calc_trade_count(trades, OrderSide::Buy),
+            short_trade_count: Strategy::calc_trade_count(trades, OrderSide::Sell),
+        }
+    }
+
+    /// Returns the total profit of the trades.
+
+    pub fn profit(&self) -> f64 {
+        from_decimal(self.balance)
+    }
+
+    /// Returns the highest cumulative profit reached.
+
+    pub fn max_profit(&self) -> f64 {
+        from_decimal(self.peak)
+    }
+
+    /// Returns the largest drop of the cumulative profit from its peak.
+
+    pub fn max_drawdown(&self) -> f64 {
+        from_decimal(self.max_drawdown)
+    }
+
+    /// Returns the number of long trades.
+
+    pub fn long_trade_count(&self) -> usize {
+        self.long_trade_count
+    }
+
+    /// Returns the number of short trades.
+
+    pub fn short_trade_count(&self) -> usize {
+        self.short_trade_count
     }
 }
 
@@ -600,6 +905,9 @@ pub struct StrategyInfo {
     pub running: bool,
     pub start_time: Option<String>,
     pub end_time: Option<String>,
+    /// Capital allocation left for new entries, only set for running strategies with an allocation.
+    #[serde(default)]
+    pub remaining_allocation_usd: Option<f64>,
 }
 
 /// Provides default values for `StrategyInfo`.
@@ -619,6 +927,7 @@ impl Default for StrategyInfo {
             start_time: None,
             end_time: None,
             running: false,
+            remaining_allocation_usd: None,
         }
     }
 }
@@ -627,13 +936,300 @@ impl Default for StrategyInfo {
 ///
 /// This struct defines essential settings that control the execution of a trading strategy,
 /// including the maximum number of open orders, margin usage, leverage, and an optional stop loss.
+///
+/// `signal_cooldown_ms` and `min_flip_move_pct` debounce noisy algorithms, a value of zero
+/// disables the respective check.
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct StrategySettings {
+    /// Maximum positions the strategy holds open at once, counted per strategy rather than as
+    /// exchange orders. Entries beyond the limit are ignored, signals on the opposite side still
+    /// close the open positions.
     pub max_open_orders: u32,
     pub margin_usd: f64,
     pub leverage: u32,
-    pub stop_loss: Option<f64>,
+    /// Stop loss resolved to a price when a position is opened.
+    #[serde(default, deserialize_with = "deserialize_stop_loss")]
+    pub stop_loss: Option<StopLoss>,
+    /// Ignore a signal on the same side as the last accepted signal within this many milliseconds.
+    #[serde(default)]
+    pub signal_cooldown_ms: u64,
+    /// Minimum price move, in percent from the last accepted signal, required to flip sides.
+    #[serde(default)]
+    pub min_flip_move_pct: f64,
+    /// How entries are sized, when `None` every entry uses `margin_usd`.
+    #[serde(default)]
+    pub sizing_mode: Option<SizingMode>,
+    /// Maximum margin the strategy may hold across its open positions, `None` for no limit.
+    #[serde(default)]
+    pub capital_allocation_usd: Option<f64>,
+    /// Account the strategy trades on, `None` for the default account.
+    #[serde(default)]
+    pub account_id: Option<AccountId>,
+    /// Klines with a volume below this are not evaluated, `None` to evaluate every kline.
+    #[serde(default)]
+    pub min_kline_volume: Option<f64>,
+    /// Number of evaluations kept in the evaluation log, `None` to disable the log.
+    #[serde(default)]
+    pub eval_log_size: Option<usize>,
+    /// Seconds after each interval close the strategy is evaluated at, `None` for
+    /// `DEFAULT_EVAL_OFFSET_SECS`.
+    #[serde(default)]
+    pub eval_offset_secs: Option<u64>,
+    /// What happens to the strategy's positions if its task ends unexpectedly.
+    #[serde(default)]
+    pub error_policy: StrategyErrorPolicy,
+    /// Positions held longer than this many seconds are closed on the next kline, `None` for no
+    /// limit.
+    #[serde(default)]
+    pub max_hold_secs: Option<u64>,
+    /// Whether signals are traded, simulated or only recorded, fixed when the strategy starts
+    /// unless a shadow strategy is promoted by `promote_after`.
+    #[serde(default)]
+    pub mode: StrategyMode,
+    /// Sinks every signal is sent to, empty to trade on `account_id` only.
+    #[serde(default)]
+    pub signal_sinks: Vec<SignalSink>,
+    /// Switches a strategy in shadow mode to live trading once its simulated trades meet the
+    /// policy, `None` to stay in shadow mode.
+    #[serde(default)]
+    pub promote_after: Option<PromotionPolicy>,
+    /// Whether a back test records every kline in its audit, otherwise only the counts of
+    /// evaluated and skipped klines are kept.
+    #[serde(default)]
+    pub audit_klines: bool,
+}
+
+impl StrategySettings {
+    /// Resolves the margin to use for a new entry.
+    ///
+    /// Fixed sizes are used as is, percent of equity sizes are clamped to the equity not already
+    /// committed as margin on open positions.
+    ///
+    /// # Arguments
+    ///
+    /// * `equity` - Current equity of the account.
+    /// * `committed_margin` - Margin currently held by open positions on the account.
+    ///
+    /// # Returns
+    ///
+    /// The margin in USD, or `None` if there is no equity left to size the entry with.
+
+    pub fn entry_margin(&self, equity: f64, committed_margin: f64) -> Option<f64> {
+        match self.sizing_mode {
+            None => Some(self.margin_usd),
+            Some(SizingMode::FixedUsd(margin_usd)) => Some(margin_usd),
+            Some(SizingMode::PercentEquity(percent)) => {
+                let available = equity - committed_margin;
+                let margin = (equity * percent / 100.0).min(available);
+
+                if margin > 0.0 {
+                    Some(margin)
+                } else {
+                    None
+                }
+            }
+        }
+    }
+}
+
+impl StrategySettings {
+    /// Checks whether a kline is too illiquid to evaluate.
+    ///
+    /// # Arguments
+    ///
+    /// * `kline` - The kline about to be evaluated.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the kline volume is below `min_kline_volume`.
+
+    pub fn is_below_min_volume(&self, kline: &Kline) -> bool {
+        match self.min_kline_volume {
+            Some(min_volume) => kline.volume < min_volume,
+            None => false,
+        }
+    }
+
+    /// Returns the sinks signals are sent to, the strategy's account if no sinks are set.
+
+    pub fn signal_sinks(&self) -> Vec<SignalSink> {
+        if self.signal_sinks.is_empty() {
+            return vec![SignalSink::Account {
+                account_id: self.account_id.clone(),
+            }];
+        }
+
+        self.signal_sinks.clone()
+    }
+
+    /// Returns the seconds after each interval close the strategy is evaluated at.
+
+    pub fn eval_offset_secs(&self) -> u64 {
+        self.eval_offset_secs.unwrap_or(DEFAULT_EVAL_OFFSET_SECS)
+    }
+
+    /// Calculates the capital allocation left for new entries.
+    ///
+    /// # Arguments
+    ///
+    /// * `strategy_margin` - Margin currently held by the strategy's open positions.
+    ///
+    /// # Returns
+    ///
+    /// The remaining allocation in USD, or `None` if the strategy has no allocation.
+
+    pub fn remaining_allocation(&self, strategy_margin: f64) -> Option<f64> {
+        self.capital_allocation_usd
+            .map(|allocation| (allocation - strategy_margin).max(0.0))
+    }
+}
+
+/// What happens to the open positions of a strategy whose task ends unexpectedly, ie. it panicked.
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum StrategyErrorPolicy {
+    /// Positions stay open and attached to the stopped strategy.
+    #[default]
+    LeaveOpen,
+    /// Positions are closed at the last price.
+    CloseAll,
+    /// Positions stay open and are detached from the strategy, to be managed manually.
+    ConvertToManual,
+}
+
+/// How the signals of a running strategy are acted on.
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum StrategyMode {
+    /// Signals open and close positions on the strategy's account.
+    #[default]
+    Live,
+    /// Signals open and close positions on a simulated account at real-time prices, the
+    /// simulated summary is saved to storage after each signal.
+    Shadow,
+    /// Klines are evaluated and signals recorded on the strategy, nothing is traded or simulated.
+    DryRun,
+}
+
+/// When a strategy in shadow mode is promoted to live trading.
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct PromotionPolicy {
+    /// Seconds the strategy trades in shadow mode before it can be promoted.
+    pub min_duration_secs: u64,
+    /// Realized profit in USD of the simulated trades required to promote the strategy.
+    pub min_shadow_profit: f64,
+}
+
+impl PromotionPolicy {
+    /// Checks whether a strategy in shadow mode is due to be promoted.
+    ///
+    /// # Arguments
+    ///
+    /// * `shadow_since` - Time the strategy started trading in shadow mode, in milliseconds.
+    /// * `now` - The current time in milliseconds.
+    /// * `shadow_profit` - Realized profit of the simulated trades.
+    ///
+    /// # Returns
+    ///
+    /// `true` once the strategy traded in shadow mode for the minimum duration with at least the
+    /// minimum profit.
+
+    pub fn is_met(&self, shadow_since: u64, now: u64, shadow_profit: f64) -> bool {
+        now.saturating_sub(shadow_since) >= self.min_duration_secs * SEC_AS_MILI
+            && shadow_profit >= self.min_shadow_profit
+    }
+}
+
+/// Position sizing used by a strategy when opening new positions.
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum SizingMode {
+    /// Fixed margin in USD for every entry.
+    FixedUsd(f64),
+    /// Margin as a percent of current account equity.
+    PercentEquity(f64),
+}
+
+/// Stop loss used by a strategy, resolved to a concrete price when a position is opened.
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum StopLoss {
+    /// Fixed stop loss price.
+    Price(f64),
+    /// Distance from the entry price in percent.
+    Percent(f64),
+    /// Distance from the entry price in multiples of the average true range over `period` klines.
+    Atr { period: usize, mult: f64 },
+}
+
+impl StopLoss {
+    /// Number of klines needed before entry to resolve the stop loss.
+    ///
+    /// # Returns
+    ///
+    /// The kline count, 0 if the stop loss does not depend on klines.
+
+    pub fn klines_needed(&self) -> usize {
+        match self {
+            StopLoss::Atr { period, .. } => period + 1,
+            _ => 0,
+        }
+    }
+
+    /// Resolves the stop loss to a price for a new position.
+    ///
+    /// # Arguments
+    ///
+    /// * `order_side` - Side of the position being opened, the stop is placed below the entry for
+    ///   buys and above the entry for sells.
+    /// * `entry_price` - The price the position is opened at.
+    /// * `klines` - Recent klines in ascending order, only used by `StopLoss::Atr`.
+    ///
+    /// # Returns
+    ///
+    /// The stop loss price, or `None` if there are not enough klines to compute the ATR.
+
+    pub fn resolve(
+        &self,
+        order_side: OrderSide,
+        entry_price: f64,
+        klines: &[Kline],
+    ) -> Option<f64> {
+        let distance = match *self {
+            StopLoss::Price(price) => return Some(price),
+            StopLoss::Percent(percent) => entry_price * percent / 100.0,
+            StopLoss::Atr { period, mult } => calc_atr(klines, period)? * mult,
+        };
+
+        match order_side {
+            OrderSide::Buy => Some(entry_price - distance),
+            OrderSide::Sell => Some(entry_price + distance),
+        }
+    }
+}
+
+/// Deserializes an optional stop loss, also accepting the bare price settings were saved with
+/// before stop loss modes were added, read as `StopLoss::Price`.
+
+pub fn deserialize_stop_loss<'de, D>(deserializer: D) -> Result<Option<StopLoss>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum StopLossSetting {
+        Price(f64),
+        Mode(StopLoss),
+    }
+
+    Ok(
+        Option::<StopLossSetting>::deserialize(deserializer)?.map(|setting| match setting {
+            StopLossSetting::Price(price) => StopLoss::Price(price),
+            StopLossSetting::Mode(stop_loss) => stop_loss,
+        }),
+    )
 }
 
 /// Provides default values for `StrategySettings`.
@@ -648,6 +1244,20 @@ impl Default for StrategySettings {
             margin_usd: 100.0,
             leverage: 1,
             stop_loss: None,
+            signal_cooldown_ms: 0,
+            min_flip_move_pct: 0.0,
+            sizing_mode: None,
+            capital_allocation_usd: None,
+            account_id: None,
+            min_kline_volume: None,
+            eval_log_size: None,
+            eval_offset_secs: None,
+            error_policy: StrategyErrorPolicy::default(),
+            max_hold_secs: None,
+            mode: StrategyMode::default(),
+            signal_sinks: vec![],
+            promote_after: None,
+            audit_klines: false,
         }
     }
 }
@@ -657,6 +1267,10 @@ impl Default for StrategySettings {
 /// Includes details about performance, such as profit, trades, positions, trade counts, and price
 /// information at the start and end of execution. Also covers maximum profit and drawdown
 /// experienced.
+///
+/// Returns are in percent of the capital deployed, which is the margin of the largest entry, not
+/// the notional. The strategy return includes its leverage while buy and hold holds the same
+/// capital unlevered, so `alpha` is what leverage and timing added over simply holding.
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct StrategySummary {
@@ -672,6 +1286,91 @@ pub struct StrategySummary {
     // pub signals: Vec<SignalMessage>,
     pub trades: Vec<TradeTx>,
     pub positions: Vec<Position>,
+    /// Margin of the largest entry, in USD.
+    #[serde(default)]
+    pub capital_deployed: f64,
+    /// Profit in percent of the capital deployed.
+    #[serde(default)]
+    pub strategy_return: f64,
+    /// Return in percent of buying at the start price and selling at the end price.
+    #[serde(default)]
+    pub buy_and_hold_return: f64,
+    /// Strategy return minus buy and hold return, in percentage points.
+    #[serde(default)]
+    pub alpha: f64,
+    /// Klines acted on and skipped by a back test, `None` for live strategies.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub audit: Option<BackTestAudit>,
+}
+
+impl StrategySummary {
+    /// Calculates the strategy and buy and hold returns from the trades and the start and end
+    /// prices of the period, returns are left at zero if there is nothing to compare against.
+
+    pub fn calc_returns(&mut self) {
+        let capital_deployed = self
+            .trades
+            .iter()
+            .map(|trade| to_decimal(trade.position.margin_usd))
+            .max()
+            .unwrap_or_default();
+
+        let hundred = Decimal::ONE_HUNDRED;
+
+        let strategy_return = if capital_deployed > Decimal::ZERO {
+            to_decimal(self.profit) / capital_deployed * hundred
+        } else {
+            Decimal::ZERO
+        };
+
+        let buy_and_hold_return = if self.start_price > 0.0 {
+            (to_decimal(self.end_price) - to_decimal(self.start_price))
+                / to_decimal(self.start_price)
+                * hundred
+        } else {
+            Decimal::ZERO
+        };
+
+        self.capital_deployed = from_decimal(capital_deployed);
+        self.strategy_return = from_decimal(strategy_return);
+        self.buy_and_hold_return = from_decimal(buy_and_hold_return);
+        self.alpha = from_decimal(strategy_return - buy_and_hold_return);
+    }
+
+    /// Reconstructs the period the strategy ran over, to rerun it over the same klines.
+    ///
+    /// The period spans the recorded start and end time of the run and the open and close time
+    /// of every trade, so summaries saved without a start or end time are covered by their trades.
+    ///
+    /// # Returns
+    ///
+    /// The open time of the first kline and the end of the last kline of the period, or `None` if
+    /// the summary has no times to reconstruct it from.
+
+    pub fn run_window(&self) -> Option<(u64, u64)> {
+        let times = self
+            .trades
+            .iter()
+            .flat_map(|trade| [trade.position.open_time, trade.close_time])
+            .chain(
+                self.info
+                    .start_time
+                    .iter()
+                    .chain(self.info.end_time.iter())
+                    .filter_map(|time| string_to_timestamp(time).ok()),
+            );
+
+        let (from_ts, to_ts) = times.fold(None, |window, ts| match window {
+            None => Some((ts, ts)),
+            Some((from_ts, to_ts)) => Some((u64::min(from_ts, ts), u64::max(to_ts, ts))),
+        })?;
+
+        // times are recorded to the second, include the whole second of the last time
+        Some((
+            floor_mili_ts(from_ts, self.info.interval.to_mili()),
+            to_ts + SEC_AS_MILI - 1,
+        ))
+    }
 }
 
 /// Sets default values for `StrategySummary`.
@@ -694,10 +1393,150 @@ impl Default for StrategySummary {
             max_drawdown: 0.0,
             max_profit: 0.0,
             // signals: vec![],
+            capital_deployed: 0.0,
+            strategy_return: 0.0,
+            buy_and_hold_return: 0.0,
+            alpha: 0.0,
+            audit: None,
+        }
+    }
+}
+
+/// Comparison of a saved strategy summary with a rerun of the strategy over the same period.
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SummaryDiff {
+    pub original: StrategySummary,
+    pub rerun: StrategySummary,
+    /// Rerun profit minus original profit.
+    pub profit_diff: f64,
+    /// Rerun trade count minus original trade count.
+    pub trade_count_diff: i64,
+    /// Rerun max drawdown minus original max drawdown.
+    pub max_drawdown_diff: f64,
+}
+
+impl SummaryDiff {
+    /// Compares a rerun summary with the original summary.
+    ///
+    /// # Arguments
+    ///
+    /// * `original` - The saved summary the strategy was rerun from.
+    /// * `rerun` - The summary of the rerun.
+    ///
+    /// # Returns
+    ///
+    /// The `SummaryDiff` of both summaries.
+
+    pub fn new(original: StrategySummary, rerun: StrategySummary) -> Self {
+        Self {
+            profit_diff: sum_decimal([rerun.profit, -original.profit]),
+            trade_count_diff: rerun.trades.len() as i64 - original.trades.len() as i64,
+            max_drawdown_diff: sum_decimal([rerun.max_drawdown, -original.max_drawdown]),
+            original,
+            rerun,
         }
     }
 }
 
+/// A single point on a strategy's drawdown timeline.
+///
+/// Holds the trade breakdown along with the cumulative balance, the running peak balance and
+/// the drawdown from that peak after the trade closed.
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DrawdownPoint {
+    pub trade_id: Uuid,
+    pub close_time: String,
+    pub order_side: OrderSide,
+    pub open_price: f64,
+    pub close_price: f64,
+    pub profit: f64,
+    pub balance: f64,
+    pub peak: f64,
+    pub drawdown: f64,
+}
+
+/// Evaluates a kline with an algorithm, passing it the indicators it declares updated with the
+/// kline, and records the result and the algorithm debug fields in an evaluation log.
+async fn evaluate_kline(
+    algorithm: &ArcMutex<Box<dyn Algorithm>>,
+    indicators: &ArcMutex<IndicatorSet>,
+    eval_log: &ArcMutex<EvalLog>,
+    kline: &Kline,
+    trades: &[Trade],
+) -> AlgoEvalResult {
+    let (result, debug_fields) = {
+        let mut algorithm = algorithm.lock().await;
+        let mut indicators = indicators.lock().await;
+        let ctx = indicators.update(kline);
+        let result = algorithm.evaluate(kline.clone(), trades, ctx);
+        (result, algorithm.debug_fields())
+    };
+
+    eval_log.lock().await.record(kline, result, debug_fields);
+
+    result
+}
+
+/// Closes the positions of a strategy held longer than the max hold duration at the close of a
+/// kline, recording the forced close on the positions.
+async fn close_expired_positions(
+    account: &ArcMutex<Account>,
+    strategy_id: StrategyId,
+    max_hold_secs: u64,
+    kline: &Kline,
+) {
+    let mut account = account.lock().await;
+
+    let trades = account
+        .close_expired_positions(strategy_id, max_hold_secs, kline.close_time, kline.close)
+        .await;
+
+    for trade in trades {
+        info!(
+            "Closed position {} of strategy {strategy_id}, held longer than {max_hold_secs}s",
+            trade.position.id
+        );
+
+        let signal = SignalMessage {
+            strategy_id,
+            order_side: trade.position.order_side,
+            symbol: trade.position.symbol,
+            price: trade.close_price,
+            is_back_test: false,
+            close_time: timestamp_to_string(trade.close_time),
+            interval: None,
+            ty: SignalMessageType::ForcedClose("Max hold duration exceeded".to_string()),
+        };
+
+        account.add_position_meta(trade.position.id, &signal)
+    }
+}
+
+/// Calculates when a strategy should next be evaluated, ie. `offset_secs` after the next close of
+/// a kline of its interval.
+///
+/// # Arguments
+///
+/// * `now` - The current timestamp in milliseconds.
+/// * `last_wake` - The previous evaluation time, a system clock lagging the timer the strategy
+///   slept on would otherwise evaluate the same kline again right away.
+/// * `interval` - The interval of the strategy.
+/// * `offset_secs` - Seconds to wait after the interval close.
+///
+/// # Returns
+///
+/// The timestamp in milliseconds of the next evaluation, always after `now` and `last_wake`.
+
+fn next_eval_time(now: u64, last_wake: u64, interval: Interval, offset_secs: u64) -> u64 {
+    let offset = offset_secs * SEC_AS_MILI;
+    let from = now.max(last_wake);
+    let last_close = floor_mili_ts(from.saturating_sub(offset), interval.to_mili());
+
+    last_close + interval.to_mili() + offset
+}
+
 /// Manages k-line data for a strategy's execution period.
 ///
 /// Tracks the initial and final k-lines, providing strategies with price data at the beginning
@@ -731,21 +1570,542 @@ impl StrategyKlineManager {
         }
     }
 
+    /// Records a kline and checks if it was already evaluated.
+    ///
+    /// A kline is received several times per interval, each update of the same open time
+    /// replaces the recorded last kline so it holds the latest price of the candle. Klines older
+    /// than the last kline are ignored.
+    ///
+    /// * `kline` - The kline received by the strategy.
+    ///
+    /// Returns `true` if the strategy must skip the kline, as its open time was already seen.
+
     pub fn must_continue(&mut self, kline: &Kline) -> bool {
-        let mut must_continue = false;
+        if self.first_kline.is_none() {
+            self.first_kline = Some(kline.clone());
+        }
+
+        let must_continue = match &self.last_kline {
+            Some(last_kline) if kline.open_time < last_kline.open_time => return true,
+            Some(last_kline) => last_kline.open_time == kline.open_time,
+            None => false,
+        };
+
+        self.last_kline = Some(kline.clone());
+
+        must_continue
+    }
+}
 
-        if let Some(last_kline) = &self.last_kline {
-            if last_kline.open_time == kline.open_time {
-                must_continue = true
+#[cfg(test)]
+mod test {
+    use super::*;
+    use tokio::test;
+
+    use crate::{
+        exchange::api::ExchangeApi,
+        market::channel::{build_market_channel, DEFAULT_MARKET_CHANNEL_CAPACITY},
+        storage::{manager::StorageManager, memory::MemoryStorage},
+        testutil::make_klines,
+        utils::{channel::build_arc_channel, kline::build_kline_key},
+    };
+
+    fn build_trades(profits: &[f64]) -> Vec<TradeTx> {
+        profits
+            .iter()
+            .enumerate()
+            .map(|(i, profit)| {
+                let position = Position::new("BTCUSDT", 100.0, OrderSide::Buy, 100.0, 1, None);
+                let mut trade =
+                    TradeTx::new(100.0, 1_700_000_000_000 + i as u64 * MIN_AS_MILI, position);
+                trade.profit = to_decimal(*profit);
+                trade
+            })
+            .collect()
+    }
+
+    #[test]
+    async fn test_next_eval_time_aligns_to_interval_close() {
+        let hour = Interval::Hour1.to_mili();
+        let hour_open = 1_700_002_800_000;
+        assert_eq!(hour_open % hour, 0);
+
+        // evaluated shortly after the hour closes, not before each minute
+        let now = hour_open + 17 * MIN_AS_MILI + 3 * SEC_AS_MILI;
+        assert_eq!(
+            next_eval_time(now, 0, Interval::Hour1, 5),
+            hour_open + hour + 5 * SEC_AS_MILI
+        );
+
+        // within the offset after a close the pending evaluation is not skipped
+        let now = hour_open + 2 * SEC_AS_MILI;
+        assert_eq!(
+            next_eval_time(now, 0, Interval::Hour1, 5),
+            hour_open + 5 * SEC_AS_MILI
+        );
+    }
+
+    #[test]
+    async fn test_next_eval_time_never_immediate() {
+        // every interval waits for a whole interval, so the strategy loop never busy-loops
+        for interval in Interval::ALL {
+            assert!(!interval.to_duration().is_zero());
+
+            let close = 1_700_006_400_000;
+            for now in [
+                close - 1,
+                close,
+                close + 5 * SEC_AS_MILI,
+                close + 6 * SEC_AS_MILI,
+            ] {
+                let wake_time = next_eval_time(now, 0, interval, 5);
+                assert!(wake_time > now, "{interval} evaluates immediately at {now}");
             }
-        } else {
-            self.last_kline = Some(kline.clone());
         }
+    }
 
-        if self.first_kline.is_none() {
-            self.first_kline = Some(kline.clone());
+    #[test]
+    async fn test_eval_loop_waits_when_clock_lags() {
+        for interval in Interval::ALL {
+            let mut now = 1_700_006_400_000 + 2 * SEC_AS_MILI;
+            let mut last_wake = 0;
+
+            for _ in 0..3 {
+                let wake_time = next_eval_time(now, last_wake, interval, 5);
+                assert!(wake_time > now && wake_time > last_wake);
+
+                // each evaluation waits for the next kline to close
+                if last_wake > 0 {
+                    assert_eq!(wake_time, last_wake + interval.to_mili());
+                }
+
+                // the system clock reads a little before the wake time once the strategy wakes
+                last_wake = wake_time;
+                now = wake_time - 3;
+            }
         }
+    }
 
-        must_continue
+    #[test]
+    async fn test_resolve_stop_loss() {
+        // true ranges of the last 3 klines are 4.0, 6.0 and 5.0
+        let klines: Vec<Kline> = [
+            (100.0, 98.0, 99.0),
+            (102.0, 98.0, 101.0),
+            (107.0, 101.0, 103.0),
+            (104.0, 99.0, 100.0),
+        ]
+        .iter()
+        .enumerate()
+        .map(|(i, &(high, low, close))| Kline {
+            symbol: "BTCUSDT".to_string(),
+            interval: Interval::Min1,
+            open_time: 1_700_000_040_000 + i as u64 * MIN_AS_MILI,
+            open: close,
+            high,
+            low,
+            close,
+            volume: 1.0,
+            quote_volume: 0.0,
+            close_time: 1_700_000_040_000 + (i as u64 + 1) * MIN_AS_MILI - 1,
+        })
+        .collect();
+
+        let price = StopLoss::Price(95.0);
+        assert_eq!(price.resolve(OrderSide::Buy, 100.0, &[]), Some(95.0));
+        assert_eq!(price.resolve(OrderSide::Sell, 100.0, &[]), Some(95.0));
+
+        let percent = StopLoss::Percent(2.0);
+        assert_eq!(percent.resolve(OrderSide::Buy, 100.0, &[]), Some(98.0));
+        assert_eq!(percent.resolve(OrderSide::Sell, 100.0, &[]), Some(102.0));
+
+        let atr = StopLoss::Atr {
+            period: 3,
+            mult: 2.0,
+        };
+        assert_eq!(atr.klines_needed(), 4);
+        assert_eq!(atr.resolve(OrderSide::Buy, 98.0, &klines), Some(88.0));
+        assert_eq!(atr.resolve(OrderSide::Sell, 98.0, &klines), Some(108.0));
+
+        // not enough klines for the ATR period
+        assert_eq!(atr.resolve(OrderSide::Buy, 98.0, &klines[1..]), None);
+    }
+
+    #[test]
+    async fn test_stop_loss_setting_accepts_bare_price() {
+        let settings: StrategySettings = serde_json::from_value(json!({
+            "max_open_orders": 1,
+            "margin_usd": 100.0,
+            "leverage": 1,
+            "stop_loss": 49000.0
+        }))
+        .unwrap();
+        assert_eq!(settings.stop_loss, Some(StopLoss::Price(49000.0)));
+
+        let settings: StrategySettings = serde_json::from_value(json!({
+            "max_open_orders": 1,
+            "margin_usd": 100.0,
+            "leverage": 1,
+            "stop_loss": { "Percent": 2.0 }
+        }))
+        .unwrap();
+        assert_eq!(settings.stop_loss, Some(StopLoss::Percent(2.0)));
+
+        let settings: StrategySettings = serde_json::from_value(json!({
+            "max_open_orders": 1,
+            "margin_usd": 100.0,
+            "leverage": 1,
+            "stop_loss": null
+        }))
+        .unwrap();
+        assert_eq!(settings.stop_loss, None);
+    }
+
+    #[test]
+    async fn test_calc_max_drawdown() {
+        let trades = build_trades(&[100.0, -50.0, -80.0, 200.0, -30.0]);
+
+        assert_eq!(Strategy::calc_max_drawdown(&trades), 130.0);
+        assert_eq!(Strategy::calc_max_drawdown(&vec![]), 0.0);
+
+        // losing from the first trade counts from the starting balance
+        let trades = build_trades(&[-20.0, -10.0, 5.0]);
+        assert_eq!(Strategy::calc_max_drawdown(&trades), 30.0);
+    }
+
+    #[test]
+    async fn test_calc_drawdown_timeline() {
+        let trades = build_trades(&[100.0, -50.0, -80.0, 200.0, -30.0]);
+
+        let timeline = Strategy::calc_drawdown_timeline(&trades);
+
+        let balances: Vec<f64> = timeline.iter().map(|p| p.balance).collect();
+        let peaks: Vec<f64> = timeline.iter().map(|p| p.peak).collect();
+        let drawdowns: Vec<f64> = timeline.iter().map(|p| p.drawdown).collect();
+
+        assert_eq!(balances, vec![100.0, 50.0, -30.0, 170.0, 140.0]);
+        assert_eq!(peaks, vec![100.0, 100.0, 100.0, 170.0, 170.0]);
+        assert_eq!(drawdowns, vec![0.0, 50.0, 130.0, 0.0, 30.0]);
+        assert_eq!(timeline[0].trade_id, trades[0].id);
+    }
+
+    #[test]
+    async fn test_trade_aggregates_match_full_recompute() {
+        let mut trades = build_trades(&[100.0, -50.0, -80.0, 200.0, -30.0, 0.1, 0.2]);
+        trades[1].position.order_side = OrderSide::Sell;
+        trades[4].position.order_side = OrderSide::Sell;
+
+        let mut aggregates = TradeAggregates::default();
+        for (i, trade) in trades.iter().enumerate() {
+            aggregates.add_trade(trade);
+            assert_eq!(
+                aggregates,
+                TradeAggregates::from_trades(&trades[..=i].to_vec())
+            );
+        }
+
+        assert_eq!(aggregates.profit(), 140.3);
+        assert_eq!(aggregates.max_profit(), 170.0);
+        assert_eq!(aggregates.max_drawdown(), 130.0);
+        assert_eq!(aggregates.long_trade_count(), 5);
+        assert_eq!(aggregates.short_trade_count(), 2);
+    }
+
+    #[test]
+    async fn test_summary_reads_trades_with_their_signals() {
+        let (_, market_rx) = build_market_channel(DEFAULT_MARKET_CHANNEL_CAPACITY);
+        let exchange_api: Arc<dyn ExchangeApi> = Arc::new(MockExchangeApi::default());
+        let storage_manager: Arc<dyn StorageManager> = Arc::new(MemoryStorage::default());
+        let market = ArcMutex::new(
+            Market::new(market_rx, exchange_api.clone(), storage_manager, false).await,
+        );
+        let (strategy_tx, _) = build_arc_channel::<SignalMessage>();
+        let strategy = Strategy::new(
+            "Rsi",
+            "BTCUSDT",
+            Interval::Min1,
+            strategy_tx,
+            market,
+            StrategySettings::default(),
+            json!({ "rsi_period": 14 }),
+        )
+        .unwrap();
+
+        let account = ArcMutex::new(Account::new(exchange_api, false, true).await);
+        for close_price in [110.0, 90.0] {
+            let mut account = account.lock().await;
+            let position_id = account
+                .open_position(
+                    "BTCUSDT",
+                    100.0,
+                    1,
+                    OrderSide::Buy,
+                    100.0,
+                    Some(strategy.id),
+                    None,
+                )
+                .await
+                .unwrap()
+                .id;
+            let signal = SignalMessage {
+                strategy_id: strategy.id,
+                order_side: OrderSide::Buy,
+                symbol: "BTCUSDT".to_string(),
+                price: 100.0,
+                is_back_test: false,
+                close_time: timestamp_to_string(generate_ts()),
+                interval: Some(Interval::Min1),
+                ty: SignalMessageType::Standard,
+            };
+            account.add_position_meta(position_id, &signal);
+            account.close_position(position_id, close_price).await;
+        }
+
+        let summary = strategy.summary(account.clone()).await;
+        let aggregates = TradeAggregates::from_trades(&summary.trades);
+
+        assert_eq!(summary.trades.len(), 2);
+        assert!(summary
+            .trades
+            .iter()
+            .all(|trade| trade.meta.as_ref().unwrap().signals.len() == 1));
+        assert_eq!(summary.profit, aggregates.profit());
+        assert_eq!(summary.max_drawdown, aggregates.max_drawdown());
+    }
+
+    #[test]
+    async fn test_profit_of_many_small_trades_is_exact() {
+        // 0.1 qty bought at 100.1 and sold at 100.2 makes exactly 0.01 per trade
+        let trades: Vec<TradeTx> = (0..1000)
+            .map(|i| {
+                let mut position = Position::new("BTCUSDT", 100.1, OrderSide::Buy, 10.01, 1, None);
+                position.quantity = 0.1;
+                TradeTx::new(100.2, 1_700_000_000_000 + i * MIN_AS_MILI, position)
+            })
+            .collect();
+
+        assert_eq!(from_decimal(trades[0].profit), 0.01);
+
+        // float addition drifts away from the exact total
+        let float_sum: f64 = trades.iter().map(|trade| from_decimal(trade.profit)).sum();
+        assert_ne!(float_sum, 10.0);
+
+        assert_eq!(Strategy::calc_profit(&trades), 10.0);
+        assert_eq!(Strategy::calc_max_profit(&trades), 10.0);
+        assert_eq!(Strategy::calc_max_drawdown(&trades), 0.0);
+        assert_eq!(
+            Strategy::calc_drawdown_timeline(&trades)
+                .last()
+                .unwrap()
+                .balance,
+            10.0
+        );
+    }
+
+    #[test]
+    async fn test_returns_against_buy_and_hold() {
+        let trade = |open_price: f64, close_price: f64| {
+            let position = Position::new("BTCUSDT", open_price, OrderSide::Buy, 100.0, 2, None);
+            TradeTx::new(close_price, 1_700_000_000_000, position)
+        };
+
+        // 2x leverage on 100 USD margin, +15 and -2 USD
+        let trades = vec![trade(100.0, 107.5), trade(100.0, 99.0)];
+        let mut summary = StrategySummary {
+            profit: Strategy::calc_profit(&trades),
+            trades,
+            start_price: 100.0,
+            end_price: 110.0,
+            ..Default::default()
+        };
+        summary.calc_returns();
+
+        assert_eq!(summary.profit, 13.0);
+        assert_eq!(summary.capital_deployed, 100.0);
+        assert_eq!(summary.strategy_return, 13.0);
+        assert_eq!(summary.buy_and_hold_return, 10.0);
+        assert_eq!(summary.alpha, 3.0);
+
+        // nothing traded and no price data
+        let mut summary = StrategySummary::default();
+        summary.calc_returns();
+        assert_eq!(summary.strategy_return, 0.0);
+        assert_eq!(summary.buy_and_hold_return, 0.0);
+        assert_eq!(summary.alpha, 0.0);
+    }
+
+    #[test]
+    async fn test_kline_manager_tracks_last_kline() {
+        let kline = |i: u64, close: f64| Kline {
+            symbol: "BTCUSDT".to_string(),
+            interval: Interval::Min1,
+            open_time: 1_700_000_040_000 + i * MIN_AS_MILI,
+            open: 100.0,
+            high: close,
+            low: 100.0,
+            close,
+            volume: 1.0,
+            quote_volume: 0.0,
+            close_time: 1_700_000_040_000 + (i + 1) * MIN_AS_MILI - 1,
+        };
+
+        let mut kline_manager = StrategyKlineManager::new();
+
+        assert!(!kline_manager.must_continue(&kline(0, 100.0)));
+        assert!(!kline_manager.must_continue(&kline(1, 101.0)));
+
+        // updates within the same interval are skipped but keep the latest price
+        assert!(kline_manager.must_continue(&kline(1, 102.0)));
+        assert!(!kline_manager.must_continue(&kline(2, 103.0)));
+        assert!(kline_manager.must_continue(&kline(2, 104.0)));
+
+        // late klines of an earlier interval are ignored
+        assert!(kline_manager.must_continue(&kline(1, 99.0)));
+
+        assert_eq!(
+            kline_manager.get_kline(FirstLastEnum::First),
+            Some(kline(0, 100.0))
+        );
+        assert_eq!(
+            kline_manager.get_kline(FirstLastEnum::Last),
+            Some(kline(2, 104.0))
+        );
+    }
+
+    #[test]
+    async fn test_update_params_on_running_strategy() {
+        let (_, market_rx) = build_market_channel(DEFAULT_MARKET_CHANNEL_CAPACITY);
+        let exchange_api: Arc<dyn ExchangeApi> = Arc::new(MockExchangeApi::default());
+        let storage_manager: Arc<dyn StorageManager> = Arc::new(MemoryStorage::default());
+        let market =
+            ArcMutex::new(Market::new(market_rx, exchange_api, storage_manager, false).await);
+        let (strategy_tx, _) = build_arc_channel::<SignalMessage>();
+
+        let strategy = Strategy::new(
+            "Rsi",
+            "BTCUSDT",
+            Interval::Min1,
+            strategy_tx,
+            market,
+            StrategySettings::default(),
+            json!({ "rsi_period": 14 }),
+        )
+        .unwrap();
+
+        // steadily rising closes are overbought once the period is seeded
+        let kline = |i: u64| Kline {
+            symbol: "BTCUSDT".to_string(),
+            interval: Interval::Min1,
+            open_time: 1_700_000_040_000 + i * MIN_AS_MILI,
+            open: 100.0,
+            high: 100.0 + i as f64,
+            low: 100.0,
+            close: 100.0 + i as f64,
+            volume: 1.0,
+            quote_volume: 0.0,
+            close_time: 1_700_000_040_000 + (i + 1) * MIN_AS_MILI - 1,
+        };
+
+        // a 14 kline period isn't seeded after 5 klines
+        for i in 0..5 {
+            strategy.evaluate(&kline(i), &[]).await;
+        }
+        assert_eq!(strategy.algorithm.lock().await.warmup(), 15);
+
+        strategy
+            .set_algorithm_params(json!({ "rsi_period": 3 }), false)
+            .await
+            .unwrap();
+        assert_eq!(strategy.info().await.params, json!({ "rsi_period": 3 }));
+        assert_eq!(strategy.algorithm.lock().await.warmup(), 4);
+
+        // the klines already evaluated seed the new period
+        let result = strategy.evaluate(&kline(5), &[]).await;
+        assert_eq!(result, AlgoEvalResult::Sell);
+        assert_eq!(strategy.algorithm.lock().await.data_points().len(), 6);
+
+        // resetting state clears the accumulated klines
+        strategy
+            .set_algorithm_params(json!({ "rsi_period": 2 }), true)
+            .await
+            .unwrap();
+        assert!(strategy.algorithm.lock().await.data_points().is_empty());
+        assert_eq!(strategy.algorithm.lock().await.warmup(), 3);
+
+        for i in 6..8 {
+            strategy.evaluate(&kline(i), &[]).await;
+        }
+        let result = strategy.evaluate(&kline(8), &[]).await;
+        assert_eq!(result, AlgoEvalResult::Sell);
+
+        // invalid params leave the algorithm as it was
+        assert!(strategy
+            .set_algorithm_params(json!({ "rsi_period": "two" }), true)
+            .await
+            .is_err());
+        assert_eq!(strategy.info().await.params, json!({ "rsi_period": 2 }));
+        assert_eq!(strategy.algorithm.lock().await.data_points().len(), 3);
+    }
+
+    #[test]
+    async fn test_stop_closes_at_latest_kline_without_price() {
+        let (_, market_rx) = build_market_channel(DEFAULT_MARKET_CHANNEL_CAPACITY);
+        // every ticker request fails so the market has no last price
+        let exchange_api: Arc<dyn ExchangeApi> =
+            Arc::new(MockExchangeApi::with_ticker_failures(usize::MAX));
+        let storage_manager: Arc<dyn StorageManager> = Arc::new(MemoryStorage::default());
+
+        let klines = make_klines(
+            "BTCUSDT",
+            Interval::Min1,
+            1_700_000_000_000,
+            &[100.0, 104.0],
+        );
+        storage_manager
+            .save_klines(&klines, &build_kline_key("BTCUSDT", Interval::Min1), false)
+            .await
+            .unwrap();
+
+        let market = ArcMutex::new(
+            Market::new(market_rx, exchange_api.clone(), storage_manager, false).await,
+        );
+        let (strategy_tx, _) = build_arc_channel::<SignalMessage>();
+        let mut strategy = Strategy::new(
+            "Rsi",
+            "BTCUSDT",
+            Interval::Min1,
+            strategy_tx,
+            market.clone(),
+            StrategySettings::default(),
+            json!({ "rsi_period": 14 }),
+        )
+        .unwrap();
+        assert_eq!(market.lock().await.last_price("BTCUSDT").await, None);
+
+        let account = ArcMutex::new(Account::new(exchange_api, false, true).await);
+        account
+            .lock()
+            .await
+            .open_position(
+                "BTCUSDT",
+                100.0,
+                1,
+                OrderSide::Buy,
+                100.0,
+                Some(strategy.id),
+                None,
+            )
+            .await
+            .unwrap();
+
+        let summary = strategy.stop(account.clone(), true).await;
+
+        assert!(account
+            .lock()
+            .await
+            .strategy_positions(strategy.id)
+            .is_empty());
+        assert_eq!(summary.trades.len(), 1);
+        assert_eq!(summary.trades[0].close_price, 104.0);
     }
 }
@@ -1,6 +1,6 @@
 use std::fmt::{self};
 
-use crate::{account::trade::OrderSide, market::kline::Kline};
+use crate::{account::trade::OrderSide, exchange::types::ApiError, market::kline::Kline};
 use serde::{Deserialize, Serialize};
 use serde_json::Error as SerdeJsonError;
 
@@ -10,7 +10,7 @@ use super::strategy::StrategyId;
 ///
 /// This can indicate a recommendation to enter a long position, enter a short position, or to make no trade (ignore).
 
-#[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
 pub enum AlgoEvalResult {
     Buy,
     Sell,
@@ -34,8 +34,13 @@ pub enum FirstLastEnum {
 pub enum AlgoError {
     UnkownName(String),
     UnknownInterval(String),
+    UnknownSymbol(String),
     InvalidParams(String),
     SerdeJsonError(SerdeJsonError),
+    /// The exchange failed a request needed to validate the strategy.
+    Exchange(ApiError),
+    /// The strategy collides with a running strategy.
+    Conflict(String),
 }
 
 impl From<SerdeJsonError> for AlgoError {
@@ -53,8 +58,11 @@ impl fmt::Display for AlgoError {
         match self {
             AlgoError::UnkownName(msg) => write!(f, "Unknown Name error: {}", msg),
             AlgoError::UnknownInterval(msg) => write!(f, "Unknown Interval error: {}", msg),
+            AlgoError::UnknownSymbol(msg) => write!(f, "Unknown Symbol error: {}", msg),
             AlgoError::InvalidParams(msg) => write!(f, "Invalid Params error: {}", msg),
             AlgoError::SerdeJsonError(msg) => write!(f, "Invalid Params error: {}", msg),
+            AlgoError::Exchange(e) => write!(f, "Exchange error: {}", e),
+            AlgoError::Conflict(msg) => write!(f, "Conflict error: {}", msg),
         }
     }
 }
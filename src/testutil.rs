@@ -0,0 +1,231 @@
+//! Builders of market data series, bots and clocks used by tests, so tests don't hand-roll
+//! klines, trades and their setup.
+
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use crate::{
+    account::trade::OrderSide,
+    bot::{RaderBot, DEFAULT_SNAPSHOT_INTERVAL_SECS},
+    exchange::api::ExchangeApi,
+    market::{
+        channel::{build_market_channel, DEFAULT_MARKET_CHANNEL_CAPACITY},
+        interval::Interval,
+        kline::Kline,
+        market::Market,
+        trade::Trade,
+    },
+    storage::{manager::StorageManager, memory::MemoryStorage},
+    utils::time::{floor_mili_ts, Clock},
+};
+
+/// Builds a contiguous series of closed klines with one kline per price.
+///
+/// Each kline opens at the close of the previous kline, the first opens at its own close, and
+/// has a volume of `1.0`.
+///
+/// # Arguments
+///
+/// * `symbol` - The symbol of the klines.
+/// * `interval` - The interval of the klines.
+/// * `start_ts` - Timestamp the series starts at, floored to the interval.
+/// * `prices` - The close price of each kline.
+///
+/// # Returns
+///
+/// The klines in open time order.
+
+pub fn make_klines(symbol: &str, interval: Interval, start_ts: u64, prices: &[f64]) -> Vec<Kline> {
+    let step = interval.to_mili();
+    let start = floor_mili_ts(start_ts, step);
+
+    prices
+        .iter()
+        .enumerate()
+        .map(|(i, &close)| {
+            let open = if i == 0 { close } else { prices[i - 1] };
+            let open_time = start + i as u64 * step;
+
+            Kline {
+                symbol: symbol.to_string(),
+                interval,
+                open,
+                high: open.max(close),
+                low: open.min(close),
+                close,
+                volume: 1.0,
+                quote_volume: 0.0,
+                open_time,
+                close_time: open_time + step - 1,
+            }
+        })
+        .collect()
+}
+
+/// Builds a series of trades with one trade per price.
+///
+/// Trades have a quantity of `1.0`, trades at or above the previous price are buys and trades
+/// below it are sells.
+///
+/// # Arguments
+///
+/// * `symbol` - The symbol of the trades.
+/// * `start_ts` - Timestamp of the first trade.
+/// * `step` - Milliseconds between trades.
+/// * `prices` - The price of each trade.
+///
+/// # Returns
+///
+/// The trades in timestamp order.
+
+pub fn make_trades(symbol: &str, start_ts: u64, step: u64, prices: &[f64]) -> Vec<Trade> {
+    prices
+        .iter()
+        .enumerate()
+        .map(|(i, &price)| {
+            let order_side = if i > 0 && price < prices[i - 1] {
+                OrderSide::Sell
+            } else {
+                OrderSide::Buy
+            };
+
+            Trade {
+                symbol: symbol.to_string(),
+                timestamp: start_ts + i as u64 * step,
+                qty: 1.0,
+                price,
+                order_side,
+                id: None,
+            }
+        })
+        .collect()
+}
+
+/// Builds a market on an exchange without starting its workers, storing its data in memory.
+///
+/// # Arguments
+///
+/// * `exchange_api` - The exchange of the market.
+///
+/// # Returns
+///
+/// The market.
+
+pub async fn test_market(exchange_api: Arc<dyn ExchangeApi>) -> Market {
+    let (_, market_rx) = build_market_channel(DEFAULT_MARKET_CHANNEL_CAPACITY);
+    let storage_manager: Arc<dyn StorageManager> = Arc::new(MemoryStorage::default());
+
+    Market::new(market_rx, exchange_api, storage_manager, false).await
+}
+
+/// Builds a dry run bot reading market data and trading on one exchange, storing its data in
+/// memory.
+///
+/// # Arguments
+///
+/// * `exchange_api` - The exchange of the bot.
+///
+/// # Returns
+///
+/// The bot, its background tasks are started.
+
+pub async fn test_bot(exchange_api: Arc<dyn ExchangeApi>) -> RaderBot {
+    test_bot_with(
+        exchange_api.clone(),
+        exchange_api,
+        Arc::new(MemoryStorage::default()),
+    )
+    .await
+}
+
+/// Builds a dry run bot with separate data and execution exchanges and the given storage.
+///
+/// # Arguments
+///
+/// * `data_exchange_api` - The exchange market data is read from.
+/// * `execution_exchange_api` - The exchange the default account trades on.
+/// * `storage_manager` - The storage of the bot.
+///
+/// # Returns
+///
+/// The bot, its background tasks are started.
+
+pub async fn test_bot_with(
+    data_exchange_api: Arc<dyn ExchangeApi>,
+    execution_exchange_api: Arc<dyn ExchangeApi>,
+    storage_manager: Arc<dyn StorageManager>,
+) -> RaderBot {
+    let (market_tx, market_rx) = build_market_channel(DEFAULT_MARKET_CHANNEL_CAPACITY);
+
+    RaderBot::from_exchanges(
+        data_exchange_api,
+        execution_exchange_api,
+        market_tx,
+        market_rx,
+        storage_manager,
+        true,
+        Duration::from_secs(DEFAULT_SNAPSHOT_INTERVAL_SECS),
+    )
+    .await
+}
+
+/// Clock whose time is set by the test.
+
+pub struct TestClock {
+    now: AtomicU64,
+}
+
+impl TestClock {
+    /// Creates a clock reading `now` until it's set.
+
+    pub fn new(now: u64) -> Self {
+        Self {
+            now: AtomicU64::new(now),
+        }
+    }
+
+    /// Sets the current timestamp in milliseconds.
+
+    pub fn set(&self, now: u64) {
+        self.now.store(now, Ordering::SeqCst);
+    }
+}
+
+impl Clock for TestClock {
+    fn now(&self) -> u64 {
+        self.now.load(Ordering::SeqCst)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use tokio::test;
+
+    #[test]
+    async fn test_make_klines_contiguous() {
+        let interval = Interval::Min15;
+        let klines = make_klines("BTCUSDT", interval, 1_700_000_123_456, &[1.0, 3.0, 2.0]);
+
+        assert_eq!(klines.len(), 3);
+        for kline in &klines {
+            assert_eq!(kline.open_time % interval.to_mili(), 0);
+            assert_eq!(kline.close_time, kline.open_time + interval.to_mili() - 1);
+        }
+        for pair in klines.windows(2) {
+            assert_eq!(pair[1].open_time, pair[0].close_time + 1);
+            assert_eq!(pair[1].open, pair[0].close);
+        }
+        assert_eq!((klines[1].low, klines[1].high), (1.0, 3.0));
+
+        let trades = make_trades("BTCUSDT", 1_700_000_000_000, 100, &[1.0, 3.0, 2.0]);
+        assert_eq!(trades[2].timestamp, 1_700_000_000_200);
+        assert_eq!(trades[2].order_side, OrderSide::Sell);
+    }
+}
@@ -12,5 +12,8 @@ pub fn build_bson_kline_meta(kline: &Kline) -> String {
 }
 
 pub fn build_bson_trade_meta(trade: &Trade) -> String {
-    format!("{}@{}", trade.timestamp, trade.order_side).to_string()
+    match trade.id {
+        Some(id) => format!("{}@{id}", trade.timestamp),
+        None => format!("{}@{}", trade.timestamp, trade.order_side),
+    }
 }
@@ -49,3 +49,26 @@ pub fn has_header(file_path: &str, expected_header: &[&str]) -> Result<bool> {
         None => Ok(false),
     }
 }
+
+/// Counts the non-empty rows of a CSV file without a header.
+///
+/// # Parameters
+///
+/// * `file_path`: Path of the CSV file.
+///
+/// # Returns
+///
+/// A `Result<usize>` with the number of rows, or `Err` if the file can't be read.
+pub fn count_rows(file_path: impl AsRef<std::path::Path>) -> Result<usize> {
+    let file = File::open(file_path)?;
+    let reader = BufReader::new(file);
+
+    let mut count = 0;
+    for line in reader.lines() {
+        if !line?.trim().is_empty() {
+            count += 1;
+        }
+    }
+
+    Ok(count)
+}
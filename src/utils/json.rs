@@ -3,6 +3,122 @@ use flate2::read::GzDecoder;
 use serde_json::Value;
 use std::io::Read;
 
+use crate::exchange::api::SymbolInfo;
+
+/// Keys of market data fields holding a price, rounded to the symbol's tick size.
+const PRICE_KEYS: [&str; 7] = [
+    "open",
+    "high",
+    "low",
+    "close",
+    "price",
+    "last_price",
+    "open_price",
+];
+
+/// Keys of market data fields holding a quantity, rounded to the symbol's step size.
+const QTY_KEYS: [&str; 3] = ["volume", "traded_vol", "qty"];
+
+/// Global formatting applied to JSON responses of the market endpoints.
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ResponseFormat {
+    /// Pretty print the JSON body instead of writing it compact.
+    pub pretty: bool,
+    /// Round price and quantity fields to the symbol's precision.
+    pub round_precision: bool,
+}
+
+impl ResponseFormat {
+    /// Builds the response format from the `RESPONSE_FORMAT` and `RESPONSE_PRECISION` settings.
+    ///
+    /// # Arguments
+    ///
+    /// * `format` - `PRETTY` to pretty print responses, anything else writes compact JSON.
+    /// * `precision` - `RAW` to keep full float precision, anything else rounds to the symbol.
+
+    pub fn from_settings(format: &str, precision: &str) -> Self {
+        Self {
+            pretty: format == "PRETTY",
+            round_precision: precision != "RAW",
+        }
+    }
+}
+
+/// Rounds all price and quantity fields found in a JSON value to the symbol's precision.
+///
+/// Nested objects and arrays are walked, so a whole response can be rounded at once.
+///
+/// # Arguments
+///
+/// * `value` - The JSON value to round in place.
+/// * `symbol_info` - Trading rules of the symbol the values belong to.
+
+pub fn round_market_values(value: &mut Value, symbol_info: &SymbolInfo) {
+    match value {
+        Value::Object(map) => {
+            for (key, val) in map.iter_mut() {
+                match val.as_f64() {
+                    Some(num) if PRICE_KEYS.contains(&key.as_str()) => {
+                        *val = Value::from(symbol_info.round_price(num));
+                    }
+                    Some(num) if QTY_KEYS.contains(&key.as_str()) => {
+                        *val = Value::from(symbol_info.round_qty(num));
+                    }
+                    _ => round_market_values(val, symbol_info),
+                }
+            }
+        }
+        Value::Array(values) => {
+            for val in values.iter_mut() {
+                round_market_values(val, symbol_info);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Applies a JSON merge patch to a value, ie. to override some parameters of an algorithm.
+///
+/// Objects are merged key by key, keys set to `null` in the patch are removed and any other
+/// patch value replaces the target.
+///
+/// # Arguments
+///
+/// * `target` - The JSON value to patch in place.
+/// * `patch` - The merge patch.
+
+pub fn merge_patch(target: &mut Value, patch: &Value) {
+    let Value::Object(patch) = patch else {
+        *target = patch.clone();
+        return;
+    };
+
+    if !target.is_object() {
+        *target = Value::Object(Default::default());
+    }
+
+    // SAFETY: target is an object
+    let target = target.as_object_mut().unwrap();
+    for (key, value) in patch {
+        if value.is_null() {
+            target.remove(key);
+        } else {
+            merge_patch(target.entry(key.as_str()).or_insert(Value::Null), value);
+        }
+    }
+}
+
+/// Serializes a JSON value as either pretty or compact text.
+
+pub fn to_json_string(value: &Value, pretty: bool) -> String {
+    if pretty {
+        serde_json::to_string_pretty(value).unwrap_or_else(|_| value.to_string())
+    } else {
+        value.to_string()
+    }
+}
+
 pub fn _parse_gzip_to_json(gzip_data: Vec<u8>) -> Result<Value, Box<dyn std::error::Error>> {
     let mut decoder = GzDecoder::new(gzip_data.as_slice());
     let mut json_string = String::new();
@@ -21,6 +137,9 @@ mod tests {
     use serde_json::json;
     use std::io::Write;
 
+    use crate::account::trade::ContractType;
+    use crate::exchange::api::{SymbolStatus, DEFAULT_MAX_LEVERAGE, DEFAULT_MIN_LEVERAGE};
+
     #[test]
     fn test_parse_gzip_to_json() {
         // Test with valid gzip data
@@ -40,6 +159,35 @@ mod tests {
         // Add more test cases as needed to cover different scenarios
     }
 
+    #[test]
+    fn test_round_market_values() {
+        let symbol_info = SymbolInfo {
+            symbol: "BTCUSDT".to_string(),
+            tick_size: 0.1,
+            step_size: 0.001,
+            min_leverage: DEFAULT_MIN_LEVERAGE,
+            max_leverage: DEFAULT_MAX_LEVERAGE,
+            contract_type: ContractType::Linear,
+            min_notional: 0.0,
+            status: SymbolStatus::Trading,
+        };
+
+        let close = 26696.1 + 0.02;
+        let mut value = json!({
+            "last_kline": { "close": close, "volume": 1.23456, "open_time": 1700000000000u64 },
+            "trades": [{ "price": 26696.16, "qty": 0.5 }]
+        });
+
+        round_market_values(&mut value, &symbol_info);
+
+        let body = to_json_string(&value, false);
+        assert!(body.contains("\"close\":26696.1,"), "{body}");
+        assert!(body.contains("\"volume\":1.235"), "{body}");
+        assert!(body.contains("\"open_time\":1700000000000"), "{body}");
+        assert!(body.contains("\"price\":26696.2"), "{body}");
+        assert!(body.contains("\"qty\":0.5"), "{body}");
+    }
+
     // Helper function to compress JSON into gzip data
     fn compress_json(json: &serde_json::Value) -> Vec<u8> {
         let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
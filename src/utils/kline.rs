@@ -2,14 +2,17 @@ use chrono::Datelike;
 use log::info;
 use uuid::Uuid;
 
-use std::fs::File;
+use std::{collections::HashSet, fs::File};
 
 use crate::{
     market::{
         interval::Interval,
         kline::{BinanceKline, Kline},
     },
-    utils::{csv::has_header, time::timestamp_to_datetime},
+    utils::{
+        csv::has_header,
+        time::{add_month_to_timestamp, floor_month_ts, timestamp_to_datetime},
+    },
 };
 use csv::Reader;
 
@@ -79,6 +82,7 @@ pub fn load_binance_klines(
                     close: binance_kline.close,
                     volume: binance_kline.volume,
                     close_time: binance_kline.close_time,
+                    quote_volume: binance_kline.quote_volume,
                 };
                 klines.push(kline);
             }
@@ -142,6 +146,9 @@ pub fn save_klines(filename: std::path::PathBuf, klines: &[Kline], _append: bool
 
 /// Generates filenames for saving k-line data based on a key, from and to timestamps.
 ///
+/// Both timestamps are inclusive, the file of the month `to_ts` falls in is included even if
+/// `to_ts` is the first millisecond of the month.
+///
 /// # Arguments
 ///
 /// * `kline_key` - A key representing the k-line data set.
@@ -150,29 +157,22 @@ pub fn save_klines(filename: std::path::PathBuf, klines: &[Kline], _append: bool
 ///
 /// # Returns
 ///
-/// A vector of filenames as `String`.
+/// A vector of filenames as `String`, one for each month in the range, empty if `to_ts` is
+/// before `from_ts`.
 pub fn generate_kline_filenames_in_range(kline_key: &str, from_ts: u64, to_ts: u64) -> Vec<String> {
-    let from_date = timestamp_to_datetime(from_ts);
-    let to_date = timestamp_to_datetime(to_ts);
+    let mut filenames = Vec::new();
 
-    let from_year = from_date.year() as u32;
-    let from_month = from_date.month();
-    let to_year = to_date.year() as u32;
-    let to_month = to_date.month();
+    if to_ts < from_ts {
+        return filenames;
+    }
 
-    let mut filenames = Vec::new();
+    let end_month = floor_month_ts(to_ts);
 
-    let mut current_year = from_year;
-    let mut current_month = from_month;
-    while current_year < to_year || (current_year == to_year && current_month <= to_month) {
-        let filename = build_kline_filename_from_year_month(kline_key, current_year, current_month);
-        filenames.push(filename);
+    let mut current_month = floor_month_ts(from_ts);
+    while current_month <= end_month {
+        filenames.push(build_kline_filename(kline_key, current_month));
 
-        current_month += 1;
-        if current_month > 12 {
-            current_month = 1;
-            current_year += 1;
-        }
+        current_month = add_month_to_timestamp(current_month as i64) as u64;
     }
 
     filenames
@@ -192,6 +192,102 @@ pub fn get_min_max_open_time(klines: &[Kline]) -> (u64, u64) {
     (min_time, max_time)
 }
 
+/// Largest number of klines a gap check may expect, longer ranges are refused rather than
+/// enumerating every open time of the range.
+pub const MAX_GAP_CHECK_KLINES: u64 = 100_000;
+
+/// Groups missing open times into contiguous ranges.
+///
+/// # Arguments
+///
+/// * `missing` - Missing open times in ascending order.
+/// * `interval` - The interval of the klines.
+///
+/// # Returns
+///
+/// The open time of the first and the close time of the last missing kline of every run of
+/// consecutive missing klines.
+pub fn group_missing_open_times(missing: &[u64], interval: Interval) -> Vec<(u64, u64)> {
+    let step = interval.to_mili();
+    let mut ranges: Vec<(u64, u64)> = vec![];
+
+    for &open_time in missing {
+        match ranges.last_mut() {
+            Some((_, close_time)) if *close_time + 1 == open_time => {
+                *close_time = open_time + step - 1;
+            }
+            _ => ranges.push((open_time, open_time + step - 1)),
+        }
+    }
+
+    ranges
+}
+
+/// Finds the open times of all klines missing from a series within a time range.
+///
+/// Expected open times are aligned to the interval, starting at the first interval
+/// boundary at or after `from_ts` and stepping by the interval length up to `to_ts`.
+///
+/// # Arguments
+///
+/// * `klines` - The stored klines to check, in any order.
+/// * `interval` - The interval of the klines.
+/// * `from_ts` - The start of the range in milliseconds.
+/// * `to_ts` - The end of the range in milliseconds.
+///
+/// # Returns
+///
+/// A vector of missing open times in ascending order.
+pub fn find_missing_open_times(
+    klines: &[Kline],
+    interval: Interval,
+    from_ts: u64,
+    to_ts: u64,
+) -> Vec<u64> {
+    let step = interval.to_mili();
+    let first_open_time = from_ts.div_ceil(step) * step;
+
+    if first_open_time > to_ts {
+        return vec![];
+    }
+
+    let open_times: HashSet<u64> = klines.iter().map(|kline| kline.open_time).collect();
+
+    (0..=interval.count_between(first_open_time, to_ts))
+        .map(|i| first_open_time + i * step)
+        .filter(|open_time| !open_times.contains(open_time))
+        .collect()
+}
+
+/// Calculates the average true range over the last `period` klines.
+///
+/// # Arguments
+///
+/// * `klines` - Klines in ascending order of open time.
+/// * `period` - Number of true ranges to average.
+///
+/// # Returns
+///
+/// The average true range, or `None` if there are fewer than `period + 1` klines.
+pub fn calc_atr(klines: &[Kline], period: usize) -> Option<f64> {
+    if period == 0 || klines.len() < period + 1 {
+        return None;
+    }
+
+    let true_ranges: Vec<f64> = klines[klines.len() - period - 1..]
+        .windows(2)
+        .map(|pair| {
+            let prev_close = pair[0].close;
+            let kline = &pair[1];
+            (kline.high - kline.low)
+                .max((kline.high - prev_close).abs())
+                .max((kline.low - prev_close).abs())
+        })
+        .collect();
+
+    Some(true_ranges.iter().sum::<f64>() / period as f64)
+}
+
 pub fn build_kline_key(symbol: &str, interval: Interval) -> String {
     format!("{}@kline_{}", symbol, interval)
 }
@@ -200,15 +296,18 @@ pub fn build_ticker_key(symbol: &str) -> String {
     format!("{}@ticker", symbol)
 }
 
+/// Builds the filename of the daily file holding the ticker samples of a ticker key.
+
+pub fn build_ticker_filename(ticker_key: &str, timestamp: u64) -> String {
+    let date_str = timestamp_to_datetime(timestamp).format("%Y-%m-%d");
+    format!("{ticker_key}-{date_str}.csv")
+}
+
 pub fn build_kline_filename(kline_key: &str, timestamp: u64) -> String {
     let month_str = build_kline_month_string(timestamp);
     format!("{kline_key}-{month_str}.csv")
 }
 
-pub fn build_kline_filename_from_year_month(kline_key: &str, year: u32, month: u32) -> String {
-    format!("{kline_key}-{:04}-{:02}.csv", year, month)
-}
-
 pub fn build_kline_month_string(timestamp: u64) -> String {
     let timestamp = timestamp_to_datetime(timestamp);
     timestamp.format("%Y-%m").to_string()
@@ -216,5 +315,139 @@ pub fn build_kline_month_string(timestamp: u64) -> String {
 
 #[cfg(test)]
 mod tests {
-    // TODO: Write tests
+    use super::*;
+    use crate::utils::time::{DAY_AS_MILI, MIN_AS_MILI};
+
+    fn build_klines(interval: Interval, open_times: &[u64]) -> Vec<Kline> {
+        open_times
+            .iter()
+            .map(|&open_time| Kline {
+                symbol: "BTCUSDT".to_string(),
+                interval,
+                open_time,
+                open: 100.0,
+                high: 100.0,
+                low: 100.0,
+                close: 100.0,
+                volume: 1.0,
+                quote_volume: 0.0,
+                close_time: open_time + interval.to_mili() - 1,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_find_missing_open_times() {
+        let interval = Interval::Min5;
+        let step = interval.to_mili();
+        let start = 1_700_000_100_000;
+
+        // gaps at the 3rd, 6th and 7th candle
+        let stored: Vec<u64> = [0, 1, 3, 4, 7, 8]
+            .iter()
+            .map(|i| start + i * step)
+            .collect();
+        let klines = build_klines(interval, &stored);
+
+        let missing = find_missing_open_times(&klines, interval, start, start + 8 * step);
+        assert_eq!(
+            missing,
+            vec![start + 2 * step, start + 5 * step, start + 6 * step]
+        );
+
+        // range starting mid candle is aligned to the next open time
+        let missing = find_missing_open_times(&klines, interval, start + 1, start + 8 * step);
+        assert_eq!(
+            missing,
+            vec![start + 2 * step, start + 5 * step, start + 6 * step]
+        );
+
+        // missing candles past the end of the stored series
+        let missing = find_missing_open_times(&klines, interval, start, start + 10 * step);
+        assert_eq!(missing.len(), 5);
+        assert_eq!(missing[3..], [start + 9 * step, start + 10 * step]);
+    }
+
+    #[test]
+    fn test_group_missing_open_times() {
+        let interval = Interval::Min5;
+        let step = interval.to_mili();
+        let start = 1_700_000_100_000;
+
+        let missing = [start + 2 * step, start + 5 * step, start + 6 * step];
+        assert_eq!(
+            group_missing_open_times(&missing, interval),
+            vec![
+                (start + 2 * step, start + 3 * step - 1),
+                (start + 5 * step, start + 7 * step - 1)
+            ]
+        );
+        assert!(group_missing_open_times(&[], interval).is_empty());
+    }
+
+    #[test]
+    fn test_find_missing_open_times_complete() {
+        let interval = Interval::Min1;
+        let start = 1_700_000_040_000;
+        let stored: Vec<u64> = (0..60).map(|i| start + i * MIN_AS_MILI).collect();
+        let klines = build_klines(interval, &stored);
+
+        let missing = find_missing_open_times(&klines, interval, start, start + 59 * MIN_AS_MILI);
+        assert!(missing.is_empty());
+
+        // empty range
+        let missing = find_missing_open_times(&klines, interval, start + 1, start + 2);
+        assert!(missing.is_empty());
+    }
+
+    #[test]
+    fn test_kline_filenames_include_month_boundaries() {
+        let kline_key = "BTCUSDT@kline_1m";
+        // 2024-03-01T00:00:00Z
+        let march = 1_709_251_200_000;
+
+        // a range ending on the first millisecond of a month includes that month
+        assert_eq!(
+            generate_kline_filenames_in_range(kline_key, march - DAY_AS_MILI, march),
+            vec![
+                "BTCUSDT@kline_1m-2024-02.csv".to_string(),
+                "BTCUSDT@kline_1m-2024-03.csv".to_string()
+            ]
+        );
+        assert_eq!(
+            generate_kline_filenames_in_range(kline_key, march - DAY_AS_MILI, march - 1),
+            vec!["BTCUSDT@kline_1m-2024-02.csv".to_string()]
+        );
+
+        // a range within one month yields that month
+        assert_eq!(
+            generate_kline_filenames_in_range(kline_key, march, march),
+            vec!["BTCUSDT@kline_1m-2024-03.csv".to_string()]
+        );
+        assert_eq!(
+            generate_kline_filenames_in_range(kline_key, march + MIN_AS_MILI, march + DAY_AS_MILI),
+            vec!["BTCUSDT@kline_1m-2024-03.csv".to_string()]
+        );
+
+        assert!(generate_kline_filenames_in_range(kline_key, march, march - 1).is_empty());
+    }
+
+    #[test]
+    fn test_kline_filenames_span_year_rollover() {
+        // 2023-11-15T00:00:00Z to 2024-01-01T00:00:00Z
+        let filenames = generate_kline_filenames_in_range(
+            "BTCUSDT@kline_1h",
+            1_700_006_400_000,
+            1_704_067_200_000,
+        );
+
+        assert_eq!(
+            filenames,
+            vec![
+                "BTCUSDT@kline_1h-2023-11.csv".to_string(),
+                "BTCUSDT@kline_1h-2023-12.csv".to_string(),
+                "BTCUSDT@kline_1h-2024-01.csv".to_string()
+            ]
+        );
+    }
 }
@@ -1,4 +1,6 @@
 use rand::Rng;
+use rust_decimal::prelude::{FromPrimitive, ToPrimitive};
+use rust_decimal::{Decimal, RoundingStrategy};
 
 use crate::exchange::types::ApiError;
 use crate::exchange::types::ApiResult;
@@ -95,6 +97,74 @@ pub fn _gen_random_milliseconds() -> u64 {
     rand::thread_rng().gen_range(1000..3000)
 }
 
+/// Converts a float to a `Decimal` so money can be added up without float rounding errors.
+///
+/// # Arguments
+///
+/// * `value` - The float to convert, NaN and infinite values convert to zero.
+///
+/// # Returns
+///
+/// The `Decimal` closest to the shortest representation of the float.
+pub fn to_decimal(value: f64) -> Decimal {
+    Decimal::from_f64(value).unwrap_or_default()
+}
+
+/// Converts a `Decimal` back to a float, used where amounts are serialized.
+///
+/// # Arguments
+///
+/// * `value` - The `Decimal` to convert.
+///
+/// # Returns
+///
+/// The float closest to the decimal value.
+pub fn from_decimal(value: Decimal) -> f64 {
+    value.to_f64().unwrap_or_default()
+}
+
+/// Sums floats as decimals, so many small amounts add up to their exact total.
+///
+/// # Arguments
+///
+/// * `values` - The floats to sum.
+///
+/// # Returns
+///
+/// The total as a float.
+pub fn sum_decimal(values: impl IntoIterator<Item = f64>) -> f64 {
+    from_decimal(values.into_iter().map(to_decimal).sum())
+}
+
+/// Rounds a float to a number of decimals, half away from zero.
+///
+/// # Arguments
+///
+/// * `value` - The float to round.
+/// * `decimals` - The number of decimals to keep.
+///
+/// # Returns
+///
+/// The rounded float.
+pub fn round_decimal(value: f64, decimals: u32) -> f64 {
+    from_decimal(
+        to_decimal(value).round_dp_with_strategy(decimals, RoundingStrategy::MidpointAwayFromZero),
+    )
+}
+
+/// Counts the decimal places of a float, ie. `2` for `0.05` and `0` for `10.0`.
+///
+/// # Arguments
+///
+/// * `value` - The float to count the decimal places of.
+///
+/// # Returns
+///
+/// The number of decimals of the shortest representation of the float.
+pub fn decimal_places(value: f64) -> usize {
+    to_decimal(value).normalize().scale() as usize
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
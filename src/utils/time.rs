@@ -6,6 +6,7 @@ use chrono::NaiveDateTime;
 use chrono::TimeZone;
 use chrono::Utc;
 use dateparser::parse;
+use serde::{de::Error, Deserialize, Deserializer};
 
 use std::time::{Duration, SystemTime};
 
@@ -75,11 +76,11 @@ pub fn string_to_timestamp(date_str: &str) -> Result<u64, &'static str> {
         if let Ok(ts) = s_ts.parse::<u64>() {
             return Ok(ts);
         } else {
-            return Err("Unable to parse date string");
+            return Err("date is before the UNIX epoch");
         }
     };
 
-    Err("Unable to parse date string")
+    Err("date format is not recognised")
 }
 
 /// Converts a UNIX timestamp in milliseconds to a date string in ISO 8601 format.
@@ -174,13 +175,9 @@ pub fn calculate_kline_open_time(close_time: u64, interval: Interval) -> u64 {
 ///
 /// A `Result<Duration, &'static str>` which is Ok containing the `Duration` if the interval is supported, or an Err with an error message.
 pub fn build_interval(interval: &str) -> Result<Duration, &'static str> {
-    match interval {
-        "1m" => Ok(Duration::from_secs(60)),
-        "5m" => Ok(Duration::from_secs(300)),
-        "15m" => Ok(Duration::from_secs(900)),
-        "1h" => Ok(Duration::from_secs(3600)),
-        _ => Err("Unsupported interval"),
-    }
+    interval_to_millis(interval)
+        .map(Duration::from_millis)
+        .map_err(|_| "Unsupported interval")
 }
 
 // TODO: docs
@@ -235,13 +232,53 @@ pub fn elapsed_time(start: Instant) -> actix_web::cookie::time::Duration {
     start.elapsed()
 }
 
-pub fn interval_to_millis(interval: &str) -> u64 {
-    match interval {
-        "1m" => MIN_AS_MILI,
-        "5m" => MIN_AS_MILI * 5,
-        "15m" => MIN_AS_MILI * 15,
-        "1h" => HOUR_AS_MILI,
-        _ => SEC_AS_MILI,
+/// Converts an interval string, ie. `5m`, to its length in milliseconds.
+///
+/// # Arguments
+///
+/// * `interval` - The interval string to convert.
+///
+/// # Returns
+///
+/// The length of the interval, or an error for an unsupported interval rather than a default
+/// length which would silently break freshness windows and evaluation timing.
+pub fn interval_to_millis(interval: &str) -> Result<u64, &'static str> {
+    Interval::try_from(interval).map(|interval| interval.to_mili())
+}
+
+/// Deserializes a timestamp in milliseconds which may have been saved as a date string, ie.
+/// `2024-03-01T20:00:00Z`, by earlier versions.
+
+pub fn deserialize_timestamp<'de, D>(deserializer: D) -> Result<u64, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Timestamp {
+        Mili(u64),
+        Date(String),
+    }
+
+    match Timestamp::deserialize(deserializer)? {
+        Timestamp::Mili(ts) => Ok(ts),
+        Timestamp::Date(date) => string_to_timestamp(&date).map_err(D::Error::custom),
+    }
+}
+
+/// Source of the current time, injected into time driven components so tests can control it.
+pub trait Clock: Send + Sync {
+    /// Current timestamp in milliseconds.
+    fn now(&self) -> u64;
+}
+
+/// Clock reading the system time.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> u64 {
+        generate_ts()
     }
 }
 
@@ -275,6 +312,17 @@ mod tests {
         assert!(result.is_none());
     }
 
+    #[test]
+    fn test_interval_to_millis() {
+        assert_eq!(interval_to_millis("5m"), Ok(5 * MIN_AS_MILI));
+        assert!(interval_to_millis("7m").is_err());
+        assert!(interval_to_millis("").is_err());
+
+        // an unsupported interval never becomes a zero length sleep
+        assert_eq!(build_interval("1m"), Ok(Duration::from_secs(60)));
+        assert!(build_interval("7m").is_err());
+    }
+
     #[test]
     fn test_string_to_timestamp() {
         let ts = 1640995200000;
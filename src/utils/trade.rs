@@ -21,6 +21,7 @@ use crate::{
 };
 use csv::Reader;
 
+use super::number::sum_decimal;
 use super::time::{floor_mili_ts, string_to_timestamp, SEC_AS_MILI};
 
 #[derive(Deserialize)]
@@ -88,6 +89,7 @@ pub fn load_binance_agg_trades(file_path: std::path::PathBuf, symbol: &str) -> V
                 symbol: symbol.to_string(),
                 qty: row.quantity,
                 order_side,
+                id: Some(row.agg_trade_id),
             };
 
             market_data.add_trade(&mut market_trade);
@@ -138,6 +140,18 @@ pub fn build_market_trade_filename(trade_key: &str, timestamp: u64) -> String {
     format!("{trade_key}-{date_str}.csv")
 }
 
+/// Generates the filenames of the daily trade files within a range, both timestamps are
+/// inclusive so a range ending on the first millisecond of a day includes that day.
+///
+/// # Arguments
+///
+/// * `trade_key` - A key representing the trade data set.
+/// * `from_ts` - The starting UNIX timestamp for the trade data.
+/// * `to_ts` - The ending UNIX timestamp for the trade data.
+///
+/// # Returns
+///
+/// A vector of filenames as `String`, one for each day in the range.
 pub fn generate_trade_filenames_in_range(trade_key: &str, from_ts: u64, to_ts: u64) -> Vec<String> {
     let start_day = floor_mili_ts(from_ts, DAY_AS_MILI);
     let end_day = floor_mili_ts(to_ts, DAY_AS_MILI);
@@ -171,16 +185,54 @@ pub fn calc_min_max(trades: &[Trade]) -> (f64, f64) {
     (min_price, max_price)
 }
 
+/// Sums the volumes of buckets as decimals, so the total matches the sum of the bucket volumes
+/// as displayed.
 pub fn calc_total_volume(buckets: &BTreeMap<String, BucketVolume>) -> BucketVolume {
-    let mut volume = BucketVolume::default();
-
-    for val in buckets.values() {
-        volume.buy_volume += val.buy_volume;
-        volume.sell_volume += val.sell_volume
+    BucketVolume {
+        buy_volume: sum_decimal(buckets.values().map(|bucket| bucket.buy_volume)),
+        sell_volume: sum_decimal(buckets.values().map(|bucket| bucket.sell_volume)),
     }
+}
 
-    volume
+/// Rounds the volumes of buckets to a number of decimals, removing the float error accumulated
+/// by summing many trade quantities.
+pub fn round_bucket_volumes(
+    buckets: &BTreeMap<String, BucketVolume>,
+    decimals: u32,
+) -> BTreeMap<String, BucketVolume> {
+    buckets
+        .iter()
+        .map(|(key, bucket)| (key.clone(), bucket.rounded(decimals)))
+        .collect()
 }
 
 #[cfg(test)]
-mod tests {}
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trade_filenames_include_day_boundaries() {
+        let trade_key = build_market_trade_key("BTCUSDT");
+        // 2024-01-01T00:00:00Z
+        let new_year = 1_704_067_200_000;
+
+        // a range ending on the first millisecond of a day includes that day, across a year
+        assert_eq!(
+            generate_trade_filenames_in_range(&trade_key, new_year - DAY_AS_MILI, new_year),
+            vec![
+                "BTCUSDT@trade-2023-12-31.csv".to_string(),
+                "BTCUSDT@trade-2024-01-01.csv".to_string()
+            ]
+        );
+        assert_eq!(
+            generate_trade_filenames_in_range(&trade_key, new_year - DAY_AS_MILI, new_year - 1),
+            vec!["BTCUSDT@trade-2023-12-31.csv".to_string()]
+        );
+
+        // a range within one day yields that day
+        assert_eq!(
+            generate_trade_filenames_in_range(&trade_key, new_year + 1, new_year + 1_000),
+            vec!["BTCUSDT@trade-2024-01-01.csv".to_string()]
+        );
+    }
+}